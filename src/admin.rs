@@ -0,0 +1,351 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Local admin RPC, so owner maintenance can be done from the command line while the bot is running.
+//!
+//! If the `admin_socket` config setting is set, the running bot listens on that Unix socket. The owner CLI subcommands
+//! send their [`Request`] to it and print the response, falling back to running the request against the DB themselves
+//! when no bot is listening. Either way the request is handled by [`execute`], so the result is the same.
+//!
+//! The protocol is line based: the client writes a single request line, and the server replies with `ok` or `error` on
+//! the first line followed by the response text, then closes the connection. The socket is only accessible by the user
+//! running the bot.
+
+use crate::db::JinxDb;
+use crate::error::JinxError;
+use std::path::PathBuf;
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// Most events returned by a single [`Request::Events`]. Consumers page through the rest by asking again from the last
+/// sequence number they got.
+const EVENT_PAGE_SIZE: u64 = 1000;
+
+/// Longest request line we'll read before giving up on a client
+#[cfg(unix)]
+const MAX_REQUEST_LENGTH: u64 = 4096;
+
+/// An owner maintenance action
+#[derive(Debug, PartialEq, Eq)]
+pub enum Request {
+    AddOwner(u64),
+    RemoveOwner(u64),
+    ListOwners,
+    /// Write a copy of the DB to a new file at this path
+    Backup(PathBuf),
+    Stats,
+    /// Globally disable (or re-enable) license registration, like `/set_incident_mode`
+    SetIncidentMode(bool),
+    /// Get events from the event stream with a sequence number after this one, as JSON lines
+    Events(u64),
+}
+
+impl Request {
+    /// Encode this request as a single protocol line, without the trailing newline
+    fn to_line(&self) -> String {
+        match self {
+            Request::AddOwner(owner_id) => format!("owner add {owner_id}"),
+            Request::RemoveOwner(owner_id) => format!("owner rm {owner_id}"),
+            Request::ListOwners => "owner ls".to_string(),
+            Request::Backup(path) => format!("backup {}", path.display()),
+            Request::Stats => "stats".to_string(),
+            Request::SetIncidentMode(true) => "incident on".to_string(),
+            Request::SetIncidentMode(false) => "incident off".to_string(),
+            Request::Events(since) => format!("events {since}"),
+        }
+    }
+
+    /// Decode a request line created by [`Self::to_line`]
+    fn parse(line: &str) -> Result<Self, String> {
+        let parse_id = |id: &str| {
+            id.parse::<u64>()
+                .map_err(|e| format!("invalid Discord ID {id}: {e}"))
+        };
+        let request = match line.split_once(' ').unwrap_or((line, "")) {
+            ("owner", "ls") => Request::ListOwners,
+            ("owner", arguments) => match arguments.split_once(' ') {
+                Some(("add", owner_id)) => Request::AddOwner(parse_id(owner_id)?),
+                Some(("rm", owner_id)) => Request::RemoveOwner(parse_id(owner_id)?),
+                _ => return Err(format!("unknown owner command: {arguments}")),
+            },
+            // paths may contain spaces, so everything after the command is the path
+            ("backup", path) if !path.is_empty() => Request::Backup(PathBuf::from(path)),
+            ("stats", "") => Request::Stats,
+            ("incident", "on") => Request::SetIncidentMode(true),
+            ("incident", "off") => Request::SetIncidentMode(false),
+            ("events", since) => Request::Events(
+                since
+                    .parse()
+                    .map_err(|e| format!("invalid sequence number {since}: {e}"))?,
+            ),
+            _ => return Err(format!("unknown request: {line}")),
+        };
+        Ok(request)
+    }
+}
+
+/// Carry out a request against the DB, returning the text to show the user
+pub async fn execute(db: &JinxDb, request: &Request) -> Result<String, Error> {
+    let response = match request {
+        Request::AddOwner(owner_id) => {
+            db.add_owner(*owner_id).await?;
+            format!("added owner {owner_id}")
+        }
+        Request::RemoveOwner(owner_id) => {
+            db.delete_owner(*owner_id).await?;
+            format!("removed owner {owner_id}")
+        }
+        Request::ListOwners => db
+            .get_owners()
+            .await?
+            .into_iter()
+            .map(|owner_id| owner_id.to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Request::Backup(path) => {
+            if path.exists() {
+                return Err(JinxError::boxed(format!(
+                    "{} already exists",
+                    path.display()
+                )));
+            }
+            db.backup(path.clone()).await?;
+            format!("backed up DB to {}", path.display())
+        }
+        Request::Stats => {
+            let db_size = db.size().await?.div_ceil(1024);
+            let configured_guild_count = db.guild_count().await?;
+            let log_channel_count = db.log_channel_count().await?;
+            let owner_count = db.get_owners().await?.len();
+            let incident_mode = db.get_registration_incident_notice().await?.is_some();
+            format!(
+                "db_size={db_size} KiB\n\
+                configured guilds={configured_guild_count}\n\
+                log channels={log_channel_count}\n\
+                license activations={}\n\
+                product→role links={}\n\
+                owners={owner_count}\n\
+                incident mode={incident_mode}",
+                db.license_activation_count(),
+                db.product_role_count(),
+            )
+        }
+        Request::Events(since) => db
+            .get_events(*since, EVENT_PAGE_SIZE)
+            .await?
+            .into_iter()
+            // IDs are strings, as they don't fit in the doubles many JSON parsers use for numbers
+            .map(|event| {
+                serde_json::json!({
+                    "sequence": event.sequence,
+                    "created_unix_ms": event.created_unix_ms,
+                    "guild_id": event.guild_id.get().to_string(),
+                    "kind": event.kind.as_db_str(),
+                    "user_id": event.user_id.map(|user_id| user_id.to_string()),
+                    "license_id": event.license_id,
+                    "product_id": event.product_id,
+                    "role_id": event.role_id.map(|role_id| role_id.get().to_string()),
+                })
+                .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Request::SetIncidentMode(enabled) => {
+            if *enabled {
+                db.set_registration_incident_notice(Some(String::new()))
+                    .await?;
+                "Registration is now disabled in every server.".to_string()
+            } else {
+                db.set_registration_incident_notice(None).await?;
+                "Registration is enabled again.".to_string()
+            }
+        }
+    };
+    Ok(response)
+}
+
+/// Serve admin requests on the socket at `path` until the process exits
+#[cfg(unix)]
+pub async fn serve(path: PathBuf, db: std::sync::Arc<JinxDb>) -> Result<(), Error> {
+    let listener = bind(&path).await?;
+    tracing::info!("admin socket listening on {}", path.display());
+    accept(listener, db).await
+}
+
+/// Create the admin socket at `path`, replacing one left behind by a bot that didn't shut down cleanly
+#[cfg(unix)]
+async fn bind(path: &std::path::Path) -> Result<tokio::net::UnixListener, Error> {
+    use std::os::unix::fs::{DirBuilderExt as _, PermissionsExt as _};
+    use tokio::net::{UnixListener, UnixStream};
+
+    if path.exists() {
+        if UnixStream::connect(path).await.is_ok() {
+            return Err(JinxError::boxed(format!(
+                "another process is already listening on {}",
+                path.display()
+            )));
+        }
+        // left behind by a bot that didn't shut down cleanly
+        std::fs::remove_file(path)?;
+    }
+
+    // The socket is created with permissions from the umask, so it's bound inside a directory only we can enter and
+    // only moved into place once it's locked down. Otherwise another user could connect before the chmod.
+    let mut staging_name = path.file_name().unwrap_or_default().to_os_string();
+    staging_name.push(format!(".{}.tmp", std::process::id()));
+    let staging_dir = path.with_file_name(staging_name);
+    std::fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&staging_dir)?;
+    let result = (|| {
+        let staging_path = staging_dir.join("socket");
+        let listener = UnixListener::bind(&staging_path)?;
+        std::fs::set_permissions(&staging_path, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&staging_path, path)?;
+        Ok(listener)
+    })();
+    std::fs::remove_dir_all(&staging_dir)?;
+    result
+}
+
+/// Serve admin requests on an already bound socket until the process exits
+#[cfg(unix)]
+async fn accept(
+    listener: tokio::net::UnixListener,
+    db: std::sync::Arc<JinxDb>,
+) -> Result<(), Error> {
+    use tracing::warn;
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let db = db.clone();
+        tokio::task::spawn(async move {
+            if let Err(e) = handle_connection(stream, &db).await {
+                warn!("error handling admin request: {:?}", e);
+            }
+        });
+    }
+}
+
+/// Serve admin requests on the socket at `path` until the process exits
+#[cfg(not(unix))]
+pub async fn serve(_path: PathBuf, _db: std::sync::Arc<JinxDb>) -> Result<(), Error> {
+    Err(JinxError::boxed(
+        "the admin socket is only supported on Unix",
+    ))
+}
+
+#[cfg(unix)]
+async fn handle_connection(stream: tokio::net::UnixStream, db: &JinxDb) -> Result<(), Error> {
+    use tokio::io::{AsyncBufReadExt as _, AsyncReadExt as _, AsyncWriteExt as _, BufReader};
+
+    let (reader, mut writer) = stream.into_split();
+    let mut line = String::new();
+    BufReader::new(reader.take(MAX_REQUEST_LENGTH))
+        .read_line(&mut line)
+        .await?;
+    let result = match Request::parse(line.trim_end()) {
+        Ok(request) => {
+            tracing::info!("admin request: {}", request.to_line());
+            execute(db, &request).await.map_err(|e| e.to_string())
+        }
+        Err(e) => Err(e),
+    };
+    let response = match result {
+        Ok(message) => format!("ok\n{message}"),
+        Err(message) => format!("error\n{message}"),
+    };
+    writer.write_all(response.as_bytes()).await?;
+    writer.shutdown().await?;
+    Ok(())
+}
+
+/// Send a request to the bot listening on the socket at `path`. Returns `None` if no bot is listening, in which case the
+/// caller should [`execute`] the request itself. Otherwise returns the bot's response, or its error message.
+#[cfg(unix)]
+pub async fn call(path: &std::path::Path, request: &Request) -> Option<Result<String, String>> {
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+    let mut stream = tokio::net::UnixStream::connect(path).await.ok()?;
+    let exchange = async {
+        stream
+            .write_all(format!("{}\n", request.to_line()).as_bytes())
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        Ok::<_, std::io::Error>(response)
+    };
+    let response = match exchange.await {
+        Ok(response) => response,
+        Err(e) => return Some(Err(format!("error talking to the running bot: {e}"))),
+    };
+    let (status, message) = response.split_once('\n').unwrap_or((&response, ""));
+    match status {
+        "ok" => Some(Ok(message.to_string())),
+        _ => Some(Err(message.to_string())),
+    }
+}
+
+/// Send a request to the bot listening on the socket at `path`. Returns `None` if no bot is listening, in which case the
+/// caller should [`execute`] the request itself. Otherwise returns the bot's response, or its error message.
+#[cfg(not(unix))]
+pub async fn call(_path: &std::path::Path, _request: &Request) -> Option<Result<String, String>> {
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_request_round_trip() {
+        let requests = [
+            Request::AddOwner(1),
+            Request::RemoveOwner(2),
+            Request::ListOwners,
+            Request::Backup(PathBuf::from("/var/backups/jinx backup.sqlite")),
+            Request::Stats,
+            Request::SetIncidentMode(true),
+            Request::SetIncidentMode(false),
+            Request::Events(42),
+        ];
+        for request in requests {
+            assert_eq!(Request::parse(&request.to_line()), Ok(request));
+        }
+        assert!(Request::parse("owner add nope").is_err());
+        assert!(Request::parse("backup").is_err());
+        assert!(Request::parse("stats now").is_err());
+        assert!(Request::parse("events").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_serve() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let db = std::sync::Arc::new(JinxDb::open_path(":memory:").await.unwrap());
+            let path = std::env::temp_dir().join(format!("jinx-admin-{}.sock", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            assert_eq!(call(&path, &Request::ListOwners).await, None);
+
+            // bind before spawning, so the socket is ready before the first call
+            let listener = bind(&path).await.unwrap();
+            let mode =
+                std::os::unix::fs::PermissionsExt::mode(&path.metadata().unwrap().permissions());
+            assert_eq!(mode & 0o777, 0o600);
+            tokio::task::spawn(accept(listener, db));
+            assert_eq!(
+                call(&path, &Request::AddOwner(42)).await,
+                Some(Ok("added owner 42".to_string()))
+            );
+            assert_eq!(
+                call(&path, &Request::ListOwners).await,
+                Some(Ok("42".to_string()))
+            );
+            std::fs::remove_file(&path).unwrap();
+        });
+    }
+}
@@ -0,0 +1,178 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Extra actions that run after a license is activated, configured per product as an ordered list.
+//!
+//! Role grants always happen first and aren't hooks. To add a new kind of hook, add a variant to
+//! [`ActivationHookKind`], then handle it in [`run_hook`] and [`describe`].
+
+use crate::bot::i18n;
+use crate::bot::i18n::Text;
+use crate::bot::license_expiry;
+use crate::bot::util::send_bot_log_message;
+use crate::bot::Error;
+use crate::db::{ActivationHook, ActivationHookKind, JinxDb, ScheduledExpiry};
+use crate::error::JinxError;
+use crate::http::jinxxy::LicenseInfo;
+use crate::http::webhook;
+use poise::serenity_prelude as serenity;
+use poise::serenity_prelude::{
+    ChannelId, Colour, CreateEmbed, CreateMessage, GuildId, Member, PermissionOverwrite,
+    PermissionOverwriteType, Permissions,
+};
+use std::sync::Arc;
+use tracing::warn;
+
+/// A license activation that hooks run for
+pub struct Activation {
+    pub guild_id: GuildId,
+    pub member: Member,
+    pub license_info: LicenseInfo,
+    /// `false` if the user had already activated this license and just registered it again
+    pub new_activation: bool,
+}
+
+/// Run every hook configured for the activated product in the background, so slow hooks don't hold up registration.
+/// Failures are logged and reported to the guild's bot log channel.
+pub fn spawn(context: &serenity::Context, db: Arc<JinxDb>, activation: Activation) {
+    let context = context.clone();
+    tokio::task::spawn(async move {
+        if let Err(e) = run(&context, &db, &activation).await {
+            warn!(
+                "in {} error running activation hooks: {:?}",
+                activation.guild_id.get(),
+                e
+            );
+        }
+    });
+}
+
+/// Run every hook configured for the activated product, in order. A failing hook doesn't stop later hooks from
+/// running. Each hook that failed is reported to the bot log channel, if there is one.
+async fn run(
+    context: &serenity::Context,
+    db: &JinxDb,
+    activation: &Activation,
+) -> Result<(), Error> {
+    let guild_id = activation.guild_id;
+    let hooks = db
+        .get_activation_hooks(guild_id, activation.license_info.product_id.clone())
+        .await?;
+    let mut failures = String::new();
+    for hook in hooks {
+        if let Err(e) = run_hook(context, db, activation, &hook).await {
+            warn!(
+                "in {} activation hook {} failed: {:?}",
+                guild_id.get(),
+                hook.position,
+                e
+            );
+            failures.push_str(format!("\n- {}. {}", hook.position, describe(&hook)).as_str());
+        }
+    }
+
+    if failures.is_empty() {
+        return Ok(());
+    }
+    let Some(log_channel) = db.get_log_channel(guild_id).await? else {
+        return Ok(());
+    };
+    let log_locale = i18n::guild_locale(db, guild_id, None).await?;
+    let user = format!("<@{}>", activation.member.user.id.get());
+    let message = i18n::format(
+        log_locale,
+        Text::LogHookError,
+        &[("user", &user), ("hooks", &failures)],
+    );
+    let embed = CreateEmbed::default()
+        .title(i18n::text(log_locale, Text::LogHookErrorTitle))
+        .description(message)
+        .color(Colour::RED);
+    send_bot_log_message(context, log_channel, CreateMessage::default().embed(embed)).await?;
+    Ok(())
+}
+
+async fn run_hook(
+    context: &serenity::Context,
+    db: &JinxDb,
+    activation: &Activation,
+    hook: &ActivationHook,
+) -> Result<(), Error> {
+    let user_id = activation.member.user.id;
+    match hook.kind {
+        ActivationHookKind::Webhook => {
+            let license_info = &activation.license_info;
+            let payload = webhook::ActivationPayload {
+                event: "license_activated",
+                guild_id: activation.guild_id.get().to_string(),
+                user_id: user_id.get().to_string(),
+                product_id: license_info.product_id.clone(),
+                product_name: license_info.product_name.clone(),
+                product_version_id: license_info.product_version_id.clone(),
+                license_id: license_info.license_id.clone(),
+                new_activation: activation.new_activation,
+            };
+            let signing_secret = hook
+                .signing_secret
+                .as_ref()
+                .ok_or_else(|| JinxError::new("webhook hook has no signing secret"))?;
+            webhook::post_activation(&hook.target, signing_secret, &payload).await?;
+        }
+        ActivationHookKind::AddToThread => {
+            parse_channel(hook)?
+                .add_thread_member(context, user_id)
+                .await?;
+        }
+        ActivationHookKind::GrantChannelAccess => {
+            let overwrite = PermissionOverwrite {
+                allow: Permissions::VIEW_CHANNEL,
+                deny: Permissions::empty(),
+                kind: PermissionOverwriteType::Member(user_id),
+            };
+            parse_channel(hook)?
+                .create_permission(context, overwrite)
+                .await?;
+        }
+        ActivationHookKind::ScheduleExpiry => {
+            let days: u64 = hook
+                .target
+                .parse()
+                .map_err(|_| JinxError::new(format!("invalid day count {:?}", hook.target)))?;
+            let license_info = &activation.license_info;
+            let expiry = ScheduledExpiry {
+                guild_id: activation.guild_id,
+                user_id,
+                license_id: license_info.license_id.clone(),
+                product_id: license_info.product_id.clone(),
+                product_version_id: license_info.product_version_id.clone(),
+                expires_unix_ms: license_expiry::now_unix_ms() + days * 24 * 60 * 60 * 1000,
+            };
+            db.schedule_expiry(expiry).await?;
+        }
+    }
+    Ok(())
+}
+
+fn parse_channel(hook: &ActivationHook) -> Result<ChannelId, JinxError> {
+    hook.target
+        .parse()
+        .map(ChannelId::new)
+        .map_err(|_| JinxError::new(format!("invalid channel ID {:?}", hook.target)))
+}
+
+/// Describe what a hook does, for display in Discord
+pub fn describe(hook: &ActivationHook) -> String {
+    match hook.kind {
+        ActivationHookKind::Webhook => {
+            // webhook URLs often have a secret in their path, so only show the host
+            let host = reqwest::Url::parse(&hook.target)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_string))
+                .unwrap_or_else(|| "an invalid URL".to_string());
+            format!("send webhook to {}", host)
+        }
+        ActivationHookKind::AddToThread => format!("add to thread <#{}>", hook.target),
+        ActivationHookKind::GrantChannelAccess => format!("grant access to <#{}>", hook.target),
+        ActivationHookKind::ScheduleExpiry => format!("remove roles after {} days", hook.target),
+    }
+}
@@ -0,0 +1,112 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! One-time advice for guilds whose data shows they're stuck or missing something.
+//!
+//! Each [`Advisory`] selects the guilds it applies to from the DB. A background task periodically sends every pending
+//! advisory to its guild's bot log channel, or to the system channel if there's no log channel, and records the delivery
+//! so no guild ever gets the same advisory twice.
+
+use crate::bot::util::send_bot_log_message;
+use crate::bot::Error;
+use crate::db::{Advisory, JinxDb, OnboardingStep};
+use poise::serenity_prelude as serenity;
+use serenity::{ChannelId, Colour, CreateEmbed, CreateMessage, GuildId};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::Duration;
+use tracing::warn;
+
+/// How long a guild is left alone to sort things out on its own before an advisory applies
+const SETTLE_DELAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Title and message for an advisory
+fn advisory_text(advisory: Advisory) -> (&'static str, String) {
+    match advisory {
+        Advisory::StoreWithoutLinks => (
+            "Finish Setting Up Jinx",
+            format!(
+                "Your store is linked, but no products are linked to roles yet, so registering a license won't grant anything. {}",
+                OnboardingStep::FirstLink.hint()
+            ),
+        ),
+        Advisory::LinksWithoutPost => (
+            "Finish Setting Up Jinx",
+            format!(
+                "Your products are linked, but there's no registration post for customers to use yet. {}",
+                OnboardingStep::FirstPost.hint()
+            ),
+        ),
+        Advisory::ActivationsWithoutLogChannel => (
+            "No Bot Log Channel",
+            format!(
+                "Customers are registering licenses, but without a log channel you won't hear about role grants or problems. {}",
+                OnboardingStep::LogChannelSet.hint()
+            ),
+        ),
+    }
+}
+
+/// Send every pending advisory. Failures are logged and skipped.
+pub async fn deliver_all(context: &serenity::Context, db: &JinxDb) {
+    let settled_before_unix_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+        .saturating_sub(SETTLE_DELAY.as_millis() as u64);
+    for advisory in Advisory::ALL {
+        let candidates = match db
+            .get_advisory_candidates(advisory, settled_before_unix_ms)
+            .await
+        {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                warn!(
+                    "Error reading candidates for {:?} advisory: {:?}",
+                    advisory, e
+                );
+                continue;
+            }
+        };
+        for (guild_id, log_channel_id) in candidates {
+            if let Err(e) = deliver(context, db, advisory, guild_id, log_channel_id).await {
+                warn!(
+                    "Error delivering {:?} advisory to {}: {:?}",
+                    advisory,
+                    guild_id.get(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// Send an advisory to a guild, unless it has nowhere to send it
+async fn deliver(
+    context: &serenity::Context,
+    db: &JinxDb,
+    advisory: Advisory,
+    guild_id: GuildId,
+    log_channel_id: Option<ChannelId>,
+) -> Result<(), Error> {
+    let channel_id = log_channel_id.or_else(|| {
+        guild_id
+            .to_guild_cached(context)
+            .and_then(|guild| guild.system_channel_id)
+    });
+    let Some(channel_id) = channel_id else {
+        return Ok(());
+    };
+    // record the delivery first, so a channel we can't send to doesn't get retried every time
+    if !db.record_advisory_delivery(guild_id, advisory).await? {
+        return Ok(());
+    }
+    let (title, message) = advisory_text(advisory);
+    let embed = CreateEmbed::default()
+        .title(title)
+        .description(format!(
+            "{message}\n\nUse `/setup_progress` to see how your setup is going."
+        ))
+        .color(Colour::ORANGE);
+    send_bot_log_message(context, channel_id, CreateMessage::default().embed(embed)).await?;
+    Ok(())
+}
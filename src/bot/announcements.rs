@@ -0,0 +1,95 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Owner announcements to guilds' bot log channels.
+//!
+//! Announcements are either sent right away or scheduled, in which case a background task sends them once they're due.
+//! Each announcement is claimed before it's sent, so it's never delivered twice, and the result for every guild is
+//! recorded for `/announce_status`.
+
+use crate::bot::util::send_bot_log_message;
+use crate::bot::Error;
+use crate::db::{AnnouncementAudience, JinxDb};
+use poise::serenity_prelude as serenity;
+use serenity::{CreateEmbed, CreateMessage};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::Duration;
+use tracing::{error, info, warn};
+
+/// Delay between messages, which keeps us at around 20 messages per second
+const SEND_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Current time in unix milliseconds
+pub fn now_unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Send an announcement to its audience. Returns how many channels it was delivered to and how many it was sent to, or
+/// `None` if the announcement doesn't exist or was already sent.
+pub async fn deliver(
+    context: &serenity::Context,
+    db: &JinxDb,
+    announcement_id: u64,
+) -> Result<Option<(usize, usize)>, Error> {
+    let Some((title, message, audience)) = db
+        .claim_announcement(announcement_id, now_unix_ms())
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    let title = title.unwrap_or_else(|| {
+        if audience == AnnouncementAudience::Test {
+            "Test Announcement".to_string()
+        } else {
+            "Announcement".to_string()
+        }
+    });
+    let embed = CreateEmbed::default().title(title).description(message);
+    let message = CreateMessage::default().embed(embed);
+    let targets = db.get_announcement_targets(audience).await?;
+    let target_count = targets.len();
+    let mut delivered_count: usize = 0;
+    for (guild_id, channel_id) in targets {
+        let error = match send_bot_log_message(context, channel_id, message.clone()).await {
+            Ok(_) => {
+                delivered_count += 1;
+                None
+            }
+            Err(e) => {
+                warn!(
+                    "Error sending announcement {} to {}: {:?}",
+                    announcement_id, channel_id, e
+                );
+                Some(e.to_string())
+            }
+        };
+        db.record_announcement_delivery(announcement_id, guild_id, channel_id, error)
+            .await?;
+        tokio::time::sleep(SEND_INTERVAL).await;
+    }
+    info!(
+        "delivered announcement {} to {}/{} channels",
+        announcement_id, delivered_count, target_count
+    );
+    Ok(Some((delivered_count, target_count)))
+}
+
+/// Send every scheduled announcement that's due. Failures are logged and skipped.
+pub async fn deliver_due(context: &serenity::Context, db: &JinxDb) {
+    let announcement_ids = match db.get_due_announcements(now_unix_ms()).await {
+        Ok(announcement_ids) => announcement_ids,
+        Err(e) => {
+            error!("Error reading due announcements: {:?}", e);
+            return;
+        }
+    };
+    for announcement_id in announcement_ids {
+        if let Err(e) = deliver(context, db, announcement_id).await {
+            error!("Error delivering announcement {}: {:?}", announcement_id, e);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Autocomplete for slash command arguments.
+//!
+//! Each kind of argument has a [`Provider`], which only has to find candidate values. [`complete`] does the rest:
+//! logging errors, dropping values Discord won't accept, removing duplicates, and limiting the number of choices. To add
+//! completion for a new kind of argument, add a provider here and a function for poise's `#[autocomplete]` attribute
+//! that calls [`complete`] with it.
+
+use crate::bot::{Context, Error};
+use crate::error::JinxError;
+use std::collections::HashSet;
+use std::future::Future;
+use tracing::warn;
+
+/// Discord allows at most this many autocomplete choices
+pub const CHOICE_LIMIT: usize = 25;
+
+/// Discord rejects choice values longer than this many characters
+const CHOICE_VALUE_MAX_LENGTH: usize = 100;
+
+/// A source of autocomplete values for one kind of argument
+pub trait Provider {
+    /// What this provides, for logging
+    const NAME: &'static str;
+
+    /// Find values matching what the user has typed so far, best matches first. There's no need to limit the number of
+    /// values returned.
+    fn candidates(
+        &self,
+        context: &Context<'_>,
+        partial: &str,
+    ) -> impl Future<Output = Result<Vec<String>, Error>> + Send;
+}
+
+/// Get the autocomplete choices from a provider. Errors are logged, and result in no choices.
+pub async fn complete<P: Provider>(
+    context: Context<'_>,
+    provider: P,
+    partial: &str,
+) -> impl Iterator<Item = String> {
+    let candidates = match provider.candidates(&context, partial).await {
+        Ok(candidates) => candidates,
+        Err(e) => {
+            warn!("Failed to autocomplete {}: {:?}", P::NAME, e);
+            Vec::new()
+        }
+    };
+    to_choices(candidates).into_iter()
+}
+
+fn to_choices(candidates: Vec<String>) -> Vec<String> {
+    let mut seen: HashSet<String, ahash::RandomState> = Default::default();
+    candidates
+        .into_iter()
+        // a truncated value wouldn't match anything once submitted, so it's better to not offer it at all
+        .filter(|value| !value.is_empty() && value.chars().count() <= CHOICE_VALUE_MAX_LENGTH)
+        .filter(|value| seen.insert(value.clone()))
+        .take(CHOICE_LIMIT)
+        .collect()
+}
+
+/// Names of the guild's products. Initializes the product cache if needed.
+pub struct Products;
+
+impl Provider for Products {
+    const NAME: &'static str = "product";
+
+    async fn candidates(&self, context: &Context<'_>, partial: &str) -> Result<Vec<String>, Error> {
+        context
+            .data()
+            .api_cache
+            .product_names_with_prefix(context, partial)
+            .await
+    }
+}
+
+/// Patterns of the guild's product auto-link rules
+pub struct LinkRulePatterns;
+
+impl Provider for LinkRulePatterns {
+    const NAME: &'static str = "link rule pattern";
+
+    async fn candidates(&self, context: &Context<'_>, partial: &str) -> Result<Vec<String>, Error> {
+        let guild_id = context
+            .guild_id()
+            .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+        let partial = partial.to_lowercase();
+        let patterns = context
+            .data()
+            .db
+            .get_link_rules(guild_id)
+            .await?
+            .into_iter()
+            .map(|(pattern, _)| pattern)
+            .filter(|pattern| pattern.to_lowercase().starts_with(&partial))
+            .collect();
+        Ok(patterns)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_to_choices() {
+        let candidates = vec![
+            "b".to_string(),
+            String::new(),
+            "a".to_string(),
+            "b".to_string(),
+            "x".repeat(CHOICE_VALUE_MAX_LENGTH + 1),
+        ];
+        assert_eq!(to_choices(candidates), vec!["b", "a"]);
+
+        let candidates = (0..CHOICE_LIMIT * 2).map(|i| i.to_string()).collect();
+        assert_eq!(to_choices(candidates).len(), CHOICE_LIMIT);
+    }
+}
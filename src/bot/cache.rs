@@ -9,25 +9,85 @@
 //!
 //! The idea here is we have a cache with a short expiry time (maybe 60s) and we reuse the results.
 //! I can clear the cache with some kind of background task that checks timestamps ever 60s or so.
+//!
+//! Owners can additionally configure a cron-like schedule to pre-warm the cache right before known high-traffic
+//! events. See [`crate::bot::schedule`] for the schedule format. The schedule is parsed once when it's set and kept here,
+//! and each guild is warmed in its own task, so a warm only waits on the per-API-key request queue.
+//!
+//! Product names are also persisted to the DB whenever the cache is loaded. Right after startup autocomplete reads
+//! from the DB while the cache loads in the background, rather than making the user wait on the API. Products that
+//! weren't persisted before are new to the store, so that's also where [`crate::bot::link_rules`] get applied and where
+//! [`crate::bot::product_changes`] are reported.
+//!
+//! The cache can also be exported to a snapshot and imported on another instance (or after a data reset) so a cold
+//! start doesn't have to hit the API for every guild at once. Imported entries expire normally, so the API load of
+//! refreshing them is spread out over actual usage.
+//!
+//! Stores are also refreshed in the background, more often the more recently their cache was used. Active stores
+//! stay fresh so their users rarely wait on the API, while dormant stores are only refreshed occasionally, which keeps
+//! their persisted products and auto-link rules reasonably up to date without spending API calls on nobody.
+//!
+//! Refreshes that someone is waiting on, like the one after a store is registered, are persisted to the DB until they
+//! finish and replayed on startup, so a restart doesn't leave those guilds with stale autocomplete.
 
+use crate::bot::autocomplete;
+use crate::bot::link_rules;
+use crate::bot::product_changes;
+use crate::bot::schedule::{Schedule, UtcTime};
 use crate::bot::{Context, MISSING_API_KEY_MESSAGE};
+use crate::config;
+use crate::db::JinxDb;
 use crate::error::JinxError;
 use crate::http::jinxxy;
 use crate::http::jinxxy::PartialProduct;
-use dashmap::{DashMap, Entry};
-use poise::serenity_prelude::GuildId;
+use dashmap::{DashMap, DashSet, Entry};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use poise::serenity_prelude as serenity;
+use serenity::GuildId;
 use std::collections::{HashMap, HashSet};
+use std::io::{Read as _, Write as _};
+use std::sync::{Arc, OnceLock, RwLock};
+use tokio::task::JoinSet;
 use tokio::time::{Duration, Instant};
-use tracing::{debug, warn};
+use tracing::{debug, info, warn};
 use trie_rs::map::{Trie, TrieBuilder};
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 
-const CACHE_EXPIRY_TIME: Duration = Duration::from_secs(60);
+/// First line of a cache snapshot. Bump the version if the format ever changes.
+const SNAPSHOT_HEADER: &str = "jinx-cache-snapshot v1";
+
+/// A store counts as active if its cache was used this recently. Active stores are refreshed as often as the cache
+/// expires.
+const ACTIVE_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+/// A store that isn't active counts as recently used if its cache was used this recently
+const RECENT_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How often recently used stores are refreshed in the background
+const RECENT_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often dormant stores are refreshed in the background
+const DORMANT_REFRESH_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Most stores to refresh in a single background pass, so a backlog is worked through gradually instead of all at once
+const BACKGROUND_REFRESH_BATCH: usize = 10;
 
 #[derive(Default)]
 pub struct ApiCache {
     map: DashMap<GuildId, GuildCache, ahash::RandomState>,
+    /// guilds with a background load in progress
+    loading: DashSet<GuildId, ahash::RandomState>,
+    /// used to notify guilds about product changes found by background loads
+    discord_http: OnceLock<Arc<serenity::Http>>,
+    /// when each guild's cache was last used
+    activity: DashMap<GuildId, Instant, ahash::RandomState>,
+    /// when each guild's cache was last loaded from the API. Unlike the entries themselves, this survives [`Self::clean`].
+    refreshed: DashMap<GuildId, Instant, ahash::RandomState>,
+    /// when to run [`Self::warm`], if at all
+    warm_schedule: RwLock<Option<Schedule>>,
 }
 
 impl ApiCache {
@@ -41,6 +101,7 @@ impl ApiCache {
         let guild_id = context
             .guild_id()
             .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+        self.record_activity(guild_id);
         let lookup_result = match self.map.entry(guild_id) {
             Entry::Occupied(entry) => {
                 let cache_entry = entry.get();
@@ -63,14 +124,36 @@ impl ApiCache {
             guild_cache
         } else {
             // expired or vacant entry
-            let guild_cache = GuildCache::new(context, guild_id).await?;
-            self.map.insert(guild_id, guild_cache.clone());
+            let guild_cache = GuildCache::new(
+                &context.data().db,
+                guild_id,
+                Some(&context.serenity_context().http),
+            )
+            .await?;
+            self.insert(guild_id, guild_cache.clone());
             guild_cache
         };
 
         Ok(f(&guild_cache))
     }
 
+    /// Note that a guild's cache was used, which makes it refresh more often in the background
+    pub fn record_activity(&self, guild_id: GuildId) {
+        self.activity.insert(guild_id, Instant::now());
+    }
+
+    /// Store a freshly loaded cache entry
+    fn insert(&self, guild_id: GuildId, guild_cache: GuildCache) {
+        self.refreshed.insert(guild_id, guild_cache.create_time);
+        self.map.insert(guild_id, guild_cache);
+    }
+
+    /// Set the HTTP client used to notify guilds about product changes found by background loads. Only the first call
+    /// has any effect.
+    pub fn set_discord_http(&self, http: Arc<serenity::Http>) {
+        let _ = self.discord_http.set(http);
+    }
+
     pub fn len(&self) -> usize {
         self.map.len()
     }
@@ -86,10 +169,261 @@ impl ApiCache {
             .sum()
     }
 
+    /// Drop cached entries for a single guild, or for every guild if `None`. Returns the guilds that were cleared.
+    pub fn clear(&self, guild_id: Option<GuildId>) -> Vec<GuildId> {
+        match guild_id {
+            Some(guild_id) => self
+                .map
+                .remove(&guild_id)
+                .map(|(guild_id, _)| guild_id)
+                .into_iter()
+                .collect(),
+            None => {
+                let mut guild_ids = Vec::with_capacity(self.map.len());
+                self.map.retain(|guild_id, _| {
+                    guild_ids.push(*guild_id);
+                    false
+                });
+                guild_ids
+            }
+        }
+    }
+
+    /// Set the schedule [`Self::warm`] should be run on, or `None` to stop scheduled warming
+    pub fn set_warm_schedule(&self, schedule: Option<Schedule>) {
+        *self.warm_schedule.write().unwrap() = schedule;
+    }
+
+    /// Check if [`Self::warm`] is scheduled to run at the given time
+    pub fn is_warm_scheduled(&self, time: UtcTime) -> bool {
+        self.warm_schedule
+            .read()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|schedule| schedule.matches(time))
+    }
+
+    /// Unconditionally refresh the cache for every configured guild. This is intended to be run ahead of known
+    /// high-traffic events, such as sales or product launches.
+    ///
+    /// Every guild is refreshed in its own task. Guilds sharing an API key still take turns through that key's request
+    /// queue, so this doesn't send more requests at once than registrations would.
+    pub async fn warm(self: &Arc<Self>, db: &Arc<JinxDb>) -> Result<(), Error> {
+        let start = Instant::now();
+        let mut tasks = JoinSet::new();
+        for guild_id in db.get_guilds_with_api_key().await? {
+            let api_cache = self.clone();
+            let db = db.clone();
+            tasks.spawn(async move { api_cache.warm_guild(&db, guild_id).await });
+        }
+        let mut warmed_count: usize = 0;
+        while let Some(result) = tasks.join_next().await {
+            match result {
+                Ok(true) => warmed_count += 1,
+                Ok(false) => {}
+                Err(e) => warn!("error joining product cache warm task: {:?}", e),
+            }
+        }
+        info!(
+            "warmed product cache for {} guilds in {}ms",
+            warmed_count,
+            start.elapsed().as_millis()
+        );
+        Ok(())
+    }
+
+    /// Unconditionally refresh the cache for the given guilds. Failures are logged and skipped.
+    pub async fn warm_guilds(&self, db: &JinxDb, guild_ids: Vec<GuildId>) {
+        let start = Instant::now();
+        let mut warmed_count: usize = 0;
+        for guild_id in guild_ids {
+            if self.warm_guild(db, guild_id).await {
+                warmed_count += 1;
+            }
+        }
+        info!(
+            "warmed product cache for {} guilds in {}ms",
+            warmed_count,
+            start.elapsed().as_millis()
+        );
+    }
+
+    /// Unconditionally refresh the cache for one guild, returning whether it worked. Failures are logged.
+    async fn warm_guild(&self, db: &JinxDb, guild_id: GuildId) -> bool {
+        match GuildCache::new(db, guild_id, self.discord_http.get().map(Arc::as_ref)).await {
+            Ok(guild_cache) => {
+                self.insert(guild_id, guild_cache);
+                true
+            }
+            Err(e) => {
+                warn!("error warming product cache in {}: {:?}", guild_id.get(), e);
+                false
+            }
+        }
+    }
+
+    /// Refresh the stores that are most overdue for a background refresh, based on how recently their cache was used.
+    /// Failures are logged and skipped.
+    pub async fn refresh_by_activity(&self, db: &JinxDb) -> Result<(), Error> {
+        let guild_ids = db.get_guilds_with_api_key().await?;
+        let now = Instant::now();
+        let cache_expiry = config::get().cache_expiry;
+        let mut due: Vec<(Duration, GuildId)> = Vec::new();
+        for guild_id in guild_ids {
+            // stores we haven't loaded since startup count from now, so they don't all come due at once
+            let refreshed = *self.refreshed.entry(guild_id).or_insert(now);
+            let since_activity = self
+                .activity
+                .get(&guild_id)
+                .map(|activity| now.duration_since(*activity));
+            let interval = refresh_interval(since_activity, cache_expiry);
+            let age = now.duration_since(refreshed);
+            if age >= interval {
+                due.push((age - interval, guild_id));
+            }
+        }
+        // most overdue first
+        due.sort_unstable_by(|(a, _), (b, _)| b.cmp(a));
+        for (_, guild_id) in due.into_iter().take(BACKGROUND_REFRESH_BATCH) {
+            match GuildCache::new(db, guild_id, self.discord_http.get().map(Arc::as_ref)).await {
+                Ok(guild_cache) => self.insert(guild_id, guild_cache),
+                Err(e) => {
+                    // don't retry a failing store every pass
+                    self.refreshed.insert(guild_id, Instant::now());
+                    warn!(
+                        "error refreshing product cache in {}: {:?}",
+                        guild_id.get(),
+                        e
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Refresh the cache for guilds someone is waiting on, such as after an admin action. Unlike scheduled warming these
+    /// are persisted until they finish, so a restart partway through doesn't leave the guilds with stale autocomplete.
+    pub async fn refresh_guilds(&self, db: &JinxDb, guild_ids: Vec<GuildId>) {
+        if let Err(e) = db.add_pending_cache_refreshes(guild_ids.clone()).await {
+            warn!("error persisting pending cache refreshes: {:?}", e);
+        }
+        self.warm_guilds(db, guild_ids.clone()).await;
+        if let Err(e) = db.remove_pending_cache_refreshes(guild_ids).await {
+            warn!("error clearing pending cache refreshes: {:?}", e);
+        }
+    }
+
+    /// Finish any refreshes from [`Self::refresh_guilds`] that were still pending when the bot last stopped
+    pub async fn replay_pending_refreshes(&self, db: &JinxDb) -> Result<(), Error> {
+        let guild_ids = db.get_pending_cache_refreshes().await?;
+        if !guild_ids.is_empty() {
+            info!("replaying {} pending cache refreshes", guild_ids.len());
+            self.refresh_guilds(db, guild_ids).await;
+        }
+        Ok(())
+    }
+
+    /// Register a guild's store with the cache. Call this whenever a guild's API key is set, including when an invalid
+    /// key is replaced with a valid one. Anything cached under the old key (which may belong to a different store) is
+    /// dropped, and the cache is loaded right away instead of on the next autocomplete or scheduled warm.
+    pub async fn register_store_in_cache(&self, db: &JinxDb, guild_id: GuildId) {
+        self.map.remove(&guild_id);
+        // the persisted product names are used for autocomplete on a cold cache, so they must not outlive the old key
+        if let Err(e) = db.replace_products(guild_id, Vec::new()).await {
+            warn!(
+                "error clearing persisted products in {}: {:?}",
+                guild_id.get(),
+                e
+            );
+        }
+        self.loading.insert(guild_id);
+        self.refresh_guilds(db, vec![guild_id]).await;
+        self.loading.remove(&guild_id);
+    }
+
+    /// Export the current cache contents as a gzipped snapshot. Once decompressed, each line after the header is a
+    /// tab-separated `guild_id, product_id, product_name` triple.
+    pub fn export_snapshot(&self) -> Result<Vec<u8>, Error> {
+        let mut snapshot = String::from(SNAPSHOT_HEADER);
+        for entry in self.map.iter() {
+            let guild_id = entry.key().get();
+            for (product_id, product_name) in entry.value().product_id_to_name_map.iter() {
+                snapshot.push_str(
+                    format!(
+                        "\n{}\t{}\t{}",
+                        guild_id,
+                        escape_snapshot_field(product_id),
+                        escape_snapshot_field(product_name)
+                    )
+                    .as_str(),
+                );
+            }
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(snapshot.as_bytes())?;
+        Ok(encoder.finish()?)
+    }
+
+    /// Import a snapshot created by [`Self::export_snapshot`]. Guilds that already have an unexpired cache entry are
+    /// left alone. Returns the number of guilds imported.
+    pub fn import_snapshot(&self, snapshot: &[u8]) -> Result<usize, Error> {
+        let mut decompressed = String::new();
+        GzDecoder::new(snapshot)
+            .read_to_string(&mut decompressed)
+            .map_err(|e| JinxError::new(format!("could not decompress snapshot: {e}")))?;
+        let mut lines = decompressed.lines();
+        if lines.next() != Some(SNAPSHOT_HEADER) {
+            return Err(JinxError::boxed("not a jinx cache snapshot"));
+        }
+
+        let mut products_by_guild: HashMap<GuildId, Vec<PartialProduct>, ahash::RandomState> =
+            Default::default();
+        for (index, line) in lines.enumerate() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [guild_id, product_id, product_name] = fields.as_slice() else {
+                return Err(JinxError::boxed(format!(
+                    "malformed snapshot line {}",
+                    index + 2
+                )));
+            };
+            let guild_id = guild_id
+                .parse::<u64>()
+                .ok()
+                .filter(|guild_id| *guild_id != 0)
+                .map(GuildId::new)
+                .ok_or_else(|| {
+                    JinxError::new(format!("invalid guild ID on snapshot line {}", index + 2))
+                })?;
+            products_by_guild
+                .entry(guild_id)
+                .or_default()
+                .push(PartialProduct {
+                    id: unescape_snapshot_field(product_id),
+                    name: unescape_snapshot_field(product_name),
+                    ..Default::default()
+                });
+        }
+
+        let mut imported_count: usize = 0;
+        for (guild_id, products) in products_by_guild {
+            match self.map.entry(guild_id) {
+                Entry::Occupied(entry) if !entry.get().is_expired() => {}
+                entry => {
+                    entry.insert(GuildCache::from_products(products));
+                    imported_count += 1;
+                }
+            }
+        }
+        Ok(imported_count)
+    }
+
     /// Remove expired cache entries
     pub fn clean(&self) {
         self.map
             .retain(|_guild_id, cache_entry| !cache_entry.is_expired());
+        // stores used longer ago than this are dormant, which is the same as never having been used
+        self.activity
+            .retain(|_guild_id, activity| activity.elapsed() <= RECENT_WINDOW);
 
         // if the capacity is much larger than the actual usage, then try shrinking
         let len = self.map.len();
@@ -111,12 +445,57 @@ impl ApiCache {
         context: &Context<'_>,
         prefix: &str,
     ) -> Result<Vec<String>, Error> {
+        let guild_id = context
+            .guild_id()
+            .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+        self.record_activity(guild_id);
+
+        // on a cold cache, answer from the DB and load the cache in the background
+        if !self.map.contains_key(&guild_id) {
+            let db = &context.data().db;
+            let product_names: Vec<String> = db
+                .search_products(
+                    guild_id,
+                    prefix.to_string(),
+                    autocomplete::CHOICE_LIMIT as u64,
+                )
+                .await?
+                .into_iter()
+                .map(|(_, product_name)| product_name)
+                .collect();
+            if !product_names.is_empty() {
+                if self.loading.insert(guild_id) {
+                    let db = db.clone();
+                    let api_cache = context.data().api_cache.clone();
+                    tokio::task::spawn(async move {
+                        api_cache.refresh_guilds(&db, vec![guild_id]).await;
+                        api_cache.loading.remove(&guild_id);
+                    });
+                }
+                return Ok(product_names);
+            }
+        }
+
         self.get(context, |cache_entry| {
             cache_entry.product_names_with_prefix(prefix).collect()
         })
         .await
     }
 
+    /// Get every product as `(product_id, product_name)` pairs, sorted by name
+    pub async fn products(&self, context: &Context<'_>) -> Result<Vec<(String, String)>, Error> {
+        self.get(context, |cache_entry| {
+            let mut products: Vec<(String, String)> = cache_entry
+                .product_id_to_name_map
+                .iter()
+                .map(|(product_id, product_name)| (product_id.clone(), product_name.clone()))
+                .collect();
+            products.sort_by(|(_, a), (_, b)| a.cmp(b));
+            products
+        })
+        .await
+    }
+
     pub async fn product_name_to_id(
         &self,
         context: &Context<'_>,
@@ -140,8 +519,14 @@ pub struct GuildCache {
 }
 
 impl GuildCache {
-    async fn new(context: &Context<'_>, guild_id: GuildId) -> Result<GuildCache, Error> {
-        if let Some(api_key) = context.data().db.get_jinxxy_api_key(guild_id).await? {
+    /// Load a guild's products from the API. If `discord_http` is given, the guild is notified about any changes to its
+    /// products since the last load.
+    async fn new(
+        db: &JinxDb,
+        guild_id: GuildId,
+        discord_http: Option<&serenity::Http>,
+    ) -> Result<GuildCache, Error> {
+        if let Some(api_key) = db.get_jinxxy_api_key(guild_id).await? {
             let products: Vec<PartialProduct> = jinxxy::get_products(&api_key)
                 .await?
                 .into_iter()
@@ -152,48 +537,82 @@ impl GuildCache {
                 })
                 .collect();
 
-            // check for duplicate product names
-            {
-                let mut dupe_set: HashSet<&str, ahash::RandomState> = Default::default();
-                products.iter().for_each(|product| {
-                    if !dupe_set.insert(product.name.as_str()) {
-                        warn!(
-                            "product {} \"{}\" has the same name as some other product",
-                            product.id, product.name
-                        )
+            match db.replace_products(guild_id, products.clone()).await {
+                Ok(diff) => {
+                    match link_rules::apply_all(db, guild_id, &diff.added).await {
+                        Ok(0) => {}
+                        Ok(created_count) => info!(
+                            "auto-link rules created {} links in {}",
+                            created_count,
+                            guild_id.get()
+                        ),
+                        Err(e) => warn!(
+                            "error applying auto-link rules in {}: {:?}",
+                            guild_id.get(),
+                            e
+                        ),
                     }
-                });
+                    if let Some(discord_http) = discord_http {
+                        if let Err(e) =
+                            product_changes::notify(discord_http, db, guild_id, &diff).await
+                        {
+                            warn!(
+                                "error notifying {} about product changes: {:?}",
+                                guild_id.get(),
+                                e
+                            );
+                        }
+                    }
+                }
+                Err(e) => warn!("error persisting products in {}: {:?}", guild_id.get(), e),
             }
 
-            // build trie
-            let mut trie_builder = TrieBuilder::new();
-            for product_name in products.iter().map(|product| product.name.as_str()) {
-                trie_builder.push(product_name.to_lowercase(), product_name.to_string());
-            }
-            let product_name_trie = trie_builder.build();
+            Ok(GuildCache::from_products(products))
+        } else {
+            Err(JinxError::boxed(MISSING_API_KEY_MESSAGE))
+        }
+    }
 
-            // build forward map
-            let product_id_to_name_map = products
-                .iter()
-                .map(|product| (product.id.to_string(), product.name.to_string()))
-                .collect();
+    fn from_products(products: Vec<PartialProduct>) -> GuildCache {
+        // check for duplicate product names
+        {
+            let mut dupe_set: HashSet<&str, ahash::RandomState> = Default::default();
+            products.iter().for_each(|product| {
+                if !dupe_set.insert(product.name.as_str()) {
+                    warn!(
+                        "product {} \"{}\" has the same name as some other product",
+                        product.id, product.name
+                    )
+                }
+            });
+        }
 
-            // build reverse map
-            let product_name_to_id_map = products
-                .into_iter()
-                .map(|product| (product.name, product.id))
-                .collect();
+        // build trie
+        let mut trie_builder = TrieBuilder::new();
+        for product_name in products.iter().map(|product| product.name.as_str()) {
+            trie_builder.push(product_name.to_lowercase(), product_name.to_string());
+        }
+        let product_name_trie = trie_builder.build();
 
-            let create_time = Instant::now();
+        // build forward map
+        let product_id_to_name_map = products
+            .iter()
+            .map(|product| (product.id.to_string(), product.name.to_string()))
+            .collect();
 
-            Ok(GuildCache {
-                product_id_to_name_map,
-                product_name_to_id_map,
-                product_name_trie,
-                create_time,
-            })
-        } else {
-            Err(JinxError::boxed(MISSING_API_KEY_MESSAGE))
+        // build reverse map
+        let product_name_to_id_map = products
+            .into_iter()
+            .map(|product| (product.name, product.id))
+            .collect();
+
+        let create_time = Instant::now();
+
+        GuildCache {
+            product_id_to_name_map,
+            product_name_to_id_map,
+            product_name_trie,
+            create_time,
         }
     }
 
@@ -223,14 +642,74 @@ impl GuildCache {
     }
 
     fn is_expired(&self) -> bool {
-        self.create_time.elapsed() > CACHE_EXPIRY_TIME
+        self.create_time.elapsed() > config::get().cache_expiry
     }
 }
 
+/// How often a store should be refreshed in the background, given how long ago its cache was last used
+fn refresh_interval(since_activity: Option<Duration>, cache_expiry: Duration) -> Duration {
+    match since_activity {
+        Some(since_activity) if since_activity <= ACTIVE_WINDOW => cache_expiry,
+        Some(since_activity) if since_activity <= RECENT_WINDOW => RECENT_REFRESH_INTERVAL,
+        _ => DORMANT_REFRESH_INTERVAL,
+    }
+}
+
+/// Escape the characters that have meaning in the snapshot format
+fn escape_snapshot_field(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// Reverse of [`escape_snapshot_field`]
+fn unescape_snapshot_field(field: &str) -> String {
+    let mut result = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('t') => result.push('\t'),
+                Some('n') => result.push('\n'),
+                Some('r') => result.push('\r'),
+                Some(other) => result.push(other),
+                None => result.push('\\'),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
 #[cfg(test)]
 mod test {
+    use super::*;
     use trie_rs::map::TrieBuilder;
 
+    #[test]
+    fn test_refresh_interval() {
+        let cache_expiry = Duration::from_secs(60);
+        assert_eq!(
+            refresh_interval(Some(Duration::from_secs(5)), cache_expiry),
+            cache_expiry
+        );
+        assert_eq!(
+            refresh_interval(Some(Duration::from_secs(60 * 60)), cache_expiry),
+            RECENT_REFRESH_INTERVAL
+        );
+        assert_eq!(
+            refresh_interval(Some(RECENT_WINDOW * 2), cache_expiry),
+            DORMANT_REFRESH_INTERVAL
+        );
+        assert_eq!(
+            refresh_interval(None, cache_expiry),
+            DORMANT_REFRESH_INTERVAL
+        );
+    }
+
     #[test]
     fn test_trie_empty_prefix() {
         let tuples = [
@@ -266,4 +745,68 @@ mod test {
             );
         }
     }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let cache = ApiCache::default();
+        let guild_id = GuildId::new(1234);
+        let products = vec![
+            PartialProduct {
+                id: "product_a".to_string(),
+                name: "Tabbed\tName".to_string(),
+                ..Default::default()
+            },
+            PartialProduct {
+                id: "product_b".to_string(),
+                name: "Back\\slash\nNewline".to_string(),
+                ..Default::default()
+            },
+        ];
+        cache
+            .map
+            .insert(guild_id, GuildCache::from_products(products));
+
+        let snapshot = cache.export_snapshot().unwrap();
+        let imported = ApiCache::default();
+        assert_eq!(imported.import_snapshot(&snapshot).unwrap(), 1);
+
+        let guild_cache = imported.map.get(&guild_id).unwrap();
+        assert_eq!(guild_cache.product_count(), 2);
+        assert_eq!(
+            guild_cache.product_id_to_name("product_a"),
+            Some("Tabbed\tName")
+        );
+        assert_eq!(
+            guild_cache.product_name_to_id("Back\\slash\nNewline"),
+            Some("product_b")
+        );
+    }
+
+    #[test]
+    fn test_snapshot_invalid() {
+        fn gzip(snapshot: &str) -> Vec<u8> {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(snapshot.as_bytes()).unwrap();
+            encoder.finish().unwrap()
+        }
+
+        let cache = ApiCache::default();
+        // uncompressed snapshots are rejected
+        assert!(cache
+            .import_snapshot(format!("{SNAPSHOT_HEADER}\n1234\tproduct_a\tname").as_bytes())
+            .is_err());
+        assert!(cache.import_snapshot(&gzip("not a snapshot")).is_err());
+        assert!(cache
+            .import_snapshot(&gzip(&format!("{SNAPSHOT_HEADER}\n1234\tproduct_a")))
+            .is_err());
+        assert!(cache
+            .import_snapshot(&gzip(&format!("{SNAPSHOT_HEADER}\n0\tproduct_a\tname")))
+            .is_err());
+        assert_eq!(
+            cache
+                .import_snapshot(&gzip(&format!("{SNAPSHOT_HEADER}\n1234\tproduct_a\tname")))
+                .unwrap(),
+            1
+        );
+    }
 }
@@ -1,17 +1,49 @@
 // This file is part of jinx. Copyright © 2024 jinx contributors.
 // jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
 
-use crate::bot::util::{check_owner, error_reply, set_guild_commands, success_reply};
+use crate::bot::commands::{post_template_image_url, registration_post};
+use crate::bot::event_handler::{record_registration_failure, registration_unavailable_embed};
+use crate::bot::i18n;
+use crate::bot::i18n::Text;
+use crate::bot::tasks::{self, TaskKind};
+use crate::bot::util::{
+    api_key_scopes_embed, assignable_roles, check_owner, create_role_warning_from_roles,
+    error_reply, send_bot_log_message, set_guild_commands, success_reply, SafeDisplayExt as _,
+};
 use crate::bot::Context;
 use crate::constants;
+use crate::db::{AuditAction, AuditLogEntry, OnboardingStep};
 use crate::error::JinxError;
 use crate::http::{jinxxy, update_checker};
+use crate::license;
 use poise::serenity_prelude as serenity;
-use poise::CreateReply;
+use poise::{CreateReply, ReplyHandle};
 use regex::Regex;
-use serenity::{Colour, CreateEmbed};
+use secrecy::SecretString;
+use serenity::{
+    ActionRowComponent, ButtonStyle, ChannelId, ChannelType, Colour, ComponentInteraction,
+    ComponentInteractionDataKind, CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter,
+    CreateInputText, CreateInteractionResponse, CreateMessage, CreateModal, CreateSelectMenu,
+    CreateSelectMenuKind, GuildId, InputTextStyle, ModalInteractionCollector,
+};
 use std::sync::LazyLock;
-use tracing::debug;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+// discord component ids
+const SETUP_API_KEY_BUTTON_ID: &str = "jinx_setup_api_key_button";
+const SETUP_API_KEY_MODAL_ID: &str = "jinx_setup_api_key_modal";
+const SETUP_API_KEY_INPUT_ID: &str = "jinx_setup_api_key_input";
+const SETUP_LOG_CHANNEL_ID: &str = "jinx_setup_log_channel";
+const SETUP_BLANKET_ROLE_ID: &str = "jinx_setup_blanket_role";
+const SETUP_POST_CHANNEL_ID: &str = "jinx_setup_post_channel";
+const SETUP_SKIP_ID: &str = "jinx_setup_skip";
+
+/// Number of steps in the `/setup` wizard
+const SETUP_STEPS: u8 = 4;
+
+/// How long `/setup` waits on each step before giving up
+const SETUP_TIMEOUT: Duration = Duration::from_secs(5 * 60);
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 
@@ -27,6 +59,32 @@ thread_local! {
     static JINXXY_API_KEY_REGEX: Regex = GLOBAL_JINXXY_API_KEY_REGEX.clone();
 }
 
+/// Check if this looks like a Jinxxy API key
+pub(in crate::bot) fn is_jinxxy_api_key(api_key: &str) -> bool {
+    JINXXY_API_KEY_REGEX.with(|regex| regex.is_match(api_key))
+}
+
+/// Form for entering a Jinxxy API key. Keys entered here stay out of the user's slash command history.
+#[derive(Debug, poise::Modal)]
+#[name = "Jinxxy API Key"]
+struct ApiKeyModal {
+    #[name = "API Key"]
+    #[placeholder = "sk_9bba2064ee8c20aa4fd6b015eed2001a"]
+    api_key: String,
+}
+
+/// Ask for a Jinxxy API key in a form. This must be the command's first response, so call it before deferring. Returns
+/// the trimmed key, or `None` if the user didn't submit the form in time.
+pub(in crate::bot) async fn prompt_api_key(context: Context<'_>) -> Result<Option<String>, Error> {
+    let poise::Context::Application(application_context) = context else {
+        return Err(JinxError::boxed("expected a slash command"));
+    };
+    let modal =
+        poise::execute_modal::<_, _, ApiKeyModal>(application_context, None, Some(SETUP_TIMEOUT))
+            .await?;
+    Ok(modal.map(|modal| modal.api_key.trim().to_string()))
+}
+
 /// Shows bot help
 #[poise::command(
     slash_command,
@@ -34,13 +92,10 @@ thread_local! {
     interaction_context = "Guild"
 )]
 pub(in crate::bot) async fn help(context: Context<'_>) -> Result<(), Error> {
+    let locale = i18n::command_locale(context).await?;
     let embed = CreateEmbed::default()
-        .title("Jinx Help")
-        .description(
-            "Jinx is a Discord bot that grants roles to users when they register Jinxxy license keys.\n\
-            For documentation, see https://github.com/zkxs/jinx\n\
-            For support, join https://discord.gg/aKkA6m26f9"
-        );
+        .title(i18n::text(locale, Text::HelpTitle))
+        .description(i18n::text(locale, Text::Help));
     let reply = CreateReply::default().ephemeral(true).embed(embed);
     context.send(reply).await?;
     Ok(())
@@ -54,20 +109,22 @@ pub(in crate::bot) async fn help(context: Context<'_>) -> Result<(), Error> {
 )]
 pub(in crate::bot) async fn version(context: Context<'_>) -> Result<(), Error> {
     context.defer_ephemeral().await?;
+    let locale = i18n::command_locale(context).await?;
     let embed = CreateEmbed::default()
-        .title("Version Check")
+        .title(i18n::text(locale, Text::VersionTitle))
         .description(constants::DISCORD_BOT_VERSION);
     let reply = CreateReply::default().ephemeral(true).embed(embed);
-    let version_check = update_checker::check_for_update().await;
+    let preferences = context.data().db.get_update_preferences().await?;
+    let version_check = update_checker::check_for_update(&preferences).await;
     let reply = if version_check.is_warn() {
         let embed = CreateEmbed::default()
-            .title("Warning")
+            .title(i18n::text(locale, Text::WarningTitle))
             .color(Colour::ORANGE)
             .description(version_check.to_string());
         reply.embed(embed)
     } else if version_check.is_error() {
         let embed = CreateEmbed::default()
-            .title("Error")
+            .title(i18n::text(locale, Text::ErrorTitle))
             .color(Colour::RED)
             .description(version_check.to_string());
         reply.embed(embed)
@@ -78,6 +135,158 @@ pub(in crate::bot) async fn version(context: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+/// See which roles a license key would grant, without registering it
+#[poise::command(
+    slash_command,
+    guild_only,
+    user_cooldown = 10,
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn preview_roles(
+    context: Context<'_>,
+    #[description = "Your license key"] license: String,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+    let user_id = context.author().id;
+    // checking a license is as sensitive as registering it, so this gets all the same limits. Otherwise it could be used
+    // to try license keys without tripping the registration failure blocks.
+    let _task = match tasks::start(TaskKind::Registration, guild_id, user_id) {
+        Ok(task) => task,
+        Err(_) => {
+            let embed = CreateEmbed::default()
+                .title(i18n::text(locale, Text::RegistrationBusyTitle))
+                .description(i18n::text(locale, Text::RegistrationBusy))
+                .color(Colour::ORANGE);
+            context
+                .send(CreateReply::default().embed(embed).ephemeral(true))
+                .await?;
+            return Ok(());
+        }
+    };
+    if let Some(embed) =
+        registration_unavailable_embed(&context.data().db, guild_id, user_id, locale).await?
+    {
+        context
+            .send(CreateReply::default().embed(embed).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+    let Some(api_key) = context.data().db.get_jinxxy_api_key(guild_id).await? else {
+        context
+            .send(error_reply(
+                i18n::text(locale, Text::RolePreviewFailureTitle),
+                i18n::text(locale, Text::RegistrationNotSetUp),
+            ))
+            .await?;
+        return Ok(());
+    };
+
+    let license_key = license.trim();
+    let license_type = license::identify_license(license_key);
+    let license_info = match license_type.create_untrusted_jinxxy_license(license_key) {
+        Some(license) => jinxxy::check_license(&api_key, license).await?,
+        None => None,
+    };
+
+    // Like registration, a license someone else has used up gets the same response as an invalid one. Otherwise this
+    // command would reveal which licenses are valid.
+    let license_info = match license_info {
+        Some(license_info) => {
+            let validation = if license_info.activations == 0 {
+                Default::default()
+            } else {
+                let activations =
+                    jinxxy::get_license_activations(&api_key, &license_info.license_id).await?;
+                license::validate_jinxxy_license_activation(user_id, &activations)
+            };
+            let max_activations = context
+                .data()
+                .db
+                .get_max_activations(guild_id, license_info.product_id.clone())
+                .await?;
+            Some(license_info).filter(|_| !validation.blocked(max_activations))
+        }
+        None => None,
+    };
+
+    let reply = if let Some(license_info) = license_info {
+        let roles = context
+            .data()
+            .db
+            .get_role_grants(
+                guild_id,
+                license_info.product_id.clone(),
+                license_info.product_version_id.clone(),
+            )
+            .await?;
+        let product_name = license_info.product_name.safe_display();
+        let message = if roles.is_empty() {
+            i18n::format(
+                locale,
+                Text::RolePreviewNoRoles,
+                &[("product", &product_name)],
+            )
+        } else {
+            let mut message =
+                i18n::format(locale, Text::RolePreview, &[("product", &product_name)]);
+            for role in roles {
+                message.push_str(format!("\n- <@&{}>", role.get()).as_str());
+            }
+            message
+        };
+        CreateReply::default()
+            .embed(
+                CreateEmbed::default()
+                    .title(i18n::text(locale, Text::RolePreviewTitle))
+                    .description(message)
+                    .footer(CreateEmbedFooter::new(i18n::text(
+                        locale,
+                        Text::RolePreviewFooter,
+                    ))),
+            )
+            .ephemeral(true)
+    } else {
+        record_registration_failure(
+            context.serenity_context(),
+            &context.data().db,
+            guild_id,
+            user_id,
+        )
+        .await?;
+        let message = format!("{}.", i18n::text(locale, Text::InvalidLicense));
+        let message = if license_type.is_jinxxy_license() {
+            message
+        } else {
+            let hint = i18n::format(
+                locale,
+                Text::WrongLicenseTypeHint,
+                &[("license_type", &license_type)],
+            );
+            format!("{}\n{}", message, hint)
+        };
+        error_reply(i18n::text(locale, Text::RolePreviewFailureTitle), message)
+    };
+
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Register a guild's newly set store with the product cache. This runs in the background so the command doesn't have
+/// to wait on the Jinxxy API.
+fn register_store(context: Context<'_>, guild_id: GuildId) {
+    let db = context.data().db.clone();
+    let api_cache = context.data().api_cache.clone();
+    tokio::task::spawn(async move {
+        api_cache.register_store_in_cache(&db, guild_id).await;
+    });
+}
+
 /// Set up Jinx for this Discord server
 #[poise::command(
     slash_command,
@@ -88,9 +297,10 @@ pub(in crate::bot) async fn version(context: Context<'_>) -> Result<(), Error> {
 )]
 pub(in crate::bot) async fn init(
     context: Context<'_>,
-    #[description = "Jinxxy API key"] api_key: Option<String>,
+    #[description = "Jinxxy API key. Leave this out to enter it in a private form instead"] api_key: Option<
+        String,
+    >,
 ) -> Result<(), Error> {
-    context.defer_ephemeral().await?;
     let guild_id = context
         .guild_id()
         .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
@@ -99,6 +309,26 @@ pub(in crate::bot) async fn init(
     let api_key = api_key
         .map(|api_key| api_key.trim().to_string())
         .filter(|api_key| !api_key.is_empty());
+    // without a key we reinstall commands for the existing store, or ask for one in a form that keeps it out of the
+    // user's command history
+    let api_key = match api_key {
+        Some(api_key) => Some(api_key),
+        None if context
+            .data()
+            .db
+            .get_jinxxy_api_key(guild_id)
+            .await?
+            .is_some() =>
+        {
+            None
+        }
+        None => match prompt_api_key(context).await? {
+            Some(api_key) => Some(api_key),
+            None => return Ok(()),
+        },
+    };
+    context.defer_ephemeral().await?;
+    let locale = i18n::command_locale(context).await?;
 
     let reply = if let Some(api_key) = api_key {
         // here we have a bit of an easter-egg to install owner commands
@@ -114,69 +344,605 @@ pub(in crate::bot) async fn init(
                 set_guild_commands(&context, &context.data().db, guild_id, Some(true), None)
                     .await?;
 
-                success_reply("Success", "Owner commands installed.")
+                success_reply(
+                    i18n::text(locale, Text::SuccessTitle),
+                    i18n::text(locale, Text::OwnerCommandsInstalled),
+                )
             } else {
-                error_reply("Error Installing Owner Commands", "Not an owner")
+                error_reply(
+                    i18n::text(locale, Text::OwnerCommandsInstallErrorTitle),
+                    i18n::text(locale, Text::NotAnOwner),
+                )
             }
         } else if api_key == "uninstall_owner_commands" {
             if check_owner(context).await? {
                 context.data().db.set_owner_guild(guild_id, false).await?;
                 set_guild_commands(&context, &context.data().db, guild_id, Some(false), None)
                     .await?;
-                success_reply("Success", "Owner commands uninstalled.")
+                success_reply(
+                    i18n::text(locale, Text::SuccessTitle),
+                    i18n::text(locale, Text::OwnerCommandsUninstalled),
+                )
             } else {
-                error_reply("Error Uninstalling Owner Commands", "Not an owner")
+                error_reply(
+                    i18n::text(locale, Text::OwnerCommandsUninstallErrorTitle),
+                    i18n::text(locale, Text::NotAnOwner),
+                )
             }
-        } else if JINXXY_API_KEY_REGEX.with(|regex| regex.is_match(api_key.as_str())) {
+        } else if is_jinxxy_api_key(api_key.as_str()) {
             // normal /init <key> use ends up in this branch
+            let api_key = SecretString::new(api_key.trim().to_string());
             match jinxxy::get_own_user(&api_key).await {
                 Ok(auth_user) => {
-                    let has_required_scopes = auth_user.has_required_scopes();
-                    let display_name = auth_user.into_display_name();
+                    let scopes_embed = api_key_scopes_embed(locale, &auth_user);
+                    let jinxxy_user_id = auth_user.id.clone();
+                    let display_name = auth_user.into_display_name().safe_display().to_string();
+                    context
+                        .data()
+                        .db
+                        .set_jinxxy_api_key(guild_id, api_key, jinxxy_user_id)
+                        .await?;
+                    register_store(context, guild_id);
                     context
                         .data()
                         .db
-                        .set_jinxxy_api_key(guild_id, api_key.trim().to_string())
+                        .audit(
+                            guild_id,
+                            AuditLogEntry::new(AuditAction::SetApiKey)
+                                .actor(context.author().id)
+                                .detail(format!("account {display_name}")),
+                        )
                         .await?;
                     set_guild_commands(&context, &context.data().db, guild_id, None, Some(true))
                         .await?;
-                    let reply = success_reply("Success", format!("Welcome, {display_name}! API key set and additional slash commands enabled. Please continue bot setup."));
-                    if has_required_scopes {
-                        reply
-                    } else {
-                        let embed = CreateEmbed::default()
-                            .title("Permission Warning")
-                            .color(Colour::ORANGE)
-                            .description("Provided API key is missing at least one of the mandatory scopes. Jinx commands may not work correctly. Please double-check your API key setup against the documentation [here](<https://github.com/zkxs/jinx#installation>).");
-                        reply.embed(embed)
-                    }
+                    success_reply(
+                        i18n::text(locale, Text::SuccessTitle),
+                        i18n::format(locale, Text::InitSuccess, &[("user", &display_name)]),
+                    )
+                    .embed(scopes_embed)
                 }
                 Err(e) => error_reply(
-                    "Error Initializing Jinx",
-                    format!("Error verifying API key: {e}"),
+                    i18n::text(locale, Text::InitErrorTitle),
+                    i18n::format(locale, Text::ApiKeyVerifyError, &[("error", &e)]),
                 ),
             }
         } else {
             // user has given us some mystery garbage value for their API key
-            debug!("invalid API key provided: \"{}\"", api_key); // log it to try and diagnose why people have trouble with the initial setup
-            error_reply("Error Initializing Jinx","Provided API key appears to be invalid. API keys should look like `sk_9bba2064ee8c20aa4fd6b015eed2001a`. If you need help, bot setup documentation can be found [here](<https://github.com/zkxs/jinx#installation>).")
+            // log its shape (but never the value itself) to try and diagnose why people have trouble with the initial setup
+            debug!(
+                "invalid API key provided: {} chars, sk_ prefix: {}",
+                api_key.len(),
+                api_key.starts_with("sk_")
+            );
+            error_reply(
+                i18n::text(locale, Text::InitErrorTitle),
+                i18n::text(locale, Text::InitInvalidApiKey),
+            )
         }
-    } else if context
-        .data()
-        .db
-        .get_jinxxy_api_key(guild_id)
-        .await?
-        .is_some()
-    {
-        // re-initialize commands but only if API key is already set
-        set_guild_commands(&context, &context.data().db, guild_id, None, Some(true)).await?;
-
-        success_reply("Success", "Commands reinstalled.")
     } else {
-        error_reply("Error Initializing Jinx", "Please provide a Jinxxy API key")
+        // re-initialize commands, as we only get here if the API key is already set
+        set_guild_commands(&context, &context.data().db, guild_id, None, Some(true)).await?;
+        success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::text(locale, Text::CommandsReinstalled),
+        )
     };
 
     context.send(reply).await?;
 
     Ok(())
 }
+
+/// Walk through setting up Jinx for this Discord server step by step
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn setup(context: Context<'_>) -> Result<(), Error> {
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let db = &context.data().db;
+    let locale = i18n::command_locale(context).await?;
+
+    let embed = CreateEmbed::default()
+        .title(i18n::text(locale, Text::SetupTitle))
+        .description(i18n::text(locale, Text::SetupStarting));
+    let reply = context
+        .send(CreateReply::default().ephemeral(true).embed(embed))
+        .await?;
+
+    let mut summary: Vec<String> = Vec::new();
+    let mut warnings: Vec<CreateEmbed> = Vec::new();
+
+    // step 1: API key. This is the only step that can't be skipped, unless a key was already set.
+    let mut jinxxy_user: Option<jinxxy::DisplayUser> = None;
+    let mut error: Option<String> = None;
+    loop {
+        let has_api_key = db.get_jinxxy_api_key(guild_id).await?.is_some();
+        let mut buttons = vec![CreateButton::new(SETUP_API_KEY_BUTTON_ID)
+            .label(i18n::text(locale, Text::SetupEnterApiKey))
+            .style(ButtonStyle::Primary)];
+        if has_api_key {
+            buttons.push(
+                CreateButton::new(SETUP_SKIP_ID)
+                    .label(i18n::text(locale, Text::SetupKeepApiKey))
+                    .style(ButtonStyle::Secondary),
+            );
+        }
+        let page = setup_page(
+            locale,
+            1,
+            Text::JinxxyApiKeyTitle,
+            Text::SetupApiKey,
+            error.take(),
+            vec![CreateActionRow::Buttons(buttons)],
+        );
+        let Some(interaction) = await_setup_step(context, &reply, page).await? else {
+            return setup_timed_out(context, &reply, locale).await;
+        };
+        if interaction.data.custom_id == SETUP_SKIP_ID {
+            interaction
+                .create_response(context, CreateInteractionResponse::Acknowledge)
+                .await?;
+            summary.push(i18n::text(locale, Text::SetupKeptApiKey).to_string());
+            break;
+        }
+
+        let components = vec![CreateActionRow::InputText(
+            CreateInputText::new(
+                InputTextStyle::Short,
+                i18n::text(locale, Text::ApiKeyLabel),
+                SETUP_API_KEY_INPUT_ID,
+            )
+            .placeholder("sk_9bba2064ee8c20aa4fd6b015eed2001a"),
+        )];
+        let modal = CreateModal::new(
+            SETUP_API_KEY_MODAL_ID,
+            i18n::text(locale, Text::JinxxyApiKeyTitle),
+        )
+        .components(components);
+        interaction
+            .create_response(context, CreateInteractionResponse::Modal(modal))
+            .await?;
+        let Some(modal_interaction) = ModalInteractionCollector::new(context.serenity_context())
+            .author_id(context.author().id)
+            .custom_ids(vec![SETUP_API_KEY_MODAL_ID.to_string()])
+            .timeout(SETUP_TIMEOUT)
+            .await
+        else {
+            return setup_timed_out(context, &reply, locale).await;
+        };
+        modal_interaction
+            .create_response(context, CreateInteractionResponse::Acknowledge)
+            .await?;
+
+        let api_key = modal_interaction
+            .data
+            .components
+            .iter()
+            .flat_map(|row| row.components.iter())
+            .find_map(|component| match component {
+                ActionRowComponent::InputText(input_text)
+                    if input_text.custom_id == SETUP_API_KEY_INPUT_ID =>
+                {
+                    input_text
+                        .value
+                        .as_deref()
+                        .map(|value| value.trim().to_string())
+                }
+                _ => None,
+            })
+            .unwrap_or_default();
+        if !is_jinxxy_api_key(api_key.as_str()) {
+            debug!(
+                "invalid API key provided: {} chars, sk_ prefix: {}",
+                api_key.len(),
+                api_key.starts_with("sk_")
+            );
+            error = Some(i18n::text(locale, Text::InvalidApiKey).to_string());
+            continue;
+        }
+        let api_key = SecretString::new(api_key);
+        match jinxxy::get_own_user(&api_key).await {
+            Ok(auth_user) => {
+                if !auth_user.has_required_scopes() {
+                    warnings.push(api_key_scopes_embed(locale, &auth_user));
+                }
+                let jinxxy_user_id = auth_user.id.clone();
+                let display_user: jinxxy::DisplayUser = auth_user.into();
+                db.set_jinxxy_api_key(guild_id, api_key, jinxxy_user_id)
+                    .await?;
+                register_store(context, guild_id);
+                db.audit(
+                    guild_id,
+                    AuditLogEntry::new(AuditAction::SetApiKey)
+                        .actor(context.author().id)
+                        .detail(format!("account {}", display_user.display_name)),
+                )
+                .await?;
+                set_guild_commands(&context, db, guild_id, None, Some(true)).await?;
+                summary.push(i18n::format(
+                    locale,
+                    Text::SetupApiKeySet,
+                    &[("user", &display_user.display_name.safe_display())],
+                ));
+                jinxxy_user = Some(display_user);
+                break;
+            }
+            Err(e) => {
+                error = Some(i18n::format(
+                    locale,
+                    Text::ApiKeyVerifyError,
+                    &[("error", &e)],
+                ));
+            }
+        }
+    }
+
+    // step 2: log channel
+    loop {
+        let select = CreateSelectMenu::new(
+            SETUP_LOG_CHANNEL_ID,
+            CreateSelectMenuKind::Channel {
+                channel_types: Some(vec![ChannelType::Text]),
+                default_channels: None,
+            },
+        )
+        .placeholder(i18n::text(locale, Text::SetupLogChannelPlaceholder));
+        let page = setup_page(
+            locale,
+            2,
+            Text::SetupLogChannelTitle,
+            Text::SetupLogChannel,
+            error.take(),
+            vec![CreateActionRow::SelectMenu(select), skip_button_row(locale)],
+        );
+        let Some(interaction) = await_setup_step(context, &reply, page).await? else {
+            return setup_timed_out(context, &reply, locale).await;
+        };
+        interaction
+            .create_response(context, CreateInteractionResponse::Acknowledge)
+            .await?;
+        let Some(channel) = selected_channel(&interaction) else {
+            summary.push(i18n::text(locale, Text::SetupNoLogChannel).to_string());
+            break;
+        };
+
+        // attempt to write a test log to the channel, same as /set_log_channel
+        let log_locale = i18n::guild_locale(db, guild_id, None).await?;
+        let embed = CreateEmbed::default()
+            .title(i18n::text(log_locale, Text::LogConfigurationChangedTitle))
+            .description(i18n::text(log_locale, Text::LogChannelSet));
+        match send_bot_log_message(context, channel, CreateMessage::default().embed(embed)).await {
+            Ok(_) => {
+                db.set_log_channel(guild_id, Some(channel)).await?;
+                summary.push(i18n::format(
+                    locale,
+                    Text::SetupLogChannelSet,
+                    &[("channel", &format!("<#{}>", channel.get()))],
+                ));
+                break;
+            }
+            Err(e) => {
+                warn!("Error sending message to test log channel: {:?}", e);
+                error = Some(i18n::format(
+                    locale,
+                    Text::LogChannelSendError,
+                    &[("channel", &format!("<#{}>", channel.get())), ("error", &e)],
+                ));
+            }
+        }
+    }
+
+    // step 3: blanket role
+    {
+        let select = CreateSelectMenu::new(
+            SETUP_BLANKET_ROLE_ID,
+            CreateSelectMenuKind::Role {
+                default_roles: None,
+            },
+        )
+        .placeholder(i18n::text(locale, Text::SetupBlanketRolePlaceholder));
+        let page = setup_page(
+            locale,
+            3,
+            Text::SetupBlanketRoleTitle,
+            Text::SetupBlanketRole,
+            None,
+            vec![CreateActionRow::SelectMenu(select), skip_button_row(locale)],
+        );
+        let Some(interaction) = await_setup_step(context, &reply, page).await? else {
+            return setup_timed_out(context, &reply, locale).await;
+        };
+        interaction
+            .create_response(context, CreateInteractionResponse::Acknowledge)
+            .await?;
+        let role = match &interaction.data.kind {
+            ComponentInteractionDataKind::RoleSelect { values } => values.first().copied(),
+            _ => None,
+        };
+        if let Some(role) = role {
+            db.set_blanket_role(guild_id, Some(role)).await?;
+            db.audit(
+                guild_id,
+                AuditLogEntry::new(AuditAction::SetBlanketRole)
+                    .actor(context.author().id)
+                    .role(role),
+            )
+            .await?;
+            summary.push(i18n::format(
+                locale,
+                Text::SetupBlanketRoleSet,
+                &[("role", &format!("<@&{}>", role.get()))],
+            ));
+            let assignable_roles = assignable_roles(&context, guild_id).await?;
+            if let Some(embed) =
+                create_role_warning_from_roles(locale, &assignable_roles, [role].into_iter())
+            {
+                warnings.push(embed);
+            }
+        } else {
+            summary.push(i18n::text(locale, Text::SetupNoBlanketRole).to_string());
+        }
+    }
+
+    // step 4: registration post
+    loop {
+        let select = CreateSelectMenu::new(
+            SETUP_POST_CHANNEL_ID,
+            CreateSelectMenuKind::Channel {
+                channel_types: Some(vec![ChannelType::Text]),
+                default_channels: None,
+            },
+        )
+        .placeholder(i18n::text(locale, Text::SetupPostPlaceholder));
+        let page = setup_page(
+            locale,
+            4,
+            Text::SetupPostTitle,
+            Text::SetupPost,
+            error.take(),
+            vec![CreateActionRow::SelectMenu(select), skip_button_row(locale)],
+        );
+        let Some(interaction) = await_setup_step(context, &reply, page).await? else {
+            return setup_timed_out(context, &reply, locale).await;
+        };
+        interaction
+            .create_response(context, CreateInteractionResponse::Acknowledge)
+            .await?;
+        let Some(channel) = selected_channel(&interaction) else {
+            summary.push(i18n::text(locale, Text::SetupNoPost).to_string());
+            break;
+        };
+
+        let display_user = match jinxxy_user.take() {
+            Some(display_user) => display_user,
+            None => {
+                let api_key = db
+                    .get_jinxxy_api_key(guild_id)
+                    .await?
+                    .ok_or_else(|| JinxError::new("Jinxxy API key is not set"))?;
+                match jinxxy::get_own_user(&api_key).await {
+                    Ok(auth_user) => auth_user.into(),
+                    Err(e) => {
+                        error = Some(i18n::format(
+                            locale,
+                            Text::JinxxyUserError,
+                            &[("error", &e)],
+                        ));
+                        continue;
+                    }
+                }
+            }
+        };
+        let template = db.get_post_template(guild_id).await?;
+        let product_image_url = post_template_image_url(db, guild_id, &template).await?;
+        // everyone sees the post, so it's in the guild's language rather than the user's
+        let post_locale = i18n::guild_locale(db, guild_id, None).await?;
+        match channel
+            .send_message(
+                context,
+                registration_post(post_locale, &display_user, &template, product_image_url),
+            )
+            .await
+        {
+            Ok(message) => {
+                db.add_registration_post(guild_id, channel, message.id)
+                    .await?;
+                db.complete_onboarding_step(guild_id, OnboardingStep::FirstPost)
+                    .await?;
+                summary.push(i18n::format(
+                    locale,
+                    Text::SetupPostCreated,
+                    &[("channel", &format!("<#{}>", channel.get()))],
+                ));
+                break;
+            }
+            Err(e) => {
+                warn!("Error in /setup when sending registration post: {:?}", e);
+                error = Some(i18n::format(
+                    locale,
+                    Text::SetupPostSendError,
+                    &[("channel", &format!("<#{}>", channel.get()))],
+                ));
+            }
+        }
+    }
+
+    let summary = summary
+        .into_iter()
+        .map(|line| format!("- {line}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut result =
+        success_reply(i18n::text(locale, Text::SetupCompleteTitle), summary).components(vec![]);
+    for embed in warnings {
+        result = result.embed(embed);
+    }
+    reply.edit(context, result).await?;
+    Ok(())
+}
+
+/// See which setup steps are done for this Discord server, and what to do next
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn setup_progress(context: Context<'_>) -> Result<(), Error> {
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+    let completed = context.data().db.get_onboarding_steps(guild_id).await?;
+
+    let mut message = String::new();
+    let mut complete_count = 0;
+    for step in OnboardingStep::ALL {
+        let completed_unix_ms = completed
+            .iter()
+            .find(|(completed_step, _)| *completed_step == step)
+            .map(|(_, completed_unix_ms)| *completed_unix_ms);
+        let (description, hint) = onboarding_step_texts(step);
+        let line = if let Some(completed_unix_ms) = completed_unix_ms {
+            complete_count += 1;
+            format!(
+                "\n✅ {} (<t:{}:d>)",
+                i18n::text(locale, description),
+                completed_unix_ms / 1000
+            )
+        } else {
+            format!(
+                "\n⬜ {}: {}",
+                i18n::text(locale, description),
+                i18n::text(locale, hint)
+            )
+        };
+        message.push_str(line.as_str());
+    }
+    let message = format!(
+        "{}{}",
+        i18n::format(
+            locale,
+            Text::SetupProgress,
+            &[
+                ("complete", &complete_count),
+                ("steps", &OnboardingStep::ALL.len())
+            ],
+        ),
+        message
+    );
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SetupProgressTitle),
+            message,
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Messages describing an onboarding step, and what to do to complete it
+fn onboarding_step_texts(step: OnboardingStep) -> (Text, Text) {
+    match step {
+        OnboardingStep::StoreLinked => {
+            (Text::OnboardingStoreLinked, Text::OnboardingStoreLinkedHint)
+        }
+        OnboardingStep::LogChannelSet => (
+            Text::OnboardingLogChannelSet,
+            Text::OnboardingLogChannelSetHint,
+        ),
+        OnboardingStep::FirstLink => (Text::OnboardingFirstLink, Text::OnboardingFirstLinkHint),
+        OnboardingStep::FirstPost => (Text::OnboardingFirstPost, Text::OnboardingFirstPostHint),
+        OnboardingStep::FirstActivation => (
+            Text::OnboardingFirstActivation,
+            Text::OnboardingFirstActivationHint,
+        ),
+    }
+}
+
+/// Build one page of the `/setup` wizard
+fn setup_page(
+    locale: Option<&str>,
+    step: u8,
+    title: Text,
+    description: Text,
+    error: Option<String>,
+    components: Vec<CreateActionRow>,
+) -> CreateReply {
+    let title = i18n::format(
+        locale,
+        Text::SetupPageTitle,
+        &[
+            ("step", &step),
+            ("steps", &SETUP_STEPS),
+            ("title", &i18n::text(locale, title)),
+        ],
+    );
+    let embed = CreateEmbed::default()
+        .title(title)
+        .description(i18n::text(locale, description));
+    let reply = CreateReply::default()
+        .ephemeral(true)
+        .embed(embed)
+        .components(components);
+    if let Some(error) = error {
+        let embed = CreateEmbed::default()
+            .title(i18n::text(locale, Text::ErrorTitle))
+            .color(Colour::RED)
+            .description(error);
+        reply.embed(embed)
+    } else {
+        reply
+    }
+}
+
+fn skip_button_row(locale: Option<&str>) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![CreateButton::new(SETUP_SKIP_ID)
+        .label(i18n::text(locale, Text::Skip))
+        .style(ButtonStyle::Secondary)])
+}
+
+/// Show a `/setup` page and wait for the user to interact with it. The caller is responsible for responding to the
+/// returned interaction. Returns `None` if the user didn't respond in time.
+async fn await_setup_step(
+    context: Context<'_>,
+    reply: &ReplyHandle<'_>,
+    page: CreateReply,
+) -> Result<Option<ComponentInteraction>, Error> {
+    reply.edit(context, page).await?;
+    let interaction = reply
+        .message()
+        .await?
+        .await_component_interaction(context.serenity_context())
+        .author_id(context.author().id)
+        .timeout(SETUP_TIMEOUT)
+        .await;
+    Ok(interaction)
+}
+
+async fn setup_timed_out(
+    context: Context<'_>,
+    reply: &ReplyHandle<'_>,
+    locale: Option<&str>,
+) -> Result<(), Error> {
+    let result = error_reply(
+        i18n::text(locale, Text::SetupTimedOutTitle),
+        i18n::text(locale, Text::SetupTimedOut),
+    )
+    .components(vec![]);
+    reply.edit(context, result).await?;
+    Ok(())
+}
+
+fn selected_channel(interaction: &ComponentInteraction) -> Option<ChannelId> {
+    match &interaction.data.kind {
+        ComponentInteractionDataKind::ChannelSelect { values } => values.first().copied(),
+        _ => None,
+    }
+}
@@ -1,33 +1,118 @@
 // This file is part of jinx. Copyright © 2024 jinx contributors.
 // jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
 
+use crate::bot::activation_hooks;
+use crate::bot::autocomplete;
+use crate::bot::commands::{is_jinxxy_api_key, prompt_api_key};
+use crate::bot::i18n;
+use crate::bot::i18n::Text;
+use crate::bot::license_import;
+use crate::bot::license_import::ImportRow;
+use crate::bot::link_rules;
+use crate::bot::links_document;
+use crate::bot::member_chunks;
+use crate::bot::milestones;
+use crate::bot::tasks;
+use crate::bot::tasks::{LimitReached, TaskKind};
 use crate::bot::util::{
-    assignable_roles, create_role_warning_from_roles, create_role_warning_from_unassignable,
-    error_reply, license_to_id, success_reply,
+    api_key_scopes_embed, assignable_roles, create_role_warning_from_roles,
+    create_role_warning_from_unassignable, error_reply, field_value, find_product_version,
+    license_to_id, masked_link, parse_color, redact_count, send_bot_log_message, send_paginated,
+    sparkline, success_reply, SafeDisplayExt as _,
+};
+use crate::bot::verification;
+use crate::bot::{Context, CooldownTier, CLAIM_BUTTON_ID_PREFIX};
+use crate::db::{
+    ActivationHookKind, AuditAction, AuditLogEntry, AuditLogFilter, CountRedaction, JinxDb,
+    Language, NotificationDigest, OnboardingStep, PostTemplate,
 };
-use crate::bot::{Context, MISSING_API_KEY_MESSAGE};
 use crate::error::JinxError;
 use crate::http::jinxxy;
 use crate::http::jinxxy::{GetProfileImageUrl as _, GetProfileUrl as _};
+use crate::http::webhook;
+use crate::license;
 use crate::license::LOCKING_USER_ID;
 use poise::serenity_prelude as serenity;
-use poise::CreateReply;
+use poise::{ChoiceParameter as _, CreateReply, ReplyHandle};
+use secrecy::{ExposeSecret as _, SecretString};
 use serenity::{
-    ButtonStyle, ChannelId, Colour, CreateActionRow, CreateButton, CreateEmbed, CreateMessage,
-    RoleId,
+    ActionRowComponent, Button, ButtonKind, ButtonStyle, ChannelId, Colour,
+    ComponentInteractionDataKind, CreateActionRow, CreateAllowedMentions, CreateAttachment,
+    CreateButton, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse, CreateMessage,
+    CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, EditMessage, GetMessages,
+    GuildId, MessageId, RoleId,
 };
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tokio::time::{Duration, Instant};
 use tracing::warn;
 
 // discord component ids
 pub(in crate::bot) const REGISTER_BUTTON_ID: &str = "jinx_register_button";
 pub(in crate::bot) const LICENSE_KEY_ID: &str = "jinx_license_key_input";
+const BULK_LINK_SELECT_ID_PREFIX: &str = "jinx_bulk_link_select_";
+const BULK_LINK_CONFIRM_ID: &str = "jinx_bulk_link_confirm";
+const BULK_LINK_CANCEL_ID: &str = "jinx_bulk_link_cancel";
+const REVIEW_LINKS_SELECT_ID_PREFIX: &str = "jinx_review_links_select_";
+const REVIEW_LINKS_PREVIOUS_ID: &str = "jinx_review_links_previous";
+const REVIEW_LINKS_NEXT_ID: &str = "jinx_review_links_next";
+const REVIEW_LINKS_DONE_ID: &str = "jinx_review_links_done";
+const GRANT_MISSING_ROLES_CONTINUE_ID: &str = "jinx_grant_missing_roles_continue";
+const GRANT_MISSING_ROLES_STOP_ID: &str = "jinx_grant_missing_roles_stop";
+const CREATE_POST_PUBLISH_ID: &str = "jinx_create_post_publish";
+const CREATE_POST_CANCEL_ID: &str = "jinx_create_post_cancel";
+
+/// How long `/create_post` waits for the admin to publish a preview
+const CREATE_POST_PREVIEW_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Discord allows at most this many options in a select menu
+const SELECT_MENU_OPTION_LIMIT: usize = 25;
+
+/// Discord allows five action rows per message. One is needed for the buttons, leaving the rest for select menus.
+const BULK_LINK_SELECT_MENU_LIMIT: usize = 4;
+
+/// How long the admin has to finish picking products in `/link_products_bulk`
+const BULK_LINK_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Versions shown per page of `/review_links`. Each gets a select menu, leaving one action row for the buttons.
+const REVIEW_LINKS_PAGE_SIZE: usize = 4;
+
+/// How long `/review_links` waits for the next interaction before removing its controls
+const REVIEW_LINKS_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// How long `/grant_missing_roles` waits for the admin to ask for the next batch
+const GRANT_MISSING_ROLES_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Characters of planned grants shown per `/grant_missing_roles` dry run page. This leaves room for the summary in the
+/// 4096 character embed description.
+const GRANT_MISSING_ROLES_PREVIEW_LENGTH: usize = 3500;
+
+/// Discord allows at most this many buttons in an action row
+const ACTION_ROW_BUTTON_LIMIT: usize = 5;
+
+/// Buttons per `/create_claim_post` message: five full action rows
+const CLAIM_POST_BUTTON_LIMIT: usize = 5 * ACTION_ROW_BUTTON_LIMIT;
+
+/// Longest label Discord allows on a button
+const BUTTON_LABEL_LIMIT: usize = 80;
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 
+/// Number of products shown by `/stats`
+const STATS_TOP_PRODUCTS_LIMIT: u64 = 5;
+
+/// Number of days charted by `/stats`
+const STATS_CHART_DAYS: usize = 30;
+
+/// Number of products `/user_info` shows, each in its own embed field. This keeps the embed under Discord's size limit.
+const USER_INFO_PRODUCT_FIELD_LIMIT: usize = 4;
+
+/// Number of matches shown by `/lookup_license`. Each one costs a Jinxxy API call.
+const LICENSE_LOOKUP_LIMIT: u64 = 10;
+
 /// Get statistics about license activations
 #[poise::command(
     slash_command,
+    custom_data = "CooldownTier::Cheap",
     default_member_permissions = "MANAGE_GUILD",
     install_context = "Guild",
     interaction_context = "Guild"
@@ -36,331 +121,388 @@ pub(in crate::bot) async fn stats(context: Context<'_>) -> Result<(), Error> {
     let guild_id = context
         .guild_id()
         .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
-    let license_activation_count = context
-        .data()
-        .db
-        .guild_license_activation_count(guild_id)
-        .await
-        .unwrap();
-    let product_role_count = context
-        .data()
-        .db
-        .guild_product_role_count(guild_id)
-        .await
-        .unwrap();
+    let locale = i18n::command_locale(context).await?;
+    let db = &context.data().db;
+    let license_activation_count = db.guild_license_activation_count(guild_id).await?;
+    let product_role_count = db.guild_product_role_count(guild_id).await?;
+    let now_unix_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0);
+    let days_ago = |days: u64| now_unix_ms.saturating_sub(days * 24 * 60 * 60 * 1000);
+    let week_activation_count = db
+        .guild_license_activation_count_since(guild_id, days_ago(7))
+        .await?;
+    let month_activation_count = db
+        .guild_license_activation_count_since(guild_id, days_ago(30))
+        .await?;
+    let daily_activations = db.get_daily_activations(guild_id, 1).await?;
+    let top_products = db
+        .get_top_products(guild_id, None, STATS_TOP_PRODUCTS_LIMIT)
+        .await?;
 
-    let message = format!(
-        "license activations={license_activation_count}\n\
-        product→role links={product_role_count}"
+    let message = i18n::format(
+        locale,
+        Text::Stats,
+        &[
+            ("total", &license_activation_count),
+            ("week", &week_activation_count),
+            ("month", &month_activation_count),
+            ("links", &product_role_count),
+        ],
     );
-    let embed = CreateEmbed::default()
-        .title("Jinx Stats")
-        .description(message);
+    let daily_counts: Vec<u64> = daily_activations
+        .iter()
+        .rev()
+        .take(STATS_CHART_DAYS)
+        .rev()
+        .map(|(_, count)| *count)
+        .collect();
+    let chart = format!(
+        "`{}`\n{}",
+        sparkline(&daily_counts),
+        i18n::format(
+            locale,
+            Text::StatsChartPeak,
+            &[("count", &daily_counts.iter().max().copied().unwrap_or(0))],
+        )
+    );
+    let mut embed = CreateEmbed::default()
+        .title(i18n::text(locale, Text::StatsTitle))
+        .description(message)
+        .field(
+            i18n::format(
+                locale,
+                Text::StatsChartTitle,
+                &[("days", &STATS_CHART_DAYS)],
+            ),
+            chart,
+            false,
+        );
+    if !top_products.is_empty() {
+        let mut top_products_message = String::new();
+        for (rank, (product_id, product_name, activation_count)) in
+            top_products.into_iter().enumerate()
+        {
+            let product_name = product_name.unwrap_or(product_id);
+            top_products_message.push_str(
+                format!(
+                    "\n{}. {} — {}",
+                    rank + 1,
+                    product_name.safe_display(),
+                    i18n::format(
+                        locale,
+                        Text::RegistrationCount,
+                        &[("count", &activation_count)],
+                    )
+                )
+                .as_str(),
+            );
+        }
+        embed = embed.field(
+            i18n::text(locale, Text::TopProductsField),
+            top_products_message,
+            false,
+        );
+    }
     context
         .send(CreateReply::default().embed(embed).ephemeral(true))
         .await?;
     Ok(())
 }
 
-/// Set (or unset) channel for bot to log to.
+/// Number of products shown by `/top_products`
+const TOP_PRODUCTS_LIMIT: u64 = 10;
+
+/// Time window for `/top_products`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum TopProductsWindow {
+    #[name = "last 24 hours"]
+    Day,
+    #[name = "last 7 days"]
+    Week,
+    #[name = "last 30 days"]
+    Month,
+    #[name = "last 365 days"]
+    Year,
+    #[name = "all time"]
+    AllTime,
+}
+
+impl TopProductsWindow {
+    fn days(self) -> Option<u64> {
+        match self {
+            TopProductsWindow::Day => Some(1),
+            TopProductsWindow::Week => Some(7),
+            TopProductsWindow::Month => Some(30),
+            TopProductsWindow::Year => Some(365),
+            TopProductsWindow::AllTime => None,
+        }
+    }
+}
+
+/// Show the most registered products in this server
 #[poise::command(
     slash_command,
+    custom_data = "CooldownTier::Cheap",
     guild_only,
     default_member_permissions = "MANAGE_GUILD",
     install_context = "Guild",
     interaction_context = "Guild"
 )]
-pub(in crate::bot) async fn set_log_channel(
+pub(in crate::bot) async fn top_products(
     context: Context<'_>,
-    #[description = "user to query licenses for"] channel: Option<ChannelId>, // we can't use Channel here because it throws FrameworkError::ArgumentParse on access problems, which cannot be handled cleanly.
+    #[description = "time window to rank products over (default last 30 days)"] window: Option<
+        TopProductsWindow,
+    >,
+    #[description = "show the leaderboard to everyone in the channel (default false)"]
+    public: Option<bool>,
 ) -> Result<(), Error> {
-    context.defer_ephemeral().await?;
+    let public = public.unwrap_or(false);
+    if public {
+        context.defer().await?;
+    } else {
+        context.defer_ephemeral().await?;
+    }
 
     let guild_id = context
         .guild_id()
         .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
-
-    // if setting a channel, then attempt to write a test log to the channel
-    let test_result = match channel {
-        Some(channel) => {
-            let embed = CreateEmbed::default()
-                .title("Configuration Changed")
-                .description("I will now log to this channel.");
-            let message = CreateMessage::default().embed(embed);
-            channel.send_message(context, message).await.map(|_| ())
-        }
-        None => Ok(()),
+    let window = window.unwrap_or(TopProductsWindow::Month);
+    let locale = i18n::command_locale(context).await?;
+    let since_unix_ms = window.days().map(|days| {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0)
+            .saturating_sub(days * 24 * 60 * 60 * 1000)
+    });
+    let products = context
+        .data()
+        .db
+        .get_top_products(guild_id, since_unix_ms, TOP_PRODUCTS_LIMIT)
+        .await?;
+    // only the creator can see ephemeral replies, so those can always be exact
+    let redaction = if public {
+        context
+            .data()
+            .db
+            .get_public_count_redaction(guild_id)
+            .await?
+    } else {
+        CountRedaction::Exact
     };
 
-    let reply = match test_result {
-        Ok(()) => {
-            // test log worked, so set the channel
-            context.data().db.set_log_channel(guild_id, channel).await?;
-
-            // let the user know what we just did
-            let message = if let Some(channel) = channel {
-                format!("Bot log channel set to <#{}>.", channel.get())
-            } else {
-                "Bot log channel unset.".to_string()
-            };
-            success_reply("Success", message)
-        }
-        Err(e) => {
-            // test log failed, so let the user know
-            warn!("Error sending message to test log channel: {:?}", e);
-            error_reply("Error Setting Log Channel", format!("Log channel not set because there was an error sending a message to <#{}>: {}. Please check bot and channel permissions.", channel.unwrap().get(), e))
+    let message = if products.is_empty() {
+        i18n::text(locale, Text::TopProductsEmpty).to_string()
+    } else {
+        let mut message = String::new();
+        for (rank, (product_id, product_name, activation_count)) in products.into_iter().enumerate()
+        {
+            let product_name = product_name.unwrap_or(product_id);
+            message.push_str(format!("\n{}. {}", rank + 1, product_name.safe_display()).as_str());
+            if let Some(activation_count) = redact_count(activation_count, redaction) {
+                let count = i18n::format(
+                    locale,
+                    Text::RegistrationCount,
+                    &[("count", &activation_count)],
+                );
+                message.push_str(format!(" — {count}").as_str());
+            }
         }
+        message
     };
-
-    context.send(reply).await?;
+    let embed = CreateEmbed::default()
+        .title(i18n::format(
+            locale,
+            Text::TopProductsTitle,
+            &[("window", &window.name())],
+        ))
+        .description(message);
+    context
+        .send(CreateReply::default().embed(embed).ephemeral(!public))
+        .await?;
     Ok(())
 }
 
-/// Create post with buttons to register product keys
+/// Export a CSV of license registrations per day, for charting in a spreadsheet
 #[poise::command(
     slash_command,
+    custom_data = "CooldownTier::Cheap",
     guild_only,
-    default_member_permissions = "MANAGE_ROLES",
+    default_member_permissions = "MANAGE_GUILD",
     install_context = "Guild",
     interaction_context = "Guild"
 )]
-pub(in crate::bot) async fn create_post(context: Context<'_>) -> Result<(), Error> {
+pub(in crate::bot) async fn activity_export(
+    context: Context<'_>,
+    #[description = "number of months to export (default 3)"]
+    #[min = 1]
+    #[max = 24]
+    months: Option<u32>,
+) -> Result<(), Error> {
     context.defer_ephemeral().await?;
 
-    let channel = context.channel_id();
-
-    let components = vec![CreateActionRow::Buttons(vec![CreateButton::new(
-        REGISTER_BUTTON_ID,
-    )
-    .label("Register")
-    .style(ButtonStyle::Primary)])];
-
-    let api_key = context
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let months = months.unwrap_or(3);
+    let locale = i18n::command_locale(context).await?;
+    let days = context
         .data()
         .db
-        .get_jinxxy_api_key(
-            context
-                .guild_id()
-                .ok_or_else(|| JinxError::new("expected to be in a guild"))?,
-        )
-        .await?
-        .ok_or_else(|| JinxError::new("Jinxxy API key is not set"))?;
-    let reply = match jinxxy::get_own_user(&api_key).await {
-        Ok(jinxxy_user) => {
-            let jinxxy_user: jinxxy::DisplayUser = jinxxy_user.into(); // convert into just the data we need for this command
-            let embed = CreateEmbed::default()
-                .title("Jinxxy Product Registration")
-                .description(format!("Press the button below to register a Jinxxy license key for any of {} products. You can find your license key in your email receipt or at [jinxxy.com](<https://jinxxy.com/my/inventory>).", jinxxy_user.name_possessive()));
-            let embed = if let Some(profile_image_url) = jinxxy_user.profile_image_url() {
-                embed.thumbnail(profile_image_url)
-            } else {
-                embed
-            };
-
-            let message = CreateMessage::default().embed(embed).components(components);
-
-            if let Err(e) = channel.send_message(context, message).await {
-                warn!("Error in /create_post when sending message: {:?}", e);
-                error_reply("Error Creating Post", "Post not created because there was an error sending a message to this channel. Please check bot and channel permissions.")
-            } else {
-                success_reply("Success", "Registration post created!")
-            }
-        }
-        Err(e) => error_reply(
-            "Error Creating Post",
-            format!("Could not get info for your Jinxxy user: {}", e),
-        ),
-    };
+        .get_daily_activations(guild_id, months)
+        .await?;
 
-    context.send(reply).await?;
+    let total: u64 = days.iter().map(|(_, count)| count).sum();
+    let mut csv = "date,registrations\n".to_string();
+    for (date, count) in days {
+        csv.push_str(format!("{date},{count}\n").as_str());
+    }
+    let attachment = CreateAttachment::bytes(csv, "jinx-activity.csv");
+    context
+        .send(
+            success_reply(
+                i18n::text(locale, Text::ActivityExportTitle),
+                i18n::format(
+                    locale,
+                    Text::ActivityExport,
+                    &[("count", &total), ("months", &months)],
+                ),
+            )
+            .attachment(attachment),
+        )
+        .await?;
     Ok(())
 }
 
-// requires MANAGE_GUILD permission because it can print license keys and a bunch of other customer information
-/// Query license information for a user
+/// Post activations to the bot log as an hourly or daily summary instead of one message each
 #[poise::command(
-    context_menu_command = "List Jinxxy licenses",
     slash_command,
     guild_only,
     default_member_permissions = "MANAGE_GUILD",
     install_context = "Guild",
     interaction_context = "Guild"
 )]
-pub async fn user_info(
+pub(in crate::bot) async fn set_notification_digest(
     context: Context<'_>,
-    #[description = "user to query licenses for"] user: serenity::User,
+    #[description = "how often activations are summarized (errors are always posted right away)"]
+    mode: NotificationDigest,
 ) -> Result<(), Error> {
     context.defer_ephemeral().await?;
 
     let guild_id = context
         .guild_id()
         .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
-
-    let reply = if let Some(api_key) = context.data().db.get_jinxxy_api_key(guild_id).await? {
-        let license_ids = context
-            .data()
-            .db
-            .get_user_licenses(guild_id, user.id.get())
-            .await?;
-        let message = if license_ids.is_empty() {
-            format!("<@{}> has no license activations.", user.id.get())
-        } else {
-            let mut message = format!("Licenses for <@{}>:", user.id.get());
-
-            // build a cache of product versions that we need names for
-            // Map structure: product_id -> {product_version_id -> product_version_name}
-            let mut product_cache: HashMap<
-                String,
-                Option<HashMap<String, String, ahash::RandomState>>,
-                ahash::RandomState,
-            > = Default::default();
-
-            for license_id in license_ids {
-                let license_info = jinxxy::check_license_id(&api_key, &license_id).await?;
-                if let Some(license_info) = license_info {
-                    let product_version_cache = if let Some(product) =
-                        product_cache.get(&license_info.product_id)
-                    {
-                        product.as_ref()
-                    } else {
-                        let result = jinxxy::get_product(&api_key, &license_info.product_id).await;
-                        if let Err(e) = &result {
-                            warn!("Error looking up product info for {}, which is in license {}: {:?}", license_info.product_id, license_id, e);
-                        }
-                        let result = result.ok().map(|product| {
-                            product
-                                .versions
-                                .into_iter()
-                                .map(|version| (version.id, version.name))
-                                .collect()
-                        });
-                        product_cache
-                            .entry(license_info.product_id.clone())
-                            .or_insert(result)
-                            .as_ref() // kind of a weird use of this API because there's an extra empty check but oh well. We can't use or_insert_with because async reasons.
-                    };
-                    let product_version_name = product_version_cache
-                        .and_then(|cache| {
-                            license_info
-                                .product_version_id
-                                .as_ref()
-                                .and_then(|version_id| cache.get(version_id))
-                        })
-                        .map(|version| format!("\"{}\"", version))
-                        .unwrap_or("`null`".to_string());
-
-                    let locked = context
-                        .data()
-                        .db
-                        .is_license_locked(guild_id, license_id.clone())
-                        .await?;
-
-                    let username = if let Some(username) = &license_info.username {
-                        format!(
-                            "[{}](<{}>)",
-                            username,
-                            license_info.profile_url().ok_or_else(|| JinxError::new(
-                                "expected profile_url to exist when username is set"
-                            ))?
-                        )
-                    } else {
-                        format!("`{}`", license_info.user_id)
-                    };
-
-                    message.push_str(
-                        format!(
-                            "\n- `{}` activations={} locked={} user={} product=\"{}\" version={}",
-                            license_info.short_key,
-                            license_info.activations, // this field came from Jinxxy and is up to date
-                            locked, // this field came from the local DB and may be out of sync
-                            username,
-                            license_info.product_name,
-                            product_version_name
-                        )
-                        .as_str(),
-                    );
-                } else {
-                    // we had a license ID in our local DB, but could not find info on it in the Jinxxy API
-                    message.push_str(format!("\n- ID=`{}` (no data found)", license_id).as_str());
-                }
-            }
-            message
-        };
-        success_reply("User Info", message)
+    context
+        .data()
+        .db
+        .set_notification_digest(guild_id, mode)
+        .await?;
+    let locale = i18n::command_locale(context).await?;
+    let message = if mode == NotificationDigest::Off {
+        i18n::text(locale, Text::NotificationDigestOff).to_string()
     } else {
-        error_reply("Error Getting User Info", MISSING_API_KEY_MESSAGE)
+        i18n::format(
+            locale,
+            Text::NotificationDigestSet,
+            &[("mode", &mode.name())],
+        )
     };
-
-    context.send(reply).await?;
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            message,
+        ))
+        .await?;
     Ok(())
 }
 
-/// Deactivate a license. Does not revoke any granted roles.
+/// Set how registration counts are shown in public outputs, such as a public `/top_products`
 #[poise::command(
     slash_command,
     guild_only,
-    default_member_permissions = "MANAGE_ROLES",
+    default_member_permissions = "MANAGE_GUILD",
     install_context = "Guild",
     interaction_context = "Guild"
 )]
-pub async fn deactivate_license(
+pub(in crate::bot) async fn set_public_count_redaction(
     context: Context<'_>,
-    #[description = "user to deactivate license for"] user: serenity::User,
-    #[description = "Jinxxy license to deactivate for user"] license: String,
+    #[description = "how counts are shown to everyone (private views always show exact counts)"]
+    redaction: CountRedaction,
 ) -> Result<(), Error> {
     context.defer_ephemeral().await?;
 
     let guild_id = context
         .guild_id()
         .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    context
+        .data()
+        .db
+        .set_public_count_redaction(guild_id, redaction)
+        .await?;
+    let locale = i18n::command_locale(context).await?;
+    let example = redact_count(1234, redaction)
+        .map(|count| i18n::format(locale, Text::CountRedactionExample, &[("count", &count)]))
+        .unwrap_or_else(|| i18n::text(locale, Text::CountRedactionHidden).to_string());
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::format(
+                locale,
+                Text::CountRedactionSet,
+                &[("redaction", &redaction.name()), ("example", &example)],
+            ),
+        ))
+        .await?;
+    Ok(())
+}
 
-    let reply = if let Some(api_key) = context.data().db.get_jinxxy_api_key(guild_id).await? {
-        let license_id = license_to_id(&api_key, &license).await?;
-        if let Some(license_id) = license_id {
-            let activations = context
-                .data()
-                .db
-                .get_user_license_activations(guild_id, user.id.get(), license_id.clone())
-                .await?;
-            for activation_id in activations {
-                let license_id = license_id.clone();
-                jinxxy::delete_license_activation(&api_key, &license_id, &activation_id).await?;
-                context
-                    .data()
-                    .db
-                    .deactivate_license(guild_id, license_id, activation_id, user.id.get())
-                    .await?;
-            }
-            success_reply(
-                "Success",
-                format!(
-                    "All of <@{}>'s activations for `{}` have been deleted.",
-                    user.id.get(),
-                    license
-                ),
-            )
-        } else {
-            error_reply("Error Deactivating License", format!("License `{}` not found: please verify that the key is correct and belongs to the Jinxxy account linked to this Discord server.", license))
+/// Set the language for the bot log, and for members whose own language isn't available
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn set_language(
+    context: Context<'_>,
+    #[description = "language to use, or leave unset to use English"] language: Option<Language>,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    context.data().db.set_language(guild_id, language).await?;
+    let locale = i18n::command_locale(context).await?;
+    let message = match language {
+        Some(language) => {
+            i18n::format(locale, Text::LanguageSet, &[("language", &language.name())])
         }
-    } else {
-        error_reply("Error Deactivating License", MISSING_API_KEY_MESSAGE)
+        None => i18n::text(locale, Text::LanguageUnset).to_string(),
     };
-    context.send(reply).await?;
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            message,
+        ))
+        .await?;
     Ok(())
 }
 
-// only requires MANAGE_ROLES permission because it can't emit license key info
-/// Query activation information for a license
+/// Opt this server in or out of the bot's published global statistics
 #[poise::command(
     slash_command,
     guild_only,
-    default_member_permissions = "MANAGE_ROLES",
+    default_member_permissions = "MANAGE_GUILD",
     install_context = "Guild",
     interaction_context = "Guild"
 )]
-pub async fn license_info(
+pub(in crate::bot) async fn set_stats_opt_out(
     context: Context<'_>,
-    #[description = "Jinxxy license to query activations for"] license: String,
+    #[description = "exclude this server from global statistics?"] opt_out: bool,
 ) -> Result<(), Error> {
     context.defer_ephemeral().await?;
 
@@ -368,41 +510,28 @@ pub async fn license_info(
         .guild_id()
         .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
 
-    let reply = if let Some(api_key) = context.data().db.get_jinxxy_api_key(guild_id).await? {
-        let license_id = license_to_id(&api_key, &license).await?;
-        if let Some(license_id) = license_id {
-            // look up license usage info from local DB: this avoids doing some expensive Jinxxy API requests
-            let license_users = context
-                .data()
-                .db
-                .get_license_users(guild_id, license_id)
-                .await?;
-            let message = if license_users.is_empty() {
-                format!("`{}` is valid, but has no registered users.", license)
-            } else {
-                let mut message = format!("Users for `{}`:", license);
-                for user_id in license_users {
-                    if user_id == 0 {
-                        message.push_str("\n- **LOCKED** (prevents further use)");
-                    } else {
-                        message.push_str(format!("\n- <@{}>", user_id).as_str());
-                    }
-                }
-                message
-            };
-            success_reply("License Info", message)
-        } else {
-            error_reply("Error Getting License Info", format!("License `{}` not found: please verify that the key is correct and belongs to the Jinxxy account linked to this Discord server.", license))
-        }
+    context
+        .data()
+        .db
+        .set_stats_opt_out(guild_id, opt_out)
+        .await?;
+
+    let message = if opt_out {
+        Text::StatsOptedOut
     } else {
-        error_reply("Error Getting License Info", MISSING_API_KEY_MESSAGE)
+        Text::StatsOptedIn
     };
-    context.send(reply).await?;
+    let locale = i18n::command_locale(context).await?;
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::text(locale, message),
+        ))
+        .await?;
     Ok(())
 }
 
-// only requires MANAGE_ROLES permission because it can't emit license key info
-/// Lock a license, preventing it from being used to grant roles.
+/// Set whether users who rejoin this server get back the roles from licenses they activated
 #[poise::command(
     slash_command,
     guild_only,
@@ -410,9 +539,9 @@ pub async fn license_info(
     install_context = "Guild",
     interaction_context = "Guild"
 )]
-pub async fn lock_license(
+pub(in crate::bot) async fn set_restore_roles(
     context: Context<'_>,
-    #[description = "Jinxxy license to lock"] license: String,
+    #[description = "restore roles when a user rejoins?"] restore: bool,
 ) -> Result<(), Error> {
     context.defer_ephemeral().await?;
 
@@ -420,45 +549,38 @@ pub async fn lock_license(
         .guild_id()
         .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
 
-    let reply = if let Some(api_key) = context.data().db.get_jinxxy_api_key(guild_id).await? {
-        let license_id = license_to_id(&api_key, &license).await?;
-        if let Some(license_id) = license_id {
-            let activation_id =
-                jinxxy::create_license_activation(&api_key, &license_id, LOCKING_USER_ID).await?;
-            context
-                .data()
-                .db
-                .activate_license(guild_id, license_id, activation_id, LOCKING_USER_ID)
-                .await?;
-            success_reply(
-                "Success",
-                format!(
-                    "License `{}` is now locked and cannot be used to grant roles.",
-                    license
-                ),
-            )
-        } else {
-            error_reply("Error Locking License",format!("License `{}` not found: please verify that the key is correct and belongs to the Jinxxy account linked to this Discord server.", license))
-        }
+    context
+        .data()
+        .db
+        .set_restore_roles(guild_id, restore)
+        .await?;
+
+    let message = if restore {
+        Text::RestoreRolesEnabled
     } else {
-        error_reply("Error Locking License", MISSING_API_KEY_MESSAGE)
+        Text::RestoreRolesDisabled
     };
-    context.send(reply).await?;
+    let locale = i18n::command_locale(context).await?;
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::text(locale, message),
+        ))
+        .await?;
     Ok(())
 }
 
-// only requires MANAGE_ROLES permission because it can't emit license key info
-/// Unlock a license, allowing it to be used to grant roles.
+/// Set whether users with activated licenses leaving this server is logged to the bot log channel
 #[poise::command(
     slash_command,
     guild_only,
-    default_member_permissions = "MANAGE_ROLES",
+    default_member_permissions = "MANAGE_GUILD",
     install_context = "Guild",
     interaction_context = "Guild"
 )]
-pub async fn unlock_license(
+pub(in crate::bot) async fn set_log_member_leave(
     context: Context<'_>,
-    #[description = "Jinxxy license to unlock"] license: String,
+    #[description = "log when users with licenses leave?"] log: bool,
 ) -> Result<(), Error> {
     context.defer_ephemeral().await?;
 
@@ -466,258 +588,4932 @@ pub async fn unlock_license(
         .guild_id()
         .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
 
-    let reply = if let Some(api_key) = context.data().db.get_jinxxy_api_key(guild_id).await? {
-        let license_id = license_to_id(&api_key, &license).await?;
-        if let Some(license_id) = license_id {
-            let activations = jinxxy::get_license_activations(&api_key, &license_id).await?;
-            let lock_activation_id = activations
-                .into_iter()
-                .find(|activation| activation.is_lock())
-                .map(|activation| activation.id);
-
-            let message = if let Some(lock_activation_id) = lock_activation_id {
-                jinxxy::delete_license_activation(&api_key, &license_id, &lock_activation_id)
-                    .await?;
-                context
-                    .data()
-                    .db
-                    .deactivate_license(guild_id, license_id, lock_activation_id, LOCKING_USER_ID)
-                    .await?;
-                format!(
-                    "License `{}` is now unlocked and may be used to grant roles.",
-                    license
-                )
-            } else {
-                format!("License `{}` not found: please verify that the key is correct and belongs to the Jinxxy account linked to this Discord server.", license)
-            };
+    context
+        .data()
+        .db
+        .set_log_member_leave(guild_id, log)
+        .await?;
 
-            success_reply("Success", message)
-        } else {
-            error_reply("Error Unlocking License",format!("License `{}` not found: please verify that the key is correct and belongs to the Jinxxy account linked to this Discord server.", license))
-        }
+    let message = if log {
+        Text::LogMemberLeaveEnabled
     } else {
-        error_reply("Error Unlocking License", MISSING_API_KEY_MESSAGE)
+        Text::LogMemberLeaveDisabled
     };
-    context.send(reply).await?;
+    let locale = i18n::command_locale(context).await?;
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::text(locale, message),
+        ))
+        .await?;
     Ok(())
 }
 
-/// Initializes autocomplete data, and then does the product autocomplete
-async fn product_autocomplete(
-    context: Context<'_>,
-    product_prefix: &str,
-) -> impl Iterator<Item = String> {
-    match context
-        .data()
-        .api_cache
-        .product_names_with_prefix(&context, product_prefix)
-        .await
-    {
-        Ok(result) => result.into_iter(),
-        Err(e) => {
-            warn!("Failed to read API cache: {:?}", e);
-            Vec::new().into_iter()
-        }
-    }
-}
-
-/// Link a product to a role. Activating a license for the product will grant all linked roles.
+/// Set whether new, removed, and renamed products are logged to the bot log channel
 #[poise::command(
     slash_command,
     guild_only,
-    default_member_permissions = "MANAGE_ROLES",
+    default_member_permissions = "MANAGE_GUILD",
     install_context = "Guild",
     interaction_context = "Guild"
 )]
-pub(in crate::bot) async fn link_product(
+pub(in crate::bot) async fn set_log_product_changes(
     context: Context<'_>,
-    #[description = "Product to modify role links for"]
-    #[autocomplete = "product_autocomplete"]
-    product: String,
-    #[description = "Role to link"] role: RoleId, // note that Discord does not presently support variadic arguments: https://github.com/discord/discord-api-docs/discussions/3286
+    #[description = "log product changes?"] log: bool,
 ) -> Result<(), Error> {
     context.defer_ephemeral().await?;
 
-    let product_id = context
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+
+    context
         .data()
-        .api_cache
-        .product_name_to_id(&context, &product)
+        .db
+        .set_log_product_changes(guild_id, log)
         .await?;
 
-    let reply = if let Some(product_id) = product_id {
-        let guild_id = context
-            .guild_id()
-            .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
-        let assignable_roles = assignable_roles(&context, guild_id).await?;
-
-        let mut unassignable_roles: HashSet<RoleId, ahash::RandomState> =
-            HashSet::with_hasher(Default::default());
-        context
-            .data()
-            .db
-            .link_product(guild_id, product_id.clone(), role)
-            .await?;
-        if !assignable_roles.contains(&role) && !unassignable_roles.contains(&role) {
-            unassignable_roles.insert(role);
-        }
-
-        let roles = context.data().db.get_roles(guild_id, product_id).await?;
-        let mut message_lines = String::new();
-        for role in roles {
-            message_lines.push_str(format!("\n- <@&{}>", role.get()).as_str());
-        }
-
-        let embed = CreateEmbed::default()
-            .title("Product Link Successful")
-            .description(format!(
-                "{} will now grant the following roles:{}",
-                product, message_lines
-            ))
-            .color(Colour::DARK_GREEN);
-        let reply = CreateReply::default().embed(embed).ephemeral(true);
-        if let Some(embed) = create_role_warning_from_unassignable(unassignable_roles.into_iter()) {
-            reply.embed(embed)
-        } else {
-            reply
-        }
+    let message = if log {
+        Text::LogProductChangesEnabled
     } else {
-        error_reply("Error Linking Product", "Product not found.")
+        Text::LogProductChangesDisabled
     };
-
-    context.send(reply).await?;
+    let locale = i18n::command_locale(context).await?;
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::text(locale, message),
+        ))
+        .await?;
     Ok(())
 }
 
-/// Unlink a product from a role.
+/// Pause or resume license registration for this server's store, keeping its API key and links
 #[poise::command(
     slash_command,
     guild_only,
-    default_member_permissions = "MANAGE_ROLES",
+    default_member_permissions = "MANAGE_GUILD",
     install_context = "Guild",
     interaction_context = "Guild"
 )]
-pub(in crate::bot) async fn unlink_product(
+pub(in crate::bot) async fn pause_store(
     context: Context<'_>,
-    #[description = "Product to modify role links for"]
-    #[autocomplete = "product_autocomplete"]
-    product: String,
-    #[description = "Role to unlink"] role: RoleId, // note that Discord does not presently support variadic arguments: https://github.com/discord/discord-api-docs/discussions/3286
+    #[description = "pause registrations?"] paused: bool,
 ) -> Result<(), Error> {
     context.defer_ephemeral().await?;
 
-    let product_id = context
-        .data()
-        .api_cache
-        .product_name_to_id(&context, &product)
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+
+    let db = &context.data().db;
+    db.set_registrations_paused(guild_id, paused).await?;
+    let action = if paused {
+        AuditAction::PauseStore
+    } else {
+        AuditAction::ResumeStore
+    };
+    db.audit(
+        guild_id,
+        AuditLogEntry::new(action).actor(context.author().id),
+    )
+    .await?;
+
+    let message = if paused {
+        Text::StorePaused
+    } else {
+        Text::StoreResumed
+    };
+    let locale = i18n::command_locale(context).await?;
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::text(locale, message),
+        ))
         .await?;
+    Ok(())
+}
 
-    let reply = if let Some(product_id) = product_id {
-        let guild_id = context
-            .guild_id()
-            .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
-        let assignable_roles = assignable_roles(&context, guild_id).await?;
+/// Replace this server's Jinxxy API key with a new key for the same Jinxxy account
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn rotate_api_key(context: Context<'_>) -> Result<(), Error> {
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
 
+    let db = &context.data().db;
+    let locale = i18n::command_locale(context).await?;
+    let error_title = i18n::text(locale, Text::RotateApiKeyErrorTitle);
+    let Some(old_api_key) = db.get_jinxxy_api_key(guild_id).await? else {
         context
-            .data()
-            .db
-            .unlink_product(guild_id, product_id.clone(), role)
+            .send(error_reply(
+                error_title,
+                i18n::text(locale, Text::ApiKeyNotSet),
+            ))
             .await?;
-
-        let roles = context.data().db.get_roles(guild_id, product_id).await?;
-        let mut message_lines = String::new();
-        for role in &roles {
-            message_lines.push_str(format!("\n- <@&{}>", role.get()).as_str());
+        return Ok(());
+    };
+    // the new key is entered in a form so it stays out of the user's command history
+    let Some(api_key) = prompt_api_key(context).await? else {
+        return Ok(());
+    };
+    context.defer_ephemeral().await?;
+    let api_key = api_key.as_str();
+    if !is_jinxxy_api_key(api_key) {
+        context
+            .send(error_reply(
+                error_title,
+                i18n::text(locale, Text::InvalidApiKey),
+            ))
+            .await?;
+        return Ok(());
+    }
+    let api_key = SecretString::new(api_key.to_string());
+    let auth_user = match jinxxy::get_own_user(&api_key).await {
+        Ok(auth_user) => auth_user,
+        Err(e) => {
+            context
+                .send(error_reply(
+                    error_title,
+                    i18n::format(locale, Text::ApiKeyVerifyError, &[("error", &e)]),
+                ))
+                .await?;
+            return Ok(());
         }
+    };
+    let Some(new_user_id) = auth_user.id.clone() else {
+        context
+            .send(error_reply(
+                error_title,
+                i18n::text(locale, Text::RotateApiKeyNoAccount),
+            ))
+            .await?;
+        return Ok(());
+    };
 
-        let embed = CreateEmbed::default()
-            .title("Product Link Successful")
-            .description(format!(
-                "{} will now grant the following roles:{}",
-                product, message_lines
+    // Keys set before we started recording the account ID can still be checked against the old key, as long as it
+    // hasn't been revoked yet.
+    let old_user_id = match db.get_jinxxy_user_id(guild_id).await? {
+        Some(user_id) => Some(user_id),
+        None => jinxxy::get_own_user(&old_api_key)
+            .await
+            .ok()
+            .and_then(|old_user| old_user.id),
+    };
+    let Some(old_user_id) = old_user_id else {
+        context
+            .send(error_reply(
+                error_title,
+                i18n::text(locale, Text::RotateApiKeyUnknownAccount),
             ))
-            .color(Colour::DARK_GREEN);
-        let reply = CreateReply::default().embed(embed).ephemeral(true);
-        if let Some(embed) = create_role_warning_from_roles(&assignable_roles, roles.into_iter()) {
-            reply.embed(embed)
-        } else {
-            reply
-        }
-    } else {
-        error_reply("Error Unlinking Product", "Product not found.")
+            .await?;
+        return Ok(());
     };
+    if old_user_id != new_user_id {
+        context
+            .send(error_reply(
+                error_title,
+                i18n::text(locale, Text::RotateApiKeyDifferentAccount),
+            ))
+            .await?;
+        return Ok(());
+    }
+
+    let scopes_embed = api_key_scopes_embed(locale, &auth_user);
+    let display_name = auth_user.into_display_name().safe_display().to_string();
+    if !db
+        .rotate_jinxxy_api_key(guild_id, api_key, new_user_id)
+        .await?
+    {
+        // someone linked a different store while we were checking the key
+        context
+            .send(error_reply(
+                error_title,
+                i18n::text(locale, Text::RotateApiKeyStoreChanged),
+            ))
+            .await?;
+        return Ok(());
+    }
+    db.audit(
+        guild_id,
+        AuditLogEntry::new(AuditAction::SetApiKey)
+            .actor(context.author().id)
+            .detail(format!("rotated key for account {display_name}")),
+    )
+    .await?;
 
+    let reply = success_reply(
+        i18n::text(locale, Text::SuccessTitle),
+        i18n::format(locale, Text::ApiKeyRotated, &[("user", &display_name)]),
+    )
+    .embed(scopes_embed);
     context.send(reply).await?;
     Ok(())
 }
 
-/// List all product→role links
+/// Check that Jinx can grant every linked role and post in the bot log channel.
 #[poise::command(
     slash_command,
     guild_only,
-    default_member_permissions = "MANAGE_ROLES",
+    default_member_permissions = "MANAGE_GUILD",
     install_context = "Guild",
     interaction_context = "Guild"
 )]
-pub(in crate::bot) async fn list_links(context: Context<'_>) -> Result<(), Error> {
+pub(in crate::bot) async fn verify_setup(context: Context<'_>) -> Result<(), Error> {
     context.defer_ephemeral().await?;
 
     let guild_id = context
         .guild_id()
         .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
-
-    let assignable_roles = assignable_roles(&context, guild_id).await?;
-    let mut links = context.data().db.get_links(guild_id).await?;
-    let message = if links.is_empty() {
-        "No product→role links configured".to_string()
-    } else {
-        links.sort_unstable_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0))); // sort by role, then product
-        context
-            .data()
-            .api_cache
-            .get(&context, |cache| {
-                let mut message = String::new();
-                let mut current_role = None;
-
-                for (product_id, role) in &links {
-                    let product_name = cache
-                        .product_id_to_name(product_id)
-                        .map(|name| format!("\"{}\"", name))
-                        .unwrap_or_else(|| product_id.clone());
-                    if current_role != Some(role) {
-                        current_role = Some(role);
-                        if message.is_empty() {
-                            message.push_str(
-                                format!("- <@&{}> granted by {}", role.get(), product_name)
-                                    .as_str(),
-                            );
-                        } else {
-                            message.push_str(
-                                format!("\n- <@&{}> granted by {}", role.get(), product_name)
-                                    .as_str(),
-                            );
-                        }
-                    } else {
-                        message.push_str(format!(", {}", product_name).as_str());
-                    }
-                }
-                message
-            })
-            .await?
-    };
-    let unassignable_embed = create_role_warning_from_roles(
-        &assignable_roles,
-        links.iter().map(|(_product_id, role_id)| *role_id),
-    );
-    let embed = CreateEmbed::default()
-        .title("All product→role links")
-        .description(message);
-    let reply = CreateReply::default().embed(embed).ephemeral(true);
-    let reply = if let Some(embed) = unassignable_embed {
-        reply.embed(embed)
+    let locale = i18n::command_locale(context).await?;
+    let problems = verification::verify(
+        locale,
+        context.serenity_context(),
+        &context.data().db,
+        guild_id,
+    )
+    .await?;
+    let reply = if problems.is_empty() {
+        success_reply(
+            i18n::text(locale, Text::SetupVerifiedTitle),
+            i18n::text(locale, Text::SetupVerified),
+        )
     } else {
-        reply
+        error_reply(
+            i18n::text(locale, Text::SetupProblemsTitle),
+            verification::problems_message(locale, &problems),
+        )
     };
+    context.send(reply).await?;
+    Ok(())
+}
 
+/// Set (or unset) channel for bot to log to.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn set_log_channel(
+    context: Context<'_>,
+    #[description = "user to query licenses for"] channel: Option<ChannelId>, // we can't use Channel here because it throws FrameworkError::ArgumentParse on access problems, which cannot be handled cleanly.
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+
+    // the test message goes to the bot log, so it's in the guild's language rather than the user's
+    let log_locale = i18n::guild_locale(&context.data().db, guild_id, None).await?;
+    let locale = i18n::command_locale(context).await?;
+
+    // if setting a channel, then attempt to write a test log to the channel
+    let test_result = match channel {
+        Some(channel) => {
+            let embed = CreateEmbed::default()
+                .title(i18n::text(log_locale, Text::LogConfigurationChangedTitle))
+                .description(i18n::text(log_locale, Text::LogChannelSet));
+            let message = CreateMessage::default().embed(embed);
+            send_bot_log_message(context, channel, message)
+                .await
+                .map(|_| ())
+        }
+        None => Ok(()),
+    };
+
+    let reply = match test_result {
+        Ok(()) => {
+            // test log worked, so set the channel
+            context.data().db.set_log_channel(guild_id, channel).await?;
+
+            // let the user know what we just did
+            let message = if let Some(channel) = channel {
+                i18n::format(
+                    locale,
+                    Text::LogChannelSetTo,
+                    &[("channel", &format!("<#{}>", channel.get()))],
+                )
+            } else {
+                i18n::text(locale, Text::LogChannelUnset).to_string()
+            };
+            success_reply(i18n::text(locale, Text::SuccessTitle), message)
+        }
+        Err(e) => {
+            // test log failed, so let the user know
+            warn!("Error sending message to test log channel: {:?}", e);
+            error_reply(
+                i18n::text(locale, Text::LogChannelErrorTitle),
+                i18n::format(
+                    locale,
+                    Text::LogChannelNotSet,
+                    &[
+                        ("channel", &format!("<#{}>", channel.unwrap().get())),
+                        ("error", &e),
+                    ],
+                ),
+            )
+        }
+    };
+
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Set (or unset) channel to post new purchases in.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn set_sales_feed(
+    context: Context<'_>,
+    #[description = "channel to post new purchases in"] channel: Option<ChannelId>, // see set_log_channel for why this isn't a Channel
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+
+    // the test message is posted to the channel, so it's in the guild's language rather than the user's
+    let channel_locale = i18n::guild_locale(&context.data().db, guild_id, None).await?;
+    let locale = i18n::command_locale(context).await?;
+
+    // if setting a channel, then attempt to post a test message to the channel
+    let test_result = match channel {
+        Some(channel) => {
+            let embed = CreateEmbed::default()
+                .title(i18n::text(
+                    channel_locale,
+                    Text::LogConfigurationChangedTitle,
+                ))
+                .description(i18n::text(channel_locale, Text::SalesFeedChannelTest));
+            let message = CreateMessage::default().embed(embed);
+            send_bot_log_message(context, channel, message)
+                .await
+                .map(|_| ())
+        }
+        None => Ok(()),
+    };
+
+    let reply = match test_result {
+        Ok(()) => {
+            context
+                .data()
+                .db
+                .set_sales_feed_channel(guild_id, channel)
+                .await?;
+            if let Some(channel) = channel {
+                success_reply(
+                    i18n::text(locale, Text::SuccessTitle),
+                    i18n::format(
+                        locale,
+                        Text::SalesFeedChannelSet,
+                        &[("channel", &format!("<#{}>", channel.get()))],
+                    ),
+                )
+            } else {
+                success_reply(
+                    i18n::text(locale, Text::SuccessTitle),
+                    i18n::text(locale, Text::SalesFeedChannelUnset),
+                )
+            }
+        }
+        Err(e) => {
+            warn!("Error sending message to test sales feed channel: {:?}", e);
+            error_reply(
+                i18n::text(locale, Text::SalesFeedChannelErrorTitle),
+                i18n::format(
+                    locale,
+                    Text::SalesFeedChannelNotSet,
+                    &[
+                        ("channel", &format!("<#{}>", channel.unwrap().get())),
+                        ("error", &e),
+                    ],
+                ),
+            )
+        }
+    };
+
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Set (or unset) an HTTPS URL to receive signed JSON on every license activation and deactivation.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn set_event_webhook(
+    context: Context<'_>,
+    #[description = "HTTPS URL to send events to"] url: Option<String>,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+
+    let reply = match url {
+        Some(url) => match webhook::check_url(&url).await {
+            Ok(_) => {
+                let signing_secret = webhook::generate_signing_secret()?;
+                context
+                    .data()
+                    .db
+                    .set_event_webhook(guild_id, url, signing_secret.clone())
+                    .await?;
+                success_reply(
+                    i18n::text(locale, Text::SuccessTitle),
+                    webhook_secret_message(locale, Text::EventWebhookSet, &signing_secret),
+                )
+            }
+            Err(message) => error_reply(i18n::text(locale, Text::EventWebhookErrorTitle), message),
+        },
+        None => {
+            if context.data().db.delete_event_webhook(guild_id).await? {
+                success_reply(
+                    i18n::text(locale, Text::SuccessTitle),
+                    i18n::text(locale, Text::EventWebhookUnset),
+                )
+            } else {
+                error_reply(
+                    i18n::text(locale, Text::EventWebhookUnsetErrorTitle),
+                    i18n::text(locale, Text::EventWebhookMissing),
+                )
+            }
+        }
+    };
+
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Replace the event webhook's signing secret, in case the old one leaked.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn rotate_event_webhook_secret(context: Context<'_>) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+
+    let locale = i18n::command_locale(context).await?;
+    let signing_secret = webhook::generate_signing_secret()?;
+    let reply = if context
+        .data()
+        .db
+        .set_event_webhook_secret(guild_id, signing_secret.clone())
+        .await?
+    {
+        success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            webhook_secret_message(locale, Text::EventWebhookSecretRotated, &signing_secret),
+        )
+    } else {
+        error_reply(
+            i18n::text(locale, Text::EventWebhookSecretErrorTitle),
+            i18n::text(locale, Text::EventWebhookSecretMissing),
+        )
+    };
+
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Reply text showing a new webhook signing secret. This is the only time it's ever shown.
+fn webhook_secret_message(
+    locale: Option<&str>,
+    message: Text,
+    signing_secret: &SecretString,
+) -> String {
+    format!(
+        "{}\n\n{}",
+        i18n::text(locale, message),
+        webhook_signing_secret(locale, signing_secret)
+    )
+}
+
+/// Explanation of a new webhook signing secret, including the secret itself
+fn webhook_signing_secret(locale: Option<&str>, signing_secret: &SecretString) -> String {
+    i18n::format(
+        locale,
+        Text::WebhookSigningSecret,
+        &[
+            ("secret", &signing_secret.expose_secret()),
+            ("signature_header", &webhook::SIGNATURE_HEADER),
+            ("timestamp_header", &webhook::TIMESTAMP_HEADER),
+        ],
+    )
+}
+
+/// Set (or unset) channel to celebrate activation milestones in.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn set_milestone_channel(
+    context: Context<'_>,
+    #[description = "channel to post milestones in"] channel: Option<ChannelId>, // see set_log_channel for why this isn't a Channel
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+
+    // the test message is posted to the channel, so it's in the guild's language rather than the user's
+    let channel_locale = i18n::guild_locale(&context.data().db, guild_id, None).await?;
+    let locale = i18n::command_locale(context).await?;
+
+    // if setting a channel, then attempt to post a test message to the channel
+    let test_result = match channel {
+        Some(channel) => {
+            let embed = CreateEmbed::default()
+                .title(i18n::text(
+                    channel_locale,
+                    Text::LogConfigurationChangedTitle,
+                ))
+                .description(i18n::text(channel_locale, Text::MilestoneChannelTest));
+            let message = CreateMessage::default().embed(embed);
+            send_bot_log_message(context, channel, message)
+                .await
+                .map(|_| ())
+        }
+        None => Ok(()),
+    };
+
+    let reply = match test_result {
+        Ok(()) => {
+            context
+                .data()
+                .db
+                .set_milestone_channel(guild_id, channel)
+                .await?;
+            if let Some(channel) = channel {
+                // celebrate anything already reached before milestones were turned on
+                milestones::celebrate_guild(context, context.data(), guild_id).await?;
+                let product_counts = context
+                    .data()
+                    .db
+                    .get_product_activation_counts(guild_id)
+                    .await?;
+                for (product_id, product_name, activation_count) in product_counts {
+                    let product_name = product_name.unwrap_or_else(|| product_id.clone());
+                    milestones::celebrate_product(
+                        context,
+                        context.data(),
+                        guild_id,
+                        product_id,
+                        &product_name,
+                        activation_count,
+                    )
+                    .await?;
+                }
+                success_reply(
+                    i18n::text(locale, Text::SuccessTitle),
+                    i18n::format(
+                        locale,
+                        Text::MilestoneChannelSet,
+                        &[("channel", &format!("<#{}>", channel.get()))],
+                    ),
+                )
+            } else {
+                success_reply(
+                    i18n::text(locale, Text::SuccessTitle),
+                    i18n::text(locale, Text::MilestoneChannelUnset),
+                )
+            }
+        }
+        Err(e) => {
+            warn!("Error sending message to test milestone channel: {:?}", e);
+            error_reply(
+                i18n::text(locale, Text::MilestoneChannelErrorTitle),
+                i18n::format(
+                    locale,
+                    Text::MilestoneChannelNotSet,
+                    &[
+                        ("channel", &format!("<#{}>", channel.unwrap().get())),
+                        ("error", &e),
+                    ],
+                ),
+            )
+        }
+    };
+
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Build the post with a button to register product keys. `locale` should be the guild's, as everyone sees the post.
+pub(in crate::bot) fn registration_post(
+    locale: Option<&str>,
+    jinxxy_user: &jinxxy::DisplayUser,
+    template: &PostTemplate,
+    product_image_url: Option<String>,
+) -> CreateMessage {
+    let components = vec![CreateActionRow::Buttons(vec![registration_post_button(
+        locale, template,
+    )])];
+    let embed = registration_post_embed(locale, jinxxy_user, template, product_image_url);
+    CreateMessage::default().embed(embed).components(components)
+}
+
+/// Build the embed of a registration post. Anything the template doesn't customize gets the default.
+fn registration_post_embed(
+    locale: Option<&str>,
+    jinxxy_user: &jinxxy::DisplayUser,
+    template: &PostTemplate,
+    product_image_url: Option<String>,
+) -> CreateEmbed {
+    let title = template
+        .title
+        .clone()
+        .unwrap_or_else(|| i18n::text(locale, Text::RegistrationPostTitle).to_string());
+    let description = template.description.clone().unwrap_or_else(|| {
+        i18n::format(
+            locale,
+            Text::RegistrationPostDescription,
+            &[("creator", &jinxxy_user.display_name.safe_display())],
+        )
+    });
+    let embed = CreateEmbed::default().title(title).description(description);
+    let embed = if let Some(profile_image_url) = jinxxy_user.profile_image_url() {
+        embed.thumbnail(profile_image_url)
+    } else {
+        embed
+    };
+    let embed = if let Some(product_image_url) = product_image_url {
+        embed.image(product_image_url)
+    } else {
+        embed
+    };
+    if let Some(color) = template.color {
+        embed.color(Colour::new(color))
+    } else {
+        embed
+    }
+}
+
+/// Build the register button of a registration post
+fn registration_post_button(locale: Option<&str>, template: &PostTemplate) -> CreateButton {
+    CreateButton::new(REGISTER_BUTTON_ID)
+        .label(
+            template
+                .button_label
+                .as_deref()
+                .unwrap_or(i18n::text(locale, Text::Register)),
+        )
+        .style(ButtonStyle::Primary)
+}
+
+/// Look up the product art a registration post template shows, if any
+pub(in crate::bot) async fn post_template_image_url(
+    db: &JinxDb,
+    guild_id: GuildId,
+    template: &PostTemplate,
+) -> Result<Option<String>, Error> {
+    match &template.product_id {
+        Some(product_id) => Ok(db
+            .get_product_details(guild_id, product_id.clone())
+            .await?
+            .thumbnail_url),
+        None => Ok(None),
+    }
+}
+
+/// Apply the customization options of `/create_post` and `/update_post` to a template. Returns a message for the admin
+/// if an option is invalid.
+async fn customize_post_template(
+    context: Context<'_>,
+    template: &mut PostTemplate,
+    product: Option<String>,
+    title: Option<String>,
+    description: Option<String>,
+    button_label: Option<String>,
+    color: Option<String>,
+) -> Result<Option<Text>, Error> {
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    if let Some(title) = title {
+        template.title = Some(title);
+    }
+    if let Some(description) = description {
+        template.description = Some(description.replace("\\n", "\n"));
+    }
+    if let Some(button_label) = button_label {
+        template.button_label = Some(button_label);
+    }
+    if let Some(color) = color {
+        let Some(color) = parse_color(&color) else {
+            return Ok(Some(Text::InvalidColor));
+        };
+        template.color = Some(color.0);
+    }
+    if let Some(product) = product {
+        let Some(product_id) = context
+            .data()
+            .api_cache
+            .product_name_to_id(&context, &product)
+            .await?
+        else {
+            return Ok(Some(Text::ProductNotFound));
+        };
+        let thumbnail_url = context
+            .data()
+            .db
+            .get_product_details(guild_id, product_id.clone())
+            .await?
+            .thumbnail_url;
+        if thumbnail_url.is_none() {
+            return Ok(Some(Text::ProductArtMissing));
+        }
+        template.product_id = Some(product_id);
+    }
+    Ok(None)
+}
+
+/// Send a registration post to a channel, and save the template it was built from so later posts match it. Returns the
+/// reply to show the admin.
+async fn publish_registration_post(
+    context: Context<'_>,
+    locale: Option<&str>,
+    channel: ChannelId,
+    message: CreateMessage,
+    template: PostTemplate,
+) -> Result<CreateReply, Error> {
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let message = match channel.send_message(context, message).await {
+        Ok(message) => message,
+        Err(e) => {
+            warn!("Error in /create_post when sending message: {:?}", e);
+            return Ok(error_reply(
+                i18n::text(locale, Text::CreatePostErrorTitle),
+                i18n::text(locale, Text::PostSendError),
+            ));
+        }
+    };
+    let db = &context.data().db;
+    db.add_registration_post(guild_id, channel, message.id)
+        .await?;
+    db.set_post_template(guild_id, template).await?;
+    db.complete_onboarding_step(guild_id, OnboardingStep::FirstPost)
+        .await?;
+    Ok(success_reply(
+        i18n::text(locale, Text::SuccessTitle),
+        i18n::text(locale, Text::RegistrationPostCreated),
+    ))
+}
+
+/// Create post with buttons to register product keys
+#[poise::command(
+    slash_command,
+    custom_data = "CooldownTier::Cheap",
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+#[allow(clippy::too_many_arguments)]
+pub(in crate::bot) async fn create_post(
+    context: Context<'_>,
+    #[description = "show this product's art on the post"]
+    #[autocomplete = "product_autocomplete"]
+    product: Option<String>,
+    #[description = "post title"]
+    #[max_length = 256]
+    title: Option<String>,
+    #[description = "post text. Use \\n for a line break."]
+    #[max_length = 4000]
+    description: Option<String>,
+    #[description = "register button label"]
+    #[max_length = 80]
+    button_label: Option<String>,
+    #[description = "post color as a hex code, like #5865F2"] color: Option<String>,
+    #[description = "preview the post before publishing it"] preview: Option<bool>,
+    #[description = "start from the default post instead of your saved customizations"]
+    reset: Option<bool>,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let channel = context.channel_id();
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+    let post_locale = i18n::guild_locale(&context.data().db, guild_id, None).await?;
+    let error_title = i18n::text(locale, Text::CreatePostErrorTitle);
+
+    // options customize the saved template, which /update_post also uses
+    let mut template = if reset.unwrap_or(false) {
+        PostTemplate::default()
+    } else {
+        context.data().db.get_post_template(guild_id).await?
+    };
+    if let Some(problem) = customize_post_template(
+        context,
+        &mut template,
+        product,
+        title,
+        description,
+        button_label,
+        color,
+    )
+    .await?
+    {
+        context
+            .send(error_reply(error_title, i18n::text(locale, problem)))
+            .await?;
+        return Ok(());
+    }
+    let product_image_url =
+        post_template_image_url(&context.data().db, guild_id, &template).await?;
+
+    let api_key = context
+        .data()
+        .db
+        .get_jinxxy_api_key(guild_id)
+        .await?
+        .ok_or_else(|| JinxError::new("Jinxxy API key is not set"))?;
+    let jinxxy_user: jinxxy::DisplayUser = match jinxxy::get_own_user(&api_key).await {
+        Ok(jinxxy_user) => jinxxy_user.into(), // convert into just the data we need for this command
+        Err(e) => {
+            context
+                .send(error_reply(
+                    error_title,
+                    i18n::format(locale, Text::JinxxyUserError, &[("error", &e)]),
+                ))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if !preview.unwrap_or(false) {
+        let message = registration_post(post_locale, &jinxxy_user, &template, product_image_url);
+        let reply = publish_registration_post(context, locale, channel, message, template).await?;
+        context.send(reply).await?;
+        return Ok(());
+    }
+
+    // the preview's register button is disabled, so it can't be mistaken for the real thing
+    let components = vec![
+        CreateActionRow::Buttons(vec![
+            registration_post_button(post_locale, &template).disabled(true)
+        ]),
+        CreateActionRow::Buttons(vec![
+            CreateButton::new(CREATE_POST_PUBLISH_ID)
+                .label(i18n::text(locale, Text::Publish))
+                .style(ButtonStyle::Success),
+            CreateButton::new(CREATE_POST_CANCEL_ID)
+                .label(i18n::text(locale, Text::Cancel))
+                .style(ButtonStyle::Secondary),
+        ]),
+    ];
+    let preview_reply = CreateReply::default()
+        .content(i18n::text(locale, Text::PostPreview))
+        .embed(registration_post_embed(
+            post_locale,
+            &jinxxy_user,
+            &template,
+            product_image_url.clone(),
+        ))
+        .components(components)
+        .ephemeral(true);
+    let reply_handle = context.send(preview_reply).await?;
+    let interaction = reply_handle
+        .message()
+        .await?
+        .await_component_interaction(context.serenity_context())
+        .author_id(context.author().id)
+        .timeout(CREATE_POST_PREVIEW_TIMEOUT)
+        .await;
+    if let Some(interaction) = &interaction {
+        interaction
+            .create_response(context, CreateInteractionResponse::Acknowledge)
+            .await?;
+    }
+    let publish_pressed =
+        interaction.is_some_and(|interaction| interaction.data.custom_id == CREATE_POST_PUBLISH_ID);
+    let reply = if publish_pressed {
+        let message = registration_post(post_locale, &jinxxy_user, &template, product_image_url);
+        publish_registration_post(context, locale, channel, message, template).await?
+    } else {
+        error_reply(
+            i18n::text(locale, Text::CancelledTitle),
+            i18n::text(locale, Text::PostNotCreated),
+        )
+    };
+    reply_handle
+        .edit(context, reply.content("").components(vec![]))
+        .await?;
+    Ok(())
+}
+
+/// Update your registration posts to match your post customizations and Jinxxy profile
+#[poise::command(
+    slash_command,
+    custom_data = "CooldownTier::Expensive",
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+#[allow(clippy::too_many_arguments)]
+pub(in crate::bot) async fn update_post(
+    context: Context<'_>,
+    #[description = "show this product's art on the posts"]
+    #[autocomplete = "product_autocomplete"]
+    product: Option<String>,
+    #[description = "post title"]
+    #[max_length = 256]
+    title: Option<String>,
+    #[description = "post text. Use \\n for a line break."]
+    #[max_length = 4000]
+    description: Option<String>,
+    #[description = "register button label"]
+    #[max_length = 80]
+    button_label: Option<String>,
+    #[description = "post color as a hex code, like #5865F2"] color: Option<String>,
+    #[description = "go back to the default post instead of your saved customizations"]
+    reset: Option<bool>,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let db = &context.data().db;
+    let locale = i18n::command_locale(context).await?;
+    let post_locale = i18n::guild_locale(db, guild_id, None).await?;
+    let error_title = i18n::text(locale, Text::UpdatePostsErrorTitle);
+
+    let mut template = if reset.unwrap_or(false) {
+        PostTemplate::default()
+    } else {
+        db.get_post_template(guild_id).await?
+    };
+    if let Some(problem) = customize_post_template(
+        context,
+        &mut template,
+        product,
+        title,
+        description,
+        button_label,
+        color,
+    )
+    .await?
+    {
+        context
+            .send(error_reply(error_title, i18n::text(locale, problem)))
+            .await?;
+        return Ok(());
+    }
+
+    let api_key = db
+        .get_jinxxy_api_key(guild_id)
+        .await?
+        .ok_or_else(|| JinxError::new("Jinxxy API key is not set"))?;
+    let jinxxy_user: jinxxy::DisplayUser = match jinxxy::get_own_user(&api_key).await {
+        Ok(jinxxy_user) => jinxxy_user.into(),
+        Err(e) => {
+            context
+                .send(error_reply(
+                    error_title,
+                    i18n::format(locale, Text::JinxxyUserError, &[("error", &e)]),
+                ))
+                .await?;
+            return Ok(());
+        }
+    };
+    db.set_post_template(guild_id, template.clone()).await?;
+
+    let found_count = track_untracked_posts(context, guild_id, context.channel_id()).await?;
+    let posts = db.get_registration_posts(guild_id).await?;
+    if posts.is_empty() {
+        context
+            .send(error_reply(
+                error_title,
+                i18n::text(locale, Text::UpdatePostsNone),
+            ))
+            .await?;
+        return Ok(());
+    }
+
+    let product_image_url = post_template_image_url(db, guild_id, &template).await?;
+    let embed = registration_post_embed(post_locale, &jinxxy_user, &template, product_image_url);
+    let components = vec![CreateActionRow::Buttons(vec![registration_post_button(
+        post_locale,
+        &template,
+    )])];
+    let mut updated_count: usize = 0;
+    let mut removed_count: usize = 0;
+    let mut failures = String::new();
+    for (channel_id, message_id) in posts {
+        let edit = EditMessage::new()
+            .embed(embed.clone())
+            .components(components.clone());
+        match channel_id.edit_message(context, message_id, edit).await {
+            Ok(_) => updated_count += 1,
+            Err(serenity::Error::Http(serenity::HttpError::UnsuccessfulRequest(response)))
+                if response.status_code.as_u16() == 404 =>
+            {
+                // the post or its channel was deleted
+                db.delete_registration_post(guild_id, message_id).await?;
+                removed_count += 1;
+            }
+            Err(e) => {
+                warn!(
+                    "in {} error updating registration post {}: {:?}",
+                    guild_id.get(),
+                    message_id.get(),
+                    e
+                );
+                failures.push_str(
+                    format!(
+                        "\n- https://discord.com/channels/{}/{}/{}",
+                        guild_id.get(),
+                        channel_id.get(),
+                        message_id.get()
+                    )
+                    .as_str(),
+                );
+            }
+        }
+    }
+
+    let mut message = i18n::format(locale, Text::PostsUpdated, &[("count", &updated_count)]);
+    if found_count != 0 {
+        message.push(' ');
+        message.push_str(&i18n::format(
+            locale,
+            Text::PostsFound,
+            &[("count", &found_count)],
+        ));
+    }
+    if removed_count != 0 {
+        message.push(' ');
+        message.push_str(&i18n::format(
+            locale,
+            Text::PostsForgotten,
+            &[("count", &removed_count)],
+        ));
+    }
+    let reply = if failures.is_empty() {
+        success_reply(i18n::text(locale, Text::PostsUpdatedTitle), message)
+    } else {
+        message.push_str("\n\n");
+        message.push_str(i18n::text(locale, Text::PostsNotUpdated));
+        message.push_str(&failures);
+        error_reply(i18n::text(locale, Text::PostsNotUpdatedTitle), message)
+    };
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Start tracking registration posts in a channel that were made before posts were tracked. Returns how many were
+/// found. Only the most recent messages are checked.
+async fn track_untracked_posts(
+    context: Context<'_>,
+    guild_id: GuildId,
+    channel: ChannelId,
+) -> Result<usize, Error> {
+    let bot_id = context.framework().bot_id;
+    let messages = match channel
+        .messages(context, GetMessages::new().limit(100))
+        .await
+    {
+        Ok(messages) => messages,
+        Err(e) => {
+            // most likely we can't read this channel's history, which just means there's nothing to find
+            warn!(
+                "in {} error reading messages of {}: {:?}",
+                guild_id.get(),
+                channel.get(),
+                e
+            );
+            return Ok(0);
+        }
+    };
+    let tracked: HashSet<MessageId> = context
+        .data()
+        .db
+        .get_registration_posts(guild_id)
+        .await?
+        .into_iter()
+        .map(|(_, message_id)| message_id)
+        .collect();
+    let mut found_count = 0;
+    for message in messages {
+        let is_registration_post = message.author.id == bot_id
+            && message.components.iter().any(|row| {
+                row.components.iter().any(|component| {
+                    matches!(
+                        component,
+                        ActionRowComponent::Button(Button {
+                            data: ButtonKind::NonLink { custom_id, .. },
+                            ..
+                        }) if custom_id == REGISTER_BUTTON_ID
+                    )
+                })
+            });
+        if is_registration_post && !tracked.contains(&message.id) {
+            context
+                .data()
+                .db
+                .add_registration_post(guild_id, channel, message.id)
+                .await?;
+            found_count += 1;
+        }
+    }
+    Ok(found_count)
+}
+
+/// Create post with a button per linked product, each only accepting that product's license keys
+#[poise::command(
+    slash_command,
+    custom_data = "CooldownTier::Cheap",
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn create_claim_post(context: Context<'_>) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let channel = context.channel_id();
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+    let post_locale = i18n::guild_locale(&context.data().db, guild_id, None).await?;
+
+    let product_names: HashMap<String, String> = context
+        .data()
+        .api_cache
+        .products(&context)
+        .await?
+        .into_iter()
+        .collect();
+    // a product linked to several roles still only gets one button
+    let mut products: Vec<(String, String)> = context
+        .data()
+        .db
+        .get_links(guild_id)
+        .await?
+        .into_iter()
+        .map(|(product_id, _)| product_id)
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .filter_map(|product_id| {
+            let product_name = product_names.get(&product_id)?.clone();
+            Some((product_id, product_name))
+        })
+        .collect();
+    products.sort_unstable_by(|(_, a), (_, b)| a.cmp(b));
+
+    if products.is_empty() {
+        let reply = error_reply(
+            i18n::text(locale, Text::CreatePostErrorTitle),
+            i18n::text(locale, Text::ClaimPostNoLinks),
+        );
+        context.send(reply).await?;
+        return Ok(());
+    }
+
+    let messages = products
+        .chunks(CLAIM_POST_BUTTON_LIMIT)
+        .enumerate()
+        .map(|(index, products)| {
+            let components = products
+                .chunks(ACTION_ROW_BUTTON_LIMIT)
+                .map(|row| {
+                    CreateActionRow::Buttons(
+                        row.iter()
+                            .map(|(product_id, product_name)| {
+                                // Discord limits button labels to 80 characters
+                                let label: String =
+                                    product_name.chars().take(BUTTON_LABEL_LIMIT).collect();
+                                CreateButton::new(format!(
+                                    "{}{}",
+                                    CLAIM_BUTTON_ID_PREFIX, product_id
+                                ))
+                                .label(label)
+                                .style(ButtonStyle::Primary)
+                            })
+                            .collect(),
+                    )
+                })
+                .collect();
+            let message = CreateMessage::default().components(components);
+            // only the first message needs to explain what the buttons are for
+            if index == 0 {
+                let embed = CreateEmbed::default()
+                    .title(i18n::text(post_locale, Text::RegistrationPostTitle))
+                    .description(i18n::text(post_locale, Text::ClaimPostDescription));
+                message.embed(embed)
+            } else {
+                message
+            }
+        });
+
+    for message in messages {
+        if let Err(e) = channel.send_message(context, message).await {
+            warn!("Error in /create_claim_post when sending message: {:?}", e);
+            let reply = error_reply(
+                i18n::text(locale, Text::CreatePostErrorTitle),
+                i18n::text(locale, Text::PostSendError),
+            );
+            context.send(reply).await?;
+            return Ok(());
+        }
+    }
+    context
+        .data()
+        .db
+        .complete_onboarding_step(guild_id, OnboardingStep::FirstPost)
+        .await?;
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::text(locale, Text::ClaimPostCreated),
+        ))
+        .await?;
+    Ok(())
+}
+
+// requires MANAGE_GUILD permission because it can print license keys and a bunch of other customer information
+/// Query license information for a user
+#[poise::command(
+    context_menu_command = "List Jinxxy licenses",
+    slash_command,
+    custom_data = "CooldownTier::Expensive",
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub async fn user_info(
+    context: Context<'_>,
+    #[description = "user to query licenses for"] user: serenity::User,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+
+    let locale = i18n::command_locale(context).await?;
+    let Some(api_key) = context.data().db.get_jinxxy_api_key(guild_id).await? else {
+        context
+            .send(error_reply(
+                i18n::text(locale, Text::UserInfoErrorTitle),
+                i18n::text(locale, Text::ApiKeyNotSet),
+            ))
+            .await?;
+        return Ok(());
+    };
+    let db = &context.data().db;
+    let activations = db.get_user_license_states(guild_id, user.id.get()).await?;
+    if activations.is_empty() {
+        context
+            .send(success_reply(
+                i18n::text(locale, Text::UserInfoTitle),
+                i18n::format(
+                    locale,
+                    Text::UserInfoNoActivations,
+                    &[("user", &format!("<@{}>", user.id.get()))],
+                ),
+            ))
+            .await?;
+        return Ok(());
+    }
+
+    // license lines grouped by product name, and the licenses that grant each role
+    let mut products: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut role_sources: HashMap<RoleId, Vec<String>> = HashMap::new();
+    for (license_id, locked, created_unix_ms) in &activations {
+        let Some(license_info) = jinxxy::check_license_id(&api_key, license_id).await? else {
+            // we had a license ID in our local DB, but could not find info on it in the Jinxxy API
+            products
+                .entry(i18n::text(locale, Text::UnknownProduct).to_string())
+                .or_default()
+                .push(format!(
+                    "- ID=`{}` ({})",
+                    license_id,
+                    i18n::text(locale, Text::NoDataFound)
+                ));
+            continue;
+        };
+
+        // names come from the product cache where possible, falling back to what Jinxxy just told us
+        let cached_names = db
+            .get_product_version_name(
+                guild_id,
+                license_info.product_id.clone(),
+                license_info.product_version_id.clone(),
+            )
+            .await?;
+        let (product_name, mut product_version_name) = match cached_names {
+            Some((product_name, product_version_name)) => (product_name, product_version_name),
+            None => (license_info.product_name.clone(), None),
+        };
+        if let (None, Some(product_version_id)) =
+            (&product_version_name, &license_info.product_version_id)
+        {
+            let result = find_product_version(
+                db,
+                &api_key,
+                guild_id,
+                &license_info.product_id,
+                |version_id, _| version_id == product_version_id,
+            )
+            .await;
+            if let Err(e) = &result {
+                warn!(
+                    "Error looking up product info for {}, which is in license {}: {:?}",
+                    license_info.product_id, license_id, e
+                );
+            }
+            product_version_name = result.ok().flatten().map(|(_, version_name)| version_name);
+        }
+        let product_version_name = product_version_name
+            .map(|version_name| format!("\"{}\"", version_name.safe_display()))
+            .unwrap_or("`null`".to_string());
+
+        let username = if let Some(username) = &license_info.username {
+            masked_link(
+                username,
+                &license_info.profile_url().ok_or_else(|| {
+                    JinxError::new("expected profile_url to exist when username is set")
+                })?,
+            )
+        } else {
+            format!("`{}`", license_info.user_id)
+        };
+
+        products.entry(product_name).or_default().push(format!(
+            "- `{}` version={} activations={} locked={} user={} registered={}",
+            license_info.short_key,
+            product_version_name,
+            license_info.activations, // this field came from Jinxxy and is up to date
+            locked,                   // this field came from the local DB and may be out of sync
+            username,
+            registered_since(*created_unix_ms),
+        ));
+
+        if !locked {
+            let role_grants = db
+                .get_role_grants(
+                    guild_id,
+                    license_info.product_id.clone(),
+                    license_info.product_version_id.clone(),
+                )
+                .await?;
+            for role in role_grants {
+                role_sources
+                    .entry(role)
+                    .or_default()
+                    .push(format!("`{}`", license_info.short_key));
+            }
+        }
+    }
+
+    let mut embed = CreateEmbed::default()
+        .title(i18n::text(locale, Text::UserInfoTitle))
+        .description(i18n::format(
+            locale,
+            Text::UserInfo,
+            &[
+                ("user", &format!("<@{}>", user.id.get())),
+                ("licenses", &activations.len()),
+                ("products", &products.len()),
+            ],
+        ))
+        .color(Colour::DARK_GREEN);
+    for (product_name, lines) in products.iter().take(USER_INFO_PRODUCT_FIELD_LIMIT) {
+        embed = embed.field(
+            truncate_chars(&product_name.safe_display().to_string(), 256),
+            field_value(lines),
+            false,
+        );
+    }
+    if products.len() > USER_INFO_PRODUCT_FIELD_LIMIT {
+        embed = embed.field(
+            "…",
+            i18n::format(
+                locale,
+                Text::MoreProducts,
+                &[("count", &(products.len() - USER_INFO_PRODUCT_FIELD_LIMIT))],
+            ),
+            false,
+        );
+    }
+
+    // annotate the user's current licensed roles with the licenses that grant them, and point out any they're missing
+    let member = guild_id.member(&context, user.id).await.ok();
+    let mut role_lines = Vec::new();
+    if let Some(member) = member {
+        let mut licensed_roles: HashSet<RoleId> = db
+            .get_links(guild_id)
+            .await?
+            .into_iter()
+            .map(|(_, role)| role)
+            .collect();
+        licensed_roles.extend(db.get_blanket_role(guild_id).await?);
+        for role in &member.roles {
+            let mention = format!("<@&{}>", role.get());
+            if let Some(sources) = role_sources.get(role) {
+                let line = i18n::format(
+                    locale,
+                    Text::LicensedRoleSource,
+                    &[("role", &mention), ("licenses", &sources.join(", "))],
+                );
+                role_lines.push(format!("- {line}"));
+            } else if licensed_roles.contains(role) {
+                let line = i18n::format(locale, Text::LicensedRoleUnsourced, &[("role", &mention)]);
+                role_lines.push(format!("- {line}"));
+            }
+        }
+        for (role, sources) in &role_sources {
+            if !member.roles.contains(role) {
+                let line = i18n::format(
+                    locale,
+                    Text::LicensedRoleMissing,
+                    &[
+                        ("role", &format!("<@&{}>", role.get())),
+                        ("licenses", &sources.join(", ")),
+                    ],
+                );
+                role_lines.push(format!("- {line}"));
+            }
+        }
+    } else {
+        role_lines.push(format!("- {}", i18n::text(locale, Text::NotAServerMember)));
+    }
+    if role_lines.is_empty() {
+        role_lines.push(format!("- {}", i18n::text(locale, Text::None)));
+    }
+    embed = embed.field(
+        i18n::text(locale, Text::LicensedRolesField),
+        field_value(&role_lines),
+        false,
+    );
+
+    context
+        .send(
+            CreateReply::default()
+                .ephemeral(true)
+                .embed(embed)
+                .allowed_mentions(CreateAllowedMentions::new()),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Relative Discord timestamp for when an activation was made. Activations from before these were recorded, and not
+/// recoverable from the audit log, show as unknown.
+fn registered_since(created_unix_ms: Option<u64>) -> String {
+    match created_unix_ms {
+        Some(created_unix_ms) => format!("<t:{}:R>", created_unix_ms / 1000),
+        None => "`unknown`".to_string(),
+    }
+}
+
+/// Truncate a string to at most `max` characters
+fn truncate_chars(string: &str, max: usize) -> String {
+    if string.chars().count() <= max {
+        string.to_string()
+    } else {
+        let mut truncated: String = string.chars().take(max - 1).collect();
+        truncated.push('…');
+        truncated
+    }
+}
+
+/// Find recorded license activations by part of a license ID.
+#[poise::command(
+    slash_command,
+    custom_data = "CooldownTier::Expensive",
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn lookup_license(
+    context: Context<'_>,
+    #[description = "part of the license ID to search for"]
+    #[min_length = 4]
+    query: String,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+
+    let locale = i18n::command_locale(context).await?;
+    let query = query.trim().to_string();
+    let matches = context
+        .data()
+        .db
+        .search_license_activations(guild_id, query.clone(), LICENSE_LOOKUP_LIMIT + 1)
+        .await?;
+    if matches.is_empty() {
+        context
+            .send(error_reply(
+                i18n::text(locale, Text::LicenseLookupTitle),
+                i18n::format(
+                    locale,
+                    Text::LicenseLookupEmpty,
+                    &[("query", &query.safe_display())],
+                ),
+            ))
+            .await?;
+        return Ok(());
+    }
+
+    // the key and product only live in Jinxxy, so they're filled in if we can reach it
+    let api_key = context.data().db.get_jinxxy_api_key(guild_id).await?;
+    let mut message = i18n::format(
+        locale,
+        Text::LicenseLookup,
+        &[("query", &query.safe_display())],
+    );
+    for (license_id, user_id) in matches.iter().take(LICENSE_LOOKUP_LIMIT as usize) {
+        let license_info = match &api_key {
+            Some(api_key) => jinxxy::check_license_id(api_key, license_id).await?,
+            None => None,
+        };
+        let detail = match license_info {
+            Some(license_info) => format!(
+                " key=`{}` product=\"{}\"",
+                license_info.short_key,
+                license_info.product_name.safe_display()
+            ),
+            None => String::new(),
+        };
+        message.push_str(
+            format!(
+                "\n- ID=`{}` user=<@{}>{}",
+                license_id,
+                user_id.get(),
+                detail
+            )
+            .as_str(),
+        );
+    }
+    if matches.len() > LICENSE_LOOKUP_LIMIT as usize {
+        message.push('\n');
+        message.push_str(i18n::text(locale, Text::LicenseLookupMore));
+    }
+
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::LicenseLookupTitle),
+            message,
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Deactivate a license. Does not revoke any granted roles.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub async fn deactivate_license(
+    context: Context<'_>,
+    #[description = "user to deactivate license for"] user: serenity::User,
+    #[description = "Jinxxy license to deactivate for user"] license: String,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+    let error_title = i18n::text(locale, Text::DeactivateLicenseErrorTitle);
+
+    let reply = if let Some(api_key) = context.data().db.get_jinxxy_api_key(guild_id).await? {
+        let license_id = license_to_id(&api_key, &license).await?;
+        if let Some(license_id) = license_id {
+            let activations = context
+                .data()
+                .db
+                .get_user_license_activations(guild_id, user.id.get(), license_id.clone())
+                .await?;
+            for activation_id in activations {
+                let license_id = license_id.clone();
+                jinxxy::delete_license_activation(&api_key, &license_id, &activation_id).await?;
+                context
+                    .data()
+                    .db
+                    .deactivate_license(guild_id, license_id, activation_id, user.id.get())
+                    .await?;
+            }
+            context
+                .data()
+                .db
+                .audit(
+                    guild_id,
+                    AuditLogEntry::new(AuditAction::Deactivate)
+                        .actor(context.author().id)
+                        .user(user.id)
+                        .license(license_id),
+                )
+                .await?;
+            success_reply(
+                i18n::text(locale, Text::SuccessTitle),
+                i18n::format(
+                    locale,
+                    Text::LicenseDeactivated,
+                    &[
+                        ("user", &format!("<@{}>", user.id.get())),
+                        ("license", &license),
+                    ],
+                ),
+            )
+        } else {
+            error_reply(
+                error_title,
+                i18n::format(locale, Text::LicenseNotFound, &[("license", &license)]),
+            )
+        }
+    } else {
+        error_reply(error_title, i18n::text(locale, Text::ApiKeyNotSet))
+    };
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Transfer a license activation from one user to another, moving any roles it granted.
+#[poise::command(
+    slash_command,
+    custom_data = "CooldownTier::Expensive",
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub async fn transfer_license(
+    context: Context<'_>,
+    #[description = "user currently holding the license"] from_user: serenity::User,
+    #[description = "user to transfer the license to"] to_user: serenity::User,
+    #[description = "Jinxxy license to transfer"] license: String,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+
+    let locale = i18n::command_locale(context).await?;
+    let error_title = i18n::text(locale, Text::TransferLicenseErrorTitle);
+    let Some(api_key) = context.data().db.get_jinxxy_api_key(guild_id).await? else {
+        context
+            .send(error_reply(
+                error_title,
+                i18n::text(locale, Text::ApiKeyNotSet),
+            ))
+            .await?;
+        return Ok(());
+    };
+    let license_info = match license_to_id(&api_key, &license).await? {
+        Some(license_id) => jinxxy::check_license_id(&api_key, &license_id).await?,
+        None => None,
+    };
+    let Some(license_info) = license_info else {
+        context
+            .send(error_reply(
+                error_title,
+                i18n::format(locale, Text::LicenseNotFound, &[("license", &license)]),
+            ))
+            .await?;
+        return Ok(());
+    };
+    let license_id = license_info.license_id;
+    let product_id = license_info.product_id;
+
+    if context
+        .data()
+        .db
+        .is_license_locked(guild_id, license_id.clone())
+        .await?
+    {
+        context
+            .send(error_reply(
+                error_title,
+                i18n::format(
+                    locale,
+                    Text::TransferLicenseLocked,
+                    &[("license", &license)],
+                ),
+            ))
+            .await?;
+        return Ok(());
+    }
+
+    let activations = context
+        .data()
+        .db
+        .get_user_license_activations(guild_id, from_user.id.get(), license_id.clone())
+        .await?;
+    if activations.is_empty() {
+        context
+            .send(error_reply(
+                error_title,
+                i18n::format(
+                    locale,
+                    Text::TransferLicenseNotActivated,
+                    &[
+                        ("user", &format!("<@{}>", from_user.id.get())),
+                        ("license", &license),
+                    ],
+                ),
+            ))
+            .await?;
+        return Ok(());
+    }
+
+    // move the activation upstream and locally
+    for activation_id in activations {
+        jinxxy::delete_license_activation(&api_key, &license_id, &activation_id).await?;
+        context
+            .data()
+            .db
+            .deactivate_license(
+                guild_id,
+                license_id.clone(),
+                activation_id,
+                from_user.id.get(),
+            )
+            .await?;
+    }
+    let new_activation_id =
+        jinxxy::create_license_activation(&api_key, &license_id, to_user.id.get()).await?;
+    context
+        .data()
+        .db
+        .activate_license(
+            guild_id,
+            license_id.clone(),
+            new_activation_id,
+            to_user.id.get(),
+        )
+        .await?;
+
+    // figure out which roles the old user is still entitled to via their other licenses, so we don't take those away
+    let mut retained_roles: HashSet<RoleId, ahash::RandomState> = Default::default();
+    for other_license_id in context
+        .data()
+        .db
+        .get_user_licenses(guild_id, from_user.id.get())
+        .await?
+    {
+        if let Some(other_license) = jinxxy::check_license_id(&api_key, &other_license_id).await? {
+            retained_roles.extend(
+                context
+                    .data()
+                    .db
+                    .get_role_grants(
+                        guild_id,
+                        other_license.product_id,
+                        other_license.product_version_id,
+                    )
+                    .await?,
+            );
+        }
+    }
+
+    let roles = context
+        .data()
+        .db
+        .get_role_grants(
+            guild_id,
+            product_id.clone(),
+            license_info.product_version_id,
+        )
+        .await?;
+    context
+        .data()
+        .db
+        .audit(
+            guild_id,
+            AuditLogEntry::new(AuditAction::Transfer)
+                .actor(context.author().id)
+                .user(to_user.id)
+                .product(product_id.clone())
+                .license(license_id.clone())
+                .detail(format!("from <@{}>", from_user.id.get())),
+        )
+        .await?;
+    let mut errors = String::new();
+    let mut role_lines = String::new();
+    let from_member = guild_id.member(context, from_user.id).await.ok();
+    let to_member = guild_id.member(context, to_user.id).await?;
+    for role in roles {
+        role_lines.push_str(format!("\n- <@&{}>", role.get()).as_str());
+        if let Some(from_member) = &from_member {
+            if !retained_roles.contains(&role) {
+                if let Err(e) = from_member.remove_role(context, role).await {
+                    warn!("in {} error revoking role: {:?}", guild_id.get(), e);
+                    let error = i18n::format(
+                        locale,
+                        Text::RoleRevokeFailed,
+                        &[("role", &format!("<@&{}>", role.get()))],
+                    );
+                    errors.push_str(format!("\n- {error}").as_str());
+                } else {
+                    context
+                        .data()
+                        .db
+                        .audit(
+                            guild_id,
+                            AuditLogEntry::new(AuditAction::RoleRevoke)
+                                .actor(context.author().id)
+                                .user(from_user.id)
+                                .product(product_id.clone())
+                                .role(role)
+                                .license(license_id.clone()),
+                        )
+                        .await?;
+                }
+            }
+        }
+        if let Err(e) = to_member.add_role(context, role).await {
+            warn!("in {} error granting role: {:?}", guild_id.get(), e);
+            let error = i18n::format(
+                locale,
+                Text::RoleGrantFailed,
+                &[("role", &format!("<@&{}>", role.get()))],
+            );
+            errors.push_str(format!("\n- {error}").as_str());
+        } else {
+            context
+                .data()
+                .db
+                .audit(
+                    guild_id,
+                    AuditLogEntry::new(AuditAction::RoleGrant)
+                        .actor(context.author().id)
+                        .user(to_user.id)
+                        .product(product_id.clone())
+                        .role(role)
+                        .license(license_id.clone()),
+                )
+                .await?;
+        }
+    }
+
+    let from = format!("<@{}>", from_user.id.get());
+    let to = format!("<@{}>", to_user.id.get());
+    let message = i18n::format(
+        locale,
+        Text::LicenseTransferred,
+        &[
+            ("license", &license),
+            ("product", &license_info.product_name.safe_display()),
+            ("from", &from),
+            ("to", &to),
+        ],
+    ) + role_lines.as_str();
+
+    // log the transfer, omitting the license key itself
+    if let Some(log_channel) = context.data().db.get_log_channel(guild_id).await? {
+        let log_locale = i18n::guild_locale(&context.data().db, guild_id, None).await?;
+        let log_message = i18n::format(
+            log_locale,
+            Text::LogLicenseTransferred,
+            &[
+                ("user", &format!("<@{}>", context.author().id.get())),
+                ("product", &license_info.product_name.safe_display()),
+                ("from", &from),
+                ("to", &to),
+            ],
+        );
+        let embed = CreateEmbed::default()
+            .title(i18n::text(log_locale, Text::LogLicenseTransferredTitle))
+            .description(log_message)
+            .color(Colour::DARK_GREEN);
+        send_bot_log_message(context, log_channel, CreateMessage::default().embed(embed)).await?;
+    }
+
+    let reply = if errors.is_empty() {
+        success_reply(i18n::text(locale, Text::SuccessTitle), message)
+    } else {
+        error_reply(
+            i18n::text(locale, Text::LicenseTransferredWithErrorsTitle),
+            format!(
+                "{}\n\n{}{}",
+                message,
+                i18n::text(locale, Text::RolesNotUpdated),
+                errors
+            ),
+        )
+    };
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Maximum number of rows in a single license import. Each row takes several Jinxxy API calls, and the whole import has
+/// to finish before the interaction expires.
+const MAX_IMPORT_ROWS: usize = 200;
+
+/// Maximum number of skipped rows to list in the import summary
+const MAX_IMPORT_SKIPS_SHOWN: usize = 20;
+
+/// What happened to a single row of a license import
+enum ImportOutcome {
+    /// An activation was created for the user
+    Activated { granted_roles: usize },
+    /// The user had already activated the license. Their roles are still granted, in case they're missing.
+    AlreadyActivated { granted_roles: usize },
+    /// The row was not imported, for the given reason
+    Skipped(Text),
+}
+
+/// Import license activations from another bot, granting roles without making users re-register
+#[poise::command(
+    slash_command,
+    custom_data = "CooldownTier::Expensive",
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn import_licenses(
+    context: Context<'_>,
+    #[description = "CSV file with a license column and a Discord user ID column"]
+    file: serenity::Attachment,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+    let error_title = i18n::text(locale, Text::ImportLicensesErrorTitle);
+    let Some(api_key) = context.data().db.get_jinxxy_api_key(guild_id).await? else {
+        context
+            .send(error_reply(
+                error_title,
+                i18n::text(locale, Text::ApiKeyNotSet),
+            ))
+            .await?;
+        return Ok(());
+    };
+    let rows = match String::from_utf8(file.download().await?) {
+        Ok(csv) => license_import::parse(&csv),
+        Err(_) => {
+            context
+                .send(error_reply(
+                    error_title,
+                    i18n::text(locale, Text::FileNotUtf8),
+                ))
+                .await?;
+            return Ok(());
+        }
+    };
+    let rows = match rows {
+        Ok(rows) if rows.len() > MAX_IMPORT_ROWS => {
+            context
+                .send(error_reply(
+                    error_title,
+                    i18n::format(
+                        locale,
+                        Text::ImportTooManyRows,
+                        &[("rows", &rows.len()), ("limit", &MAX_IMPORT_ROWS)],
+                    ),
+                ))
+                .await?;
+            return Ok(());
+        }
+        Ok(rows) => rows,
+        Err(e) => {
+            context
+                .send(error_reply(
+                    error_title,
+                    i18n::format(locale, Text::ImportInvalidFile, &[("error", &e)]),
+                ))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let mut activated_count: usize = 0;
+    let mut already_activated_count: usize = 0;
+    let mut granted_role_count: usize = 0;
+    let mut skips = Vec::new();
+    for row in &rows {
+        match import_license(context, &api_key, guild_id, row).await? {
+            ImportOutcome::Activated { granted_roles } => {
+                activated_count += 1;
+                granted_role_count += granted_roles;
+            }
+            ImportOutcome::AlreadyActivated { granted_roles } => {
+                already_activated_count += 1;
+                granted_role_count += granted_roles;
+            }
+            ImportOutcome::Skipped(reason) => skips.push((row.line, reason)),
+        }
+    }
+
+    let mut message = i18n::format(
+        locale,
+        Text::LicensesImported,
+        &[
+            ("activated", &activated_count),
+            ("already_activated", &already_activated_count),
+            ("roles", &granted_role_count),
+        ],
+    );
+    if !skips.is_empty() {
+        message.push_str("\n\n");
+        message.push_str(&i18n::format(
+            locale,
+            Text::ImportSkipped,
+            &[("count", &skips.len())],
+        ));
+        for (line, reason) in skips.iter().take(MAX_IMPORT_SKIPS_SHOWN) {
+            let skip = i18n::format(
+                locale,
+                Text::ImportSkippedLine,
+                &[("line", line), ("reason", &i18n::text(locale, *reason))],
+            );
+            message.push_str(format!("\n- {skip}").as_str());
+        }
+        if skips.len() > MAX_IMPORT_SKIPS_SHOWN {
+            let more = i18n::format(
+                locale,
+                Text::AndMore,
+                &[("count", &(skips.len() - MAX_IMPORT_SKIPS_SHOWN))],
+            );
+            message.push_str(format!("\n- {more}").as_str());
+        }
+    }
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::LicensesImportedTitle),
+            message,
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Import a single license activation. This follows the same seat and lock rules as a user registering the license
+/// themselves.
+async fn import_license(
+    context: Context<'_>,
+    api_key: &SecretString,
+    guild_id: GuildId,
+    row: &ImportRow,
+) -> Result<ImportOutcome, Error> {
+    let license_type = license::identify_license(&row.license);
+    let Some(license) = license_type.create_trusted_jinxxy_license(&row.license) else {
+        return Ok(ImportOutcome::Skipped(Text::ImportNotJinxxy));
+    };
+    let Some(license_info) = jinxxy::check_license(api_key, license).await? else {
+        return Ok(ImportOutcome::Skipped(Text::ImportLicenseNotFound));
+    };
+    let activations = if license_info.activations == 0 {
+        Vec::new()
+    } else {
+        jinxxy::get_license_activations(api_key, &license_info.license_id).await?
+    };
+    let max_activations = context
+        .data()
+        .db
+        .get_max_activations(guild_id, license_info.product_id.clone())
+        .await?;
+    let validation = license::validate_jinxxy_license_activation(row.user_id, &activations);
+    if validation.locked {
+        return Ok(ImportOutcome::Skipped(Text::ImportLicenseLocked));
+    } else if validation.blocked(max_activations) {
+        return Ok(ImportOutcome::Skipped(Text::ImportLicenseUsed));
+    }
+
+    let already_activated = validation.own_user;
+    let activation_id = if already_activated {
+        // make sure the existing activation is also recorded locally
+        activations
+            .into_iter()
+            .find(|activation| activation.try_into_user_id() == Some(row.user_id.get()))
+            .map(|activation| activation.id)
+    } else {
+        Some(
+            jinxxy::create_license_activation(api_key, &license_info.license_id, row.user_id.get())
+                .await?,
+        )
+    };
+    if let Some(activation_id) = activation_id {
+        context
+            .data()
+            .db
+            .activate_license(
+                guild_id,
+                license_info.license_id.clone(),
+                activation_id,
+                row.user_id.get(),
+            )
+            .await?;
+    }
+    if !already_activated {
+        // milestones aren't posted here, so an import doesn't flood the channel. They're posted on the next registration.
+        context
+            .data()
+            .db
+            .increment_product_activation_count(guild_id, license_info.product_id.clone())
+            .await?;
+    }
+
+    let mut granted_roles: usize = 0;
+    // users who have left can still be imported: they'll get their roles when they next register or rejoin
+    if let Ok(member) = guild_id.member(context, row.user_id).await {
+        let roles = context
+            .data()
+            .db
+            .get_role_grants(
+                guild_id,
+                license_info.product_id.clone(),
+                license_info.product_version_id,
+            )
+            .await?;
+        for role in roles {
+            if let Err(e) = member.add_role(context, role).await {
+                warn!("in {} error granting role: {:?}", guild_id.get(), e);
+            } else {
+                granted_roles += 1;
+                context
+                    .data()
+                    .db
+                    .audit(
+                        guild_id,
+                        AuditLogEntry::new(AuditAction::RoleGrant)
+                            .actor(context.author().id)
+                            .user(row.user_id)
+                            .product(license_info.product_id.clone())
+                            .role(role)
+                            .license(license_info.license_id.clone())
+                            .detail("imported"),
+                    )
+                    .await?;
+            }
+        }
+    }
+
+    if already_activated {
+        Ok(ImportOutcome::AlreadyActivated { granted_roles })
+    } else {
+        Ok(ImportOutcome::Activated { granted_roles })
+    }
+}
+
+// only requires MANAGE_ROLES permission because it can't emit license key info
+/// Query activation information for a license
+#[poise::command(
+    slash_command,
+    custom_data = "CooldownTier::Expensive",
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub async fn license_info(
+    context: Context<'_>,
+    #[description = "Jinxxy license to query activations for"] license: String,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+    let error_title = i18n::text(locale, Text::LicenseInfoErrorTitle);
+
+    let reply = if let Some(api_key) = context.data().db.get_jinxxy_api_key(guild_id).await? {
+        let license_id = license_to_id(&api_key, &license).await?;
+        if let Some(license_id) = license_id {
+            // look up license usage info from local DB: this avoids doing some expensive Jinxxy API requests
+            let license_users = context
+                .data()
+                .db
+                .get_license_users(guild_id, license_id)
+                .await?;
+            let message = if license_users.is_empty() {
+                i18n::format(locale, Text::LicenseNoUsers, &[("license", &license)])
+            } else {
+                let mut message =
+                    i18n::format(locale, Text::LicenseUsers, &[("license", &license)]);
+                for (user_id, created_unix_ms) in license_users {
+                    let line = if user_id == 0 {
+                        i18n::text(locale, Text::LicenseLockedLine).to_string()
+                    } else {
+                        i18n::format(
+                            locale,
+                            Text::LicenseUserRegistered,
+                            &[
+                                ("user", &format!("<@{}>", user_id)),
+                                ("time", &registered_since(created_unix_ms)),
+                            ],
+                        )
+                    };
+                    message.push_str(format!("\n- {line}").as_str());
+                }
+                message
+            };
+            success_reply(i18n::text(locale, Text::LicenseInfoTitle), message)
+        } else {
+            error_reply(
+                error_title,
+                i18n::format(locale, Text::LicenseNotFound, &[("license", &license)]),
+            )
+        }
+    } else {
+        error_reply(error_title, i18n::text(locale, Text::ApiKeyNotSet))
+    };
+    context.send(reply).await?;
+    Ok(())
+}
+
+// only requires MANAGE_ROLES permission because it can't emit license key info
+/// Lock a license, preventing it from being used to grant roles.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub async fn lock_license(
+    context: Context<'_>,
+    #[description = "Jinxxy license to lock"] license: String,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+    let error_title = i18n::text(locale, Text::LockLicenseErrorTitle);
+
+    let reply = if let Some(api_key) = context.data().db.get_jinxxy_api_key(guild_id).await? {
+        let license_id = license_to_id(&api_key, &license).await?;
+        if let Some(license_id) = license_id {
+            let activation_id =
+                jinxxy::create_license_activation(&api_key, &license_id, LOCKING_USER_ID).await?;
+            context
+                .data()
+                .db
+                .activate_license(guild_id, license_id.clone(), activation_id, LOCKING_USER_ID)
+                .await?;
+            context
+                .data()
+                .db
+                .audit(
+                    guild_id,
+                    AuditLogEntry::new(AuditAction::Lock)
+                        .actor(context.author().id)
+                        .license(license_id),
+                )
+                .await?;
+            success_reply(
+                i18n::text(locale, Text::SuccessTitle),
+                i18n::format(locale, Text::LicenseLocked, &[("license", &license)]),
+            )
+        } else {
+            error_reply(
+                error_title,
+                i18n::format(locale, Text::LicenseNotFound, &[("license", &license)]),
+            )
+        }
+    } else {
+        error_reply(error_title, i18n::text(locale, Text::ApiKeyNotSet))
+    };
+    context.send(reply).await?;
+    Ok(())
+}
+
+// only requires MANAGE_ROLES permission because it can't emit license key info
+/// Unlock a license, allowing it to be used to grant roles.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub async fn unlock_license(
+    context: Context<'_>,
+    #[description = "Jinxxy license to unlock"] license: String,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+    let error_title = i18n::text(locale, Text::UnlockLicenseErrorTitle);
+
+    let reply = if let Some(api_key) = context.data().db.get_jinxxy_api_key(guild_id).await? {
+        let license_id = license_to_id(&api_key, &license).await?;
+        if let Some(license_id) = license_id {
+            let activations = jinxxy::get_license_activations(&api_key, &license_id).await?;
+            let lock_activation_id = activations
+                .into_iter()
+                .find(|activation| activation.is_lock())
+                .map(|activation| activation.id);
+
+            let message = if let Some(lock_activation_id) = lock_activation_id {
+                jinxxy::delete_license_activation(&api_key, &license_id, &lock_activation_id)
+                    .await?;
+                context
+                    .data()
+                    .db
+                    .deactivate_license(
+                        guild_id,
+                        license_id.clone(),
+                        lock_activation_id,
+                        LOCKING_USER_ID,
+                    )
+                    .await?;
+                context
+                    .data()
+                    .db
+                    .audit(
+                        guild_id,
+                        AuditLogEntry::new(AuditAction::Unlock)
+                            .actor(context.author().id)
+                            .license(license_id),
+                    )
+                    .await?;
+                i18n::format(locale, Text::LicenseUnlocked, &[("license", &license)])
+            } else {
+                i18n::format(locale, Text::LicenseNotFound, &[("license", &license)])
+            };
+
+            success_reply(i18n::text(locale, Text::SuccessTitle), message)
+        } else {
+            error_reply(
+                error_title,
+                i18n::format(locale, Text::LicenseNotFound, &[("license", &license)]),
+            )
+        }
+    } else {
+        error_reply(error_title, i18n::text(locale, Text::ApiKeyNotSet))
+    };
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Number of audit log entries shown per page
+const AUDIT_LOG_PAGE_SIZE: u64 = 10;
+
+/// Query the history of role grants, link changes, and other administrative actions
+#[poise::command(
+    slash_command,
+    custom_data = "CooldownTier::Expensive",
+    guild_only,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn audit_log(
+    context: Context<'_>,
+    #[description = "only show actions by or on this user"] user: Option<serenity::User>,
+    #[description = "only show actions involving this product"]
+    #[autocomplete = "product_autocomplete"]
+    product: Option<String>,
+    #[description = "only show this kind of action"] action: Option<AuditAction>,
+    #[description = "only show actions from the last this many days"]
+    #[min = 1]
+    days: Option<u32>,
+    #[description = "page number"]
+    #[min = 1]
+    page: Option<u32>,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+
+    let product_id = if let Some(product) = &product {
+        let product_id = context
+            .data()
+            .api_cache
+            .product_name_to_id(&context, product)
+            .await?;
+        if product_id.is_none() {
+            context
+                .send(error_reply(
+                    i18n::text(locale, Text::AuditLogErrorTitle),
+                    i18n::text(locale, Text::ProductNotFound),
+                ))
+                .await?;
+            return Ok(());
+        }
+        product_id
+    } else {
+        None
+    };
+
+    let since_unix_ms = days.map(|days| {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        now.saturating_sub(u64::from(days) * 24 * 60 * 60 * 1000)
+    });
+    let filter = AuditLogFilter {
+        user: user.map(|user| user.id),
+        product_id,
+        action,
+        license_id: None,
+        since_unix_ms,
+    };
+    let page = u64::from(page.unwrap_or(1));
+    let (entries, total) = context
+        .data()
+        .db
+        .get_audit_log(
+            guild_id,
+            filter,
+            (page - 1) * AUDIT_LOG_PAGE_SIZE,
+            AUDIT_LOG_PAGE_SIZE,
+        )
+        .await?;
+    let page_count = total.div_ceil(AUDIT_LOG_PAGE_SIZE).max(1);
+
+    let message = if entries.is_empty() {
+        i18n::text(locale, Text::AuditLogEmpty).to_string()
+    } else {
+        let product_names = audit_product_names(&context, &entries).await;
+        let mut message = String::new();
+        for entry in &entries {
+            message.push_str("\n- ");
+            push_audit_entry(locale, &mut message, entry, &product_names, true);
+        }
+        message
+    };
+
+    let embed = CreateEmbed::default()
+        .title(i18n::text(locale, Text::AuditLogTitle))
+        .description(message)
+        .footer(CreateEmbedFooter::new(i18n::format(
+            locale,
+            Text::AuditLogFooter,
+            &[("page", &page), ("pages", &page_count), ("count", &total)],
+        )));
+    context
+        .send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Look up names for the products referenced by some audit log entries. Product names are a nicety: if the cache can't
+/// be loaded this is empty, and callers should just show IDs.
+async fn audit_product_names(
+    context: &Context<'_>,
+    entries: &[AuditLogEntry],
+) -> HashMap<String, String> {
+    context
+        .data()
+        .api_cache
+        .get(context, |cache| {
+            entries
+                .iter()
+                .filter_map(|entry| entry.product_id.as_ref())
+                .filter_map(|product_id| {
+                    cache
+                        .product_id_to_name(product_id)
+                        .map(|name| (product_id.clone(), name.to_string()))
+                })
+                .collect()
+        })
+        .await
+        .unwrap_or_default()
+}
+
+/// Append a description of an audit log entry to `message`. The license can be left out when every entry is for the
+/// same license.
+fn push_audit_entry(
+    locale: Option<&str>,
+    message: &mut String,
+    entry: &AuditLogEntry,
+    product_names: &HashMap<String, String>,
+    show_license: bool,
+) {
+    message.push_str(
+        format!(
+            "<t:{}:f> {}",
+            entry.timestamp_unix_ms / 1000,
+            entry.action.name()
+        )
+        .as_str(),
+    );
+    let mut push = |text: Text, name: &str, value: &(dyn std::fmt::Display + Sync)| {
+        message.push(' ');
+        message.push_str(&i18n::format(locale, text, &[(name, value)]));
+    };
+    if let Some(actor) = entry.actor {
+        push(Text::AuditBy, "user", &format!("<@{}>", actor.get()));
+    }
+    if let Some(user) = entry.user {
+        push(Text::AuditFor, "user", &format!("<@{}>", user.get()));
+    }
+    if let Some(product_id) = &entry.product_id {
+        let product_name = product_names
+            .get(product_id)
+            .map(|name| format!("\"{}\"", name.safe_display()))
+            .unwrap_or_else(|| product_id.clone());
+        push(Text::AuditProduct, "product", &product_name);
+    }
+    if let Some(role) = entry.role {
+        push(Text::AuditRole, "role", &format!("<@&{}>", role.get()));
+    }
+    if show_license {
+        if let Some(license_id) = &entry.license_id {
+            push(Text::AuditLicense, "license", &format!("`{}`", license_id));
+        }
+    }
+    if let Some(detail) = &entry.detail {
+        message.push_str(format!(" ({})", detail.safe_display()).as_str());
+    }
+}
+
+/// Maximum number of audit log entries shown in a license's history
+const LICENSE_HISTORY_LIMIT: u64 = 20;
+
+// only requires MANAGE_ROLES permission because it can't emit license key info
+/// Show a timeline of role grants, locks, deactivations, and other events for a license
+#[poise::command(
+    slash_command,
+    custom_data = "CooldownTier::Expensive",
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub async fn license_history(
+    context: Context<'_>,
+    #[description = "Jinxxy license to show the history of"] license: String,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+    let error_title = i18n::text(locale, Text::LicenseHistoryErrorTitle);
+
+    let reply = if let Some(api_key) = context.data().db.get_jinxxy_api_key(guild_id).await? {
+        let license_id = license_to_id(&api_key, &license).await?;
+        if let Some(license_id) = license_id {
+            let filter = AuditLogFilter {
+                license_id: Some(license_id.clone()),
+                ..Default::default()
+            };
+            let (mut entries, total) = context
+                .data()
+                .db
+                .get_audit_log(guild_id, filter, 0, LICENSE_HISTORY_LIMIT)
+                .await?;
+            // the audit log is newest first, but a timeline reads better oldest first
+            entries.reverse();
+            // local records only cover what happened through this bot, so Jinxxy is the source of truth for who currently holds an activation
+            let activations = jinxxy::get_license_activations(&api_key, &license_id).await?;
+
+            let mut message = i18n::format(locale, Text::LicenseHistory, &[("license", &license)]);
+            if entries.is_empty() {
+                message.push('\n');
+                message.push_str(i18n::text(locale, Text::LicenseHistoryEmpty));
+            } else {
+                if total > entries.len() as u64 {
+                    let truncated = i18n::format(
+                        locale,
+                        Text::LicenseHistoryTruncated,
+                        &[("shown", &entries.len()), ("count", &total)],
+                    );
+                    message.push_str(format!("\n-# {truncated}").as_str());
+                }
+                let product_names = audit_product_names(&context, &entries).await;
+                for entry in &entries {
+                    message.push_str("\n- ");
+                    push_audit_entry(locale, &mut message, entry, &product_names, false);
+                }
+            }
+
+            message.push_str(
+                format!("\n\n**{}**", i18n::text(locale, Text::CurrentActivations)).as_str(),
+            );
+            if activations.is_empty() {
+                message.push('\n');
+                message.push_str(i18n::text(locale, Text::NoneSentence));
+            }
+            for activation in activations {
+                let line = match activation.try_into_user_id() {
+                    Some(LOCKING_USER_ID) => {
+                        i18n::text(locale, Text::LicenseLockedLine).to_string()
+                    }
+                    Some(user_id) => format!("<@{}>", user_id),
+                    None => i18n::format(
+                        locale,
+                        Text::ActivationNotFromDiscord,
+                        &[("description", &activation.description.safe_display())],
+                    ),
+                };
+                message.push_str(format!("\n- {line}").as_str());
+            }
+            success_reply(i18n::text(locale, Text::LicenseHistoryTitle), message)
+        } else {
+            error_reply(
+                error_title,
+                i18n::format(locale, Text::LicenseNotFound, &[("license", &license)]),
+            )
+        }
+    } else {
+        error_reply(error_title, i18n::text(locale, Text::ApiKeyNotSet))
+    };
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Initializes autocomplete data, and then does the product autocomplete
+async fn product_autocomplete(
+    context: Context<'_>,
+    product_prefix: &str,
+) -> impl Iterator<Item = String> {
+    autocomplete::complete(context, autocomplete::Products, product_prefix).await
+}
+
+async fn link_rule_pattern_autocomplete(
+    context: Context<'_>,
+    pattern_prefix: &str,
+) -> impl Iterator<Item = String> {
+    autocomplete::complete(context, autocomplete::LinkRulePatterns, pattern_prefix).await
+}
+
+/// Link a product to a role. Activating a license for the product will grant all linked roles.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn link_product(
+    context: Context<'_>,
+    #[description = "Product to modify role links for"]
+    #[autocomplete = "product_autocomplete"]
+    product: String,
+    #[description = "Role to link"] role: RoleId, // note that Discord does not presently support variadic arguments: https://github.com/discord/discord-api-docs/discussions/3286
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+    let locale = i18n::command_locale(context).await?;
+
+    let product_id = context
+        .data()
+        .api_cache
+        .product_name_to_id(&context, &product)
+        .await?;
+
+    let reply = if let Some(product_id) = product_id {
+        let guild_id = context
+            .guild_id()
+            .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+        let assignable_roles = assignable_roles(&context, guild_id).await?;
+
+        let mut unassignable_roles: HashSet<RoleId, ahash::RandomState> =
+            HashSet::with_hasher(Default::default());
+        context
+            .data()
+            .db
+            .link_product(guild_id, product_id.clone(), role)
+            .await?;
+        context
+            .data()
+            .db
+            .audit(
+                guild_id,
+                AuditLogEntry::new(AuditAction::Link)
+                    .actor(context.author().id)
+                    .product(product_id.clone())
+                    .role(role),
+            )
+            .await?;
+        if !assignable_roles.contains(&role) && !unassignable_roles.contains(&role) {
+            unassignable_roles.insert(role);
+        }
+
+        let roles = context.data().db.get_roles(guild_id, product_id).await?;
+        let mut message_lines = String::new();
+        for role in roles {
+            message_lines.push_str(format!("\n- <@&{}>", role.get()).as_str());
+        }
+
+        let embed = CreateEmbed::default()
+            .title(i18n::text(locale, Text::ProductLinkSuccessTitle))
+            .description(format!(
+                "{}{}",
+                i18n::format(
+                    locale,
+                    Text::ProductGrantsRoles,
+                    &[("product", &product.safe_display())],
+                ),
+                message_lines
+            ))
+            .color(Colour::DARK_GREEN);
+        let reply = CreateReply::default().embed(embed).ephemeral(true);
+        if let Some(embed) =
+            create_role_warning_from_unassignable(locale, unassignable_roles.into_iter())
+        {
+            reply.embed(embed)
+        } else {
+            reply
+        }
+    } else {
+        error_reply(
+            i18n::text(locale, Text::LinkProductErrorTitle),
+            i18n::text(locale, Text::ProductNotFound),
+        )
+    };
+
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Link many products to a role at once, picking them from a menu.
+#[poise::command(
+    slash_command,
+    custom_data = "CooldownTier::Expensive",
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn link_products_bulk(
+    context: Context<'_>,
+    #[description = "Role to link"] role: RoleId,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+    let products = context.data().api_cache.products(&context).await?;
+    if products.is_empty() {
+        context
+            .send(error_reply(
+                i18n::text(locale, Text::LinkProductsErrorTitle),
+                i18n::text(locale, Text::NoProductsFound),
+            ))
+            .await?;
+        return Ok(());
+    }
+
+    let max_products = SELECT_MENU_OPTION_LIMIT * BULK_LINK_SELECT_MENU_LIMIT;
+    let mut components: Vec<CreateActionRow> = products
+        .chunks(SELECT_MENU_OPTION_LIMIT)
+        .take(BULK_LINK_SELECT_MENU_LIMIT)
+        .enumerate()
+        .map(|(index, chunk)| {
+            let options = chunk
+                .iter()
+                .map(|(product_id, product_name)| {
+                    // option labels are limited to 100 characters
+                    let label: String = product_name.chars().take(100).collect();
+                    CreateSelectMenuOption::new(label, product_id)
+                })
+                .collect();
+            let select = CreateSelectMenu::new(
+                format!("{BULK_LINK_SELECT_ID_PREFIX}{index}"),
+                CreateSelectMenuKind::String { options },
+            )
+            .placeholder(i18n::format(
+                locale,
+                Text::ProductsRange,
+                &[
+                    ("first", &(index * SELECT_MENU_OPTION_LIMIT + 1)),
+                    ("last", &(index * SELECT_MENU_OPTION_LIMIT + chunk.len())),
+                ],
+            ))
+            .min_values(0)
+            .max_values(chunk.len() as u8);
+            CreateActionRow::SelectMenu(select)
+        })
+        .collect();
+    components.push(CreateActionRow::Buttons(vec![
+        CreateButton::new(BULK_LINK_CONFIRM_ID)
+            .label(i18n::text(locale, Text::Link))
+            .style(ButtonStyle::Primary),
+        CreateButton::new(BULK_LINK_CANCEL_ID)
+            .label(i18n::text(locale, Text::Cancel))
+            .style(ButtonStyle::Secondary),
+    ]));
+    let mut description = i18n::format(
+        locale,
+        Text::LinkProductsPrompt,
+        &[("role", &format!("<@&{}>", role.get()))],
+    );
+    if products.len() > max_products {
+        description.push_str("\n\n");
+        description.push_str(&i18n::format(
+            locale,
+            Text::LinkProductsOverflow,
+            &[("shown", &max_products), ("count", &products.len())],
+        ));
+    }
+    let prompt = CreateReply::default()
+        .embed(
+            CreateEmbed::default()
+                .title(i18n::text(locale, Text::LinkProductsTitle))
+                .description(description),
+        )
+        .components(components)
+        .ephemeral(true);
+    let reply = context.send(prompt).await?;
+    let message = reply.message().await?;
+
+    // each menu's selection replaces that menu's previous selection
+    let mut selections: Vec<Vec<String>> = vec![Vec::new(); BULK_LINK_SELECT_MENU_LIMIT];
+    let deadline = Instant::now() + BULK_LINK_TIMEOUT;
+    loop {
+        let interaction = message
+            .await_component_interaction(context.serenity_context())
+            .author_id(context.author().id)
+            .timeout(deadline.saturating_duration_since(Instant::now()))
+            .await;
+        let Some(interaction) = interaction else {
+            reply
+                .edit(
+                    context,
+                    error_reply(
+                        i18n::text(locale, Text::TimedOutTitle),
+                        i18n::text(locale, Text::NoProductsLinked),
+                    )
+                    .components(vec![]),
+                )
+                .await?;
+            return Ok(());
+        };
+        interaction
+            .create_response(context, CreateInteractionResponse::Acknowledge)
+            .await?;
+
+        let custom_id = interaction.data.custom_id.as_str();
+        if custom_id == BULK_LINK_CANCEL_ID {
+            reply
+                .edit(
+                    context,
+                    error_reply(
+                        i18n::text(locale, Text::CancelledTitle),
+                        i18n::text(locale, Text::NoProductsLinked),
+                    )
+                    .components(vec![]),
+                )
+                .await?;
+            return Ok(());
+        } else if custom_id == BULK_LINK_CONFIRM_ID {
+            break;
+        } else if let Some(index) = custom_id
+            .strip_prefix(BULK_LINK_SELECT_ID_PREFIX)
+            .and_then(|index| index.parse::<usize>().ok())
+            .filter(|index| *index < selections.len())
+        {
+            if let ComponentInteractionDataKind::StringSelect { values } = &interaction.data.kind {
+                selections[index] = values.clone();
+            }
+        }
+    }
+
+    let product_ids: Vec<String> = selections.into_iter().flatten().collect();
+    if product_ids.is_empty() {
+        reply
+            .edit(
+                context,
+                error_reply(
+                    i18n::text(locale, Text::LinkProductsErrorTitle),
+                    i18n::text(locale, Text::NoProductsSelected),
+                )
+                .components(vec![]),
+            )
+            .await?;
+        return Ok(());
+    }
+    let created_count = context
+        .data()
+        .db
+        .link_products(guild_id, product_ids.clone(), role)
+        .await?;
+    let product_names: HashMap<&str, &str> = products
+        .iter()
+        .map(|(product_id, product_name)| (product_id.as_str(), product_name.as_str()))
+        .collect();
+    let mut message_lines = String::new();
+    for product_id in &product_ids {
+        context
+            .data()
+            .db
+            .audit(
+                guild_id,
+                AuditLogEntry::new(AuditAction::Link)
+                    .actor(context.author().id)
+                    .product(product_id.clone())
+                    .role(role),
+            )
+            .await?;
+        let product_name = product_names
+            .get(product_id.as_str())
+            .copied()
+            .unwrap_or(product_id.as_str());
+        message_lines.push_str(format!("\n- {}", product_name.safe_display()).as_str());
+    }
+
+    let embed = CreateEmbed::default()
+        .title(i18n::text(locale, Text::ProductLinkSuccessTitle))
+        .description(format!(
+            "{}{}",
+            i18n::format(
+                locale,
+                Text::RoleGrantedByProducts,
+                &[
+                    ("role", &format!("<@&{}>", role.get())),
+                    ("count", &created_count),
+                ],
+            ),
+            message_lines
+        ))
+        .color(Colour::DARK_GREEN);
+    let assignable_roles = assignable_roles(&context, guild_id).await?;
+    let edit = CreateReply::default().embed(embed).components(vec![]);
+    let edit = if assignable_roles.contains(&role) {
+        edit
+    } else if let Some(embed) = create_role_warning_from_unassignable(locale, std::iter::once(role))
+    {
+        edit.embed(embed)
+    } else {
+        edit
+    };
+    reply.edit(context, edit).await?;
+    Ok(())
+}
+
+/// Automatically link products whose names match a pattern, like `*VIP*`, to a role.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn add_link_rule(
+    context: Context<'_>,
+    #[description = "Product name pattern. * matches anything and ? matches one character."]
+    pattern: String,
+    #[description = "Role to link matching products to"] role: RoleId,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+
+    let locale = i18n::command_locale(context).await?;
+    let error_title = i18n::text(locale, Text::AddLinkRuleErrorTitle);
+    let pattern = pattern.trim().to_string();
+    if pattern.is_empty() || pattern.chars().count() > link_rules::MAX_PATTERN_LENGTH {
+        let message = i18n::format(
+            locale,
+            Text::LinkRulePatternLength,
+            &[("max", &link_rules::MAX_PATTERN_LENGTH)],
+        );
+        context.send(error_reply(error_title, message)).await?;
+        return Ok(());
+    }
+
+    let added = context
+        .data()
+        .db
+        .add_link_rule(guild_id, pattern.clone(), role)
+        .await?;
+    if !added {
+        context
+            .send(error_reply(
+                error_title,
+                i18n::text(locale, Text::LinkRuleExists),
+            ))
+            .await?;
+        return Ok(());
+    }
+    context
+        .data()
+        .db
+        .audit(
+            guild_id,
+            AuditLogEntry::new(AuditAction::Link)
+                .actor(context.author().id)
+                .role(role)
+                .detail(format!("added auto-link rule {pattern}")),
+        )
+        .await?;
+
+    // apply the rule to existing products now; future products get it when they first show up in the cache
+    let products = context.data().api_cache.products(&context).await?;
+    let created_count =
+        link_rules::apply_rule(&context.data().db, guild_id, &pattern, role, &products).await?;
+
+    let embed = CreateEmbed::default()
+        .title(i18n::text(locale, Text::LinkRuleAddedTitle))
+        .description(i18n::format(
+            locale,
+            Text::LinkRuleAdded,
+            &[
+                ("pattern", &pattern.safe_display()),
+                ("role", &format!("<@&{}>", role.get())),
+                ("count", &created_count),
+            ],
+        ))
+        .color(Colour::DARK_GREEN);
+    let reply = CreateReply::default().embed(embed).ephemeral(true);
+    let assignable_roles = assignable_roles(&context, guild_id).await?;
+    let reply = if assignable_roles.contains(&role) {
+        reply
+    } else if let Some(embed) = create_role_warning_from_unassignable(locale, std::iter::once(role))
+    {
+        reply.embed(embed)
+    } else {
+        reply
+    };
+
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Remove an automatic product link rule. Links the rule already created are kept.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn remove_link_rule(
+    context: Context<'_>,
+    #[description = "Pattern of the rule, as shown by /list_link_rules"]
+    #[autocomplete = "link_rule_pattern_autocomplete"]
+    pattern: String,
+    #[description = "Role of the rule"] role: RoleId,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+
+    let locale = i18n::command_locale(context).await?;
+    let pattern = pattern.trim().to_string();
+    let removed = context
+        .data()
+        .db
+        .remove_link_rule(guild_id, pattern.clone(), role)
+        .await?;
+    let reply = if removed {
+        context
+            .data()
+            .db
+            .audit(
+                guild_id,
+                AuditLogEntry::new(AuditAction::Unlink)
+                    .actor(context.author().id)
+                    .role(role)
+                    .detail(format!("removed auto-link rule {pattern}")),
+            )
+            .await?;
+        success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::format(
+                locale,
+                Text::LinkRuleRemoved,
+                &[
+                    ("pattern", &pattern.safe_display()),
+                    ("role", &format!("<@&{}>", role.get())),
+                ],
+            ),
+        )
+    } else {
+        error_reply(
+            i18n::text(locale, Text::RemoveLinkRuleErrorTitle),
+            i18n::text(locale, Text::LinkRuleNotFound),
+        )
+    };
+
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// List the rules that automatically link products to roles by name
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn list_link_rules(context: Context<'_>) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+
+    let locale = i18n::command_locale(context).await?;
+    let rules = context.data().db.get_link_rules(guild_id).await?;
+    let lines =
+        if rules.is_empty() {
+            vec![i18n::text(locale, Text::LinkRulesEmpty).to_string()]
+        } else {
+            let mut lines = vec![i18n::text(locale, Text::LinkRules).to_string()];
+            lines.extend(rules.into_iter().map(|(pattern, role)| {
+                format!("- `{}` → <@&{}>", pattern.safe_display(), role.get())
+            }));
+            lines
+        };
+    let embed = CreateEmbed::default().title(i18n::text(locale, Text::LinkRulesTitle));
+    send_paginated(context, embed, &lines, vec![]).await
+}
+
+/// Set (or unset) a role granted by every product, in addition to any per-product links.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn set_blanket_role(
+    context: Context<'_>,
+    #[description = "Role to grant for any product"] role: Option<RoleId>,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+
+    context.data().db.set_blanket_role(guild_id, role).await?;
+    let audit_entry = AuditLogEntry::new(AuditAction::SetBlanketRole).actor(context.author().id);
+    let audit_entry = if let Some(role) = role {
+        audit_entry.role(role)
+    } else {
+        audit_entry
+    };
+    context.data().db.audit(guild_id, audit_entry).await?;
+
+    let locale = i18n::command_locale(context).await?;
+    let reply = if let Some(role) = role {
+        let reply = success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::format(
+                locale,
+                Text::BlanketRoleSet,
+                &[("role", &format!("<@&{}>", role.get()))],
+            ),
+        );
+        let assignable_roles = assignable_roles(&context, guild_id).await?;
+        if let Some(embed) =
+            create_role_warning_from_roles(locale, &assignable_roles, [role].into_iter())
+        {
+            reply.embed(embed)
+        } else {
+            reply
+        }
+    } else {
+        success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::text(locale, Text::BlanketRoleUnset),
+        )
+    };
+
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Unlink a product from a role.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn unlink_product(
+    context: Context<'_>,
+    #[description = "Product to modify role links for"]
+    #[autocomplete = "product_autocomplete"]
+    product: String,
+    #[description = "Role to unlink"] role: RoleId, // note that Discord does not presently support variadic arguments: https://github.com/discord/discord-api-docs/discussions/3286
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+    let locale = i18n::command_locale(context).await?;
+
+    let product_id = context
+        .data()
+        .api_cache
+        .product_name_to_id(&context, &product)
+        .await?;
+
+    let reply = if let Some(product_id) = product_id {
+        let guild_id = context
+            .guild_id()
+            .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+        let assignable_roles = assignable_roles(&context, guild_id).await?;
+
+        context
+            .data()
+            .db
+            .unlink_product(guild_id, product_id.clone(), role)
+            .await?;
+        context
+            .data()
+            .db
+            .audit(
+                guild_id,
+                AuditLogEntry::new(AuditAction::Unlink)
+                    .actor(context.author().id)
+                    .product(product_id.clone())
+                    .role(role),
+            )
+            .await?;
+
+        let roles = context.data().db.get_roles(guild_id, product_id).await?;
+        let mut message_lines = String::new();
+        for role in &roles {
+            message_lines.push_str(format!("\n- <@&{}>", role.get()).as_str());
+        }
+
+        let embed = CreateEmbed::default()
+            .title(i18n::text(locale, Text::ProductLinkSuccessTitle))
+            .description(format!(
+                "{}{}",
+                i18n::format(
+                    locale,
+                    Text::ProductGrantsRoles,
+                    &[("product", &product.safe_display())],
+                ),
+                message_lines
+            ))
+            .color(Colour::DARK_GREEN);
+        let reply = CreateReply::default().embed(embed).ephemeral(true);
+        if let Some(embed) =
+            create_role_warning_from_roles(locale, &assignable_roles, roles.into_iter())
+        {
+            reply.embed(embed)
+        } else {
+            reply
+        }
+    } else {
+        error_reply(
+            i18n::text(locale, Text::UnlinkProductErrorTitle),
+            i18n::text(locale, Text::ProductNotFound),
+        )
+    };
+
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Set how many different users may register a single license for a product. Omit seats to reset to 1.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn set_product_seats(
+    context: Context<'_>,
+    #[description = "Product to set the seat count for"]
+    #[autocomplete = "product_autocomplete"]
+    product: String,
+    #[description = "Number of users that may register one license"]
+    #[min = 1]
+    #[max = 1000]
+    seats: Option<u32>,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+
+    let product_id = context
+        .data()
+        .api_cache
+        .product_name_to_id(&context, &product)
+        .await?;
+
+    let reply = if let Some(product_id) = product_id {
+        context
+            .data()
+            .db
+            .set_max_activations(guild_id, product_id.clone(), seats)
+            .await?;
+        let seats = seats.unwrap_or(1);
+        context
+            .data()
+            .db
+            .audit(
+                guild_id,
+                AuditLogEntry::new(AuditAction::SetSeats)
+                    .actor(context.author().id)
+                    .product(product_id)
+                    .detail(format!("seats={seats}")),
+            )
+            .await?;
+        let message = if seats == 1 {
+            i18n::format(
+                locale,
+                Text::ProductSeatsSingle,
+                &[("product", &product.safe_display())],
+            )
+        } else {
+            i18n::format(
+                locale,
+                Text::ProductSeats,
+                &[("product", &product.safe_display()), ("seats", &seats)],
+            )
+        };
+        success_reply(i18n::text(locale, Text::SuccessTitle), message)
+    } else {
+        error_reply(
+            i18n::text(locale, Text::ProductSeatsErrorTitle),
+            i18n::text(locale, Text::ProductNotFound),
+        )
+    };
+
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Grant linked roles that users with activated licenses are missing, a batch of users at a time.
+///
+/// With `dry_run` no roles are changed: each batch instead lists which users would get which roles. A batch is cut short
+/// when the next user wouldn't fit in the embed, and that user starts the following page.
+#[poise::command(
+    slash_command,
+    custom_data = "CooldownTier::Expensive",
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn grant_missing_roles(
+    context: Context<'_>,
+    #[description = "Only grant roles from this product"]
+    #[autocomplete = "product_autocomplete"]
+    product: Option<String>,
+    #[description = "Only grant this role"] role: Option<RoleId>,
+    #[description = "Only users who joined the server on or after this date (YYYY-MM-DD)"]
+    joined_after: Option<String>,
+    #[description = "Users to check per batch (default 100)"]
+    #[min = 1]
+    #[max = 500]
+    batch_size: Option<u64>,
+    #[description = "Only list the roles that would be granted, without changing anything (default false)"]
+    dry_run: Option<bool>,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+    let error_title = i18n::text(locale, Text::GrantMissingRolesErrorTitle);
+    let _task = match tasks::start(TaskKind::GrantMissingRoles, guild_id, context.author().id) {
+        Ok(task) => task,
+        Err(limit) => {
+            let message = match limit {
+                LimitReached::Guild => Text::GrantMissingRolesRunning,
+                LimitReached::Global => Text::BotBusy,
+            };
+            context
+                .send(error_reply(error_title, i18n::text(locale, message)))
+                .await?;
+            return Ok(());
+        }
+    };
+    let Some(api_key) = context.data().db.get_jinxxy_api_key(guild_id).await? else {
+        context
+            .send(error_reply(
+                error_title,
+                i18n::text(locale, Text::ApiKeyNotSet),
+            ))
+            .await?;
+        return Ok(());
+    };
+    let product_id = match &product {
+        Some(product) => {
+            let product_id = context
+                .data()
+                .api_cache
+                .product_name_to_id(&context, product)
+                .await?;
+            if product_id.is_none() {
+                context
+                    .send(error_reply(
+                        error_title,
+                        i18n::text(locale, Text::ProductNotFound),
+                    ))
+                    .await?;
+                return Ok(());
+            }
+            product_id
+        }
+        None => None,
+    };
+    let joined_after = match &joined_after {
+        Some(date) => {
+            match serenity::Timestamp::parse(format!("{}T00:00:00Z", date.trim()).as_str()) {
+                Ok(timestamp) => Some(timestamp),
+                Err(_) => {
+                    context
+                        .send(error_reply(
+                            error_title,
+                            i18n::text(locale, Text::JoinDateInvalid),
+                        ))
+                        .await?;
+                    return Ok(());
+                }
+            }
+        }
+        None => None,
+    };
+    let batch_size = batch_size.unwrap_or(100);
+    let dry_run = dry_run.unwrap_or(false);
+
+    let mut after_user = LOCKING_USER_ID;
+    let mut checked_count: u64 = 0;
+    let mut granted_count: u64 = 0;
+    let mut failed_count: u64 = 0;
+    let mut reply_handle: Option<ReplyHandle> = None;
+    loop {
+        let user_ids = context
+            .data()
+            .db
+            .get_activated_users(guild_id, after_user, batch_size)
+            .await?;
+        let mut done = (user_ids.len() as u64) < batch_size;
+        let mut preview = String::new();
+        // one gateway request per hundred users, rather than a REST request per user
+        let members =
+            member_chunks::fetch_members(context.serenity_context(), guild_id, &user_ids).await?;
+        for user_id in user_ids {
+            let previous_user = after_user;
+            after_user = user_id.get();
+            let Some(member) = members.get(&user_id) else {
+                // users who left the server can't be given roles
+                continue;
+            };
+            if let Some(joined_after) = joined_after {
+                if member
+                    .joined_at
+                    .map_or(true, |joined_at| joined_at < joined_after)
+                {
+                    continue;
+                }
+            }
+
+            // local records only have the license, so we need Jinxxy to tell us what product (and version) it's for
+            let mut handled_roles: HashSet<RoleId> = HashSet::new();
+            let mut missing_roles: Vec<(RoleId, String, String)> = Vec::new();
+            let license_ids = context
+                .data()
+                .db
+                .get_user_licenses(guild_id, user_id.get())
+                .await?;
+            for license_id in license_ids {
+                let Some(license_info) = jinxxy::check_license_id(&api_key, &license_id).await?
+                else {
+                    continue;
+                };
+                if product_id
+                    .as_ref()
+                    .is_some_and(|product_id| *product_id != license_info.product_id)
+                {
+                    continue;
+                }
+                let roles = context
+                    .data()
+                    .db
+                    .get_role_grants(
+                        guild_id,
+                        license_info.product_id.clone(),
+                        license_info.product_version_id,
+                    )
+                    .await?;
+                for grant_role in roles {
+                    if role.is_some_and(|role| role != grant_role)
+                        || member.roles.contains(&grant_role)
+                        || !handled_roles.insert(grant_role)
+                    {
+                        continue;
+                    }
+                    missing_roles.push((
+                        grant_role,
+                        license_info.product_id.clone(),
+                        license_id.clone(),
+                    ));
+                }
+            }
+
+            if dry_run {
+                if !missing_roles.is_empty() {
+                    let roles = missing_roles
+                        .iter()
+                        .map(|(role, _, _)| format!("<@&{}>", role.get()))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    let line = format!("\n- <@{}>: {}", user_id.get(), roles);
+                    if !preview.is_empty()
+                        && preview.len() + line.len() > GRANT_MISSING_ROLES_PREVIEW_LENGTH
+                    {
+                        // out of room: this user starts the next page instead
+                        after_user = previous_user;
+                        done = false;
+                        break;
+                    }
+                    preview.push_str(&line);
+                    granted_count += missing_roles.len() as u64;
+                }
+            } else {
+                for (grant_role, product_id, license_id) in missing_roles {
+                    match member.add_role(context, grant_role).await {
+                        Ok(()) => {
+                            granted_count += 1;
+                            let audit_entry = AuditLogEntry::new(AuditAction::RoleGrant)
+                                .actor(context.author().id)
+                                .user(user_id)
+                                .product(product_id)
+                                .role(grant_role)
+                                .license(license_id)
+                                .detail("granted by /grant_missing_roles");
+                            context.data().db.audit(guild_id, audit_entry).await?;
+                        }
+                        Err(e) => {
+                            failed_count += 1;
+                            warn!("in {} error granting missing role: {:?}", guild_id.get(), e);
+                        }
+                    }
+                }
+            }
+            checked_count += 1;
+        }
+
+        let mut message = i18n::format(
+            locale,
+            if dry_run {
+                Text::MissingRolesDryRun
+            } else {
+                Text::MissingRolesGranted
+            },
+            &[("users", &checked_count), ("roles", &granted_count)],
+        );
+        message.push_str(&preview);
+        if failed_count != 0 {
+            message.push('\n');
+            message.push_str(&i18n::format(
+                locale,
+                Text::MissingRolesFailed,
+                &[("count", &failed_count)],
+            ));
+        }
+        let (title, components) = if done {
+            let title = if dry_run {
+                Text::MissingRolesPreviewTitle
+            } else {
+                Text::MissingRolesGrantedTitle
+            };
+            (title, vec![])
+        } else {
+            message.push('\n');
+            message.push_str(i18n::text(locale, Text::MissingRolesMore));
+            let buttons = vec![CreateActionRow::Buttons(vec![
+                CreateButton::new(GRANT_MISSING_ROLES_CONTINUE_ID)
+                    .label(i18n::text(locale, Text::NextBatch))
+                    .style(ButtonStyle::Primary),
+                CreateButton::new(GRANT_MISSING_ROLES_STOP_ID)
+                    .label(i18n::text(locale, Text::Stop))
+                    .style(ButtonStyle::Secondary),
+            ])];
+            let title = if dry_run {
+                Text::PreviewingMissingRolesTitle
+            } else {
+                Text::GrantingMissingRolesTitle
+            };
+            (title, buttons)
+        };
+        let embed = CreateEmbed::default()
+            .title(i18n::text(locale, title))
+            .description(message);
+        let reply = CreateReply::default()
+            .embed(embed.clone())
+            .components(components)
+            .ephemeral(true);
+        let handle = match reply_handle.take() {
+            Some(handle) => {
+                handle.edit(context, reply).await?;
+                handle
+            }
+            None => context.send(reply).await?,
+        };
+        if done {
+            return Ok(());
+        }
+
+        let message = handle.message().await?;
+        let interaction = message
+            .await_component_interaction(context.serenity_context())
+            .author_id(context.author().id)
+            .timeout(GRANT_MISSING_ROLES_TIMEOUT)
+            .await;
+        if let Some(interaction) = &interaction {
+            interaction
+                .create_response(context, CreateInteractionResponse::Acknowledge)
+                .await?;
+        }
+        let continue_pressed = interaction.is_some_and(|interaction| {
+            interaction.data.custom_id == GRANT_MISSING_ROLES_CONTINUE_ID
+        });
+        if !continue_pressed {
+            // stopped, or timed out: either way leave the progress so far without the buttons
+            let reply = CreateReply::default().embed(embed).components(vec![]);
+            handle.edit(context, reply).await?;
+            return Ok(());
+        }
+        reply_handle = Some(handle);
+    }
+}
+
+/// Add an action to run after a license for a product is activated. Actions run in the order added.
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn add_activation_hook(
+    context: Context<'_>,
+    #[description = "Product to add the action to"]
+    #[autocomplete = "product_autocomplete"]
+    product: String,
+    #[description = "What to do after activation"] action: ActivationHookKind,
+    #[description = "HTTPS URL to send the webhook to"] url: Option<String>,
+    #[description = "Thread or channel to act on"] channel: Option<ChannelId>, // see user_info for why this isn't a Channel
+    #[description = "Days until the roles are removed"]
+    #[min = 1]
+    #[max = 3650]
+    days: Option<u32>,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+
+    let locale = i18n::command_locale(context).await?;
+    let error_title = i18n::text(locale, Text::AddHookErrorTitle);
+    let target = match (action, url, channel, days) {
+        (ActivationHookKind::Webhook, Some(url), _, _) => {
+            webhook::check_url(&url).await.map(|_| url)
+        }
+        (ActivationHookKind::Webhook, None, _, _) => Err(i18n::text(locale, Text::HookUrlRequired)),
+        (ActivationHookKind::ScheduleExpiry, _, _, Some(days)) => Ok(days.to_string()),
+        (ActivationHookKind::ScheduleExpiry, _, _, None) => {
+            Err(i18n::text(locale, Text::HookDaysRequired))
+        }
+        (_, _, None, _) => Err(i18n::text(locale, Text::HookChannelRequired)),
+        (kind, _, Some(channel), _) => match channel.to_channel(context).await {
+            Ok(serenity::Channel::Guild(channel)) if channel.guild_id == guild_id => {
+                if kind == ActivationHookKind::AddToThread && channel.thread_metadata.is_none() {
+                    Err(i18n::text(locale, Text::HookThreadRequired))
+                } else {
+                    Ok(channel.id.get().to_string())
+                }
+            }
+            Ok(_) => Err(i18n::text(locale, Text::HookChannelOtherGuild)),
+            Err(_) => Err(i18n::text(locale, Text::HookChannelInvisible)),
+        },
+    };
+    let target = match target {
+        Ok(target) => target,
+        Err(message) => {
+            context.send(error_reply(error_title, message)).await?;
+            return Ok(());
+        }
+    };
+
+    let product_id = context
+        .data()
+        .api_cache
+        .product_name_to_id(&context, &product)
+        .await?;
+
+    let reply = if let Some(product_id) = product_id {
+        let signing_secret = if action == ActivationHookKind::Webhook {
+            Some(webhook::generate_signing_secret()?)
+        } else {
+            None
+        };
+        let position = context
+            .data()
+            .db
+            .add_activation_hook(guild_id, product_id, action, target, signing_secret.clone())
+            .await?;
+        let mut message = i18n::format(
+            locale,
+            Text::HookAdded,
+            &[
+                ("hook", &position),
+                ("product", &product.safe_display()),
+                ("action", &action.name()),
+            ],
+        );
+        if let Some(signing_secret) = &signing_secret {
+            message.push_str("\n\n");
+            message.push_str(&webhook_signing_secret(locale, signing_secret));
+        }
+        success_reply(i18n::text(locale, Text::SuccessTitle), message)
+    } else {
+        error_reply(error_title, i18n::text(locale, Text::ProductNotFound))
+    };
+
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Remove an action that runs after a license for a product is activated
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn remove_activation_hook(
+    context: Context<'_>,
+    #[description = "Product to remove the action from"]
+    #[autocomplete = "product_autocomplete"]
+    product: String,
+    #[description = "Number of the hook, as shown by /list_activation_hooks"] hook: u64,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+    let error_title = i18n::text(locale, Text::RemoveHookErrorTitle);
+
+    let product_id = context
+        .data()
+        .api_cache
+        .product_name_to_id(&context, &product)
+        .await?;
+
+    let reply = if let Some(product_id) = product_id {
+        let removed = context
+            .data()
+            .db
+            .remove_activation_hook(guild_id, product_id, hook)
+            .await?;
+        if removed {
+            success_reply(
+                i18n::text(locale, Text::SuccessTitle),
+                i18n::format(locale, Text::HookRemoved, &[("hook", &hook)]),
+            )
+        } else {
+            error_reply(error_title, i18n::text(locale, Text::HookNotFound))
+        }
+    } else {
+        error_reply(error_title, i18n::text(locale, Text::ProductNotFound))
+    };
+
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// List the actions that run after a license for a product is activated
+#[poise::command(
+    slash_command,
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn list_activation_hooks(
+    context: Context<'_>,
+    #[description = "Product to list actions for"]
+    #[autocomplete = "product_autocomplete"]
+    product: String,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+
+    let product_id = context
+        .data()
+        .api_cache
+        .product_name_to_id(&context, &product)
+        .await?;
+
+    let reply = if let Some(product_id) = product_id {
+        let hooks = context
+            .data()
+            .db
+            .get_activation_hooks(guild_id, product_id)
+            .await?;
+        let message = if hooks.is_empty() {
+            i18n::format(
+                locale,
+                Text::HooksEmpty,
+                &[("product", &product.safe_display())],
+            )
+        } else {
+            let mut message =
+                i18n::format(locale, Text::Hooks, &[("product", &product.safe_display())]);
+            for hook in hooks {
+                message.push_str(
+                    format!("\n{}. {}", hook.position, activation_hooks::describe(&hook)).as_str(),
+                );
+            }
+            message
+        };
+        CreateReply::default()
+            .embed(
+                CreateEmbed::default()
+                    .title(i18n::text(locale, Text::HooksTitle))
+                    .description(message),
+            )
+            .ephemeral(true)
+    } else {
+        error_reply(
+            i18n::text(locale, Text::ListHooksErrorTitle),
+            i18n::text(locale, Text::ProductNotFound),
+        )
+    };
+
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Exclude a product version from granting a role, even though the product is linked to that role.
+#[poise::command(
+    slash_command,
+    custom_data = "CooldownTier::Expensive",
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn exclude_product_version(
+    context: Context<'_>,
+    #[description = "Product to modify role links for"]
+    #[autocomplete = "product_autocomplete"]
+    product: String,
+    #[description = "Name of the product version that should not grant the role"] version: String,
+    #[description = "Role to exclude the version from"] role: RoleId,
+) -> Result<(), Error> {
+    set_product_version_exclusion::<true>(context, product, version, role).await
+}
+
+/// Undo `/exclude_product_version`, allowing a product version to grant a role again.
+#[poise::command(
+    slash_command,
+    custom_data = "CooldownTier::Expensive",
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn include_product_version(
+    context: Context<'_>,
+    #[description = "Product to modify role links for"]
+    #[autocomplete = "product_autocomplete"]
+    product: String,
+    #[description = "Name of the product version that should grant the role again"] version: String,
+    #[description = "Role to include the version in"] role: RoleId,
+) -> Result<(), Error> {
+    set_product_version_exclusion::<false>(context, product, version, role).await
+}
+
+async fn set_product_version_exclusion<const EXCLUDE: bool>(
+    context: Context<'_>,
+    product: String,
+    version: String,
+    role: RoleId,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+    let error_title = i18n::text(
+        locale,
+        if EXCLUDE {
+            Text::ExcludeVersionErrorTitle
+        } else {
+            Text::IncludeVersionErrorTitle
+        },
+    );
+
+    let product_id = context
+        .data()
+        .api_cache
+        .product_name_to_id(&context, &product)
+        .await?;
+
+    let reply = if let Some(product_id) = product_id {
+        if let Some(api_key) = context.data().db.get_jinxxy_api_key(guild_id).await? {
+            let product_version = find_product_version(
+                &context.data().db,
+                &api_key,
+                guild_id,
+                &product_id,
+                |version_id, version_name| {
+                    version_id == version || version_name.eq_ignore_ascii_case(&version)
+                },
+            )
+            .await?;
+            if let Some((product_version_id, product_version_name)) = product_version {
+                let message = if EXCLUDE {
+                    context
+                        .data()
+                        .db
+                        .exclude_product_version(
+                            guild_id,
+                            product_id.clone(),
+                            product_version_id.clone(),
+                            role,
+                        )
+                        .await?;
+                    context
+                        .data()
+                        .db
+                        .audit(
+                            guild_id,
+                            AuditLogEntry::new(AuditAction::ExcludeVersion)
+                                .actor(context.author().id)
+                                .product(product_id)
+                                .role(role)
+                                .detail(format!("version \"{}\"", product_version_name)),
+                        )
+                        .await?;
+                    i18n::format(
+                        locale,
+                        Text::VersionExcluded,
+                        &[
+                            ("product", &product.safe_display()),
+                            ("version", &product_version_name.safe_display()),
+                            ("role", &format!("<@&{}>", role.get())),
+                        ],
+                    )
+                } else if context
+                    .data()
+                    .db
+                    .include_product_version(
+                        guild_id,
+                        product_id.clone(),
+                        product_version_id.clone(),
+                        role,
+                    )
+                    .await?
+                {
+                    context
+                        .data()
+                        .db
+                        .audit(
+                            guild_id,
+                            AuditLogEntry::new(AuditAction::IncludeVersion)
+                                .actor(context.author().id)
+                                .product(product_id)
+                                .role(role)
+                                .detail(format!("version \"{}\"", product_version_name)),
+                        )
+                        .await?;
+                    i18n::format(
+                        locale,
+                        Text::VersionIncluded,
+                        &[
+                            ("product", &product.safe_display()),
+                            ("version", &product_version_name.safe_display()),
+                            ("role", &format!("<@&{}>", role.get())),
+                        ],
+                    )
+                } else {
+                    i18n::format(
+                        locale,
+                        Text::VersionNotExcluded,
+                        &[
+                            ("product", &product.safe_display()),
+                            ("version", &product_version_name.safe_display()),
+                            ("role", &format!("<@&{}>", role.get())),
+                        ],
+                    )
+                };
+                success_reply(i18n::text(locale, Text::SuccessTitle), message)
+            } else {
+                let mut message = i18n::format(
+                    locale,
+                    Text::VersionNotFound,
+                    &[
+                        ("product", &product.safe_display()),
+                        ("version", &version.safe_display()),
+                    ],
+                );
+                // the failed lookup above just refreshed the cache from the API, so this list is up to date
+                let product_versions = context
+                    .data()
+                    .db
+                    .get_product_versions(guild_id, product_id)
+                    .await?;
+                for (_, product_version_name) in product_versions {
+                    message
+                        .push_str(format!("\n- {}", product_version_name.safe_display()).as_str());
+                }
+                error_reply(error_title, message)
+            }
+        } else {
+            error_reply(error_title, i18n::text(locale, Text::ApiKeyNotSet))
+        }
+    } else {
+        error_reply(error_title, i18n::text(locale, Text::ProductNotFound))
+    };
+
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Review the roles each version of a product grants, and change them from a menu.
+#[poise::command(
+    slash_command,
+    custom_data = "CooldownTier::Expensive",
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn review_links(
+    context: Context<'_>,
+    #[description = "Product to review"]
+    #[autocomplete = "product_autocomplete"]
+    product: String,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+    let error_title = i18n::text(locale, Text::ReviewLinksErrorTitle);
+    let Some(api_key) = context.data().db.get_jinxxy_api_key(guild_id).await? else {
+        context
+            .send(error_reply(
+                error_title,
+                i18n::text(locale, Text::ApiKeyNotSet),
+            ))
+            .await?;
+        return Ok(());
+    };
+    let Some(product_id) = context
+        .data()
+        .api_cache
+        .product_name_to_id(&context, &product)
+        .await?
+    else {
+        context
+            .send(error_reply(
+                error_title,
+                i18n::text(locale, Text::ProductNotFound),
+            ))
+            .await?;
+        return Ok(());
+    };
+
+    // always fetch the versions fresh: reviewing is exactly when a stale version list would be confusing
+    let versions: Vec<(String, String)> = jinxxy::get_product(&api_key, &product_id)
+        .await?
+        .versions
+        .into_iter()
+        .map(|version| (version.id, version.name))
+        .collect();
+    context
+        .data()
+        .db
+        .replace_product_versions(guild_id, product_id.clone(), versions.clone())
+        .await?;
+    let mut roles = context
+        .data()
+        .db
+        .get_roles(guild_id, product_id.clone())
+        .await?;
+    if versions.is_empty() || roles.is_empty() {
+        let message = i18n::format(
+            locale,
+            if versions.is_empty() {
+                Text::ReviewLinksNoVersions
+            } else {
+                Text::ReviewLinksNotLinked
+            },
+            &[("product", &product.safe_display())],
+        );
+        context.send(error_reply(error_title, message)).await?;
+        return Ok(());
+    }
+    roles.truncate(SELECT_MENU_OPTION_LIMIT);
+    let role_names: HashMap<RoleId, String> = {
+        let guild = context
+            .guild()
+            .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+        roles
+            .iter()
+            .map(|role| {
+                let name = guild
+                    .roles
+                    .get(role)
+                    .map(|role| role.name.clone())
+                    .unwrap_or_else(|| role.get().to_string());
+                (*role, name)
+            })
+            .collect()
+    };
+    let mut excluded: HashSet<(String, RoleId)> = context
+        .data()
+        .db
+        .get_exclusions(guild_id)
+        .await?
+        .into_iter()
+        .filter(|(exclusion_product_id, _, _)| *exclusion_product_id == product_id)
+        .map(|(_, product_version_id, role)| (product_version_id, role))
+        .collect();
+
+    let review = LinkReview {
+        locale,
+        product: &product,
+        versions: &versions,
+        roles: &roles,
+        role_names: &role_names,
+    };
+    let page_count = versions.len().div_ceil(REVIEW_LINKS_PAGE_SIZE);
+    let mut page = 0;
+    let reply = context.send(review.page(&excluded, page, true)).await?;
+    let message = reply.message().await?;
+    loop {
+        let interaction = message
+            .await_component_interaction(context.serenity_context())
+            .author_id(context.author().id)
+            .timeout(REVIEW_LINKS_TIMEOUT)
+            .await;
+        let Some(interaction) = interaction else {
+            reply
+                .edit(context, review.page(&excluded, page, false))
+                .await?;
+            return Ok(());
+        };
+        interaction
+            .create_response(context, CreateInteractionResponse::Acknowledge)
+            .await?;
+
+        let custom_id = interaction.data.custom_id.as_str();
+        if custom_id == REVIEW_LINKS_DONE_ID {
+            reply
+                .edit(context, review.page(&excluded, page, false))
+                .await?;
+            return Ok(());
+        } else if custom_id == REVIEW_LINKS_PREVIOUS_ID {
+            page = page.saturating_sub(1);
+        } else if custom_id == REVIEW_LINKS_NEXT_ID {
+            page = (page + 1).min(page_count - 1);
+        } else if let Some((product_version_id, product_version_name)) = custom_id
+            .strip_prefix(REVIEW_LINKS_SELECT_ID_PREFIX)
+            .and_then(|index| index.parse::<usize>().ok())
+            .and_then(|index| versions.get(index))
+        {
+            let ComponentInteractionDataKind::StringSelect { values } = &interaction.data.kind
+            else {
+                continue;
+            };
+            let selected: HashSet<RoleId> = values
+                .iter()
+                .filter_map(|value| value.parse().ok().map(RoleId::new))
+                .collect();
+            for role in &roles {
+                let key = (product_version_id.clone(), *role);
+                let was_excluded = excluded.contains(&key);
+                let exclude = !selected.contains(role);
+                if exclude == was_excluded {
+                    continue;
+                }
+                let action = if exclude {
+                    context
+                        .data()
+                        .db
+                        .exclude_product_version(
+                            guild_id,
+                            product_id.clone(),
+                            product_version_id.clone(),
+                            *role,
+                        )
+                        .await?;
+                    excluded.insert(key);
+                    AuditAction::ExcludeVersion
+                } else {
+                    context
+                        .data()
+                        .db
+                        .include_product_version(
+                            guild_id,
+                            product_id.clone(),
+                            product_version_id.clone(),
+                            *role,
+                        )
+                        .await?;
+                    excluded.remove(&key);
+                    AuditAction::IncludeVersion
+                };
+                context
+                    .data()
+                    .db
+                    .audit(
+                        guild_id,
+                        AuditLogEntry::new(action)
+                            .actor(context.author().id)
+                            .product(product_id.clone())
+                            .role(*role)
+                            .detail(format!("version \"{}\"", product_version_name)),
+                    )
+                    .await?;
+            }
+        }
+        reply
+            .edit(context, review.page(&excluded, page, true))
+            .await?;
+    }
+}
+
+/// What `/review_links` is showing
+struct LinkReview<'a> {
+    locale: Option<&'a str>,
+    product: &'a str,
+    /// (product version ID, product version name)
+    versions: &'a [(String, String)],
+    /// Roles linked to the product
+    roles: &'a [RoleId],
+    role_names: &'a HashMap<RoleId, String>,
+}
+
+impl LinkReview<'_> {
+    /// Render one page of versions. Without controls, this is the final summary left behind once the review is over.
+    fn page(
+        &self,
+        excluded: &HashSet<(String, RoleId)>,
+        page: usize,
+        controls: bool,
+    ) -> CreateReply {
+        let page_count = self.versions.len().div_ceil(REVIEW_LINKS_PAGE_SIZE);
+        let start = page * REVIEW_LINKS_PAGE_SIZE;
+        let page_versions = self
+            .versions
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(REVIEW_LINKS_PAGE_SIZE);
+
+        let mut description = i18n::format(
+            self.locale,
+            Text::ReviewLinks,
+            &[("product", &self.product.safe_display())],
+        );
+        description.push('\n');
+        let mut components = Vec::new();
+        for (index, (product_version_id, product_version_name)) in page_versions {
+            let granted: Vec<RoleId> = self
+                .roles
+                .iter()
+                .copied()
+                .filter(|role| !excluded.contains(&(product_version_id.clone(), *role)))
+                .collect();
+            let granted_list = if granted.is_empty() {
+                i18n::text(self.locale, Text::NoRoles).to_string()
+            } else {
+                granted
+                    .iter()
+                    .map(|role| format!("<@&{}>", role.get()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            description.push_str(
+                format!(
+                    "\n**{}**: {}",
+                    product_version_name.safe_display(),
+                    granted_list
+                )
+                .as_str(),
+            );
+
+            let options = self
+                .roles
+                .iter()
+                .map(|role| {
+                    // option labels are limited to 100 characters
+                    let label: String = self.role_names[role].chars().take(100).collect();
+                    CreateSelectMenuOption::new(label, role.get().to_string())
+                        .default_selection(granted.contains(role))
+                })
+                .collect();
+            // placeholders are limited to 150 characters
+            let placeholder: String = product_version_name.chars().take(150).collect();
+            let select = CreateSelectMenu::new(
+                format!("{REVIEW_LINKS_SELECT_ID_PREFIX}{index}"),
+                CreateSelectMenuKind::String { options },
+            )
+            .placeholder(placeholder)
+            .min_values(0)
+            .max_values(self.roles.len() as u8);
+            components.push(CreateActionRow::SelectMenu(select));
+        }
+        components.push(CreateActionRow::Buttons(vec![
+            CreateButton::new(REVIEW_LINKS_PREVIOUS_ID)
+                .label(i18n::text(self.locale, Text::Previous))
+                .style(ButtonStyle::Secondary)
+                .disabled(page == 0),
+            CreateButton::new(REVIEW_LINKS_NEXT_ID)
+                .label(i18n::text(self.locale, Text::Next))
+                .style(ButtonStyle::Secondary)
+                .disabled(page + 1 >= page_count),
+            CreateButton::new(REVIEW_LINKS_DONE_ID)
+                .label(i18n::text(self.locale, Text::Done))
+                .style(ButtonStyle::Primary),
+        ]));
+
+        let embed = CreateEmbed::default()
+            .title(i18n::text(self.locale, Text::ReviewLinksTitle))
+            .description(description)
+            .footer(CreateEmbedFooter::new(i18n::format(
+                self.locale,
+                Text::PageFooter,
+                &[("page", &(page + 1)), ("pages", &page_count)],
+            )));
+        let reply = CreateReply::default().embed(embed).ephemeral(true);
+        if controls {
+            reply.components(components)
+        } else {
+            reply.components(vec![])
+        }
+    }
+}
+
+/// List all product→role links
+#[poise::command(
+    slash_command,
+    custom_data = "CooldownTier::Expensive",
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn list_links(context: Context<'_>) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let locale = i18n::command_locale(context).await?;
+
+    let assignable_roles = assignable_roles(&context, guild_id).await?;
+    let mut links = context.data().db.get_links(guild_id).await?;
+    let mut lines = if links.is_empty() {
+        vec![i18n::text(locale, Text::LinksEmpty).to_string()]
+    } else {
+        links.sort_unstable_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0))); // sort by role, then product
+        let product_details = context.data().db.get_all_product_details(guild_id).await?;
+        context
+            .data()
+            .api_cache
+            .get(&context, |cache| {
+                let mut lines: Vec<String> = Vec::new();
+                let mut current_role = None;
+
+                for (product_id, role) in &links {
+                    let product_name = cache
+                        .product_id_to_name(product_id)
+                        .map(|name| format!("\"{}\"", name.safe_display()))
+                        .unwrap_or_else(|| product_id.clone());
+                    let product_name = match product_details
+                        .get(product_id)
+                        .and_then(|details| details.price.as_deref())
+                    {
+                        Some(price) => format!("{} ({})", product_name, price),
+                        None => product_name,
+                    };
+                    match lines.last_mut() {
+                        Some(line) if current_role == Some(role) => {
+                            line.push_str(format!(", {}", product_name).as_str());
+                        }
+                        _ => {
+                            current_role = Some(role);
+                            lines.push(format!(
+                                "- {}",
+                                i18n::format(
+                                    locale,
+                                    Text::LinkGrantedBy,
+                                    &[
+                                        ("role", &format!("<@&{}>", role.get())),
+                                        ("products", &product_name),
+                                    ],
+                                )
+                            ));
+                        }
+                    }
+                }
+                lines
+            })
+            .await?
+    };
+    let exclusions = context.data().db.get_exclusions(guild_id).await?;
+    if !exclusions.is_empty() {
+        let exclusion_lines = context
+            .data()
+            .api_cache
+            .get(&context, |cache| {
+                exclusions
+                    .iter()
+                    .map(|(product_id, product_version_id, role)| {
+                        let product_name = cache
+                            .product_id_to_name(product_id)
+                            .map(|name| format!("\"{}\"", name.safe_display()))
+                            .unwrap_or_else(|| product_id.clone());
+                        format!(
+                            "- {}",
+                            i18n::format(
+                                locale,
+                                Text::LinkVersionExcluded,
+                                &[
+                                    ("role", &format!("<@&{}>", role.get())),
+                                    ("product", &product_name),
+                                    ("version", &format!("`{}`", product_version_id)),
+                                ],
+                            )
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .await?;
+        lines.push(String::new());
+        lines.push(format!(
+            "**{}**",
+            i18n::text(locale, Text::ExcludedVersions)
+        ));
+        lines.extend(exclusion_lines);
+    }
+    let blanket_role = context.data().db.get_blanket_role(guild_id).await?;
+    if let Some(blanket_role) = blanket_role {
+        lines.push(String::new());
+        lines.push(format!("**{}**", i18n::text(locale, Text::BlanketRole)));
+        lines.push(format!(
+            "- {}",
+            i18n::format(
+                locale,
+                Text::BlanketRoleGranted,
+                &[("role", &format!("<@&{}>", blanket_role.get()))],
+            )
+        ));
+    }
+    let unassignable_embed = create_role_warning_from_roles(
+        locale,
+        &assignable_roles,
+        links
+            .iter()
+            .map(|(_product_id, role_id)| *role_id)
+            .chain(blanket_role),
+    );
+    let embed = CreateEmbed::default().title(i18n::text(locale, Text::LinksTitle));
+    send_paginated(
+        context,
+        embed,
+        &lines,
+        unassignable_embed.into_iter().collect(),
+    )
+    .await
+}
+
+/// Get `(role ID, role name)` pairs for every role in the guild, or nothing if the guild isn't cached
+fn guild_role_names(context: &Context<'_>, guild_id: GuildId) -> Vec<(String, String)> {
+    guild_id
+        .to_guild_cached(context)
+        .map(|guild| {
+            guild
+                .roles
+                .values()
+                .filter(|role| role.id.get() != guild_id.get()) // skip @everyone
+                .map(|role| (role.id.get().to_string(), role.name.clone()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Export all product→role links as a JSON file, for version control or copying to another server
+#[poise::command(
+    slash_command,
+    custom_data = "CooldownTier::Expensive",
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn links_export(context: Context<'_>) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let db = &context.data().db;
+    let locale = i18n::command_locale(context).await?;
+
+    let product_names: HashMap<String, String> = context
+        .data()
+        .api_cache
+        .products(&context)
+        .await?
+        .into_iter()
+        .collect();
+    let role_names: HashMap<String, String> =
+        guild_role_names(&context, guild_id).into_iter().collect();
+    // anything we can't name is exported by ID, which still imports correctly into the same server
+    let product_name = |product_id: &str| {
+        product_names
+            .get(product_id)
+            .cloned()
+            .unwrap_or_else(|| product_id.to_string())
+    };
+    let role_name = |role: RoleId| {
+        let role_id = role.get().to_string();
+        role_names.get(&role_id).cloned().unwrap_or(role_id)
+    };
+
+    let links = db
+        .get_links(guild_id)
+        .await?
+        .into_iter()
+        .map(|(product_id, role)| links_document::Link {
+            product: product_name(&product_id),
+            role: role_name(role),
+        })
+        .collect();
+    let mut excluded_versions = Vec::new();
+    for (product_id, product_version_id, role) in db.get_exclusions(guild_id).await? {
+        let version = db
+            .get_product_versions(guild_id, product_id.clone())
+            .await?
+            .into_iter()
+            .find(|(version_id, _)| *version_id == product_version_id)
+            .map(|(_, version_name)| version_name)
+            .unwrap_or(product_version_id);
+        excluded_versions.push(links_document::ExcludedVersion {
+            product: product_name(&product_id),
+            version,
+            role: role_name(role),
+        });
+    }
+    let link_rules = db
+        .get_link_rules(guild_id)
+        .await?
+        .into_iter()
+        .map(|(pattern, role)| links_document::LinkRule {
+            pattern,
+            role: role_name(role),
+        })
+        .collect();
+    let blanket_role = db.get_blanket_role(guild_id).await?.map(role_name);
+
+    let document = links_document::LinksDocument {
+        links,
+        excluded_versions,
+        link_rules,
+        blanket_role,
+    };
+    let summary = i18n::format(
+        locale,
+        Text::LinksExported,
+        &[
+            ("links", &document.links.len()),
+            ("exclusions", &document.excluded_versions.len()),
+            ("rules", &document.link_rules.len()),
+        ],
+    );
+    let attachment =
+        CreateAttachment::bytes(serde_json::to_vec_pretty(&document)?, "jinx-links.json");
+    context
+        .send(
+            success_reply(i18n::text(locale, Text::LinksExportTitle), summary)
+                .attachment(attachment),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Resolve a name from an imported links document to an ID, noting fuzzy matches and names that weren't found
+fn resolve_import_name<'a>(
+    locale: Option<&str>,
+    kind: Text,
+    name: &str,
+    candidates: &'a [(String, String)],
+    notes: &mut Vec<String>,
+) -> Option<&'a str> {
+    let kind = i18n::text(locale, kind);
+    match links_document::find_match(name, candidates) {
+        links_document::Match::Exact(id) => Some(id),
+        links_document::Match::Fuzzy(id, matched_name) => {
+            let note = i18n::format(
+                locale,
+                Text::ImportNameMatched,
+                &[
+                    ("kind", &kind),
+                    ("name", &name.safe_display()),
+                    ("matched", &matched_name.safe_display()),
+                ],
+            );
+            notes.push(format!("- {note}"));
+            Some(id)
+        }
+        links_document::Match::None => {
+            let note = i18n::format(
+                locale,
+                Text::ImportNameNotFound,
+                &[("kind", &kind), ("name", &name.safe_display())],
+            );
+            notes.push(format!("- {note}"));
+            None
+        }
+    }
+}
+
+/// Add the product→role links from a `/links_export` file. Existing links are kept.
+#[poise::command(
+    slash_command,
+    custom_data = "CooldownTier::Expensive",
+    guild_only,
+    default_member_permissions = "MANAGE_ROLES",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn links_import(
+    context: Context<'_>,
+    #[description = "JSON file from /links_export"] file: serenity::Attachment,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let db = &context.data().db;
+    let locale = i18n::command_locale(context).await?;
+    let error_title = i18n::text(locale, Text::ImportLinksErrorTitle);
+    let Some(api_key) = db.get_jinxxy_api_key(guild_id).await? else {
+        context
+            .send(error_reply(
+                error_title,
+                i18n::text(locale, Text::ApiKeyNotSet),
+            ))
+            .await?;
+        return Ok(());
+    };
+    if file.size > links_document::MAX_DOCUMENT_BYTES {
+        context
+            .send(error_reply(
+                error_title,
+                i18n::text(locale, Text::FileTooLarge),
+            ))
+            .await?;
+        return Ok(());
+    }
+    let document: links_document::LinksDocument =
+        match serde_json::from_slice(&file.download().await?) {
+            Ok(document) => document,
+            Err(e) => {
+                context
+                    .send(error_reply(
+                        error_title,
+                        i18n::format(locale, Text::ImportInvalidFile, &[("error", &e)]),
+                    ))
+                    .await?;
+                return Ok(());
+            }
+        };
+
+    let products = context.data().api_cache.products(&context).await?;
+    let roles = guild_role_names(&context, guild_id);
+    let resolve_role = |name: &str, notes: &mut Vec<String>| {
+        resolve_import_name(locale, Text::ImportKindRole, name, &roles, notes)
+            .and_then(|role_id| role_id.parse().ok())
+            .map(RoleId::new)
+    };
+    let mut notes = Vec::new();
+    let mut imported_roles: HashSet<RoleId, ahash::RandomState> = Default::default();
+
+    let mut link_count: usize = 0;
+    for link in &document.links {
+        let product_id = resolve_import_name(
+            locale,
+            Text::ImportKindProduct,
+            &link.product,
+            &products,
+            &mut notes,
+        );
+        let role = resolve_role(&link.role, &mut notes);
+        let (Some(product_id), Some(role)) = (product_id, role) else {
+            continue;
+        };
+        db.link_product(guild_id, product_id.to_string(), role)
+            .await?;
+        db.audit(
+            guild_id,
+            AuditLogEntry::new(AuditAction::Link)
+                .actor(context.author().id)
+                .product(product_id.to_string())
+                .role(role)
+                .detail("imported"),
+        )
+        .await?;
+        imported_roles.insert(role);
+        link_count += 1;
+    }
+
+    let mut exclusion_count: usize = 0;
+    for exclusion in &document.excluded_versions {
+        let product_id = resolve_import_name(
+            locale,
+            Text::ImportKindProduct,
+            &exclusion.product,
+            &products,
+            &mut notes,
+        );
+        let role = resolve_role(&exclusion.role, &mut notes);
+        let (Some(product_id), Some(role)) = (product_id, role) else {
+            continue;
+        };
+        let version = find_product_version(db, &api_key, guild_id, product_id, |id, name| {
+            id == exclusion.version || name.eq_ignore_ascii_case(exclusion.version.trim())
+        })
+        .await?;
+        let Some((product_version_id, _)) = version else {
+            let note = i18n::format(
+                locale,
+                Text::ImportVersionNotFound,
+                &[("version", &exclusion.version.safe_display())],
+            );
+            notes.push(format!("- {note}"));
+            continue;
+        };
+        db.exclude_product_version(
+            guild_id,
+            product_id.to_string(),
+            product_version_id.clone(),
+            role,
+        )
+        .await?;
+        db.audit(
+            guild_id,
+            AuditLogEntry::new(AuditAction::ExcludeVersion)
+                .actor(context.author().id)
+                .product(product_id.to_string())
+                .role(role)
+                .detail(format!("imported version {product_version_id}")),
+        )
+        .await?;
+        exclusion_count += 1;
+    }
+
+    let mut rule_count: usize = 0;
+    for rule in &document.link_rules {
+        let pattern = rule.pattern.trim().to_string();
+        if pattern.is_empty() || pattern.chars().count() > link_rules::MAX_PATTERN_LENGTH {
+            let note = i18n::format(
+                locale,
+                Text::ImportInvalidLinkRule,
+                &[("pattern", &format!("`{}`", pattern.safe_display()))],
+            );
+            notes.push(format!("- {note}"));
+            continue;
+        }
+        let Some(role) = resolve_role(&rule.role, &mut notes) else {
+            continue;
+        };
+        if db.add_link_rule(guild_id, pattern.clone(), role).await? {
+            db.audit(
+                guild_id,
+                AuditLogEntry::new(AuditAction::Link)
+                    .actor(context.author().id)
+                    .role(role)
+                    .detail(format!("imported auto-link rule {pattern}")),
+            )
+            .await?;
+        }
+        link_rules::apply_rule(db, guild_id, &pattern, role, &products).await?;
+        imported_roles.insert(role);
+        rule_count += 1;
+    }
+
+    let blanket_role = match &document.blanket_role {
+        Some(name) => resolve_role(name, &mut notes),
+        None => None,
+    };
+    if let Some(role) = blanket_role {
+        db.set_blanket_role(guild_id, Some(role)).await?;
+        db.audit(
+            guild_id,
+            AuditLogEntry::new(AuditAction::SetBlanketRole)
+                .actor(context.author().id)
+                .role(role),
+        )
+        .await?;
+        imported_roles.insert(role);
+    }
+
+    let summary = if blanket_role.is_some() {
+        Text::LinksImportedWithBlanketRole
+    } else {
+        Text::LinksImported
+    };
+    let embed = CreateEmbed::default()
+        .title(i18n::text(locale, Text::LinksImportedTitle))
+        .description(i18n::format(
+            locale,
+            summary,
+            &[
+                ("links", &link_count),
+                ("exclusions", &exclusion_count),
+                ("rules", &rule_count),
+            ],
+        ))
+        .color(Colour::DARK_GREEN);
+    let embed = if notes.is_empty() {
+        embed
+    } else {
+        embed.field(
+            i18n::text(locale, Text::DoubleCheck),
+            field_value(&notes),
+            false,
+        )
+    };
+    let reply = CreateReply::default().embed(embed).ephemeral(true);
+    let assignable_roles = assignable_roles(&context, guild_id).await?;
+    let reply = match create_role_warning_from_unassignable(
+        locale,
+        imported_roles
+            .into_iter()
+            .filter(|role| !assignable_roles.contains(role)),
+    ) {
+        Some(embed) => reply.embed(embed),
+        None => reply,
+    };
     context.send(reply).await?;
     Ok(())
 }
@@ -1,34 +1,255 @@
 // This file is part of jinx. Copyright © 2024 jinx contributors.
 // jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
 
-use crate::bot::util::{check_owner, success_reply};
+use crate::bot::announcements;
+use crate::bot::drain;
+use crate::bot::i18n;
+use crate::bot::i18n::Text;
+use crate::bot::policy;
+use crate::bot::policy::Tier;
+use crate::bot::schedule::Schedule;
+use crate::bot::tasks;
+use crate::bot::util::{
+    error_reply, field_value, masked_link, sparkline, success_reply, SafeDisplayExt as _,
+};
 use crate::bot::Context;
+use crate::db::{
+    Announcement, AnnouncementAudience, AuditAction, AuditLogEntry, ConfirmableCommand,
+    ConfirmationMode, MessageKey, ReleaseChannel,
+};
 use crate::error::JinxError;
 use crate::http::jinxxy;
 use crate::http::jinxxy::{GetProfileImageUrl as _, GetProfileUrl as _};
+use crate::http::update_checker;
 use crate::SHOULD_RESTART;
 use poise::serenity_prelude as serenity;
-use poise::CreateReply;
-use serenity::{Colour, CreateEmbed, CreateMessage, GuildId, GuildRef, UserId};
+use poise::{ChoiceParameter as _, CreateReply, ReplyHandle};
+use serenity::{
+    ButtonStyle, Colour, CreateActionRow, CreateAttachment, CreateButton, CreateEmbed,
+    CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage, GuildId,
+    GuildRef, UserId,
+};
+use std::collections::HashMap;
 use std::sync::atomic;
-use tokio::time::Duration;
+use tokio::time::{Duration, Instant};
 use tracing::{info, warn};
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 
+/// Number of queries to show in the owner stats latency breakdown
+const SLOWEST_QUERY_COUNT: usize = 5;
+
+/// Number of tasks `/running_tasks` lists individually
+const OLDEST_TASK_COUNT: usize = 20;
+
+/// Number of announcements `/announce_status` lists
+const RECENT_ANNOUNCEMENT_COUNT: u64 = 10;
+
+/// Number of failed deliveries `/announce_status` shows for an announcement
+const ANNOUNCEMENT_FAILURE_COUNT: usize = 20;
+
+/// Failed delivery errors are cut down to this many characters in `/announce_status`
+const ANNOUNCEMENT_ERROR_LENGTH: usize = 100;
+
+// discord component ids
+const CONFIRM_BUTTON_ID: &str = "jinx_owner_confirm";
+const CANCEL_BUTTON_ID: &str = "jinx_owner_cancel";
+
+/// How long to wait for the owner who ran a destructive command to confirm it before giving up
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long to wait for another owner to approve a destructive command before giving up
+const SECOND_OWNER_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// How long to wait for in-flight registrations to finish before shutting down anyways
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Outcome of asking for confirmation of a destructive command
+enum Confirmation<'a> {
+    /// The command may run. If a prompt was shown, it should be replaced with the result.
+    Confirmed(Option<ReplyHandle<'a>>),
+    /// The command must not run. The prompt has already been updated to say so.
+    Rejected,
+}
+
+/// Ask for confirmation of a destructive command as configured by `/set_confirmation_mode`. `action` describes what
+/// the command will do in the user's locale, such as "shut down the bot".
+async fn confirm_command<'a>(
+    context: Context<'a>,
+    locale: Option<&str>,
+    command: ConfirmableCommand,
+    action: &str,
+) -> Result<Confirmation<'a>, Error> {
+    let mode = context.data().db.get_confirmation_mode(command).await?;
+    let (prompt, timeout) = match mode {
+        ConfirmationMode::None => return Ok(Confirmation::Confirmed(None)),
+        ConfirmationMode::Button => {
+            let embed = CreateEmbed::default()
+                .title(i18n::text(locale, Text::ConfirmTitle))
+                .description(i18n::format(
+                    locale,
+                    Text::ConfirmPrompt,
+                    &[("action", &action)],
+                ))
+                .color(Colour::ORANGE);
+            (
+                CreateReply::default().embed(embed).ephemeral(true),
+                CONFIRMATION_TIMEOUT,
+            )
+        }
+        ConfirmationMode::SecondOwner => {
+            if context.data().db.get_owners().await?.len() < 2 {
+                context
+                    .send(error_reply(
+                        i18n::text(locale, Text::ApprovalUnavailableTitle),
+                        i18n::format(
+                            locale,
+                            Text::ApprovalUnavailable,
+                            &[("command", &command.name())],
+                        ),
+                    ))
+                    .await?;
+                return Ok(Confirmation::Rejected);
+            }
+            // other owners need to be able to see this, so it can't be ephemeral
+            let embed = CreateEmbed::default()
+                .title(i18n::text(locale, Text::ApprovalRequiredTitle))
+                .description(i18n::format(
+                    locale,
+                    Text::ApprovalRequired,
+                    &[
+                        ("user", &format!("<@{}>", context.author().id.get())),
+                        ("action", &action),
+                        ("minutes", &(SECOND_OWNER_TIMEOUT.as_secs() / 60)),
+                    ],
+                ))
+                .color(Colour::ORANGE);
+            (CreateReply::default().embed(embed), SECOND_OWNER_TIMEOUT)
+        }
+    };
+    let confirm_label = if mode == ConfirmationMode::SecondOwner {
+        i18n::text(locale, Text::Approve)
+    } else {
+        i18n::text(locale, Text::Confirm)
+    };
+    let components = vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(CONFIRM_BUTTON_ID)
+            .label(confirm_label)
+            .style(ButtonStyle::Danger),
+        CreateButton::new(CANCEL_BUTTON_ID)
+            .label(i18n::text(locale, Text::Cancel))
+            .style(ButtonStyle::Secondary),
+    ])];
+    let reply = context.send(prompt.components(components)).await?;
+    let message = reply.message().await?;
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        let collector = message
+            .await_component_interaction(context.serenity_context())
+            .timeout(deadline.saturating_duration_since(Instant::now()));
+        let collector = if mode == ConfirmationMode::SecondOwner {
+            collector
+        } else {
+            collector.author_id(context.author().id)
+        };
+        let Some(interaction) = collector.await else {
+            reply
+                .edit(
+                    context,
+                    error_reply(
+                        i18n::text(locale, Text::ConfirmationTimedOutTitle),
+                        i18n::format(locale, Text::ActionNotTaken, &[("action", &action)]),
+                    )
+                    .components(vec![]),
+                )
+                .await?;
+            return Ok(Confirmation::Rejected);
+        };
+
+        // anyone in the channel can press the buttons on a second owner prompt, so we have to check who it was
+        let rejection = if mode != ConfirmationMode::SecondOwner {
+            None
+        } else if !context
+            .data()
+            .db
+            .is_user_owner(interaction.user.id.get())
+            .await?
+        {
+            Some(i18n::text(locale, Text::OwnersOnly))
+        } else if interaction.data.custom_id == CONFIRM_BUTTON_ID
+            && interaction.user.id == context.author().id
+        {
+            Some(i18n::text(locale, Text::DifferentOwnerRequired))
+        } else {
+            None
+        };
+        if let Some(rejection) = rejection {
+            let response = CreateInteractionResponseMessage::new()
+                .content(rejection)
+                .ephemeral(true);
+            interaction
+                .create_response(context, CreateInteractionResponse::Message(response))
+                .await?;
+            continue;
+        }
+
+        interaction
+            .create_response(context, CreateInteractionResponse::Acknowledge)
+            .await?;
+        if interaction.data.custom_id == CONFIRM_BUTTON_ID {
+            if mode == ConfirmationMode::SecondOwner {
+                info!(
+                    "<@{}> approved <@{}>'s request to {}",
+                    interaction.user.id.get(),
+                    context.author().id.get(),
+                    action
+                );
+            }
+            return Ok(Confirmation::Confirmed(Some(reply)));
+        } else {
+            reply
+                .edit(
+                    context,
+                    success_reply(
+                        i18n::text(locale, Text::CancelledTitle),
+                        i18n::format(locale, Text::ActionNotTaken, &[("action", &action)]),
+                    )
+                    .components(vec![]),
+                )
+                .await?;
+            return Ok(Confirmation::Rejected);
+        }
+    }
+}
+
+/// Report the result of a confirmed destructive command, replacing the confirmation prompt if there was one
+async fn send_confirmed_reply(
+    context: Context<'_>,
+    prompt: Option<ReplyHandle<'_>>,
+    reply: CreateReply,
+) -> Result<(), Error> {
+    if let Some(prompt) = prompt {
+        prompt.edit(context, reply.components(vec![])).await?;
+    } else {
+        context.send(reply).await?;
+    }
+    Ok(())
+}
+
 /// Get statistics about bot load and performance
 #[poise::command(
     slash_command,
     default_member_permissions = "MANAGE_GUILD",
-    check = "check_owner",
     install_context = "Guild",
     interaction_context = "Guild"
 )]
 pub(in crate::bot) async fn owner_stats(context: Context<'_>) -> Result<(), Error> {
+    let locale = i18n::command_locale(context).await?;
     let db_size = context.data().db.size().await.unwrap().div_ceil(1024);
     let configured_guild_count = context.data().db.guild_count().await.unwrap();
-    let license_activation_count = context.data().db.license_activation_count().await.unwrap();
-    let product_role_count = context.data().db.product_role_count().await.unwrap();
+    let license_activation_count = context.data().db.license_activation_count();
+    let product_role_count = context.data().db.product_role_count();
     let api_cache_products = context.data().api_cache.product_count();
     let api_cache_len = context.data().api_cache.len();
     let api_cache_capacity = context.data().api_cache.capacity();
@@ -49,6 +270,22 @@ pub(in crate::bot) async fn owner_stats(context: Context<'_>) -> Result<(), Erro
     let tokio_num_workers = tokio_metrics.num_workers();
     let tokio_num_alive_tasks = tokio_metrics.num_alive_tasks();
     let tokio_global_queue_depth = tokio_metrics.global_queue_depth();
+    let slow_query_threshold = context.data().db.slow_query_threshold().as_millis();
+    let mut query_stats = context.data().db.query_stats();
+    query_stats.sort_unstable_by_key(|(_, stats)| std::cmp::Reverse(stats.total));
+    let mut query_list = String::new();
+    for (query_name, stats) in query_stats.iter().take(SLOWEST_QUERY_COUNT) {
+        query_list.push_str(
+            format!(
+                "\n- {} n={} avg={:?} max={:?}",
+                query_name,
+                stats.count,
+                stats.average(),
+                stats.max
+            )
+            .as_str(),
+        );
+    }
 
     let message = format!(
         "db_size={db_size} KiB\n\
@@ -64,10 +301,12 @@ pub(in crate::bot) async fn owner_stats(context: Context<'_>) -> Result<(), Erro
         shards={shard_count}{shard_list}\n\
         tokio_num_workers={tokio_num_workers}\n\
         tokio_num_alive_tasks={tokio_num_alive_tasks}\n\
-        tokio_global_queue_depth={tokio_global_queue_depth}"
+        tokio_global_queue_depth={tokio_global_queue_depth}\n\
+        slow query threshold={slow_query_threshold}ms\n\
+        top queries by total time:{query_list}"
     );
     let embed = CreateEmbed::default()
-        .title("Jinx Owner Stats")
+        .title(i18n::text(locale, Text::OwnerStatsTitle))
         .description(message);
     context
         .send(CreateReply::default().embed(embed).ephemeral(true))
@@ -75,19 +314,416 @@ pub(in crate::bot) async fn owner_stats(context: Context<'_>) -> Result<(), Erro
     Ok(())
 }
 
+/// Show long-running interaction handlers that are currently in progress
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn running_tasks(context: Context<'_>) -> Result<(), Error> {
+    let locale = i18n::command_locale(context).await?;
+    let running = tasks::running();
+    let mut counts: Vec<(&'static str, usize)> = Vec::new();
+    for task in &running {
+        match counts
+            .iter_mut()
+            .find(|(name, _)| *name == task.kind.name())
+        {
+            Some((_, count)) => *count += 1,
+            None => counts.push((task.kind.name(), 1)),
+        }
+    }
+    let mut message = i18n::format(
+        locale,
+        Text::RunningTasks,
+        &[("running", &running.len()), ("limit", &tasks::GLOBAL_LIMIT)],
+    );
+    for (name, count) in counts {
+        message.push_str(format!("\n- {name}: {count}").as_str());
+    }
+    if !running.is_empty() {
+        message.push_str("\n\n");
+        message.push_str(i18n::text(locale, Text::RunningTasksOldest));
+        for task in running.iter().take(OLDEST_TASK_COUNT) {
+            message.push_str(
+                format!(
+                    "\n- {} in {} by <@{}> for {}s",
+                    task.kind.name(),
+                    task.guild_id.get(),
+                    task.user_id.get(),
+                    task.started.elapsed().as_secs()
+                )
+                .as_str(),
+            );
+        }
+    }
+    let embed = CreateEmbed::default()
+        .title(i18n::text(locale, Text::RunningTasksTitle))
+        .description(message);
+    context
+        .send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Show Jinxxy API latency and error rates
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn api_health(
+    context: Context<'_>,
+    #[description = "size of the window to report on, in hours (default 24)"]
+    #[min = 1]
+    #[max = 168]
+    hours: Option<u32>,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+    let locale = i18n::command_locale(context).await?;
+
+    // flush pending samples first so the report is up to date
+    let samples = jinxxy::drain_health_samples();
+    if !samples.is_empty() {
+        context.data().db.record_api_samples(samples).await?;
+    }
+
+    let hours = hours.unwrap_or(24);
+    let since_unix_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+        .saturating_sub(u64::from(hours) * 60 * 60 * 1000);
+    let endpoints = context.data().db.get_api_health(since_unix_ms).await?;
+
+    let message = if endpoints.is_empty() {
+        i18n::text(locale, Text::ApiHealthEmpty).to_string()
+    } else {
+        let mut message = String::new();
+        for endpoint in endpoints {
+            message.push_str(
+                format!(
+                    "\n- `{}` n={} errors={:.1}% p50={}ms p95={}ms p99={}ms",
+                    endpoint.endpoint,
+                    endpoint.request_count,
+                    endpoint.error_rate() * 100.0,
+                    endpoint.p50.as_millis(),
+                    endpoint.p95.as_millis(),
+                    endpoint.p99.as_millis()
+                )
+                .as_str(),
+            );
+        }
+        message
+    };
+    let embed = CreateEmbed::default()
+        .title(i18n::format(
+            locale,
+            Text::ApiHealthTitle,
+            &[("hours", &hours)],
+        ))
+        .description(message);
+    context
+        .send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Show long-term trends in activations, Jinxxy API health, and guild count
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn trends(
+    context: Context<'_>,
+    #[description = "number of days to chart (default 30)"]
+    #[min = 2]
+    #[max = 365]
+    days: Option<u32>,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+    let locale = i18n::command_locale(context).await?;
+
+    let days = days.unwrap_or(30);
+    let metrics = context.data().db.get_daily_metrics(u64::from(days)).await?;
+    let mut embed =
+        CreateEmbed::default().title(i18n::format(locale, Text::TrendsTitle, &[("days", &days)]));
+    if metrics.is_empty() {
+        embed = embed.description(i18n::text(locale, Text::TrendsEmpty));
+    } else {
+        let first_day = &metrics[0].day;
+        let last_day = &metrics[metrics.len() - 1].day;
+        embed = embed.description(i18n::format(
+            locale,
+            Text::Trends,
+            &[
+                ("count", &metrics.len()),
+                ("first_day", first_day),
+                ("last_day", last_day),
+            ],
+        ));
+        let charts: [(Text, Vec<u64>, &str); 5] = [
+            (
+                Text::TrendsActivations,
+                metrics.iter().map(|day| day.activation_count).collect(),
+                "",
+            ),
+            (
+                Text::TrendsApiRequests,
+                metrics.iter().map(|day| day.api_request_count).collect(),
+                "",
+            ),
+            (
+                Text::TrendsApiErrors,
+                metrics.iter().map(|day| day.api_error_count).collect(),
+                "",
+            ),
+            (
+                Text::TrendsApiLatency,
+                metrics
+                    .iter()
+                    .map(|day| day.api_latency_p95.as_millis() as u64)
+                    .collect(),
+                "ms",
+            ),
+            (
+                Text::TrendsGuilds,
+                metrics.iter().map(|day| day.guild_count).collect(),
+                "",
+            ),
+        ];
+        for (name, values, unit) in charts {
+            let min = values.iter().min().copied().unwrap_or(0);
+            let max = values.iter().max().copied().unwrap_or(0);
+            let latest = values.last().copied().unwrap_or(0);
+            embed = embed.field(
+                i18n::text(locale, name),
+                format!(
+                    "`{}`\nmin={min}{unit} max={max}{unit} latest={latest}{unit}",
+                    sparkline(&values)
+                ),
+                false,
+            );
+        }
+    }
+    context
+        .send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Show how each phrasing of a message is performing
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn message_experiments(
+    context: Context<'_>,
+    #[description = "message to report on"] message: MessageKey,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+    let locale = i18n::command_locale(context).await?;
+    let variants = context.data().db.get_message_variant_stats(message).await?;
+
+    let mut description = String::new();
+    for variant in variants {
+        let text = variant
+            .text
+            .as_deref()
+            .unwrap_or(i18n::text(locale, Text::MessageVariantBuiltIn));
+        let retired = if variant.active {
+            ""
+        } else {
+            i18n::text(locale, Text::MessageVariantRetired)
+        };
+        description.push_str(
+            format!(
+                "\n- **#{}**{}: shown={} retry success={} retry failure={} success rate={:.1}%\n  > {}",
+                variant.variant_id,
+                retired,
+                variant.shown_count,
+                variant.followup_success_count,
+                variant.followup_failure_count,
+                variant.success_rate() * 100.0,
+                text
+            )
+            .as_str(),
+        );
+    }
+    let embed = CreateEmbed::default()
+        .title(i18n::format(
+            locale,
+            Text::MessageExperimentTitle,
+            &[("message", &message.name())],
+        ))
+        .description(description);
+    context
+        .send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
+/// Add an alternative phrasing of a message, to be randomly assigned to guilds
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn add_message_variant(
+    context: Context<'_>,
+    #[description = "message to add a phrasing for"] message: MessageKey,
+    #[description = "new phrasing"] text: String,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+    let locale = i18n::command_locale(context).await?;
+    let text = text.trim().to_string();
+    let reply = if text.is_empty() {
+        error_reply(
+            i18n::text(locale, Text::AddVariantErrorTitle),
+            i18n::text(locale, Text::AddVariantEmpty),
+        )
+    } else {
+        let variant_id = context.data().db.add_message_variant(message, text).await?;
+        success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::format(locale, Text::AddVariantSuccess, &[("variant", &variant_id)]),
+        )
+    };
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Retire a message phrasing. Guilds using it are re-assigned; its stats are kept.
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn retire_message_variant(
+    context: Context<'_>,
+    #[description = "variant number, as shown by /message_experiments"] variant_id: u64,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+    let locale = i18n::command_locale(context).await?;
+    let reply = if context.data().db.retire_message_variant(variant_id).await? {
+        success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::format(
+                locale,
+                Text::RetireVariantSuccess,
+                &[("variant", &variant_id)],
+            ),
+        )
+    } else {
+        error_reply(
+            i18n::text(locale, Text::RetireVariantErrorTitle),
+            i18n::format(
+                locale,
+                Text::RetireVariantError,
+                &[("variant", &variant_id)],
+            ),
+        )
+    };
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Show which commands are installed in this server and who may use them
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn permission_matrix(context: Context<'_>) -> Result<(), Error> {
+    let guild_id = context
+        .guild_id()
+        .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+    let creator_installed = context
+        .data()
+        .db
+        .get_jinxxy_api_key(guild_id)
+        .await?
+        .is_some();
+    let owner_installed = context.data().db.is_owner_guild(guild_id).await?;
+    let locale = i18n::command_locale(context).await?;
+
+    let mut message = String::new();
+    for tier in Tier::ALL {
+        let (installed, users) = match tier {
+            Tier::Global => (true, Text::PermissionAnyone),
+            Tier::Creator => (creator_installed, Text::PermissionCreator),
+            Tier::Owner => (owner_installed, Text::PermissionOwner),
+        };
+        let users = i18n::text(locale, users);
+        let installed = if installed {
+            i18n::text(locale, Text::PermissionInstalled)
+        } else {
+            i18n::text(locale, Text::PermissionNotInstalled)
+        };
+        message.push_str(format!("\n**{}** ({installed}; {users})", tier.name()).as_str());
+        for command in tier.commands() {
+            let permissions = command.default_member_permissions.get_permission_names();
+            let permissions = if permissions.is_empty() {
+                i18n::text(locale, Text::PermissionAnyone).to_string()
+            } else {
+                permissions.join(", ")
+            };
+            message.push_str(format!("\n- `/{}`: {}", command.name, permissions).as_str());
+            let problems = policy::lint(tier, command);
+            if !problems.is_empty() {
+                message.push_str(format!(" ⚠️ {}", problems.join(", ")).as_str());
+            }
+        }
+    }
+
+    let embed = CreateEmbed::default()
+        .title(i18n::text(locale, Text::PermissionMatrixTitle))
+        .description(message)
+        .footer(CreateEmbedFooter::new(i18n::text(
+            locale,
+            Text::PermissionMatrixFooter,
+        )));
+    context
+        .send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
+
 /// Remotely shuts down the bot. If you do not have access to restart the bot this is PERMANENT.
 #[poise::command(
     slash_command,
     default_member_permissions = "MANAGE_GUILD",
-    check = "check_owner",
     install_context = "Guild",
     interaction_context = "Guild"
 )]
 pub(in crate::bot) async fn exit(context: Context<'_>) -> Result<(), Error> {
+    let locale = i18n::command_locale(context).await?;
+    let action = i18n::text(locale, Text::ActionExit);
+    let Confirmation::Confirmed(prompt) =
+        confirm_command(context, locale, ConfirmableCommand::Exit, action).await?
+    else {
+        return Ok(());
+    };
     info!("starting shutdown…");
-    context
-        .send(success_reply("Success", "Shutting down now!"))
-        .await?;
+    send_confirmed_reply(
+        context,
+        prompt,
+        success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::text(locale, Text::ShuttingDown),
+        ),
+    )
+    .await?;
+    drain_for_shutdown(context).await;
     context.framework().shard_manager.shutdown_all().await;
     Ok(())
 }
@@ -96,25 +732,56 @@ pub(in crate::bot) async fn exit(context: Context<'_>) -> Result<(), Error> {
 #[poise::command(
     slash_command,
     default_member_permissions = "MANAGE_GUILD",
-    check = "check_owner",
     install_context = "Guild",
     interaction_context = "Guild"
 )]
 pub(in crate::bot) async fn restart(context: Context<'_>) -> Result<(), Error> {
+    let locale = i18n::command_locale(context).await?;
+    let action = i18n::text(locale, Text::ActionRestart);
+    let Confirmation::Confirmed(prompt) =
+        confirm_command(context, locale, ConfirmableCommand::Restart, action).await?
+    else {
+        return Ok(());
+    };
     info!("starting restart…");
-    context
-        .send(success_reply("Success", "Restarting now!"))
-        .await?;
+    send_confirmed_reply(
+        context,
+        prompt,
+        success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::text(locale, Text::RestartingNow),
+        ),
+    )
+    .await?;
+    drain_for_shutdown(context).await;
     SHOULD_RESTART.store(true, atomic::Ordering::Release);
     context.framework().shard_manager.shutdown_all().await;
     Ok(())
 }
 
-/// Send an announcement to ALL bot log channels.
+/// Stop taking new interactions, give in-flight registrations a chance to finish, and checkpoint the DB so the bot can
+/// go down without losing anything.
+async fn drain_for_shutdown(context: Context<'_>) {
+    let start = Instant::now();
+    let remaining = drain::drain(DRAIN_TIMEOUT).await;
+    if remaining == 0 {
+        info!("drained in {}ms", start.elapsed().as_millis());
+    } else {
+        warn!(
+            "gave up waiting on {} in-flight registrations after {}s",
+            remaining,
+            DRAIN_TIMEOUT.as_secs()
+        );
+    }
+    if let Err(e) = context.data().db.checkpoint().await {
+        warn!("Error checkpointing DB: {:?}", e);
+    }
+}
+
+/// Send or schedule an announcement to bot log channels.
 #[poise::command(
     slash_command,
     default_member_permissions = "MANAGE_GUILD",
-    check = "check_owner",
     install_context = "Guild",
     interaction_context = "Guild"
 )]
@@ -122,15 +789,27 @@ pub(in crate::bot) async fn announce(
     context: Context<'_>,
     #[description = "Message title"] title: Option<String>,
     #[description = "Message to broadcast"] message: String,
+    #[description = "Which servers to send to (defaults to all servers)"] audience: Option<
+        AnnouncementAudience,
+    >,
+    #[description = "When to send, as YYYY-MM-DD HH:MM in UTC (defaults to now)"] send_at: Option<
+        String,
+    >,
 ) -> Result<(), Error> {
-    announce_internal::<false>(context, title, message).await
+    announce_internal(
+        context,
+        title,
+        message,
+        audience.unwrap_or_default(),
+        send_at,
+    )
+    .await
 }
 
 /// Send an announcement to all test server bot log channels.
 #[poise::command(
     slash_command,
     default_member_permissions = "MANAGE_GUILD",
-    check = "check_owner",
     install_context = "Guild",
     interaction_context = "Guild"
 )]
@@ -139,52 +818,245 @@ pub(in crate::bot) async fn announce_test(
     #[description = "Message title"] title: Option<String>,
     #[description = "Message to broadcast"] message: String,
 ) -> Result<(), Error> {
-    announce_internal::<true>(context, title, message).await
+    announce_internal(context, title, message, AnnouncementAudience::Test, None).await
 }
 
-/// Internal implementation of the announce command that handles whether to use test servers or ALL servers
-async fn announce_internal<const TEST_ONLY: bool>(
+/// Internal implementation of the announce commands. Records the announcement, then either sends it right away or leaves
+/// it for the scheduled announcement task.
+async fn announce_internal(
     context: Context<'_>,
     title: Option<String>,
     message: String,
+    audience: AnnouncementAudience,
+    send_at: Option<String>,
 ) -> Result<(), Error> {
     context.defer_ephemeral().await?; // gives us 15 minutes to complete our work
+    let locale = i18n::command_locale(context).await?;
+
+    let now_unix_ms = announcements::now_unix_ms();
+    let send_at_unix_ms = match send_at {
+        Some(send_at) => {
+            let timestamp = format!("{}:00Z", send_at.trim().replace(' ', "T"));
+            match serenity::Timestamp::parse(timestamp.as_str()) {
+                Ok(timestamp) if timestamp.unix_timestamp() * 1000 > now_unix_ms as i64 => {
+                    Some(timestamp.unix_timestamp() as u64 * 1000)
+                }
+                Ok(_) => {
+                    context
+                        .send(error_reply(
+                            i18n::text(locale, Text::AnnouncementErrorTitle),
+                            i18n::text(locale, Text::AnnouncementTimeInPast),
+                        ))
+                        .await?;
+                    return Ok(());
+                }
+                Err(_) => {
+                    context
+                        .send(error_reply(
+                            i18n::text(locale, Text::AnnouncementErrorTitle),
+                            i18n::text(locale, Text::AnnouncementTimeInvalid),
+                        ))
+                        .await?;
+                    return Ok(());
+                }
+            }
+        }
+        None => None,
+    };
 
     let message = message.replace(r"\n", "\n");
-    let embed = CreateEmbed::default().description(message);
-    let embed = if let Some(title) = title {
-        embed.title(title)
-    } else if TEST_ONLY {
-        embed.title("Test Announcement")
+    let announcement_id = context
+        .data()
+        .db
+        .add_announcement(
+            title,
+            message,
+            audience,
+            context.author().id,
+            send_at_unix_ms.unwrap_or(now_unix_ms),
+        )
+        .await?;
+
+    let reply = if let Some(send_at_unix_ms) = send_at_unix_ms {
+        success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::format(
+                locale,
+                Text::AnnouncementScheduled,
+                &[
+                    ("announcement", &announcement_id),
+                    ("audience", &audience.name()),
+                    ("time", &format!("<t:{}:F>", send_at_unix_ms / 1000)),
+                ],
+            ),
+        )
     } else {
-        embed.title("Announcement")
+        match announcements::deliver(
+            context.serenity_context(),
+            &context.data().db,
+            announcement_id,
+        )
+        .await?
+        {
+            Some((delivered_count, target_count)) => success_reply(
+                i18n::text(locale, Text::SuccessTitle),
+                i18n::format(
+                    locale,
+                    Text::AnnouncementSent,
+                    &[
+                        ("announcement", &announcement_id),
+                        ("delivered", &delivered_count),
+                        ("targets", &target_count),
+                    ],
+                ),
+            ),
+            // the scheduled announcement task got to it first
+            None => success_reply(
+                i18n::text(locale, Text::SuccessTitle),
+                i18n::format(
+                    locale,
+                    Text::AnnouncementSending,
+                    &[("announcement", &announcement_id)],
+                ),
+            ),
+        }
+    };
+    context.send(reply).await?;
+    Ok(())
+}
+
+/// Show recent announcements, or the delivery results for one announcement.
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn announce_status(
+    context: Context<'_>,
+    #[description = "Announcement number (defaults to listing recent announcements)"]
+    announcement_id: Option<u64>,
+) -> Result<(), Error> {
+    let db = &context.data().db;
+    let locale = i18n::command_locale(context).await?;
+    let Some(announcement_id) = announcement_id else {
+        let announcements = db.get_announcements(RECENT_ANNOUNCEMENT_COUNT).await?;
+        let message = if announcements.is_empty() {
+            i18n::text(locale, Text::AnnouncementsEmpty).to_string()
+        } else {
+            let mut message = String::new();
+            for announcement in announcements {
+                message.push_str(
+                    format!(
+                        "- #{} to {}: {}\n",
+                        announcement.announcement_id,
+                        announcement.audience.name(),
+                        announcement_status(locale, &announcement)
+                    )
+                    .as_str(),
+                );
+            }
+            message
+        };
+        let embed = CreateEmbed::default()
+            .title(i18n::text(locale, Text::RecentAnnouncementsTitle))
+            .description(message);
+        context
+            .send(CreateReply::default().embed(embed).ephemeral(true))
+            .await?;
+        return Ok(());
     };
 
-    let message = CreateMessage::default().embed(embed);
-    let channels = context.data().db.get_log_channels::<TEST_ONLY>().await?;
-    let channel_count = channels.len();
-    let mut successful_messages: usize = 0;
-    for channel in channels {
-        match channel.send_message(context, message.clone()).await {
-            Ok(_) => successful_messages += 1,
-            Err(e) => warn!("Error sending message to {}: {:?}", channel, e),
+    let Some(announcement) = db.get_announcement(announcement_id).await? else {
+        context
+            .send(error_reply(
+                i18n::text(locale, Text::AnnouncementStatusErrorTitle),
+                i18n::format(
+                    locale,
+                    Text::AnnouncementNotFound,
+                    &[("announcement", &announcement_id)],
+                ),
+            ))
+            .await?;
+        return Ok(());
+    };
+    let failures = db.get_announcement_failures(announcement_id).await?;
+    let mut message = format!(
+        "{}\n\n{}",
+        i18n::format(
+            locale,
+            Text::AnnouncementDetails,
+            &[
+                ("audience", &announcement.audience.name()),
+                ("status", &announcement_status(locale, &announcement)),
+            ],
+        ),
+        announcement.message
+    );
+    if !failures.is_empty() {
+        message.push_str("\n\n");
+        message.push_str(i18n::text(locale, Text::AnnouncementFailures));
+        for (guild_id, channel_id, error) in failures.iter().take(ANNOUNCEMENT_FAILURE_COUNT) {
+            let error: String = error.chars().take(ANNOUNCEMENT_ERROR_LENGTH).collect();
+            message.push_str(format!("\n- {guild_id} <#{channel_id}>: {error}").as_str());
+        }
+        if failures.len() > ANNOUNCEMENT_FAILURE_COUNT {
+            let more = i18n::format(
+                locale,
+                Text::AnnouncementFailuresMore,
+                &[("count", &(failures.len() - ANNOUNCEMENT_FAILURE_COUNT))],
+            );
+            message.push_str(format!("\n- {more}").as_str());
         }
-        tokio::time::sleep(Duration::from_millis(50)).await; // rate limit to 20 TPS
     }
+    let title = i18n::format(
+        locale,
+        Text::AnnouncementTitle,
+        &[
+            ("announcement", &announcement_id),
+            (
+                "title",
+                &announcement
+                    .title
+                    .as_deref()
+                    .unwrap_or(i18n::text(locale, Text::AnnouncementUntitled)),
+            ),
+        ],
+    );
+    let embed = CreateEmbed::default().title(title).description(message);
     context
-        .send(success_reply(
-            "Success",
-            format!("Sent announcement to {successful_messages}/{channel_count} channels"),
-        ))
+        .send(CreateReply::default().embed(embed).ephemeral(true))
         .await?;
     Ok(())
 }
 
+/// Describe where an announcement is in its delivery
+fn announcement_status(locale: Option<&str>, announcement: &Announcement) -> String {
+    match announcement.sent_unix_ms {
+        Some(sent_unix_ms) => i18n::format(
+            locale,
+            Text::AnnouncementStatusSent,
+            &[
+                ("time", &format!("<t:{}:f>", sent_unix_ms / 1000)),
+                ("delivered", &announcement.delivered_count),
+                ("failed", &announcement.failed_count),
+            ],
+        ),
+        None => i18n::format(
+            locale,
+            Text::AnnouncementStatusScheduled,
+            &[(
+                "time",
+                &format!("<t:{}:f>", announcement.send_at_unix_ms / 1000),
+            )],
+        ),
+    }
+}
+
 /// Set or unset this guild as a test guild
 #[poise::command(
     slash_command,
     default_member_permissions = "MANAGE_GUILD",
-    check = "check_owner",
     install_context = "Guild",
     interaction_context = "Guild"
 )]
@@ -197,13 +1069,524 @@ pub(in crate::bot) async fn set_test(
         .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
 
     context.data().db.set_test(guild_id, test).await?;
+    let locale = i18n::command_locale(context).await?;
 
     let message = if test {
-        "this guild is now set as a test guild"
+        i18n::text(locale, Text::TestGuildSet)
+    } else {
+        i18n::text(locale, Text::ProductionGuildSet)
+    };
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            message,
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Globally disable (or re-enable) license registration, such as during a Jinxxy outage
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn set_incident_mode(
+    context: Context<'_>,
+    #[description = "disable registration in every server?"] enabled: bool,
+    #[description = "notice shown to users instead of the default"] notice: Option<String>,
+) -> Result<(), Error> {
+    let locale = i18n::command_locale(context).await?;
+    let message = if enabled {
+        context
+            .data()
+            .db
+            .set_registration_incident_notice(Some(notice.unwrap_or_default()))
+            .await?;
+        warn!(
+            "<@{}> disabled registration for an incident",
+            context.author().id.get()
+        );
+        i18n::text(locale, Text::IncidentModeEnabled)
+    } else {
+        context
+            .data()
+            .db
+            .set_registration_incident_notice(None)
+            .await?;
+        info!(
+            "<@{}> re-enabled registration after an incident",
+            context.author().id.get()
+        );
+        i18n::text(locale, Text::IncidentModeDisabled)
+    };
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            message,
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Set or unset a cron-like schedule (UTC) for pre-warming the product cache, e.g. "55 16 * * 5"
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn set_cache_warm_schedule(
+    context: Context<'_>,
+    #[description = "minute hour day-of-month month day-of-week"] schedule: Option<String>,
+) -> Result<(), Error> {
+    let locale = i18n::command_locale(context).await?;
+    let message = if let Some(schedule) = schedule {
+        let parsed = match Schedule::parse(&schedule) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                context
+                    .send(error_reply(
+                        i18n::text(locale, Text::ScheduleErrorTitle),
+                        i18n::format(locale, Text::ScheduleInvalid, &[("error", &e)]),
+                    ))
+                    .await?;
+                return Ok(());
+            }
+        };
+        let message = i18n::format(
+            locale,
+            Text::CacheWarmScheduleSet,
+            &[("schedule", &schedule)],
+        );
+        context
+            .data()
+            .db
+            .set_cache_warm_schedule(Some(schedule))
+            .await?;
+        context.data().api_cache.set_warm_schedule(Some(parsed));
+        message
     } else {
-        "this guild is now set as a production guild"
+        context.data().db.set_cache_warm_schedule(None).await?;
+        context.data().api_cache.set_warm_schedule(None);
+        i18n::text(locale, Text::CacheWarmScheduleUnset).to_string()
     };
-    context.send(success_reply("Success", message)).await?;
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            message,
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Set the status messages to rotate through. {users} and {guilds} are replaced with live counts.
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn set_presence_messages(
+    context: Context<'_>,
+    #[description = "messages separated by |, or omit to restore the defaults"] messages: Option<
+        String,
+    >,
+) -> Result<(), Error> {
+    let locale = i18n::command_locale(context).await?;
+    let messages: Option<Vec<String>> = messages.map(|messages| {
+        messages
+            .split('|')
+            .map(|message| message.trim().to_string())
+            .filter(|message| !message.is_empty())
+            .collect()
+    });
+    let message = match &messages {
+        Some(messages) if messages.is_empty() => {
+            context
+                .send(error_reply(
+                    i18n::text(locale, Text::PresenceErrorTitle),
+                    i18n::text(locale, Text::PresenceEmpty),
+                ))
+                .await?;
+            return Ok(());
+        }
+        Some(messages) => {
+            let mut message = i18n::text(locale, Text::PresenceMessagesSet).to_string();
+            for presence_message in messages {
+                message.push_str(format!("\n- {}", presence_message).as_str());
+            }
+            message
+        }
+        None => i18n::text(locale, Text::PresenceMessagesReset).to_string(),
+    };
+    context.data().db.set_presence_messages(messages).await?;
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            message,
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Set how long each status message is shown before rotating to the next
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn set_presence_interval(
+    context: Context<'_>,
+    #[description = "interval in seconds"]
+    #[min = 10]
+    seconds: u64,
+) -> Result<(), Error> {
+    let locale = i18n::command_locale(context).await?;
+    context
+        .data()
+        .db
+        .set_presence_rotation_interval(Duration::from_secs(seconds))
+        .await?;
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::format(locale, Text::PresenceIntervalSet, &[("seconds", &seconds)]),
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Tune the DB connection. Omitted parameters are left unchanged.
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn tune_db(
+    context: Context<'_>,
+    #[description = "how long to wait on a locked DB, in milliseconds"]
+    #[max = 60000] // MAX_BUSY_TIMEOUT_MS
+    busy_timeout_ms: Option<u64>,
+    #[description = "number of prepared statements to cache"]
+    #[max = 1024]
+    statement_cache_capacity: Option<u16>,
+) -> Result<(), Error> {
+    let locale = i18n::command_locale(context).await?;
+    context
+        .data()
+        .db
+        .set_connection_tuning(
+            busy_timeout_ms.map(Duration::from_millis),
+            statement_cache_capacity.map(usize::from),
+        )
+        .await?;
+    let tuning = context.data().db.connection_tuning();
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            format!(
+                "busy timeout={}ms\nstatement cache capacity={}",
+                tuning.busy_timeout.as_millis(),
+                tuning.statement_cache_capacity
+            ),
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Set the threshold above which DB queries are logged as slow
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn set_slow_query_threshold(
+    context: Context<'_>,
+    #[description = "threshold in milliseconds"] threshold_ms: u64,
+) -> Result<(), Error> {
+    let locale = i18n::command_locale(context).await?;
+    context
+        .data()
+        .db
+        .set_slow_query_threshold(Duration::from_millis(threshold_ms))
+        .await?;
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::format(
+                locale,
+                Text::SlowQueryThresholdSet,
+                &[("milliseconds", &threshold_ms)],
+            ),
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Set how a destructive owner command must be confirmed before it runs
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn set_confirmation_mode(
+    context: Context<'_>,
+    #[description = "command to configure"] command: ConfirmableCommand,
+    #[description = "how the command must be confirmed"] mode: ConfirmationMode,
+) -> Result<(), Error> {
+    let locale = i18n::command_locale(context).await?;
+    context
+        .data()
+        .db
+        .set_confirmation_mode(command, mode)
+        .await?;
+    let requirement = match mode {
+        ConfirmationMode::None => Text::ConfirmationModeNone,
+        ConfirmationMode::Button => Text::ConfirmationModeButton,
+        ConfirmationMode::SecondOwner => Text::ConfirmationModeSecondOwner,
+    };
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::format(
+                locale,
+                Text::ConfirmationModeSet,
+                &[
+                    ("command", &command.name()),
+                    ("requirement", &i18n::text(locale, requirement)),
+                ],
+            ),
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Choose whether update checks consider pre-releases
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn set_release_channel(
+    context: Context<'_>,
+    #[description = "releases to check for"] channel: ReleaseChannel,
+) -> Result<(), Error> {
+    context.data().db.set_release_channel(channel).await?;
+    let locale = i18n::command_locale(context).await?;
+    let message = match channel {
+        ReleaseChannel::Stable => Text::ReleaseChannelStable,
+        ReleaseChannel::PreRelease => Text::ReleaseChannelPreRelease,
+    };
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::text(locale, message),
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Stop (or resume) suggesting an upgrade to a specific release
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn ignore_update_version(
+    context: Context<'_>,
+    #[description = "release version, such as 1.2.3"] version: String,
+    #[description = "ignore this version? Defaults to true"] ignored: Option<bool>,
+) -> Result<(), Error> {
+    let locale = i18n::command_locale(context).await?;
+    let ignored = ignored.unwrap_or(true);
+    let version = match update_checker::parse_version(version.trim()) {
+        Ok(version) => version,
+        Err(e) => {
+            context
+                .send(error_reply(
+                    i18n::text(locale, Text::IgnoreVersionErrorTitle),
+                    i18n::format(
+                        locale,
+                        Text::IgnoreVersionInvalid,
+                        &[("version", &version.safe_display()), ("error", &e)],
+                    ),
+                ))
+                .await?;
+            return Ok(());
+        }
+    };
+    let db = &context.data().db;
+    let mut ignored_versions = db.get_update_preferences().await?.ignored_versions;
+    ignored_versions.retain(|ignored_version| *ignored_version != version);
+    if ignored {
+        ignored_versions.push(version.clone());
+    }
+    db.set_ignored_update_versions(ignored_versions.clone())
+        .await?;
+
+    let mut message = if ignored {
+        i18n::format(locale, Text::VersionIgnored, &[("version", &version)])
+    } else {
+        i18n::format(locale, Text::VersionUnignored, &[("version", &version)])
+    };
+    if !ignored_versions.is_empty() {
+        let ignored_versions: Vec<String> = ignored_versions
+            .iter()
+            .map(|version| version.to_string())
+            .collect();
+        message.push_str("\n\n");
+        message.push_str(&i18n::format(
+            locale,
+            Text::IgnoredVersions,
+            &[("versions", &ignored_versions.join(", "))],
+        ));
+    }
+    context
+        .send(success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            message,
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Clear the product cache for one guild or for all guilds. Cleared entries are immediately re-warmed.
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn clear_cache(
+    context: Context<'_>,
+    #[description = "ID of guild to clear; omit to clear all guilds"] guild_id: Option<String>,
+) -> Result<(), Error> {
+    let locale = i18n::command_locale(context).await?;
+    let guild_id = match guild_id.map(|guild_id| guild_id.parse::<u64>()) {
+        None => None,
+        Some(Ok(guild_id)) if guild_id != 0 => Some(GuildId::new(guild_id)),
+        Some(_) => {
+            context
+                .send(error_reply(
+                    i18n::text(locale, Text::ClearCacheErrorTitle),
+                    i18n::text(locale, Text::InvalidGuildId),
+                ))
+                .await?;
+            return Ok(());
+        }
+    };
+    let action = match guild_id {
+        Some(guild_id) => i18n::format(
+            locale,
+            Text::ActionClearGuildCache,
+            &[("guild", &guild_id.get())],
+        ),
+        None => i18n::format(
+            locale,
+            Text::ActionClearAllCaches,
+            &[("count", &context.data().api_cache.len())],
+        ),
+    };
+
+    let Confirmation::Confirmed(prompt) = confirm_command(
+        context,
+        locale,
+        ConfirmableCommand::ClearCache,
+        action.as_str(),
+    )
+    .await?
+    else {
+        return Ok(());
+    };
+
+    let cleared_guild_ids = context.data().api_cache.clear(guild_id);
+    // other instances sharing the DB may be holding the same stale data
+    context
+        .data()
+        .db
+        .invalidate_guild_caches(cleared_guild_ids.clone())
+        .await?;
+    let message = i18n::format(
+        locale,
+        Text::CacheCleared,
+        &[("count", &cleared_guild_ids.len())],
+    );
+
+    // re-warm right away so the next autocomplete doesn't have to wait on the API
+    let db = context.data().db.clone();
+    let api_cache = context.data().api_cache.clone();
+    tokio::task::spawn(async move {
+        api_cache.refresh_guilds(&db, cleared_guild_ids).await;
+    });
+
+    send_confirmed_reply(
+        context,
+        prompt,
+        success_reply(i18n::text(locale, Text::SuccessTitle), message),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Export the product cache to a snapshot file, which can be imported on another instance
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn export_cache(context: Context<'_>) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+    let locale = i18n::command_locale(context).await?;
+    let guild_count = context.data().api_cache.len();
+    let snapshot = context.data().api_cache.export_snapshot()?;
+    let attachment = CreateAttachment::bytes(snapshot, "jinx-cache-snapshot.tsv.gz");
+    context
+        .send(
+            success_reply(
+                i18n::text(locale, Text::SuccessTitle),
+                i18n::format(locale, Text::CacheExported, &[("count", &guild_count)]),
+            )
+            .attachment(attachment),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Import a product cache snapshot created by /export_cache
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn import_cache(
+    context: Context<'_>,
+    #[description = "snapshot file from /export_cache"] snapshot: serenity::Attachment,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+    let locale = i18n::command_locale(context).await?;
+    let error_title = i18n::text(locale, Text::ImportCacheErrorTitle);
+    let snapshot = snapshot.download().await?;
+    let reply = match context.data().api_cache.import_snapshot(&snapshot) {
+        Ok(imported_count) => success_reply(
+            i18n::text(locale, Text::SuccessTitle),
+            i18n::format(locale, Text::CacheImported, &[("count", &imported_count)]),
+        ),
+        Err(e) => error_reply(
+            error_title,
+            i18n::format(locale, Text::SnapshotInvalid, &[("error", &e)]),
+        ),
+    };
+    context.send(reply).await?;
     Ok(())
 }
 
@@ -211,7 +1594,6 @@ pub(in crate::bot) async fn set_test(
 #[poise::command(
     slash_command,
     default_member_permissions = "MANAGE_GUILD",
-    check = "check_owner",
     install_context = "Guild",
     interaction_context = "Guild"
 )]
@@ -221,15 +1603,16 @@ pub(in crate::bot) async fn verify_guild(
     #[description = "Optional ID of expected owner"] owner_id: Option<UserId>,
 ) -> Result<(), Error> {
     context.defer_ephemeral().await?;
+    let locale = i18n::command_locale(context).await?;
 
     let reply = match guild_id.parse::<u64>() {
         Ok(guild_id) => {
             if guild_id == 0 {
                 // guild was invalid (0)
                 let embed = CreateEmbed::default()
-                    .title("Guild Verification Error")
+                    .title(i18n::text(locale, Text::GuildVerificationErrorTitle))
                     .color(Colour::RED)
-                    .description("Guild was invalid (id of 0)");
+                    .description(i18n::text(locale, Text::GuildIdZero));
                 CreateReply::default().embed(embed)
             } else {
                 let guild_id = GuildId::new(guild_id);
@@ -267,24 +1650,29 @@ pub(in crate::bot) async fn verify_guild(
                         // we have an expected owner to check
                         if guild.owner_id == expected_owner_id {
                             CreateEmbed::default()
-                                .title("Guild Verification Success")
+                                .title(i18n::text(locale, Text::GuildVerificationSuccessTitle))
                                 .color(Colour::DARK_GREEN)
-                                .description("Provided user owns that guild.")
+                                .description(i18n::text(locale, Text::GuildOwnerMatches))
                         } else {
                             CreateEmbed::default()
-                                .title("Guild Verification Failure")
+                                .title(i18n::text(locale, Text::GuildVerificationFailureTitle))
                                 .color(Colour::ORANGE)
-                                .description(format!(
-                                    "Provided user does not own that guild. Actual owner is <@{}>.",
-                                    guild.owner_id.get()
+                                .description(i18n::format(
+                                    locale,
+                                    Text::GuildOwnerMismatch,
+                                    &[("user", &format!("<@{}>", guild.owner_id.get()))],
                                 ))
                         }
                     } else {
                         // we don't have an expected owner to check, so just print the actual owner
                         CreateEmbed::default()
-                            .title("Guild Verification Result")
+                            .title(i18n::text(locale, Text::GuildVerificationResultTitle))
                             .color(Colour::DARK_GREEN)
-                            .description(format!("Guild owned by <@{}>", guild.owner_id.get()))
+                            .description(i18n::format(
+                                locale,
+                                Text::GuildOwner,
+                                &[("user", &format!("<@{}>", guild.owner_id.get()))],
+                            ))
                     };
 
                     let api_embed = if let Some(api_key) =
@@ -293,7 +1681,7 @@ pub(in crate::bot) async fn verify_guild(
                         match jinxxy::get_own_user(&api_key).await {
                             Ok(auth_user) => {
                                 let embed = CreateEmbed::default()
-                                    .title("API Verification Success")
+                                    .title(i18n::text(locale, Text::ApiVerificationSuccessTitle))
                                     .color(Colour::DARK_GREEN);
                                 let embed = if let Some(profile_image_url) =
                                     auth_user.profile_image_url()
@@ -306,24 +1694,33 @@ pub(in crate::bot) async fn verify_guild(
                                 let scopes = format!("{:?}", auth_user.scopes);
                                 let profile_url = auth_user.profile_url();
                                 let display_name = auth_user.into_display_name();
-                                let message = if let Some(profile_url) = profile_url {
-                                    format!("[{display_name}]({profile_url}) has scopes {scopes}")
+                                let user = if let Some(profile_url) = profile_url {
+                                    masked_link(&display_name, &profile_url)
                                 } else {
-                                    format!("{display_name} has scopes {scopes}")
+                                    display_name.safe_display().to_string()
                                 };
+                                let message = i18n::format(
+                                    locale,
+                                    Text::ApiScopes,
+                                    &[("user", &user), ("scopes", &scopes)],
+                                );
 
                                 embed.description(message)
                             }
                             Err(e) => CreateEmbed::default()
-                                .title("API Verification Error")
+                                .title(i18n::text(locale, Text::ApiVerificationErrorTitle))
                                 .color(Colour::RED)
-                                .description(format!("API key invalid: {}", e)),
+                                .description(i18n::format(
+                                    locale,
+                                    Text::ApiKeyInvalid,
+                                    &[("error", &e)],
+                                )),
                         }
                     } else {
                         CreateEmbed::default()
-                            .title("API Verification Skipped")
+                            .title(i18n::text(locale, Text::ApiVerificationSkippedTitle))
                             .color(Colour::ORANGE)
-                            .description("API key was unset")
+                            .description(i18n::text(locale, Text::ApiKeyUnset))
                     };
 
                     let guild_embed = {
@@ -331,13 +1728,16 @@ pub(in crate::bot) async fn verify_guild(
                             context.data().db.get_log_channel(guild_id).await?.is_some();
                         let is_test = context.data().db.is_test_guild(guild_id).await?;
                         let guild_embed = CreateEmbed::default()
-                            .title("Guild Information")
-                            .description(format!(
-                                "Name: {}\n\
-                                Description: {:?}\n\
-                                Log channel: {}\n\
-                                Test: {}",
-                                guild.name, guild.description, log_channel, is_test
+                            .title(i18n::text(locale, Text::GuildInformationTitle))
+                            .description(i18n::format(
+                                locale,
+                                Text::GuildInformation,
+                                &[
+                                    ("name", &guild.name.safe_display()),
+                                    ("description", &format!("{:?}", guild.description)),
+                                    ("log_channel", &log_channel),
+                                    ("test", &is_test),
+                                ],
                             ));
                         if let Some(thumbnail_url) = guild.thumbnail_url {
                             guild_embed.thumbnail(thumbnail_url)
@@ -353,9 +1753,9 @@ pub(in crate::bot) async fn verify_guild(
                 } else {
                     // guild was not cached
                     let embed = CreateEmbed::default()
-                        .title("Guild Verification Error")
+                        .title(i18n::text(locale, Text::GuildVerificationErrorTitle))
                         .color(Colour::RED)
-                        .description("Guild not in cache");
+                        .description(i18n::text(locale, Text::GuildNotCached));
                     CreateReply::default().embed(embed)
                 }
             }
@@ -363,9 +1763,13 @@ pub(in crate::bot) async fn verify_guild(
         Err(e) => {
             // guild was invalid (not a number)
             let embed = CreateEmbed::default()
-                .title("Guild Verification Error")
+                .title(i18n::text(locale, Text::GuildVerificationErrorTitle))
                 .color(Colour::RED)
-                .description(format!("Guild was invalid (parse error: {})", e));
+                .description(i18n::format(
+                    locale,
+                    Text::GuildIdUnparseable,
+                    &[("error", &e)],
+                ));
             CreateReply::default().embed(embed)
         }
     };
@@ -373,3 +1777,216 @@ pub(in crate::bot) async fn verify_guild(
     context.send(reply.ephemeral(true)).await?;
     Ok(())
 }
+
+/// A guild setting `/sudo_guild_config` can clear
+#[derive(Clone, Copy, Debug, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum GuildConfigField {
+    #[name = "log channel"]
+    LogChannel,
+    #[name = "blanket role"]
+    BlanketRole,
+    #[name = "milestone channel"]
+    MilestoneChannel,
+    #[name = "sales feed channel"]
+    SalesFeedChannel,
+    #[name = "language"]
+    Language,
+    #[name = "registrations paused"]
+    RegistrationsPaused,
+}
+
+/// Show everything stored for any guild, and optionally clear a broken setting
+#[poise::command(
+    slash_command,
+    default_member_permissions = "MANAGE_GUILD",
+    install_context = "Guild",
+    interaction_context = "Guild"
+)]
+pub(in crate::bot) async fn sudo_guild_config(
+    context: Context<'_>,
+    #[description = "ID of guild"] guild_id: String,
+    #[description = "setting to clear before showing the configuration"] clear: Option<
+        GuildConfigField,
+    >,
+) -> Result<(), Error> {
+    context.defer_ephemeral().await?;
+    let locale = i18n::command_locale(context).await?;
+
+    let guild_id = match guild_id.parse::<u64>() {
+        Ok(guild_id) if guild_id != 0 => GuildId::new(guild_id),
+        _ => {
+            context
+                .send(error_reply(
+                    i18n::text(locale, Text::GuildConfigErrorTitle),
+                    i18n::text(locale, Text::GuildConfigInvalidId),
+                ))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let db = &context.data().db;
+    if let Some(field) = clear {
+        match field {
+            GuildConfigField::LogChannel => db.set_log_channel(guild_id, None).await?,
+            GuildConfigField::BlanketRole => {
+                db.set_blanket_role(guild_id, None).await?;
+                db.audit(
+                    guild_id,
+                    AuditLogEntry::new(AuditAction::SetBlanketRole).actor(context.author().id),
+                )
+                .await?;
+            }
+            GuildConfigField::MilestoneChannel => db.set_milestone_channel(guild_id, None).await?,
+            GuildConfigField::SalesFeedChannel => db.set_sales_feed_channel(guild_id, None).await?,
+            GuildConfigField::Language => db.set_language(guild_id, None).await?,
+            GuildConfigField::RegistrationsPaused => {
+                db.set_registrations_paused(guild_id, false).await?;
+                db.audit(
+                    guild_id,
+                    AuditLogEntry::new(AuditAction::ResumeStore).actor(context.author().id),
+                )
+                .await?;
+            }
+        }
+        info!(
+            "<@{}> cleared the {} of guild {}",
+            context.author().id.get(),
+            field.name(),
+            guild_id.get()
+        );
+    }
+
+    let Some(config) = db.get_guild_config(guild_id).await? else {
+        context
+            .send(error_reply(
+                i18n::text(locale, Text::GuildConfigErrorTitle),
+                i18n::text(locale, Text::GuildConfigMissing),
+            ))
+            .await?;
+        return Ok(());
+    };
+
+    let unset = i18n::text(locale, Text::Unset);
+    let channel = |channel: Option<serenity::ChannelId>| {
+        channel.map_or_else(
+            || unset.to_string(),
+            |channel| format!("<#{}>", channel.get()),
+        )
+    };
+    let guild_name = guild_id
+        .to_guild_cached(&context)
+        .map(|guild| guild.name.safe_display().to_string())
+        .unwrap_or_else(|| i18n::text(locale, Text::NotInCache).to_string());
+    let settings = i18n::format(
+        locale,
+        Text::GuildConfig,
+        &[
+            ("name", &guild_name),
+            ("api_key_set", &config.api_key_set),
+            (
+                "jinxxy_user",
+                &config
+                    .jinxxy_user_id
+                    .as_deref()
+                    .unwrap_or(i18n::text(locale, Text::Unknown)),
+            ),
+            ("log_channel", &channel(config.log_channel)),
+            (
+                "blanket_role",
+                &config
+                    .blanket_role
+                    .map_or_else(|| unset.to_string(), |role| format!("<@&{}>", role.get())),
+            ),
+            ("milestone_channel", &channel(config.milestone_channel)),
+            ("sales_feed_channel", &channel(config.sales_feed_channel)),
+            (
+                "language",
+                &config.language.map_or(unset, |language| language.name()),
+            ),
+            (
+                "public_count_redaction",
+                &config
+                    .public_count_redaction
+                    .map_or(unset, |redaction| redaction.name()),
+            ),
+            (
+                "notification_digest",
+                &config
+                    .notification_digest
+                    .map_or(unset, |digest| digest.name()),
+            ),
+            ("registrations_paused", &config.registrations_paused),
+            ("restore_roles", &config.restore_roles),
+            ("log_member_leave", &config.log_member_leave),
+            ("log_product_changes", &config.log_product_changes),
+            ("test", &config.test),
+            ("owner", &config.owner),
+            ("stats_opt_out", &config.stats_opt_out),
+            (
+                "deleted",
+                &config.deleted_unix_ms.map_or_else(
+                    || i18n::text(locale, Text::No).to_string(),
+                    |deleted| format!("<t:{}>", deleted / 1000),
+                ),
+            ),
+        ],
+    );
+
+    let product_names: HashMap<String, String> =
+        db.get_products(guild_id).await?.into_iter().collect();
+    let links: Vec<String> = db
+        .get_links(guild_id)
+        .await?
+        .into_iter()
+        .map(|(product_id, role)| {
+            let product = product_names
+                .get(&product_id)
+                .map(|name| name.safe_display().to_string())
+                .unwrap_or(product_id);
+            format!("- {product} → <@&{}>", role.get())
+        })
+        .collect();
+    let link_rules: Vec<String> = db
+        .get_link_rules(guild_id)
+        .await?
+        .into_iter()
+        .map(|(pattern, role)| format!("- `{pattern}` → <@&{}>", role.get()))
+        .collect();
+
+    let embed = CreateEmbed::default()
+        .title(i18n::format(
+            locale,
+            Text::GuildConfigTitle,
+            &[("guild", &guild_id.get())],
+        ))
+        .description(settings)
+        .color(Colour::BLUE);
+    let embed = if links.is_empty() {
+        embed
+    } else {
+        embed.field(
+            i18n::format(locale, Text::GuildConfigLinks, &[("count", &links.len())]),
+            field_value(&links),
+            false,
+        )
+    };
+    let embed = if link_rules.is_empty() {
+        embed
+    } else {
+        embed.field(
+            i18n::format(
+                locale,
+                Text::GuildConfigLinkRules,
+                &[("count", &link_rules.len())],
+            ),
+            field_value(&link_rules),
+            false,
+        )
+    };
+    // role mentions in an ephemeral embed don't ping anyone
+    context
+        .send(CreateReply::default().embed(embed).ephemeral(true))
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,131 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Cleanup of links to roles that were deleted.
+//!
+//! We don't listen for role deletions, and couldn't rely on them anyways since the bot may be offline when one happens.
+//! Instead a background task periodically compares every role the DB refers to against the guild's roles in the cache.
+//! References to roles that no longer exist are removed, audited, and reported in the guild's bot log channel.
+
+use crate::bot::i18n;
+use crate::bot::i18n::Text;
+use crate::bot::util::{send_bot_log_message, SafeDisplayExt as _};
+use crate::bot::Error;
+use crate::db::{AuditAction, AuditLogEntry, JinxDb};
+use poise::serenity_prelude as serenity;
+use serenity::{Colour, CreateEmbed, CreateMessage, GuildId, RoleId};
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+/// Remove references to deleted roles in every cached guild. Failures are logged and skipped.
+pub async fn clean_up_all(context: &serenity::Context, db: &JinxDb) {
+    let references = match db.get_role_references().await {
+        Ok(references) => references,
+        Err(e) => {
+            warn!("Error reading role references: {:?}", e);
+            return;
+        }
+    };
+    let mut roles_by_guild: HashMap<GuildId, Vec<RoleId>, ahash::RandomState> = Default::default();
+    for (guild_id, role_id) in references {
+        roles_by_guild.entry(guild_id).or_default().push(role_id);
+    }
+    for (guild_id, roles) in roles_by_guild {
+        let dead_roles: Vec<RoleId> = {
+            // a guild that isn't cached is either unavailable or one we've left, and in both cases we can't tell which
+            // roles exist. Every guild has an @everyone role, so an empty role list means the data is incomplete.
+            let Some(guild) = guild_id.to_guild_cached(context) else {
+                continue;
+            };
+            if guild.roles.is_empty() {
+                continue;
+            }
+            roles
+                .into_iter()
+                .filter(|role| !guild.roles.contains_key(role))
+                .collect()
+        };
+        if dead_roles.is_empty() {
+            continue;
+        }
+        if let Err(e) = clean_up_guild(context, db, guild_id, dead_roles).await {
+            warn!(
+                "Error cleaning up deleted roles in {}: {:?}",
+                guild_id.get(),
+                e
+            );
+        }
+    }
+}
+
+/// Remove references to the given deleted roles in one guild
+async fn clean_up_guild(
+    context: &serenity::Context,
+    db: &JinxDb,
+    guild_id: GuildId,
+    dead_roles: Vec<RoleId>,
+) -> Result<(), Error> {
+    let product_names: HashMap<String, String> =
+        db.get_products(guild_id).await?.into_iter().collect();
+    let locale = i18n::guild_locale(db, guild_id, None).await?;
+    let mut message = i18n::text(locale, Text::LogDeadRoles).to_string();
+    for role in dead_roles {
+        let (product_ids, blanket_role_removed) = db.remove_role_references(guild_id, role).await?;
+        for product_id in &product_ids {
+            db.audit(
+                guild_id,
+                AuditLogEntry::new(AuditAction::Unlink)
+                    .product(product_id.clone())
+                    .role(role)
+                    .detail("role was deleted"),
+            )
+            .await?;
+        }
+        if blanket_role_removed {
+            db.audit(
+                guild_id,
+                AuditLogEntry::new(AuditAction::SetBlanketRole).detail("blanket role was deleted"),
+            )
+            .await?;
+        }
+
+        message.push_str("\n- ");
+        message.push_str(&i18n::format(
+            locale,
+            Text::LogDeadRole,
+            &[("role", &format!("`{}`", role.get()))],
+        ));
+        if blanket_role_removed {
+            message.push_str(i18n::text(locale, Text::LogDeadRoleBlanket));
+        }
+        if !product_ids.is_empty() {
+            let names: Vec<String> = product_ids
+                .iter()
+                .map(|product_id| {
+                    product_names
+                        .get(product_id)
+                        .unwrap_or(product_id)
+                        .safe_display()
+                        .to_string()
+                })
+                .collect();
+            message.push_str(&i18n::format(
+                locale,
+                Text::LogDeadRoleUnlinked,
+                &[("products", &names.join(", "))],
+            ));
+        }
+    }
+    info!("cleaned up deleted roles in {}", guild_id.get());
+
+    if let Some(log_channel) = db.get_log_channel(guild_id).await? {
+        message.push_str("\n\n");
+        message.push_str(i18n::text(locale, Text::LogDeadRolesFooter));
+        let embed = CreateEmbed::default()
+            .title(i18n::text(locale, Text::LogDeadRolesTitle))
+            .description(message)
+            .color(Colour::ORANGE);
+        send_bot_log_message(context, log_channel, CreateMessage::default().embed(embed)).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,27 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Draining in-flight work before the bot restarts or shuts down.
+//!
+//! Once draining starts new registrations and commands are turned away with a "try again shortly" message, and
+//! [`drain`] waits for the [`crate::bot::tasks`] that were already running to finish so they aren't cut off halfway
+//! through. Handlers must register their task before checking [`is_draining`], otherwise one could slip in between the
+//! check and [`drain`] counting what's running.
+
+use crate::bot::tasks;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tokio::time::Duration;
+
+static DRAINING: AtomicBool = AtomicBool::new(false);
+
+/// Check if we're draining, in which case no new work should be started
+pub fn is_draining() -> bool {
+    DRAINING.load(Ordering::SeqCst)
+}
+
+/// Stop accepting new work, then wait up to `timeout` for running tasks to finish. Returns how many were still running
+/// when we stopped waiting.
+pub async fn drain(timeout: Duration) -> usize {
+    DRAINING.store(true, Ordering::SeqCst);
+    tasks::wait_idle(timeout).await
+}
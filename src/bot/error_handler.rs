@@ -89,7 +89,21 @@ pub async fn error_handler(error: FrameworkError<'_, Data, Error>) {
             error!("Command structure mismatch: {:}", description);
             None
         }
-        FrameworkError::CooldownHit { ctx, .. } => PoiseError::new_cmd("Cooldown hit", ctx),
+        FrameworkError::CooldownHit {
+            ctx,
+            remaining_cooldown,
+            ..
+        } => {
+            // this is expected behavior, so just tell the user to slow down
+            let message = format!(
+                "This command was used recently. Please try again in {}s.",
+                remaining_cooldown.as_secs().max(1)
+            );
+            if let Err(e) = ctx.send(error_reply("Slow Down", message)).await {
+                error!("Error sending cooldown message: {:?}", e);
+            }
+            None
+        }
         FrameworkError::MissingBotPermissions { ctx, .. } => {
             PoiseError::new_cmd("Missing bot permissions", ctx)
         }
@@ -100,6 +114,10 @@ pub async fn error_handler(error: FrameworkError<'_, Data, Error>) {
         FrameworkError::GuildOnly { ctx, .. } => PoiseError::new_cmd("Guild only", ctx),
         FrameworkError::DmOnly { ctx, .. } => PoiseError::new_cmd("DM only", ctx),
         FrameworkError::NsfwOnly { ctx, .. } => PoiseError::new_cmd("NSFW only", ctx),
+        FrameworkError::CommandCheckFailed { error: None, .. } => {
+            // the policy check denied the command and has already told the user why
+            None
+        }
         FrameworkError::CommandCheckFailed { ctx, error, .. } => {
             PoiseError::debug_cmd("Command check failed", ctx, error)
         }
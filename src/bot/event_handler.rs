@@ -1,20 +1,49 @@
 // This file is part of jinx. Copyright © 2024 jinx contributors.
 // jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
 
+use crate::bot::activation_hooks;
 use crate::bot::commands::{LICENSE_KEY_ID, REGISTER_BUTTON_ID};
-use crate::bot::util::{set_guild_commands, MessageExtensions};
-use crate::bot::{Data, Error, REGISTER_MODAL_ID};
+use crate::bot::drain;
+use crate::bot::i18n;
+use crate::bot::i18n::Text;
+use crate::bot::license_expiry;
+use crate::bot::member_chunks;
+use crate::bot::milestones;
+use crate::bot::notification_digest;
+use crate::bot::tasks;
+use crate::bot::tasks::TaskKind;
+use crate::bot::util::{
+    incident_notice_embed, send_bot_log_message, set_guild_commands, MessageExtensions,
+    SafeDisplayExt as _,
+};
+use crate::bot::verification;
+use crate::bot::{
+    Data, Error, CLAIM_BUTTON_ID_PREFIX, CLAIM_MODAL_ID_PREFIX, DM_GUILD_SELECT_ID,
+    DM_REGISTER_MODAL_ID_PREFIX, REGISTER_MODAL_ID, REJOIN_RELINK_BUTTON_ID,
+};
+use crate::db::{
+    AuditAction, AuditLogEntry, DigestEntry, JinxDb, MessageKey, NotificationDigest,
+    RegistrationBlock, GUILD_REGISTRATION_FAILURE_LIMIT, REGISTRATION_FAILURE_WINDOW_MS,
+    USER_REGISTRATION_FAILURE_LIMIT,
+};
 use crate::error::JinxError;
 use crate::http::jinxxy;
+use crate::http::jinxxy::LicenseInfo;
 use crate::license;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
 use poise::serenity_prelude::{
-    ActionRowComponent, Colour, CreateActionRow, CreateEmbed, CreateInputText,
-    CreateInteractionResponse, CreateMessage, CreateModal, EditInteractionResponse, FullEvent,
-    InputTextStyle, Interaction,
+    ActionRowComponent, ButtonStyle, Colour, ComponentInteraction, ComponentInteractionDataKind,
+    CreateActionRow, CreateButton, CreateEmbed, CreateInputText, CreateInteractionResponse,
+    CreateInteractionResponseMessage, CreateMessage, CreateModal, CreateSelectMenu,
+    CreateSelectMenuKind, CreateSelectMenuOption, EditInteractionResponse, FullEvent, GuildId,
+    InputTextStyle, Interaction, Member, ModalInteraction, RoleId, UserId,
 };
 use poise::{serenity_prelude as serenity, FrameworkContext};
 use regex::Regex;
+use std::collections::{HashMap, HashSet};
 use std::sync::LazyLock;
+use tokio::time::{Duration, Instant};
 use tracing::{debug, error, info, warn};
 
 static GLOBAL_EASTER_EGG_REGEX: LazyLock<Regex> = LazyLock::new(|| {
@@ -29,6 +58,25 @@ thread_local! {
     static EASTER_EGG_REGEX: Regex = GLOBAL_EASTER_EGG_REGEX.clone();
 }
 
+/// Registrations slower than this are logged as warnings
+const SLOW_REGISTRATION_THRESHOLD: Duration = Duration::from_secs(3);
+
+/// Most products listed in a single rejoin assistant message
+const REJOIN_PRODUCT_LIST_LIMIT: usize = 20;
+
+/// Longest title Discord allows on a modal
+const MODAL_TITLE_LIMIT: usize = 45;
+
+/// Least time between two DMs from one user that we'll reply to. Each reply can cost a few Discord API calls.
+const DM_COOLDOWN: Duration = Duration::from_secs(10);
+
+/// Most Discord API calls to spend checking which guilds a user who sent a DM is still in
+const MAX_DM_MEMBER_LOOKUPS: usize = 5;
+
+/// When each user last got a reply to a DM, for [`DM_COOLDOWN`]
+static DM_REPLY_TIMES: LazyLock<DashMap<UserId, Instant, ahash::RandomState>> =
+    LazyLock::new(Default::default);
+
 /// Outer event handler layer for error handling. See [`event_handler_inner`] for the actual event handler implementation.
 pub async fn event_handler<'a>(
     context: &'a serenity::Context,
@@ -59,6 +107,21 @@ async fn event_handler_inner<'a>(
                 info!("GuildCreate guild={} is_new={:?}", guild.id.get(), is_new);
             }
 
+            match data.db.restore_guild(guild.id).await {
+                Ok(true) => {
+                    info!("restored data for re-added guild {}", guild.id.get());
+                    if let Err(e) = post_rejoin_assistant(context, data, guild).await {
+                        warn!(
+                            "Error posting rejoin assistant in {}: {:?}",
+                            guild.id.get(),
+                            e
+                        );
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => error!("Error restoring data for guild {}: {:?}", guild.id.get(), e),
+            }
+
             if let Err(e) = set_guild_commands(&context.http, &data.db, guild.id, None, None).await
             {
                 error!(
@@ -67,6 +130,13 @@ async fn event_handler_inner<'a>(
                     e
                 );
             }
+
+            // check the setup when we join, since this is when role positions and permissions are most likely to be off
+            if !matches!(is_new, Some(false)) {
+                if let Err(e) = report_setup_problems(context, data, guild).await {
+                    warn!("Error verifying setup in {}: {:?}", guild.id.get(), e);
+                }
+            }
         }
         // bot was removed from a guild (kick, ban, or guild deleted)
         FullEvent::GuildDelete { incomplete, full } => {
@@ -74,6 +144,12 @@ async fn event_handler_inner<'a>(
             if incomplete.unavailable || full.is_some() {
                 info!("GuildDelete guild={:?} full={:?}", incomplete, full)
             }
+            // an unavailable guild is just an outage, and the bot is still in it
+            if !incomplete.unavailable {
+                data.db.soft_delete_guild(incomplete.id).await?;
+                // the guild's products aren't needed until it's re-added, and refreshing them would only cost API calls
+                data.api_cache.clear(Some(incomplete.id));
+            }
         }
         /*
         the docs claim this happens "when the cache has received and inserted all data from
@@ -83,6 +159,33 @@ async fn event_handler_inner<'a>(
         FullEvent::CacheReady { guilds } => {
             debug!("cache ready! {} guilds.", guilds.len());
         }
+        // user joined (or rejoined) a guild
+        FullEvent::GuildMemberAddition { new_member } => {
+            if !new_member.user.bot {
+                restore_member_roles(context, data, new_member).await?;
+            }
+        }
+        // user left a guild (leave, kick, or ban)
+        FullEvent::GuildMemberRemoval {
+            guild_id,
+            user,
+            member_data_if_available,
+        } => {
+            if !user.bot {
+                log_member_leave(
+                    context,
+                    data,
+                    *guild_id,
+                    user.id,
+                    member_data_if_available.as_ref(),
+                )
+                .await?;
+            }
+        }
+        // members requested by a chunked member fetch
+        FullEvent::GuildMembersChunk { chunk } => {
+            member_chunks::receive(chunk);
+        }
         // I'm curious if this ever happens. I'll debug log it for now and worry about it later.
         FullEvent::Ratelimit { data } => {
             warn!("Ratelimit event: {:?}", data);
@@ -99,12 +202,58 @@ async fn event_handler_inner<'a>(
             So, basically any case where Discord thinks a user may actually intend for the bot to see the message.
             */
 
-            if new_message.fixed_is_private(context).await {
+            if new_message.author.bot {
+                // never respond to bots, which importantly includes ourselves
+            } else if new_message.fixed_is_private(context).await {
+                // DMs are where users paste license keys, so the content must never be logged
                 debug!(
-                    "Received DM {}: {}",
+                    "Received DM {} from <@{}>",
                     new_message.id.get(),
-                    new_message.content
+                    new_message.author.id.get()
                 );
+
+                if !dm_cooldown_elapsed(new_message.author.id) {
+                    debug!(
+                        "ignoring DM {} from <@{}> during cooldown",
+                        new_message.id.get(),
+                        new_message.author.id.get()
+                    );
+                    return Ok(());
+                }
+
+                // offer to register a license in any server we share with this user. Messages don't come with a locale
+                // like interactions do, so this uses the language the servers have in common, if any.
+                let guilds = registrable_guilds(context, &data.db, new_message.author.id).await?;
+                let locale = common_locale(&data.db, &guilds).await?;
+                let message = if guilds.is_empty() {
+                    let embed = CreateEmbed::default()
+                        .title(i18n::text(locale, Text::DmRegistrationTitle))
+                        .description(i18n::text(locale, Text::DmNoGuilds))
+                        .color(Colour::ORANGE);
+                    CreateMessage::default().embed(embed)
+                } else {
+                    let options = guilds
+                        .into_iter()
+                        .map(|(guild_id, guild_name)| {
+                            CreateSelectMenuOption::new(guild_name, guild_id.get().to_string())
+                        })
+                        .collect();
+                    let components = vec![CreateActionRow::SelectMenu(
+                        CreateSelectMenu::new(
+                            DM_GUILD_SELECT_ID,
+                            CreateSelectMenuKind::String { options },
+                        )
+                        .placeholder(i18n::text(locale, Text::DmSelectGuildPlaceholder)),
+                    )];
+                    let embed = CreateEmbed::default()
+                        .title(i18n::text(locale, Text::DmRegistrationTitle))
+                        .description(i18n::text(locale, Text::DmSelectGuild));
+                    CreateMessage::default().embed(embed).components(components)
+                };
+                new_message
+                    .channel_id
+                    .send_message(context, message)
+                    .await?;
             } else if new_message.mentions_me(context).await.unwrap_or(false) {
                 debug!(
                     "Mentioned in guild {} in message {}: {}",
@@ -162,367 +311,1382 @@ async fn event_handler_inner<'a>(
         FullEvent::InteractionCreate {
             interaction: Interaction::Component(component_interaction),
         } => {
-            #[allow(clippy::single_match)]
-            // likely to add more matches later, so I'm leaving it like this because it's obnoxious to switch between `if let` and `match`
             match component_interaction.data.custom_id.as_str() {
                 // create the register form when a user presses the register button
                 REGISTER_BUTTON_ID => {
-                    let components = vec![CreateActionRow::InputText(
-                        CreateInputText::new(InputTextStyle::Short, "License Key", LICENSE_KEY_ID)
-                            .placeholder("XXXX-cd071c534191"),
-                    )];
-                    let modal = CreateModal::new(REGISTER_MODAL_ID, "License Registration")
-                        .components(components);
-                    let response = CreateInteractionResponse::Modal(modal);
+                    let user_locale = Some(component_interaction.locale.as_str());
+                    let response = match component_interaction.guild_id {
+                        Some(guild_id) => {
+                            let locale =
+                                i18n::guild_locale(&data.db, guild_id, user_locale).await?;
+                            register_response(
+                                &data.db,
+                                guild_id,
+                                component_interaction.user.id,
+                                REGISTER_MODAL_ID.to_string(),
+                                locale,
+                                None,
+                            )
+                            .await?
+                        }
+                        None => register_modal(REGISTER_MODAL_ID.to_string(), user_locale, None),
+                    };
+                    component_interaction
+                        .create_response(context, response)
+                        .await?;
+                }
+                // list the products that need re-linking when an admin presses the rejoin assistant's button
+                REJOIN_RELINK_BUTTON_ID => {
+                    let response =
+                        rejoin_relink_response(context, data, component_interaction).await?;
                     component_interaction
                         .create_response(context, response)
                         .await?;
                 }
-                _ => {}
+                // create the register form when a user picks a guild to register in from a DM
+                DM_GUILD_SELECT_ID => {
+                    if let ComponentInteractionDataKind::StringSelect { values } =
+                        &component_interaction.data.kind
+                    {
+                        if let Some(guild_id) = values.first() {
+                            let guild_id: GuildId = guild_id.parse()?;
+                            let locale = i18n::guild_locale(
+                                &data.db,
+                                guild_id,
+                                Some(component_interaction.locale.as_str()),
+                            )
+                            .await?;
+                            let response = register_response(
+                                &data.db,
+                                guild_id,
+                                component_interaction.user.id,
+                                format!("{}{}", DM_REGISTER_MODAL_ID_PREFIX, guild_id.get()),
+                                locale,
+                                None,
+                            )
+                            .await?;
+                            component_interaction
+                                .create_response(context, response)
+                                .await?;
+                        }
+                    }
+                }
+                custom_id => {
+                    // create a register form for a single product when a user presses one of a claim post's buttons
+                    if let Some(product_id) = custom_id.strip_prefix(CLAIM_BUTTON_ID_PREFIX) {
+                        let guild_id = component_interaction
+                            .guild_id
+                            .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+                        let locale = i18n::guild_locale(
+                            &data.db,
+                            guild_id,
+                            Some(component_interaction.locale.as_str()),
+                        )
+                        .await?;
+                        let product_name = data
+                            .db
+                            .get_products(guild_id)
+                            .await?
+                            .into_iter()
+                            .find(|(id, _)| id == product_id)
+                            .map(|(_, name)| name);
+                        let response = register_response(
+                            &data.db,
+                            guild_id,
+                            component_interaction.user.id,
+                            format!("{}{}", CLAIM_MODAL_ID_PREFIX, product_id),
+                            locale,
+                            product_name.as_deref(),
+                        )
+                        .await?;
+                        component_interaction
+                            .create_response(context, response)
+                            .await?;
+                    }
+                }
             }
         }
         // handle modal interactions
         FullEvent::InteractionCreate {
             interaction: Interaction::Modal(modal_interaction),
         } => {
-            // this may take some time, so we defer the modal_interaction. If we don't ACK the interaction during the first 3s it is invalidated.
-            modal_interaction.defer_ephemeral(context).await?;
-
-            // likely to add more matches later, so I'm suppressing this lint because it's obnoxious to switch between `if let` and `match`
-            #[allow(clippy::single_match)]
-            match modal_interaction.data.custom_id.as_str() {
-                // this is the code that handles a user submitting the register form. All the license activation logic lives here.
-                REGISTER_MODAL_ID => {
-                    let license_key = modal_interaction
-                        .data
-                        .components
-                        .iter()
-                        .flat_map(|row| row.components.iter())
-                        .find_map(|component| {
-                            if let ActionRowComponent::InputText(input_text) = component {
-                                if input_text.custom_id == LICENSE_KEY_ID {
-                                    input_text
-                                        .value
-                                        .as_deref()
-                                        .map(|value| value.trim())
-                                        .filter(|value| !value.is_empty())
-                                } else {
-                                    None
-                                }
-                            } else {
-                                None
-                            }
-                        });
-                    if let Some(license_key) = license_key {
-                        let guild_id = modal_interaction
-                            .guild_id
-                            .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
-                        let user_id = modal_interaction.user.id;
-                        let license_type = license::identify_license(license_key);
+            // Modals we don't know about (such as the one from /setup) are handled by a collector, so we must not ACK them
+            // here. Registration may take some time, so we defer those. If we don't ACK the interaction during the
+            // first 3s it is invalidated.
+            let custom_id = modal_interaction.data.custom_id.as_str();
+            if custom_id == REGISTER_MODAL_ID {
+                modal_interaction.defer_ephemeral(context).await?;
+                // a user submitted the register form from a register post in a guild
+                let guild_id = modal_interaction
+                    .guild_id
+                    .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+                handle_license_registration(context, data, modal_interaction, guild_id, None)
+                    .await?;
+            } else if let Some(guild_id) = custom_id.strip_prefix(DM_REGISTER_MODAL_ID_PREFIX) {
+                // a user submitted the register form from a DM, so the guild is encoded in the modal ID
+                let guild_id: GuildId = guild_id.parse()?;
+                modal_interaction.defer_ephemeral(context).await?;
+                handle_license_registration(context, data, modal_interaction, guild_id, None)
+                    .await?;
+            } else if let Some(product_id) = custom_id.strip_prefix(CLAIM_MODAL_ID_PREFIX) {
+                // a user submitted a single product's register form from a claim post
+                modal_interaction.defer_ephemeral(context).await?;
+                let guild_id = modal_interaction
+                    .guild_id
+                    .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
+                handle_license_registration(
+                    context,
+                    data,
+                    modal_interaction,
+                    guild_id,
+                    Some(product_id),
+                )
+                .await?;
+            }
+        }
+        FullEvent::InteractionCreate {
+            interaction: Interaction::Command(command_interaction),
+        } => {
+            debug!(
+                "command \"{}\" invoked in {:?} by <@{}>",
+                command_interaction.data.name,
+                command_interaction.guild_id.map(|guild| guild.get()),
+                command_interaction.user.id.get()
+            );
+        }
+        _ => {}
+    }
 
+    Ok(())
+}
+
+/// Create the license registration form. `custom_id` determines how the submission is handled. If the form is for a
+/// single product, its name is used as the title.
+fn register_modal(
+    custom_id: String,
+    locale: Option<&str>,
+    product_name: Option<&str>,
+) -> CreateInteractionResponse {
+    let components = vec![CreateActionRow::InputText(
+        CreateInputText::new(
+            InputTextStyle::Short,
+            i18n::text(locale, Text::LicenseKeyLabel),
+            LICENSE_KEY_ID,
+        )
+        .placeholder("XXXX-cd071c534191"),
+    )];
+    let title = match product_name {
+        // Discord limits modal titles to 45 characters
+        Some(product_name) => product_name.chars().take(MODAL_TITLE_LIMIT).collect(),
+        None => i18n::text(locale, Text::RegistrationTitle).to_string(),
+    };
+    let modal = CreateModal::new(custom_id, title).components(components);
+    CreateInteractionResponse::Modal(modal)
+}
+
+/// Respond to a user asking to register in a guild: with the registration form, or with an explanation if registration
+/// is unavailable.
+async fn register_response(
+    db: &JinxDb,
+    guild_id: GuildId,
+    user_id: UserId,
+    custom_id: String,
+    locale: Option<&str>,
+    product_name: Option<&str>,
+) -> Result<CreateInteractionResponse, Error> {
+    if let Some(embed) = registration_unavailable_embed(db, guild_id, user_id, locale).await? {
+        let message = CreateInteractionResponseMessage::new()
+            .ephemeral(true)
+            .embed(embed);
+        Ok(CreateInteractionResponse::Message(message))
+    } else {
+        Ok(register_modal(custom_id, locale, product_name))
+    }
+}
+
+/// Explain to a user why registration is unavailable in this guild, or get `None` if it's available. A pending restart
+/// takes priority over a global incident, which takes priority over the guild pausing registration, which takes priority
+/// over a block from too many failed attempts. `/preview_roles` checks a license too, so it's gated the same way.
+pub(in crate::bot) async fn registration_unavailable_embed(
+    db: &JinxDb,
+    guild_id: GuildId,
+    user_id: UserId,
+    locale: Option<&str>,
+) -> Result<Option<CreateEmbed>, Error> {
+    if drain::is_draining() {
+        let embed = CreateEmbed::default()
+            .title(i18n::text(locale, Text::RestartingTitle))
+            .description(i18n::text(locale, Text::Restarting))
+            .color(Colour::ORANGE);
+        Ok(Some(embed))
+    } else if let Some(embed) = incident_notice_embed(db, locale).await? {
+        Ok(Some(embed))
+    } else if db.get_registrations_paused(guild_id).await? {
+        let embed = CreateEmbed::default()
+            .title(i18n::text(locale, Text::RegistrationPausedTitle))
+            .description(i18n::text(locale, Text::RegistrationPaused))
+            .color(Colour::ORANGE);
+        Ok(Some(embed))
+    } else if let Some(block) = db.get_registration_block(guild_id, user_id.get()).await? {
+        let message = if block.guild_wide {
+            Text::RegistrationGuildRateLimited
+        } else {
+            Text::RegistrationRateLimited
+        };
+        let retry = format!("<t:{}:R>", block.until_unix_ms.div_ceil(1000));
+        let embed = CreateEmbed::default()
+            .title(i18n::text(locale, Text::RegistrationRateLimitedTitle))
+            .description(i18n::format(locale, message, &[("retry", &retry)]))
+            .color(Colour::ORANGE);
+        Ok(Some(embed))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Count a failed license check towards the user's and guild's registration blocks, logging any block it starts
+pub(in crate::bot) async fn record_registration_failure(
+    context: &serenity::Context,
+    db: &JinxDb,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<(), Error> {
+    if let Some(block) = db
+        .record_registration_failure(guild_id, user_id.get())
+        .await?
+    {
+        debug!(
+            "registration blocked in {} after a failure from <@{}>: {:?}",
+            guild_id.get(),
+            user_id.get(),
+            block
+        );
+        log_registration_block(context, db, guild_id, user_id, block).await?;
+    }
+    Ok(())
+}
+
+/// Let the guild's bot log know that a registration block has started
+async fn log_registration_block(
+    context: &serenity::Context,
+    db: &JinxDb,
+    guild_id: GuildId,
+    user_id: UserId,
+    block: RegistrationBlock,
+) -> Result<(), Error> {
+    let Some(log_channel) = db.get_log_channel(guild_id).await? else {
+        return Ok(());
+    };
+    let log_locale = i18n::guild_locale(db, guild_id, None).await?;
+    let until = format!("<t:{}:t>", block.until_unix_ms.div_ceil(1000));
+    let minutes = REGISTRATION_FAILURE_WINDOW_MS / (60 * 1000);
+    let message = if block.guild_wide {
+        i18n::format(
+            log_locale,
+            Text::LogGuildRegistrationBlocked,
+            &[
+                ("count", &GUILD_REGISTRATION_FAILURE_LIMIT),
+                ("minutes", &minutes),
+                ("until", &until),
+            ],
+        )
+    } else {
+        i18n::format(
+            log_locale,
+            Text::LogUserRegistrationBlocked,
+            &[
+                ("user", &format!("<@{}>", user_id.get())),
+                ("count", &USER_REGISTRATION_FAILURE_LIMIT),
+                ("minutes", &minutes),
+                ("until", &until),
+            ],
+        )
+    };
+    let embed = CreateEmbed::default()
+        .title(i18n::text(log_locale, Text::LogRegistrationBlockedTitle))
+        .description(message)
+        .color(Colour::ORANGE);
+    send_bot_log_message(context, log_channel, CreateMessage::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Check if a user is out of their DM cooldown, starting a new one if so
+fn dm_cooldown_elapsed(user_id: UserId) -> bool {
+    let now = Instant::now();
+    DM_REPLY_TIMES.retain(|_, last| now.duration_since(*last) < DM_COOLDOWN);
+    match DM_REPLY_TIMES.entry(user_id) {
+        Entry::Occupied(_) => false,
+        Entry::Vacant(entry) => {
+            entry.insert(now);
+            true
+        }
+    }
+}
+
+/// The language every one of the guilds has set, or `None` if they don't all agree
+async fn common_locale(
+    db: &JinxDb,
+    guilds: &[(GuildId, String)],
+) -> Result<Option<&'static str>, Error> {
+    let mut common = None;
+    for (guild_id, _) in guilds {
+        let language = db.get_language(*guild_id).await?;
+        if language.is_none() || (common.is_some() && common != language) {
+            return Ok(None);
+        }
+        common = language;
+    }
+    Ok(common.map(|language| language.locale()))
+}
+
+/// Find guilds a user could register a license in from a DM: that is, guilds that both the bot and the user are in
+/// that have a Jinxxy API key set. Only guilds the user is already known in are checked: ones where they're in the
+/// member cache, and ones they've activated a license in. Discord limits select menus to 25 options, so at most that
+/// many guilds are returned.
+async fn registrable_guilds(
+    context: &serenity::Context,
+    db: &JinxDb,
+    user_id: UserId,
+) -> Result<Vec<(GuildId, String)>, Error> {
+    const MAX_SELECT_OPTIONS: usize = 25;
+    let start = Instant::now();
+
+    // cached members cost nothing to check, so they go first
+    let mut candidates: Vec<(GuildId, bool)> = context
+        .cache
+        .guilds()
+        .into_iter()
+        .filter(|guild_id| {
+            context
+                .cache
+                .guild(*guild_id)
+                .is_some_and(|guild| guild.members.contains_key(&user_id))
+        })
+        .map(|guild_id| (guild_id, true))
+        .collect();
+    for guild_id in db.get_user_guilds(user_id).await? {
+        let known = candidates
+            .iter()
+            .any(|(candidate, _)| *candidate == guild_id);
+        if !known && context.cache.guild(guild_id).is_some() {
+            candidates.push((guild_id, false));
+        }
+    }
+
+    let mut guilds = Vec::new();
+    let mut member_lookups = 0;
+    for (guild_id, cached) in candidates {
+        if db.get_jinxxy_api_key(guild_id).await?.is_none() {
+            // guild is not set up, so there's nothing to register
+            continue;
+        }
+        if !cached {
+            // they may have left since activating, which we can only tell by asking the Discord API
+            if member_lookups >= MAX_DM_MEMBER_LOOKUPS {
+                continue;
+            }
+            member_lookups += 1;
+            if guild_id.member(context, user_id).await.is_err() {
+                continue;
+            }
+        }
+        let guild_name = guild_id
+            .name(context)
+            .unwrap_or_else(|| guild_id.get().to_string());
+        guilds.push((guild_id, guild_name));
+        if guilds.len() >= MAX_SELECT_OPTIONS {
+            break;
+        }
+    }
+    debug!(
+        "found {} registrable guilds for <@{}> with {} member lookups in {}ms",
+        guilds.len(),
+        user_id.get(),
+        member_lookups,
+        start.elapsed().as_millis()
+    );
+    Ok(guilds)
+}
+
+/// Handles a user submitting the register form. See [`handle_license_registration_inner`] for the actual logic; this
+/// layer just keeps track of the registration and how long it takes.
+async fn handle_license_registration(
+    context: &serenity::Context,
+    data: &Data,
+    modal_interaction: &ModalInteraction,
+    guild_id: GuildId,
+    expected_product_id: Option<&str>,
+) -> Result<(), Error> {
+    data.api_cache.record_activity(guild_id);
+    let _task = match tasks::start(TaskKind::Registration, guild_id, modal_interaction.user.id) {
+        Ok(task) => task,
+        Err(limit) => {
+            debug!(
+                "turning away registration in {}: {:?} limit reached",
+                guild_id.get(),
+                limit
+            );
+            let locale =
+                i18n::guild_locale(&data.db, guild_id, Some(modal_interaction.locale.as_str()))
+                    .await?;
+            let embed = CreateEmbed::default()
+                .title(i18n::text(locale, Text::RegistrationBusyTitle))
+                .description(i18n::text(locale, Text::RegistrationBusy))
+                .color(Colour::ORANGE);
+            let edit = EditInteractionResponse::default().embed(embed);
+            modal_interaction.edit_response(context, edit).await?;
+            return Ok(());
+        }
+    };
+    let start = Instant::now();
+    let result = handle_license_registration_inner(
+        context,
+        data,
+        modal_interaction,
+        guild_id,
+        expected_product_id,
+    )
+    .await;
+    let elapsed = start.elapsed();
+    if elapsed > SLOW_REGISTRATION_THRESHOLD {
+        warn!(
+            "License registration took {}ms in {}",
+            elapsed.as_millis(),
+            guild_id.get()
+        );
+    } else {
+        debug!("License registration took {}ms", elapsed.as_millis());
+    }
+    result
+}
+
+/// All the license activation logic lives here.
+///
+/// `guild_id` is passed separately from the interaction, as registrations started from a DM have no guild. If
+/// `expected_product_id` is set, licenses for any other product are rejected.
+async fn handle_license_registration_inner(
+    context: &serenity::Context,
+    data: &Data,
+    modal_interaction: &ModalInteraction,
+    guild_id: GuildId,
+    expected_product_id: Option<&str>,
+) -> Result<(), Error> {
+    let license_key = modal_interaction
+        .data
+        .components
+        .iter()
+        .flat_map(|row| row.components.iter())
+        .find_map(|component| {
+            if let ActionRowComponent::InputText(input_text) = component {
+                if input_text.custom_id == LICENSE_KEY_ID {
+                    input_text
+                        .value
+                        .as_deref()
+                        .map(|value| value.trim())
+                        .filter(|value| !value.is_empty())
+                } else {
+                    None
+                }
+            } else {
+                None
+            }
+        });
+    let locale =
+        i18n::guild_locale(&data.db, guild_id, Some(modal_interaction.locale.as_str())).await?;
+    // the form may have been opened before registration became unavailable
+    if let Some(embed) =
+        registration_unavailable_embed(&data.db, guild_id, modal_interaction.user.id, locale)
+            .await?
+    {
+        let edit = EditInteractionResponse::default().embed(embed);
+        modal_interaction.edit_response(context, edit).await?;
+        return Ok(());
+    }
+    if let Some(license_key) = license_key {
+        let user_id = modal_interaction.user.id;
+        let license_type = license::identify_license(license_key);
+
+        debug!(
+            "got license in {} from <@{}> which looks like {}",
+            guild_id.get(),
+            user_id.get(),
+            license_type
+        );
+
+        // interactions from DMs don't come with a member, so we have to look it up ourselves
+        let member = if let Some(member) = &modal_interaction.member {
+            member.clone()
+        } else {
+            match guild_id.member(context, user_id).await {
+                Ok(member) => member,
+                Err(e) => {
+                    debug!(
+                        "could not find <@{}> in {} for DM registration: {:?}",
+                        user_id.get(),
+                        guild_id.get(),
+                        e
+                    );
+                    let embed = CreateEmbed::default()
+                        .title(i18n::text(locale, Text::RegistrationFailureTitle))
+                        .description(i18n::text(locale, Text::NotAMember))
+                        .color(Colour::RED);
+                    let edit = EditInteractionResponse::default().embed(embed);
+                    modal_interaction.edit_response(context, edit).await?;
+                    return Ok(());
+                }
+            }
+        };
+
+        /*
+        Generic fail message. This message is deterministic based solely on the user-provided string,
+        which prevents leaking information regarding license validity. For example, different messages
+        for different contexts could let someone distinguish between:
+        - A valid license that has already been activated by someone else
+        - A valid, previously unactivated license that was activated by someone else while going through this flow
+        - An invalid license
+        */
+        let send_fail_message = || async {
+            record_registration_failure(context, &data.db, guild_id, user_id).await?;
+            if license_type.is_license() {
+                debug!(
+                    "failed to verify license in {} for <@{}> which looks like {}",
+                    guild_id.get(),
+                    user_id.get(),
+                    license_type
+                );
+            } else {
+                // if the user gave me something that I don't believe is a license, debug print it so I can learn if there's some weird case I need to handle
+                debug!(
+                    "failed to verify license \"{}\" in {} for <@{}> which looks like {}",
+                    license_key,
+                    guild_id.get(),
+                    user_id.get(),
+                    license_type
+                );
+            }
+
+            // The phrasing may be under experiment. The variant is per-guild, not per-attempt, so it still doesn't
+            // depend on the license's validity.
+            data.db
+                .record_message_followup(
+                    guild_id,
+                    user_id.get(),
+                    MessageKey::RegistrationFailure,
+                    false,
+                )
+                .await?;
+            let variant = data
+                .db
+                .get_message_variant(guild_id, MessageKey::RegistrationFailure)
+                .await?;
+            let failure_text = variant
+                .text
+                .as_deref()
+                .unwrap_or(i18n::text(locale, Text::InvalidLicense));
+            let description = if license_type.is_jinxxy_license() {
+                failure_text.to_string()
+            } else {
+                let hint = i18n::format(
+                    locale,
+                    Text::WrongLicenseTypeHint,
+                    &[("license_type", &license_type)],
+                );
+                format!("{}.\n{}", failure_text.trim_end_matches('.'), hint)
+            };
+            let embed = CreateEmbed::default()
+                .title(i18n::text(locale, Text::RegistrationFailureTitle))
+                .description(description)
+                .color(Colour::RED);
+            let edit = EditInteractionResponse::default().embed(embed);
+            modal_interaction.edit_response(context, edit).await?;
+            data.db
+                .record_message_shown(
+                    guild_id,
+                    user_id.get(),
+                    MessageKey::RegistrationFailure,
+                    variant.variant_id,
+                )
+                .await?;
+            Ok::<(), Error>(())
+        };
+
+        if let Some(api_key) = data.db.get_jinxxy_api_key(guild_id).await? {
+            // if we're going to have to wait on other registrations, let the user know where they are in line
+            if let Some(position) = jinxxy::queue_position(&api_key) {
+                let embed = CreateEmbed::default()
+                    .title(i18n::text(locale, Text::RegistrationQueuedTitle))
+                    .description(i18n::format(
+                        locale,
+                        Text::RegistrationQueued,
+                        &[("position", &position)],
+                    ));
+                let edit = EditInteractionResponse::default().embed(embed);
+                modal_interaction.edit_response(context, edit).await?;
+            }
+
+            let license = license_type.create_untrusted_jinxxy_license(license_key);
+            let license_response = if let Some(license) = license {
+                jinxxy::check_license(&api_key, license).await?
+            } else {
+                // if the user has given us something that is very clearly not a Jinxxy license then don't even try hitting the API
+                None
+            };
+            // a license for a different product than the form was for fails the same way as an invalid one, so the
+            // failure doesn't reveal that the license is valid
+            let license_response = license_response.filter(|license_info| {
+                let expected = expected_product_id
+                    .map_or(true, |product_id| product_id == license_info.product_id);
+                if !expected {
+                    debug!(
+                        "license in {} from <@{}> is for product {}, not the claimed product",
+                        guild_id.get(),
+                        user_id.get(),
+                        license_info.product_id
+                    );
+                }
+                expected
+            });
+            // as does a subscription that has lapsed
+            let license_response = license_response.filter(|license_info| {
+                let lapsed = license_expiry::is_lapsed(license_info);
+                if lapsed {
+                    debug!(
+                        "license in {} from <@{}> is a lapsed subscription",
+                        guild_id.get(),
+                        user_id.get()
+                    );
+                }
+                !lapsed
+            });
+            // and so does one whose scheduled expiry has already passed for this user
+            let license_response = match license_response {
+                Some(license_info) => {
+                    let expired_licenses = data
+                        .db
+                        .get_expired_licenses(guild_id, user_id, license_expiry::now_unix_ms())
+                        .await?;
+                    if expired_licenses.contains(&license_info.license_id) {
                         debug!(
-                            "got license in {} from <@{}> which looks like {}",
+                            "license in {} from <@{}> is past its scheduled expiry",
                             guild_id.get(),
-                            user_id.get(),
-                            license_type
+                            user_id.get()
                         );
+                        None
+                    } else {
+                        Some(license_info)
+                    }
+                }
+                None => None,
+            };
+            if let Some(license_info) = license_response {
+                // the activation list and seat limit are independent, so look them up concurrently
+                let (activations, max_activations) = tokio::join!(
+                    async {
+                        if license_info.activations == 0 {
+                            // API call saving check: we already know how many validations there are, so if there are 0 we don't need to query them
+                            Ok(None)
+                        } else {
+                            jinxxy::get_license_activations(&api_key, &license_info.license_id)
+                                .await
+                                .map(Some)
+                        }
+                    },
+                    data.db
+                        .get_max_activations(guild_id, license_info.product_id.clone()),
+                );
+                let max_activations = max_activations?;
+                let (activations, mut validation) = match activations? {
+                    Some(activations) => {
+                        let validation =
+                            license::validate_jinxxy_license_activation(user_id, &activations);
+                        (Some(activations), validation)
+                    }
+                    None => (None, Default::default()),
+                };
 
-                        /*
-                        Generic fail message. This message is deterministic based solely on the user-provided string,
-                        which prevents leaking information regarding license validity. For example, different messages
-                        for different contexts could let someone distinguish between:
-                        - A valid license that has already been activated by someone else
-                        - A valid, previously unactivated license that was activated by someone else while going through this flow
-                        - An invalid license
-                        */
-                        let send_fail_message = || async {
-                            if license_type.is_license() {
-                                debug!(
-                                    "failed to verify license in {} for <@{}> which looks like {}",
-                                    guild_id.get(),
-                                    user_id.get(),
-                                    license_type
-                                );
-                            } else {
-                                // if the user gave me something that I don't believe is a license, debug print it so I can learn if there's some weird case I need to handle
-                                debug!("failed to verify license \"{}\" in {} for <@{}> which looks like {}", license_key, guild_id.get(), user_id.get(), license_type);
-                            }
+                // verify no activations from unexpected users beyond what the license's seats allow
+                if validation.blocked(max_activations) {
+                    // some other user has already activated this license. This is the NORMAL fail case. The other fail cases are abnormal.
 
-                            let description = if license_type.is_jinxxy_license() {
-                                "The provided license key was not valid or is already in use"
-                                    .to_string()
+                    // send a notification to the guild owner bot log if it's set up for this guild
+                    if let Some(log_channel) = data.db.get_log_channel(guild_id).await? {
+                        let log_locale = i18n::guild_locale(&data.db, guild_id, None).await?;
+                        let user = format!("<@{}>", user_id.get());
+                        let message = if validation.locked {
+                            i18n::format(log_locale, Text::LogLockedLicense, &[("user", &user)])
+                        } else {
+                            let mut message = if max_activations == 1 {
+                                i18n::format(log_locale, Text::LogLicenseUsed, &[("user", &user)])
                             } else {
-                                format!(
-                                    "The provided license key was not valid or is already in use.\n\
-                                    Hint: I expect a Jinxxy key, but you appear to have provided {}. Please confirm you are providing the correct value.",
-                                    license_type
+                                i18n::format(
+                                    log_locale,
+                                    Text::LogLicenseSeatsUsed,
+                                    &[("user", &user), ("seats", &max_activations)],
                                 )
                             };
-                            let embed = CreateEmbed::default()
-                                .title("Registration Failure")
-                                .description(description)
-                                .color(Colour::RED);
-                            let edit = EditInteractionResponse::default().embed(embed);
-                            modal_interaction.edit_response(context, edit).await?;
-                            Ok::<(), Error>(())
+                            activations
+                                .iter()
+                                .flat_map(|vec| vec.iter())
+                                .flat_map(|activation| activation.try_into_user_id())
+                                .for_each(|user_id| {
+                                    message.push_str(format!("\n- <@{}>", user_id).as_str())
+                                });
+                            message
                         };
+                        info!(
+                            "in {} for license id {}, {}",
+                            guild_id, license_info.license_id, message
+                        );
+                        let embed = CreateEmbed::default()
+                            .title(i18n::text(log_locale, Text::LogActivationFailedTitle))
+                            .description(message)
+                            .color(Colour::ORANGE);
+                        let bot_log_message = CreateMessage::default().embed(embed);
+                        send_bot_log_message(context, log_channel, bot_log_message).await?;
+                    }
 
-                        if let Some(api_key) = data.db.get_jinxxy_api_key(guild_id).await? {
-                            let license = license_type.create_untrusted_jinxxy_license(license_key);
-                            let license_response = if let Some(license) = license {
-                                jinxxy::check_license(&api_key, license).await?
-                            } else {
-                                // if the user has given us something that is very clearly not a Jinxxy license then don't even try hitting the API
-                                None
-                            };
-                            if let Some(license_info) = license_response {
-                                let member = modal_interaction
-                                    .member
-                                    .as_ref()
-                                    .ok_or_else(|| JinxError::new("expected to be in a guild"))?;
-
-                                let (activations, mut validation) = if license_info.activations == 0
-                                {
-                                    // API call saving check: we already know how many validations there are, so if there are 0 we don't need to query them
-                                    (None, Default::default())
-                                } else {
-                                    let activations = jinxxy::get_license_activations(
-                                        &api_key,
-                                        &license_info.license_id,
-                                    )
-                                    .await?;
-                                    let validation = license::validate_jinxxy_license_activation(
-                                        user_id,
-                                        &activations,
-                                    );
-                                    (Some(activations), validation)
-                                };
-
-                                // verify no activations from unexpected users
-                                if validation.other_user || validation.locked {
-                                    // some other user has already activated this license. This is the NORMAL fail case. The other fail cases are abnormal.
-
-                                    // send a notification to the guild owner bot log if it's set up for this guild
-                                    if let Some(log_channel) =
-                                        data.db.get_log_channel(guild_id).await?
-                                    {
-                                        let message = if validation.locked {
-                                            format!("<@{}> attempted to activate a locked license. An admin can unlock this license with the `/unlock_license` command.", user_id.get())
-                                        } else {
-                                            let mut message = format!("<@{}> attempted to activate a license that has already been used by:", user_id.get());
-                                            activations
-                                                .iter()
-                                                .flat_map(|vec| vec.iter())
-                                                .flat_map(|activation| {
-                                                    activation.try_into_user_id()
-                                                })
-                                                .for_each(|user_id| {
-                                                    message.push_str(
-                                                        format!("\n- <@{}>", user_id).as_str(),
-                                                    )
-                                                });
-                                            message
-                                        };
-                                        info!(
-                                            "in {} for license id {}, {}",
-                                            guild_id, license_info.license_id, message
-                                        );
-                                        let embed = CreateEmbed::default()
-                                            .title("Activation Attempt Failed")
-                                            .description(message)
-                                            .color(Colour::ORANGE);
-                                        let bot_log_message = CreateMessage::default().embed(embed);
-                                        log_channel.send_message(context, bot_log_message).await?;
-                                    }
-
-                                    send_fail_message().await?;
-                                } else {
-                                    // log if multiple activations for this user
-                                    if validation.multiple {
-                                        warn!("in {} <@{}> is about to activate {}. User already has multiple activations: {:?}", guild_id.get(), user_id.get(), license_info.license_id, activations);
-                                    }
-
-                                    // calculate if we should grant roles
-                                    let grant_roles = if validation.own_user {
-                                        // if already activated grant roles now and skip next steps
-                                        true
-                                    } else {
-                                        // we aren't activated, so we need to create the activation... and then check again to prevent race conditions
-                                        let new_activation_id = jinxxy::create_license_activation(
-                                            &api_key,
-                                            &license_info.license_id,
-                                            user_id.get(),
-                                        )
-                                        .await?;
-                                        data.db
-                                            .activate_license(
-                                                guild_id,
-                                                license_info.license_id.clone(),
-                                                new_activation_id.clone(),
-                                                user_id.get(),
-                                            )
-                                            .await?;
-                                        let activations = jinxxy::get_license_activations(
-                                            &api_key,
-                                            &license_info.license_id,
-                                        )
-                                        .await?;
-                                        validation = license::validate_jinxxy_license_activation(
-                                            user_id,
-                                            &activations,
-                                        );
-
-                                        // log if multiple activations for different users
-                                        if validation.multiple {
-                                            warn!("in {} <@{}> just activated {} via {}. User already has multiple activations: {:?}", guild_id.get(), user_id.get(), license_info.license_id, new_activation_id, activations);
-                                        }
-
-                                        // create roles if no non-us activations
-                                        !(validation.other_user || validation.locked)
-                                    };
-                                    if validation.deadlocked() {
-                                        // Two different people just race-conditioned their way to multiple activations so this license is now rendered unusable ever again.
-                                        // A moderator can use `/deactivate_license` to fix this manually.
-                                        warn!("in {} license {} is deadlocked: multiple different users have somehow managed to activate it, rendering it unusable", guild_id.get(), license_info.license_id);
-
-                                        // also send a notification to the guild owner bot log if it's set up for this guild
-                                        if let Some(log_channel) =
-                                            data.db.get_log_channel(guild_id).await?
-                                        {
-                                            let message = format!("<@{}> attempted to activate a deadlocked license. It shouldn't be possible, but multiple users have already activated this license. An admin can use the `/deactivate_license` command to fix this manually.", user_id.get());
-                                            let embed = CreateEmbed::default()
-                                                .title("Activation Error")
-                                                .description(message)
-                                                .color(Colour::RED);
-                                            let bot_log_message =
-                                                CreateMessage::default().embed(embed);
-                                            log_channel
-                                                .send_message(context, bot_log_message)
-                                                .await?;
-                                        }
-                                    }
-
-                                    if grant_roles {
-                                        let roles = data
-                                            .db
-                                            .get_roles(guild_id, license_info.product_id)
-                                            .await?;
-                                        let mut client_message = format!("Congratulations, you are now registered as an owner of the {} product and have been granted the following roles:", license_info.product_name);
-                                        let mut owner_message = format!("<@{}> has registered the {} product and has been granted the following roles:", user_id.get(), license_info.product_name);
-                                        let mut errors: String = String::new();
-                                        for role in roles {
-                                            match member.add_role(context, role).await {
-                                                Ok(()) => {
-                                                    let bullet_point =
-                                                        format!("\n- <@&{}>", role.get());
-                                                    client_message.push_str(bullet_point.as_str());
-                                                    owner_message.push_str(bullet_point.as_str());
-                                                }
-                                                Err(e) => {
-                                                    errors.push_str(
-                                                        format!("\n- <@&{}>", role.get()).as_str(),
-                                                    );
-                                                    warn!(
-                                                        "in {} error granting role: {:?}",
-                                                        guild_id.get(),
-                                                        e
-                                                    );
-                                                }
-                                            }
-                                        }
-                                        let embed = if errors.is_empty() {
-                                            CreateEmbed::default()
-                                                .title("Registration Success")
-                                                .description(client_message)
-                                                .color(Colour::DARK_GREEN)
-                                        } else {
-                                            let message = format!("{}\n\nFailed to grant access to roles:{}\nThe bot may lack permission to grant the above roles. Contact your server administrator for support.", client_message, errors);
-                                            CreateEmbed::default()
-                                                .title("Registration Partial Success")
-                                                .description(message)
-                                                .color(Colour::ORANGE)
-                                        };
-
-                                        /*
-                                        Let the user know what happened.
-                                        Note that this can fail if the interaction has been invalidated, which happens in some cases:
-                                        - 3s after a non-acked interaction
-                                        - 15m after an acked interaction
-                                         */
-                                        let edit = EditInteractionResponse::default().embed(embed);
-                                        let user_notification_result =
-                                            modal_interaction.edit_response(context, edit).await;
-                                        if let Err(error) = user_notification_result {
-                                            error!(
-                                                "Error notifying user of license activation: {:?}",
-                                                error
-                                            );
-                                        }
-
-                                        // also send a notification to the guild owner bot log if it's set up for this guild
-                                        if let Some(log_channel) =
-                                            data.db.get_log_channel(guild_id).await?
-                                        {
-                                            let embed = CreateEmbed::default()
-                                                .title("License Activation")
-                                                .description(owner_message);
-                                            let bot_log_message =
-                                                CreateMessage::default().embed(embed);
-                                            let bot_log_message = if errors.is_empty() {
-                                                bot_log_message
-                                            } else {
-                                                let error_embed = CreateEmbed::default()
-                                                    .title("Role Grant Error")
-                                                    .description(format!("Failed to grant <@{}> access to the following roles:{}\nPlease check bot permissions.", user_id.get(), errors))
-                                                    .color(Colour::RED);
-                                                bot_log_message.embed(error_embed)
-                                            };
-                                            log_channel
-                                                .send_message(context, bot_log_message)
-                                                .await?;
-                                        }
-                                    } else {
-                                        // license activation check failed. This happens if we created an activation but the double check failed due to finding a second user's activation.
-                                        send_fail_message().await?;
-                                    }
-                                }
-                            } else {
-                                // could not find a matching license in Jinxxy
-                                send_fail_message().await?;
-                            }
-                        } else {
+                    send_fail_message().await?;
+                } else {
+                    // log if multiple activations for this user
+                    if validation.multiple {
+                        warn!("in {} <@{}> is about to activate {}. User already has multiple activations: {:?}", guild_id.get(), user_id.get(), license_info.license_id, activations);
+                    }
+
+                    let new_activation = !validation.own_user;
+                    // calculate if we should grant roles
+                    let grant_roles = if validation.own_user {
+                        // if already activated grant roles now and skip next steps
+                        true
+                    } else {
+                        // we aren't activated, so we need to create the activation... and then check again to prevent race conditions
+                        let new_activation_id = jinxxy::create_license_activation(
+                            &api_key,
+                            &license_info.license_id,
+                            user_id.get(),
+                        )
+                        .await?;
+                        // recording the activation locally and re-checking remotely are independent
+                        let (activate_result, activations) = tokio::join!(
+                            data.db.activate_license(
+                                guild_id,
+                                license_info.license_id.clone(),
+                                new_activation_id.clone(),
+                                user_id.get(),
+                            ),
+                            jinxxy::get_license_activations(&api_key, &license_info.license_id),
+                        );
+                        activate_result?;
+                        let activations = activations?;
+                        data.db
+                            .record_license_registration(
+                                guild_id,
+                                license_info.license_id.clone(),
+                                user_id.get(),
+                                license_info.user_id.clone(),
+                            )
+                            .await?;
+                        validation =
+                            license::validate_jinxxy_license_activation(user_id, &activations);
+
+                        // log if multiple activations for different users
+                        if validation.multiple {
+                            warn!("in {} <@{}> just activated {} via {}. User already has multiple activations: {:?}", guild_id.get(), user_id.get(), license_info.license_id, new_activation_id, activations);
+                        }
+
+                        // create roles if there were enough seats for us
+                        !validation.blocked(max_activations)
+                    };
+                    if validation.deadlocked(max_activations) {
+                        // Two different people just race-conditioned their way to multiple activations so this license is now rendered unusable ever again.
+                        // A moderator can use `/deactivate_license` to fix this manually.
+                        warn!("in {} license {} is deadlocked: more different users than the license has seats ({}) have somehow managed to activate it, rendering it unusable", guild_id.get(), license_info.license_id, max_activations);
+
+                        // also send a notification to the guild owner bot log if it's set up for this guild
+                        if let Some(log_channel) = data.db.get_log_channel(guild_id).await? {
+                            let log_locale = i18n::guild_locale(&data.db, guild_id, None).await?;
+                            let message = i18n::format(
+                                log_locale,
+                                Text::LogDeadlockedLicense,
+                                &[("user", &format!("<@{}>", user_id.get()))],
+                            );
                             let embed = CreateEmbed::default()
-                                .title("Jinx Misconfiguration")
-                                .description("Jinxxy API key is not set: please contact the server administrator for support.")
+                                .title(i18n::text(log_locale, Text::LogActivationErrorTitle))
+                                .description(message)
                                 .color(Colour::RED);
-                            let edit = EditInteractionResponse::default().embed(embed);
-                            modal_interaction.edit_response(context, edit).await?;
+                            let bot_log_message = CreateMessage::default().embed(embed);
+                            send_bot_log_message(context, log_channel, bot_log_message).await?;
                         }
+                    }
+
+                    if grant_roles {
+                        finish_registration(
+                            context,
+                            data,
+                            modal_interaction,
+                            &member,
+                            guild_id,
+                            &license_info,
+                            new_activation,
+                        )
+                        .await?;
                     } else {
-                        // User did not provide a license string, or provided all whitespace or something weird like that.
-                        let embed = CreateEmbed::default()
-                            .title("Registration Failure")
-                            .description("You must provide a license key")
-                            .color(Colour::RED);
-                        let edit = EditInteractionResponse::default().embed(embed);
-                        modal_interaction.edit_response(context, edit).await?;
+                        // license activation check failed. This happens if we created an activation but the double check failed due to finding a second user's activation.
+                        send_fail_message().await?;
                     }
                 }
-                _ => {}
+            } else {
+                // could not find a matching license in Jinxxy
+                send_fail_message().await?;
             }
+        } else {
+            let embed = CreateEmbed::default()
+                .title(i18n::text(locale, Text::MisconfigurationTitle))
+                .description(i18n::text(locale, Text::MissingApiKey))
+                .color(Colour::RED);
+            let edit = EditInteractionResponse::default().embed(embed);
+            modal_interaction.edit_response(context, edit).await?;
         }
-        FullEvent::InteractionCreate {
-            interaction: Interaction::Command(command_interaction),
-        } => {
-            debug!(
-                "command \"{}\" invoked in {:?} by <@{}>",
-                command_interaction.data.name,
-                command_interaction.guild_id.map(|guild| guild.get()),
-                command_interaction.user.id.get()
-            );
+    } else {
+        // User did not provide a license string, or provided all whitespace or something weird like that.
+        let embed = CreateEmbed::default()
+            .title(i18n::text(locale, Text::RegistrationFailureTitle))
+            .description(i18n::text(locale, Text::MissingLicenseKey))
+            .color(Colour::RED);
+        let edit = EditInteractionResponse::default().embed(embed);
+        modal_interaction.edit_response(context, edit).await?;
+    }
+
+    Ok(())
+}
+
+/// Grant roles for a license the user now holds, start its activation hooks, then tell the user and the log channel what
+/// happened
+async fn finish_registration(
+    context: &serenity::Context,
+    data: &Data,
+    modal_interaction: &ModalInteraction,
+    member: &Member,
+    guild_id: GuildId,
+    license_info: &LicenseInfo,
+    new_activation: bool,
+) -> Result<(), Error> {
+    let user_id = member.user.id;
+    if let Some(expires_unix_ms) = license_expiry::expires_unix_ms(license_info) {
+        data.db
+            .set_license_expiry(
+                guild_id,
+                license_info.license_id.clone(),
+                license_info.product_id.clone(),
+                license_info.product_version_id.clone(),
+                expires_unix_ms,
+            )
+            .await?;
+    }
+    data.db
+        .record_message_followup(
+            guild_id,
+            user_id.get(),
+            MessageKey::RegistrationFailure,
+            true,
+        )
+        .await?;
+    let roles = data
+        .db
+        .get_role_grants(
+            guild_id,
+            license_info.product_id.clone(),
+            license_info.product_version_id.clone(),
+        )
+        .await?;
+    let locale =
+        i18n::guild_locale(&data.db, guild_id, Some(modal_interaction.locale.as_str())).await?;
+    let log_locale = i18n::guild_locale(&data.db, guild_id, None).await?;
+    let user = format!("<@{}>", user_id.get());
+    let product_name = license_info.product_name.safe_display();
+    let mut client_message = i18n::format(
+        locale,
+        Text::RegistrationSuccess,
+        &[("product", &product_name)],
+    );
+    let mut owner_message = i18n::format(
+        log_locale,
+        Text::LogActivation,
+        &[("user", &user), ("product", &product_name)],
+    );
+    let mut errors: String = String::new();
+    let mut granted_roles = Vec::new();
+    for role in roles {
+        match member.add_role(context, role).await {
+            Ok(()) => {
+                granted_roles.push(role);
+                let bullet_point = format!("\n- <@&{}>", role.get());
+                client_message.push_str(bullet_point.as_str());
+                owner_message.push_str(bullet_point.as_str());
+                let audit_entry = AuditLogEntry::new(AuditAction::RoleGrant)
+                    .actor(user_id)
+                    .user(user_id)
+                    .product(license_info.product_id.clone())
+                    .role(role)
+                    .license(license_info.license_id.clone());
+                data.db.audit(guild_id, audit_entry).await?;
+            }
+            Err(e) => {
+                errors.push_str(format!("\n- <@&{}>", role.get()).as_str());
+                warn!("in {} error granting role: {:?}", guild_id.get(), e);
+            }
         }
-        _ => {}
     }
+    let embed = if errors.is_empty() {
+        CreateEmbed::default()
+            .title(i18n::text(locale, Text::RegistrationSuccessTitle))
+            .description(client_message)
+            .color(Colour::DARK_GREEN)
+    } else {
+        let failure = i18n::format(locale, Text::RoleGrantFailure, &[("roles", &errors)]);
+        let message = format!("{}\n\n{}", client_message, failure);
+        CreateEmbed::default()
+            .title(i18n::text(locale, Text::RegistrationPartialSuccessTitle))
+            .description(message)
+            .color(Colour::ORANGE)
+    };
+    let thumbnail_url = data
+        .db
+        .get_product_details(guild_id, license_info.product_id.clone())
+        .await?
+        .thumbnail_url;
+    let embed = if let Some(thumbnail_url) = &thumbnail_url {
+        embed.thumbnail(thumbnail_url)
+    } else {
+        embed
+    };
 
+    /*
+    Let the user know what happened.
+    Note that this can fail if the interaction has been invalidated, which happens in some cases:
+    - 3s after a non-acked interaction
+    - 15m after an acked interaction
+     */
+    let edit = EditInteractionResponse::default().embed(embed);
+    let user_notification_result = modal_interaction.edit_response(context, edit).await;
+    if let Err(error) = user_notification_result {
+        error!("Error notifying user of license activation: {:?}", error);
+    }
+
+    // hooks run in the background after the user has been told about their roles, so slow hooks don't keep them waiting
+    let activation = activation_hooks::Activation {
+        guild_id,
+        member: member.clone(),
+        license_info: license_info.clone(),
+        new_activation,
+    };
+    activation_hooks::spawn(context, data.db.clone(), activation);
+
+    if new_activation {
+        let activation_count = data
+            .db
+            .increment_product_activation_count(guild_id, license_info.product_id.clone())
+            .await?;
+        let celebration_result = async {
+            milestones::celebrate_product(
+                context,
+                data,
+                guild_id,
+                license_info.product_id.clone(),
+                &license_info.product_name,
+                activation_count,
+            )
+            .await?;
+            milestones::celebrate_guild(context, data, guild_id).await
+        }
+        .await;
+        if let Err(e) = celebration_result {
+            warn!("in {} error posting milestone: {:?}", guild_id.get(), e);
+        }
+    }
+
+    // also send a notification to the guild owner bot log if it's set up for this guild
+    if let Some(log_channel) = data.db.get_log_channel(guild_id).await? {
+        let mut embeds = Vec::new();
+        if data.db.get_notification_digest(guild_id).await? == NotificationDigest::Off {
+            let embed = CreateEmbed::default()
+                .title(i18n::text(log_locale, Text::LogActivationTitle))
+                .description(owner_message);
+            let embed = if let Some(thumbnail_url) = thumbnail_url {
+                embed.thumbnail(thumbnail_url)
+            } else {
+                embed
+            };
+            embeds.push(embed);
+        } else {
+            // the activation waits for the next digest, but the error below still needs attention now
+            let entry = DigestEntry {
+                created_unix_ms: notification_digest::now_unix_ms(),
+                user_id,
+                product_name: license_info.product_name.clone(),
+                roles: granted_roles,
+            };
+            data.db.add_digest_entry(guild_id, entry).await?;
+        }
+        if !errors.is_empty() {
+            let error_embed = CreateEmbed::default()
+                .title(i18n::text(log_locale, Text::LogRoleGrantErrorTitle))
+                .description(i18n::format(
+                    log_locale,
+                    Text::LogRoleGrantError,
+                    &[("user", &user), ("roles", &errors)],
+                ))
+                .color(Colour::RED);
+            embeds.push(error_embed);
+        }
+        if !embeds.is_empty() {
+            let bot_log_message = CreateMessage::default().embeds(embeds);
+            send_bot_log_message(context, log_channel, bot_log_message).await?;
+        }
+    }
+    Ok(())
+}
+
+/// If enabled for the guild, give a rejoining user back the roles from the licenses they've activated there
+async fn restore_member_roles(
+    context: &serenity::Context,
+    data: &Data,
+    member: &Member,
+) -> Result<(), Error> {
+    let guild_id = member.guild_id;
+    let user_id = member.user.id;
+    if !data.db.get_restore_roles(guild_id).await? {
+        return Ok(());
+    }
+    let license_ids = data.db.get_user_licenses(guild_id, user_id.get()).await?;
+    if license_ids.is_empty() {
+        return Ok(());
+    }
+    let Some(api_key) = data.db.get_jinxxy_api_key(guild_id).await? else {
+        return Ok(());
+    };
+
+    // local records only have the license, so we need Jinxxy to tell us what product (and version) it's for
+    let mut granted_roles: HashSet<RoleId> = HashSet::new();
+    let log_locale = i18n::guild_locale(&data.db, guild_id, None).await?;
+    let user = format!("<@{}>", user_id.get());
+    let mut message = i18n::format(log_locale, Text::LogRolesRestored, &[("user", &user)]);
+    let mut errors = String::new();
+    for license_id in license_ids {
+        let Some(license_info) = jinxxy::check_license_id(&api_key, &license_id).await? else {
+            // the license was deleted from Jinxxy since it was activated, so it no longer grants anything
+            continue;
+        };
+        let roles = data
+            .db
+            .get_role_grants(
+                guild_id,
+                license_info.product_id.clone(),
+                license_info.product_version_id,
+            )
+            .await?;
+        for role in roles {
+            if !granted_roles.insert(role) {
+                continue;
+            }
+            match member.add_role(context, role).await {
+                Ok(()) => {
+                    message.push_str(format!("\n- <@&{}>", role.get()).as_str());
+                    let audit_entry = AuditLogEntry::new(AuditAction::RoleGrant)
+                        .user(user_id)
+                        .product(license_info.product_id.clone())
+                        .role(role)
+                        .license(license_id.clone())
+                        .detail("restored on rejoin");
+                    data.db.audit(guild_id, audit_entry).await?;
+                }
+                Err(e) => {
+                    errors.push_str(format!("\n- <@&{}>", role.get()).as_str());
+                    warn!("in {} error restoring role: {:?}", guild_id.get(), e);
+                }
+            }
+        }
+    }
+
+    if granted_roles.is_empty() {
+        return Ok(());
+    }
+    if let Some(log_channel) = data.db.get_log_channel(guild_id).await? {
+        let embed = CreateEmbed::default()
+            .title(i18n::text(log_locale, Text::LogRolesRestoredTitle))
+            .description(message);
+        let bot_log_message = CreateMessage::default().embed(embed);
+        let bot_log_message = if errors.is_empty() {
+            bot_log_message
+        } else {
+            let error_embed = CreateEmbed::default()
+                .title(i18n::text(log_locale, Text::LogRoleGrantErrorTitle))
+                .description(i18n::format(
+                    log_locale,
+                    Text::LogRoleRestoreError,
+                    &[("user", &user), ("roles", &errors)],
+                ))
+                .color(Colour::RED);
+            bot_log_message.embed(error_embed)
+        };
+        send_bot_log_message(context, log_channel, bot_log_message).await?;
+    }
+    Ok(())
+}
+
+/// If enabled for the guild, log a user with activated licenses leaving it, along with which licensed roles they held
+async fn log_member_leave(
+    context: &serenity::Context,
+    data: &Data,
+    guild_id: GuildId,
+    user_id: UserId,
+    member: Option<&Member>,
+) -> Result<(), Error> {
+    if !data.db.get_log_member_leave(guild_id).await? {
+        return Ok(());
+    }
+    let Some(log_channel) = data.db.get_log_channel(guild_id).await? else {
+        return Ok(());
+    };
+    let license_count = data
+        .db
+        .get_user_licenses(guild_id, user_id.get())
+        .await?
+        .len();
+    if license_count == 0 {
+        return Ok(());
+    }
+
+    let log_locale = i18n::guild_locale(&data.db, guild_id, None).await?;
+    let mut message = i18n::format(
+        log_locale,
+        Text::LogMemberLeft,
+        &[
+            ("user", &format!("<@{}>", user_id.get())),
+            ("count", &license_count),
+        ],
+    );
+    // member data is only available if the member happened to be cached
+    if let Some(member) = member {
+        let mut licensed_roles: HashSet<RoleId> = data
+            .db
+            .get_links(guild_id)
+            .await?
+            .into_iter()
+            .map(|(_, role)| role)
+            .collect();
+        licensed_roles.extend(data.db.get_blanket_role(guild_id).await?);
+        let held_roles: Vec<RoleId> = member
+            .roles
+            .iter()
+            .copied()
+            .filter(|role| licensed_roles.contains(role))
+            .collect();
+        if held_roles.is_empty() {
+            message.push(' ');
+            message.push_str(i18n::text(log_locale, Text::LogMemberLeftNoRoles));
+        } else {
+            message.push(' ');
+            message.push_str(i18n::text(log_locale, Text::LogMemberLeftRoles));
+            for role in held_roles {
+                message.push_str(format!("\n- <@&{}>", role.get()).as_str());
+            }
+        }
+    }
+    let embed = CreateEmbed::default()
+        .title(i18n::text(log_locale, Text::LogMemberLeftTitle))
+        .description(message);
+    send_bot_log_message(context, log_channel, CreateMessage::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// When the bot is re-added to a guild whose data was restored, check if its product links survived. Roles deleted
+/// while we were away leave links that can't grant anything, so if that happened (or no links are left at all) post a
+/// summary in the system channel with a button to start re-linking, rather than silently carrying on.
+async fn post_rejoin_assistant(
+    context: &serenity::Context,
+    data: &Data,
+    guild: &serenity::Guild,
+) -> Result<(), Error> {
+    let Some(channel_id) = guild.system_channel_id else {
+        return Ok(());
+    };
+    if data.db.get_jinxxy_api_key(guild.id).await?.is_none() {
+        // the store was never set up, so there's nothing to re-link
+        return Ok(());
+    }
+    let links = data.db.get_links(guild.id).await?;
+    let lost_links: Vec<&(String, RoleId)> = links
+        .iter()
+        .filter(|(_, role)| !guild.roles.contains_key(role))
+        .collect();
+    if !links.is_empty() && lost_links.is_empty() {
+        return Ok(());
+    }
+
+    let locale = i18n::guild_locale(&data.db, guild.id, None).await?;
+    let mut description = if links.is_empty() {
+        i18n::text(locale, Text::RejoinNoLinks).to_string()
+    } else {
+        let product_names: HashMap<String, String> =
+            data.db.get_products(guild.id).await?.into_iter().collect();
+        let mut description = i18n::format(
+            locale,
+            Text::RejoinLostLinks,
+            &[("count", &lost_links.len())],
+        );
+        for (product_id, role) in lost_links.iter().take(REJOIN_PRODUCT_LIST_LIMIT) {
+            let product_name = product_names.get(product_id).unwrap_or(product_id);
+            description.push_str("\n- ");
+            description.push_str(&i18n::format(
+                locale,
+                Text::RejoinLostLink,
+                &[
+                    ("product", &product_name.safe_display()),
+                    ("role", &format!("`{}`", role.get())),
+                ],
+            ));
+        }
+        if lost_links.len() > REJOIN_PRODUCT_LIST_LIMIT {
+            description.push_str("\n- ");
+            description.push_str(&i18n::format(
+                locale,
+                Text::AndMore,
+                &[("count", &(lost_links.len() - REJOIN_PRODUCT_LIST_LIMIT))],
+            ));
+        }
+        description
+    };
+    description.push_str("\n\n");
+    description.push_str(i18n::text(locale, Text::RejoinRelinkPrompt));
+
+    let embed = CreateEmbed::default()
+        .title(i18n::text(locale, Text::RejoinTitle))
+        .description(description)
+        .color(Colour::ORANGE);
+    let components = vec![CreateActionRow::Buttons(vec![CreateButton::new(
+        REJOIN_RELINK_BUTTON_ID,
+    )
+    .label(i18n::text(locale, Text::RelinkTitle))
+    .style(ButtonStyle::Primary)])];
+    channel_id
+        .send_message(
+            context,
+            CreateMessage::default().embed(embed).components(components),
+        )
+        .await?;
+    Ok(())
+}
+
+/// List the products that aren't linked to any existing role, for the rejoin assistant's button. Only members who could
+/// link products themselves get the list.
+async fn rejoin_relink_response(
+    context: &serenity::Context,
+    data: &Data,
+    component_interaction: &ComponentInteraction,
+) -> Result<CreateInteractionResponse, Error> {
+    let can_manage_roles = component_interaction
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| permissions.manage_roles());
+    let user_locale = Some(component_interaction.locale.as_str());
+    let (Some(guild_id), true) = (component_interaction.guild_id, can_manage_roles) else {
+        let embed = CreateEmbed::default()
+            .title(i18n::text(user_locale, Text::RelinkTitle))
+            .description(i18n::text(user_locale, Text::RelinkNoPermission))
+            .color(Colour::RED);
+        return Ok(CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .ephemeral(true)
+                .embed(embed),
+        ));
+    };
+
+    let existing_roles: HashSet<RoleId> = guild_id
+        .to_guild_cached(context)
+        .map(|guild| guild.roles.keys().copied().collect())
+        .unwrap_or_default();
+    let linked_products: HashSet<String> = data
+        .db
+        .get_links(guild_id)
+        .await?
+        .into_iter()
+        .filter(|(_, role)| existing_roles.contains(role))
+        .map(|(product_id, _)| product_id)
+        .collect();
+    let unlinked_products: Vec<String> = data
+        .db
+        .get_products(guild_id)
+        .await?
+        .into_iter()
+        .filter(|(product_id, _)| !linked_products.contains(product_id))
+        .map(|(_, product_name)| product_name)
+        .collect();
+
+    let locale = i18n::guild_locale(&data.db, guild_id, user_locale).await?;
+    let description = if unlinked_products.is_empty() {
+        i18n::text(locale, Text::RelinkAllLinked).to_string()
+    } else {
+        let mut description = i18n::text(locale, Text::RelinkUnlinked).to_string();
+        for product_name in unlinked_products.iter().take(REJOIN_PRODUCT_LIST_LIMIT) {
+            description.push_str(format!("\n- {}", product_name.safe_display()).as_str());
+        }
+        if unlinked_products.len() > REJOIN_PRODUCT_LIST_LIMIT {
+            description.push_str("\n- ");
+            description.push_str(&i18n::format(
+                locale,
+                Text::AndMore,
+                &[(
+                    "count",
+                    &(unlinked_products.len() - REJOIN_PRODUCT_LIST_LIMIT),
+                )],
+            ));
+        }
+        description.push_str("\n\n");
+        description.push_str(i18n::text(locale, Text::RelinkInstructions));
+        description
+    };
+    let embed = CreateEmbed::default()
+        .title(i18n::text(locale, Text::RelinkTitle))
+        .description(description);
+    Ok(CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .ephemeral(true)
+            .embed(embed),
+    ))
+}
+
+/// Run the setup preflight checks for a guild with a store, and post any problems to its bot log channel. If that
+/// doesn't work, they go to the system channel instead.
+async fn report_setup_problems(
+    context: &serenity::Context,
+    data: &Data,
+    guild: &serenity::Guild,
+) -> Result<(), Error> {
+    if data.db.get_jinxxy_api_key(guild.id).await?.is_none() {
+        // nothing is set up yet, so there's nothing to check
+        return Ok(());
+    }
+    let locale = i18n::guild_locale(&data.db, guild.id, None).await?;
+    let problems = verification::verify(locale, context, &data.db, guild.id).await?;
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let embed = CreateEmbed::default()
+        .title(i18n::text(locale, Text::SetupProblemsTitle))
+        .description(verification::problems_message(locale, &problems))
+        .color(Colour::ORANGE);
+    let message = CreateMessage::default().embed(embed);
+    if let Some(log_channel) = data.db.get_log_channel(guild.id).await? {
+        if send_bot_log_message(context, log_channel, message.clone())
+            .await
+            .is_ok()
+        {
+            return Ok(());
+        }
+    }
+    if let Some(system_channel) = guild.system_channel_id {
+        send_bot_log_message(context, system_channel, message).await?;
+    }
     Ok(())
 }
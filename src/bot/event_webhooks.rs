@@ -0,0 +1,159 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Delivery of guild event webhooks, which push every license activation and deactivation to an external system.
+//!
+//! Deliveries are driven by the event stream: each webhook keeps a cursor at the last event it's done with, and a
+//! background task posts whatever comes after it, in order. A failed delivery is retried with exponential backoff. An
+//! event that still can't be delivered after [`MAX_ATTEMPTS`] tries is skipped so it doesn't hold up the rest, and the
+//! guild's bot log channel is told about it.
+
+use crate::bot::i18n;
+use crate::bot::i18n::Text;
+use crate::bot::util::send_bot_log_message;
+use crate::bot::Error;
+use crate::db::{EventKind, EventWebhook, JinxDb};
+use crate::http::webhook;
+use poise::serenity_prelude as serenity;
+use serenity::{Colour, CreateEmbed, CreateMessage};
+use tokio::time::Duration;
+use tracing::warn;
+
+/// Most events delivered to a single webhook per run of the delivery task
+const BATCH_SIZE: u64 = 50;
+
+/// How long to wait before retrying a failed delivery. This doubles after each further failure.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Longest wait between retries
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// Attempts to deliver an event before giving up on it. With the backoff above this is a little over three hours.
+const MAX_ATTEMPTS: u32 = 10;
+
+/// Deliver new events to every event webhook that isn't waiting to retry. Failures are logged and skipped.
+pub async fn deliver_all(context: &serenity::Context, db: &JinxDb) {
+    let webhooks = match db.get_due_event_webhooks().await {
+        Ok(webhooks) => webhooks,
+        Err(e) => {
+            warn!("Error reading event webhooks: {:?}", e);
+            return;
+        }
+    };
+    for webhook in webhooks {
+        if let Err(e) = deliver(context, db, &webhook).await {
+            warn!(
+                "Error delivering event webhooks in {}: {:?}",
+                webhook.guild_id.get(),
+                e
+            );
+        }
+    }
+}
+
+/// Deliver events after the webhook's cursor, stopping at the first failure
+async fn deliver(
+    context: &serenity::Context,
+    db: &JinxDb,
+    event_webhook: &EventWebhook,
+) -> Result<(), Error> {
+    let guild_id = event_webhook.guild_id;
+    let events = db
+        .get_guild_activation_events(guild_id, event_webhook.cursor, BATCH_SIZE)
+        .await?;
+    let mut failure_count = event_webhook.failure_count;
+    for event in events {
+        let event_name = match event.kind {
+            EventKind::Activation => "license_activated",
+            EventKind::Deactivation => "license_deactivated",
+            EventKind::Link | EventKind::Unlink => continue,
+        };
+        let payload = webhook::EventPayload {
+            event: event_name,
+            sequence: event.sequence,
+            created_unix_ms: event.created_unix_ms,
+            guild_id: guild_id.get().to_string(),
+            user_id: event.user_id.map(|user_id| user_id.to_string()),
+            license_id: event.license_id,
+        };
+        match webhook::post_event(&event_webhook.url, &event_webhook.signing_secret, &payload).await
+        {
+            Ok(()) => {
+                db.advance_event_webhook(guild_id, event.sequence).await?;
+                failure_count = 0;
+            }
+            Err(e) => {
+                failure_count += 1;
+                warn!(
+                    "in {} event webhook delivery of event {} failed (attempt {}): {:?}",
+                    guild_id.get(),
+                    event.sequence,
+                    failure_count,
+                    e
+                );
+                if failure_count >= MAX_ATTEMPTS {
+                    db.advance_event_webhook(guild_id, event.sequence).await?;
+                    notify_dropped(context, db, event_webhook, event.sequence).await?;
+                } else {
+                    db.record_event_webhook_failure(guild_id, backoff(failure_count))
+                        .await?;
+                }
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// How long to wait before the next attempt, after `failure_count` failed attempts in a row
+fn backoff(failure_count: u32) -> Duration {
+    INITIAL_BACKOFF
+        .saturating_mul(2u32.saturating_pow(failure_count.saturating_sub(1)))
+        .min(MAX_BACKOFF)
+}
+
+/// Let the guild know an event was given up on, if it has a bot log channel
+async fn notify_dropped(
+    context: &serenity::Context,
+    db: &JinxDb,
+    event_webhook: &EventWebhook,
+    sequence: u64,
+) -> Result<(), Error> {
+    let Some(log_channel) = db.get_log_channel(event_webhook.guild_id).await? else {
+        return Ok(());
+    };
+    let locale = i18n::guild_locale(db, event_webhook.guild_id, None).await?;
+    // webhook URLs often have a secret in their path, so only show the host
+    let host = reqwest::Url::parse(&event_webhook.url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_else(|| i18n::text(locale, Text::InvalidUrl).to_string());
+    let embed = CreateEmbed::default()
+        .title(i18n::text(locale, Text::LogEventWebhookFailedTitle))
+        .description(i18n::format(
+            locale,
+            Text::LogEventWebhookFailed,
+            &[
+                ("sequence", &sequence),
+                ("host", &host),
+                ("attempts", &MAX_ATTEMPTS),
+            ],
+        ))
+        .color(Colour::RED);
+    send_bot_log_message(context, log_channel, CreateMessage::default().embed(embed)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(1), INITIAL_BACKOFF);
+        assert_eq!(backoff(2), INITIAL_BACKOFF * 2);
+        assert_eq!(backoff(4), INITIAL_BACKOFF * 8);
+        assert_eq!(backoff(MAX_ATTEMPTS), MAX_BACKOFF);
+        assert_eq!(backoff(u32::MAX), MAX_BACKOFF);
+    }
+}
@@ -0,0 +1,856 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Translations of the messages buyers see while registering, of command replies, and of the bot log.
+//!
+//! Buyers see messages in the locale Discord sends with each interaction, if there's a catalog for it. Otherwise they see
+//! messages in the language the guild picked with `/set_language`, which is also the language of the bot log and of
+//! anything else posted publicly, such as registration posts, falling back to English. Command replies work the same way
+//! as registration messages for whoever ran the command.
+//!
+//! Each locale is a catalog file in `src/bot/locales`, with one `key = "value"` per line much like the config file, and
+//! `\n` for line breaks. Placeholders such as `{product}` are filled in by the bot. Catalogs may leave out messages, which
+//! then fall back to English. To add a locale, add a catalog named after a Discord locale (or just its language, like
+//! `es` for both `es-ES` and `es-419`), list it in [`CATALOGS`], and add it to [`crate::db::Language`] so guilds can
+//! pick it. To add a message, add a [`Text`] variant and an English translation.
+
+use crate::bot::{Context, Error};
+use crate::db::JinxDb;
+use poise::serenity_prelude::GuildId;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::LazyLock;
+
+/// Locale used when a message has no translation for the requested locale
+const FALLBACK_LOCALE: &str = "en";
+
+/// Message catalogs, by locale
+const CATALOGS: [(&str, &str); 2] = [
+    ("en", include_str!("locales/en.toml")),
+    ("es", include_str!("locales/es.toml")),
+];
+
+static PARSED_CATALOGS: LazyLock<HashMap<&'static str, HashMap<String, String>>> =
+    LazyLock::new(|| {
+        CATALOGS
+            .into_iter()
+            .map(|(locale, catalog)| {
+                // catalogs are checked by tests, so this only fails if someone skips them
+                let catalog = parse(catalog)
+                    .unwrap_or_else(|e| panic!("invalid {locale} message catalog: {e}"));
+                (locale, catalog)
+            })
+            .collect()
+    });
+
+/// Declares [`Text`] along with each message's catalog key
+macro_rules! texts {
+    ($($variant:ident => $key:literal,)*) => {
+        /// A translatable message
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum Text {
+            $($variant,)*
+        }
+
+        impl Text {
+            #[cfg(test)]
+            const ALL: &'static [Text] = &[$(Text::$variant,)*];
+
+            /// Key of this message in the catalogs
+            fn key(self) -> &'static str {
+                match self {
+                    $(Text::$variant => $key,)*
+                }
+            }
+        }
+    };
+}
+
+texts! {
+    RegistrationTitle => "registration_title",
+    LicenseKeyLabel => "license_key_label",
+    RegistrationFailureTitle => "registration_failure_title",
+    NotAMember => "not_a_member",
+    InvalidLicense => "invalid_license",
+    WrongLicenseTypeHint => "wrong_license_type_hint",
+    MissingLicenseKey => "missing_license_key",
+    RegistrationQueuedTitle => "registration_queued_title",
+    RegistrationQueued => "registration_queued",
+    MisconfigurationTitle => "misconfiguration_title",
+    MissingApiKey => "missing_api_key",
+    RegistrationPausedTitle => "registration_paused_title",
+    RegistrationPaused => "registration_paused",
+    RegistrationIncidentTitle => "registration_incident_title",
+    RegistrationIncident => "registration_incident",
+    RestartingTitle => "restarting_title",
+    Restarting => "restarting",
+    RegistrationBusyTitle => "registration_busy_title",
+    RegistrationBusy => "registration_busy",
+    RegistrationRateLimitedTitle => "registration_rate_limited_title",
+    RegistrationRateLimited => "registration_rate_limited",
+    RegistrationGuildRateLimited => "registration_guild_rate_limited",
+    RegistrationSuccessTitle => "registration_success_title",
+    RegistrationSuccess => "registration_success",
+    RegistrationPartialSuccessTitle => "registration_partial_success_title",
+    RoleGrantFailure => "role_grant_failure",
+    RolePreviewTitle => "role_preview_title",
+    RolePreview => "role_preview",
+    RolePreviewNoRoles => "role_preview_no_roles",
+    RolePreviewFooter => "role_preview_footer",
+    RolePreviewFailureTitle => "role_preview_failure_title",
+    RegistrationNotSetUp => "registration_not_set_up",
+    LogActivationFailedTitle => "log_activation_failed_title",
+    LogLockedLicense => "log_locked_license",
+    LogLicenseUsed => "log_license_used",
+    LogLicenseSeatsUsed => "log_license_seats_used",
+    LogActivationErrorTitle => "log_activation_error_title",
+    LogDeadlockedLicense => "log_deadlocked_license",
+    LogActivationTitle => "log_activation_title",
+    LogActivation => "log_activation",
+    LogRoleGrantErrorTitle => "log_role_grant_error_title",
+    LogRoleGrantError => "log_role_grant_error",
+    LogHookErrorTitle => "log_hook_error_title",
+    LogHookError => "log_hook_error",
+    LogRolesRestoredTitle => "log_roles_restored_title",
+    LogRolesRestored => "log_roles_restored",
+    LogRoleRestoreError => "log_role_restore_error",
+    LogMemberLeftTitle => "log_member_left_title",
+    LogMemberLeft => "log_member_left",
+    LogMemberLeftNoRoles => "log_member_left_no_roles",
+    LogMemberLeftRoles => "log_member_left_roles",
+    LogRegistrationBlockedTitle => "log_registration_blocked_title",
+    LogUserRegistrationBlocked => "log_user_registration_blocked",
+    LogGuildRegistrationBlocked => "log_guild_registration_blocked",
+    // replies shared by many commands
+    SuccessTitle => "success_title",
+    ErrorTitle => "error_title",
+    WarningTitle => "warning_title",
+    NotAnOwner => "not_an_owner",
+    Skip => "skip",
+    ApiKeyLabel => "api_key_label",
+    JinxxyApiKeyTitle => "jinxxy_api_key_title",
+    ApiKeyVerifyError => "api_key_verify_error",
+    InvalidApiKey => "invalid_api_key",
+    JinxxyUserError => "jinxxy_user_error",
+    LogChannelSendError => "log_channel_send_error",
+    LogConfigurationChangedTitle => "log_configuration_changed_title",
+    LogChannelSet => "log_channel_set",
+    // /help, /version, /init and /setup
+    HelpTitle => "help_title",
+    Help => "help",
+    VersionTitle => "version_title",
+    OwnerCommandsInstalled => "owner_commands_installed",
+    OwnerCommandsInstallErrorTitle => "owner_commands_install_error_title",
+    OwnerCommandsUninstalled => "owner_commands_uninstalled",
+    OwnerCommandsUninstallErrorTitle => "owner_commands_uninstall_error_title",
+    InitSuccess => "init_success",
+    InitErrorTitle => "init_error_title",
+    InitInvalidApiKey => "init_invalid_api_key",
+    CommandsReinstalled => "commands_reinstalled",
+    SetupTitle => "setup_title",
+    SetupStarting => "setup_starting",
+    SetupPageTitle => "setup_page_title",
+    SetupEnterApiKey => "setup_enter_api_key",
+    SetupKeepApiKey => "setup_keep_api_key",
+    SetupApiKey => "setup_api_key",
+    SetupKeptApiKey => "setup_kept_api_key",
+    SetupApiKeySet => "setup_api_key_set",
+    SetupLogChannelTitle => "setup_log_channel_title",
+    SetupLogChannelPlaceholder => "setup_log_channel_placeholder",
+    SetupLogChannel => "setup_log_channel",
+    SetupNoLogChannel => "setup_no_log_channel",
+    SetupLogChannelSet => "setup_log_channel_set",
+    SetupBlanketRoleTitle => "setup_blanket_role_title",
+    SetupBlanketRolePlaceholder => "setup_blanket_role_placeholder",
+    SetupBlanketRole => "setup_blanket_role",
+    SetupBlanketRoleSet => "setup_blanket_role_set",
+    SetupNoBlanketRole => "setup_no_blanket_role",
+    SetupPostTitle => "setup_post_title",
+    SetupPostPlaceholder => "setup_post_placeholder",
+    SetupPost => "setup_post",
+    SetupNoPost => "setup_no_post",
+    SetupPostCreated => "setup_post_created",
+    SetupPostSendError => "setup_post_send_error",
+    SetupCompleteTitle => "setup_complete_title",
+    SetupTimedOutTitle => "setup_timed_out_title",
+    SetupTimedOut => "setup_timed_out",
+    SetupProgressTitle => "setup_progress_title",
+    SetupProgress => "setup_progress",
+    OnboardingStoreLinked => "onboarding_store_linked",
+    OnboardingStoreLinkedHint => "onboarding_store_linked_hint",
+    OnboardingLogChannelSet => "onboarding_log_channel_set",
+    OnboardingLogChannelSetHint => "onboarding_log_channel_set_hint",
+    OnboardingFirstLink => "onboarding_first_link",
+    OnboardingFirstLinkHint => "onboarding_first_link_hint",
+    OnboardingFirstPost => "onboarding_first_post",
+    OnboardingFirstPostHint => "onboarding_first_post_hint",
+    OnboardingFirstActivation => "onboarding_first_activation",
+    OnboardingFirstActivationHint => "onboarding_first_activation_hint",
+    // bot owner commands
+    ConfirmTitle => "confirm_title",
+    ConfirmPrompt => "confirm_prompt",
+    ApprovalUnavailableTitle => "approval_unavailable_title",
+    ApprovalUnavailable => "approval_unavailable",
+    ApprovalRequiredTitle => "approval_required_title",
+    ApprovalRequired => "approval_required",
+    Approve => "approve",
+    Confirm => "confirm",
+    Cancel => "cancel",
+    ConfirmationTimedOutTitle => "confirmation_timed_out_title",
+    CancelledTitle => "cancelled_title",
+    ActionNotTaken => "action_not_taken",
+    OwnersOnly => "owners_only",
+    DifferentOwnerRequired => "different_owner_required",
+    ActionExit => "action_exit",
+    ActionRestart => "action_restart",
+    ActionClearGuildCache => "action_clear_guild_cache",
+    ActionClearAllCaches => "action_clear_all_caches",
+    OwnerStatsTitle => "owner_stats_title",
+    RunningTasksTitle => "running_tasks_title",
+    RunningTasks => "running_tasks",
+    RunningTasksOldest => "running_tasks_oldest",
+    ApiHealthTitle => "api_health_title",
+    ApiHealthEmpty => "api_health_empty",
+    TrendsTitle => "trends_title",
+    TrendsEmpty => "trends_empty",
+    Trends => "trends",
+    TrendsActivations => "trends_activations",
+    TrendsApiRequests => "trends_api_requests",
+    TrendsApiErrors => "trends_api_errors",
+    TrendsApiLatency => "trends_api_latency",
+    TrendsGuilds => "trends_guilds",
+    MessageExperimentTitle => "message_experiment_title",
+    MessageVariantBuiltIn => "message_variant_built_in",
+    MessageVariantRetired => "message_variant_retired",
+    AddVariantErrorTitle => "add_variant_error_title",
+    AddVariantEmpty => "add_variant_empty",
+    AddVariantSuccess => "add_variant_success",
+    RetireVariantSuccess => "retire_variant_success",
+    RetireVariantErrorTitle => "retire_variant_error_title",
+    RetireVariantError => "retire_variant_error",
+    PermissionMatrixTitle => "permission_matrix_title",
+    PermissionMatrixFooter => "permission_matrix_footer",
+    PermissionAnyone => "permission_anyone",
+    PermissionCreator => "permission_creator",
+    PermissionOwner => "permission_owner",
+    PermissionInstalled => "permission_installed",
+    PermissionNotInstalled => "permission_not_installed",
+    ShuttingDown => "shutting_down",
+    RestartingNow => "restarting_now",
+    AnnouncementErrorTitle => "announcement_error_title",
+    AnnouncementTimeInPast => "announcement_time_in_past",
+    AnnouncementTimeInvalid => "announcement_time_invalid",
+    AnnouncementScheduled => "announcement_scheduled",
+    AnnouncementSent => "announcement_sent",
+    AnnouncementSending => "announcement_sending",
+    AnnouncementsEmpty => "announcements_empty",
+    RecentAnnouncementsTitle => "recent_announcements_title",
+    AnnouncementStatusErrorTitle => "announcement_status_error_title",
+    AnnouncementNotFound => "announcement_not_found",
+    AnnouncementDetails => "announcement_details",
+    AnnouncementFailures => "announcement_failures",
+    AnnouncementFailuresMore => "announcement_failures_more",
+    AnnouncementTitle => "announcement_title",
+    AnnouncementUntitled => "announcement_untitled",
+    AnnouncementStatusSent => "announcement_status_sent",
+    AnnouncementStatusScheduled => "announcement_status_scheduled",
+    TestGuildSet => "test_guild_set",
+    ProductionGuildSet => "production_guild_set",
+    IncidentModeEnabled => "incident_mode_enabled",
+    IncidentModeDisabled => "incident_mode_disabled",
+    ScheduleErrorTitle => "schedule_error_title",
+    ScheduleInvalid => "schedule_invalid",
+    CacheWarmScheduleSet => "cache_warm_schedule_set",
+    CacheWarmScheduleUnset => "cache_warm_schedule_unset",
+    PresenceErrorTitle => "presence_error_title",
+    PresenceEmpty => "presence_empty",
+    PresenceMessagesSet => "presence_messages_set",
+    PresenceMessagesReset => "presence_messages_reset",
+    PresenceIntervalSet => "presence_interval_set",
+    SlowQueryThresholdSet => "slow_query_threshold_set",
+    ConfirmationModeSet => "confirmation_mode_set",
+    ConfirmationModeNone => "confirmation_mode_none",
+    ConfirmationModeButton => "confirmation_mode_button",
+    ConfirmationModeSecondOwner => "confirmation_mode_second_owner",
+    ReleaseChannelStable => "release_channel_stable",
+    ReleaseChannelPreRelease => "release_channel_pre_release",
+    IgnoreVersionErrorTitle => "ignore_version_error_title",
+    IgnoreVersionInvalid => "ignore_version_invalid",
+    VersionIgnored => "version_ignored",
+    VersionUnignored => "version_unignored",
+    IgnoredVersions => "ignored_versions",
+    ClearCacheErrorTitle => "clear_cache_error_title",
+    InvalidGuildId => "invalid_guild_id",
+    CacheCleared => "cache_cleared",
+    CacheExported => "cache_exported",
+    CacheImported => "cache_imported",
+    ImportCacheErrorTitle => "import_cache_error_title",
+    SnapshotInvalid => "snapshot_invalid",
+    GuildVerificationErrorTitle => "guild_verification_error_title",
+    GuildIdZero => "guild_id_zero",
+    GuildVerificationSuccessTitle => "guild_verification_success_title",
+    GuildOwnerMatches => "guild_owner_matches",
+    GuildVerificationFailureTitle => "guild_verification_failure_title",
+    GuildOwnerMismatch => "guild_owner_mismatch",
+    GuildVerificationResultTitle => "guild_verification_result_title",
+    GuildOwner => "guild_owner",
+    ApiVerificationSuccessTitle => "api_verification_success_title",
+    ApiScopes => "api_scopes",
+    ApiVerificationErrorTitle => "api_verification_error_title",
+    ApiKeyInvalid => "api_key_invalid",
+    ApiVerificationSkippedTitle => "api_verification_skipped_title",
+    ApiKeyUnset => "api_key_unset",
+    GuildInformationTitle => "guild_information_title",
+    GuildInformation => "guild_information",
+    GuildNotCached => "guild_not_cached",
+    GuildIdUnparseable => "guild_id_unparseable",
+    GuildConfigErrorTitle => "guild_config_error_title",
+    GuildConfigInvalidId => "guild_config_invalid_id",
+    GuildConfigMissing => "guild_config_missing",
+    GuildConfigTitle => "guild_config_title",
+    GuildConfig => "guild_config",
+    Unset => "unset",
+    Unknown => "unknown",
+    No => "no",
+    NotInCache => "not_in_cache",
+    GuildConfigLinks => "guild_config_links",
+    GuildConfigLinkRules => "guild_config_link_rules",
+    // server admin commands
+    ApiKeyNotSet => "api_key_not_set",
+    StatsTitle => "stats_title",
+    Stats => "stats",
+    StatsChartTitle => "stats_chart_title",
+    StatsChartPeak => "stats_chart_peak",
+    TopProductsField => "top_products_field",
+    RegistrationCount => "registration_count",
+    TopProductsEmpty => "top_products_empty",
+    TopProductsTitle => "top_products_title",
+    ActivityExportTitle => "activity_export_title",
+    ActivityExport => "activity_export",
+    NotificationDigestOff => "notification_digest_off",
+    NotificationDigestSet => "notification_digest_set",
+    CountRedactionExample => "count_redaction_example",
+    CountRedactionHidden => "count_redaction_hidden",
+    CountRedactionSet => "count_redaction_set",
+    LanguageSet => "language_set",
+    LanguageUnset => "language_unset",
+    StatsOptedOut => "stats_opted_out",
+    StatsOptedIn => "stats_opted_in",
+    RestoreRolesEnabled => "restore_roles_enabled",
+    RestoreRolesDisabled => "restore_roles_disabled",
+    LogMemberLeaveEnabled => "log_member_leave_enabled",
+    LogMemberLeaveDisabled => "log_member_leave_disabled",
+    LogProductChangesEnabled => "log_product_changes_enabled",
+    LogProductChangesDisabled => "log_product_changes_disabled",
+    StorePaused => "store_paused",
+    StoreResumed => "store_resumed",
+    RotateApiKeyErrorTitle => "rotate_api_key_error_title",
+    RotateApiKeyNoAccount => "rotate_api_key_no_account",
+    RotateApiKeyUnknownAccount => "rotate_api_key_unknown_account",
+    RotateApiKeyDifferentAccount => "rotate_api_key_different_account",
+    RotateApiKeyStoreChanged => "rotate_api_key_store_changed",
+    ApiKeyRotated => "api_key_rotated",
+    SetupVerifiedTitle => "setup_verified_title",
+    SetupVerified => "setup_verified",
+    SetupProblemsTitle => "setup_problems_title",
+    LogChannelSetTo => "log_channel_set_to",
+    LogChannelUnset => "log_channel_unset",
+    LogChannelErrorTitle => "log_channel_error_title",
+    LogChannelNotSet => "log_channel_not_set",
+    SalesFeedChannelTest => "sales_feed_channel_test",
+    SalesFeedChannelSet => "sales_feed_channel_set",
+    SalesFeedChannelUnset => "sales_feed_channel_unset",
+    SalesFeedChannelErrorTitle => "sales_feed_channel_error_title",
+    SalesFeedChannelNotSet => "sales_feed_channel_not_set",
+    // server admin commands: webhooks, channels and posts
+    EventWebhookSet => "event_webhook_set",
+    EventWebhookErrorTitle => "event_webhook_error_title",
+    EventWebhookUnset => "event_webhook_unset",
+    EventWebhookUnsetErrorTitle => "event_webhook_unset_error_title",
+    EventWebhookMissing => "event_webhook_missing",
+    EventWebhookSecretRotated => "event_webhook_secret_rotated",
+    EventWebhookSecretErrorTitle => "event_webhook_secret_error_title",
+    EventWebhookSecretMissing => "event_webhook_secret_missing",
+    WebhookSigningSecret => "webhook_signing_secret",
+    MilestoneChannelTest => "milestone_channel_test",
+    MilestoneChannelSet => "milestone_channel_set",
+    MilestoneChannelUnset => "milestone_channel_unset",
+    MilestoneChannelErrorTitle => "milestone_channel_error_title",
+    MilestoneChannelNotSet => "milestone_channel_not_set",
+    RegistrationPostTitle => "registration_post_title",
+    RegistrationPostDescription => "registration_post_description",
+    Register => "register",
+    InvalidColor => "invalid_color",
+    ProductNotFound => "product_not_found",
+    ProductArtMissing => "product_art_missing",
+    CreatePostErrorTitle => "create_post_error_title",
+    PostSendError => "post_send_error",
+    RegistrationPostCreated => "registration_post_created",
+    Publish => "publish",
+    PostPreview => "post_preview",
+    PostNotCreated => "post_not_created",
+    UpdatePostsErrorTitle => "update_posts_error_title",
+    UpdatePostsNone => "update_posts_none",
+    PostsUpdated => "posts_updated",
+    PostsFound => "posts_found",
+    PostsForgotten => "posts_forgotten",
+    PostsNotUpdated => "posts_not_updated",
+    PostsUpdatedTitle => "posts_updated_title",
+    PostsNotUpdatedTitle => "posts_not_updated_title",
+    ClaimPostNoLinks => "claim_post_no_links",
+    ClaimPostDescription => "claim_post_description",
+    ClaimPostCreated => "claim_post_created",
+    // server admin commands: licenses
+    UserInfoErrorTitle => "user_info_error_title",
+    UserInfoTitle => "user_info_title",
+    UserInfoNoActivations => "user_info_no_activations",
+    UnknownProduct => "unknown_product",
+    NoDataFound => "no_data_found",
+    UserInfo => "user_info",
+    MoreProducts => "more_products",
+    LicensedRoleSource => "licensed_role_source",
+    LicensedRoleUnsourced => "licensed_role_unsourced",
+    LicensedRoleMissing => "licensed_role_missing",
+    NotAServerMember => "not_a_server_member",
+    None => "none",
+    LicensedRolesField => "licensed_roles_field",
+    LicenseLookupTitle => "license_lookup_title",
+    LicenseLookupEmpty => "license_lookup_empty",
+    LicenseLookup => "license_lookup",
+    LicenseLookupMore => "license_lookup_more",
+    // server admin commands: license management
+    LicenseNotFound => "license_not_found",
+    DeactivateLicenseErrorTitle => "deactivate_license_error_title",
+    LicenseDeactivated => "license_deactivated",
+    TransferLicenseErrorTitle => "transfer_license_error_title",
+    TransferLicenseLocked => "transfer_license_locked",
+    TransferLicenseNotActivated => "transfer_license_not_activated",
+    RoleRevokeFailed => "role_revoke_failed",
+    RoleGrantFailed => "role_grant_failed",
+    LicenseTransferred => "license_transferred",
+    LogLicenseTransferredTitle => "log_license_transferred_title",
+    LogLicenseTransferred => "log_license_transferred",
+    LicenseTransferredWithErrorsTitle => "license_transferred_with_errors_title",
+    RolesNotUpdated => "roles_not_updated",
+    ImportLicensesErrorTitle => "import_licenses_error_title",
+    FileNotUtf8 => "file_not_utf8",
+    ImportTooManyRows => "import_too_many_rows",
+    ImportInvalidFile => "import_invalid_file",
+    LicensesImported => "licenses_imported",
+    ImportSkipped => "import_skipped",
+    ImportSkippedLine => "import_skipped_line",
+    AndMore => "and_more",
+    LicensesImportedTitle => "licenses_imported_title",
+    ImportNotJinxxy => "import_not_jinxxy",
+    ImportLicenseNotFound => "import_license_not_found",
+    ImportLicenseLocked => "import_license_locked",
+    ImportLicenseUsed => "import_license_used",
+    LicenseInfoTitle => "license_info_title",
+    LicenseInfoErrorTitle => "license_info_error_title",
+    LicenseNoUsers => "license_no_users",
+    LicenseUsers => "license_users",
+    LicenseLockedLine => "license_locked_line",
+    LicenseUserRegistered => "license_user_registered",
+    LockLicenseErrorTitle => "lock_license_error_title",
+    LicenseLocked => "license_locked",
+    UnlockLicenseErrorTitle => "unlock_license_error_title",
+    LicenseUnlocked => "license_unlocked",
+    // server admin commands: audit log and links
+    AuditLogErrorTitle => "audit_log_error_title",
+    AuditLogEmpty => "audit_log_empty",
+    AuditLogTitle => "audit_log_title",
+    AuditLogFooter => "audit_log_footer",
+    AuditBy => "audit_by",
+    AuditFor => "audit_for",
+    AuditProduct => "audit_product",
+    AuditRole => "audit_role",
+    AuditLicense => "audit_license",
+    LicenseHistory => "license_history",
+    LicenseHistoryEmpty => "license_history_empty",
+    LicenseHistoryTruncated => "license_history_truncated",
+    CurrentActivations => "current_activations",
+    NoneSentence => "none_sentence",
+    ActivationNotFromDiscord => "activation_not_from_discord",
+    LicenseHistoryTitle => "license_history_title",
+    LicenseHistoryErrorTitle => "license_history_error_title",
+    ProductLinkSuccessTitle => "product_link_success_title",
+    ProductGrantsRoles => "product_grants_roles",
+    LinkProductErrorTitle => "link_product_error_title",
+    LinkProductsErrorTitle => "link_products_error_title",
+    NoProductsFound => "no_products_found",
+    // server admin commands: bulk links and link rules
+    ProductsRange => "products_range",
+    Link => "link",
+    LinkProductsPrompt => "link_products_prompt",
+    LinkProductsOverflow => "link_products_overflow",
+    LinkProductsTitle => "link_products_title",
+    TimedOutTitle => "timed_out_title",
+    NoProductsLinked => "no_products_linked",
+    NoProductsSelected => "no_products_selected",
+    RoleGrantedByProducts => "role_granted_by_products",
+    AddLinkRuleErrorTitle => "add_link_rule_error_title",
+    LinkRulePatternLength => "link_rule_pattern_length",
+    LinkRuleExists => "link_rule_exists",
+    LinkRuleAddedTitle => "link_rule_added_title",
+    LinkRuleAdded => "link_rule_added",
+    LinkRuleRemoved => "link_rule_removed",
+    RemoveLinkRuleErrorTitle => "remove_link_rule_error_title",
+    LinkRuleNotFound => "link_rule_not_found",
+    // server admin commands: roles and activation hooks
+    LinkRulesEmpty => "link_rules_empty",
+    LinkRules => "link_rules",
+    LinkRulesTitle => "link_rules_title",
+    BlanketRoleSet => "blanket_role_set",
+    BlanketRoleUnset => "blanket_role_unset",
+    UnlinkProductErrorTitle => "unlink_product_error_title",
+    ProductSeatsSingle => "product_seats_single",
+    ProductSeats => "product_seats",
+    ProductSeatsErrorTitle => "product_seats_error_title",
+    GrantMissingRolesErrorTitle => "grant_missing_roles_error_title",
+    GrantMissingRolesRunning => "grant_missing_roles_running",
+    BotBusy => "bot_busy",
+    JoinDateInvalid => "join_date_invalid",
+    MissingRolesDryRun => "missing_roles_dry_run",
+    MissingRolesGranted => "missing_roles_granted",
+    MissingRolesFailed => "missing_roles_failed",
+    MissingRolesPreviewTitle => "missing_roles_preview_title",
+    MissingRolesGrantedTitle => "missing_roles_granted_title",
+    MissingRolesMore => "missing_roles_more",
+    NextBatch => "next_batch",
+    Stop => "stop",
+    PreviewingMissingRolesTitle => "previewing_missing_roles_title",
+    GrantingMissingRolesTitle => "granting_missing_roles_title",
+    HookUrlRequired => "hook_url_required",
+    HookDaysRequired => "hook_days_required",
+    HookChannelRequired => "hook_channel_required",
+    HookThreadRequired => "hook_thread_required",
+    HookChannelOtherGuild => "hook_channel_other_guild",
+    HookChannelInvisible => "hook_channel_invisible",
+    AddHookErrorTitle => "add_hook_error_title",
+    HookAdded => "hook_added",
+    HookRemoved => "hook_removed",
+    RemoveHookErrorTitle => "remove_hook_error_title",
+    HookNotFound => "hook_not_found",
+    HooksEmpty => "hooks_empty",
+    Hooks => "hooks",
+    HooksTitle => "hooks_title",
+    ListHooksErrorTitle => "list_hooks_error_title",
+    // server admin commands: version exclusions and link review
+    ExcludeVersionErrorTitle => "exclude_version_error_title",
+    IncludeVersionErrorTitle => "include_version_error_title",
+    VersionExcluded => "version_excluded",
+    VersionIncluded => "version_included",
+    VersionNotExcluded => "version_not_excluded",
+    VersionNotFound => "version_not_found",
+    ReviewLinksErrorTitle => "review_links_error_title",
+    ReviewLinksNoVersions => "review_links_no_versions",
+    ReviewLinksNotLinked => "review_links_not_linked",
+    ReviewLinks => "review_links",
+    NoRoles => "no_roles",
+    Previous => "previous",
+    Next => "next",
+    Done => "done",
+    ReviewLinksTitle => "review_links_title",
+    PageFooter => "page_footer",
+    LinksEmpty => "links_empty",
+    LinkGrantedBy => "link_granted_by",
+    ExcludedVersions => "excluded_versions",
+    LinkVersionExcluded => "link_version_excluded",
+    BlanketRole => "blanket_role",
+    BlanketRoleGranted => "blanket_role_granted",
+    LinksTitle => "links_title",
+    // server admin commands: links export and import
+    LinksExported => "links_exported",
+    LinksExportTitle => "links_export_title",
+    ImportKindProduct => "import_kind_product",
+    ImportKindRole => "import_kind_role",
+    ImportNameMatched => "import_name_matched",
+    ImportNameNotFound => "import_name_not_found",
+    ImportLinksErrorTitle => "import_links_error_title",
+    FileTooLarge => "file_too_large",
+    ImportVersionNotFound => "import_version_not_found",
+    ImportInvalidLinkRule => "import_invalid_link_rule",
+    LinksImportedTitle => "links_imported_title",
+    LinksImported => "links_imported",
+    LinksImportedWithBlanketRole => "links_imported_with_blanket_role",
+    DoubleCheck => "double_check",
+    // notification digests
+    LogDigestTitle => "log_digest_title",
+    LogDigest => "log_digest",
+    LogDigestLine => "log_digest_line",
+    // event webhooks
+    LogEventWebhookFailedTitle => "log_event_webhook_failed_title",
+    LogEventWebhookFailed => "log_event_webhook_failed",
+    InvalidUrl => "invalid_url",
+    // subscription expiry
+    LogSubscriptionLapsedTitle => "log_subscription_lapsed_title",
+    LogSubscriptionLapsed => "log_subscription_lapsed",
+    // scheduled expiry
+    LogRolesExpiredTitle => "log_roles_expired_title",
+    LogRolesExpired => "log_roles_expired",
+    // suspicious activity
+    LogSuspiciousActivityTitle => "log_suspicious_activity_title",
+    LogProlificRegistrant => "log_prolific_registrant",
+    LogSharedBuyer => "log_shared_buyer",
+    // dead roles
+    LogDeadRolesTitle => "log_dead_roles_title",
+    LogDeadRoles => "log_dead_roles",
+    LogDeadRole => "log_dead_role",
+    LogDeadRoleBlanket => "log_dead_role_blanket",
+    LogDeadRoleUnlinked => "log_dead_role_unlinked",
+    LogDeadRolesFooter => "log_dead_roles_footer",
+    // product changes
+    LogProductChangesTitle => "log_product_changes_title",
+    LogProductsAddedOne => "log_products_added_one",
+    LogProductsAdded => "log_products_added",
+    LogProductsRemoved => "log_products_removed",
+    LogProductsRenamed => "log_products_renamed",
+    LogProductsChanged => "log_products_changed",
+    LogNewProducts => "log_new_products",
+    LogRemovedProducts => "log_removed_products",
+    LogRenamedProducts => "log_renamed_products",
+    // sales feed
+    SalesFeedTitle => "sales_feed_title",
+    SalesFeedPurchase => "sales_feed_purchase",
+    SalesFeedBought => "sales_feed_bought",
+    // milestones
+    MilestoneTitle => "milestone_title",
+    MilestoneProduct => "milestone_product",
+    MilestoneGuild => "milestone_guild",
+    // update notifications
+    LogUpdateAvailableTitle => "log_update_available_title",
+    LogUpdateAvailable => "log_update_available",
+    // api key scopes
+    ApiKeyScopesTitle => "api_key_scopes_title",
+    ApiKeyScopesField => "api_key_scopes_field",
+    ApiKeyScopeRequired => "api_key_scope_required",
+    ApiKeyScopeOptional => "api_key_scope_optional",
+    ApiKeyScopeProductsRead => "api_key_scope_products_read",
+    ApiKeyScopeLicensesRead => "api_key_scope_licenses_read",
+    ApiKeyScopeLicensesWrite => "api_key_scope_licenses_write",
+    ApiKeyScopeOrdersRead => "api_key_scope_orders_read",
+    ApiKeyPermissionWarningTitle => "api_key_permission_warning_title",
+    ApiKeyPermissionWarning => "api_key_permission_warning",
+    // role warnings
+    RoleWarningTitle => "role_warning_title",
+    RoleWarning => "role_warning",
+    // dm registration
+    DmRegistrationTitle => "dm_registration_title",
+    DmNoGuilds => "dm_no_guilds",
+    DmSelectGuild => "dm_select_guild",
+    DmSelectGuildPlaceholder => "dm_select_guild_placeholder",
+    // rejoin assistant
+    RejoinTitle => "rejoin_title",
+    RejoinNoLinks => "rejoin_no_links",
+    RejoinLostLinks => "rejoin_lost_links",
+    RejoinLostLink => "rejoin_lost_link",
+    RejoinRelinkPrompt => "rejoin_relink_prompt",
+    RelinkTitle => "relink_title",
+    RelinkNoPermission => "relink_no_permission",
+    RelinkAllLinked => "relink_all_linked",
+    RelinkUnlinked => "relink_unlinked",
+    RelinkInstructions => "relink_instructions",
+    // setup verification
+    SetupProblemManageRoles => "setup_problem_manage_roles",
+    SetupProblemRoleDeleted => "setup_problem_role_deleted",
+    SetupProblemRoleManaged => "setup_problem_role_managed",
+    SetupProblemRoleTooHigh => "setup_problem_role_too_high",
+    SetupProblemLogPermissions => "setup_problem_log_permissions",
+    SetupProblemLogChannelMissing => "setup_problem_log_channel_missing",
+    SetupProblems => "setup_problems",
+    // command policy
+    PermissionDeniedTitle => "permission_denied_title",
+    PermissionDenied => "permission_denied",
+}
+
+/// Pick the locale to use in a guild: the user's locale if we have a catalog for it, otherwise the guild's chosen
+/// language if it has one. Pass `None` as the user's locale for messages that aren't for a specific user, such as the
+/// bot log, which are then in the guild's language.
+pub async fn guild_locale<'a>(
+    db: &JinxDb,
+    guild_id: GuildId,
+    user_locale: Option<&'a str>,
+) -> Result<Option<&'a str>, Error> {
+    if let Some(user_locale) = user_locale.filter(|locale| has_catalog(locale)) {
+        return Ok(Some(user_locale));
+    }
+    let language = db.get_language(guild_id).await?;
+    Ok(language.map(|language| language.locale()).or(user_locale))
+}
+
+/// Pick the locale for a command's replies, like [`guild_locale`] does for the user who ran it. Commands outside a guild
+/// just use the user's locale.
+pub async fn command_locale(context: Context<'_>) -> Result<Option<&str>, Error> {
+    match context.guild_id() {
+        Some(guild_id) => guild_locale(&context.data().db, guild_id, context.locale()).await,
+        None => Ok(context.locale()),
+    }
+}
+
+/// Check if there's a catalog for a Discord locale or its language
+fn has_catalog(locale: &str) -> bool {
+    let language = locale.split('-').next().unwrap_or(locale);
+    PARSED_CATALOGS.contains_key(locale) || PARSED_CATALOGS.contains_key(language)
+}
+
+/// Get a message in the given Discord locale, such as `es-ES`. `None` gets the fallback locale.
+pub fn text(locale: Option<&str>, text: Text) -> &'static str {
+    let key = text.key();
+    let language = locale.and_then(|locale| locale.split('-').next());
+    [locale, language, Some(FALLBACK_LOCALE)]
+        .into_iter()
+        .flatten()
+        .filter_map(|locale| PARSED_CATALOGS.get(locale))
+        .find_map(|catalog| catalog.get(key))
+        .map(String::as_str)
+        .unwrap_or(key)
+}
+
+/// Get a message in the given Discord locale, with its `{name}` placeholders filled in
+pub fn format(
+    locale: Option<&str>,
+    message: Text,
+    args: &[(&str, &(dyn Display + Sync))],
+) -> String {
+    fill(text(locale, message), args)
+}
+
+/// Fill in placeholders in a single pass, so argument values that look like placeholders are left alone
+fn fill(template: &str, args: &[(&str, &(dyn Display + Sync))]) -> String {
+    let mut result = String::with_capacity(template.len());
+    let mut remaining = template;
+    while let Some(start) = remaining.find('{') {
+        result.push_str(&remaining[..start]);
+        let placeholder = &remaining[start + 1..];
+        let value = placeholder.find('}').and_then(|end| {
+            let name = &placeholder[..end];
+            args.iter()
+                .find(|(arg_name, _)| *arg_name == name)
+                .map(|(_, value)| (end, value))
+        });
+        match value {
+            Some((end, value)) => {
+                result.push_str(value.to_string().as_str());
+                remaining = &placeholder[end + 1..];
+            }
+            None => {
+                result.push('{');
+                remaining = placeholder;
+            }
+        }
+    }
+    result.push_str(remaining);
+    result
+}
+
+/// Parse a message catalog, which is a flat TOML table of message keys to strings
+fn parse(catalog: &str) -> Result<HashMap<String, String>, toml::de::Error> {
+    toml::from_str(catalog)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::{test_db, Language};
+
+    /// Find the `{name}` placeholders in a message
+    fn placeholders(message: &str) -> Vec<&str> {
+        let mut placeholders: Vec<&str> = message
+            .split('{')
+            .skip(1)
+            .filter_map(|part| part.split_once('}').map(|(name, _)| name))
+            .collect();
+        placeholders.sort_unstable();
+        placeholders
+    }
+
+    #[test]
+    fn test_catalogs() {
+        let fallback = &PARSED_CATALOGS[FALLBACK_LOCALE];
+        for text in Text::ALL.iter().copied() {
+            assert!(
+                fallback.contains_key(text.key()),
+                "{:?} has no fallback",
+                text
+            );
+        }
+        for (locale, catalog) in PARSED_CATALOGS.iter() {
+            for (key, message) in catalog {
+                assert!(
+                    Text::ALL.iter().any(|text| text.key() == key),
+                    "{locale} has unknown key {key}"
+                );
+                assert_eq!(
+                    placeholders(message),
+                    placeholders(&fallback[key]),
+                    "{locale} {key} has different placeholders"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_languages_have_catalogs() {
+        for language in Language::ALL {
+            assert!(
+                PARSED_CATALOGS.contains_key(language.locale()),
+                "{:?} has no catalog",
+                language
+            );
+        }
+    }
+
+    #[test]
+    fn test_text() {
+        assert_eq!(text(None, Text::LicenseKeyLabel), "License Key");
+        assert_eq!(text(Some("en-US"), Text::LicenseKeyLabel), "License Key");
+        assert_eq!(
+            text(Some("es-ES"), Text::LicenseKeyLabel),
+            "Clave de licencia"
+        );
+        assert_eq!(
+            text(Some("es-419"), Text::LicenseKeyLabel),
+            "Clave de licencia"
+        );
+        assert_eq!(text(Some("ja"), Text::LicenseKeyLabel), "License Key");
+    }
+
+    #[test]
+    fn test_guild_locale() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            assert_eq!(
+                guild_locale(&db, guild, Some("ja")).await.unwrap(),
+                Some("ja")
+            );
+            assert_eq!(guild_locale(&db, guild, None).await.unwrap(), None);
+
+            db.set_language(guild, Some(Language::Spanish))
+                .await
+                .unwrap();
+            // users see their own language when we have it, and the guild's language otherwise
+            assert_eq!(
+                guild_locale(&db, guild, Some("en-US")).await.unwrap(),
+                Some("en-US")
+            );
+            assert_eq!(
+                guild_locale(&db, guild, Some("ja")).await.unwrap(),
+                Some(Language::Spanish.locale())
+            );
+            assert_eq!(
+                guild_locale(&db, guild, None).await.unwrap(),
+                Some(Language::Spanish.locale())
+            );
+        });
+    }
+
+    #[test]
+    fn test_fill() {
+        assert_eq!(fill("no placeholders", &[]), "no placeholders");
+        assert_eq!(
+            fill("{a} and {b}", &[("a", &"{b}"), ("b", &2)]),
+            "{b} and 2"
+        );
+        assert_eq!(fill("{unknown} {", &[("a", &1)]), "{unknown} {");
+    }
+}
@@ -0,0 +1,415 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Role expiry for subscription licenses.
+//!
+//! The Jinxxy API gives subscription licenses an expiry time, which is recorded when one is registered. A daily sweep
+//! re-checks every license due to expire before the next sweep. A renewed license just has its new expiry recorded. A
+//! lapsed one has its roles removed from the users who registered it, except for roles another of their licenses still
+//! grants, and the guild's bot log channel is told. Registering the license again after renewing it grants the roles
+//! again, while a lapsed license can't be registered at all.
+//!
+//! A product's "schedule expiry" activation hook works the same way, for licenses that never expire in Jinxxy. It gives
+//! each activation a fixed number of days, after which an hourly sweep removes its roles. The original expiry is kept
+//! if the license is registered again, and once it passes the license can't be registered again by that user.
+
+use crate::bot::i18n;
+use crate::bot::i18n::Text;
+use crate::bot::util::{send_bot_log_message, SafeDisplayExt as _};
+use crate::bot::{Error, LICENSE_EXPIRY_SWEEP_INTERVAL};
+use crate::db::{AuditAction, AuditLogEntry, ExpiringLicense, JinxDb, ScheduledExpiry};
+use crate::http::jinxxy;
+use crate::http::jinxxy::LicenseInfo;
+use crate::license::LOCKING_USER_ID;
+use poise::serenity_prelude as serenity;
+use secrecy::SecretString;
+use serenity::{Colour, CreateEmbed, CreateMessage, GuildId, Member, RoleId, UserId};
+use std::collections::HashSet;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+/// When a license expires, in ms since the epoch. `None` for licenses that don't expire, or whose expiry can't be read.
+pub fn expires_unix_ms(license_info: &LicenseInfo) -> Option<u64> {
+    let expires_at = license_info.expires_at.as_deref()?;
+    match serenity::Timestamp::parse(expires_at) {
+        Ok(timestamp) => Some(timestamp.unix_timestamp().max(0) as u64 * 1000),
+        Err(e) => {
+            warn!(
+                "could not parse expiry {:?} of license {}: {:?}",
+                expires_at, license_info.license_id, e
+            );
+            None
+        }
+    }
+}
+
+/// Check if a license is a subscription that has already expired
+pub fn is_lapsed(license_info: &LicenseInfo) -> bool {
+    expires_unix_ms(license_info).is_some_and(|expires_unix_ms| expires_unix_ms <= now_unix_ms())
+}
+
+pub(super) fn now_unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Re-check every license expiring before the next sweep. Failures are logged and the license is left for the next sweep.
+pub async fn sweep(context: &serenity::Context, db: &JinxDb) {
+    let before = now_unix_ms() + LICENSE_EXPIRY_SWEEP_INTERVAL.as_millis() as u64;
+    let licenses = match db.get_expiring_licenses(before).await {
+        Ok(licenses) => licenses,
+        Err(e) => {
+            warn!("Error reading expiring licenses: {:?}", e);
+            return;
+        }
+    };
+    for license in licenses {
+        if let Err(e) = check(context, db, &license).await {
+            warn!(
+                "in {} error checking expiry of license {}: {:?}",
+                license.guild_id.get(),
+                license.license_id,
+                e
+            );
+        }
+    }
+}
+
+/// Re-check a single license, removing its roles if it has lapsed
+async fn check(
+    context: &serenity::Context,
+    db: &JinxDb,
+    license: &ExpiringLicense,
+) -> Result<(), Error> {
+    let guild_id = license.guild_id;
+    let Some(api_key) = db.get_jinxxy_api_key(guild_id).await? else {
+        // without an API key we can't tell whether it was renewed, so leave the roles alone
+        return Ok(());
+    };
+    // a license that no longer exists can't be renewed, so it counts as lapsed
+    if let Some(license_info) = jinxxy::check_license_id(&api_key, &license.license_id).await? {
+        match expires_unix_ms(&license_info) {
+            Some(expires_unix_ms) if expires_unix_ms > now_unix_ms() => {
+                debug!(
+                    "in {} license {} now expires at {}",
+                    guild_id.get(),
+                    license.license_id,
+                    expires_unix_ms
+                );
+                return Ok(db
+                    .set_license_expiry(
+                        guild_id,
+                        license.license_id.clone(),
+                        license.product_id.clone(),
+                        license.product_version_id.clone(),
+                        expires_unix_ms,
+                    )
+                    .await?);
+            }
+            Some(_) => {}
+            None => {
+                // no longer a subscription
+                return Ok(db
+                    .delete_license_expiry(guild_id, license.license_id.clone())
+                    .await?);
+            }
+        }
+    }
+
+    let roles = db
+        .get_role_grants(
+            guild_id,
+            license.product_id.clone(),
+            license.product_version_id.clone(),
+        )
+        .await?;
+    let mut lines = String::new();
+    let mut failed = false;
+    for (user_id, _) in db
+        .get_license_users(guild_id, license.license_id.clone())
+        .await?
+    {
+        if user_id == LOCKING_USER_ID {
+            continue;
+        }
+        let user_id = UserId::new(user_id);
+        let Ok(member) = guild_id.member(context, user_id).await else {
+            // they left, so there are no roles to remove
+            continue;
+        };
+
+        let retained_roles =
+            retained_roles(db, &api_key, guild_id, user_id, &license.license_id).await?;
+        let removed_roles = match remove_roles(
+            context,
+            db,
+            &member,
+            &roles,
+            &retained_roles,
+            &license.license_id,
+            &license.product_id,
+            "subscription lapsed",
+        )
+        .await
+        {
+            Ok(removed_roles) => removed_roles,
+            Err(removed_roles) => {
+                failed = true;
+                removed_roles
+            }
+        };
+        if !removed_roles.is_empty() {
+            lines.push_str(
+                format!("\n- <@{}>: {}", user_id.get(), removed_roles.join(", ")).as_str(),
+            );
+        }
+    }
+    // keep tracking the license until all its roles are gone, so the next sweep tries again
+    if !failed {
+        db.delete_license_expiry(guild_id, license.license_id.clone())
+            .await?;
+    }
+
+    if lines.is_empty() {
+        return Ok(());
+    }
+    let Some(log_channel) = db.get_log_channel(guild_id).await? else {
+        return Ok(());
+    };
+    let product_name = db
+        .get_product_version_name(
+            guild_id,
+            license.product_id.clone(),
+            license.product_version_id.clone(),
+        )
+        .await?
+        .map(|(product_name, _)| format!("\"{}\"", product_name.safe_display()))
+        .unwrap_or_else(|| license.product_id.clone());
+    let locale = i18n::guild_locale(db, guild_id, None).await?;
+    let embed = CreateEmbed::default()
+        .title(i18n::text(locale, Text::LogSubscriptionLapsedTitle))
+        .description(format!(
+            "{}{}",
+            i18n::format(
+                locale,
+                Text::LogSubscriptionLapsed,
+                &[("product", &product_name)],
+            ),
+            lines
+        ))
+        .color(Colour::ORANGE);
+    send_bot_log_message(context, log_channel, CreateMessage::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// Roles a user's other licenses still grant, which should be kept when removing one license's roles. Licenses that
+/// have lapsed or passed their scheduled expiry don't count.
+async fn retained_roles(
+    db: &JinxDb,
+    api_key: &SecretString,
+    guild_id: GuildId,
+    user_id: UserId,
+    license_id: &str,
+) -> Result<HashSet<RoleId, ahash::RandomState>, Error> {
+    let expired_licenses = db
+        .get_expired_licenses(guild_id, user_id, now_unix_ms())
+        .await?;
+    let mut retained_roles: HashSet<RoleId, ahash::RandomState> = Default::default();
+    for other_license_id in db.get_user_licenses(guild_id, user_id.get()).await? {
+        if other_license_id == license_id || expired_licenses.contains(&other_license_id) {
+            continue;
+        }
+        if let Some(other_license) = jinxxy::check_license_id(api_key, &other_license_id).await? {
+            if !is_lapsed(&other_license) {
+                retained_roles.extend(
+                    db.get_role_grants(
+                        guild_id,
+                        other_license.product_id,
+                        other_license.product_version_id,
+                    )
+                    .await?,
+                );
+            }
+        }
+    }
+    Ok(retained_roles)
+}
+
+/// Remove a license's roles from a member, except retained ones, auditing each removal with the given reason. Returns
+/// the removed roles as mentions, as an error if any of them couldn't be removed.
+#[allow(clippy::too_many_arguments)]
+async fn remove_roles(
+    context: &serenity::Context,
+    db: &JinxDb,
+    member: &Member,
+    roles: &[RoleId],
+    retained_roles: &HashSet<RoleId, ahash::RandomState>,
+    license_id: &str,
+    product_id: &str,
+    reason: &'static str,
+) -> Result<Vec<String>, Vec<String>> {
+    let guild_id = member.guild_id;
+    let mut removed_roles = Vec::new();
+    let mut failed = false;
+    for role in roles.iter().filter(|role| !retained_roles.contains(role)) {
+        if !member.roles.contains(role) {
+            continue;
+        }
+        if let Err(e) = member.remove_role(context, *role).await {
+            warn!("in {} error revoking role: {:?}", guild_id.get(), e);
+            failed = true;
+            continue;
+        }
+        let audit_entry = AuditLogEntry::new(AuditAction::RoleRevoke)
+            .user(member.user.id)
+            .product(product_id.to_string())
+            .role(*role)
+            .license(license_id.to_string())
+            .detail(reason);
+        if let Err(e) = db.audit(guild_id, audit_entry).await {
+            warn!("in {} error auditing role revoke: {:?}", guild_id.get(), e);
+        }
+        removed_roles.push(format!("<@&{}>", role.get()));
+    }
+    if failed {
+        Err(removed_roles)
+    } else {
+        Ok(removed_roles)
+    }
+}
+
+/// Remove the roles of every activation whose scheduled expiry has passed. Failures are logged and the expiry is left for
+/// the next sweep.
+pub async fn sweep_scheduled(context: &serenity::Context, db: &JinxDb) {
+    let expiries = match db.get_due_scheduled_expiries(now_unix_ms()).await {
+        Ok(expiries) => expiries,
+        Err(e) => {
+            warn!("Error reading scheduled expiries: {:?}", e);
+            return;
+        }
+    };
+    for expiry in expiries {
+        if let Err(e) = check_scheduled(context, db, &expiry).await {
+            warn!(
+                "in {} error removing roles of license {} from <@{}>: {:?}",
+                expiry.guild_id.get(),
+                expiry.license_id,
+                expiry.user_id.get(),
+                e
+            );
+        }
+    }
+}
+
+/// Remove the roles of a single activation whose scheduled expiry has passed
+async fn check_scheduled(
+    context: &serenity::Context,
+    db: &JinxDb,
+    expiry: &ScheduledExpiry,
+) -> Result<(), Error> {
+    let guild_id = expiry.guild_id;
+    let Ok(member) = guild_id.member(context, expiry.user_id).await else {
+        // they left, so there are no roles to remove
+        return Ok(db
+            .set_scheduled_expiry_revoked(guild_id, expiry.user_id, expiry.license_id.clone())
+            .await?);
+    };
+    let Some(api_key) = db.get_jinxxy_api_key(guild_id).await? else {
+        // without an API key we can't tell which roles their other licenses grant, so leave the roles alone
+        return Ok(());
+    };
+    let roles = db
+        .get_role_grants(
+            guild_id,
+            expiry.product_id.clone(),
+            expiry.product_version_id.clone(),
+        )
+        .await?;
+    let retained_roles =
+        retained_roles(db, &api_key, guild_id, expiry.user_id, &expiry.license_id).await?;
+    let removed_roles = match remove_roles(
+        context,
+        db,
+        &member,
+        &roles,
+        &retained_roles,
+        &expiry.license_id,
+        &expiry.product_id,
+        "scheduled expiry",
+    )
+    .await
+    {
+        Ok(removed_roles) => {
+            // only stop once every role is gone, so the next sweep tries again
+            db.set_scheduled_expiry_revoked(guild_id, expiry.user_id, expiry.license_id.clone())
+                .await?;
+            removed_roles
+        }
+        Err(removed_roles) => removed_roles,
+    };
+
+    if removed_roles.is_empty() {
+        return Ok(());
+    }
+    let Some(log_channel) = db.get_log_channel(guild_id).await? else {
+        return Ok(());
+    };
+    let product_name = db
+        .get_product_version_name(
+            guild_id,
+            expiry.product_id.clone(),
+            expiry.product_version_id.clone(),
+        )
+        .await?
+        .map(|(product_name, _)| format!("\"{}\"", product_name.safe_display()))
+        .unwrap_or_else(|| expiry.product_id.clone());
+    let locale = i18n::guild_locale(db, guild_id, None).await?;
+    let embed = CreateEmbed::default()
+        .title(i18n::text(locale, Text::LogRolesExpiredTitle))
+        .description(i18n::format(
+            locale,
+            Text::LogRolesExpired,
+            &[
+                ("user", &format!("<@{}>", expiry.user_id.get())),
+                ("product", &product_name),
+                ("roles", &removed_roles.join(", ")),
+            ],
+        ))
+        .color(Colour::ORANGE);
+    send_bot_log_message(context, log_channel, CreateMessage::default().embed(embed)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn license_info(expires_at: Option<&str>) -> LicenseInfo {
+        LicenseInfo {
+            license_id: "license".to_string(),
+            short_key: "short".to_string(),
+            user_id: "user".to_string(),
+            username: None,
+            product_id: "product".to_string(),
+            product_name: "Product".to_string(),
+            product_version_id: None,
+            activations: 0,
+            expires_at: expires_at.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_expiry() {
+        assert_eq!(
+            expires_unix_ms(&license_info(Some("2024-01-02T03:04:05Z"))),
+            Some(1_704_164_645_000)
+        );
+        assert_eq!(expires_unix_ms(&license_info(Some("next tuesday"))), None);
+        assert_eq!(expires_unix_ms(&license_info(None)), None);
+        assert!(is_lapsed(&license_info(Some("2024-01-02T03:04:05Z"))));
+        assert!(!is_lapsed(&license_info(Some("9999-01-01T00:00:00Z"))));
+        assert!(!is_lapsed(&license_info(None)));
+    }
+}
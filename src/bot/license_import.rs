@@ -0,0 +1,172 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Parsing for license import files, which let creators migrate a community from another bot (such as GumCord)
+//! without making every user re-register.
+//!
+//! Import files are CSV with a header row. One column must hold the license (any key or ID Jinx accepts) and another
+//! the Discord user ID it belongs to. Other columns are ignored, so exports from other bots can usually be uploaded
+//! as-is. See [`LICENSE_COLUMNS`] and [`USER_COLUMNS`] for the recognized column names.
+
+use poise::serenity_prelude::UserId;
+use std::fmt::{Display, Formatter};
+
+/// Recognized names for the license column, after normalizing to lowercase with `_` separators
+const LICENSE_COLUMNS: [&str; 4] = ["license", "license_key", "license_id", "key"];
+
+/// Recognized names for the Discord user ID column, after normalizing to lowercase with `_` separators
+const USER_COLUMNS: [&str; 5] = [
+    "discord_id",
+    "discord_user_id",
+    "user_id",
+    "discord_user",
+    "user",
+];
+
+/// A single license to import
+#[derive(Debug, PartialEq, Eq)]
+pub struct ImportRow {
+    /// 1-based line number in the file, for error messages
+    pub line: usize,
+    pub license: String,
+    pub user_id: UserId,
+}
+
+/// A problem that makes an entire import file unusable
+#[derive(Debug)]
+pub struct ImportParseError {
+    message: String,
+}
+
+impl Display for ImportParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ImportParseError {}
+
+impl ImportParseError {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+/// Parse an import file. Blank lines are skipped. Rows with a blank license or an invalid user ID are an error, as they
+/// most likely mean the wrong column was picked up.
+pub fn parse(csv: &str) -> Result<Vec<ImportRow>, ImportParseError> {
+    let mut lines = csv
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty());
+    let (_, header) = lines
+        .next()
+        .ok_or_else(|| ImportParseError::new("file is empty"))?;
+    let header: Vec<String> = split_line(header)
+        .iter()
+        .map(|column| normalize_column(column))
+        .collect();
+    let find_column = |names: &[&str], description: &str| {
+        header
+            .iter()
+            .position(|column| names.contains(&column.as_str()))
+            .ok_or_else(|| {
+                ImportParseError::new(format!(
+                    "no {description} column found. Expected one of: {}",
+                    names.join(", ")
+                ))
+            })
+    };
+    let license_column = find_column(&LICENSE_COLUMNS, "license")?;
+    let user_column = find_column(&USER_COLUMNS, "Discord user ID")?;
+
+    let mut rows = Vec::new();
+    for (line, fields) in lines {
+        let fields = split_line(fields);
+        let license = fields
+            .get(license_column)
+            .map(|license| license.trim())
+            .filter(|license| !license.is_empty())
+            .ok_or_else(|| ImportParseError::new(format!("line {line} has no license")))?;
+        let user_id = fields
+            .get(user_column)
+            .and_then(|user_id| user_id.trim().parse::<u64>().ok())
+            .filter(|user_id| *user_id != 0)
+            .ok_or_else(|| {
+                ImportParseError::new(format!("line {line} has an invalid Discord user ID"))
+            })?;
+        rows.push(ImportRow {
+            line,
+            license: license.to_string(),
+            user_id: UserId::new(user_id),
+        });
+    }
+    Ok(rows)
+}
+
+/// Normalize a header name so `Discord ID`, `discord-id`, and `discord_id` all match
+fn normalize_column(column: &str) -> String {
+    column.trim().to_lowercase().replace([' ', '-'], "_")
+}
+
+/// Split a CSV line into fields. Fields may be quoted, with `""` as an escaped quote. Quoted fields may not span
+/// multiple lines, which is fine as neither licenses nor user IDs contain newlines.
+fn split_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(char) = chars.next() {
+        match char {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(char),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let csv = "Email,License Key,Discord ID\r\n\
+            a@example.com,ABCD-1234,123\r\n\
+            \r\n\
+            \"b, \"\"quoted\"\"\",\"EFGH-5678\",456\r\n";
+        let rows = parse(csv).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                ImportRow {
+                    line: 2,
+                    license: "ABCD-1234".to_string(),
+                    user_id: UserId::new(123),
+                },
+                ImportRow {
+                    line: 4,
+                    license: "EFGH-5678".to_string(),
+                    user_id: UserId::new(456),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse("").is_err());
+        assert!(parse("license,email\nABCD,a@example.com").is_err());
+        assert!(parse("license,user_id\nABCD,not a number").is_err());
+        assert!(parse("license,user_id\n,123").is_err());
+    }
+}
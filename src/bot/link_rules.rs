@@ -0,0 +1,119 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Per-guild rules that link products to roles by name, so newly published products get their roles without an admin
+//! having to run `/link_product`.
+//!
+//! Patterns are globs: `*` matches any run of characters, `?` matches a single character, and matching ignores case.
+//! Rules are applied to every existing product when they're added, and after that only to products that show up for
+//! the first time in a cache refresh. That way an admin can still unlink a product a rule matched without the rule
+//! putting the link right back.
+
+use crate::bot::Error;
+use crate::db::{AuditAction, AuditLogEntry, JinxDb};
+use poise::serenity_prelude::{GuildId, RoleId};
+
+/// Longest pattern a rule may use
+pub const MAX_PATTERN_LENGTH: usize = 100;
+
+/// Check if a product name matches a glob pattern
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().flat_map(char::to_lowercase).collect();
+    let name: Vec<char> = name.chars().flat_map(char::to_lowercase).collect();
+
+    // greedy matching that backtracks to the most recent `*`, which is linear for any pattern with a single `*`
+    let mut pattern_index = 0;
+    let mut name_index = 0;
+    let mut backtrack: Option<(usize, usize)> = None;
+    while name_index < name.len() {
+        match pattern.get(pattern_index) {
+            Some('*') => {
+                backtrack = Some((pattern_index, name_index));
+                pattern_index += 1;
+            }
+            Some(char) if *char == '?' || *char == name[name_index] => {
+                pattern_index += 1;
+                name_index += 1;
+            }
+            _ => match backtrack {
+                Some((star_index, star_name_index)) => {
+                    pattern_index = star_index + 1;
+                    name_index = star_name_index + 1;
+                    backtrack = Some((star_index, name_index));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[pattern_index..].iter().all(|char| *char == '*')
+}
+
+/// Link one rule's matching products to its role. Returns how many links were created.
+pub async fn apply_rule(
+    db: &JinxDb,
+    guild_id: GuildId,
+    pattern: &str,
+    role: RoleId,
+    products: &[(String, String)],
+) -> Result<u64, Error> {
+    let product_ids: Vec<String> = products
+        .iter()
+        .filter(|(_, product_name)| matches(pattern, product_name))
+        .map(|(product_id, _)| product_id.clone())
+        .collect();
+    if product_ids.is_empty() {
+        return Ok(0);
+    }
+    let created_count = db
+        .link_products(guild_id, product_ids.clone(), role)
+        .await?;
+    if created_count != 0 {
+        for product_id in product_ids {
+            db.audit(
+                guild_id,
+                AuditLogEntry::new(AuditAction::Link)
+                    .product(product_id)
+                    .role(role)
+                    .detail(format!("auto-link rule {pattern}")),
+            )
+            .await?;
+        }
+    }
+    Ok(created_count)
+}
+
+/// Apply every rule in a guild to the given products. Returns how many links were created.
+pub async fn apply_all(
+    db: &JinxDb,
+    guild_id: GuildId,
+    products: &[(String, String)],
+) -> Result<u64, Error> {
+    if products.is_empty() {
+        return Ok(0);
+    }
+    let mut created_count = 0;
+    for (pattern, role) in db.get_link_rules(guild_id).await? {
+        created_count += apply_rule(db, guild_id, &pattern, role, products).await?;
+    }
+    Ok(created_count)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("*VIP*", "Avatar VIP Pack"));
+        assert!(matches("*vip*", "Avatar VIP Pack"));
+        assert!(matches("avatar*", "Avatar VIP Pack"));
+        assert!(!matches("avatar", "Avatar VIP Pack"));
+        assert!(matches("*pack", "Avatar VIP Pack"));
+        assert!(!matches("*pack", "Avatar VIP Pack 2"));
+        assert!(matches("Avatar ?IP*", "Avatar VIP Pack"));
+        assert!(matches("*a*a*", "banana"));
+        assert!(!matches("*x*", "banana"));
+        assert!(matches("*", ""));
+        assert!(!matches("?", ""));
+    }
+}
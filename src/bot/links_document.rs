@@ -0,0 +1,155 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! JSON documents describing a guild's product→role links, for `/links_export` and `/links_import`.
+//!
+//! Products, versions, and roles are referred to by name rather than ID, so a document can be kept in version control
+//! or copied between a test and a production server whose IDs differ. On import names are matched by [`find_match`],
+//! which tolerates differences in case, punctuation, and spacing as long as the match is unambiguous.
+
+use serde::{Deserialize, Serialize};
+
+/// Largest document `/links_import` accepts
+pub const MAX_DOCUMENT_BYTES: u32 = 1024 * 1024;
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinksDocument {
+    #[serde(default)]
+    pub links: Vec<Link>,
+    #[serde(default)]
+    pub excluded_versions: Vec<ExcludedVersion>,
+    #[serde(default)]
+    pub link_rules: Vec<LinkRule>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blanket_role: Option<String>,
+}
+
+/// A product that grants a role
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Link {
+    pub product: String,
+    pub role: String,
+}
+
+/// A product version that doesn't grant a role, even though its product does
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExcludedVersion {
+    pub product: String,
+    pub version: String,
+    pub role: String,
+}
+
+/// An auto-link rule, as added with `/add_link_rule`
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkRule {
+    pub pattern: String,
+    pub role: String,
+}
+
+/// How a name in the document was matched
+#[derive(Debug, PartialEq, Eq)]
+pub enum Match<'a, T> {
+    /// The name or ID matched exactly
+    Exact(&'a T),
+    /// The name only matched after ignoring case, punctuation, and spacing, so the user should double-check it
+    Fuzzy(&'a T, &'a str),
+    /// No candidate matched, or more than one did
+    None,
+}
+
+/// Find the `(id, name)` candidate that `name` refers to. An exact name or ID wins, then a unique case and punctuation
+/// insensitive match, then a unique candidate containing `name` (or contained in it) after that same normalization.
+pub fn find_match<'a, T: AsRef<str>>(name: &str, candidates: &'a [(T, String)]) -> Match<'a, T> {
+    if let Some((id, _)) = candidates
+        .iter()
+        .find(|(id, candidate)| candidate == name || id.as_ref() == name)
+    {
+        return Match::Exact(id);
+    }
+    let name = normalize(name);
+    if name.is_empty() {
+        return Match::None;
+    }
+    let normalized: Vec<String> = candidates
+        .iter()
+        .map(|(_, candidate)| normalize(candidate))
+        .collect();
+    let tiers: [&dyn Fn(&str) -> bool; 2] = [&|candidate| candidate == name, &|candidate| {
+        !candidate.is_empty() && (candidate.contains(&name) || name.contains(candidate))
+    }];
+    for tier in tiers {
+        let mut matches = candidates
+            .iter()
+            .zip(&normalized)
+            .filter(|(_, candidate)| tier(candidate));
+        match (matches.next(), matches.next()) {
+            (Some(((id, candidate), _)), None) => return Match::Fuzzy(id, candidate),
+            (Some(_), Some(_)) => return Match::None,
+            (None, _) => {}
+        }
+    }
+    Match::None
+}
+
+/// Lowercase alphanumerics only, so "Cool Avatar (PC)" and "cool-avatar pc" compare equal
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|char| char.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn candidates() -> Vec<(String, String)> {
+        [
+            ("1", "Cool Avatar (PC)"),
+            ("2", "Cool Avatar (Quest)"),
+            ("3", "Hat"),
+        ]
+        .into_iter()
+        .map(|(id, name)| (id.to_string(), name.to_string()))
+        .collect()
+    }
+
+    #[test]
+    fn test_find_match() {
+        let candidates = candidates();
+        assert_eq!(
+            find_match("Cool Avatar (PC)", &candidates),
+            Match::Exact(&"1".to_string())
+        );
+        assert_eq!(find_match("3", &candidates), Match::Exact(&"3".to_string()));
+        assert_eq!(
+            find_match("cool-avatar pc", &candidates),
+            Match::Fuzzy(&"1".to_string(), "Cool Avatar (PC)")
+        );
+        assert_eq!(
+            find_match("quest", &candidates),
+            Match::Fuzzy(&"2".to_string(), "Cool Avatar (Quest)")
+        );
+        // matches both avatars
+        assert_eq!(find_match("cool avatar", &candidates), Match::None);
+        assert_eq!(find_match("shoes", &candidates), Match::None);
+        assert_eq!(find_match("???", &candidates), Match::None);
+    }
+
+    #[test]
+    fn test_document_defaults() {
+        let document: LinksDocument =
+            serde_json::from_str(r#"{"links": [{"product": "Hat", "role": "Hat Owner"}]}"#)
+                .unwrap();
+        assert_eq!(
+            document,
+            LinksDocument {
+                links: vec![Link {
+                    product: "Hat".to_string(),
+                    role: "Hat Owner".to_string()
+                }],
+                ..Default::default()
+            }
+        );
+    }
+}
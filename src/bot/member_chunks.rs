@@ -0,0 +1,123 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Fetching guild members over the gateway a chunk at a time, for commands that reconcile roles across many users.
+//!
+//! Looking members up with one REST request each is far too slow on large servers. Instead we ask the gateway for the
+//! members we need and Discord streams them back as `GuildMembersChunk` events, which the event handler hands to
+//! [`receive`]. Every gateway request gets its own nonce, so concurrent fetches never see each other's chunks, and a
+//! fetch is done once each of its requests has received all the chunks Discord said it would send.
+
+use crate::bot::Error;
+use crate::error::JinxError;
+use poise::serenity_prelude as serenity;
+use serenity::{ChunkGuildFilter, GuildId, GuildMembersChunkEvent, Member, UserId};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use tokio::sync::mpsc;
+use tokio::time::Duration;
+
+/// Most users Discord will look up in a single request by user ID
+const USER_ID_REQUEST_LIMIT: usize = 100;
+
+/// How long to wait for the next chunk before giving up on the fetch
+const CHUNK_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Fetches waiting on chunks, by request nonce
+static PENDING: LazyLock<Mutex<HashMap<String, mpsc::UnboundedSender<GuildMembersChunkEvent>>>> =
+    LazyLock::new(Default::default);
+
+static NEXT_NONCE: AtomicU64 = AtomicU64::new(0);
+
+/// Pass a `GuildMembersChunk` event to the fetch that requested it. Chunks nobody is waiting on are ignored.
+pub fn receive(chunk: &GuildMembersChunkEvent) {
+    let Some(nonce) = &chunk.nonce else {
+        return;
+    };
+    if let Some(sender) = PENDING.lock().unwrap().get(nonce) {
+        // the fetch may have just timed out, in which case nobody needs this chunk anymore
+        let _ = sender.send(chunk.clone());
+    }
+}
+
+/// Removes a fetch's nonces from [`PENDING`] when it finishes, however it finishes
+struct PendingGuard {
+    nonces: Vec<String>,
+}
+
+impl Drop for PendingGuard {
+    fn drop(&mut self) {
+        let mut pending = PENDING.lock().unwrap();
+        for nonce in &self.nonces {
+            pending.remove(nonce);
+        }
+    }
+}
+
+/// Fetch the members of a guild with the given user IDs. Users that aren't in the guild are left out of the result.
+pub async fn fetch_members(
+    context: &serenity::Context,
+    guild_id: GuildId,
+    user_ids: &[UserId],
+) -> Result<HashMap<UserId, Member>, Error> {
+    let mut members = HashMap::with_capacity(user_ids.len());
+    if user_ids.is_empty() {
+        return Ok(members);
+    }
+
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let mut guard = PendingGuard { nonces: Vec::new() };
+    for request_user_ids in user_ids.chunks(USER_ID_REQUEST_LIMIT) {
+        let nonce = format!(
+            "jinx_members_{}",
+            NEXT_NONCE.fetch_add(1, Ordering::Relaxed)
+        );
+        PENDING
+            .lock()
+            .unwrap()
+            .insert(nonce.clone(), sender.clone());
+        guard.nonces.push(nonce.clone());
+        context.shard.chunk_guild(
+            guild_id,
+            None,
+            false,
+            ChunkGuildFilter::UserIds(request_user_ids.to_vec()),
+            Some(nonce),
+        );
+    }
+    drop(sender);
+
+    // chunks received so far and chunks expected, per request. A request's chunk count is only known once its first chunk arrives.
+    let mut progress: HashMap<String, (u32, u32)> = HashMap::with_capacity(guard.nonces.len());
+    let done = |progress: &HashMap<String, (u32, u32)>| {
+        progress.len() == guard.nonces.len()
+            && progress
+                .values()
+                .all(|(received, expected)| received >= expected)
+    };
+    while !done(&progress) {
+        let chunk = tokio::time::timeout(CHUNK_TIMEOUT, receiver.recv())
+            .await
+            .ok()
+            .flatten()
+            .ok_or_else(|| {
+                JinxError::new(format!(
+                    "timed out fetching members of {}: got responses to {} of {} requests",
+                    guild_id.get(),
+                    progress
+                        .values()
+                        .filter(|(received, expected)| received >= expected)
+                        .count(),
+                    guard.nonces.len()
+                ))
+            })?;
+        let Some(nonce) = chunk.nonce else {
+            continue;
+        };
+        let entry = progress.entry(nonce).or_insert((0, chunk.chunk_count));
+        entry.0 += 1;
+        members.extend(chunk.members);
+    }
+    Ok(members)
+}
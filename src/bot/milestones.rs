@@ -0,0 +1,134 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Celebration posts when a guild reaches an activation milestone, either across all its products or for a single
+//! product.
+//!
+//! Only the highest milestone reached is ever posted, and each milestone is posted at most once. That makes the posts
+//! retroactive: if a guild turns milestones on after passing 500 activations, it gets a post for 500 right away.
+
+use crate::bot::i18n;
+use crate::bot::i18n::Text;
+use crate::bot::util::{send_bot_log_message, SafeDisplayExt as _};
+use crate::bot::{Data, Error};
+use crate::db::CountRedaction;
+use poise::serenity_prelude::{CacheHttp, ChannelId, Colour, CreateEmbed, CreateMessage, GuildId};
+
+/// Activation counts worth celebrating, in ascending order
+const MILESTONES: [u64; 12] = [
+    10, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000,
+];
+
+/// Find the highest milestone a count has reached
+fn milestone_reached(count: u64) -> Option<u64> {
+    MILESTONES
+        .into_iter()
+        .take_while(|milestone| *milestone <= count)
+        .last()
+}
+
+/// Post a celebration for a product if it has reached a new milestone. `product_name` is only used for display.
+pub async fn celebrate_product(
+    cache_http: impl CacheHttp,
+    data: &Data,
+    guild_id: GuildId,
+    product_id: String,
+    product_name: &str,
+    activation_count: u64,
+) -> Result<(), Error> {
+    let Some(channel) = data.db.get_milestone_channel(guild_id).await? else {
+        return Ok(());
+    };
+    let description = |locale: Option<&str>, milestone: u64| {
+        i18n::format(
+            locale,
+            Text::MilestoneProduct,
+            &[
+                ("product", &product_name.safe_display()),
+                ("count", &milestone),
+            ],
+        )
+    };
+    celebrate(
+        cache_http,
+        data,
+        guild_id,
+        channel,
+        Some(product_id),
+        activation_count,
+        description,
+    )
+    .await
+}
+
+/// Post a celebration for the whole guild if it has reached a new milestone
+pub async fn celebrate_guild(
+    cache_http: impl CacheHttp,
+    data: &Data,
+    guild_id: GuildId,
+) -> Result<(), Error> {
+    let Some(channel) = data.db.get_milestone_channel(guild_id).await? else {
+        return Ok(());
+    };
+    let activation_count = data.db.guild_license_activation_count(guild_id).await?;
+    let description = |locale: Option<&str>, milestone: u64| {
+        i18n::format(locale, Text::MilestoneGuild, &[("count", &milestone)])
+    };
+    celebrate(
+        cache_http,
+        data,
+        guild_id,
+        channel,
+        None,
+        activation_count,
+        description,
+    )
+    .await
+}
+
+async fn celebrate(
+    cache_http: impl CacheHttp,
+    data: &Data,
+    guild_id: GuildId,
+    channel: ChannelId,
+    product_id: Option<String>,
+    activation_count: u64,
+    description: impl FnOnce(Option<&str>, u64) -> String,
+) -> Result<(), Error> {
+    let Some(milestone) = milestone_reached(activation_count) else {
+        return Ok(());
+    };
+    // milestones are already coarse, so only a guild hiding its counts entirely needs them suppressed
+    if data.db.get_public_count_redaction(guild_id).await? == CountRedaction::Hidden {
+        return Ok(());
+    }
+    // claiming the milestone before posting means concurrent registrations can't both post it
+    if data
+        .db
+        .celebrate_milestone(guild_id, product_id, milestone)
+        .await?
+    {
+        let locale = i18n::guild_locale(&data.db, guild_id, None).await?;
+        let embed = CreateEmbed::default()
+            .title(i18n::text(locale, Text::MilestoneTitle))
+            .description(description(locale, milestone))
+            .color(Colour::GOLD);
+        send_bot_log_message(cache_http, channel, CreateMessage::default().embed(embed)).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_milestone_reached() {
+        assert_eq!(milestone_reached(0), None);
+        assert_eq!(milestone_reached(9), None);
+        assert_eq!(milestone_reached(10), Some(10));
+        assert_eq!(milestone_reached(499), Some(250));
+        assert_eq!(milestone_reached(500), Some(500));
+        assert_eq!(milestone_reached(u64::MAX), Some(100_000));
+    }
+}
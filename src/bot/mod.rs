@@ -1,20 +1,49 @@
 // This file is part of jinx. Copyright © 2024 jinx contributors.
 // jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
 
+mod activation_hooks;
+mod advisories;
+mod announcements;
+mod autocomplete;
 mod cache;
 mod commands;
+mod dead_roles;
+mod drain;
 mod error_handler;
 mod event_handler;
+mod event_webhooks;
+mod i18n;
+mod license_expiry;
+mod license_import;
+mod link_rules;
+mod links_document;
+mod member_chunks;
+mod milestones;
+mod notification_digest;
+mod policy;
+mod presence;
+mod product_changes;
+mod sales_feed;
+mod schedule;
+mod suspicious_activity;
+mod tasks;
+mod update_notifications;
 pub mod util;
+mod verification;
 
+use crate::admin;
 use crate::bot::cache::ApiCache;
 use crate::bot::error_handler::error_handler;
 use crate::bot::event_handler::event_handler;
+use crate::bot::schedule::{Schedule, UtcTime};
+use crate::config;
 use crate::db::JinxDb;
 use crate::error::JinxError;
+use crate::http::jinxxy;
 use commands::*;
 use poise::{serenity_prelude as serenity, Command, PrefixFrameworkOptions};
-use serenity::GatewayIntents;
+use secrecy::ExposeSecret as _;
+use serenity::{ActivityData, GatewayIntents, OnlineStatus};
 use std::sync::{Arc, LazyLock};
 use tokio::time::{Duration, Instant};
 use tracing::{debug, error, info};
@@ -26,42 +55,293 @@ type Context<'a> = poise::Context<'a, Data, Error>;
 pub static MISSING_API_KEY_MESSAGE: &str =
     "Jinxxy API key is not set: please use the `/init` command to set it.";
 
+/// How long to wait after the registered user count changes before updating the bot's presence
+const PRESENCE_UPDATE_DEBOUNCE: Duration = Duration::from_secs(10);
+
+/// How long to keep Jinxxy API health samples
+const API_HEALTH_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// How often to check for cache invalidations from other instances sharing the DB
+const CACHE_INVALIDATION_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often to check stores with a sales feed for new orders
+const SALES_FEED_POLL_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How often to check for a new release to tell owners about
+const UPDATE_CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How often to look for links to deleted roles
+const DEAD_ROLE_CHECK_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// How often to check for advisories to send
+const ADVISORY_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often to look over recent registrations for suspicious patterns
+const SUSPICIOUS_ACTIVITY_CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// How often to deliver new events to guild event webhooks
+const EVENT_WEBHOOK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often to re-check subscription licenses that are about to expire
+const LICENSE_EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How often to remove roles from activations whose "schedule expiry" hook has come due
+const SCHEDULED_EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often to post notification digests that are due
+const NOTIFICATION_DIGEST_FLUSH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How often to check if yesterday's daily metrics need recording
+const DAILY_METRICS_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How often to retry guild command registrations that failed
+const COMMAND_SYNC_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How often to look for stores that are due for a background product cache refresh
+const CACHE_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long to keep cache invalidations. This only needs to comfortably exceed the poll interval.
+const CACHE_INVALIDATION_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
 const REGISTER_MODAL_ID: &str = "jinx_register_modal";
+const DM_GUILD_SELECT_ID: &str = "jinx_dm_guild_select";
+/// Prefix for the register form shown in DMs. The remainder of the ID is the guild to register in.
+const DM_REGISTER_MODAL_ID_PREFIX: &str = "jinx_dm_register_modal_";
+/// Button on the message posted when the bot is re-added to a guild that lost product links
+const REJOIN_RELINK_BUTTON_ID: &str = "jinx_rejoin_relink_button";
+/// Prefix for the per-product buttons on a claim post. The remainder of the ID is the product ID.
+const CLAIM_BUTTON_ID_PREFIX: &str = "jinx_claim_button_";
+/// Prefix for the register form opened by a claim button. The remainder of the ID is the product ID.
+const CLAIM_MODAL_ID_PREFIX: &str = "jinx_claim_modal_";
 
 /// commands to be installed globally
-static GLOBAL_COMMANDS: LazyLock<Vec<Command<Data, Error>>> =
-    LazyLock::new(|| vec![help(), init(), version()]);
+static GLOBAL_COMMANDS: LazyLock<Vec<Command<Data, Error>>> = LazyLock::new(|| {
+    vec![
+        help(),
+        init(),
+        preview_roles(),
+        setup(),
+        setup_progress(),
+        version(),
+    ]
+});
 
 /// commands to be installed only after successful Jinxxy init
 static CREATOR_COMMANDS: LazyLock<Vec<Command<Data, Error>>> = LazyLock::new(|| {
     vec![
+        activity_export(),
+        add_activation_hook(),
+        add_link_rule(),
+        audit_log(),
+        create_claim_post(),
         create_post(),
         deactivate_license(),
+        exclude_product_version(),
+        grant_missing_roles(),
+        import_licenses(),
+        include_product_version(),
+        license_history(),
         license_info(),
         link_product(),
+        link_products_bulk(),
+        links_export(),
+        links_import(),
+        list_activation_hooks(),
+        list_link_rules(),
         list_links(),
         lock_license(),
+        lookup_license(),
+        pause_store(),
+        remove_activation_hook(),
+        remove_link_rule(),
+        review_links(),
+        rotate_api_key(),
+        rotate_event_webhook_secret(),
+        set_blanket_role(),
+        set_event_webhook(),
+        set_language(),
         set_log_channel(),
+        set_log_member_leave(),
+        set_log_product_changes(),
+        set_milestone_channel(),
+        set_notification_digest(),
+        set_product_seats(),
+        set_public_count_redaction(),
+        set_release_channel(),
+        set_restore_roles(),
+        set_sales_feed(),
+        set_stats_opt_out(),
         stats(),
+        sudo_guild_config(),
+        top_products(),
+        transfer_license(),
         unlink_product(),
         unlock_license(),
+        update_post(),
         user_info(),
+        verify_setup(),
     ]
 });
 
 /// commands to be installed only for owner-owned guilds
 static OWNER_COMMANDS: LazyLock<Vec<Command<Data, Error>>> = LazyLock::new(|| {
     vec![
+        add_message_variant(),
         announce(),
+        announce_status(),
         announce_test(),
+        api_health(),
+        clear_cache(),
         exit(),
+        export_cache(),
+        ignore_update_version(),
+        import_cache(),
+        message_experiments(),
         owner_stats(),
+        permission_matrix(),
         restart(),
+        retire_message_variant(),
+        running_tasks(),
+        set_cache_warm_schedule(),
+        set_confirmation_mode(),
+        set_incident_mode(),
+        set_presence_interval(),
+        set_presence_messages(),
+        set_release_channel(),
+        set_slow_query_threshold(),
         set_test(),
+        sudo_guild_config(),
+        trends(),
+        tune_db(),
         verify_guild(),
     ]
 });
 
+/// How expensive a command is to run, which decides its per-guild cooldown. Commands declare their tier with
+/// `custom_data = "CooldownTier::Expensive"`; commands without one have no cooldown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(in crate::bot) enum CooldownTier {
+    /// Makes many Jinxxy API calls or does a lot of Discord work
+    Expensive,
+    /// Cheap, but could still be spammed
+    Cheap,
+}
+
+impl CooldownTier {
+    fn of<U, E>(command: &Command<U, E>) -> Option<Self> {
+        command.custom_data.downcast_ref().copied()
+    }
+
+    /// Per-guild cooldown for this tier, as set in the config file
+    fn guild_cooldown(self) -> Duration {
+        match self {
+            CooldownTier::Expensive => config::get().expensive_command_cooldown,
+            CooldownTier::Cheap => config::get().cheap_command_cooldown,
+        }
+    }
+}
+
+/// Apply cooldowns to commands based on their [`CooldownTier`]
+fn with_cooldowns(commands: Vec<Command<Data, Error>>) -> Vec<Command<Data, Error>> {
+    for command in &commands {
+        if let Some(tier) = CooldownTier::of(command) {
+            command.cooldown_config.write().unwrap().guild = Some(tier.guild_cooldown());
+        }
+    }
+    commands
+}
+
+/// Every command, with cooldowns applied. All commands must appear in this list otherwise poise won't recognize
+/// interactions for them.
+///
+/// This vec is terribly redundant, but because we can't clone Command and it ONLY takes a Vec<Command>, this is the only
+/// option.
+fn all_commands() -> Vec<Command<Data, Error>> {
+    with_cooldowns(vec![
+        activity_export(),
+        add_activation_hook(),
+        add_link_rule(),
+        add_message_variant(),
+        announce(),
+        announce_status(),
+        announce_test(),
+        api_health(),
+        audit_log(),
+        clear_cache(),
+        create_claim_post(),
+        create_post(),
+        deactivate_license(),
+        exclude_product_version(),
+        exit(),
+        export_cache(),
+        grant_missing_roles(),
+        help(),
+        ignore_update_version(),
+        import_cache(),
+        import_licenses(),
+        include_product_version(),
+        init(),
+        license_history(),
+        license_info(),
+        link_product(),
+        link_products_bulk(),
+        links_export(),
+        links_import(),
+        list_activation_hooks(),
+        list_link_rules(),
+        list_links(),
+        lock_license(),
+        lookup_license(),
+        message_experiments(),
+        owner_stats(),
+        pause_store(),
+        permission_matrix(),
+        preview_roles(),
+        remove_activation_hook(),
+        remove_link_rule(),
+        restart(),
+        retire_message_variant(),
+        review_links(),
+        rotate_api_key(),
+        rotate_event_webhook_secret(),
+        running_tasks(),
+        set_blanket_role(),
+        set_cache_warm_schedule(),
+        set_confirmation_mode(),
+        set_event_webhook(),
+        set_incident_mode(),
+        set_language(),
+        set_log_channel(),
+        set_log_member_leave(),
+        set_log_product_changes(),
+        set_milestone_channel(),
+        set_notification_digest(),
+        set_presence_interval(),
+        set_presence_messages(),
+        set_product_seats(),
+        set_public_count_redaction(),
+        set_restore_roles(),
+        set_sales_feed(),
+        set_slow_query_threshold(),
+        set_stats_opt_out(),
+        set_test(),
+        setup(),
+        setup_progress(),
+        stats(),
+        top_products(),
+        transfer_license(),
+        trends(),
+        tune_db(),
+        unlink_product(),
+        unlock_license(),
+        update_post(),
+        user_info(),
+        verify_guild(),
+        verify_setup(),
+        version(),
+    ])
+}
+
 /// User data, which is stored and accessible in all command invocations
 struct Data {
     db: Arc<JinxDb>,
@@ -73,37 +353,16 @@ pub async fn run_bot() -> Result<(), Error> {
     debug!("DB opened");
     let discord_token = db.get_discord_token().await?
         .ok_or_else(|| JinxError::new("discord token not provided. Re-run the application with the `init` subcommand to run first-time setup."))?;
+    // GUILD_MEMBERS is privileged, and must be enabled in the bot's settings on the Discord developer portal
     let intents = GatewayIntents::GUILDS
+        .union(GatewayIntents::GUILD_MEMBERS)
         .union(GatewayIntents::GUILD_MESSAGES)
         .union(GatewayIntents::DIRECT_MESSAGES);
 
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
-            // all commands must appear in this list otherwise poise won't recognize interactions for them
-            // this vec is terribly redundant, but because we can't clone Command and it ONLY takes a Vec<Command>, this is the only option.
-            commands: vec![
-                announce(),
-                announce_test(),
-                create_post(),
-                deactivate_license(),
-                exit(),
-                help(),
-                init(),
-                license_info(),
-                link_product(),
-                list_links(),
-                lock_license(),
-                owner_stats(),
-                restart(),
-                set_log_channel(),
-                set_test(),
-                stats(),
-                unlink_product(),
-                unlock_license(),
-                user_info(),
-                verify_guild(),
-                version(),
-            ],
+            commands: all_commands(),
+            command_check: Some(|context| Box::pin(policy::check(context))),
             event_handler: |ctx, event, framework, data| {
                 Box::pin(event_handler(ctx, event, framework, data))
             },
@@ -141,7 +400,7 @@ pub async fn run_bot() -> Result<(), Error> {
                 const HOURS_PER_DAY: u64 = 24;
                 const SECONDS_PER_DAY: u64 = SECONDS_PER_MINUTE * MINUTES_PER_HOUR * HOURS_PER_DAY;
 
-                // set up the task to periodically optimize the DB
+                // set up the task to periodically optimize the DB, purge removed guilds, and reconcile materialized counters
                 {
                     let db_clone = db.clone();
                     tokio::task::spawn(async move {
@@ -153,11 +412,62 @@ pub async fn run_bot() -> Result<(), Error> {
                             }
                             let elapsed = start.elapsed();
                             info!("optimized db in {}ms", elapsed.as_millis());
+                            let purge_before_unix_ms = std::time::SystemTime::now()
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .map(|duration| duration.as_millis() as u64)
+                                .unwrap_or(0)
+                                .saturating_sub(config::get().guild_retention.as_millis() as u64);
+                            match db_clone.purge_deleted_guilds(purge_before_unix_ms).await {
+                                Ok(guilds) if !guilds.is_empty() => {
+                                    info!("purged data for {} removed guilds", guilds.len())
+                                }
+                                Ok(_) => {}
+                                Err(e) => error!("Error purging removed guilds: {:?}", e),
+                            }
+                            if let Err(e) = db_clone.reconcile_counters().await {
+                                error!("Error reconciling DB counters: {:?}", e);
+                            }
+                            let prune_before_unix_ms = std::time::SystemTime::now()
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .map(|duration| duration.as_millis() as u64)
+                                .unwrap_or(0)
+                                .saturating_sub(API_HEALTH_RETENTION.as_millis() as u64);
+                            if let Err(e) = db_clone.prune_api_samples(prune_before_unix_ms).await {
+                                error!("Error pruning API health samples: {:?}", e);
+                            }
+                            let prune_before_unix_ms = std::time::SystemTime::now()
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .map(|duration| duration.as_millis() as u64)
+                                .unwrap_or(0)
+                                .saturating_sub(CACHE_INVALIDATION_RETENTION.as_millis() as u64);
+                            if let Err(e) = db_clone
+                                .prune_cache_invalidations(prune_before_unix_ms)
+                                .await
+                            {
+                                error!("Error pruning cache invalidations: {:?}", e);
+                            }
+                        }
+                    });
+                }
+
+                // set up the task to periodically persist Jinxxy API health samples
+                {
+                    let db_clone = db.clone();
+                    tokio::task::spawn(async move {
+                        loop {
+                            tokio::time::sleep(Duration::from_secs(SECONDS_PER_MINUTE)).await;
+                            let samples = jinxxy::drain_health_samples();
+                            if !samples.is_empty() {
+                                if let Err(e) = db_clone.record_api_samples(samples).await {
+                                    error!("Error recording API health samples: {:?}", e);
+                                }
+                            }
                         }
                     });
                 }
 
                 let api_cache = Arc::new(ApiCache::default());
+                api_cache.set_discord_http(ctx.http.clone());
 
                 // set up the task to periodically clean the API cache
                 {
@@ -176,6 +486,313 @@ pub async fn run_bot() -> Result<(), Error> {
                     });
                 }
 
+                // set up the task to drop cached data that other instances sharing the DB have invalidated
+                {
+                    let db_clone = db.clone();
+                    let api_cache_clone = api_cache.clone();
+                    tokio::task::spawn(async move {
+                        loop {
+                            tokio::time::sleep(CACHE_INVALIDATION_POLL_INTERVAL).await;
+                            match db_clone.poll_cache_invalidations().await {
+                                Ok(guild_ids) => {
+                                    for guild_id in guild_ids {
+                                        api_cache_clone.clear(Some(guild_id));
+                                    }
+                                }
+                                Err(e) => error!("Error polling cache invalidations: {:?}", e),
+                            }
+                        }
+                    });
+                }
+
+                // set up the task to rotate through the presence messages and keep their counts up to date
+                {
+                    let db_clone = db.clone();
+                    let ctx_clone = ctx.clone();
+                    tokio::task::spawn(async move {
+                        let mut index: usize = 0;
+                        loop {
+                            let messages = match db_clone.get_presence_messages().await {
+                                Ok(Some(messages)) if !messages.is_empty() => messages,
+                                Ok(_) => presence::DEFAULT_PRESENCE_MESSAGES
+                                    .iter()
+                                    .map(|message| message.to_string())
+                                    .collect(),
+                                Err(e) => {
+                                    error!("Error reading presence messages: {:?}", e);
+                                    tokio::time::sleep(PRESENCE_UPDATE_DEBOUNCE).await;
+                                    continue;
+                                }
+                            };
+                            let interval = db_clone
+                                .get_presence_rotation_interval()
+                                .await
+                                .unwrap_or_else(|e| {
+                                    error!("Error reading presence rotation interval: {:?}", e);
+                                    Duration::from_secs(SECONDS_PER_MINUTE)
+                                });
+
+                            let message = &messages[index % messages.len()];
+                            let guild_count = if presence::needs_guild_count(message) {
+                                db_clone.guild_count().await.unwrap_or_else(|e| {
+                                    error!("Error reading guild count for presence: {:?}", e);
+                                    0
+                                })
+                            } else {
+                                0
+                            };
+                            let message = presence::render(
+                                message,
+                                db_clone.distinct_user_count(),
+                                guild_count,
+                            );
+                            ctx_clone.set_presence(
+                                Some(ActivityData::custom(message)),
+                                OnlineStatus::Online,
+                            );
+
+                            let rotate = async {
+                                if messages.len() > 1 {
+                                    tokio::time::sleep(interval).await
+                                } else {
+                                    std::future::pending().await
+                                }
+                            };
+                            tokio::select! {
+                                _ = rotate => index = index.wrapping_add(1),
+                                _ = db_clone.distinct_user_count_changed() => {
+                                    // wait a bit so a burst of registrations results in a single presence update
+                                    tokio::time::sleep(PRESENCE_UPDATE_DEBOUNCE).await;
+                                }
+                                _ = db_clone.presence_config_changed() => {}
+                            }
+                        }
+                    });
+                }
+
+                // set up the task to send scheduled announcements once they're due
+                {
+                    let db_clone = db.clone();
+                    let ctx_clone = ctx.clone();
+                    tokio::task::spawn(async move {
+                        loop {
+                            tokio::time::sleep(Duration::from_secs(SECONDS_PER_MINUTE)).await;
+                            announcements::deliver_due(&ctx_clone, &db_clone).await;
+                        }
+                    });
+                }
+
+                // set up the task to post new purchases to sales feed channels
+                {
+                    let db_clone = db.clone();
+                    let ctx_clone = ctx.clone();
+                    tokio::task::spawn(async move {
+                        loop {
+                            tokio::time::sleep(SALES_FEED_POLL_INTERVAL).await;
+                            sales_feed::poll_all(&ctx_clone, &db_clone).await;
+                        }
+                    });
+                }
+
+                // set up the task to tell owners about new releases
+                {
+                    let db_clone = db.clone();
+                    let ctx_clone = ctx.clone();
+                    tokio::task::spawn(async move {
+                        loop {
+                            tokio::time::sleep(UPDATE_CHECK_INTERVAL).await;
+                            update_notifications::notify_owners(&ctx_clone, &db_clone).await;
+                        }
+                    });
+                }
+
+                // serve admin requests from the owner CLI, if configured
+                if let Some(socket) = config::get().admin_socket.clone() {
+                    let db_clone = db.clone();
+                    tokio::task::spawn(async move {
+                        if let Err(e) = admin::serve(socket, db_clone).await {
+                            error!("Error serving admin socket: {:?}", e);
+                        }
+                    });
+                }
+
+                // set up the task to retry guild command registrations that failed, such as during GuildCreate storms
+                {
+                    let db_clone = db.clone();
+                    let ctx_clone = ctx.clone();
+                    tokio::task::spawn(async move {
+                        loop {
+                            tokio::time::sleep(COMMAND_SYNC_INTERVAL).await;
+                            util::sync_pending_guild_commands(&ctx_clone.http, &db_clone).await;
+                        }
+                    });
+                }
+
+                // set up the task to persist daily metrics for trend analysis
+                {
+                    let db_clone = db.clone();
+                    tokio::task::spawn(async move {
+                        loop {
+                            tokio::time::sleep(DAILY_METRICS_INTERVAL).await;
+                            // flush pending samples first so they count towards the day they were taken in
+                            let samples = jinxxy::drain_health_samples();
+                            if !samples.is_empty() {
+                                if let Err(e) = db_clone.record_api_samples(samples).await {
+                                    error!("Error recording API health samples: {:?}", e);
+                                }
+                            }
+                            let yesterday = std::time::SystemTime::now()
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .map(|duration| duration.as_secs() / SECONDS_PER_DAY)
+                                .unwrap_or(0)
+                                .saturating_sub(1);
+                            match db_clone.record_daily_metrics(yesterday).await {
+                                Ok(true) => info!("recorded daily metrics for day {}", yesterday),
+                                Ok(false) => {}
+                                Err(e) => error!("Error recording daily metrics: {:?}", e),
+                            }
+                        }
+                    });
+                }
+
+                // set up the task to remove links to roles that were deleted
+                {
+                    let db_clone = db.clone();
+                    let ctx_clone = ctx.clone();
+                    tokio::task::spawn(async move {
+                        loop {
+                            tokio::time::sleep(DEAD_ROLE_CHECK_INTERVAL).await;
+                            dead_roles::clean_up_all(&ctx_clone, &db_clone).await;
+                        }
+                    });
+                }
+
+                // set up the task to alert guilds about suspicious registration patterns
+                {
+                    let db_clone = db.clone();
+                    let ctx_clone = ctx.clone();
+                    tokio::task::spawn(async move {
+                        loop {
+                            tokio::time::sleep(SUSPICIOUS_ACTIVITY_CHECK_INTERVAL).await;
+                            suspicious_activity::check_all(&ctx_clone, &db_clone).await;
+                        }
+                    });
+                }
+
+                // set up the task to push new activation events to guild event webhooks
+                {
+                    let db_clone = db.clone();
+                    let ctx_clone = ctx.clone();
+                    tokio::task::spawn(async move {
+                        loop {
+                            tokio::time::sleep(EVENT_WEBHOOK_INTERVAL).await;
+                            event_webhooks::deliver_all(&ctx_clone, &db_clone).await;
+                        }
+                    });
+                }
+
+                // set up the task to remove roles granted by lapsed subscription licenses
+                {
+                    let db_clone = db.clone();
+                    let ctx_clone = ctx.clone();
+                    tokio::task::spawn(async move {
+                        loop {
+                            tokio::time::sleep(LICENSE_EXPIRY_SWEEP_INTERVAL).await;
+                            license_expiry::sweep(&ctx_clone, &db_clone).await;
+                        }
+                    });
+                }
+
+                // set up the task to remove roles from activations with a scheduled expiry
+                {
+                    let db_clone = db.clone();
+                    let ctx_clone = ctx.clone();
+                    tokio::task::spawn(async move {
+                        loop {
+                            tokio::time::sleep(SCHEDULED_EXPIRY_SWEEP_INTERVAL).await;
+                            license_expiry::sweep_scheduled(&ctx_clone, &db_clone).await;
+                        }
+                    });
+                }
+
+                // set up the task to post batched activation notifications to bot log channels
+                {
+                    let db_clone = db.clone();
+                    let ctx_clone = ctx.clone();
+                    tokio::task::spawn(async move {
+                        loop {
+                            tokio::time::sleep(NOTIFICATION_DIGEST_FLUSH_INTERVAL).await;
+                            notification_digest::flush_due(&ctx_clone, &db_clone).await;
+                        }
+                    });
+                }
+
+                // set up the task to send advisories to guilds that are stuck or missing something
+                {
+                    let db_clone = db.clone();
+                    let ctx_clone = ctx.clone();
+                    tokio::task::spawn(async move {
+                        loop {
+                            tokio::time::sleep(ADVISORY_CHECK_INTERVAL).await;
+                            advisories::deliver_all(&ctx_clone, &db_clone).await;
+                        }
+                    });
+                }
+
+                // set up the task to pre-warm the API cache on the owner-configured schedule
+                {
+                    let db_clone = db.clone();
+                    let api_cache_clone = api_cache.clone();
+                    tokio::task::spawn(async move {
+                        // finish refreshes interrupted by the last restart before resuming the schedule
+                        if let Err(e) = api_cache_clone.replay_pending_refreshes(&db_clone).await {
+                            error!("Error replaying pending cache refreshes: {:?}", e);
+                        }
+                        // later changes come from /set_cache_warm_schedule, which parses the schedule itself
+                        match db_clone.get_cache_warm_schedule().await {
+                            Ok(Some(schedule)) => match Schedule::parse(&schedule) {
+                                Ok(parsed) => api_cache_clone.set_warm_schedule(Some(parsed)),
+                                Err(e) => {
+                                    error!("Invalid cache warm schedule \"{}\": {}", schedule, e)
+                                }
+                            },
+                            Ok(None) => {}
+                            Err(e) => error!("Error reading cache warm schedule: {:?}", e),
+                        }
+                        loop {
+                            // wake up at the top of each minute
+                            let seconds_into_minute = std::time::SystemTime::now()
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .map(|duration| duration.as_secs() % SECONDS_PER_MINUTE)
+                                .unwrap_or(0);
+                            tokio::time::sleep(Duration::from_secs(
+                                SECONDS_PER_MINUTE - seconds_into_minute,
+                            ))
+                            .await;
+
+                            if api_cache_clone.is_warm_scheduled(UtcTime::now()) {
+                                if let Err(e) = api_cache_clone.warm(&db_clone).await {
+                                    error!("Error warming API cache: {:?}", e);
+                                }
+                            }
+                        }
+                    });
+                }
+
+                // set up the task to refresh product caches in the background, prioritizing recently used stores
+                {
+                    let db_clone = db.clone();
+                    let api_cache_clone = api_cache.clone();
+                    tokio::task::spawn(async move {
+                        loop {
+                            tokio::time::sleep(CACHE_REFRESH_INTERVAL).await;
+                            if let Err(e) = api_cache_clone.refresh_by_activity(&db_clone).await {
+                                error!("Error refreshing API cache: {:?}", e);
+                            }
+                        }
+                    });
+                }
+
                 debug!("framework setup complete");
 
                 Ok(Data { db, api_cache })
@@ -185,7 +802,7 @@ pub async fn run_bot() -> Result<(), Error> {
 
     debug!("framework built");
 
-    let mut client = serenity::ClientBuilder::new(discord_token, intents)
+    let mut client = serenity::ClientBuilder::new(discord_token.expose_secret(), intents)
         .framework(framework)
         .await
         .unwrap();
@@ -199,3 +816,31 @@ pub async fn run_bot() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cooldown_tiers() {
+        let commands = all_commands();
+        let tier = |name: &str| {
+            let command = commands
+                .iter()
+                .find(|command| command.name == name)
+                .unwrap();
+            CooldownTier::of(command)
+        };
+        assert_eq!(tier("lookup_license"), Some(CooldownTier::Expensive));
+        assert_eq!(tier("stats"), Some(CooldownTier::Cheap));
+        assert_eq!(tier("help"), None);
+        let lookup_license = commands
+            .iter()
+            .find(|command| command.name == "lookup_license")
+            .unwrap();
+        assert_eq!(
+            lookup_license.cooldown_config.read().unwrap().guild,
+            Some(config::get().expensive_command_cooldown)
+        );
+    }
+}
@@ -0,0 +1,163 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Notification digests, which batch a guild's activation notifications into one summary embed.
+//!
+//! Busy guilds can opt into an hourly or daily digest with `/set_notification_digest`. Activations in those guilds are
+//! held in the DB instead of each getting their own bot log embed, and a background task posts a summary of them once the
+//! oldest has waited the full digest interval. Errors during registration still go to the bot log right away. Switching
+//! the digest off posts whatever is waiting on the next run of the task.
+
+use crate::bot::i18n;
+use crate::bot::i18n::Text;
+use crate::bot::util::{send_bot_log_message, SafeDisplayExt as _};
+use crate::bot::Error;
+use crate::db::{DigestEntry, JinxDb, NotificationDigest};
+use poise::serenity_prelude as serenity;
+use serenity::{Colour, CreateEmbed, CreateMessage, GuildId};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::Duration;
+use tracing::warn;
+
+/// Longest digest description we'll build. Discord allows 4096 characters, and this leaves room for the overflow line.
+const MAX_DESCRIPTION_LENGTH: usize = 3900;
+
+/// Current time in ms since the epoch, as recorded on digest entries
+pub fn now_unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// How long activations wait before they're posted. `None` if they should be posted right away.
+fn interval(digest: NotificationDigest) -> Option<Duration> {
+    match digest {
+        NotificationDigest::Off => None,
+        NotificationDigest::Hourly => Some(Duration::from_secs(60 * 60)),
+        NotificationDigest::Daily => Some(Duration::from_secs(24 * 60 * 60)),
+    }
+}
+
+/// Post every digest that's due. Failures are logged and skipped.
+pub async fn flush_due(context: &serenity::Context, db: &JinxDb) {
+    let pending = match db.get_pending_digests().await {
+        Ok(pending) => pending,
+        Err(e) => {
+            warn!("Error reading pending notification digests: {:?}", e);
+            return;
+        }
+    };
+    let now_unix_ms = now_unix_ms();
+    for (guild_id, digest, oldest_unix_ms) in pending {
+        let due = interval(digest).map_or(true, |interval| {
+            now_unix_ms.saturating_sub(oldest_unix_ms) >= interval.as_millis() as u64
+        });
+        if !due {
+            continue;
+        }
+        if let Err(e) = flush(context, db, guild_id).await {
+            warn!(
+                "Error posting notification digest to {}: {:?}",
+                guild_id.get(),
+                e
+            );
+        }
+    }
+}
+
+/// Post a guild's waiting activations to its bot log channel
+async fn flush(context: &serenity::Context, db: &JinxDb, guild_id: GuildId) -> Result<(), Error> {
+    // take the entries first, so a channel we can't send to doesn't get retried every time
+    let entries = db.take_digest_entries(guild_id).await?;
+    let Some(oldest) = entries.first() else {
+        return Ok(());
+    };
+    // with no log channel the entries have nowhere to go, so they're just dropped
+    let Some(log_channel) = db.get_log_channel(guild_id).await? else {
+        return Ok(());
+    };
+    let locale = i18n::guild_locale(db, guild_id, None).await?;
+    let since = format!("<t:{}:f>", oldest.created_unix_ms / 1000);
+    let description = format!(
+        "{}{}",
+        i18n::format(
+            locale,
+            Text::LogDigest,
+            &[("count", &entries.len()), ("since", &since)],
+        ),
+        digest_lines(locale, &entries)
+    );
+    let embed = CreateEmbed::default()
+        .title(i18n::text(locale, Text::LogDigestTitle))
+        .description(description)
+        .color(Colour::DARK_GREEN);
+    send_bot_log_message(context, log_channel, CreateMessage::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// One line per activation, ending with a count of any that didn't fit in the embed
+fn digest_lines(locale: Option<&str>, entries: &[DigestEntry]) -> String {
+    let mut lines = String::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let mut line = format!(
+            "\n- <t:{}:t> {}",
+            entry.created_unix_ms / 1000,
+            i18n::format(
+                locale,
+                Text::LogDigestLine,
+                &[
+                    ("user", &format!("<@{}>", entry.user_id.get())),
+                    ("product", &entry.product_name.safe_display()),
+                ],
+            )
+        );
+        if !entry.roles.is_empty() {
+            let roles = entry
+                .roles
+                .iter()
+                .map(|role| format!("<@&{}>", role.get()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            line.push_str(format!(": {}", roles).as_str());
+        }
+        if lines.len() + line.len() > MAX_DESCRIPTION_LENGTH {
+            let more = i18n::format(
+                locale,
+                Text::AndMore,
+                &[("count", &(entries.len() - index))],
+            );
+            lines.push('\n');
+            lines.push_str(more.as_str());
+            break;
+        }
+        lines.push_str(line.as_str());
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serenity::{RoleId, UserId};
+
+    #[test]
+    fn test_digest_lines() {
+        let entry = DigestEntry {
+            created_unix_ms: 1_704_164_645_000,
+            user_id: UserId::new(1),
+            product_name: "Hat".to_string(),
+            roles: vec![RoleId::new(2), RoleId::new(3)],
+        };
+        assert_eq!(
+            digest_lines(None, std::slice::from_ref(&entry)),
+            "\n- <t:1704164645:t> <@1> registered \"Hat\": <@&2>, <@&3>"
+        );
+
+        let entries = vec![entry; 200];
+        let lines = digest_lines(None, &entries);
+        assert!(lines.len() <= MAX_DESCRIPTION_LENGTH + 20);
+        let shown = lines.matches("registered").count();
+        assert!(lines.ends_with(format!("\n…and {} more", 200 - shown).as_str()));
+    }
+}
@@ -0,0 +1,197 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Who may run which commands. Every command belongs to exactly one [`Tier`], which decides both where the command is
+//! installed and who may run it. The framework runs [`check`] before every command, so commands don't declare their own
+//! permission checks.
+
+use crate::bot::drain;
+use crate::bot::i18n;
+use crate::bot::i18n::Text;
+use crate::bot::util::error_reply;
+use crate::bot::{Context, Data, Error, CREATOR_COMMANDS, GLOBAL_COMMANDS, OWNER_COMMANDS};
+use poise::Command;
+use tracing::warn;
+
+/// Access tier of a command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tier {
+    /// Installed globally, and usable by anyone
+    Global,
+    /// Installed in guilds that have a Jinxxy API key set. Discord limits these to members with the command's default
+    /// member permissions, which server admins may override.
+    Creator,
+    /// Installed in owner guilds, and only usable there by bot owners
+    Owner,
+}
+
+impl Tier {
+    pub const ALL: [Tier; 3] = [Tier::Global, Tier::Creator, Tier::Owner];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tier::Global => "Global",
+            Tier::Creator => "Creator",
+            Tier::Owner => "Owner",
+        }
+    }
+
+    /// Commands in this tier
+    pub fn commands(self) -> &'static [Command<Data, Error>] {
+        match self {
+            Tier::Global => GLOBAL_COMMANDS.as_slice(),
+            Tier::Creator => CREATOR_COMMANDS.as_slice(),
+            Tier::Owner => OWNER_COMMANDS.as_slice(),
+        }
+    }
+
+    /// Find which tier a command belongs to
+    pub fn of(command_name: &str) -> Option<Tier> {
+        Tier::ALL.into_iter().find(|tier| {
+            tier.commands()
+                .iter()
+                .any(|command| command.name == command_name)
+        })
+    }
+}
+
+/// Facts about who is running a command and where
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Caller {
+    pub in_guild: bool,
+    pub owner_guild: bool,
+    pub owner: bool,
+}
+
+/// Decide if a caller may run a command in the given tier
+pub fn allows(tier: Tier, caller: Caller) -> bool {
+    match tier {
+        Tier::Global => true,
+        Tier::Creator => caller.in_guild,
+        Tier::Owner => caller.in_guild && caller.owner_guild && caller.owner,
+    }
+}
+
+/// Find problems with how a command in the given tier is declared. Returns a description of each problem.
+pub fn lint<U, E>(tier: Tier, command: &Command<U, E>) -> Vec<&'static str> {
+    let mut problems = Vec::new();
+    if tier != Tier::Global && command.default_member_permissions.is_empty() {
+        // without this, every member of the guild can see and run the command
+        problems.push("missing default_member_permissions");
+    }
+    problems
+}
+
+/// Framework-wide command check. Denied callers are told so here, so the resulting error needs no further reporting.
+pub async fn check(context: Context<'_>) -> Result<bool, Error> {
+    if drain::is_draining() {
+        let locale = i18n::command_locale(context).await?;
+        context
+            .send(error_reply(
+                i18n::text(locale, Text::RestartingTitle),
+                i18n::text(locale, Text::Restarting),
+            ))
+            .await?;
+        return Ok(false);
+    }
+    let command_name = context.command().name.as_str();
+    let Some(tier) = Tier::of(command_name) else {
+        // fail closed: a command that isn't in any tier was never given a policy
+        warn!("denying /{} because it is not in any tier", command_name);
+        return Ok(false);
+    };
+    if tier == Tier::Global {
+        return Ok(true);
+    }
+
+    let caller = match context.guild_id() {
+        Some(guild_id) if tier == Tier::Owner => Caller {
+            in_guild: true,
+            owner_guild: context.data().db.is_owner_guild(guild_id).await?,
+            owner: context
+                .data()
+                .db
+                .is_user_owner(context.author().id.get())
+                .await?,
+        },
+        Some(_) => Caller {
+            in_guild: true,
+            ..Default::default()
+        },
+        None => Caller::default(),
+    };
+    let allowed = allows(tier, caller);
+    if !allowed {
+        let locale = i18n::command_locale(context).await?;
+        context
+            .send(error_reply(
+                i18n::text(locale, Text::PermissionDeniedTitle),
+                i18n::text(locale, Text::PermissionDenied),
+            ))
+            .await?;
+    }
+    Ok(allowed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bot::all_commands;
+
+    #[test]
+    fn test_allows() {
+        let stranger_in_dm = Caller::default();
+        let member = Caller {
+            in_guild: true,
+            ..Default::default()
+        };
+        let owner_in_creator_guild = Caller {
+            in_guild: true,
+            owner_guild: false,
+            owner: true,
+        };
+        let member_in_owner_guild = Caller {
+            in_guild: true,
+            owner_guild: true,
+            owner: false,
+        };
+        let owner_in_owner_guild = Caller {
+            in_guild: true,
+            owner_guild: true,
+            owner: true,
+        };
+
+        assert!(allows(Tier::Global, stranger_in_dm));
+        assert!(!allows(Tier::Creator, stranger_in_dm));
+        assert!(allows(Tier::Creator, member));
+        assert!(!allows(Tier::Owner, member));
+        assert!(!allows(Tier::Owner, owner_in_creator_guild));
+        assert!(!allows(Tier::Owner, member_in_owner_guild));
+        assert!(allows(Tier::Owner, owner_in_owner_guild));
+    }
+
+    #[test]
+    fn test_every_command_has_one_tier() {
+        for command in all_commands() {
+            let tiers = Tier::ALL
+                .into_iter()
+                .filter(|tier| {
+                    tier.commands()
+                        .iter()
+                        .any(|tier_command| tier_command.name == command.name)
+                })
+                .count();
+            assert_eq!(tiers, 1, "/{} is in {} tiers", command.name, tiers);
+        }
+    }
+
+    #[test]
+    fn test_commands_pass_lint() {
+        for tier in Tier::ALL {
+            for command in tier.commands() {
+                let problems = lint(tier, command);
+                assert!(problems.is_empty(), "/{}: {:?}", command.name, problems);
+            }
+        }
+    }
+}
@@ -0,0 +1,40 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Bot presence (the custom status shown under the bot's name). Owners can configure several messages, which are
+//! rotated through on an interval. Messages may contain the following placeholders:
+//! - `{users}`: number of distinct registered users
+//! - `{guilds}`: number of configured guilds
+
+/// Messages used if the owner hasn't configured any
+pub const DEFAULT_PRESENCE_MESSAGES: [&str; 3] = [
+    "Registered {users} users",
+    "Serving {guilds} servers",
+    "/help for setup",
+];
+
+/// Check if a message needs the guild count, which (unlike the user count) has to be read from the DB
+pub fn needs_guild_count(message: &str) -> bool {
+    message.contains("{guilds}")
+}
+
+/// Fill in the placeholders in a presence message
+pub fn render(message: &str, user_count: u64, guild_count: u64) -> String {
+    message
+        .replace("{users}", user_count.to_string().as_str())
+        .replace("{guilds}", guild_count.to_string().as_str())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render("Registered {users} users in {guilds} servers", 12, 3),
+            "Registered 12 users in 3 servers"
+        );
+        assert_eq!(render("/help for setup", 12, 3), "/help for setup");
+    }
+}
@@ -0,0 +1,135 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Optional bot log notifications when a store's products change.
+//!
+//! Whenever the product cache is loaded the product list is persisted, and [`JinxDb::replace_products`] reports what
+//! changed since the last load. Guilds that opted in with `/set_log_product_changes` get a summary of new, removed, and
+//! renamed products in their bot log channel. The first load after a store is registered isn't reported, as every
+//! product would count as new.
+
+use crate::bot::i18n;
+use crate::bot::i18n::Text;
+use crate::bot::util::{field_value, send_bot_log_message, SafeDisplayExt as _};
+use crate::bot::Error;
+use crate::db::{JinxDb, ProductDiff};
+use poise::serenity_prelude as serenity;
+use serenity::{Colour, CreateEmbed, CreateMessage, GuildId};
+
+/// Post a summary of the product changes to the guild's bot log channel, if it has one and opted in
+pub async fn notify(
+    http: &serenity::Http,
+    db: &JinxDb,
+    guild_id: GuildId,
+    diff: &ProductDiff,
+) -> Result<(), Error> {
+    if !diff.had_products || diff.is_empty() {
+        return Ok(());
+    }
+    if !db.get_log_product_changes(guild_id).await? {
+        return Ok(());
+    }
+    let Some(log_channel) = db.get_log_channel(guild_id).await? else {
+        return Ok(());
+    };
+
+    let locale = i18n::guild_locale(db, guild_id, None).await?;
+    let embed = CreateEmbed::default()
+        .title(i18n::text(locale, Text::LogProductChangesTitle))
+        .description(summary(locale, diff))
+        .color(Colour::BLUE);
+    let embed = add_field(
+        embed,
+        i18n::text(locale, Text::LogNewProducts),
+        diff.added
+            .iter()
+            .map(|(_, name)| name.safe_display().to_string()),
+    );
+    let embed = add_field(
+        embed,
+        i18n::text(locale, Text::LogRemovedProducts),
+        diff.removed
+            .iter()
+            .map(|(_, name)| name.safe_display().to_string()),
+    );
+    let embed = add_field(
+        embed,
+        i18n::text(locale, Text::LogRenamedProducts),
+        diff.renamed.iter().map(|(_, old_name, new_name)| {
+            format!("{} → {}", old_name.safe_display(), new_name.safe_display())
+        }),
+    );
+    send_bot_log_message(http, log_channel, CreateMessage::default().embed(embed)).await?;
+    Ok(())
+}
+
+/// One-line summary such as "3 new products detected, 1 renamed"
+fn summary(locale: Option<&str>, diff: &ProductDiff) -> String {
+    let mut parts = Vec::new();
+    if !diff.added.is_empty() {
+        let text = if diff.added.len() == 1 {
+            Text::LogProductsAddedOne
+        } else {
+            Text::LogProductsAdded
+        };
+        parts.push(i18n::format(locale, text, &[("count", &diff.added.len())]));
+    }
+    if !diff.removed.is_empty() {
+        parts.push(i18n::format(
+            locale,
+            Text::LogProductsRemoved,
+            &[("count", &diff.removed.len())],
+        ));
+    }
+    if !diff.renamed.is_empty() {
+        parts.push(i18n::format(
+            locale,
+            Text::LogProductsRenamed,
+            &[("count", &diff.renamed.len())],
+        ));
+    }
+    let summary = parts.join(", ");
+    // "1 removed" doesn't say what was removed unless it follows the new products
+    if diff.added.is_empty() {
+        i18n::format(locale, Text::LogProductsChanged, &[("summary", &summary)])
+    } else {
+        summary
+    }
+}
+
+/// Add a field listing the items, or nothing if there are none
+fn add_field(embed: CreateEmbed, name: &str, items: impl Iterator<Item = String>) -> CreateEmbed {
+    let lines: Vec<String> = items.map(|item| format!("- {item}")).collect();
+    if lines.is_empty() {
+        embed
+    } else {
+        embed.field(name, field_value(&lines), false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn product(name: &str) -> (String, String) {
+        (format!("{name}_id"), name.to_string())
+    }
+
+    #[test]
+    fn test_summary() {
+        let diff = ProductDiff {
+            had_products: true,
+            added: vec![product("a"), product("b"), product("c")],
+            removed: vec![],
+            renamed: vec![("d_id".to_string(), "d".to_string(), "e".to_string())],
+        };
+        assert_eq!(summary(None, &diff), "3 new products detected, 1 renamed");
+        let diff = ProductDiff {
+            had_products: true,
+            added: vec![],
+            removed: vec![product("a")],
+            renamed: vec![],
+        };
+        assert_eq!(summary(None, &diff), "Products changed: 1 removed");
+    }
+}
@@ -0,0 +1,76 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! An optional per-guild feed of new purchases, posted to a channel of the creator's choosing.
+//!
+//! Jinxxy doesn't push orders to us, so a background task periodically polls each store's most recent orders and posts
+//! the ones it hasn't seen before. The first poll after the feed is set up only records what's already there.
+
+use crate::bot::i18n;
+use crate::bot::i18n::Text;
+use crate::bot::util::{send_bot_log_message, SafeDisplayExt as _};
+use crate::bot::Error;
+use crate::db::JinxDb;
+use crate::http::jinxxy;
+use poise::serenity_prelude as serenity;
+use serenity::{ChannelId, Colour, CreateEmbed, CreateMessage, GuildId};
+use tracing::warn;
+
+/// Poll every guild's sales feed. Failures are logged and skipped.
+pub async fn poll_all(context: &serenity::Context, db: &JinxDb) {
+    let feeds = match db.get_sales_feeds().await {
+        Ok(feeds) => feeds,
+        Err(e) => {
+            warn!("Error reading sales feeds: {:?}", e);
+            return;
+        }
+    };
+    for (guild_id, channel_id) in feeds {
+        if let Err(e) = poll_guild(context, db, guild_id, channel_id).await {
+            warn!("Error polling sales feed in {}: {:?}", guild_id.get(), e);
+        }
+    }
+}
+
+/// Post any orders that are new since the last poll
+async fn poll_guild(
+    context: &serenity::Context,
+    db: &JinxDb,
+    guild_id: GuildId,
+    channel_id: ChannelId,
+) -> Result<(), Error> {
+    let Some(api_key) = db.get_jinxxy_api_key(guild_id).await? else {
+        return Ok(());
+    };
+    let orders = jinxxy::get_recent_orders(&api_key).await?;
+    let order_ids = orders.iter().map(|order| order.id.clone()).collect();
+    let new_order_ids = db.record_sales_feed_orders(guild_id, order_ids).await?;
+    let locale = i18n::guild_locale(db, guild_id, None).await?;
+    // post oldest first, so the channel reads in order
+    for order in orders.iter().rev() {
+        if !new_order_ids.contains(&order.id) {
+            continue;
+        }
+        let product_names: Vec<String> = order
+            .order_items
+            .iter()
+            .filter_map(|item| item.target_name.as_deref())
+            .map(|name| format!("- {}", name.safe_display()))
+            .collect();
+        let description = if product_names.is_empty() {
+            i18n::text(locale, Text::SalesFeedPurchase).to_string()
+        } else {
+            i18n::format(
+                locale,
+                Text::SalesFeedBought,
+                &[("products", &product_names.join("\n"))],
+            )
+        };
+        let embed = CreateEmbed::default()
+            .title(i18n::text(locale, Text::SalesFeedTitle))
+            .description(description)
+            .color(Colour::DARK_GREEN);
+        send_bot_log_message(context, channel_id, CreateMessage::default().embed(embed)).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,235 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! A minimal cron-like schedule, used to trigger background work at specific wall-clock times.
+//!
+//! Schedules are five whitespace-separated fields: `minute hour day-of-month month day-of-week`, all in UTC. Each
+//! field may be `*`, a number, a range like `1-5`, a step like `*/15` or `0-30/10`, or a comma-separated list of any
+//! of those. Day-of-week uses 0 for Sunday. Unlike real cron, day-of-month and day-of-week must *both* match.
+
+use std::fmt::{Display, Formatter};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+}
+
+/// A broken-down UTC time, with only the fields a [`Schedule`] cares about
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UtcTime {
+    minute: u32,
+    hour: u32,
+    day_of_month: u32,
+    month: u32,
+    day_of_week: u32,
+}
+
+#[derive(Debug)]
+pub struct ScheduleParseError {
+    message: String,
+}
+
+impl Display for ScheduleParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ScheduleParseError {}
+
+impl ScheduleParseError {
+    fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl Schedule {
+    pub fn parse(expression: &str) -> Result<Self, ScheduleParseError> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if let [minutes, hours, days_of_month, months, days_of_week] = fields.as_slice() {
+            Ok(Self {
+                minutes: parse_field(minutes, 0, 59)?,
+                hours: parse_field(hours, 0, 23)?,
+                days_of_month: parse_field(days_of_month, 1, 31)?,
+                months: parse_field(months, 1, 12)?,
+                days_of_week: parse_field(days_of_week, 0, 6)?,
+            })
+        } else {
+            Err(ScheduleParseError::new(format!(
+                "expected 5 fields but got {}",
+                fields.len()
+            )))
+        }
+    }
+
+    /// Check if this schedule should fire at the given time
+    pub fn matches(&self, time: UtcTime) -> bool {
+        bit_set(self.minutes, time.minute)
+            && bit_set(self.hours, time.hour)
+            && bit_set(self.days_of_month, time.day_of_month)
+            && bit_set(self.months, time.month)
+            && bit_set(self.days_of_week, time.day_of_week)
+    }
+}
+
+fn bit_set(mask: u64, bit: u32) -> bool {
+    mask & (1 << bit) != 0
+}
+
+/// Parse a single field into a bitmask of allowed values
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, ScheduleParseError> {
+    let mut mask = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| ScheduleParseError::new(format!("invalid step in \"{part}\"")))?;
+                if step == 0 {
+                    return Err(ScheduleParseError::new(format!(
+                        "step may not be zero in \"{part}\""
+                    )));
+                }
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_value(start, min, max)?, parse_value(end, min, max)?)
+        } else {
+            let value = parse_value(range, min, max)?;
+            (value, value)
+        };
+        if start > end {
+            return Err(ScheduleParseError::new(format!(
+                "range is backwards in \"{part}\""
+            )));
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+fn parse_value(value: &str, min: u32, max: u32) -> Result<u32, ScheduleParseError> {
+    let parsed: u32 = value
+        .parse()
+        .map_err(|_| ScheduleParseError::new(format!("invalid value \"{value}\"")))?;
+    if parsed < min || parsed > max {
+        Err(ScheduleParseError::new(format!(
+            "value {parsed} is out of range {min}-{max}"
+        )))
+    } else {
+        Ok(parsed)
+    }
+}
+
+impl UtcTime {
+    pub fn now() -> Self {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        Self::from_unix_seconds(seconds)
+    }
+
+    fn from_unix_seconds(seconds: u64) -> Self {
+        let days = seconds / 86400;
+        let seconds_of_day = seconds % 86400;
+        let (month, day_of_month) = month_and_day_from_days(days);
+        Self {
+            minute: ((seconds_of_day / 60) % 60) as u32,
+            hour: (seconds_of_day / 3600) as u32,
+            day_of_month,
+            month,
+            // 1970-01-01 was a Thursday
+            day_of_week: ((days + 4) % 7) as u32,
+        }
+    }
+}
+
+/// Convert days since the Unix epoch into a (month, day) pair. See <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>
+fn month_and_day_from_days(days: u64) -> (u32, u32) {
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    (month as u32, day as u32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_utc_time() {
+        // 2024-08-17T15:42:00Z, a Saturday
+        assert_eq!(
+            UtcTime::from_unix_seconds(1723909320),
+            UtcTime {
+                minute: 42,
+                hour: 15,
+                day_of_month: 17,
+                month: 8,
+                day_of_week: 6,
+            }
+        );
+    }
+
+    #[test]
+    fn test_leap_day() {
+        // 2024-02-29T00:00:00Z, a Thursday
+        let time = UtcTime::from_unix_seconds(1709164800);
+        assert_eq!(
+            (time.month, time.day_of_month, time.day_of_week),
+            (2, 29, 4)
+        );
+    }
+
+    #[test]
+    fn test_matches() {
+        let schedule = Schedule::parse("*/15 14-16 * * 5,6").unwrap();
+        let time = UtcTime {
+            minute: 45,
+            hour: 15,
+            day_of_month: 17,
+            month: 8,
+            day_of_week: 6,
+        };
+        assert!(schedule.matches(time));
+        assert!(!schedule.matches(UtcTime { minute: 42, ..time }));
+        assert!(!schedule.matches(UtcTime {
+            day_of_week: 0,
+            ..time
+        }));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(Schedule::parse("* * * *").is_err());
+        assert!(Schedule::parse("60 * * * *").is_err());
+        assert!(Schedule::parse("* * 0 * *").is_err());
+        assert!(Schedule::parse("*/0 * * * *").is_err());
+        assert!(Schedule::parse("5-1 * * * *").is_err());
+        assert!(Schedule::parse("foo * * * *").is_err());
+    }
+}
@@ -0,0 +1,153 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Alerts for registration patterns that suggest license keys are being guessed, shared, or resold.
+//!
+//! A background task periodically looks over recent registrations for two patterns: one Discord account registering many
+//! distinct licenses, and several Discord accounts registering licenses bought by the same Jinxxy user. Only licenses
+//! users registered themselves count, so admins importing or transferring licenses don't set anything off. Matches are
+//! reported to the guild's bot log channel. Each one is only reported once per window, even though it keeps matching
+//! until its registrations age out.
+
+use crate::bot::i18n;
+use crate::bot::i18n::Text;
+use crate::bot::util::send_bot_log_message;
+use crate::bot::Error;
+use crate::db::JinxDb;
+use poise::serenity_prelude as serenity;
+use serenity::{Colour, CreateEmbed, CreateMessage, GuildId};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::Duration;
+use tracing::warn;
+
+/// Window over which a single user's registrations are counted
+const PROLIFIC_REGISTRANT_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Distinct licenses a single user may register within the window before it's reported
+const PROLIFIC_REGISTRANT_LIMIT: u64 = 5;
+
+/// Window over which the users registering a single buyer's licenses are counted
+const SHARED_BUYER_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Distinct users that may register a single buyer's licenses within the window before it's reported
+const SHARED_BUYER_LIMIT: u64 = 3;
+
+/// Alert kinds, as persisted to the DB. Do not change these!
+const PROLIFIC_REGISTRANT_ALERT: &str = "prolific_registrant";
+const SHARED_BUYER_ALERT: &str = "shared_buyer";
+
+/// Check recent registrations in every guild for suspicious patterns. Failures are logged and skipped.
+pub async fn check_all(context: &serenity::Context, db: &JinxDb) {
+    let now_unix_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0);
+
+    let since = now_unix_ms.saturating_sub(PROLIFIC_REGISTRANT_WINDOW.as_millis() as u64);
+    match db
+        .get_prolific_registrants(since, PROLIFIC_REGISTRANT_LIMIT)
+        .await
+    {
+        Ok(registrants) => {
+            for (guild_id, user_id, license_count) in registrants {
+                let message = |locale: Option<&str>| {
+                    i18n::format(
+                        locale,
+                        Text::LogProlificRegistrant,
+                        &[
+                            ("user", &format!("<@{}>", user_id.get())),
+                            ("count", &license_count),
+                        ],
+                    )
+                };
+                let subject = user_id.get().to_string();
+                if let Err(e) = alert(
+                    context,
+                    db,
+                    guild_id,
+                    PROLIFIC_REGISTRANT_ALERT,
+                    subject,
+                    PROLIFIC_REGISTRANT_WINDOW,
+                    message,
+                )
+                .await
+                {
+                    warn!(
+                        "Error sending suspicious activity alert to {}: {:?}",
+                        guild_id.get(),
+                        e
+                    );
+                }
+            }
+        }
+        Err(e) => warn!("Error reading prolific registrants: {:?}", e),
+    }
+
+    let since = now_unix_ms.saturating_sub(SHARED_BUYER_WINDOW.as_millis() as u64);
+    match db.get_shared_buyers(since, SHARED_BUYER_LIMIT).await {
+        Ok(buyers) => {
+            for (guild_id, buyer_id, user_ids) in buyers {
+                let users = user_ids
+                    .iter()
+                    .map(|user_id| format!("<@{}>", user_id.get()))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let message = |locale: Option<&str>| {
+                    i18n::format(
+                        locale,
+                        Text::LogSharedBuyer,
+                        &[("count", &user_ids.len()), ("users", &users)],
+                    )
+                };
+                if let Err(e) = alert(
+                    context,
+                    db,
+                    guild_id,
+                    SHARED_BUYER_ALERT,
+                    buyer_id,
+                    SHARED_BUYER_WINDOW,
+                    message,
+                )
+                .await
+                {
+                    warn!(
+                        "Error sending suspicious activity alert to {}: {:?}",
+                        guild_id.get(),
+                        e
+                    );
+                }
+            }
+        }
+        Err(e) => warn!("Error reading shared buyers: {:?}", e),
+    }
+}
+
+/// Send an alert to a guild's bot log channel, unless it has none or was already alerted about this within the window.
+/// `message` renders the alert in the guild's locale.
+async fn alert(
+    context: &serenity::Context,
+    db: &JinxDb,
+    guild_id: GuildId,
+    kind: &'static str,
+    subject: String,
+    window: Duration,
+    message: impl FnOnce(Option<&str>) -> String,
+) -> Result<(), Error> {
+    let Some(log_channel) = db.get_log_channel(guild_id).await? else {
+        return Ok(());
+    };
+    // record the alert first, so a channel we can't send to doesn't get retried every time
+    if !db
+        .record_suspicious_activity_alert(guild_id, kind, subject, window.as_millis() as u64)
+        .await?
+    {
+        return Ok(());
+    }
+    let locale = i18n::guild_locale(db, guild_id, None).await?;
+    let embed = CreateEmbed::default()
+        .title(i18n::text(locale, Text::LogSuspiciousActivityTitle))
+        .description(message(locale))
+        .color(Colour::ORANGE);
+    send_bot_log_message(context, log_channel, CreateMessage::default().embed(embed)).await?;
+    Ok(())
+}
@@ -0,0 +1,209 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Registry of in-flight long-running interaction handlers, such as license registrations.
+//!
+//! Handlers call [`start`] before doing any work, and are turned away if their guild or the bot as a whole already has
+//! too many running. That keeps a slow Jinxxy API from piling up an unbounded number of waiting handlers. The returned
+//! [`Task`] stays in the registry until it's dropped, which is what `/running_tasks` shows and what
+//! [`crate::bot::drain`] waits on.
+
+use poise::serenity_prelude::{GuildId, UserId};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use tokio::sync::Notify;
+use tokio::time::{Duration, Instant};
+
+/// Most tasks that may run at once across all guilds
+pub const GLOBAL_LIMIT: usize = 200;
+
+static REGISTRY: LazyLock<Registry> = LazyLock::new(Default::default);
+
+/// The kinds of long-running handlers we keep track of
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TaskKind {
+    Registration,
+    GrantMissingRoles,
+}
+
+impl TaskKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            TaskKind::Registration => "registration",
+            TaskKind::GrantMissingRoles => "grant_missing_roles",
+        }
+    }
+
+    /// Most tasks of this kind that may run at once in a single guild
+    fn guild_limit(self) -> usize {
+        match self {
+            TaskKind::Registration => 20,
+            // each run already covers every user, so a second one at the same time would only duplicate work
+            TaskKind::GrantMissingRoles => 1,
+        }
+    }
+}
+
+/// Which limit turned a task away
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LimitReached {
+    Guild,
+    Global,
+}
+
+/// A snapshot of a running task
+#[derive(Clone, Debug)]
+pub struct TaskInfo {
+    pub kind: TaskKind,
+    pub guild_id: GuildId,
+    pub user_id: UserId,
+    pub started: Instant,
+}
+
+/// A running task. It's removed from the registry when this is dropped.
+pub struct Task {
+    registry: &'static Registry,
+    task_id: u64,
+}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        let mut tasks = self.registry.tasks.lock().unwrap();
+        tasks.remove(&self.task_id);
+        if tasks.is_empty() {
+            self.registry.idle.notify_waiters();
+        }
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    tasks: Mutex<HashMap<u64, TaskInfo, ahash::RandomState>>,
+    next_task_id: AtomicU64,
+    /// Notified whenever the last running task finishes
+    idle: Notify,
+}
+
+impl Registry {
+    fn start(
+        &'static self,
+        kind: TaskKind,
+        guild_id: GuildId,
+        user_id: UserId,
+    ) -> Result<Task, LimitReached> {
+        let mut tasks = self.tasks.lock().unwrap();
+        let guild_count = tasks
+            .values()
+            .filter(|task| task.kind == kind && task.guild_id == guild_id)
+            .count();
+        if guild_count >= kind.guild_limit() {
+            return Err(LimitReached::Guild);
+        }
+        if tasks.len() >= GLOBAL_LIMIT {
+            return Err(LimitReached::Global);
+        }
+        let task_id = self.next_task_id.fetch_add(1, Ordering::Relaxed);
+        tasks.insert(
+            task_id,
+            TaskInfo {
+                kind,
+                guild_id,
+                user_id,
+                started: Instant::now(),
+            },
+        );
+        Ok(Task {
+            registry: self,
+            task_id,
+        })
+    }
+
+    fn running(&self) -> Vec<TaskInfo> {
+        let mut tasks: Vec<TaskInfo> = self.tasks.lock().unwrap().values().cloned().collect();
+        tasks.sort_unstable_by_key(|task| task.started);
+        tasks
+    }
+
+    async fn wait_idle(&self, timeout: Duration) -> usize {
+        let idle = async {
+            loop {
+                // the future must exist before we check the count, or we could miss the last task finishing
+                let notified = self.idle.notified();
+                if self.tasks.lock().unwrap().is_empty() {
+                    return;
+                }
+                notified.await;
+            }
+        };
+        let _ = tokio::time::timeout(timeout, idle).await;
+        self.tasks.lock().unwrap().len()
+    }
+}
+
+/// Register a new task, unless its guild or the bot as a whole already has as many running as allowed
+pub fn start(kind: TaskKind, guild_id: GuildId, user_id: UserId) -> Result<Task, LimitReached> {
+    REGISTRY.start(kind, guild_id, user_id)
+}
+
+/// Get every running task, oldest first
+pub fn running() -> Vec<TaskInfo> {
+    REGISTRY.running()
+}
+
+/// Wait up to `timeout` for every running task to finish. Returns how many were still running when we stopped waiting.
+pub async fn wait_idle(timeout: Duration) -> usize {
+    REGISTRY.wait_idle(timeout).await
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn registry() -> &'static Registry {
+        Box::leak(Box::default())
+    }
+
+    #[test]
+    fn test_guild_limit() {
+        let registry = registry();
+        let guild_id = GuildId::new(1);
+        let user_id = UserId::new(2);
+        let task = registry
+            .start(TaskKind::GrantMissingRoles, guild_id, user_id)
+            .unwrap();
+        assert_eq!(
+            registry
+                .start(TaskKind::GrantMissingRoles, guild_id, user_id)
+                .err(),
+            Some(LimitReached::Guild)
+        );
+        assert!(registry
+            .start(TaskKind::GrantMissingRoles, GuildId::new(3), user_id)
+            .is_ok());
+        drop(task);
+        assert!(registry
+            .start(TaskKind::GrantMissingRoles, guild_id, user_id)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_wait_idle() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let registry = registry();
+            let task = registry
+                .start(TaskKind::Registration, GuildId::new(1), UserId::new(2))
+                .unwrap();
+            assert_eq!(registry.wait_idle(Duration::from_millis(10)).await, 1);
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                drop(task);
+            });
+            assert_eq!(registry.wait_idle(Duration::from_secs(10)).await, 0);
+        });
+    }
+}
@@ -0,0 +1,127 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Telling owners about new releases.
+//!
+//! A background task periodically checks GitHub for the latest release on the configured channel, and the first time it
+//! finds one newer than the running version it posts the release notes to every owner guild's bot log channel. Versions
+//! owners chose to ignore are never announced.
+
+use crate::bot::i18n;
+use crate::bot::i18n::Text;
+use crate::bot::util::send_bot_log_message;
+use crate::db::JinxDb;
+use crate::http::update_checker;
+use crate::http::update_checker::VersionCheck;
+use poise::serenity_prelude as serenity;
+use serenity::{Colour, CreateEmbed, CreateMessage};
+use tracing::{error, info, warn};
+
+/// Most characters of the release notes to include in a notification
+const CHANGELOG_SUMMARY_LENGTH: usize = 1000;
+
+/// Notify owners if there's a release they haven't been told about yet. Failures are logged and skipped.
+pub async fn notify_owners(context: &serenity::Context, db: &JinxDb) {
+    let preferences = match db.get_update_preferences().await {
+        Ok(preferences) => preferences,
+        Err(e) => {
+            error!("Error reading update preferences: {:?}", e);
+            return;
+        }
+    };
+    let VersionCheck::Outdated(remote_version) =
+        update_checker::check_for_update(&preferences).await
+    else {
+        return;
+    };
+    match db.get_notified_update_version().await {
+        Ok(Some(notified_version)) if notified_version == remote_version.version => return,
+        Ok(_) => {}
+        Err(e) => {
+            error!("Error reading notified update version: {:?}", e);
+            return;
+        }
+    }
+    let channels = match db.get_owner_log_channels().await {
+        Ok(channels) => channels,
+        Err(e) => {
+            error!("Error reading owner log channels: {:?}", e);
+            return;
+        }
+    };
+
+    let changelog = remote_version
+        .changelog
+        .as_deref()
+        .map(str::trim)
+        .filter(|changelog| !changelog.is_empty())
+        .map(changelog_summary);
+    for (guild_id, channel_id) in channels {
+        let locale = match i18n::guild_locale(db, guild_id, None).await {
+            Ok(locale) => locale,
+            Err(e) => {
+                warn!("Error reading language for {}: {:?}", guild_id.get(), e);
+                None
+            }
+        };
+        let mut description = i18n::format(
+            locale,
+            Text::LogUpdateAvailable,
+            &[
+                ("current", &env!("CARGO_PKG_VERSION")),
+                ("latest", &remote_version),
+            ],
+        );
+        // release notes come from GitHub, so they're left as written
+        if let Some(changelog) = &changelog {
+            description.push_str("\n\n");
+            description.push_str(changelog);
+        }
+        let embed = CreateEmbed::default()
+            .title(i18n::text(locale, Text::LogUpdateAvailableTitle))
+            .description(description)
+            .color(Colour::ORANGE);
+        let message = CreateMessage::default().embed(embed);
+        if let Err(e) = send_bot_log_message(context, channel_id, message).await {
+            warn!(
+                "Error sending update notification to {}: {:?}",
+                guild_id.get(),
+                e
+            );
+        }
+    }
+    info!("notified owners of update to {}", remote_version.version);
+    if let Err(e) = db.set_notified_update_version(remote_version.version).await {
+        error!("Error saving notified update version: {:?}", e);
+    }
+}
+
+/// Shorten release notes to whole lines that fit in [`CHANGELOG_SUMMARY_LENGTH`]
+fn changelog_summary(changelog: &str) -> String {
+    if changelog.chars().count() <= CHANGELOG_SUMMARY_LENGTH {
+        return changelog.to_string();
+    }
+    let mut summary = String::new();
+    for line in changelog.lines() {
+        if summary.chars().count() + line.chars().count() + 1 > CHANGELOG_SUMMARY_LENGTH {
+            break;
+        }
+        summary.push_str(line);
+        summary.push('\n');
+    }
+    summary.push('…');
+    summary
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_changelog_summary() {
+        assert_eq!(changelog_summary("- fixed a bug"), "- fixed a bug");
+        let line = "x".repeat(400);
+        let changelog = format!("{line}\n{line}\n{line}");
+        assert_eq!(changelog_summary(&changelog), format!("{line}\n{line}\n…"));
+    }
+}
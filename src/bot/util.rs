@@ -3,21 +3,80 @@
 
 //! Utils used by bot commands.
 
+use crate::bot::i18n;
+use crate::bot::i18n::Text;
 use crate::bot::{Context, CREATOR_COMMANDS, OWNER_COMMANDS};
-use crate::db::JinxDb;
+use crate::db::{CountRedaction, JinxDb};
 use crate::error::JinxError;
 use crate::http::jinxxy;
 use crate::license;
 use poise::{serenity_prelude as serenity, CreateReply};
+use secrecy::SecretString;
 use serenity::{
-    CacheHttp, ChannelId, Colour, CreateEmbed, GuildId, Http, Message, MessageFlags, MessageType,
-    MessageUpdateEvent, Role, RoleId,
+    ButtonStyle, CacheHttp, ChannelId, Colour, CreateActionRow, CreateAllowedMentions,
+    CreateButton, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse, CreateMessage,
+    GuildId, Http, Message, MessageFlags, MessageType, MessageUpdateEvent, Role, RoleId,
 };
 use std::collections::HashSet;
+use tokio::time::Duration;
 use tracing::{error, warn};
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 
+/// How many times to try registering a guild's commands before leaving it to the background sync
+const COMMAND_SYNC_ATTEMPTS: u32 = 3;
+
+/// How long to wait before the first command registration retry. This doubles after each retry.
+const COMMAND_SYNC_INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+
+// discord component ids for paginated replies
+const PAGE_PREVIOUS_ID: &str = "jinx_page_previous";
+const PAGE_NEXT_ID: &str = "jinx_page_next";
+
+/// Characters per page of a paginated reply. Embed descriptions can hold 4096, but every embed in a message also counts
+/// towards a shared 6000 character limit, so this leaves room for a warning embed alongside the page.
+const PAGE_LENGTH: usize = 3500;
+
+/// How long a paginated reply waits for the next button press before removing its buttons
+const PAGE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Find a product version matching `predicate`, which is passed the version ID and name. Versions are read from the
+/// `product_version` cache table, and the Jinxxy API is only hit (refreshing the cache) if no cached version matches.
+///
+/// Returns a (version ID, version name) pair.
+pub async fn find_product_version<F>(
+    db: &JinxDb,
+    api_key: &SecretString,
+    guild_id: GuildId,
+    product_id: &str,
+    predicate: F,
+) -> Result<Option<(String, String)>, Error>
+where
+    F: Fn(&str, &str) -> bool,
+{
+    let cached_versions = db
+        .get_product_versions(guild_id, product_id.to_string())
+        .await?;
+    if let Some(version) = cached_versions
+        .into_iter()
+        .find(|(version_id, version_name)| predicate(version_id, version_name))
+    {
+        return Ok(Some(version));
+    }
+
+    let versions: Vec<(String, String)> = jinxxy::get_product(api_key, product_id)
+        .await?
+        .versions
+        .into_iter()
+        .map(|version| (version.id, version.name))
+        .collect();
+    db.replace_product_versions(guild_id, product_id.to_string(), versions.clone())
+        .await?;
+    Ok(versions
+        .into_iter()
+        .find(|(version_id, version_name)| predicate(version_id, version_name)))
+}
+
 /// Check if the calling user is a bot owner
 pub(super) async fn check_owner(context: Context<'_>) -> Result<bool, Error> {
     Ok(context
@@ -30,6 +89,9 @@ pub(super) async fn check_owner(context: Context<'_>) -> Result<bool, Error> {
 /// Set (or reset) guild commands for this guild.
 ///
 /// There is a global rate limit of 200 application command creates per day, per guild.
+///
+/// Rate limits and server errors are retried with backoff. If they keep failing the guild is flagged for
+/// [`sync_pending_guild_commands`], so it ends up with the right commands eventually.
 pub async fn set_guild_commands(
     http: impl AsRef<Http>,
     db: &JinxDb,
@@ -54,13 +116,79 @@ pub async fn set_guild_commands(
         .flatten();
     let command_iter = owner_commands.chain(creator_commands);
     let commands = poise::builtins::create_application_commands(command_iter);
-    guild_id.set_commands(http, commands).await?;
-    Ok(())
+
+    let http = http.as_ref();
+    let mut backoff = COMMAND_SYNC_INITIAL_BACKOFF;
+    let mut attempt = 1;
+    let result = loop {
+        match guild_id.set_commands(http, commands.clone()).await {
+            Err(e) if is_retryable(&e) && attempt < COMMAND_SYNC_ATTEMPTS => {
+                warn!(
+                    "Error setting guild commands for guild {} on attempt {}, retrying in {}s: {:?}",
+                    guild_id.get(),
+                    attempt,
+                    backoff.as_secs(),
+                    e
+                );
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => break result,
+        }
+    };
+    match result {
+        Ok(_) => {
+            db.remove_pending_command_sync(guild_id).await?;
+            Ok(())
+        }
+        Err(e) => {
+            if is_retryable(&e) {
+                // leave it to the background sync to try again once the storm has passed
+                db.add_pending_command_sync(guild_id).await?;
+            } else {
+                // retrying won't fix this, such as if we're no longer in the guild
+                db.remove_pending_command_sync(guild_id).await?;
+            }
+            Err(e.into())
+        }
+    }
+}
+
+/// Check if a Discord request failed in a way that may succeed later, such as a rate limit or server error
+fn is_retryable(e: &serenity::Error) -> bool {
+    match e {
+        serenity::Error::Http(serenity::HttpError::UnsuccessfulRequest(response)) => {
+            response.status_code.as_u16() == 429 || response.status_code.is_server_error()
+        }
+        serenity::Error::Http(serenity::HttpError::Request(_)) => true,
+        _ => false,
+    }
+}
+
+/// Retry guild command registrations that failed earlier. Failures are logged and left for the next run.
+pub async fn sync_pending_guild_commands(http: &Http, db: &JinxDb) {
+    let guild_ids = match db.get_pending_command_syncs().await {
+        Ok(guild_ids) => guild_ids,
+        Err(e) => {
+            error!("Error reading pending guild command syncs: {:?}", e);
+            return;
+        }
+    };
+    for guild_id in guild_ids {
+        if let Err(e) = set_guild_commands(http, db, guild_id, None, None).await {
+            warn!(
+                "Error syncing guild commands for guild {}: {:?}",
+                guild_id.get(),
+                e
+            );
+        }
+    }
 }
 
 /// Get a license ID from whatever the heck the user provided. This can proxy IDs through, so it may
 /// not be suitable for untrusted applications where you don't want to allow users to pass IDs directly.
-pub async fn license_to_id(api_key: &str, license: &str) -> Result<Option<String>, Error> {
+pub async fn license_to_id(api_key: &SecretString, license: &str) -> Result<Option<String>, Error> {
     let license_type = license::identify_license(license);
     let license_id = if license_type.is_integer() {
         Some(license.to_string())
@@ -125,24 +253,29 @@ pub(super) async fn assignable_roles(
 
 /// warn if the roles cannot be assigned (too high, or we lack the perm)
 pub fn create_role_warning_from_roles<T: Iterator<Item = RoleId>>(
+    locale: Option<&str>,
     assignable_roles: &HashSet<RoleId, ahash::RandomState>,
     roles: T,
 ) -> Option<CreateEmbed> {
     let roles: HashSet<RoleId, ahash::RandomState> = roles.into_iter().collect();
     let mut unassignable_roles: Vec<RoleId> = roles.difference(assignable_roles).copied().collect();
-    create_role_warning(&mut unassignable_roles)
+    create_role_warning(locale, &mut unassignable_roles)
 }
 
 /// warn if the roles cannot be assigned (too high, or we lack the perm)
 pub fn create_role_warning_from_unassignable<T: Iterator<Item = RoleId>>(
+    locale: Option<&str>,
     unassignable_roles: T,
 ) -> Option<CreateEmbed> {
     let mut unassignable_roles: Vec<RoleId> = unassignable_roles.into_iter().collect();
-    create_role_warning(&mut unassignable_roles)
+    create_role_warning(locale, &mut unassignable_roles)
 }
 
 /// warn if the roles cannot be assigned (too high, or we lack the perm)
-fn create_role_warning(unassignable_roles: &mut Vec<RoleId>) -> Option<CreateEmbed> {
+fn create_role_warning(
+    locale: Option<&str>,
+    unassignable_roles: &mut Vec<RoleId>,
+) -> Option<CreateEmbed> {
     if unassignable_roles.is_empty() {
         None
     } else {
@@ -153,8 +286,12 @@ fn create_role_warning(unassignable_roles: &mut Vec<RoleId>) -> Option<CreateEmb
             warning_lines.push_str(format!("\n- <@&{}>", role).as_str());
         }
         let embed = CreateEmbed::default()
-            .title("Warning")
-            .description(format!("I don't currently have access to grant the following roles. Please check bot permissions.{}", warning_lines))
+            .title(i18n::text(locale, Text::RoleWarningTitle))
+            .description(i18n::format(
+                locale,
+                Text::RoleWarning,
+                &[("roles", &warning_lines)],
+            ))
             .color(Colour::ORANGE);
         Some(embed)
     }
@@ -166,7 +303,10 @@ pub fn success_reply(title: impl Into<String>, message: impl Into<String>) -> Cr
         .title(title)
         .description(message)
         .color(Colour::DARK_GREEN);
-    CreateReply::default().ephemeral(true).embed(embed)
+    CreateReply::default()
+        .ephemeral(true)
+        .embed(embed)
+        .allowed_mentions(CreateAllowedMentions::new())
 }
 
 /// Create a simple error reply
@@ -175,7 +315,303 @@ pub fn error_reply(title: impl Into<String>, message: impl Into<String>) -> Crea
         .title(title)
         .description(message)
         .color(Colour::RED);
-    CreateReply::default().ephemeral(true).embed(embed)
+    CreateReply::default()
+        .ephemeral(true)
+        .embed(embed)
+        .allowed_mentions(CreateAllowedMentions::new())
+}
+
+/// Send a message to a bot log channel. Mentions in log messages are only there to identify users and roles, so they
+/// never ping anyone, no matter what store-controlled text ends up in the message.
+pub async fn send_bot_log_message(
+    cache_http: impl CacheHttp,
+    log_channel: ChannelId,
+    message: CreateMessage,
+) -> serenity::Result<Message> {
+    let message = message.allowed_mentions(CreateAllowedMentions::new());
+    log_channel.send_message(cache_http, message).await
+}
+
+/// If registration is globally disabled for an incident, get the notice to show users in its place
+pub async fn incident_notice_embed(
+    db: &JinxDb,
+    locale: Option<&str>,
+) -> Result<Option<CreateEmbed>, Error> {
+    let embed = db.get_registration_incident_notice().await?.map(|notice| {
+        let description = if notice.is_empty() {
+            i18n::text(locale, Text::RegistrationIncident).to_string()
+        } else {
+            notice
+        };
+        CreateEmbed::default()
+            .title(i18n::text(locale, Text::RegistrationIncidentTitle))
+            .description(description)
+            .color(Colour::ORANGE)
+    });
+    Ok(embed)
+}
+
+/// Lower bounds of the ranges shown for [`CountRedaction::Range`]
+const REDACTION_RANGES: [u64; 13] = [
+    0, 10, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000,
+];
+
+/// Format a count for a public output. Returns `None` if the count should not be shown at all.
+pub fn redact_count(count: u64, redaction: CountRedaction) -> Option<String> {
+    match redaction {
+        CountRedaction::Exact => Some(count.to_string()),
+        CountRedaction::Rounded if count < 10 => Some("<10".to_string()),
+        CountRedaction::Rounded => {
+            let magnitude = 10u64.pow(count.ilog10() - 1);
+            let rounded = (count + magnitude / 2) / magnitude * magnitude;
+            Some(format!("~{rounded}"))
+        }
+        CountRedaction::Range => {
+            let index = REDACTION_RANGES.partition_point(|lower| *lower <= count) - 1;
+            match REDACTION_RANGES.get(index + 1) {
+                Some(upper) => Some(format!("{}–{}", REDACTION_RANGES[index], upper - 1)),
+                None => Some(format!("{}+", REDACTION_RANGES[index])),
+            }
+        }
+        CountRedaction::Hidden => None,
+    }
+}
+
+/// Bar heights used by [`sparkline`], lowest first
+const SPARKLINE_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Draw a tiny bar chart with one character per value. The largest value gets the tallest bar, and zero gets the lowest.
+pub fn sparkline(values: &[u64]) -> String {
+    let max = values.iter().copied().max().unwrap_or(0);
+    let top_level = SPARKLINE_LEVELS.len() as u64 - 1;
+    values
+        .iter()
+        .map(|value| {
+            if max == 0 {
+                SPARKLINE_LEVELS[0]
+            } else {
+                // round to the nearest level
+                SPARKLINE_LEVELS[((value * top_level + max / 2) / max) as usize]
+            }
+        })
+        .collect()
+}
+
+/// Describe which of the scopes Jinx uses an API key has. Missing required scopes turn this into a warning, as they'd
+/// otherwise only show up as confusing failures on the first license registration.
+pub fn api_key_scopes_embed(locale: Option<&str>, auth_user: &jinxxy::AuthUser) -> CreateEmbed {
+    let lines: Vec<String> = jinxxy::SCOPES
+        .iter()
+        .map(|scope| {
+            let (icon, note) = match (auth_user.has_scope(scope.name), scope.required) {
+                (true, _) => ("✅", ""),
+                (false, true) => ("❌", i18n::text(locale, Text::ApiKeyScopeRequired)),
+                (false, false) => ("➖", i18n::text(locale, Text::ApiKeyScopeOptional)),
+            };
+            let purpose = match scope.name {
+                "products_read" => i18n::text(locale, Text::ApiKeyScopeProductsRead),
+                "licenses_read" => i18n::text(locale, Text::ApiKeyScopeLicensesRead),
+                "licenses_write" => i18n::text(locale, Text::ApiKeyScopeLicensesWrite),
+                "orders_read" => i18n::text(locale, Text::ApiKeyScopeOrdersRead),
+                _ => scope.purpose,
+            };
+            format!("{icon} `{}`: {purpose}{note}", scope.name)
+        })
+        .collect();
+    let embed = CreateEmbed::default().field(
+        i18n::text(locale, Text::ApiKeyScopesField),
+        field_value(&lines),
+        false,
+    );
+    if auth_user.has_required_scopes() {
+        embed
+            .title(i18n::text(locale, Text::ApiKeyScopesTitle))
+            .color(Colour::BLUE)
+    } else {
+        embed
+            .title(i18n::text(locale, Text::ApiKeyPermissionWarningTitle))
+            .color(Colour::ORANGE)
+            .description(i18n::text(locale, Text::ApiKeyPermissionWarning))
+    }
+}
+
+/// Join lines into an embed field value, dropping any that don't fit in Discord's 1024 character limit
+pub fn field_value(lines: &[String]) -> String {
+    const MORE: &str = "\n…and more";
+    let mut value = String::new();
+    for line in lines {
+        if value.chars().count() + line.chars().count() + 1 + MORE.chars().count() > 1024 {
+            value.push_str(MORE);
+            break;
+        }
+        if !value.is_empty() {
+            value.push('\n');
+        }
+        value.push_str(line);
+    }
+    value
+}
+
+/// Split lines into pages of at most `max_length` characters, without splitting any line across pages. A line too long
+/// to fit on a page by itself is truncated.
+fn paginate(lines: &[String], max_length: usize) -> Vec<String> {
+    let mut pages = Vec::new();
+    let mut page = String::new();
+    for line in lines {
+        let line = if line.chars().count() > max_length {
+            let mut truncated: String = line.chars().take(max_length - 1).collect();
+            truncated.push('…');
+            truncated
+        } else {
+            line.clone()
+        };
+        if !page.is_empty() && page.chars().count() + 1 + line.chars().count() > max_length {
+            pages.push(std::mem::take(&mut page));
+        }
+        if !page.is_empty() {
+            page.push('\n');
+        }
+        page.push_str(&line);
+    }
+    if !page.is_empty() || pages.is_empty() {
+        pages.push(page);
+    }
+    pages
+}
+
+/// Send lines as an ephemeral embed, split into pages with Previous and Next buttons if they don't all fit in one
+/// description. `embed` supplies everything but the description, and `extra_embeds` are shown below every page.
+///
+/// The current page is tracked by the command that sent the reply, so the buttons are removed once nobody has pressed
+/// them for a while.
+pub(super) async fn send_paginated(
+    context: Context<'_>,
+    embed: CreateEmbed,
+    lines: &[String],
+    extra_embeds: Vec<CreateEmbed>,
+) -> Result<(), Error> {
+    let pages = paginate(lines, PAGE_LENGTH);
+    let page_reply = |page: usize, controls: bool| {
+        let mut embed = embed.clone().description(pages[page].clone());
+        if pages.len() > 1 {
+            embed = embed.footer(CreateEmbedFooter::new(format!(
+                "Page {} of {}",
+                page + 1,
+                pages.len()
+            )));
+        }
+        let components = if controls && pages.len() > 1 {
+            vec![CreateActionRow::Buttons(vec![
+                CreateButton::new(PAGE_PREVIOUS_ID)
+                    .label("Previous")
+                    .style(ButtonStyle::Secondary)
+                    .disabled(page == 0),
+                CreateButton::new(PAGE_NEXT_ID)
+                    .label("Next")
+                    .style(ButtonStyle::Secondary)
+                    .disabled(page + 1 >= pages.len()),
+            ])]
+        } else {
+            vec![]
+        };
+        let mut reply = CreateReply::default()
+            .embed(embed)
+            .components(components)
+            .ephemeral(true);
+        for extra_embed in &extra_embeds {
+            reply = reply.embed(extra_embed.clone());
+        }
+        reply
+    };
+
+    let mut page = 0;
+    let reply = context.send(page_reply(page, true)).await?;
+    if pages.len() == 1 {
+        return Ok(());
+    }
+    let message = reply.message().await?;
+    loop {
+        let interaction = message
+            .await_component_interaction(context.serenity_context())
+            .author_id(context.author().id)
+            .timeout(PAGE_TIMEOUT)
+            .await;
+        let Some(interaction) = interaction else {
+            reply.edit(context, page_reply(page, false)).await?;
+            return Ok(());
+        };
+        interaction
+            .create_response(context, CreateInteractionResponse::Acknowledge)
+            .await?;
+        match interaction.data.custom_id.as_str() {
+            PAGE_PREVIOUS_ID => page = page.saturating_sub(1),
+            PAGE_NEXT_ID => page = (page + 1).min(pages.len() - 1),
+            _ => continue,
+        }
+        reply.edit(context, page_reply(page, true)).await?;
+    }
+}
+
+/// Parse a hex RGB color like `#5865F2`. The `#` is optional.
+pub fn parse_color(color: &str) -> Option<Colour> {
+    let hex = color.trim().trim_start_matches('#');
+    // from_str_radix would also accept a sign
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok().map(Colour::new)
+}
+
+/// Create a masked link. The text is escaped, and if the URL isn't a plain https URL that can't break out of the
+/// link syntax, only the text is shown.
+pub fn masked_link(text: &str, url: &str) -> String {
+    let url_is_safe = url.starts_with("https://")
+        && !url.chars().any(|c| {
+            c.is_whitespace() || c.is_control() || matches!(c, '<' | '>' | '(' | ')' | '[' | ']')
+        });
+    if url_is_safe {
+        format!("[{}](<{}>)", text.safe_display(), url)
+    } else {
+        warn!("refusing to create masked link to unsafe URL {:?}", url);
+        text.safe_display().to_string()
+    }
+}
+
+/// Displays an untrusted string, such as a product or user name from Jinxxy, with Discord markdown and mentions
+/// escaped. Anything not controlled by us or the person running the command should go through this before being
+/// interpolated into a message or embed.
+pub struct SafeDisplay<'a>(&'a str);
+
+impl std::fmt::Display for SafeDisplay<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        use std::fmt::Write as _;
+        for c in self.0.chars() {
+            match c {
+                '\\' | '*' | '_' | '~' | '`' | '|' | '>' | '#' | '-' | '[' | ']' | '(' | ')'
+                | '<' | ':' => {
+                    f.write_char('\\')?;
+                    f.write_char(c)?;
+                }
+                // a zero-width space after the @ defuses @everyone and @here
+                '@' => f.write_str("@\u{200B}")?,
+                // untrusted strings are always shown inline, so don't let them start new lines (and therefore lists or headings)
+                '\r' | '\n' => f.write_char(' ')?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+pub trait SafeDisplayExt {
+    /// Display this string with Discord markdown and mentions escaped. See [`SafeDisplay`].
+    fn safe_display(&self) -> SafeDisplay<'_>;
+}
+
+impl<T: AsRef<str> + ?Sized> SafeDisplayExt for T {
+    fn safe_display(&self) -> SafeDisplay<'_> {
+        SafeDisplay(self.as_ref())
+    }
 }
 
 pub trait MessageExtensions {
@@ -304,3 +740,97 @@ impl GetMessageFlags for MessageUpdateEvent {
         self.flags.flatten()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_safe_display() {
+        assert_eq!("Plain Hat".safe_display().to_string(), "Plain Hat");
+        assert_eq!(
+            "**Bold** [link](https://example.com)"
+                .safe_display()
+                .to_string(),
+            "\\*\\*Bold\\*\\* \\[link\\]\\(https\\://example.com\\)"
+        );
+        assert_eq!(
+            "@everyone <@123>".safe_display().to_string(),
+            "@\u{200B}everyone \\<@\u{200B}123\\>"
+        );
+        assert_eq!(
+            masked_link("Jinx", "https://jinxxy.com/Jinx"),
+            "[Jinx](<https://jinxxy.com/Jinx>)"
+        );
+        assert_eq!(
+            masked_link("Jinx", "https://evil.example/>)[x](<https://jinxxy.com"),
+            "Jinx"
+        );
+        assert_eq!(masked_link("Jinx", "javascript:alert(1)"), "Jinx");
+        assert_eq!(
+            "line\n# heading".safe_display().to_string(),
+            "line \\# heading"
+        );
+    }
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(parse_color("#5865F2"), Some(Colour::new(0x5865F2)));
+        assert_eq!(parse_color("00ff00"), Some(Colour::new(0x00FF00)));
+        assert_eq!(parse_color("#fff"), None);
+        assert_eq!(parse_color("#+12345"), None);
+        assert_eq!(parse_color("blue"), None);
+    }
+
+    #[test]
+    fn test_paginate() {
+        let lines: Vec<String> = ["aaaa", "bbbb", "cccc", "dddddddddddd"]
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        assert_eq!(paginate(&lines, 9), vec!["aaaa\nbbbb", "cccc", "dddddddd…"]);
+        assert_eq!(paginate(&lines[..1], 9), vec!["aaaa"]);
+        assert_eq!(paginate(&[], 9), vec![""]);
+    }
+
+    #[test]
+    fn test_sparkline() {
+        assert_eq!(sparkline(&[]), "");
+        assert_eq!(sparkline(&[0, 0]), "▁▁");
+        assert_eq!(sparkline(&[0, 1, 2, 3, 4, 5, 6, 7]), "▁▂▃▄▅▆▇█");
+        assert_eq!(sparkline(&[1, 1000]), "▁█");
+    }
+
+    #[test]
+    fn test_redact_count() {
+        assert_eq!(
+            redact_count(1234, CountRedaction::Exact).as_deref(),
+            Some("1234")
+        );
+        assert_eq!(
+            redact_count(7, CountRedaction::Rounded).as_deref(),
+            Some("<10")
+        );
+        assert_eq!(
+            redact_count(1234, CountRedaction::Rounded).as_deref(),
+            Some("~1200")
+        );
+        assert_eq!(
+            redact_count(95, CountRedaction::Rounded).as_deref(),
+            Some("~95")
+        );
+        assert_eq!(
+            redact_count(0, CountRedaction::Range).as_deref(),
+            Some("0–9")
+        );
+        assert_eq!(
+            redact_count(250, CountRedaction::Range).as_deref(),
+            Some("250–499")
+        );
+        assert_eq!(
+            redact_count(200_000, CountRedaction::Range).as_deref(),
+            Some("100000+")
+        );
+        assert_eq!(redact_count(1234, CountRedaction::Hidden), None);
+    }
+}
@@ -0,0 +1,117 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Preflight checks that the bot can actually do what a guild has set it up to do.
+//!
+//! Role grants fail if the bot lacks Manage Roles or a linked role sits above its own highest role, and bot logs fail if
+//! it can't post in the log channel. Without these checks such problems only come to light when a customer registers.
+
+use crate::bot::i18n;
+use crate::bot::i18n::Text;
+use crate::bot::Error;
+use crate::db::JinxDb;
+use crate::error::JinxError;
+use poise::serenity_prelude as serenity;
+use serenity::{GuildId, RoleId};
+
+/// Check a guild's setup, returning a description of each problem found along with how to fix it
+pub async fn verify(
+    locale: Option<&str>,
+    context: &serenity::Context,
+    db: &JinxDb,
+    guild_id: GuildId,
+) -> Result<Vec<String>, Error> {
+    let bot_id = context.cache.current_user().id;
+    let bot_member = guild_id.member(context, bot_id).await?;
+    let mut linked_roles: Vec<RoleId> = db
+        .get_links(guild_id)
+        .await?
+        .into_iter()
+        .map(|(_, role)| role)
+        .collect();
+    linked_roles.extend(db.get_blanket_role(guild_id).await?);
+    linked_roles.sort_unstable();
+    linked_roles.dedup();
+    let log_channel = db.get_log_channel(guild_id).await?;
+
+    let guild = guild_id
+        .to_guild_cached(context)
+        .ok_or_else(|| JinxError::new("expected guild to be cached"))?;
+    let mut problems = Vec::new();
+
+    // see `util::assignable_roles` for why guild-wide permissions are exactly what we want here
+    #[allow(deprecated)]
+    let permissions = guild.member_permissions(&bot_member);
+    if !permissions.manage_roles() {
+        problems.push(i18n::text(locale, Text::SetupProblemManageRoles).to_string());
+    }
+
+    let highest_position = guild
+        .member_highest_role(&bot_member)
+        .map(|role| role.position)
+        .unwrap_or(0);
+    for role_id in linked_roles {
+        let problem = match guild.roles.get(&role_id) {
+            None => Some((
+                Text::SetupProblemRoleDeleted,
+                format!("`{}`", role_id.get()),
+            )),
+            Some(role) if role.managed => Some((
+                Text::SetupProblemRoleManaged,
+                format!("<@&{}>", role_id.get()),
+            )),
+            Some(role) if role.position >= highest_position => Some((
+                Text::SetupProblemRoleTooHigh,
+                format!("<@&{}>", role_id.get()),
+            )),
+            Some(_) => None,
+        };
+        if let Some((text, role)) = problem {
+            problems.push(i18n::format(locale, text, &[("role", &role)]));
+        }
+    }
+
+    if let Some(log_channel) = log_channel {
+        if let Some(channel) = guild.channels.get(&log_channel) {
+            let permissions = guild.user_permissions_in(channel, &bot_member);
+            let mut missing = Vec::new();
+            if !permissions.view_channel() {
+                missing.push("View Channel");
+            }
+            if !permissions.send_messages() {
+                missing.push("Send Messages");
+            }
+            if !permissions.embed_links() {
+                missing.push("Embed Links");
+            }
+            // permission names are left in English, matching how Discord's API and most guides refer to them
+            if !missing.is_empty() {
+                problems.push(i18n::format(
+                    locale,
+                    Text::SetupProblemLogPermissions,
+                    &[
+                        ("permissions", &missing.join("**, **")),
+                        ("channel", &format!("<#{}>", log_channel.get())),
+                    ],
+                ));
+            }
+        } else if !guild.threads.iter().any(|thread| thread.id == log_channel) {
+            problems.push(i18n::format(
+                locale,
+                Text::SetupProblemLogChannelMissing,
+                &[("channel", &format!("<#{}>", log_channel.get()))],
+            ));
+        }
+    }
+
+    Ok(problems)
+}
+
+/// Describe a list of problems found by [`verify`]
+pub fn problems_message(locale: Option<&str>, problems: &[String]) -> String {
+    let mut message = i18n::text(locale, Text::SetupProblems).to_string();
+    for problem in problems {
+        message.push_str(format!("\n- {problem}").as_str());
+    }
+    message
+}
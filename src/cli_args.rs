@@ -2,13 +2,23 @@
 // jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
 
 use crate::constants::CLAP_VERSION;
-use clap::{Args, Parser, Subcommand};
+use clap::{ArgAction, Args, Parser, Subcommand};
+use std::path::PathBuf;
 
 /// Discord bot that handles Jinxxy license registration.
 /// If ran with no subcommands the bot will start.
+/// Owner maintenance subcommands are sent to the running bot if `admin_socket` is configured, and otherwise run directly
+/// against the DB.
 #[derive(Parser)]
 #[command(version = CLAP_VERSION, long_about, author)]
 pub struct JinxArgs {
+    /// Path to an optional TOML config file with operator-level settings, such as the log filter and DB path.
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// Path of the sqlite database. May also be set with the `JINX_DB_PATH` environment variable. Defaults to
+    /// `jinx.sqlite` in the working directory.
+    #[arg(long, global = true)]
+    pub db_path: Option<PathBuf>,
     #[command(subcommand)]
     pub command: Option<Command>,
 }
@@ -23,8 +33,31 @@ pub enum Command {
     },
     /// Check GitHub for updates
     UpdateCheck,
+    /// Encrypt any Jinxxy API keys and Discord token still stored in plaintext, using the key in the `JINX_SECRET_KEY`
+    /// environment variable, and exit.
+    EncryptSecrets,
     /// Modify bot owners
     Owner(OwnerArgs),
+    /// Write a copy of the DB to a new file. Safe to run while the bot is running.
+    Backup {
+        /// Path of the backup file to create
+        path: PathBuf,
+    },
+    /// Print DB stats
+    Stats,
+    /// Globally disable (or re-enable) license registration, such as during a Jinxxy outage
+    IncidentMode {
+        /// `true` to disable registration in every server, `false` to enable it again
+        #[arg(action = ArgAction::Set)]
+        enabled: bool,
+    },
+    /// Print activation and link events as JSON lines, oldest first. At most 1000 are printed per run, so to follow the
+    /// stream run again with the `sequence` of the last event printed.
+    Events {
+        /// Only print events with a sequence number after this one
+        #[arg(default_value_t = 0)]
+        since: u64,
+    },
 }
 
 #[derive(Args)]
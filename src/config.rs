@@ -0,0 +1,205 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Operator-level settings, optionally loaded from a config file passed with `--config`.
+//!
+//! The config file is TOML. Every setting is optional, and unknown keys are an error so typos don't silently fall back
+//! to defaults. Settings that server owners or bot owners change at
+//! runtime are stored in the DB instead; this file is for things that must be known before the DB is even opened, or
+//! that only the person running the process should control.
+//!
+//! ```toml
+//! log_filter = "info,jinx=debug"
+//! db_path = "/var/lib/jinx/jinx.sqlite"
+//! http_timeout_seconds = 10
+//! http_connect_timeout_seconds = 10
+//! cache_expiry_seconds = 60
+//! guild_retention_days = 30
+//! admin_socket = "/run/jinx/admin.sock"
+//! expensive_command_cooldown_seconds = 10
+//! cheap_command_cooldown_seconds = 2
+//! ```
+
+use crate::error::JinxError;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+const DEFAULT_LOG_FILTER: &str = "info,jinx=debug,serenity::gateway::shard=error";
+const DEFAULT_DB_PATH: &str = "jinx.sqlite";
+
+/// Environment variable that overrides the config file's `db_path`
+pub const DB_PATH_ENV_VAR: &str = "JINX_DB_PATH";
+const DEFAULT_HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_HTTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_CACHE_EXPIRY: Duration = Duration::from_secs(60);
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+const DEFAULT_GUILD_RETENTION: Duration = Duration::from_secs(30 * SECONDS_PER_DAY);
+const DEFAULT_EXPENSIVE_COMMAND_COOLDOWN: Duration = Duration::from_secs(10);
+const DEFAULT_CHEAP_COMMAND_COOLDOWN: Duration = Duration::from_secs(2);
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Log filter, in `tracing_subscriber::EnvFilter` syntax
+    pub log_filter: String,
+    /// Path of the sqlite DB
+    pub db_path: PathBuf,
+    /// Total timeout for a single HTTP request
+    pub http_timeout: Duration,
+    /// Timeout for establishing an HTTP connection
+    pub http_connect_timeout: Duration,
+    /// How long a guild's cached product list is used before it is refreshed from Jinxxy
+    pub cache_expiry: Duration,
+    /// How long a guild's data is kept after the bot is removed from it, in case the bot is re-added
+    pub guild_retention: Duration,
+    /// Unix socket the running bot serves admin requests on, if any. See [`crate::admin`].
+    pub admin_socket: Option<PathBuf>,
+    /// Per-guild cooldown for creator commands that make many Jinxxy API calls or do a lot of Discord work
+    pub expensive_command_cooldown: Duration,
+    /// Per-guild cooldown for creator commands that are cheap, but could still be spammed
+    pub cheap_command_cooldown: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            log_filter: DEFAULT_LOG_FILTER.to_string(),
+            db_path: PathBuf::from(DEFAULT_DB_PATH),
+            http_timeout: DEFAULT_HTTP_TIMEOUT,
+            http_connect_timeout: DEFAULT_HTTP_CONNECT_TIMEOUT,
+            cache_expiry: DEFAULT_CACHE_EXPIRY,
+            guild_retention: DEFAULT_GUILD_RETENTION,
+            admin_socket: None,
+            expensive_command_cooldown: DEFAULT_EXPENSIVE_COMMAND_COOLDOWN,
+            cheap_command_cooldown: DEFAULT_CHEAP_COMMAND_COOLDOWN,
+        }
+    }
+}
+
+/// The config file as written. Durations are in the units their keys say.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ConfigFile {
+    log_filter: Option<String>,
+    db_path: Option<PathBuf>,
+    http_timeout_seconds: Option<u64>,
+    http_connect_timeout_seconds: Option<u64>,
+    cache_expiry_seconds: Option<u64>,
+    guild_retention_days: Option<u64>,
+    admin_socket: Option<PathBuf>,
+    expensive_command_cooldown_seconds: Option<u64>,
+    cheap_command_cooldown_seconds: Option<u64>,
+}
+
+impl From<ConfigFile> for Config {
+    fn from(file: ConfigFile) -> Self {
+        let defaults = Config::default();
+        Self {
+            log_filter: file.log_filter.unwrap_or(defaults.log_filter),
+            db_path: file.db_path.unwrap_or(defaults.db_path),
+            http_timeout: file
+                .http_timeout_seconds
+                .map_or(defaults.http_timeout, Duration::from_secs),
+            http_connect_timeout: file
+                .http_connect_timeout_seconds
+                .map_or(defaults.http_connect_timeout, Duration::from_secs),
+            cache_expiry: file
+                .cache_expiry_seconds
+                .map_or(defaults.cache_expiry, Duration::from_secs),
+            guild_retention: file
+                .guild_retention_days
+                .map_or(defaults.guild_retention, |days| {
+                    Duration::from_secs(days.saturating_mul(SECONDS_PER_DAY))
+                }),
+            admin_socket: file.admin_socket.or(defaults.admin_socket),
+            expensive_command_cooldown: file
+                .expensive_command_cooldown_seconds
+                .map_or(defaults.expensive_command_cooldown, Duration::from_secs),
+            cheap_command_cooldown: file
+                .cheap_command_cooldown_seconds
+                .map_or(defaults.cheap_command_cooldown, Duration::from_secs),
+        }
+    }
+}
+
+/// Load the config file, if any, and make it the global config. Must be called at most once, before anything calls
+/// [`get`].
+///
+/// The DB path is taken from `db_path` if given, then the [`DB_PATH_ENV_VAR`] environment variable, then the config
+/// file.
+pub fn init(path: Option<&Path>, db_path: Option<PathBuf>) -> Result<(), JinxError> {
+    let mut config = match path {
+        Some(path) => {
+            let contents = std::fs::read_to_string(path)
+                .map_err(|e| JinxError::new(format!("failed to read {}: {}", path.display(), e)))?;
+            parse(&contents)
+                .map_err(|e| JinxError::new(format!("invalid config {}: {}", path.display(), e)))?
+        }
+        None => Config::default(),
+    };
+    if let Some(db_path) = db_path.or_else(|| std::env::var_os(DB_PATH_ENV_VAR).map(PathBuf::from))
+    {
+        config.db_path = db_path;
+    }
+    CONFIG
+        .set(config)
+        .map_err(|_| JinxError::new("config was already initialized"))
+}
+
+/// Get the global config. If [`init`] was never called, this is the default config.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// Parse a config file
+fn parse(contents: &str) -> Result<Config, toml::de::Error> {
+    toml::from_str::<ConfigFile>(contents).map(Config::from)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = parse(
+            "# comment\n\
+            log_filter = \"info,jinx=trace\" # trailing comment\n\
+            \n\
+            db_path = 'C:\\data\\jinx.sqlite'\n\
+            http_timeout_seconds = 30\n\
+            cache_expiry_seconds = 1_800\n\
+            guild_retention_days = 7\n\
+            admin_socket = \"/run/jinx/admin.sock\"\n\
+            expensive_command_cooldown_seconds = 30\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            Config {
+                log_filter: "info,jinx=trace".to_string(),
+                db_path: PathBuf::from("C:\\data\\jinx.sqlite"),
+                http_timeout: Duration::from_secs(30),
+                cache_expiry: Duration::from_secs(1800),
+                guild_retention: Duration::from_secs(7 * SECONDS_PER_DAY),
+                admin_socket: Some(PathBuf::from("/run/jinx/admin.sock")),
+                expensive_command_cooldown: Duration::from_secs(30),
+                ..Default::default()
+            }
+        );
+        assert_eq!(parse("").unwrap(), Config::default());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse("log_filter").is_err());
+        assert!(parse("log_filer = \"info\"").is_err());
+        assert!(parse("log_filter = 3").is_err());
+        assert!(parse("http_timeout_seconds = \"10\"").is_err());
+        assert!(parse("http_timeout_seconds = -1").is_err());
+        assert!(parse("db_path = \"unterminated").is_err());
+    }
+}
@@ -1,614 +1,5684 @@
 // This file is part of jinx. Copyright © 2024 jinx contributors.
 // jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
 
+use crate::config;
+use crate::error::JinxError;
+use crate::http::jinxxy::{ApiSample, PartialProduct, ProductDetails};
+use crate::http::update_checker;
+use crate::http::update_checker::UpdatePreferences;
+use crate::license::LOCKING_USER_ID;
+use crate::secret;
 use dashmap::DashMap;
-use poise::serenity_prelude::{ChannelId, GuildId, RoleId};
-use std::path::Path;
-use tokio::time::Instant;
+use poise::serenity_prelude::{ChannelId, GuildId, MessageId, RoleId, UserId};
+use rand::Rng as _;
+use secrecy::{ExposeSecret as _, SecretString};
+use semver::Version;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+use tokio::time::{Duration, Instant};
+use tokio_rusqlite::types::{FromSql, ToSql};
 use tokio_rusqlite::{named_params, Connection, OptionalExtension, Result};
-use tracing::debug;
+use tracing::{debug, warn};
 
 const SCHEMA_VERSION_KEY: &str = "schema_version";
-const SCHEMA_VERSION_VALUE: i32 = 4;
+const SCHEMA_VERSION_VALUE: i32 = 47;
 const DISCORD_TOKEN_KEY: &str = "discord_token";
+const SLOW_QUERY_THRESHOLD_KEY: &str = "slow_query_threshold_ms";
+const CACHE_WARM_SCHEDULE_KEY: &str = "cache_warm_schedule";
+const BUSY_TIMEOUT_KEY: &str = "busy_timeout_ms";
+const STATEMENT_CACHE_CAPACITY_KEY: &str = "statement_cache_capacity";
+const PRESENCE_MESSAGES_KEY: &str = "presence_messages";
+const PRESENCE_ROTATION_INTERVAL_KEY: &str = "presence_rotation_interval_s";
+const REGISTRATION_INCIDENT_NOTICE_KEY: &str = "registration_incident_notice";
+const NOTIFIED_UPDATE_VERSION_KEY: &str = "notified_update_version";
+const RELEASE_CHANNEL_KEY: &str = "release_channel";
+const IGNORED_UPDATE_VERSIONS_KEY: &str = "ignored_update_versions";
+
+/// How long each presence message is shown before rotating to the next, unless overridden
+const DEFAULT_PRESENCE_ROTATION_INTERVAL_S: u64 = 60;
+
+/// rusqlite's default busy timeout
+const DEFAULT_BUSY_TIMEOUT_MS: u64 = 5000;
+/// Longest busy timeout that can be set. Waiting longer than this on a lock would just stall commands, and SQLite can't
+/// take a timeout above `i32::MAX` ms at all.
+pub const MAX_BUSY_TIMEOUT_MS: u64 = 60_000;
+/// rusqlite's default prepared statement cache capacity
+const DEFAULT_STATEMENT_CACHE_CAPACITY: u64 = 16;
+
+/// Checks if a guild counts towards global statistics. Matches the `LEFT JOIN guild ... WHERE guild.test = 0 AND guild.stats_opt_out = 0` used by the global count queries.
+const PRODUCTION_GUILD_QUERY: &str =
+    "SELECT EXISTS(SELECT * FROM guild WHERE guild_id = :guild AND test = 0 AND stats_opt_out = 0)";
+
+/// Counts a user's activations in production guilds, used to detect when a user enters or leaves the distinct user count
+const PRODUCTION_USER_ACTIVATION_COUNT_QUERY: &str = "SELECT count(*) FROM license_activation LEFT JOIN guild USING (guild_id) WHERE license_activation.user_id = :user AND guild.test = 0 AND guild.stats_opt_out = 0";
+
+/// Counts guilds that count towards global statistics
+const GUILD_COUNT_QUERY: &str =
+    "SELECT count(*) FROM guild WHERE test = 0 AND stats_opt_out = 0 AND deleted_unix_ms IS NULL";
+
+/// Records that a guild completed an onboarding step. A step keeps the time it was first completed.
+const COMPLETE_ONBOARDING_STEP_QUERY: &str = "INSERT OR IGNORE INTO onboarding_step (guild_id, step, completed_unix_ms) VALUES (:guild, :step, :timestamp)";
+
+/// Failed license registrations are counted over this window
+pub const REGISTRATION_FAILURE_WINDOW_MS: u64 = 10 * 60 * 1000;
+
+/// Failed license registrations a user may make within the window before they're blocked
+pub const USER_REGISTRATION_FAILURE_LIMIT: u64 = 5;
+
+/// Failed license registrations a guild may receive within the window before registration is blocked for everyone
+pub const GUILD_REGISTRATION_FAILURE_LIMIT: u64 = 50;
+
+/// How long a user is blocked from registering after too many failures
+const USER_REGISTRATION_BLOCK_MS: u64 = 30 * 60 * 1000;
+
+/// How long registration is blocked for everyone in a guild after too many failures
+const GUILD_REGISTRATION_BLOCK_MS: u64 = 10 * 60 * 1000;
+
+/// Stand-in user ID for a registration block covering the whole guild. No Discord user has this ID.
+const GUILD_WIDE_BLOCK_USER_ID: u64 = 0;
+
+/// Append to the event stream. See [`JinxDb::get_events`].
+const INSERT_EVENT_QUERY: &str = "INSERT INTO event (created_unix_ms, guild_id, kind, user_id, license_id, product_id, role_id) VALUES (:timestamp, :guild, :kind, :user, :license, :product, :role)";
+
+/// Milliseconds in a UTC day
+const MS_PER_DAY: u64 = 24 * 60 * 60 * 1000;
+
+/// A user's next registration attempt only counts as a follow-up to a message variant if it happens within this long
+const MESSAGE_FOLLOWUP_WINDOW_MS: u64 = 60 * 60 * 1000;
+
+/// How many distinct users may activate a single license, unless overridden for the product
+const DEFAULT_MAX_ACTIVATIONS: u32 = 1;
+
+/// Queries taking longer than this are logged, unless overridden in the settings table
+const DEFAULT_SLOW_QUERY_THRESHOLD_MS: u64 = 100;
 
 pub struct JinxDb {
     connection: Connection,
-    api_key_cache: DashMap<GuildId, Option<String>, ahash::RandomState>,
+    api_key_cache: DashMap<GuildId, Option<SecretString>, ahash::RandomState>,
+    query_stats: DashMap<&'static str, QueryStats, ahash::RandomState>,
+    slow_query_threshold_ms: AtomicU64,
+    busy_timeout_ms: AtomicU64,
+    statement_cache_capacity: AtomicU64,
+    /// Materialized production license activation count, so stats don't need to scan the whole table
+    license_activation_count: AtomicU64,
+    /// Materialized production product→role link count, so stats don't need to scan the whole table
+    product_role_count: AtomicU64,
+    /// Materialized count of distinct users with a production license activation
+    distinct_user_count: AtomicU64,
+    /// Notified whenever the distinct user count changes
+    distinct_user_count_changed: Notify,
+    /// Notified whenever the presence messages or rotation interval change
+    presence_config_changed: Notify,
+    /// Identifies this process's own cache invalidations, so it doesn't act on them
+    instance_id: u32,
+    /// Highest cache invalidation ID seen so far
+    invalidation_cursor: AtomicU64,
+}
+
+/// Aggregate timing information for a single named query
+#[derive(Clone, Default)]
+pub struct QueryStats {
+    pub count: u64,
+    pub total: Duration,
+    pub max: Duration,
+}
+
+impl QueryStats {
+    pub fn average(&self) -> Duration {
+        if self.count == 0 {
+            Duration::ZERO
+        } else {
+            self.total / self.count as u32
+        }
+    }
+}
+
+/// Context used to encrypt the Discord token. See [`secret::encrypt`].
+const DISCORD_TOKEN_SECRET_CONTEXT: &str = "discord_token";
+
+/// Every table holding per-guild data, which all has to go when a guild is purged
+const GUILD_TABLES: [&str; 33] = [
+    "guild",
+    "product_role",
+    "license_activation",
+    "product_version_exclusion",
+    "product_seat_limit",
+    "activation_hook",
+    "product_link_rule",
+    "product_activation_count",
+    "product_activation_log",
+    "celebrated_milestone",
+    "audit_log",
+    "product",
+    "product_version",
+    "guild_message_variant",
+    "message_variant_pending",
+    "announcement_delivery",
+    "cache_refresh_pending",
+    "cache_invalidation",
+    "sales_feed_order",
+    "onboarding_step",
+    "advisory_delivery",
+    "command_sync_pending",
+    "event",
+    "registration_failure",
+    "registration_block",
+    "license_registration",
+    "suspicious_activity_alert",
+    "event_webhook",
+    "post_template",
+    "registration_post",
+    "license_expiry",
+    "notification_digest_entry",
+    "scheduled_expiry",
+];
+
+/// A temporary block on license registration after too many failed attempts
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegistrationBlock {
+    /// `true` if registration is blocked for everyone in the guild, rather than for one user
+    pub guild_wide: bool,
+    pub until_unix_ms: u64,
+}
+
+/// The kinds of change recorded in the event stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    Activation,
+    Deactivation,
+    Link,
+    Unlink,
+}
+
+impl EventKind {
+    const ALL: [EventKind; 4] = [
+        EventKind::Activation,
+        EventKind::Deactivation,
+        EventKind::Link,
+        EventKind::Unlink,
+    ];
+
+    /// Stable name persisted to the DB and shown to event stream consumers. Do not change these!
+    pub fn as_db_str(self) -> &'static str {
+        match self {
+            EventKind::Activation => "activation",
+            EventKind::Deactivation => "deactivation",
+            EventKind::Link => "link",
+            EventKind::Unlink => "unlink",
+        }
+    }
+
+    fn from_db_str(kind: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|known| known.as_db_str() == kind)
+    }
+}
+
+/// A guild's event webhook, along with its delivery progress through the event stream
+#[derive(Debug)]
+pub struct EventWebhook {
+    pub guild_id: GuildId,
+    pub url: String,
+    pub signing_secret: SecretString,
+    /// Sequence number of the last event delivered (or given up on)
+    pub cursor: u64,
+    /// Failed attempts to deliver the event after the cursor
+    pub failure_count: u32,
+}
+
+/// A registered subscription license, which will lose its roles once it expires unless it's renewed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpiringLicense {
+    pub guild_id: GuildId,
+    pub license_id: String,
+    pub product_id: String,
+    pub product_version_id: Option<String>,
+    pub expires_unix_ms: u64,
+}
+
+/// Roles granted by a user's license activation that a "schedule expiry" activation hook has set to be removed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScheduledExpiry {
+    pub guild_id: GuildId,
+    pub user_id: UserId,
+    pub license_id: String,
+    pub product_id: String,
+    pub product_version_id: Option<String>,
+    pub expires_unix_ms: u64,
+}
+
+/// Customizations for a guild's registration posts. Anything left unset uses the default.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PostTemplate {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub button_label: Option<String>,
+    /// Embed color as an RGB value
+    pub color: Option<u32>,
+    /// Product whose art is shown on the post
+    pub product_id: Option<String>,
+}
+
+/// An entry in the append-only event stream. Activation events have a user and license, and link events have a product
+/// and role.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    /// Increases with every event and is never reused, so consumers can resume from the last one they saw
+    pub sequence: u64,
+    pub created_unix_ms: u64,
+    pub guild_id: GuildId,
+    pub kind: EventKind,
+    pub user_id: Option<u64>,
+    pub license_id: Option<String>,
+    pub product_id: Option<String>,
+    pub role_id: Option<RoleId>,
+}
+
+/// Context used to encrypt a guild's Jinxxy API key. See [`secret::encrypt`].
+fn api_key_secret_context(guild: GuildId) -> String {
+    format!("jinxxy_api_key {}", guild.get())
+}
+
+/// Context used to encrypt a guild's event webhook signing secret. See [`secret::encrypt`].
+fn event_webhook_secret_context(guild: GuildId) -> String {
+    format!("event_webhook_secret {}", guild.get())
+}
+
+fn activation_hook_secret_context(guild: GuildId, product_id: &str) -> String {
+    format!("activation_hook_secret {} {}", guild.get(), product_id)
+}
+
+fn secret_error(error: JinxError) -> tokio_rusqlite::Error {
+    tokio_rusqlite::Error::Other(Box::new(error))
+}
+
+/// Read an event selected as `sequence, created_unix_ms, guild_id, kind, user_id, license_id, product_id, role_id`.
+/// Events of kinds written by a newer version of the bot are skipped.
+fn event_from_row(row: &tokio_rusqlite::Row) -> Result<Option<Event>> {
+    let kind: String = row.get(3)?;
+    let Some(kind) = EventKind::from_db_str(&kind) else {
+        return Ok(None);
+    };
+    let role_id: Option<u64> = row.get(7)?;
+    Ok(Some(Event {
+        sequence: row.get(0)?,
+        created_unix_ms: row.get(1)?,
+        guild_id: GuildId::new(row.get(2)?),
+        kind,
+        user_id: row.get(4)?,
+        license_id: row.get(5)?,
+        product_id: row.get(6)?,
+        role_id: role_id.map(RoleId::new),
+    }))
+}
+
+/// Escape `%`, `_`, and `\` so a string can be used literally in a `LIKE ... ESCAPE '\'` pattern
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Jinxxy API latency percentiles and error counts for a single endpoint
+pub struct EndpointHealth {
+    pub endpoint: String,
+    pub request_count: u64,
+    pub error_count: u64,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
+impl EndpointHealth {
+    /// Build from a list of latencies, which must already be sorted ascending
+    fn from_sorted_latencies(endpoint: String, latencies_ms: &[u64], error_count: u64) -> Self {
+        Self {
+            endpoint,
+            request_count: latencies_ms.len() as u64,
+            error_count,
+            p50: percentile(latencies_ms, 50),
+            p95: percentile(latencies_ms, 95),
+            p99: percentile(latencies_ms, 99),
+        }
+    }
+
+    pub fn error_rate(&self) -> f64 {
+        if self.request_count == 0 {
+            0.0
+        } else {
+            self.error_count as f64 / self.request_count as f64
+        }
+    }
+}
+
+/// Aggregates for a single UTC day, kept long after the raw samples they're built from are pruned
+pub struct DailyMetrics {
+    /// Formatted as `YYYY-MM-DD`
+    pub day: String,
+    pub activation_count: u64,
+    pub api_request_count: u64,
+    pub api_error_count: u64,
+    pub api_latency_p95: Duration,
+    /// Configured guilds when the day was recorded
+    pub guild_count: u64,
+}
+
+/// How a guild's persisted products changed when they were replaced
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ProductDiff {
+    /// `false` if no products were persisted before, in which case every product counts as added
+    pub had_products: bool,
+    /// `(product_id, product_name)` of products that weren't persisted before
+    pub added: Vec<(String, String)>,
+    /// `(product_id, product_name)` of products that are no longer in the store
+    pub removed: Vec<(String, String)>,
+    /// `(product_id, old_name, new_name)` of products whose name changed
+    pub renamed: Vec<(String, String, String)>,
+}
+
+impl ProductDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.renamed.is_empty()
+    }
+}
+
+/// Everything stored on a guild's row, for owners debugging a guild's setup. The API key itself is left out.
+#[derive(Debug)]
+pub struct GuildConfig {
+    pub api_key_set: bool,
+    pub jinxxy_user_id: Option<String>,
+    pub log_channel: Option<ChannelId>,
+    pub blanket_role: Option<RoleId>,
+    pub milestone_channel: Option<ChannelId>,
+    pub sales_feed_channel: Option<ChannelId>,
+    pub language: Option<Language>,
+    pub public_count_redaction: Option<CountRedaction>,
+    pub notification_digest: Option<NotificationDigest>,
+    pub test: bool,
+    pub owner: bool,
+    pub stats_opt_out: bool,
+    pub restore_roles: bool,
+    pub log_member_leave: bool,
+    pub log_product_changes: bool,
+    pub registrations_paused: bool,
+    pub deleted_unix_ms: Option<u64>,
+}
+
+/// Nearest-rank percentile of a sorted list of latencies
+fn percentile(sorted_latencies_ms: &[u64], percentile: u64) -> Duration {
+    if sorted_latencies_ms.is_empty() {
+        Duration::ZERO
+    } else {
+        let rank = (sorted_latencies_ms.len() as u64 * percentile)
+            .div_ceil(100)
+            .max(1);
+        Duration::from_millis(sorted_latencies_ms[rank as usize - 1])
+    }
+}
+
+/// Kinds of events recorded in the audit log
+#[derive(Clone, Copy, Debug, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum AuditAction {
+    #[name = "role granted"]
+    RoleGrant,
+    #[name = "role revoked"]
+    RoleRevoke,
+    #[name = "product linked"]
+    Link,
+    #[name = "product unlinked"]
+    Unlink,
+    #[name = "version excluded"]
+    ExcludeVersion,
+    #[name = "version included"]
+    IncludeVersion,
+    #[name = "seats changed"]
+    SetSeats,
+    #[name = "API key changed"]
+    SetApiKey,
+    #[name = "license locked"]
+    Lock,
+    #[name = "license unlocked"]
+    Unlock,
+    #[name = "license deactivated"]
+    Deactivate,
+    #[name = "license transferred"]
+    Transfer,
+    #[name = "blanket role changed"]
+    SetBlanketRole,
+    #[name = "store paused"]
+    PauseStore,
+    #[name = "store resumed"]
+    ResumeStore,
+}
+
+impl AuditAction {
+    /// Stable name persisted to the DB. Do not change these!
+    fn as_db_str(self) -> &'static str {
+        match self {
+            AuditAction::RoleGrant => "role_grant",
+            AuditAction::RoleRevoke => "role_revoke",
+            AuditAction::Link => "link",
+            AuditAction::Unlink => "unlink",
+            AuditAction::ExcludeVersion => "exclude_version",
+            AuditAction::IncludeVersion => "include_version",
+            AuditAction::SetSeats => "set_seats",
+            AuditAction::SetApiKey => "set_api_key",
+            AuditAction::Lock => "lock",
+            AuditAction::Unlock => "unlock",
+            AuditAction::Deactivate => "deactivate",
+            AuditAction::Transfer => "transfer",
+            AuditAction::SetBlanketRole => "set_blanket_role",
+            AuditAction::PauseStore => "pause_store",
+            AuditAction::ResumeStore => "resume_store",
+        }
+    }
+
+    fn from_db_str(action: &str) -> Option<Self> {
+        let action = match action {
+            "role_grant" => AuditAction::RoleGrant,
+            "role_revoke" => AuditAction::RoleRevoke,
+            "link" => AuditAction::Link,
+            "unlink" => AuditAction::Unlink,
+            "exclude_version" => AuditAction::ExcludeVersion,
+            "include_version" => AuditAction::IncludeVersion,
+            "set_seats" => AuditAction::SetSeats,
+            "set_api_key" => AuditAction::SetApiKey,
+            "lock" => AuditAction::Lock,
+            "unlock" => AuditAction::Unlock,
+            "deactivate" => AuditAction::Deactivate,
+            "transfer" => AuditAction::Transfer,
+            "set_blanket_role" => AuditAction::SetBlanketRole,
+            "pause_store" => AuditAction::PauseStore,
+            "resume_store" => AuditAction::ResumeStore,
+            _ => return None,
+        };
+        Some(action)
+    }
+}
+
+/// Steps of setting up Jinx in a guild, tracked for `/setup_progress`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OnboardingStep {
+    StoreLinked,
+    LogChannelSet,
+    FirstLink,
+    FirstPost,
+    FirstActivation,
+}
+
+impl OnboardingStep {
+    /// Every step, in the order they're normally completed
+    pub const ALL: [OnboardingStep; 5] = [
+        OnboardingStep::StoreLinked,
+        OnboardingStep::LogChannelSet,
+        OnboardingStep::FirstLink,
+        OnboardingStep::FirstPost,
+        OnboardingStep::FirstActivation,
+    ];
+
+    /// Stable name persisted to the DB. Do not change these!
+    fn as_db_str(self) -> &'static str {
+        match self {
+            OnboardingStep::StoreLinked => "store_linked",
+            OnboardingStep::LogChannelSet => "log_channel_set",
+            OnboardingStep::FirstLink => "first_link",
+            OnboardingStep::FirstPost => "first_post",
+            OnboardingStep::FirstActivation => "first_activation",
+        }
+    }
+
+    fn from_db_str(step: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|known| known.as_db_str() == step)
+    }
+
+    /// What to do to complete this step
+    pub fn hint(self) -> &'static str {
+        match self {
+            OnboardingStep::StoreLinked => "Run `/setup`, or `/init` with your Jinxxy API key.",
+            OnboardingStep::LogChannelSet => {
+                "Use `/set_log_channel` to pick a channel for bot logs."
+            }
+            OnboardingStep::FirstLink => {
+                "Use `/link_product` to link a product to the role it should grant."
+            }
+            OnboardingStep::FirstPost => {
+                "Use `/create_post` in the channel customers should register from."
+            }
+            OnboardingStep::FirstActivation => {
+                "This completes once a customer registers a license."
+            }
+        }
+    }
+}
+
+/// Advice the bot sends a guild at most once, when the guild's data shows it's in a particular state. Each advisory is
+/// defined by a query selecting the guilds it applies to, so adding one only takes a new variant here and its message
+/// in [`crate::bot::advisories`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Advisory {
+    /// The store was linked a while ago, but no products were ever linked to roles
+    StoreWithoutLinks,
+    /// Products are linked, but no registration post was ever created
+    LinksWithoutPost,
+    /// Licenses are being registered, but there's no log channel to report role grants and problems to
+    ActivationsWithoutLogChannel,
+}
+
+impl Advisory {
+    pub const ALL: [Advisory; 3] = [
+        Advisory::StoreWithoutLinks,
+        Advisory::LinksWithoutPost,
+        Advisory::ActivationsWithoutLogChannel,
+    ];
+
+    /// Stable name persisted to the DB. Do not change these!
+    fn as_db_str(self) -> &'static str {
+        match self {
+            Advisory::StoreWithoutLinks => "store_without_links",
+            Advisory::LinksWithoutPost => "links_without_post",
+            Advisory::ActivationsWithoutLogChannel => "activations_without_log_channel",
+        }
+    }
+
+    /// Query selecting `guild_id, log_channel_id` for each guild this advisory applies to and hasn't been sent to.
+    /// Setup advisories only apply once the store was linked before `:settled_before`, which gives admins a chance to
+    /// finish on their own, and stop applying once a license is registered, since the setup evidently works.
+    fn candidate_query(self) -> &'static str {
+        match self {
+            Advisory::StoreWithoutLinks => "SELECT guild_id, log_channel_id FROM guild WHERE deleted_unix_ms IS NULL \
+                AND NOT EXISTS (SELECT * FROM advisory_delivery WHERE advisory_delivery.guild_id = guild.guild_id AND advisory = :advisory) \
+                AND EXISTS (SELECT * FROM onboarding_step WHERE onboarding_step.guild_id = guild.guild_id AND step = 'store_linked' AND completed_unix_ms < :settled_before) \
+                AND NOT EXISTS (SELECT * FROM onboarding_step WHERE onboarding_step.guild_id = guild.guild_id AND step IN ('first_link', 'first_activation'))",
+            Advisory::LinksWithoutPost => "SELECT guild_id, log_channel_id FROM guild WHERE deleted_unix_ms IS NULL \
+                AND NOT EXISTS (SELECT * FROM advisory_delivery WHERE advisory_delivery.guild_id = guild.guild_id AND advisory = :advisory) \
+                AND EXISTS (SELECT * FROM onboarding_step WHERE onboarding_step.guild_id = guild.guild_id AND step = 'first_link' AND completed_unix_ms < :settled_before) \
+                AND NOT EXISTS (SELECT * FROM onboarding_step WHERE onboarding_step.guild_id = guild.guild_id AND step IN ('first_post', 'first_activation'))",
+            Advisory::ActivationsWithoutLogChannel => "SELECT guild_id, log_channel_id FROM guild WHERE deleted_unix_ms IS NULL AND log_channel_id IS NULL \
+                AND NOT EXISTS (SELECT * FROM advisory_delivery WHERE advisory_delivery.guild_id = guild.guild_id AND advisory = :advisory) \
+                AND EXISTS (SELECT * FROM onboarding_step WHERE onboarding_step.guild_id = guild.guild_id AND step = 'first_activation' AND completed_unix_ms < :settled_before)",
+        }
+    }
+}
+
+/// User-facing messages that owners can run phrasing experiments on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum MessageKey {
+    #[name = "registration failure"]
+    RegistrationFailure,
+}
+
+impl MessageKey {
+    /// Every message key. Each one gets a control variant, which shows the built-in text.
+    const ALL: [MessageKey; 1] = [MessageKey::RegistrationFailure];
+
+    /// Stable name persisted to the DB. Do not change these!
+    fn as_db_str(self) -> &'static str {
+        match self {
+            MessageKey::RegistrationFailure => "registration_failure",
+        }
+    }
+}
+
+/// Destructive owner commands that can be configured to require confirmation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum ConfirmableCommand {
+    #[name = "exit"]
+    Exit,
+    #[name = "restart"]
+    Restart,
+    #[name = "clear_cache"]
+    ClearCache,
+}
+
+impl ConfirmableCommand {
+    /// Settings key the command's confirmation mode is stored under. Do not change these!
+    fn setting_key(self) -> &'static str {
+        match self {
+            ConfirmableCommand::Exit => "confirm_exit",
+            ConfirmableCommand::Restart => "confirm_restart",
+            ConfirmableCommand::ClearCache => "confirm_clear_cache",
+        }
+    }
+}
+
+/// How a destructive owner command must be confirmed before it runs
+#[derive(Clone, Copy, Debug, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum ConfirmationMode {
+    /// Run immediately
+    #[name = "none"]
+    None,
+    /// The owner who ran the command must press a button
+    #[name = "button"]
+    Button,
+    /// A different owner must approve the command
+    #[name = "second owner"]
+    SecondOwner,
+}
+
+impl ConfirmationMode {
+    /// Stable name persisted to the DB. Do not change these!
+    fn as_db_str(self) -> &'static str {
+        match self {
+            ConfirmationMode::None => "none",
+            ConfirmationMode::Button => "button",
+            ConfirmationMode::SecondOwner => "second_owner",
+        }
+    }
+
+    fn from_db_str(mode: &str) -> Option<Self> {
+        let mode = match mode {
+            "none" => ConfirmationMode::None,
+            "button" => ConfirmationMode::Button,
+            "second_owner" => ConfirmationMode::SecondOwner,
+            _ => return None,
+        };
+        Some(mode)
+    }
+}
+
+/// Which GitHub releases update checks consider
+#[derive(Clone, Copy, Debug, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum ReleaseChannel {
+    /// Full releases only
+    #[name = "stable"]
+    Stable,
+    /// Full releases and pre-releases, such as release candidates
+    #[name = "pre-release"]
+    PreRelease,
+}
+
+impl ReleaseChannel {
+    /// Stable name persisted to the DB. Do not change these!
+    fn as_db_str(self) -> &'static str {
+        match self {
+            ReleaseChannel::Stable => "stable",
+            ReleaseChannel::PreRelease => "pre_release",
+        }
+    }
+
+    fn from_db_str(channel: &str) -> Option<Self> {
+        let channel = match channel {
+            "stable" => ReleaseChannel::Stable,
+            "pre_release" => ReleaseChannel::PreRelease,
+            _ => return None,
+        };
+        Some(channel)
+    }
+}
+
+/// Kinds of actions that can run after a license is activated
+#[derive(Clone, Copy, Debug, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum ActivationHookKind {
+    /// POST a JSON description of the activation to a URL
+    #[name = "webhook"]
+    Webhook,
+    /// Add the user to a thread
+    #[name = "add to thread"]
+    AddToThread,
+    /// Give the user permission to view a channel
+    #[name = "grant channel access"]
+    GrantChannelAccess,
+    /// Remove the roles the activation granted after a number of days
+    #[name = "schedule expiry"]
+    ScheduleExpiry,
+}
+
+impl ActivationHookKind {
+    /// Stable name persisted to the DB. Do not change these!
+    fn as_db_str(self) -> &'static str {
+        match self {
+            ActivationHookKind::Webhook => "webhook",
+            ActivationHookKind::AddToThread => "add_to_thread",
+            ActivationHookKind::GrantChannelAccess => "grant_channel_access",
+            ActivationHookKind::ScheduleExpiry => "schedule_expiry",
+        }
+    }
+
+    fn from_db_str(kind: &str) -> Option<Self> {
+        let kind = match kind {
+            "webhook" => ActivationHookKind::Webhook,
+            "add_to_thread" => ActivationHookKind::AddToThread,
+            "grant_channel_access" => ActivationHookKind::GrantChannelAccess,
+            "schedule_expiry" => ActivationHookKind::ScheduleExpiry,
+            _ => return None,
+        };
+        Some(kind)
+    }
+}
+
+/// Which guilds' bot log channels an announcement is sent to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum AnnouncementAudience {
+    #[default]
+    #[name = "all servers"]
+    All,
+    #[name = "production servers"]
+    Production,
+    #[name = "test servers"]
+    Test,
+}
+
+impl AnnouncementAudience {
+    /// Stable name persisted to the DB. Do not change these!
+    fn as_db_str(self) -> &'static str {
+        match self {
+            AnnouncementAudience::All => "all",
+            AnnouncementAudience::Production => "production",
+            AnnouncementAudience::Test => "test",
+        }
+    }
+
+    fn from_db_str(audience: &str) -> Option<Self> {
+        let audience = match audience {
+            "all" => AnnouncementAudience::All,
+            "production" => AnnouncementAudience::Production,
+            "test" => AnnouncementAudience::Test,
+            _ => return None,
+        };
+        Some(audience)
+    }
+}
+
+/// How counts are shown in outputs that anyone in a guild can see. Activation counts can reveal a store's sales, so
+/// creators may not want them public. Ephemeral outputs only creators can see always show exact counts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum CountRedaction {
+    /// Show exact counts
+    #[default]
+    #[name = "exact"]
+    Exact,
+    /// Round to two significant figures
+    #[name = "rounded"]
+    Rounded,
+    /// Show a range the count falls in
+    #[name = "range"]
+    Range,
+    /// Don't show counts at all
+    #[name = "hidden"]
+    Hidden,
+}
+
+impl CountRedaction {
+    /// Stable name persisted to the DB. Do not change these!
+    fn as_db_str(self) -> &'static str {
+        match self {
+            CountRedaction::Exact => "exact",
+            CountRedaction::Rounded => "rounded",
+            CountRedaction::Range => "range",
+            CountRedaction::Hidden => "hidden",
+        }
+    }
+
+    fn from_db_str(redaction: &str) -> Option<Self> {
+        let redaction = match redaction {
+            "exact" => CountRedaction::Exact,
+            "rounded" => CountRedaction::Rounded,
+            "range" => CountRedaction::Range,
+            "hidden" => CountRedaction::Hidden,
+            _ => return None,
+        };
+        Some(redaction)
+    }
+}
+
+/// How a guild's bot log is told about license activations
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum NotificationDigest {
+    /// Post each activation as it happens
+    #[default]
+    #[name = "off"]
+    Off,
+    /// Post a summary of activations every hour
+    #[name = "hourly"]
+    Hourly,
+    /// Post a summary of activations every day
+    #[name = "daily"]
+    Daily,
+}
+
+impl NotificationDigest {
+    /// Stable name persisted to the DB. Do not change these!
+    fn as_db_str(self) -> &'static str {
+        match self {
+            NotificationDigest::Off => "off",
+            NotificationDigest::Hourly => "hourly",
+            NotificationDigest::Daily => "daily",
+        }
+    }
+
+    fn from_db_str(digest: &str) -> Option<Self> {
+        let digest = match digest {
+            "off" => NotificationDigest::Off,
+            "hourly" => NotificationDigest::Hourly,
+            "daily" => NotificationDigest::Daily,
+            _ => return None,
+        };
+        Some(digest)
+    }
+}
+
+/// An activation waiting to be included in a guild's next notification digest
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DigestEntry {
+    pub created_unix_ms: u64,
+    pub user_id: UserId,
+    pub product_name: String,
+    /// Roles granted by the activation
+    pub roles: Vec<RoleId>,
+}
+
+/// Language a guild has chosen for the bot's translated messages, instead of each user's own Discord language
+#[derive(Clone, Copy, Debug, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Language {
+    #[name = "English"]
+    English,
+    #[name = "Español"]
+    Spanish,
+}
+
+impl Language {
+    #[cfg(test)]
+    pub const ALL: [Language; 2] = [Language::English, Language::Spanish];
+
+    /// Locale of this language's message catalog. This is also the stable name persisted to the DB, so do not change
+    /// these!
+    pub fn locale(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::Spanish => "es",
+        }
+    }
+
+    fn from_db_str(language: &str) -> Option<Self> {
+        let language = match language {
+            "en" => Language::English,
+            "es" => Language::Spanish,
+            _ => return None,
+        };
+        Some(language)
+    }
+}
+
+/// An action to run after a license for a product is activated. Hooks for a product run in `position` order.
+#[derive(Clone, Debug)]
+pub struct ActivationHook {
+    pub position: u64,
+    pub kind: ActivationHookKind,
+    /// What the hook acts on: a URL for webhooks, a number of days for scheduled expiries, or a channel ID otherwise
+    pub target: String,
+    /// Secret webhook payloads are signed with. Only set for webhooks.
+    pub signing_secret: Option<SecretString>,
+}
+
+/// The phrasing of a message assigned to a guild
+pub struct MessageVariant {
+    pub variant_id: u64,
+    /// `None` for the control variant, which uses the built-in text
+    pub text: Option<String>,
+}
+
+/// An owner announcement, along with how its delivery went
+pub struct Announcement {
+    pub announcement_id: u64,
+    pub title: Option<String>,
+    pub message: String,
+    pub audience: AnnouncementAudience,
+    pub send_at_unix_ms: u64,
+    /// `None` until delivery starts
+    pub sent_unix_ms: Option<u64>,
+    pub delivered_count: u64,
+    pub failed_count: u64,
+}
+
+/// Selects the columns read by [`announcement_from_row`]. Needs a `GROUP BY announcement_id`.
+const ANNOUNCEMENT_QUERY: &str = "SELECT announcement_id, title, message, audience, send_at_unix_ms, sent_unix_ms, \
+    count(announcement_delivery.guild_id) - count(announcement_delivery.error), count(announcement_delivery.error) \
+    FROM announcement LEFT JOIN announcement_delivery USING (announcement_id)";
+
+fn announcement_from_row(row: &tokio_rusqlite::Row) -> Result<Announcement> {
+    let audience: String = row.get(3)?;
+    Ok(Announcement {
+        announcement_id: row.get(0)?,
+        title: row.get(1)?,
+        message: row.get(2)?,
+        // an audience we don't recognize is narrowed to test servers rather than risk over-broadcasting
+        audience: AnnouncementAudience::from_db_str(&audience)
+            .unwrap_or(AnnouncementAudience::Test),
+        send_at_unix_ms: row.get(4)?,
+        sent_unix_ms: row.get(5)?,
+        delivered_count: row.get(6)?,
+        failed_count: row.get(7)?,
+    })
+}
+
+/// Outcome counts for a single message variant
+pub struct MessageVariantStats {
+    pub variant_id: u64,
+    /// `None` for the control variant, which uses the built-in text
+    pub text: Option<String>,
+    pub active: bool,
+    pub shown_count: u64,
+    /// Number of times the user's next attempt succeeded
+    pub followup_success_count: u64,
+    /// Number of times the user's next attempt failed again
+    pub followup_failure_count: u64,
+}
+
+impl MessageVariantStats {
+    /// Fraction of showings followed by a successful attempt. Showings with no follow-up count against this.
+    pub fn success_rate(&self) -> f64 {
+        if self.shown_count == 0 {
+            0.0
+        } else {
+            self.followup_success_count as f64 / self.shown_count as f64
+        }
+    }
+}
+
+/// A single audit log record. Build one with [`AuditLogEntry::new`] and the builder methods.
+#[derive(Clone, Debug)]
+pub struct AuditLogEntry {
+    pub timestamp_unix_ms: u64,
+    /// User who performed the action, if any
+    pub actor: Option<UserId>,
+    pub action: AuditAction,
+    /// User the action was performed on, if any
+    pub user: Option<UserId>,
+    pub product_id: Option<String>,
+    pub role: Option<RoleId>,
+    pub license_id: Option<String>,
+    pub detail: Option<String>,
+}
+
+impl AuditLogEntry {
+    /// Create a new entry timestamped with the current time
+    pub fn new(action: AuditAction) -> Self {
+        let timestamp_unix_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        Self {
+            timestamp_unix_ms,
+            actor: None,
+            action,
+            user: None,
+            product_id: None,
+            role: None,
+            license_id: None,
+            detail: None,
+        }
+    }
+
+    pub fn actor(mut self, actor: UserId) -> Self {
+        self.actor = Some(actor);
+        self
+    }
+
+    pub fn user(mut self, user: UserId) -> Self {
+        self.user = Some(user);
+        self
+    }
+
+    pub fn product(mut self, product_id: impl Into<String>) -> Self {
+        self.product_id = Some(product_id.into());
+        self
+    }
+
+    pub fn role(mut self, role: RoleId) -> Self {
+        self.role = Some(role);
+        self
+    }
+
+    pub fn license(mut self, license_id: impl Into<String>) -> Self {
+        self.license_id = Some(license_id.into());
+        self
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+/// Filters for querying the audit log. `None` fields match everything.
+#[derive(Default)]
+pub struct AuditLogFilter {
+    /// Matches entries where this user is either the actor or the subject
+    pub user: Option<UserId>,
+    pub product_id: Option<String>,
+    pub action: Option<AuditAction>,
+    /// Jinxxy license ID
+    pub license_id: Option<String>,
+    /// Only entries at or after this time
+    pub since_unix_ms: Option<u64>,
+}
+
+/// Tunable parameters for the DB connection. Jinx uses a single connection, so there is no pool size to tune.
+#[derive(Clone, Copy)]
+pub struct ConnectionTuning {
+    /// How long to wait on a locked DB before giving up
+    pub busy_timeout: Duration,
+    /// How many prepared statements to keep cached
+    pub statement_cache_capacity: usize,
+}
+
+/// Decrement a counter, saturating at zero in case it has drifted
+fn decrement(counter: &AtomicU64) {
+    decrement_by(counter, 1);
+}
+
+/// Subtract from a counter, saturating at zero in case it has drifted
+fn decrement_by(counter: &AtomicU64, amount: u64) {
+    let _ = counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |count| {
+        Some(count.saturating_sub(amount))
+    });
 }
 
 impl Drop for JinxDb {
     fn drop(&mut self) {
         debug!("Closing sqlite db…");
     }
-}
+}
+
+/// Add a column to a table during a migration, unless the table already has it. Tables created after the column was
+/// added to their `CREATE TABLE` already have it, so the migration must not add it again.
+fn add_column_if_missing(
+    transaction: &tokio_rusqlite::Transaction,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<()> {
+    let exists: bool = transaction.query_row(
+        "SELECT EXISTS(SELECT * FROM pragma_table_info(:table) WHERE name = :column)",
+        named_params! {":table": table, ":column": column},
+        |row| row.get(0),
+    )?;
+    if !exists {
+        transaction.execute(
+            format!("ALTER TABLE \"{table}\" ADD COLUMN {column} {definition}").as_str(),
+            (),
+        )?;
+    }
+    Ok(())
+}
+
+impl JinxDb {
+    /// Open a new database
+    pub async fn open() -> Result<Self> {
+        Self::open_path(&config::get().db_path).await
+    }
+
+    /// Open a new database
+    pub(crate) async fn open_path<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let connection = Connection::open(path).await?;
+        JinxDb::init(&connection).await?;
+        let db = JinxDb {
+            connection,
+            api_key_cache: Default::default(),
+            query_stats: Default::default(),
+            slow_query_threshold_ms: AtomicU64::new(DEFAULT_SLOW_QUERY_THRESHOLD_MS),
+            busy_timeout_ms: AtomicU64::new(DEFAULT_BUSY_TIMEOUT_MS),
+            statement_cache_capacity: AtomicU64::new(DEFAULT_STATEMENT_CACHE_CAPACITY),
+            license_activation_count: AtomicU64::new(0),
+            product_role_count: AtomicU64::new(0),
+            distinct_user_count: AtomicU64::new(0),
+            distinct_user_count_changed: Notify::new(),
+            presence_config_changed: Notify::new(),
+            instance_id: rand::thread_rng().gen(),
+            invalidation_cursor: AtomicU64::new(0),
+        };
+        db.reconcile_counters().await?;
+        // invalidations from before we started don't matter, as our caches start out empty
+        let invalidation_cursor = db.latest_cache_invalidation().await?;
+        db.invalidation_cursor
+            .store(invalidation_cursor, Ordering::Relaxed);
+        if let Some(threshold) = db.get_setting(SLOW_QUERY_THRESHOLD_KEY).await? {
+            db.slow_query_threshold_ms
+                .store(threshold, Ordering::Relaxed);
+        }
+        if let Some(busy_timeout_ms) = db.get_setting::<u64>(BUSY_TIMEOUT_KEY).await? {
+            // older versions saved any value, including ones the connection can't take
+            db.busy_timeout_ms
+                .store(busy_timeout_ms.min(MAX_BUSY_TIMEOUT_MS), Ordering::Relaxed);
+        }
+        if let Some(capacity) = db.get_setting(STATEMENT_CACHE_CAPACITY_KEY).await? {
+            db.statement_cache_capacity
+                .store(capacity, Ordering::Relaxed);
+        }
+        db.apply_connection_tuning(db.connection_tuning()).await?;
+        Ok(db)
+    }
+
+    /// Await a DB call, recording how long it took under the given query name and warning if it was slow.
+    async fn timed<T>(
+        &self,
+        query_name: &'static str,
+        call: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let start = Instant::now();
+        let result = call.await;
+        let elapsed = start.elapsed();
+
+        {
+            let mut stats = self.query_stats.entry(query_name).or_default();
+            stats.count += 1;
+            stats.total += elapsed;
+            stats.max = stats.max.max(elapsed);
+        }
+
+        let threshold_ms = self.slow_query_threshold_ms.load(Ordering::Relaxed);
+        if elapsed.as_millis() > threshold_ms as u128 {
+            warn!(
+                "slow query: {} took {}ms (threshold is {}ms)",
+                query_name,
+                elapsed.as_millis(),
+                threshold_ms
+            );
+        }
+
+        result
+    }
+
+    /// Get timing information for all queries that have run since the bot started
+    pub fn query_stats(&self) -> Vec<(&'static str, QueryStats)> {
+        self.query_stats
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().clone()))
+            .collect()
+    }
+
+    /// Get the threshold above which queries are logged as slow
+    pub fn slow_query_threshold(&self) -> Duration {
+        Duration::from_millis(self.slow_query_threshold_ms.load(Ordering::Relaxed))
+    }
+
+    /// Set and persist the threshold above which queries are logged as slow
+    pub async fn set_slow_query_threshold(&self, threshold: Duration) -> Result<()> {
+        let threshold_ms = threshold.as_millis() as u64;
+        self.set_setting(SLOW_QUERY_THRESHOLD_KEY, Some(threshold_ms))
+            .await?;
+        self.slow_query_threshold_ms
+            .store(threshold_ms, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Get the current connection tuning parameters
+    pub fn connection_tuning(&self) -> ConnectionTuning {
+        ConnectionTuning {
+            busy_timeout: Duration::from_millis(self.busy_timeout_ms.load(Ordering::Relaxed)),
+            statement_cache_capacity: self.statement_cache_capacity.load(Ordering::Relaxed)
+                as usize,
+        }
+    }
+
+    /// Apply connection tuning parameters to the live connection and persist them. `None` leaves a parameter unchanged.
+    /// Busy timeouts above [`MAX_BUSY_TIMEOUT_MS`] are rejected.
+    pub async fn set_connection_tuning(
+        &self,
+        busy_timeout: Option<Duration>,
+        statement_cache_capacity: Option<usize>,
+    ) -> Result<()> {
+        let mut tuning = self.connection_tuning();
+        if let Some(busy_timeout) = busy_timeout {
+            if busy_timeout.as_millis() > MAX_BUSY_TIMEOUT_MS as u128 {
+                return Err(tokio_rusqlite::Error::Other(Box::new(JinxError::new(
+                    format!("busy timeout must be at most {MAX_BUSY_TIMEOUT_MS}ms"),
+                ))));
+            }
+            tuning.busy_timeout = busy_timeout;
+        }
+        if let Some(statement_cache_capacity) = statement_cache_capacity {
+            tuning.statement_cache_capacity = statement_cache_capacity;
+        }
+
+        // apply before saving, so a value the connection rejects is never persisted and reapplied on every start
+        self.apply_connection_tuning(tuning).await?;
+
+        if busy_timeout.is_some() {
+            let busy_timeout_ms = tuning.busy_timeout.as_millis() as u64;
+            self.set_setting(BUSY_TIMEOUT_KEY, Some(busy_timeout_ms))
+                .await?;
+            self.busy_timeout_ms
+                .store(busy_timeout_ms, Ordering::Relaxed);
+        }
+        if statement_cache_capacity.is_some() {
+            let statement_cache_capacity = tuning.statement_cache_capacity as u64;
+            self.set_setting(STATEMENT_CACHE_CAPACITY_KEY, Some(statement_cache_capacity))
+                .await?;
+            self.statement_cache_capacity
+                .store(statement_cache_capacity, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Apply connection tuning parameters to the connection
+    async fn apply_connection_tuning(&self, tuning: ConnectionTuning) -> Result<()> {
+        self.connection
+            .call(move |connection| {
+                connection.busy_timeout(tuning.busy_timeout)?;
+                connection.set_prepared_statement_cache_capacity(tuning.statement_cache_capacity);
+                Ok(())
+            })
+            .await
+    }
+
+    /// Read a value from the settings table
+    async fn get_setting<T>(&self, key: &'static str) -> Result<Option<T>>
+    where
+        T: FromSql + Send + 'static,
+    {
+        self.timed(
+            "get_setting",
+            self.connection.call(move |connection| {
+                let mut statement =
+                    connection.prepare_cached("SELECT value FROM settings WHERE key = :key")?;
+                let result: Option<T> = statement
+                    .query_row(named_params! {":key": key}, |row| row.get(0))
+                    .optional()?;
+                Ok(result)
+            }),
+        )
+        .await
+    }
+
+    /// Write a value to the settings table, or delete it if `None`
+    async fn set_setting<T>(&self, key: &'static str, value: Option<T>) -> Result<()>
+    where
+        T: ToSql + Send + 'static,
+    {
+        self.timed(
+            "set_setting",
+            self.connection.call(move |connection| {
+                if let Some(value) = value {
+                    let mut statement = connection.prepare_cached(
+                        "INSERT OR REPLACE INTO settings (key, value) VALUES (:key, :value)",
+                    )?;
+                    statement.execute(named_params! {":key": key, ":value": value})?;
+                } else {
+                    let mut statement =
+                        connection.prepare_cached("DELETE FROM settings WHERE key = :key")?;
+                    statement.execute(named_params! {":key": key})?;
+                }
+                Ok(())
+            }),
+        )
+        .await
+    }
+
+    /// Set up the database
+    async fn init(connection: &Connection) -> Result<()> {
+        let start = Instant::now();
+        connection
+            .call(|connection| {
+                // all applications are encouraged to switch this setting off on every database connection as soon as that connection is opened
+                connection.execute("PRAGMA trusted_schema = OFF;", ())?;
+
+                // run the whole setup in one transaction, so a migration that fails leaves the DB as it was
+                let transaction = connection.transaction()?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS \"settings\" ( \
+                key                    TEXT PRIMARY KEY, \
+                value                  ANY \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS guild ( \
+                guild_id               INTEGER PRIMARY KEY, \
+                jinxxy_api_key         TEXT, \
+                log_channel_id         INTEGER, \
+                test                   INTEGER NOT NULL DEFAULT 0, \
+                owner                  INTEGER NOT NULL DEFAULT 0, \
+                stats_opt_out          INTEGER NOT NULL DEFAULT 0, \
+                blanket_role_id        INTEGER, \
+                restore_roles          INTEGER NOT NULL DEFAULT 0, \
+                log_member_leave       INTEGER NOT NULL DEFAULT 0, \
+                milestone_channel_id   INTEGER, \
+                public_count_redaction TEXT, \
+                language               TEXT, \
+                deleted_unix_ms        INTEGER, \
+                registrations_paused   INTEGER NOT NULL DEFAULT 0, \
+                sales_feed_channel_id  INTEGER, \
+                sales_feed_polled_unix_ms INTEGER, \
+                jinxxy_user_id         TEXT, \
+                log_product_changes    INTEGER NOT NULL DEFAULT 0, \
+                notification_digest    TEXT \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS product_role ( \
+                guild_id               INTEGER NOT NULL, \
+                product_id             TEXT NOT NULL, \
+                role_id                INTEGER NOT NULL, \
+                PRIMARY KEY            (guild_id, product_id, role_id) \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE INDEX IF NOT EXISTS role_lookup ON product_role (guild_id, product_id)",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS license_activation ( \
+                guild_id               INTEGER NOT NULL, \
+                license_id             TEXT NOT NULL, \
+                license_activation_id  TEXT NOT NULL, \
+                user_id                INTEGER NOT NULL, \
+                created_unix_ms        INTEGER, \
+                PRIMARY KEY            (guild_id, license_id, license_activation_id, user_id) \
+            ) STRICT",
+                    (),
+                )?;
+
+                // license_activation's primary key already covers lookups by (guild_id, license_id), but lookups by user need their own index
+                transaction.execute(
+                    "CREATE INDEX IF NOT EXISTS user_license_lookup ON license_activation (guild_id, user_id, license_id)",
+                    (),
+                )?;
+
+                // needed to cheaply check if a user has any remaining activations when maintaining the distinct user count
+                transaction.execute(
+                    "CREATE INDEX IF NOT EXISTS user_lookup ON license_activation (user_id)",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS product_version_exclusion ( \
+                guild_id               INTEGER NOT NULL, \
+                product_id             TEXT NOT NULL, \
+                product_version_id     TEXT NOT NULL, \
+                role_id                INTEGER NOT NULL, \
+                PRIMARY KEY            (guild_id, product_id, product_version_id, role_id) \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS product_seat_limit ( \
+                guild_id               INTEGER NOT NULL, \
+                product_id             TEXT NOT NULL, \
+                max_activations        INTEGER NOT NULL, \
+                PRIMARY KEY            (guild_id, product_id) \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS activation_hook ( \
+                guild_id               INTEGER NOT NULL, \
+                product_id             TEXT NOT NULL, \
+                position               INTEGER NOT NULL, \
+                kind                   TEXT NOT NULL, \
+                target                 TEXT NOT NULL, \
+                signing_secret         TEXT, \
+                PRIMARY KEY            (guild_id, product_id, position) \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS product_link_rule ( \
+                guild_id               INTEGER NOT NULL, \
+                pattern                TEXT NOT NULL, \
+                role_id                INTEGER NOT NULL, \
+                PRIMARY KEY            (guild_id, pattern, role_id) \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS product_activation_count ( \
+                guild_id               INTEGER NOT NULL, \
+                product_id             TEXT NOT NULL, \
+                activation_count       INTEGER NOT NULL, \
+                PRIMARY KEY            (guild_id, product_id) \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS product_activation_log ( \
+                guild_id               INTEGER NOT NULL, \
+                product_id             TEXT NOT NULL, \
+                timestamp_unix_ms      INTEGER NOT NULL \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE INDEX IF NOT EXISTS product_activation_log_lookup ON product_activation_log (guild_id, timestamp_unix_ms)",
+                    (),
+                )?;
+
+                // product_id is empty for guild-wide milestones
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS celebrated_milestone ( \
+                guild_id               INTEGER NOT NULL, \
+                product_id             TEXT NOT NULL, \
+                milestone              INTEGER NOT NULL, \
+                PRIMARY KEY            (guild_id, product_id) \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS audit_log ( \
+                audit_log_id           INTEGER PRIMARY KEY, \
+                guild_id               INTEGER NOT NULL, \
+                timestamp_unix_ms      INTEGER NOT NULL, \
+                actor_id               INTEGER, \
+                action                 TEXT NOT NULL, \
+                user_id                INTEGER, \
+                product_id             TEXT, \
+                role_id                INTEGER, \
+                license_id             TEXT, \
+                detail                 TEXT \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE INDEX IF NOT EXISTS audit_log_lookup ON audit_log (guild_id, timestamp_unix_ms)",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS api_request_log ( \
+                timestamp_unix_ms      INTEGER NOT NULL, \
+                endpoint               TEXT NOT NULL, \
+                latency_ms             INTEGER NOT NULL, \
+                success                INTEGER NOT NULL \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE INDEX IF NOT EXISTS api_request_log_time ON api_request_log (timestamp_unix_ms)",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS daily_metrics ( \
+                day                    INTEGER PRIMARY KEY, \
+                activation_count       INTEGER NOT NULL, \
+                api_request_count      INTEGER NOT NULL, \
+                api_error_count        INTEGER NOT NULL, \
+                api_latency_p95_ms     INTEGER NOT NULL, \
+                guild_count            INTEGER NOT NULL \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS product ( \
+                guild_id               INTEGER NOT NULL, \
+                product_id             TEXT NOT NULL, \
+                product_name           TEXT NOT NULL, \
+                thumbnail_url          TEXT, \
+                price                  TEXT, \
+                PRIMARY KEY            (guild_id, product_id) \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS product_version ( \
+                guild_id               INTEGER NOT NULL, \
+                product_id             TEXT NOT NULL, \
+                product_version_id     TEXT NOT NULL, \
+                product_version_name   TEXT NOT NULL, \
+                PRIMARY KEY            (guild_id, product_id, product_version_id) \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS message_variant ( \
+                variant_id             INTEGER PRIMARY KEY, \
+                message_key            TEXT NOT NULL, \
+                text                   TEXT, \
+                active                 INTEGER NOT NULL DEFAULT 1, \
+                shown_count            INTEGER NOT NULL DEFAULT 0, \
+                followup_success_count INTEGER NOT NULL DEFAULT 0, \
+                followup_failure_count INTEGER NOT NULL DEFAULT 0 \
+            ) STRICT",
+                    (),
+                )?;
+
+                // every message key needs a control variant, which is identified by having no text
+                for message_key in MessageKey::ALL {
+                    transaction.execute(
+                        "INSERT INTO message_variant (message_key) SELECT :key WHERE NOT EXISTS (SELECT * FROM message_variant WHERE message_key = :key AND text IS NULL)",
+                        named_params! {":key": message_key.as_db_str()},
+                    )?;
+                }
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS guild_message_variant ( \
+                guild_id               INTEGER NOT NULL, \
+                message_key            TEXT NOT NULL, \
+                variant_id             INTEGER NOT NULL, \
+                PRIMARY KEY            (guild_id, message_key) \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS message_variant_pending ( \
+                guild_id               INTEGER NOT NULL, \
+                user_id                INTEGER NOT NULL, \
+                message_key            TEXT NOT NULL, \
+                variant_id             INTEGER NOT NULL, \
+                timestamp_unix_ms      INTEGER NOT NULL, \
+                PRIMARY KEY            (guild_id, user_id, message_key) \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS announcement ( \
+                announcement_id        INTEGER PRIMARY KEY, \
+                title                  TEXT, \
+                message                TEXT NOT NULL, \
+                audience               TEXT NOT NULL, \
+                created_by             INTEGER NOT NULL, \
+                send_at_unix_ms        INTEGER NOT NULL, \
+                sent_unix_ms           INTEGER \
+            ) STRICT",
+                    (),
+                )?;
+
+                // error is NULL if the announcement was delivered
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS announcement_delivery ( \
+                announcement_id        INTEGER NOT NULL, \
+                guild_id               INTEGER NOT NULL, \
+                channel_id             INTEGER NOT NULL, \
+                error                  TEXT, \
+                PRIMARY KEY            (announcement_id, guild_id) \
+            ) STRICT",
+                    (),
+                )?;
+
+                // guilds with a product cache refresh that hasn't finished yet, so it can be picked back up after a restart
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS cache_refresh_pending ( \
+                guild_id               INTEGER PRIMARY KEY, \
+                requested_unix_ms      INTEGER NOT NULL \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS command_sync_pending ( \
+                guild_id               INTEGER PRIMARY KEY, \
+                requested_unix_ms      INTEGER NOT NULL \
+            ) STRICT",
+                    (),
+                )?;
+
+                // lets multiple instances sharing this DB tell each other to drop cached guild data. AUTOINCREMENT keeps IDs from
+                // being reused after pruning, which would hide new invalidations from instances that already saw the old IDs.
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS cache_invalidation ( \
+                invalidation_id        INTEGER PRIMARY KEY AUTOINCREMENT, \
+                guild_id               INTEGER NOT NULL, \
+                instance_id            INTEGER NOT NULL, \
+                timestamp_unix_ms      INTEGER NOT NULL \
+            ) STRICT",
+                    (),
+                )?;
+
+                // orders from the latest sales feed poll, so each order is only posted once
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS sales_feed_order ( \
+                guild_id               INTEGER NOT NULL, \
+                order_id               TEXT NOT NULL, \
+                seen_unix_ms           INTEGER NOT NULL, \
+                PRIMARY KEY            (guild_id, order_id) \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS onboarding_step ( \
+                guild_id               INTEGER NOT NULL, \
+                step                   TEXT NOT NULL, \
+                completed_unix_ms      INTEGER NOT NULL, \
+                PRIMARY KEY            (guild_id, step) \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS advisory_delivery ( \
+                guild_id               INTEGER NOT NULL, \
+                advisory               TEXT NOT NULL, \
+                delivered_unix_ms      INTEGER NOT NULL, \
+                PRIMARY KEY            (guild_id, advisory) \
+            ) STRICT",
+                    (),
+                )?;
+
+                // AUTOINCREMENT so sequence numbers are never reused, even after the newest events are purged
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS event ( \
+                sequence               INTEGER PRIMARY KEY AUTOINCREMENT, \
+                created_unix_ms        INTEGER NOT NULL, \
+                guild_id               INTEGER NOT NULL, \
+                kind                   TEXT NOT NULL, \
+                user_id                INTEGER, \
+                license_id             TEXT, \
+                product_id             TEXT, \
+                role_id                INTEGER \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS registration_failure ( \
+                guild_id               INTEGER NOT NULL, \
+                user_id                INTEGER NOT NULL, \
+                failed_unix_ms         INTEGER NOT NULL \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE INDEX IF NOT EXISTS registration_failure_lookup ON registration_failure (guild_id, user_id)",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE INDEX IF NOT EXISTS registration_failure_time ON registration_failure (failed_unix_ms)",
+                    (),
+                )?;
+
+                // user_id is GUILD_WIDE_BLOCK_USER_ID for blocks covering the whole guild
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS registration_block ( \
+                guild_id               INTEGER NOT NULL, \
+                user_id                INTEGER NOT NULL, \
+                blocked_until_unix_ms  INTEGER NOT NULL, \
+                PRIMARY KEY            (guild_id, user_id) \
+            ) STRICT",
+                    (),
+                )?;
+
+                // licenses users registered themselves, as opposed to admins importing or transferring them, along with the
+                // Jinxxy user that bought each one. Used to spot keys being guessed or shared between Discord accounts.
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS license_registration ( \
+                guild_id               INTEGER NOT NULL, \
+                license_id             TEXT NOT NULL, \
+                user_id                INTEGER NOT NULL, \
+                buyer_id               TEXT NOT NULL, \
+                registered_unix_ms     INTEGER NOT NULL, \
+                PRIMARY KEY            (guild_id, license_id, user_id) \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE INDEX IF NOT EXISTS license_registration_time ON license_registration (registered_unix_ms)",
+                    (),
+                )?;
+
+                // subject is whatever the alert is about, such as a Discord user ID or a Jinxxy buyer ID
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS suspicious_activity_alert ( \
+                guild_id               INTEGER NOT NULL, \
+                kind                   TEXT NOT NULL, \
+                subject                TEXT NOT NULL, \
+                alerted_unix_ms        INTEGER NOT NULL, \
+                PRIMARY KEY            (guild_id, kind, subject) \
+            ) STRICT",
+                    (),
+                )?;
+
+                // the event table's primary key only covers reading the whole stream in order, but webhooks read one guild's events
+                transaction.execute(
+                    "CREATE INDEX IF NOT EXISTS event_guild_lookup ON event (guild_id, sequence)",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS event_webhook ( \
+                guild_id               INTEGER PRIMARY KEY, \
+                url                    TEXT NOT NULL, \
+                signing_secret         TEXT NOT NULL, \
+                cursor                 INTEGER NOT NULL, \
+                failure_count          INTEGER NOT NULL DEFAULT 0, \
+                retry_unix_ms          INTEGER NOT NULL DEFAULT 0 \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS post_template ( \
+                guild_id               INTEGER PRIMARY KEY, \
+                title                  TEXT, \
+                description            TEXT, \
+                button_label           TEXT, \
+                color                  INTEGER, \
+                product_id             TEXT \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS registration_post ( \
+                guild_id               INTEGER NOT NULL, \
+                message_id             INTEGER NOT NULL, \
+                channel_id             INTEGER NOT NULL, \
+                PRIMARY KEY            (guild_id, message_id) \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS license_expiry ( \
+                guild_id               INTEGER NOT NULL, \
+                license_id             TEXT NOT NULL, \
+                product_id             TEXT NOT NULL, \
+                product_version_id     TEXT, \
+                expires_unix_ms        INTEGER NOT NULL, \
+                PRIMARY KEY            (guild_id, license_id) \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE INDEX IF NOT EXISTS license_expiry_lookup ON license_expiry (expires_unix_ms)",
+                    (),
+                )?;
+
+                // rows are kept once their roles are revoked, so the license can't just be registered again for more time
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS scheduled_expiry ( \
+                guild_id               INTEGER NOT NULL, \
+                user_id                INTEGER NOT NULL, \
+                license_id             TEXT NOT NULL, \
+                product_id             TEXT NOT NULL, \
+                product_version_id     TEXT, \
+                expires_unix_ms        INTEGER NOT NULL, \
+                revoked                INTEGER NOT NULL DEFAULT 0, \
+                PRIMARY KEY            (guild_id, user_id, license_id) \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE INDEX IF NOT EXISTS scheduled_expiry_lookup ON scheduled_expiry (expires_unix_ms) WHERE NOT revoked",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS notification_digest_entry ( \
+                entry_id               INTEGER PRIMARY KEY, \
+                guild_id               INTEGER NOT NULL, \
+                created_unix_ms        INTEGER NOT NULL, \
+                user_id                INTEGER NOT NULL, \
+                product_name           TEXT NOT NULL, \
+                role_ids               TEXT NOT NULL \
+            ) STRICT",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE INDEX IF NOT EXISTS notification_digest_guild_lookup ON notification_digest_entry (guild_id, created_unix_ms)",
+                    (),
+                )?;
+
+                transaction.execute(
+                    "CREATE TABLE IF NOT EXISTS \"owner\" ( \
+                owner_id               INTEGER PRIMARY KEY \
+            ) STRICT",
+                    (),
+                )?;
+
+                let schema_version: i32 = transaction
+                    .query_row(
+                        "SELECT value FROM settings where key = :key",
+                        named_params! {":key": SCHEMA_VERSION_KEY},
+                        |a| a.get(0),
+                    )
+                    .optional()?
+                    .unwrap_or(SCHEMA_VERSION_VALUE);
+
+                // handle schema v1 -> v2 migration
+                if schema_version < 2 {
+                    // "log_channel_id" column needs to be added to "guild"
+                    transaction
+                        .execute("ALTER TABLE guild ADD COLUMN log_channel_id INTEGER", ())?;
+                    // "test" column needs to be added to "guild"
+                    transaction.execute(
+                        "ALTER TABLE guild ADD COLUMN test INTEGER NOT NULL DEFAULT 0",
+                        (),
+                    )?;
+                }
+
+                // handle schema v2 -> v3 migration
+                if schema_version < 3 {
+                    // "owner" column needs to be added to "guild"
+                    transaction.execute(
+                        "ALTER TABLE guild ADD COLUMN owner INTEGER NOT NULL DEFAULT 0",
+                        (),
+                    )?;
+                }
+
+                // handle schema v3 -> v4 migration
+                if schema_version < 4 {
+                    // "guild.id" column needs to be renamed to "guild_id"
+                    transaction.execute("ALTER TABLE guild RENAME COLUMN id TO guild_id", ())?;
+                }
+
+                // schema v4 -> v5 migration only adds the `user_license_lookup` index, which is already created above
+
+                // schema v5 -> v6 migration only adds the `product_version_exclusion` table, which is already created above
+
+                // schema v6 -> v7 migration only adds the `user_lookup` index, which is already created above
+
+                // schema v7 -> v8 migration only adds the `product_seat_limit` table, which is already created above
+
+                // schema v8 -> v9 migration only adds the `audit_log` table, which is already created above
+
+                // schema v9 -> v10 migration only adds the `api_request_log` table, which is already created above
+
+                // schema v10 -> v11 migration only adds the `product` table, which is already created above
+
+                // schema v11 -> v12 migration only adds the `product_version` table, which is already created above
+
+                // handle schema v12 -> v13 migration
+                if schema_version < 13 {
+                    // "stats_opt_out" column needs to be added to "guild"
+                    transaction.execute(
+                        "ALTER TABLE guild ADD COLUMN stats_opt_out INTEGER NOT NULL DEFAULT 0",
+                        (),
+                    )?;
+                }
+
+                // handle schema v13 -> v14 migration
+                if schema_version < 14 {
+                    // "blanket_role_id" column needs to be added to "guild"
+                    transaction.execute("ALTER TABLE guild ADD COLUMN blanket_role_id INTEGER", ())?;
+                }
+
+                // schema v14 -> v15 migration only adds the `message_variant`, `guild_message_variant`, and `message_variant_pending` tables, which are already created above
+
+                // handle schema v15 -> v16 migration
+                if schema_version < 16 {
+                    // "restore_roles" and "log_member_leave" columns need to be added to "guild"
+                    transaction.execute(
+                        "ALTER TABLE guild ADD COLUMN restore_roles INTEGER NOT NULL DEFAULT 0",
+                        (),
+                    )?;
+                    transaction.execute(
+                        "ALTER TABLE guild ADD COLUMN log_member_leave INTEGER NOT NULL DEFAULT 0",
+                        (),
+                    )?;
+                }
+
+                // schema v16 -> v17 migration only adds the `activation_hook` table, which is already created above
+
+                // handle schema v17 -> v18 migration
+                if schema_version < 18 {
+                    // "milestone_channel_id" column needs to be added to "guild". The `product_activation_count` and
+                    // `celebrated_milestone` tables are already created above.
+                    transaction.execute("ALTER TABLE guild ADD COLUMN milestone_channel_id INTEGER", ())?;
+                }
+
+                // schema v18 -> v19 migration only adds the `product_activation_log` table, which is already created above
+
+                // handle schema v19 -> v20 migration
+                if schema_version < 20 {
+                    // "public_count_redaction" column needs to be added to "guild"
+                    transaction.execute("ALTER TABLE guild ADD COLUMN public_count_redaction TEXT", ())?;
+                }
+
+                // schema v20 -> v21 migration only adds the `product_link_rule` table, which is already created above
+
+                // handle schema v21 -> v22 migration
+                if schema_version < 22 {
+                    // "language" column needs to be added to "guild"
+                    transaction.execute("ALTER TABLE guild ADD COLUMN language TEXT", ())?;
+                }
+
+                // handle schema v22 -> v23 migration
+                if schema_version < 23 {
+                    // "deleted_unix_ms" column needs to be added to "guild"
+                    transaction.execute("ALTER TABLE guild ADD COLUMN deleted_unix_ms INTEGER", ())?;
+                }
+
+                // handle schema v23 -> v24 migration
+                if schema_version < 24 {
+                    // "registrations_paused" column needs to be added to "guild"
+                    transaction.execute("ALTER TABLE guild ADD COLUMN registrations_paused INTEGER NOT NULL DEFAULT 0", ())?;
+                }
+
+                // handle schema v24 -> v25 migration
+                if schema_version < 25 {
+                    // "created_unix_ms" column needs to be added to "license_activation". It stays NULL for activations made before this.
+                    transaction.execute("ALTER TABLE license_activation ADD COLUMN created_unix_ms INTEGER", ())?;
+                }
+
+                // schema v25 -> v26 migration only adds the `announcement` and `announcement_delivery` tables, which are already created above
+
+                // schema v26 -> v27 migration only adds the `cache_refresh_pending` table, which is already created above
+
+                // schema v27 -> v28 migration only adds the `cache_invalidation` table, which is already created above
+
+                // handle schema v28 -> v29 migration
+                if schema_version < 29 {
+                    // "sales_feed_channel_id" and "sales_feed_polled_unix_ms" columns need to be added to "guild". The `sales_feed_order` table is already created above.
+                    transaction.execute("ALTER TABLE guild ADD COLUMN sales_feed_channel_id INTEGER", ())?;
+                    transaction.execute("ALTER TABLE guild ADD COLUMN sales_feed_polled_unix_ms INTEGER", ())?;
+                }
+
+                // handle schema v29 -> v30 migration
+                if schema_version < 30 {
+                    // The `onboarding_step` table is already created above, but existing guilds need their progress
+                    // backfilled from the data they already have. We can't tell if a registration post was ever made, so
+                    // guilds with activations are assumed to have one.
+                    let timestamp = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map(|duration| duration.as_millis() as u64)
+                        .unwrap_or(0);
+                    transaction.execute("INSERT OR IGNORE INTO onboarding_step (guild_id, step, completed_unix_ms) SELECT guild_id, 'store_linked', :timestamp FROM guild WHERE jinxxy_api_key IS NOT NULL", named_params! {":timestamp": timestamp})?;
+                    transaction.execute("INSERT OR IGNORE INTO onboarding_step (guild_id, step, completed_unix_ms) SELECT guild_id, 'log_channel_set', :timestamp FROM guild WHERE log_channel_id IS NOT NULL", named_params! {":timestamp": timestamp})?;
+                    transaction.execute("INSERT OR IGNORE INTO onboarding_step (guild_id, step, completed_unix_ms) SELECT DISTINCT guild_id, 'first_link', :timestamp FROM product_role", named_params! {":timestamp": timestamp})?;
+                    transaction.execute("INSERT OR IGNORE INTO onboarding_step (guild_id, step, completed_unix_ms) SELECT DISTINCT guild_id, 'first_post', :timestamp FROM license_activation WHERE user_id != :locking_user", named_params! {":timestamp": timestamp, ":locking_user": LOCKING_USER_ID})?;
+                    transaction.execute("INSERT OR IGNORE INTO onboarding_step (guild_id, step, completed_unix_ms) SELECT DISTINCT guild_id, 'first_activation', :timestamp FROM license_activation WHERE user_id != :locking_user", named_params! {":timestamp": timestamp, ":locking_user": LOCKING_USER_ID})?;
+                }
+
+                // handle schema v30 -> v31 migration
+                // schema v30 -> v31 migration only adds the `advisory_delivery` table, which is already created above
+
+                // handle schema v31 -> v32 migration
+                if schema_version < 32 {
+                    // "jinxxy_user_id" column needs to be added to "guild". Existing stores leave it null until their key
+                    // is next set or rotated.
+                    transaction.execute("ALTER TABLE guild ADD COLUMN jinxxy_user_id TEXT", ())?;
+                }
+
+                // handle schema v32 -> v33 migration
+                // schema v32 -> v33 migration only adds the `daily_metrics` table, which is already created above
+
+                // handle schema v33 -> v34 migration
+                if schema_version < 34 {
+                    // "log_product_changes" column needs to be added to "guild"
+                    transaction.execute("ALTER TABLE guild ADD COLUMN log_product_changes INTEGER NOT NULL DEFAULT 0", ())?;
+                }
+
+                // handle schema v34 -> v35 migration
+                // schema v34 -> v35 migration only adds the `command_sync_pending` table, which is already created above
+
+                // handle schema v35 -> v36 migration
+                // schema v35 -> v36 migration only adds the `event` table, which is already created above
+
+                // handle schema v36 -> v37 migration
+                // schema v36 -> v37 migration only adds the `registration_failure` and `registration_block` tables, which are already created above
+
+                // handle schema v37 -> v38 migration
+                // schema v37 -> v38 migration only adds the `license_registration` and `suspicious_activity_alert` tables, which are already created above
+
+                // handle schema v38 -> v39 migration
+                if schema_version < 39 {
+                    // activations from before "created_unix_ms" was added have no timestamp. Where the role grant or
+                    // transfer that made the activation is still in the audit log, its time is close enough.
+                    transaction.execute("UPDATE license_activation SET created_unix_ms = ( \
+                        SELECT min(audit_log.timestamp_unix_ms) FROM audit_log WHERE audit_log.guild_id = license_activation.guild_id \
+                        AND audit_log.license_id = license_activation.license_id AND audit_log.user_id = license_activation.user_id \
+                        AND audit_log.action IN ('role_grant', 'transfer') \
+                    ) WHERE created_unix_ms IS NULL", ())?;
+                }
+
+                // handle schema v39 -> v40 migration
+                // schema v39 -> v40 migration only adds the `event_webhook` table and `event_guild_lookup` index, which are already created above
+
+                // handle schema v40 -> v41 migration
+                if schema_version < 41 {
+                    // "thumbnail_url" and "price" columns need to be added to "product"
+                    // "product" may have been created after v11 with them already
+                    add_column_if_missing(&transaction, "product", "thumbnail_url", "TEXT")?;
+                    add_column_if_missing(&transaction, "product", "price", "TEXT")?;
+                }
+
+                // schema v41 -> v42 migration only adds the `post_template` table, which is already created above
+
+                // schema v42 -> v43 migration only adds the `registration_post` table, which is already created above
+
+                // schema v43 -> v44 migration only adds the `license_expiry` table and `license_expiry_lookup` index, which are already created above
+
+                // handle schema v44 -> v45 migration
+                if schema_version < 45 {
+                    // "notification_digest" column needs to be added to "guild". The `notification_digest_entry` table
+                    // and `notification_digest_guild_lookup` index are already created above.
+                    transaction.execute("ALTER TABLE guild ADD COLUMN notification_digest TEXT", ())?;
+                }
+
+                // handle schema v45 -> v46 migration
+                if schema_version < 46 {
+                    // "signing_secret" column needs to be added to "activation_hook". Existing webhooks get a secret in
+                    // the same format as `webhook::generate_signing_secret`, which admins can see by adding the hook again.
+                    // "activation_hook" may have been created after v17 with it already
+                    add_column_if_missing(&transaction, "activation_hook", "signing_secret", "TEXT")?;
+                    transaction.execute("UPDATE activation_hook SET signing_secret = 'jinx_whsec_' || lower(hex(randomblob(32))) WHERE kind = 'webhook'", ())?;
+                }
+
+                // schema v46 -> v47 migration only adds the `scheduled_expiry` table and `scheduled_expiry_lookup` index, which are already created above
+
+                // update the schema version value persisted to the DB
+                transaction.execute(
+                    "INSERT OR REPLACE INTO settings (key, value) VALUES (:key, :value)",
+                    named_params! {":key": SCHEMA_VERSION_KEY, ":value": SCHEMA_VERSION_VALUE},
+                )?;
+                transaction.commit()?;
+
+                // Applications that use long-lived database connections should run "PRAGMA optimize=0x10002;" when the connection is first opened.
+                // All applications should run "PRAGMA optimize;" after a schema change.
+                connection.execute("PRAGMA optimize = 0x10002", ())?;
+
+                Ok(())
+            })
+            .await?;
+
+        let elapsed = start.elapsed();
+        debug!("initialized db in {}ms", elapsed.as_millis());
+
+        Ok(())
+    }
+
+    /// Attempt to optimize the database.
+    ///
+    /// Applications that use long-lived database connections should run "PRAGMA optimize;" periodically, perhaps once per day or once per hour.
+    pub async fn optimize(&self) -> Result<()> {
+        self.timed(
+            "optimize",
+            self.connection.call(move |connection| {
+                connection.execute("PRAGMA optimize", ())?;
+                Ok(())
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Flush the write-ahead log into the main database file, if the database uses one, so nothing is left waiting in
+    /// the log when the process exits.
+    pub async fn checkpoint(&self) -> Result<()> {
+        self.timed(
+            "checkpoint",
+            self.connection.call(move |connection| {
+                // this returns a status row, which we don't need
+                connection.query_row("PRAGMA wal_checkpoint(TRUNCATE)", (), |_| Ok(()))?;
+                Ok(())
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn add_owner(&self, owner_id: u64) -> Result<()> {
+        self.timed(
+            "add_owner",
+            self.connection.call(move |connection| {
+                let mut statement = connection
+                    .prepare_cached("INSERT OR IGNORE INTO owner (owner_id) VALUES (:owner)")?;
+                statement.execute(named_params! {":owner": owner_id})?;
+                Ok(())
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete_owner(&self, owner_id: u64) -> Result<()> {
+        self.timed(
+            "delete_owner",
+            self.connection.call(move |connection| {
+                let mut statement =
+                    connection.prepare_cached("DELETE FROM owner WHERE owner_id = :owner")?;
+                statement.execute(named_params! {":owner": owner_id})?;
+                Ok(())
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_discord_token(&self, discord_token: SecretString) -> Result<()> {
+        let discord_token =
+            secret::encrypt(discord_token.expose_secret(), DISCORD_TOKEN_SECRET_CONTEXT)
+                .map(SecretString::new)
+                .map_err(secret_error)?;
+        self.timed(
+            "set_discord_token",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "INSERT OR REPLACE INTO settings (key, value) VALUES (:key, :value)",
+                )?;
+                statement
+                    .execute(named_params! {":key": DISCORD_TOKEN_KEY, ":value": discord_token.expose_secret()})?;
+                Ok(())
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_owners(&self) -> Result<Vec<u64>> {
+        self.timed(
+            "get_owners",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached("SELECT owner_id FROM owner")?;
+                let result = statement.query_map((), |row| {
+                    let owner_id: u64 = row.get(0)?;
+                    Ok(owner_id)
+                })?;
+                let mut vec = Vec::with_capacity(result.size_hint().0);
+                for row in result {
+                    vec.push(row?);
+                }
+                Ok(vec)
+            }),
+        )
+        .await
+    }
+
+    pub async fn is_user_owner(&self, owner_id: u64) -> Result<bool> {
+        self.timed(
+            "is_user_owner",
+            self.connection.call(move |connection| {
+                let mut statement = connection
+                    .prepare_cached("SELECT EXISTS(SELECT * FROM owner WHERE owner_id = :owner)")?;
+                let owner_exists =
+                    statement.query_row(named_params! {":owner": owner_id}, |row| {
+                        let exists: bool = row.get(0)?;
+                        Ok(exists)
+                    })?;
+                Ok(owner_exists)
+            }),
+        )
+        .await
+    }
+
+    pub async fn get_discord_token(&self) -> Result<Option<SecretString>> {
+        let discord_token = self
+            .timed(
+                "get_discord_token",
+                self.connection.call(move |connection| {
+                    let result: Option<String> = connection
+                        .query_row(
+                            format!(
+                                r#"SELECT value FROM settings WHERE key = "{DISCORD_TOKEN_KEY}""#
+                            )
+                            .as_str(),
+                            [],
+                            |row| row.get(0),
+                        )
+                        .optional()?;
+                    Ok(result.map(SecretString::new))
+                }),
+            )
+            .await?;
+        discord_token
+            .map(|discord_token| {
+                secret::decrypt(discord_token.expose_secret(), DISCORD_TOKEN_SECRET_CONTEXT)
+            })
+            .transpose()
+            .map_err(secret_error)
+    }
+
+    /// Locally record that we've activated a license for a user
+    pub async fn activate_license(
+        &self,
+        guild: GuildId,
+        license_id: String,
+        license_activation_id: String,
+        user_id: u64,
+    ) -> Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        let (counted, new_user) = self.timed("activate_license", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT OR IGNORE INTO license_activation (guild_id, license_id, license_activation_id, user_id, created_unix_ms) VALUES (:guild, :license, :activation, :user, :timestamp)")?;
+            let insert_count = statement.execute(named_params! {":guild": guild.get(), ":license": license_id, ":activation": license_activation_id, ":user": user_id, ":timestamp": timestamp})?;
+            if insert_count != 0 {
+                connection.prepare_cached(INSERT_EVENT_QUERY)?.execute(named_params! {":timestamp": timestamp, ":guild": guild.get(), ":kind": EventKind::Activation.as_db_str(), ":user": user_id, ":license": license_id, ":product": None::<String>, ":role": None::<u64>})?;
+            }
+            if user_id != LOCKING_USER_ID {
+                connection.prepare_cached(COMPLETE_ONBOARDING_STEP_QUERY)?.execute(named_params! {":guild": guild.get(), ":step": OnboardingStep::FirstActivation.as_db_str(), ":timestamp": timestamp})?;
+            }
+            let counted = insert_count != 0 && connection.prepare_cached(PRODUCTION_GUILD_QUERY)?.query_row(named_params! {":guild": guild.get()}, |row| row.get(0))?;
+            let new_user = counted && user_id != LOCKING_USER_ID && {
+                let activation_count: u64 = connection.prepare_cached(PRODUCTION_USER_ACTIVATION_COUNT_QUERY)?.query_row(named_params! {":user": user_id}, |row| row.get(0))?;
+                activation_count == 1
+            };
+            Ok((counted, new_user))
+        })).await?;
+        if counted {
+            self.license_activation_count
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        if new_user {
+            self.distinct_user_count.fetch_add(1, Ordering::Relaxed);
+            self.distinct_user_count_changed.notify_waiters();
+        }
+        Ok(())
+    }
+
+    /// Locally record that we've deactivated a license for a user. Returns `true` if a row was found and deleted, or `false` if no row was found to delete.
+    pub async fn deactivate_license(
+        &self,
+        guild: GuildId,
+        license_id: String,
+        license_activation_id: String,
+        user_id: u64,
+    ) -> Result<bool> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        let (deleted, counted, lost_user) = self.timed("deactivate_license", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("DELETE FROM license_activation WHERE guild_id = :guild AND license_id = :license AND license_activation_id = :activation AND user_id = :user")?;
+            let delete_count = statement.execute(named_params! {":guild": guild.get(), ":license": license_id, ":activation": license_activation_id, ":user": user_id})?;
+            let deleted = delete_count != 0;
+            if deleted {
+                connection.prepare_cached(INSERT_EVENT_QUERY)?.execute(named_params! {":timestamp": timestamp, ":guild": guild.get(), ":kind": EventKind::Deactivation.as_db_str(), ":user": user_id, ":license": license_id, ":product": None::<String>, ":role": None::<u64>})?;
+            }
+            let counted = deleted && connection.prepare_cached(PRODUCTION_GUILD_QUERY)?.query_row(named_params! {":guild": guild.get()}, |row| row.get(0))?;
+            let lost_user = counted && user_id != LOCKING_USER_ID && {
+                let activation_count: u64 = connection.prepare_cached(PRODUCTION_USER_ACTIVATION_COUNT_QUERY)?.query_row(named_params! {":user": user_id}, |row| row.get(0))?;
+                activation_count == 0
+            };
+            Ok((deleted, counted, lost_user))
+        })).await?;
+        if counted {
+            decrement(&self.license_activation_count);
+        }
+        if lost_user {
+            decrement(&self.distinct_user_count);
+            self.distinct_user_count_changed.notify_waiters();
+        }
+        Ok(deleted)
+    }
+
+    /// Get the registration block affecting a user, if any. If both the user and the whole guild are blocked, this is
+    /// whichever block lasts longer.
+    pub async fn get_registration_block(
+        &self,
+        guild: GuildId,
+        user_id: u64,
+    ) -> Result<Option<RegistrationBlock>> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        self.timed("get_registration_block", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT user_id, blocked_until_unix_ms FROM registration_block WHERE guild_id = :guild AND user_id IN (:user, :guild_wide) AND blocked_until_unix_ms > :timestamp ORDER BY blocked_until_unix_ms DESC LIMIT 1")?;
+            let block = statement.query_row(named_params! {":guild": guild.get(), ":user": user_id, ":guild_wide": GUILD_WIDE_BLOCK_USER_ID, ":timestamp": timestamp}, |row| {
+                let blocked_user_id: u64 = row.get(0)?;
+                Ok(RegistrationBlock {
+                    guild_wide: blocked_user_id == GUILD_WIDE_BLOCK_USER_ID,
+                    until_unix_ms: row.get(1)?,
+                })
+            }).optional()?;
+            Ok(block)
+        })).await
+    }
+
+    /// Record a failed license registration. If this pushes the user or the guild over its failure limit, a block is
+    /// started and returned. Returns `None` if no new block was started.
+    pub async fn record_registration_failure(
+        &self,
+        guild: GuildId,
+        user_id: u64,
+    ) -> Result<Option<RegistrationBlock>> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        self.timed("record_registration_failure", self.connection.call(move |connection| {
+            let transaction = connection.transaction()?;
+            let block = {
+                // clean up anything that no longer matters while we're here
+                transaction.prepare_cached("DELETE FROM registration_failure WHERE failed_unix_ms <= :cutoff")?
+                    .execute(named_params! {":cutoff": timestamp.saturating_sub(REGISTRATION_FAILURE_WINDOW_MS)})?;
+                transaction.prepare_cached("DELETE FROM registration_block WHERE blocked_until_unix_ms <= :timestamp")?
+                    .execute(named_params! {":timestamp": timestamp})?;
+
+                transaction.prepare_cached("INSERT INTO registration_failure (guild_id, user_id, failed_unix_ms) VALUES (:guild, :user, :timestamp)")?
+                    .execute(named_params! {":guild": guild.get(), ":user": user_id, ":timestamp": timestamp})?;
+                let user_failure_count: u64 = transaction.prepare_cached("SELECT count(*) FROM registration_failure WHERE guild_id = :guild AND user_id = :user")?
+                    .query_row(named_params! {":guild": guild.get(), ":user": user_id}, |row| row.get(0))?;
+                let guild_failure_count: u64 = transaction.prepare_cached("SELECT count(*) FROM registration_failure WHERE guild_id = :guild")?
+                    .query_row(named_params! {":guild": guild.get()}, |row| row.get(0))?;
+
+                let block = if guild_failure_count >= GUILD_REGISTRATION_FAILURE_LIMIT {
+                    Some((GUILD_WIDE_BLOCK_USER_ID, GUILD_REGISTRATION_BLOCK_MS))
+                } else if user_failure_count >= USER_REGISTRATION_FAILURE_LIMIT {
+                    Some((user_id, USER_REGISTRATION_BLOCK_MS))
+                } else {
+                    None
+                };
+                match block {
+                    Some((blocked_user_id, duration_ms)) => {
+                        // a block that's already running isn't extended, so it's only reported once
+                        let until_unix_ms = timestamp + duration_ms;
+                        let insert_count = transaction.prepare_cached("INSERT OR IGNORE INTO registration_block (guild_id, user_id, blocked_until_unix_ms) VALUES (:guild, :user, :until)")?
+                            .execute(named_params! {":guild": guild.get(), ":user": blocked_user_id, ":until": until_unix_ms})?;
+                        (insert_count != 0).then_some(RegistrationBlock {
+                            guild_wide: blocked_user_id == GUILD_WIDE_BLOCK_USER_ID,
+                            until_unix_ms,
+                        })
+                    }
+                    None => None,
+                }
+            };
+            transaction.commit()?;
+            Ok(block)
+        })).await
+    }
+
+    /// Locally check if a license is locked. This may be out of sync with Jinxxy!
+    pub async fn is_license_locked(&self, guild: GuildId, license_id: String) -> Result<bool> {
+        self.timed(
+            "is_license_locked",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached("SELECT EXISTS(SELECT * FROM license_activation WHERE guild_id = :guild AND license_id = :license AND user_id = 0)")?; // uses user_license_lookup index
+                let lock_exists = statement.query_row(
+                    named_params! {":guild": guild.get(), ":license": license_id},
+                    |row| {
+                        let exists: bool = row.get(0)?;
+                        Ok(exists)
+                    },
+                )?;
+                Ok(lock_exists)
+            }),
+        )
+        .await
+    }
+
+    /// Set Jinxxy API key for this guild, along with the ID of the Jinxxy user it belongs to if known
+    pub async fn set_jinxxy_api_key(
+        &self,
+        guild: GuildId,
+        api_key: SecretString,
+        jinxxy_user_id: Option<String>,
+    ) -> Result<()> {
+        let stored_api_key =
+            secret::encrypt(api_key.expose_secret(), &api_key_secret_context(guild))
+                .map(SecretString::new)
+                .map_err(secret_error)?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        self.timed("set_jinxxy_api_key", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO guild (guild_id, jinxxy_api_key, jinxxy_user_id) VALUES (:guild, :api_key, :user) ON CONFLICT (guild_id) DO UPDATE SET jinxxy_api_key = excluded.jinxxy_api_key, jinxxy_user_id = excluded.jinxxy_user_id")?;
+            statement.execute(named_params! {":guild": guild.get(), ":api_key": stored_api_key.expose_secret(), ":user": jinxxy_user_id})?;
+            connection.prepare_cached(COMPLETE_ONBOARDING_STEP_QUERY)?.execute(named_params! {":guild": guild.get(), ":step": OnboardingStep::StoreLinked.as_db_str(), ":timestamp": timestamp})?;
+            Ok(())
+        })).await?;
+        self.api_key_cache.insert(guild, Some(api_key));
+        // other instances may have the old key cached
+        self.invalidate_guild_caches(vec![guild]).await
+    }
+
+    /// Replace this guild's Jinxxy API key with a new key for the same Jinxxy user. Nothing is changed if the guild has no
+    /// key, or if its key is known to belong to a different user. Returns `true` if the key was replaced.
+    pub async fn rotate_jinxxy_api_key(
+        &self,
+        guild: GuildId,
+        api_key: SecretString,
+        jinxxy_user_id: String,
+    ) -> Result<bool> {
+        let stored_api_key =
+            secret::encrypt(api_key.expose_secret(), &api_key_secret_context(guild))
+                .map(SecretString::new)
+                .map_err(secret_error)?;
+        let rotated = self.timed("rotate_jinxxy_api_key", self.connection.call(move |connection| {
+            // the user check happens in the same statement as the swap, so a concurrent /init for another store can't slip in between
+            let mut statement = connection.prepare_cached("UPDATE guild SET jinxxy_api_key = :api_key, jinxxy_user_id = :user WHERE guild_id = :guild AND jinxxy_api_key IS NOT NULL AND (jinxxy_user_id IS NULL OR jinxxy_user_id = :user)")?;
+            let updated = statement.execute(named_params! {":guild": guild.get(), ":api_key": stored_api_key.expose_secret(), ":user": jinxxy_user_id})?;
+            Ok(updated != 0)
+        })).await?;
+        if rotated {
+            self.api_key_cache.insert(guild, Some(api_key));
+            // other instances still have the old key cached
+            self.invalidate_guild_caches(vec![guild]).await?;
+        }
+        Ok(rotated)
+    }
+
+    /// Get the ID of the Jinxxy user this guild's API key belongs to. This is `None` for keys set before we started
+    /// recording it.
+    pub async fn get_jinxxy_user_id(&self, guild: GuildId) -> Result<Option<String>> {
+        self.timed(
+            "get_jinxxy_user_id",
+            self.connection.call(move |connection| {
+                let mut statement = connection
+                    .prepare_cached("SELECT jinxxy_user_id FROM guild WHERE guild_id = ?")?;
+                let result: Option<Option<String>> = statement
+                    .query_row([guild.get()], |row| row.get(0))
+                    .optional()?;
+                Ok(result.flatten())
+            }),
+        )
+        .await
+    }
+
+    /// Tell other instances sharing this DB to drop their cached data for these guilds
+    pub async fn invalidate_guild_caches(&self, guilds: Vec<GuildId>) -> Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        let instance_id = self.instance_id;
+        self.timed("invalidate_guild_caches", self.connection.call(move |connection| {
+            let transaction = connection.transaction()?;
+            {
+                let mut statement = transaction.prepare_cached("INSERT INTO cache_invalidation (guild_id, instance_id, timestamp_unix_ms) VALUES (:guild, :instance, :timestamp)")?;
+                for guild in guilds {
+                    statement.execute(named_params! {":guild": guild.get(), ":instance": instance_id, ":timestamp": timestamp})?;
+                }
+            }
+            transaction.commit()?;
+            Ok(())
+        })).await
+    }
+
+    /// Get the guilds other instances have invalidated since the last poll. Cached API keys for those guilds are dropped
+    /// here, but the caller is responsible for any other caches.
+    pub async fn poll_cache_invalidations(&self) -> Result<Vec<GuildId>> {
+        let cursor = self.invalidation_cursor.load(Ordering::Relaxed);
+        let instance_id = self.instance_id;
+        let (cursor, guilds) = self
+            .timed(
+                "poll_cache_invalidations",
+                self.connection.call(move |connection| {
+                    let mut statement = connection.prepare_cached("SELECT invalidation_id, guild_id, instance_id FROM cache_invalidation WHERE invalidation_id > :cursor ORDER BY invalidation_id")?; // uses primary key index
+                    let rows = statement.query_map(named_params! {":cursor": cursor}, |row| {
+                        Ok((
+                            row.get::<_, u64>(0)?,
+                            row.get::<_, u64>(1)?,
+                            row.get::<_, u32>(2)?,
+                        ))
+                    })?;
+                    let mut cursor = cursor;
+                    let mut guilds = Vec::new();
+                    for row in rows {
+                        let (invalidation_id, guild_id, invalidating_instance_id) = row?;
+                        cursor = invalidation_id;
+                        if invalidating_instance_id != instance_id {
+                            guilds.push(GuildId::new(guild_id));
+                        }
+                    }
+                    Ok((cursor, guilds))
+                }),
+            )
+            .await?;
+        self.invalidation_cursor.store(cursor, Ordering::Relaxed);
+        for guild in &guilds {
+            self.api_key_cache.remove(guild);
+        }
+        Ok(guilds)
+    }
+
+    /// Get the ID of the most recent cache invalidation, or 0 if there are none
+    async fn latest_cache_invalidation(&self) -> Result<u64> {
+        self.timed(
+            "latest_cache_invalidation",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "SELECT coalesce(max(invalidation_id), 0) FROM cache_invalidation",
+                )?;
+                let invalidation_id = statement.query_row((), |row| row.get(0))?;
+                Ok(invalidation_id)
+            }),
+        )
+        .await
+    }
+
+    /// Delete cache invalidations older than the given time. Every instance will have polled them long before then.
+    pub async fn prune_cache_invalidations(&self, before_unix_ms: u64) -> Result<()> {
+        self.timed(
+            "prune_cache_invalidations",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "DELETE FROM cache_invalidation WHERE timestamp_unix_ms < :before",
+                )?;
+                statement.execute(named_params! {":before": before_unix_ms})?;
+                Ok(())
+            }),
+        )
+        .await
+    }
+
+    /// Get Jinxxy API key for this guild
+    pub async fn get_jinxxy_api_key(&self, guild: GuildId) -> Result<Option<SecretString>> {
+        if let Some(api_key) = self.api_key_cache.get(&guild) {
+            // cached read
+            Ok(api_key.value().clone())
+        } else {
+            // cache miss
+            let api_key = self
+                .timed(
+                    "get_jinxxy_api_key",
+                    self.connection.call(move |connection| {
+                        let mut statement = connection.prepare_cached(
+                            "SELECT jinxxy_api_key FROM guild WHERE guild_id = ?",
+                        )?;
+                        let result: Option<Option<String>> = statement
+                            .query_row([guild.get()], |row| row.get(0))
+                            .optional()?;
+                        Ok(result.flatten().map(SecretString::new))
+                    }),
+                )
+                .await?
+                .map(|api_key| {
+                    secret::decrypt(api_key.expose_secret(), &api_key_secret_context(guild))
+                })
+                .transpose()
+                .map_err(secret_error)?;
+            self.api_key_cache.insert(guild, api_key.clone());
+            Ok(api_key)
+        }
+    }
+
+    /// Encrypt any secrets still stored in plaintext. Returns how many were encrypted. Fails if no secret key is
+    /// configured, as there would be nothing to encrypt them with.
+    pub async fn encrypt_secrets(&self) -> Result<usize> {
+        if !secret::is_enabled().map_err(secret_error)? {
+            return Err(secret_error(JinxError::new(format!(
+                "{} must be set to encrypt secrets",
+                secret::SECRET_KEY_ENV_VAR
+            ))));
+        }
+        self.timed("encrypt_secrets", self.connection.call(move |connection| {
+            let transaction = connection.transaction()?;
+            let mut encrypted_count = 0;
+            {
+                let mut select_api_keys = transaction.prepare("SELECT guild_id, jinxxy_api_key FROM guild WHERE jinxxy_api_key IS NOT NULL")?;
+                let mut update_api_key = transaction.prepare("UPDATE guild SET jinxxy_api_key = :api_key WHERE guild_id = :guild")?;
+                let api_keys = select_api_keys.query_map((), |row| {
+                    let guild_id: u64 = row.get(0)?;
+                    let api_key: String = row.get(1)?;
+                    Ok((GuildId::new(guild_id), SecretString::new(api_key)))
+                })?;
+                for row in api_keys {
+                    let (guild, api_key) = row?;
+                    if !secret::is_encrypted(api_key.expose_secret()) {
+                        let api_key = secret::encrypt(api_key.expose_secret(), &api_key_secret_context(guild)).map(SecretString::new).map_err(secret_error)?;
+                        update_api_key.execute(named_params! {":guild": guild.get(), ":api_key": api_key.expose_secret()})?;
+                        encrypted_count += 1;
+                    }
+                }
+
+                let mut select_webhook_secrets = transaction.prepare("SELECT guild_id, signing_secret FROM event_webhook")?;
+                let mut update_webhook_secret = transaction.prepare("UPDATE event_webhook SET signing_secret = :secret WHERE guild_id = :guild")?;
+                let webhook_secrets = select_webhook_secrets.query_map((), |row| {
+                    let guild_id: u64 = row.get(0)?;
+                    let signing_secret: String = row.get(1)?;
+                    Ok((GuildId::new(guild_id), SecretString::new(signing_secret)))
+                })?;
+                for row in webhook_secrets {
+                    let (guild, signing_secret) = row?;
+                    if !secret::is_encrypted(signing_secret.expose_secret()) {
+                        let signing_secret = secret::encrypt(signing_secret.expose_secret(), &event_webhook_secret_context(guild)).map(SecretString::new).map_err(secret_error)?;
+                        update_webhook_secret.execute(named_params! {":guild": guild.get(), ":secret": signing_secret.expose_secret()})?;
+                        encrypted_count += 1;
+                    }
+                }
+
+                let mut select_hook_secrets = transaction.prepare("SELECT guild_id, product_id, position, signing_secret FROM activation_hook WHERE signing_secret IS NOT NULL")?;
+                let mut update_hook_secret = transaction.prepare("UPDATE activation_hook SET signing_secret = :secret WHERE guild_id = :guild AND product_id = :product AND position = :position")?;
+                let hook_secrets = select_hook_secrets.query_map((), |row| {
+                    let guild_id: u64 = row.get(0)?;
+                    let product_id: String = row.get(1)?;
+                    let position: u64 = row.get(2)?;
+                    let signing_secret: String = row.get(3)?;
+                    Ok((GuildId::new(guild_id), product_id, position, SecretString::new(signing_secret)))
+                })?;
+                for row in hook_secrets {
+                    let (guild, product_id, position, signing_secret) = row?;
+                    if !secret::is_encrypted(signing_secret.expose_secret()) {
+                        let signing_secret = secret::encrypt(signing_secret.expose_secret(), &activation_hook_secret_context(guild, &product_id)).map(SecretString::new).map_err(secret_error)?;
+                        update_hook_secret.execute(named_params! {":guild": guild.get(), ":product": product_id, ":position": position, ":secret": signing_secret.expose_secret()})?;
+                        encrypted_count += 1;
+                    }
+                }
+
+                let discord_token: Option<SecretString> = transaction
+                    .query_row("SELECT value FROM settings WHERE key = :key", named_params! {":key": DISCORD_TOKEN_KEY}, |row| row.get(0).map(SecretString::new))
+                    .optional()?;
+                if let Some(discord_token) = discord_token.filter(|discord_token| !secret::is_encrypted(discord_token.expose_secret())) {
+                    let discord_token = secret::encrypt(discord_token.expose_secret(), DISCORD_TOKEN_SECRET_CONTEXT).map(SecretString::new).map_err(secret_error)?;
+                    transaction.execute("UPDATE settings SET value = :value WHERE key = :key", named_params! {":key": DISCORD_TOKEN_KEY, ":value": discord_token.expose_secret()})?;
+                    encrypted_count += 1;
+                }
+            }
+            transaction.commit()?;
+            Ok(encrypted_count)
+        })).await
+    }
+
+    /// link a Jinxxy product and a role
+    pub async fn link_product(
+        &self,
+        guild: GuildId,
+        product_id: String,
+        role: RoleId,
+    ) -> Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        let counted = self.timed("link_product", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT OR IGNORE INTO product_role (guild_id, product_id, role_id) VALUES (:guild, :product, :role)")?;
+            let insert_count = statement.execute(named_params! {":guild": guild.get(), ":product": product_id, ":role": role.get()})?;
+            if insert_count != 0 {
+                connection.prepare_cached(INSERT_EVENT_QUERY)?.execute(named_params! {":timestamp": timestamp, ":guild": guild.get(), ":kind": EventKind::Link.as_db_str(), ":user": None::<u64>, ":license": None::<String>, ":product": product_id, ":role": role.get()})?;
+            }
+            connection.prepare_cached(COMPLETE_ONBOARDING_STEP_QUERY)?.execute(named_params! {":guild": guild.get(), ":step": OnboardingStep::FirstLink.as_db_str(), ":timestamp": timestamp})?;
+            let counted = insert_count != 0 && connection.prepare_cached(PRODUCTION_GUILD_QUERY)?.query_row(named_params! {":guild": guild.get()}, |row| row.get(0))?;
+            Ok(counted)
+        })).await?;
+        if counted {
+            self.product_role_count.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// link several Jinxxy products to a role at once. Returns how many links were created, which excludes links that
+    /// already existed.
+    pub async fn link_products(
+        &self,
+        guild: GuildId,
+        product_ids: Vec<String>,
+        role: RoleId,
+    ) -> Result<u64> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        let (insert_count, counted) = self.timed("link_products", self.connection.call(move |connection| {
+            let transaction = connection.transaction()?;
+            let mut insert_count: u64 = 0;
+            {
+                let mut statement = transaction.prepare_cached("INSERT OR IGNORE INTO product_role (guild_id, product_id, role_id) VALUES (:guild, :product, :role)")?;
+                let mut event_statement = transaction.prepare_cached(INSERT_EVENT_QUERY)?;
+                for product_id in product_ids {
+                    let inserted = statement.execute(named_params! {":guild": guild.get(), ":product": product_id, ":role": role.get()})? != 0;
+                    if inserted {
+                        insert_count += 1;
+                        event_statement.execute(named_params! {":timestamp": timestamp, ":guild": guild.get(), ":kind": EventKind::Link.as_db_str(), ":user": None::<u64>, ":license": None::<String>, ":product": product_id, ":role": role.get()})?;
+                    }
+                }
+            }
+            if insert_count != 0 {
+                transaction.prepare_cached(COMPLETE_ONBOARDING_STEP_QUERY)?.execute(named_params! {":guild": guild.get(), ":step": OnboardingStep::FirstLink.as_db_str(), ":timestamp": timestamp})?;
+            }
+            let counted = insert_count != 0 && transaction.prepare_cached(PRODUCTION_GUILD_QUERY)?.query_row(named_params! {":guild": guild.get()}, |row| row.get(0))?;
+            transaction.commit()?;
+            Ok((insert_count, counted))
+        })).await?;
+        if counted {
+            self.product_role_count
+                .fetch_add(insert_count, Ordering::Relaxed);
+        }
+        Ok(insert_count)
+    }
+
+    /// unlink a Jinxxy product and a role. Returns `true` if a row was found and deleted, or `false` if no row was found to delete.
+    pub async fn unlink_product(
+        &self,
+        guild: GuildId,
+        product_id: String,
+        role: RoleId,
+    ) -> Result<bool> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        let (deleted, counted) = self.timed("unlink_product", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("DELETE FROM product_role WHERE guild_id = :guild AND product_id = :product AND role_id = :role")?;
+            let delete_count = statement.execute(named_params! {":guild": guild.get(), ":product": product_id, ":role": role.get()})?;
+            let deleted = delete_count != 0;
+            if deleted {
+                connection.prepare_cached(INSERT_EVENT_QUERY)?.execute(named_params! {":timestamp": timestamp, ":guild": guild.get(), ":kind": EventKind::Unlink.as_db_str(), ":user": None::<u64>, ":license": None::<String>, ":product": product_id, ":role": role.get()})?;
+            }
+            let counted = deleted && connection.prepare_cached(PRODUCTION_GUILD_QUERY)?.query_row(named_params! {":guild": guild.get()}, |row| row.get(0))?;
+            Ok((deleted, counted))
+        })).await?;
+        if counted {
+            decrement(&self.product_role_count);
+        }
+        Ok(deleted)
+    }
+
+    /// Get every role referenced by a link, version exclusion, auto-link rule, or blanket role, in guilds that haven't
+    /// been removed
+    pub async fn get_role_references(&self) -> Result<Vec<(GuildId, RoleId)>> {
+        self.timed("get_role_references", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT reference.guild_id, reference.role_id FROM ( \
+                    SELECT guild_id, role_id FROM product_role \
+                    UNION SELECT guild_id, role_id FROM product_version_exclusion \
+                    UNION SELECT guild_id, role_id FROM product_link_rule \
+                    UNION SELECT guild_id, blanket_role_id AS role_id FROM guild WHERE blanket_role_id IS NOT NULL \
+                ) AS reference LEFT JOIN guild USING (guild_id) WHERE guild.deleted_unix_ms IS NULL")?;
+            let result = statement.query_map((), |row| {
+                let guild_id: u64 = row.get(0)?;
+                let role_id: u64 = row.get(1)?;
+                Ok((GuildId::new(guild_id), RoleId::new(role_id)))
+            })?;
+            let mut vec = Vec::with_capacity(result.size_hint().0);
+            for row in result {
+                vec.push(row?);
+            }
+            Ok(vec)
+        })).await
+    }
+
+    /// Remove every reference to a role: its links, version exclusions, auto-link rules, and the blanket role if it's
+    /// this role. Returns the products that were unlinked from it, and whether it was the blanket role.
+    pub async fn remove_role_references(
+        &self,
+        guild: GuildId,
+        role: RoleId,
+    ) -> Result<(Vec<String>, bool)> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        let (product_ids, blanket_role_removed, counted) = self.timed("remove_role_references", self.connection.call(move |connection| {
+            let transaction = connection.transaction()?;
+            let mut product_ids = Vec::new();
+            let blanket_role_removed;
+            {
+                let mut statement = transaction.prepare_cached("DELETE FROM product_role WHERE guild_id = :guild AND role_id = :role RETURNING product_id")?;
+                let rows = statement.query_map(named_params! {":guild": guild.get(), ":role": role.get()}, |row| row.get::<_, String>(0))?;
+                for row in rows {
+                    product_ids.push(row?);
+                }
+                let mut statement = transaction.prepare_cached(INSERT_EVENT_QUERY)?;
+                for product_id in &product_ids {
+                    statement.execute(named_params! {":timestamp": timestamp, ":guild": guild.get(), ":kind": EventKind::Unlink.as_db_str(), ":user": None::<u64>, ":license": None::<String>, ":product": product_id, ":role": role.get()})?;
+                }
+                let mut statement = transaction.prepare_cached("DELETE FROM product_version_exclusion WHERE guild_id = :guild AND role_id = :role")?;
+                statement.execute(named_params! {":guild": guild.get(), ":role": role.get()})?;
+                let mut statement = transaction.prepare_cached("DELETE FROM product_link_rule WHERE guild_id = :guild AND role_id = :role")?;
+                statement.execute(named_params! {":guild": guild.get(), ":role": role.get()})?;
+                let mut statement = transaction.prepare_cached("UPDATE guild SET blanket_role_id = NULL WHERE guild_id = :guild AND blanket_role_id = :role")?;
+                blanket_role_removed = statement.execute(named_params! {":guild": guild.get(), ":role": role.get()})? != 0;
+            }
+            let counted = !product_ids.is_empty() && transaction.prepare_cached(PRODUCTION_GUILD_QUERY)?.query_row(named_params! {":guild": guild.get()}, |row| row.get(0))?;
+            transaction.commit()?;
+            Ok((product_ids, blanket_role_removed, counted))
+        })).await?;
+        if counted {
+            decrement_by(&self.product_role_count, product_ids.len() as u64);
+        }
+        Ok((product_ids, blanket_role_removed))
+    }
+
+    /// Get up to `limit` events from the event stream with a sequence number greater than `since`, oldest first
+    pub async fn get_events(&self, since: u64, limit: u64) -> Result<Vec<Event>> {
+        self.timed("get_events", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT sequence, created_unix_ms, guild_id, kind, user_id, license_id, product_id, role_id FROM event WHERE sequence > :since ORDER BY sequence LIMIT :limit")?;
+            let result = statement.query_and_then(named_params! {":since": since, ":limit": limit}, event_from_row)?;
+            let mut vec = Vec::with_capacity(result.size_hint().0);
+            for row in result {
+                if let Some(event) = row? {
+                    vec.push(event);
+                }
+            }
+            Ok(vec)
+        })).await
+    }
+
+    /// Get up to `limit` of a guild's activation and deactivation events with a sequence number after `since`, in order.
+    /// Lock and unlock events are left out, as they aren't a user registering or losing a license.
+    pub async fn get_guild_activation_events(
+        &self,
+        guild: GuildId,
+        since: u64,
+        limit: u64,
+    ) -> Result<Vec<Event>> {
+        self.timed("get_guild_activation_events", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT sequence, created_unix_ms, guild_id, kind, user_id, license_id, product_id, role_id FROM event \
+                WHERE guild_id = :guild AND sequence > :since AND kind IN (:activation, :deactivation) AND user_id != :locking_user ORDER BY sequence LIMIT :limit")?; // uses `event_guild_lookup` index
+            let result = statement.query_and_then(
+                named_params! {":guild": guild.get(), ":since": since, ":activation": EventKind::Activation.as_db_str(), ":deactivation": EventKind::Deactivation.as_db_str(), ":locking_user": LOCKING_USER_ID, ":limit": limit},
+                event_from_row,
+            )?;
+            let mut vec = Vec::with_capacity(result.size_hint().0);
+            for row in result {
+                if let Some(event) = row? {
+                    vec.push(event);
+                }
+            }
+            Ok(vec)
+        })).await
+    }
+
+    /// Set a guild's event webhook. Only events after this point are delivered to it.
+    pub async fn set_event_webhook(
+        &self,
+        guild: GuildId,
+        url: String,
+        signing_secret: SecretString,
+    ) -> Result<()> {
+        let signing_secret = secret::encrypt(
+            signing_secret.expose_secret(),
+            &event_webhook_secret_context(guild),
+        )
+        .map_err(secret_error)?;
+        self.timed("set_event_webhook", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT OR REPLACE INTO event_webhook (guild_id, url, signing_secret, cursor) VALUES (:guild, :url, :secret, (SELECT coalesce(max(sequence), 0) FROM event))")?;
+            statement.execute(named_params! {":guild": guild.get(), ":url": url, ":secret": signing_secret})?;
+            Ok(())
+        })).await
+    }
+
+    /// Replace a guild's event webhook signing secret. Returns `false` if the guild has no event webhook.
+    pub async fn set_event_webhook_secret(
+        &self,
+        guild: GuildId,
+        signing_secret: SecretString,
+    ) -> Result<bool> {
+        let signing_secret = secret::encrypt(
+            signing_secret.expose_secret(),
+            &event_webhook_secret_context(guild),
+        )
+        .map_err(secret_error)?;
+        self.timed(
+            "set_event_webhook_secret",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "UPDATE event_webhook SET signing_secret = :secret WHERE guild_id = :guild",
+                )?;
+                let change_count = statement
+                    .execute(named_params! {":guild": guild.get(), ":secret": signing_secret})?;
+                Ok(change_count != 0)
+            }),
+        )
+        .await
+    }
+
+    /// Remove a guild's event webhook. Returns `false` if it didn't have one.
+    pub async fn delete_event_webhook(&self, guild: GuildId) -> Result<bool> {
+        self.timed(
+            "delete_event_webhook",
+            self.connection.call(move |connection| {
+                let mut statement = connection
+                    .prepare_cached("DELETE FROM event_webhook WHERE guild_id = :guild")?;
+                let change_count = statement.execute(named_params! {":guild": guild.get()})?;
+                Ok(change_count != 0)
+            }),
+        )
+        .await
+    }
+
+    /// Get every event webhook that isn't waiting to retry a failed delivery
+    pub async fn get_due_event_webhooks(&self) -> Result<Vec<EventWebhook>> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        let webhooks = self.timed("get_due_event_webhooks", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT guild_id, url, signing_secret, cursor, failure_count FROM event_webhook WHERE retry_unix_ms <= :timestamp")?;
+            let result = statement.query_map(named_params! {":timestamp": timestamp}, |row| {
+                let guild_id: u64 = row.get(0)?;
+                let signing_secret: String = row.get(2)?;
+                Ok(EventWebhook {
+                    guild_id: GuildId::new(guild_id),
+                    url: row.get(1)?,
+                    signing_secret: SecretString::new(signing_secret),
+                    cursor: row.get(3)?,
+                    failure_count: row.get(4)?,
+                })
+            })?;
+            let mut vec = Vec::with_capacity(result.size_hint().0);
+            for row in result {
+                vec.push(row?);
+            }
+            Ok(vec)
+        })).await?;
+        webhooks
+            .into_iter()
+            .map(|webhook| {
+                let signing_secret = secret::decrypt(
+                    webhook.signing_secret.expose_secret(),
+                    &event_webhook_secret_context(webhook.guild_id),
+                )
+                .map_err(secret_error)?;
+                Ok(EventWebhook {
+                    signing_secret,
+                    ..webhook
+                })
+            })
+            .collect()
+    }
+
+    /// Record that a guild's event webhook is done with every event up to and including `cursor`, clearing any failures
+    pub async fn advance_event_webhook(&self, guild: GuildId, cursor: u64) -> Result<()> {
+        self.timed("advance_event_webhook", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("UPDATE event_webhook SET cursor = :cursor, failure_count = 0, retry_unix_ms = 0 WHERE guild_id = :guild")?;
+            statement.execute(named_params! {":guild": guild.get(), ":cursor": cursor})?;
+            Ok(())
+        })).await
+    }
+
+    /// Record a failed event webhook delivery, which will be retried after `retry_after`
+    pub async fn record_event_webhook_failure(
+        &self,
+        guild: GuildId,
+        retry_after: Duration,
+    ) -> Result<()> {
+        let retry_unix_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0)
+            + retry_after.as_millis() as u64;
+        self.timed("record_event_webhook_failure", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("UPDATE event_webhook SET failure_count = failure_count + 1, retry_unix_ms = :retry WHERE guild_id = :guild")?;
+            statement.execute(named_params! {":guild": guild.get(), ":retry": retry_unix_ms})?;
+            Ok(())
+        })).await
+    }
+
+    /// Get a guild's registration post template. Guilds that never customized their posts get the default template.
+    pub async fn get_post_template(&self, guild: GuildId) -> Result<PostTemplate> {
+        self.timed("get_post_template", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT title, description, button_label, color, product_id FROM post_template WHERE guild_id = :guild")?;
+            let template = statement.query_row(named_params! {":guild": guild.get()}, |row| {
+                Ok(PostTemplate {
+                    title: row.get(0)?,
+                    description: row.get(1)?,
+                    button_label: row.get(2)?,
+                    color: row.get(3)?,
+                    product_id: row.get(4)?,
+                })
+            }).optional()?;
+            Ok(template.unwrap_or_default())
+        })).await
+    }
+
+    /// Save a guild's registration post template
+    pub async fn set_post_template(&self, guild: GuildId, template: PostTemplate) -> Result<()> {
+        self.timed("set_post_template", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT OR REPLACE INTO post_template (guild_id, title, description, button_label, color, product_id) VALUES (:guild, :title, :description, :button_label, :color, :product)")?;
+            statement.execute(named_params! {
+                ":guild": guild.get(),
+                ":title": template.title,
+                ":description": template.description,
+                ":button_label": template.button_label,
+                ":color": template.color,
+                ":product": template.product_id,
+            })?;
+            Ok(())
+        })).await
+    }
+
+    /// Remember a registration post so `/update_post` can find it later
+    pub async fn add_registration_post(
+        &self,
+        guild: GuildId,
+        channel: ChannelId,
+        message: MessageId,
+    ) -> Result<()> {
+        self.timed("add_registration_post", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT OR IGNORE INTO registration_post (guild_id, message_id, channel_id) VALUES (:guild, :message, :channel)")?;
+            statement.execute(named_params! {":guild": guild.get(), ":message": message.get(), ":channel": channel.get()})?;
+            Ok(())
+        })).await
+    }
+
+    /// Get a guild's registration posts as (channel, message) pairs, oldest first
+    pub async fn get_registration_posts(
+        &self,
+        guild: GuildId,
+    ) -> Result<Vec<(ChannelId, MessageId)>> {
+        self.timed(
+            "get_registration_posts",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached("SELECT channel_id, message_id FROM registration_post WHERE guild_id = :guild ORDER BY message_id")?; // uses primary key index
+                let rows = statement.query_map(named_params! {":guild": guild.get()}, |row| {
+                    Ok((ChannelId::new(row.get(0)?), MessageId::new(row.get(1)?)))
+                })?;
+                let mut vec = Vec::new();
+                for row in rows {
+                    vec.push(row?);
+                }
+                Ok(vec)
+            }),
+        )
+        .await
+    }
+
+    /// Forget a registration post, such as one that was deleted
+    pub async fn delete_registration_post(&self, guild: GuildId, message: MessageId) -> Result<()> {
+        self.timed("delete_registration_post", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("DELETE FROM registration_post WHERE guild_id = :guild AND message_id = :message")?;
+            statement.execute(named_params! {":guild": guild.get(), ":message": message.get()})?;
+            Ok(())
+        })).await
+    }
+
+    /// Record when a registered subscription license expires, replacing any expiry recorded for it before
+    pub async fn set_license_expiry(
+        &self,
+        guild: GuildId,
+        license_id: String,
+        product_id: String,
+        product_version_id: Option<String>,
+        expires_unix_ms: u64,
+    ) -> Result<()> {
+        self.timed("set_license_expiry", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT OR REPLACE INTO license_expiry (guild_id, license_id, product_id, product_version_id, expires_unix_ms) VALUES (:guild, :license, :product, :version, :expires)")?;
+            statement.execute(named_params! {
+                ":guild": guild.get(),
+                ":license": license_id,
+                ":product": product_id,
+                ":version": product_version_id,
+                ":expires": expires_unix_ms,
+            })?;
+            Ok(())
+        })).await
+    }
+
+    /// Stop tracking a license's expiry
+    pub async fn delete_license_expiry(&self, guild: GuildId, license_id: String) -> Result<()> {
+        self.timed(
+            "delete_license_expiry",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "DELETE FROM license_expiry WHERE guild_id = :guild AND license_id = :license",
+                )?;
+                statement.execute(named_params! {":guild": guild.get(), ":license": license_id})?;
+                Ok(())
+            }),
+        )
+        .await
+    }
+
+    /// Get subscription licenses expiring before the given time, soonest first. Guilds marked for deletion are skipped.
+    pub async fn get_expiring_licenses(&self, before_unix_ms: u64) -> Result<Vec<ExpiringLicense>> {
+        self.timed("get_expiring_licenses", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT guild_id, license_id, product_id, product_version_id, expires_unix_ms FROM license_expiry LEFT JOIN guild USING (guild_id) \
+                WHERE expires_unix_ms < :before AND deleted_unix_ms IS NULL ORDER BY expires_unix_ms")?; // uses `license_expiry_lookup` index
+            let rows = statement.query_map(named_params! {":before": before_unix_ms}, |row| {
+                Ok(ExpiringLicense {
+                    guild_id: GuildId::new(row.get(0)?),
+                    license_id: row.get(1)?,
+                    product_id: row.get(2)?,
+                    product_version_id: row.get(3)?,
+                    expires_unix_ms: row.get(4)?,
+                })
+            })?;
+            let mut vec = Vec::new();
+            for row in rows {
+                vec.push(row?);
+            }
+            Ok(vec)
+        })).await
+    }
+
+    /// Schedule the roles from a user's license activation to be removed. If they were already scheduled the original
+    /// time is kept, so registering the license again doesn't extend it.
+    pub async fn schedule_expiry(&self, expiry: ScheduledExpiry) -> Result<()> {
+        self.timed("schedule_expiry", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT OR IGNORE INTO scheduled_expiry (guild_id, user_id, license_id, product_id, product_version_id, expires_unix_ms) VALUES (:guild, :user, :license, :product, :version, :expires)")?;
+            statement.execute(named_params! {
+                ":guild": expiry.guild_id.get(),
+                ":user": expiry.user_id.get(),
+                ":license": expiry.license_id,
+                ":product": expiry.product_id,
+                ":version": expiry.product_version_id,
+                ":expires": expiry.expires_unix_ms,
+            })?;
+            Ok(())
+        })).await
+    }
+
+    /// Get scheduled expiries due before the given time whose roles haven't been revoked yet, soonest first. Guilds marked
+    /// for deletion are skipped.
+    pub async fn get_due_scheduled_expiries(
+        &self,
+        before_unix_ms: u64,
+    ) -> Result<Vec<ScheduledExpiry>> {
+        self.timed("get_due_scheduled_expiries", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT guild_id, user_id, license_id, product_id, product_version_id, expires_unix_ms FROM scheduled_expiry LEFT JOIN guild USING (guild_id) \
+                WHERE NOT revoked AND expires_unix_ms < :before AND deleted_unix_ms IS NULL ORDER BY expires_unix_ms")?; // uses `scheduled_expiry_lookup` index
+            let rows = statement.query_map(named_params! {":before": before_unix_ms}, |row| {
+                Ok(ScheduledExpiry {
+                    guild_id: GuildId::new(row.get(0)?),
+                    user_id: UserId::new(row.get(1)?),
+                    license_id: row.get(2)?,
+                    product_id: row.get(3)?,
+                    product_version_id: row.get(4)?,
+                    expires_unix_ms: row.get(5)?,
+                })
+            })?;
+            let mut vec = Vec::new();
+            for row in rows {
+                vec.push(row?);
+            }
+            Ok(vec)
+        })).await
+    }
+
+    /// Record that a scheduled expiry's roles were revoked
+    pub async fn set_scheduled_expiry_revoked(
+        &self,
+        guild: GuildId,
+        user_id: UserId,
+        license_id: String,
+    ) -> Result<()> {
+        self.timed("set_scheduled_expiry_revoked", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("UPDATE scheduled_expiry SET revoked = 1 WHERE guild_id = :guild AND user_id = :user AND license_id = :license")?;
+            statement.execute(named_params! {":guild": guild.get(), ":user": user_id.get(), ":license": license_id})?;
+            Ok(())
+        })).await
+    }
+
+    /// Get the user's licenses whose scheduled expiry has passed, whether or not their roles were revoked yet
+    pub async fn get_expired_licenses(
+        &self,
+        guild: GuildId,
+        user_id: UserId,
+        now_unix_ms: u64,
+    ) -> Result<Vec<String>> {
+        self.timed("get_expired_licenses", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT license_id FROM scheduled_expiry WHERE guild_id = :guild AND user_id = :user AND expires_unix_ms <= :now")?;
+            let rows = statement.query_map(named_params! {":guild": guild.get(), ":user": user_id.get(), ":now": now_unix_ms}, |row| row.get(0))?;
+            let mut vec = Vec::new();
+            for row in rows {
+                vec.push(row?);
+            }
+            Ok(vec)
+        })).await
+    }
+
+    /// Get roles for a product ID
+    pub async fn get_roles(&self, guild: GuildId, product_id: String) -> Result<Vec<RoleId>> {
+        self.timed(
+            "get_roles",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached("SELECT role_id FROM product_role WHERE guild_id = :guild AND product_id = :product")?; // uses `role_lookup` index
+                let result = statement.query_map(
+                    named_params! {":guild": guild.get(), ":product": product_id},
+                    |row| {
+                        let role_id: u64 = row.get(0)?;
+                        Ok(RoleId::new(role_id))
+                    },
+                )?;
+                let mut vec = Vec::with_capacity(result.size_hint().0);
+                for row in result {
+                    vec.push(row?);
+                }
+                Ok(vec)
+            }),
+        )
+        .await
+    }
+
+    /// Get the roles a specific product version should grant. This is all roles linked to the product, minus any
+    /// roles the version has been excluded from.
+    pub async fn get_role_grants(
+        &self,
+        guild: GuildId,
+        product_id: String,
+        product_version_id: Option<String>,
+    ) -> Result<Vec<RoleId>> {
+        self.timed(
+            "get_role_grants",
+            self.connection.call(move |connection| {
+                // the blanket role is granted for every product, but version exclusions still apply to it
+                let mut statement = connection.prepare_cached("SELECT role_id FROM ( \
+                        SELECT role_id FROM product_role WHERE guild_id = :guild AND product_id = :product \
+                        UNION SELECT blanket_role_id AS role_id FROM guild WHERE guild_id = :guild AND blanket_role_id IS NOT NULL \
+                    ) WHERE role_id NOT IN (SELECT role_id FROM product_version_exclusion WHERE guild_id = :guild AND product_id = :product AND product_version_id = :version)")?;
+                let result = statement.query_map(
+                    named_params! {":guild": guild.get(), ":product": product_id, ":version": product_version_id},
+                    |row| {
+                        let role_id: u64 = row.get(0)?;
+                        Ok(RoleId::new(role_id))
+                    },
+                )?;
+                let mut vec = Vec::with_capacity(result.size_hint().0);
+                for row in result {
+                    vec.push(row?);
+                }
+                Ok(vec)
+            }),
+        )
+        .await
+    }
+
+    /// Exclude a product version from granting a role, even if the product is linked to that role
+    pub async fn exclude_product_version(
+        &self,
+        guild: GuildId,
+        product_id: String,
+        product_version_id: String,
+        role: RoleId,
+    ) -> Result<()> {
+        self.timed("exclude_product_version", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT OR IGNORE INTO product_version_exclusion (guild_id, product_id, product_version_id, role_id) VALUES (:guild, :product, :version, :role)")?;
+            statement.execute(named_params! {":guild": guild.get(), ":product": product_id, ":version": product_version_id, ":role": role.get()})?;
+            Ok(())
+        })).await
+    }
+
+    /// Remove a product version exclusion. Returns `true` if a row was found and deleted, or `false` if no row was found to delete.
+    pub async fn include_product_version(
+        &self,
+        guild: GuildId,
+        product_id: String,
+        product_version_id: String,
+        role: RoleId,
+    ) -> Result<bool> {
+        self.timed("include_product_version", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("DELETE FROM product_version_exclusion WHERE guild_id = :guild AND product_id = :product AND product_version_id = :version AND role_id = :role")?;
+            let delete_count = statement.execute(named_params! {":guild": guild.get(), ":product": product_id, ":version": product_version_id, ":role": role.get()})?;
+            Ok(delete_count != 0)
+        })).await
+    }
+
+    /// Get how many distinct users may activate a single license for this product. Defaults to 1.
+    pub async fn get_max_activations(&self, guild: GuildId, product_id: String) -> Result<u32> {
+        self.timed("get_max_activations", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT max_activations FROM product_seat_limit WHERE guild_id = :guild AND product_id = :product")?;
+            let result: Option<u32> = statement.query_row(named_params! {":guild": guild.get(), ":product": product_id}, |row| row.get(0)).optional()?;
+            Ok(result.unwrap_or(DEFAULT_MAX_ACTIVATIONS))
+        })).await
+    }
+
+    /// Set how many distinct users may activate a single license for this product, or reset it to the default.
+    pub async fn set_max_activations(
+        &self,
+        guild: GuildId,
+        product_id: String,
+        max_activations: Option<u32>,
+    ) -> Result<()> {
+        self.timed("set_max_activations", self.connection.call(move |connection| {
+            if let Some(max_activations) = max_activations {
+                let mut statement = connection.prepare_cached("INSERT OR REPLACE INTO product_seat_limit (guild_id, product_id, max_activations) VALUES (:guild, :product, :max)")?;
+                statement.execute(named_params! {":guild": guild.get(), ":product": product_id, ":max": max_activations})?;
+            } else {
+                let mut statement = connection.prepare_cached("DELETE FROM product_seat_limit WHERE guild_id = :guild AND product_id = :product")?;
+                statement.execute(named_params! {":guild": guild.get(), ":product": product_id})?;
+            }
+            Ok(())
+        })).await
+    }
+
+    /// Get the hooks to run after a license for this product is activated, in the order they run
+    pub async fn get_activation_hooks(
+        &self,
+        guild: GuildId,
+        product_id: String,
+    ) -> Result<Vec<ActivationHook>> {
+        let secret_context = activation_hook_secret_context(guild, &product_id);
+        let hooks = self.timed("get_activation_hooks", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT position, kind, target, signing_secret FROM activation_hook WHERE guild_id = :guild AND product_id = :product ORDER BY position")?;
+            let hooks = statement.query_map(named_params! {":guild": guild.get(), ":product": product_id}, |row| {
+                let kind: String = row.get(1)?;
+                let signing_secret: Option<String> = row.get(3)?;
+                Ok((row.get(0)?, kind, row.get(2)?, signing_secret.map(SecretString::new)))
+            })?;
+            let mut result = Vec::new();
+            for hook in hooks {
+                let (position, kind, target, signing_secret) = hook?;
+                // skip kinds this version doesn't know about, rather than failing the whole activation
+                if let Some(kind) = ActivationHookKind::from_db_str(&kind) {
+                    result.push(ActivationHook { position, kind, target, signing_secret });
+                }
+            }
+            Ok(result)
+        })).await?;
+        hooks
+            .into_iter()
+            .map(|hook| {
+                let signing_secret = hook
+                    .signing_secret
+                    .map(|signing_secret| {
+                        secret::decrypt(signing_secret.expose_secret(), &secret_context)
+                    })
+                    .transpose()
+                    .map_err(secret_error)?;
+                Ok(ActivationHook {
+                    signing_secret,
+                    ..hook
+                })
+            })
+            .collect()
+    }
+
+    /// Add a hook to run after all existing hooks for this product. Returns the new hook's position.
+    pub async fn add_activation_hook(
+        &self,
+        guild: GuildId,
+        product_id: String,
+        kind: ActivationHookKind,
+        target: String,
+        signing_secret: Option<SecretString>,
+    ) -> Result<u64> {
+        let signing_secret = signing_secret
+            .map(|signing_secret| {
+                secret::encrypt(
+                    signing_secret.expose_secret(),
+                    &activation_hook_secret_context(guild, &product_id),
+                )
+            })
+            .transpose()
+            .map_err(secret_error)?;
+        self.timed("add_activation_hook", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO activation_hook (guild_id, product_id, position, kind, target, signing_secret) \
+                SELECT :guild, :product, COALESCE(MAX(position), 0) + 1, :kind, :target, :secret FROM activation_hook WHERE guild_id = :guild AND product_id = :product \
+                RETURNING position")?;
+            let position = statement.query_row(named_params! {":guild": guild.get(), ":product": product_id, ":kind": kind.as_db_str(), ":target": target, ":secret": signing_secret}, |row| row.get(0))?;
+            Ok(position)
+        })).await
+    }
+
+    /// Remove a product's hook. Returns `true` if a hook was removed.
+    pub async fn remove_activation_hook(
+        &self,
+        guild: GuildId,
+        product_id: String,
+        position: u64,
+    ) -> Result<bool> {
+        self.timed("remove_activation_hook", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("DELETE FROM activation_hook WHERE guild_id = :guild AND product_id = :product AND position = :position")?;
+            let deleted = statement.execute(named_params! {":guild": guild.get(), ":product": product_id, ":position": position})?;
+            Ok(deleted != 0)
+        })).await
+    }
+
+    /// Get a guild's product auto-link rules as (pattern, role) pairs
+    pub async fn get_link_rules(&self, guild: GuildId) -> Result<Vec<(String, RoleId)>> {
+        self.timed("get_link_rules", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT pattern, role_id FROM product_link_rule WHERE guild_id = :guild ORDER BY pattern, role_id")?;
+            let rules = statement.query_map(named_params! {":guild": guild.get()}, |row| {
+                Ok((row.get(0)?, RoleId::new(row.get(1)?)))
+            })?;
+            let mut result = Vec::new();
+            for rule in rules {
+                result.push(rule?);
+            }
+            Ok(result)
+        })).await
+    }
+
+    /// Add a product auto-link rule. Returns `false` if the rule already existed.
+    pub async fn add_link_rule(
+        &self,
+        guild: GuildId,
+        pattern: String,
+        role: RoleId,
+    ) -> Result<bool> {
+        self.timed("add_link_rule", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT OR IGNORE INTO product_link_rule (guild_id, pattern, role_id) VALUES (:guild, :pattern, :role)")?;
+            let insert_count = statement.execute(named_params! {":guild": guild.get(), ":pattern": pattern, ":role": role.get()})?;
+            Ok(insert_count != 0)
+        })).await
+    }
+
+    /// Remove a product auto-link rule. Links it already created are kept. Returns `true` if a rule was removed.
+    pub async fn remove_link_rule(
+        &self,
+        guild: GuildId,
+        pattern: String,
+        role: RoleId,
+    ) -> Result<bool> {
+        self.timed("remove_link_rule", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("DELETE FROM product_link_rule WHERE guild_id = :guild AND pattern = :pattern AND role_id = :role")?;
+            let delete_count = statement.execute(named_params! {":guild": guild.get(), ":pattern": pattern, ":role": role.get()})?;
+            Ok(delete_count != 0)
+        })).await
+    }
+
+    /// Count a new activation of a product, and log when it happened. Returns how many times the product has been
+    /// activated in this guild.
+    pub async fn increment_product_activation_count(
+        &self,
+        guild: GuildId,
+        product_id: String,
+    ) -> Result<u64> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        self.timed("increment_product_activation_count", self.connection.call(move |connection| {
+            let transaction = connection.transaction()?;
+            let count = {
+                let mut log = transaction.prepare_cached("INSERT INTO product_activation_log (guild_id, product_id, timestamp_unix_ms) VALUES (:guild, :product, :timestamp)")?;
+                log.execute(named_params! {":guild": guild.get(), ":product": &product_id, ":timestamp": timestamp})?;
+                let mut count = transaction.prepare_cached("INSERT INTO product_activation_count (guild_id, product_id, activation_count) VALUES (:guild, :product, 1) \
+                    ON CONFLICT (guild_id, product_id) DO UPDATE SET activation_count = activation_count + 1 \
+                    RETURNING activation_count")?;
+                count.query_row(named_params! {":guild": guild.get(), ":product": &product_id}, |row| row.get(0))?
+            };
+            transaction.commit()?;
+            Ok(count)
+        })).await
+    }
+
+    /// Count activations in this guild for each UTC day over the last `months` months, oldest first. Every day in the
+    /// range is included, even if it had no activations. Days are formatted as `YYYY-MM-DD`.
+    pub async fn get_daily_activations(
+        &self,
+        guild: GuildId,
+        months: u32,
+    ) -> Result<Vec<(String, u64)>> {
+        self.timed("get_daily_activations", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("WITH RECURSIVE \
+                bounds (first_day, last_day) AS (SELECT CAST(strftime('%s', 'now', :offset) AS INTEGER) / 86400, CAST(strftime('%s', 'now') AS INTEGER) / 86400), \
+                day (day) AS (SELECT first_day FROM bounds UNION ALL SELECT day + 1 FROM day, bounds WHERE day < last_day), \
+                daily_count (day, activation_count) AS (SELECT timestamp_unix_ms / 86400000, COUNT(*) FROM product_activation_log, bounds \
+                    WHERE guild_id = :guild AND timestamp_unix_ms >= first_day * 86400000 GROUP BY 1) \
+                SELECT date(day * 86400, 'unixepoch'), COALESCE(activation_count, 0) FROM day LEFT JOIN daily_count USING (day) ORDER BY day")?;
+            let rows = statement.query_map(named_params! {":guild": guild.get(), ":offset": format!("-{months} months")}, |row| Ok((row.get(0)?, row.get(1)?)))?;
+            let mut result = Vec::new();
+            for row in rows {
+                result.push(row?);
+            }
+            Ok(result)
+        })).await
+    }
+
+    /// Get the most activated products in this guild, most activated first. If `since_unix_ms` is given, only
+    /// activations since then are counted. Returns each product's ID, name if it is known, and activation count.
+    pub async fn get_top_products(
+        &self,
+        guild: GuildId,
+        since_unix_ms: Option<u64>,
+        limit: u64,
+    ) -> Result<Vec<(String, Option<String>, u64)>> {
+        self.timed("get_top_products", self.connection.call(move |connection| {
+            let mut statement = if since_unix_ms.is_some() {
+                connection.prepare_cached("SELECT product_id, product_name, COUNT(*) AS activation_count FROM product_activation_log \
+                    LEFT JOIN product USING (guild_id, product_id) WHERE guild_id = :guild AND timestamp_unix_ms >= :since \
+                    GROUP BY product_id ORDER BY activation_count DESC, product_name LIMIT :limit")
+            } else {
+                // the counters go back further than the log, so prefer them when there's no window
+                connection.prepare_cached("SELECT product_id, product_name, activation_count FROM product_activation_count \
+                    LEFT JOIN product USING (guild_id, product_id) WHERE guild_id = :guild \
+                    ORDER BY activation_count DESC, product_name LIMIT :limit")
+            }?;
+            let map_row = |row: &tokio_rusqlite::Row| Ok((row.get(0)?, row.get(1)?, row.get(2)?));
+            let rows = match since_unix_ms {
+                Some(since_unix_ms) => statement.query_map(named_params! {":guild": guild.get(), ":since": since_unix_ms, ":limit": limit}, map_row)?,
+                None => statement.query_map(named_params! {":guild": guild.get(), ":limit": limit}, map_row)?,
+            };
+            let mut result = Vec::new();
+            for row in rows {
+                result.push(row?);
+            }
+            Ok(result)
+        })).await
+    }
+
+    /// Get how many times each product has been activated in this guild, along with the product's name if it is known
+    pub async fn get_product_activation_counts(
+        &self,
+        guild: GuildId,
+    ) -> Result<Vec<(String, Option<String>, u64)>> {
+        self.timed("get_product_activation_counts", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT product_id, product_name, activation_count FROM product_activation_count \
+                LEFT JOIN product USING (guild_id, product_id) WHERE guild_id = :guild")?;
+            let rows = statement.query_map(named_params! {":guild": guild.get()}, |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+            let mut result = Vec::new();
+            for row in rows {
+                result.push(row?);
+            }
+            Ok(result)
+        })).await
+    }
+
+    /// Record that a milestone was celebrated, either for a product or guild-wide. Returns `false` if this or a higher
+    /// milestone was already celebrated, in which case it shouldn't be celebrated again.
+    pub async fn celebrate_milestone(
+        &self,
+        guild: GuildId,
+        product_id: Option<String>,
+        milestone: u64,
+    ) -> Result<bool> {
+        self.timed("celebrate_milestone", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO celebrated_milestone (guild_id, product_id, milestone) VALUES (:guild, :product, :milestone) \
+                ON CONFLICT (guild_id, product_id) DO UPDATE SET milestone = excluded.milestone WHERE excluded.milestone > milestone")?;
+            let changed = statement.execute(named_params! {":guild": guild.get(), ":product": product_id.unwrap_or_default(), ":milestone": milestone})?;
+            Ok(changed != 0)
+        })).await
+    }
+
+    /// Record an event in the audit log
+    pub async fn audit(&self, guild: GuildId, entry: AuditLogEntry) -> Result<()> {
+        self.timed("audit", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO audit_log (guild_id, timestamp_unix_ms, actor_id, action, user_id, product_id, role_id, license_id, detail) \
+                VALUES (:guild, :timestamp, :actor, :action, :user, :product, :role, :license, :detail)")?;
+            statement.execute(named_params! {
+                ":guild": guild.get(),
+                ":timestamp": entry.timestamp_unix_ms,
+                ":actor": entry.actor.map(UserId::get),
+                ":action": entry.action.as_db_str(),
+                ":user": entry.user.map(UserId::get),
+                ":product": entry.product_id,
+                ":role": entry.role.map(RoleId::get),
+                ":license": entry.license_id,
+                ":detail": entry.detail,
+            })?;
+            Ok(())
+        })).await
+    }
+
+    /// Query the audit log, newest first. Returns one page of entries along with the total number of matching entries.
+    pub async fn get_audit_log(
+        &self,
+        guild: GuildId,
+        filter: AuditLogFilter,
+        offset: u64,
+        limit: u64,
+    ) -> Result<(Vec<AuditLogEntry>, u64)> {
+        self.timed("get_audit_log", self.connection.call(move |connection| {
+            const WHERE_CLAUSE: &str = "WHERE guild_id = :guild \
+                AND (:user IS NULL OR actor_id = :user OR user_id = :user) \
+                AND (:product IS NULL OR product_id = :product) \
+                AND (:action IS NULL OR action = :action) \
+                AND (:license IS NULL OR license_id = :license) \
+                AND (:since IS NULL OR timestamp_unix_ms >= :since)";
+            let user = filter.user.map(UserId::get);
+            let action = filter.action.map(AuditAction::as_db_str);
+
+            let mut statement = connection.prepare_cached(format!("SELECT count(*) FROM audit_log {WHERE_CLAUSE}").as_str())?;
+            let total: u64 = statement.query_row(named_params! {":guild": guild.get(), ":user": user, ":product": filter.product_id, ":action": action, ":license": filter.license_id, ":since": filter.since_unix_ms}, |row| row.get(0))?;
+
+            let mut statement = connection.prepare_cached(format!("SELECT timestamp_unix_ms, actor_id, action, user_id, product_id, role_id, license_id, detail FROM audit_log {WHERE_CLAUSE} \
+                ORDER BY timestamp_unix_ms DESC, audit_log_id DESC LIMIT :limit OFFSET :offset").as_str())?;
+            let result = statement.query_map(named_params! {":guild": guild.get(), ":user": user, ":product": filter.product_id, ":action": action, ":license": filter.license_id, ":since": filter.since_unix_ms, ":limit": limit, ":offset": offset}, |row| {
+                let action: String = row.get(2)?;
+                // unknown actions can only come from a newer version of jinx, so we skip them
+                let Some(action) = AuditAction::from_db_str(&action) else {
+                    return Ok(None);
+                };
+                Ok(Some(AuditLogEntry {
+                    timestamp_unix_ms: row.get(0)?,
+                    actor: row.get::<_, Option<u64>>(1)?.map(UserId::new),
+                    action,
+                    user: row.get::<_, Option<u64>>(3)?.map(UserId::new),
+                    product_id: row.get(4)?,
+                    role: row.get::<_, Option<u64>>(5)?.map(RoleId::new),
+                    license_id: row.get(6)?,
+                    detail: row.get(7)?,
+                }))
+            })?;
+            let mut vec = Vec::with_capacity(result.size_hint().0);
+            for row in result {
+                if let Some(entry) = row? {
+                    vec.push(entry);
+                }
+            }
+            Ok((vec, total))
+        })).await
+    }
+
+    /// Persist a batch of Jinxxy API request samples
+    pub async fn record_api_samples(&self, samples: Vec<ApiSample>) -> Result<()> {
+        self.timed("record_api_samples", self.connection.call(move |connection| {
+            let transaction = connection.transaction()?;
+            {
+                let mut statement = transaction.prepare_cached("INSERT INTO api_request_log (timestamp_unix_ms, endpoint, latency_ms, success) VALUES (:timestamp, :endpoint, :latency, :success)")?;
+                for sample in samples {
+                    statement.execute(named_params! {":timestamp": sample.timestamp_unix_ms, ":endpoint": sample.endpoint, ":latency": sample.latency_ms, ":success": sample.success})?;
+                }
+            }
+            transaction.commit()?;
+            Ok(())
+        })).await
+    }
+
+    /// Get per-endpoint Jinxxy API health for all requests since the given time, sorted by endpoint
+    pub async fn get_api_health(&self, since_unix_ms: u64) -> Result<Vec<EndpointHealth>> {
+        self.timed("get_api_health", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT endpoint, latency_ms, success FROM api_request_log WHERE timestamp_unix_ms >= :since ORDER BY endpoint, latency_ms")?;
+            let mut rows = statement.query(named_params! {":since": since_unix_ms})?;
+            let mut result = Vec::new();
+            let mut current_endpoint: Option<String> = None;
+            let mut latencies_ms: Vec<u64> = Vec::new();
+            let mut error_count: u64 = 0;
+            while let Some(row) = rows.next()? {
+                let endpoint: String = row.get(0)?;
+                if current_endpoint.as_ref() != Some(&endpoint) {
+                    if let Some(previous_endpoint) = current_endpoint.replace(endpoint) {
+                        result.push(EndpointHealth::from_sorted_latencies(previous_endpoint, &latencies_ms, error_count));
+                    }
+                    latencies_ms.clear();
+                    error_count = 0;
+                }
+                latencies_ms.push(row.get(1)?);
+                if !row.get::<_, bool>(2)? {
+                    error_count += 1;
+                }
+            }
+            if let Some(endpoint) = current_endpoint {
+                result.push(EndpointHealth::from_sorted_latencies(endpoint, &latencies_ms, error_count));
+            }
+            Ok(result)
+        })).await
+    }
+
+    /// Record aggregates for a UTC day, given as days since the unix epoch, unless it's already been recorded. Returns
+    /// `true` if it was recorded now. The guild count is taken as of now, so this should run soon after the day ends.
+    pub async fn record_daily_metrics(&self, day: u64) -> Result<bool> {
+        self.timed("record_daily_metrics", self.connection.call(move |connection| {
+            let transaction = connection.transaction()?;
+            let recorded = {
+                let mut existing = transaction.prepare_cached("SELECT 1 FROM daily_metrics WHERE day = :day")?;
+                if existing.exists(named_params! {":day": day})? {
+                    false
+                } else {
+                    let start_unix_ms = day * MS_PER_DAY;
+                    let end_unix_ms = start_unix_ms + MS_PER_DAY;
+                    let activation_count: u64 = transaction.prepare_cached("SELECT COUNT(*) FROM product_activation_log LEFT JOIN guild USING (guild_id) \
+                        WHERE timestamp_unix_ms >= :start AND timestamp_unix_ms < :end AND guild.test = 0 AND guild.stats_opt_out = 0")?
+                        .query_row(named_params! {":start": start_unix_ms, ":end": end_unix_ms}, |row| row.get(0))?;
+                    let mut samples = transaction.prepare_cached("SELECT latency_ms, success FROM api_request_log WHERE timestamp_unix_ms >= :start AND timestamp_unix_ms < :end ORDER BY latency_ms")?;
+                    let rows = samples.query_map(named_params! {":start": start_unix_ms, ":end": end_unix_ms}, |row| Ok((row.get::<_, u64>(0)?, row.get::<_, bool>(1)?)))?;
+                    let mut latencies_ms = Vec::new();
+                    let mut api_error_count: u64 = 0;
+                    for row in rows {
+                        let (latency_ms, success) = row?;
+                        latencies_ms.push(latency_ms);
+                        if !success {
+                            api_error_count += 1;
+                        }
+                    }
+                    let api_latency_p95_ms = percentile(&latencies_ms, 95).as_millis() as u64;
+                    let guild_count: u64 = transaction.prepare_cached(GUILD_COUNT_QUERY)?.query_row([], |row| row.get(0))?;
+                    transaction.prepare_cached("INSERT INTO daily_metrics (day, activation_count, api_request_count, api_error_count, api_latency_p95_ms, guild_count) \
+                        VALUES (:day, :activations, :requests, :errors, :p95, :guilds)")?
+                        .execute(named_params! {
+                            ":day": day,
+                            ":activations": activation_count,
+                            ":requests": latencies_ms.len() as u64,
+                            ":errors": api_error_count,
+                            ":p95": api_latency_p95_ms,
+                            ":guilds": guild_count,
+                        })?;
+                    true
+                }
+            };
+            transaction.commit()?;
+            Ok(recorded)
+        })).await
+    }
+
+    /// Get recorded daily metrics for the last `days` days, oldest first. Days that were never recorded are skipped.
+    pub async fn get_daily_metrics(&self, days: u64) -> Result<Vec<DailyMetrics>> {
+        self.timed("get_daily_metrics", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT date(day * 86400, 'unixepoch'), activation_count, api_request_count, api_error_count, api_latency_p95_ms, guild_count \
+                FROM daily_metrics WHERE day >= CAST(strftime('%s', 'now') AS INTEGER) / 86400 - :days ORDER BY day")?;
+            let rows = statement.query_map(named_params! {":days": days}, |row| {
+                Ok(DailyMetrics {
+                    day: row.get(0)?,
+                    activation_count: row.get(1)?,
+                    api_request_count: row.get(2)?,
+                    api_error_count: row.get(3)?,
+                    api_latency_p95: Duration::from_millis(row.get(4)?),
+                    guild_count: row.get(5)?,
+                })
+            })?;
+            let mut result = Vec::new();
+            for row in rows {
+                result.push(row?);
+            }
+            Ok(result)
+        })).await
+    }
+
+    /// Delete Jinxxy API request samples older than the given time
+    pub async fn prune_api_samples(&self, before_unix_ms: u64) -> Result<usize> {
+        self.timed(
+            "prune_api_samples",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "DELETE FROM api_request_log WHERE timestamp_unix_ms < :before",
+                )?;
+                let deleted = statement.execute(named_params! {":before": before_unix_ms})?;
+                Ok(deleted)
+            }),
+        )
+        .await
+    }
+
+    /// Get the message variant assigned to this guild, assigning one at random from the active variants if the guild
+    /// has none or its variant has been retired
+    pub async fn get_message_variant(
+        &self,
+        guild: GuildId,
+        message_key: MessageKey,
+    ) -> Result<MessageVariant> {
+        self.timed("get_message_variant", self.connection.call(move |connection| {
+            let transaction = connection.transaction()?;
+            let variant = {
+                let mut assigned = transaction.prepare_cached("SELECT variant_id, text FROM guild_message_variant INNER JOIN message_variant USING (message_key, variant_id) WHERE guild_id = :guild AND message_key = :key AND active = 1")?;
+                let variant = assigned
+                    .query_row(named_params! {":guild": guild.get(), ":key": message_key.as_db_str()}, |row| {
+                        Ok(MessageVariant {
+                            variant_id: row.get(0)?,
+                            text: row.get(1)?,
+                        })
+                    })
+                    .optional()?;
+                if let Some(variant) = variant {
+                    variant
+                } else {
+                    let mut candidates = transaction.prepare_cached("SELECT variant_id, text FROM message_variant WHERE message_key = :key AND active = 1")?;
+                    let result = candidates
+                        .query_map(named_params! {":key": message_key.as_db_str()}, |row| {
+                            Ok(MessageVariant {
+                                variant_id: row.get(0)?,
+                                text: row.get(1)?,
+                            })
+                        })?;
+                    let mut candidates = Vec::with_capacity(result.size_hint().0);
+                    for row in result {
+                        candidates.push(row?);
+                    }
+                    // the control variant can't be retired, so there is always at least one candidate
+                    let index = rand::thread_rng().gen_range(0..candidates.len());
+                    let variant = candidates.swap_remove(index);
+                    let mut assign = transaction.prepare_cached("INSERT INTO guild_message_variant (guild_id, message_key, variant_id) VALUES (:guild, :key, :variant) ON CONFLICT (guild_id, message_key) DO UPDATE SET variant_id = excluded.variant_id")?;
+                    assign.execute(named_params! {":guild": guild.get(), ":key": message_key.as_db_str(), ":variant": variant.variant_id})?;
+                    variant
+                }
+            };
+            transaction.commit()?;
+            Ok(variant)
+        })).await
+    }
+
+    /// Record that a user was shown a message variant. Their next attempt will be counted as a follow-up to it.
+    pub async fn record_message_shown(
+        &self,
+        guild: GuildId,
+        user_id: u64,
+        message_key: MessageKey,
+        variant_id: u64,
+    ) -> Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        self.timed("record_message_shown", self.connection.call(move |connection| {
+            let transaction = connection.transaction()?;
+            {
+                let mut shown = transaction.prepare_cached("UPDATE message_variant SET shown_count = shown_count + 1 WHERE variant_id = :variant")?;
+                shown.execute(named_params! {":variant": variant_id})?;
+                let mut pending = transaction.prepare_cached("INSERT OR REPLACE INTO message_variant_pending (guild_id, user_id, message_key, variant_id, timestamp_unix_ms) VALUES (:guild, :user, :key, :variant, :timestamp)")?;
+                pending.execute(named_params! {":guild": guild.get(), ":user": user_id, ":key": message_key.as_db_str(), ":variant": variant_id, ":timestamp": timestamp})?;
+            }
+            transaction.commit()?;
+            Ok(())
+        })).await
+    }
+
+    /// Record the outcome of a user's attempt as a follow-up to the last message variant they were shown, if any
+    pub async fn record_message_followup(
+        &self,
+        guild: GuildId,
+        user_id: u64,
+        message_key: MessageKey,
+        success: bool,
+    ) -> Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        self.timed("record_message_followup", self.connection.call(move |connection| {
+            let transaction = connection.transaction()?;
+            {
+                let mut pending = transaction.prepare_cached("DELETE FROM message_variant_pending WHERE guild_id = :guild AND user_id = :user AND message_key = :key RETURNING variant_id, timestamp_unix_ms")?;
+                let shown: Option<(u64, u64)> = pending
+                    .query_row(named_params! {":guild": guild.get(), ":user": user_id, ":key": message_key.as_db_str()}, |row| Ok((row.get(0)?, row.get(1)?)))
+                    .optional()?;
+                if let Some((variant_id, shown_at)) = shown {
+                    if now.saturating_sub(shown_at) <= MESSAGE_FOLLOWUP_WINDOW_MS {
+                        let query = if success {
+                            "UPDATE message_variant SET followup_success_count = followup_success_count + 1 WHERE variant_id = :variant"
+                        } else {
+                            "UPDATE message_variant SET followup_failure_count = followup_failure_count + 1 WHERE variant_id = :variant"
+                        };
+                        let mut followup = transaction.prepare_cached(query)?;
+                        followup.execute(named_params! {":variant": variant_id})?;
+                    }
+                }
+            }
+            transaction.commit()?;
+            Ok(())
+        })).await
+    }
+
+    /// Add a new phrasing for a message. Guilds are only assigned it once their current variant is retired.
+    pub async fn add_message_variant(&self, message_key: MessageKey, text: String) -> Result<u64> {
+        self.timed(
+            "add_message_variant",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "INSERT INTO message_variant (message_key, text) VALUES (:key, :text)",
+                )?;
+                statement
+                    .execute(named_params! {":key": message_key.as_db_str(), ":text": text})?;
+                Ok(connection.last_insert_rowid() as u64)
+            }),
+        )
+        .await
+    }
+
+    /// Retire a message variant, so guilds assigned it get re-assigned. Its stats are kept. Returns `false` if there
+    /// was no such active variant. The control variant cannot be retired.
+    pub async fn retire_message_variant(&self, variant_id: u64) -> Result<bool> {
+        self.timed("retire_message_variant", self.connection.call(move |connection| {
+            let transaction = connection.transaction()?;
+            let retired = {
+                let mut retire = transaction.prepare_cached("UPDATE message_variant SET active = 0 WHERE variant_id = :variant AND active = 1 AND text IS NOT NULL")?;
+                let retired = retire.execute(named_params! {":variant": variant_id})? != 0;
+                let mut unassign = transaction.prepare_cached("DELETE FROM guild_message_variant WHERE variant_id = :variant")?;
+                unassign.execute(named_params! {":variant": variant_id})?;
+                retired
+            };
+            transaction.commit()?;
+            Ok(retired)
+        })).await
+    }
+
+    /// Get stats for every variant of a message, control first
+    pub async fn get_message_variant_stats(
+        &self,
+        message_key: MessageKey,
+    ) -> Result<Vec<MessageVariantStats>> {
+        self.timed("get_message_variant_stats", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT variant_id, text, active, shown_count, followup_success_count, followup_failure_count FROM message_variant WHERE message_key = :key ORDER BY text IS NOT NULL, variant_id")?;
+            let result = statement.query_map(named_params! {":key": message_key.as_db_str()}, |row| {
+                Ok(MessageVariantStats {
+                    variant_id: row.get(0)?,
+                    text: row.get(1)?,
+                    active: row.get(2)?,
+                    shown_count: row.get(3)?,
+                    followup_success_count: row.get(4)?,
+                    followup_failure_count: row.get(5)?,
+                })
+            })?;
+            let mut vec = Vec::with_capacity(result.size_hint().0);
+            for row in result {
+                vec.push(row?);
+            }
+            Ok(vec)
+        })).await
+    }
+
+    /// get all product version exclusions
+    pub async fn get_exclusions(&self, guild: GuildId) -> Result<Vec<(String, String, RoleId)>> {
+        self.timed(
+            "get_exclusions",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "SELECT product_id, product_version_id, role_id FROM product_version_exclusion WHERE guild_id = ?",
+                )?; // uses primary key index
+                let result = statement.query_map([guild.get()], |row| {
+                    let product_id: String = row.get(0)?;
+                    let product_version_id: String = row.get(1)?;
+                    let role_id: u64 = row.get(2)?;
+                    Ok((product_id, product_version_id, RoleId::new(role_id)))
+                })?;
+                let mut vec = Vec::with_capacity(result.size_hint().0);
+                for row in result {
+                    vec.push(row?);
+                }
+                Ok(vec)
+            }),
+        )
+        .await
+    }
+
+    /// get all links
+    pub async fn get_links(&self, guild: GuildId) -> Result<Vec<(String, RoleId)>> {
+        self.timed(
+            "get_links",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "SELECT product_id, role_id FROM product_role WHERE guild_id = ?",
+                )?; // uses primary key index
+                let result = statement.query_map([guild.get()], |row| {
+                    let product_id: String = row.get(0)?;
+                    let role_id: u64 = row.get(1)?;
+                    Ok((product_id, RoleId::new(role_id)))
+                })?;
+                let mut vec = Vec::with_capacity(result.size_hint().0);
+                for row in result {
+                    vec.push(row?);
+                }
+                Ok(vec)
+            }),
+        )
+        .await
+    }
+
+    /// Search this guild's license activations for license IDs containing `query`, returning up to `limit`
+    /// (license ID, user ID) pairs. The placeholder activations used to lock licenses are skipped.
+    pub async fn search_license_activations(
+        &self,
+        guild: GuildId,
+        query: String,
+        limit: u64,
+    ) -> Result<Vec<(String, UserId)>> {
+        self.timed("search_license_activations", self.connection.call(move |connection| {
+            let pattern = format!("%{}%", escape_like(&query));
+            let mut statement = connection.prepare_cached("SELECT DISTINCT license_id, user_id FROM license_activation WHERE guild_id = :guild AND user_id != :locking_user AND license_id LIKE :pattern ESCAPE '\\' \
+                ORDER BY license_id, user_id LIMIT :limit")?; // uses primary key index
+            let rows = statement.query_map(
+                named_params! {":guild": guild.get(), ":locking_user": LOCKING_USER_ID, ":pattern": pattern, ":limit": limit},
+                |row| Ok((row.get(0)?, UserId::new(row.get(1)?))),
+            )?;
+            let mut vec = Vec::new();
+            for row in rows {
+                vec.push(row?);
+            }
+            Ok(vec)
+        })).await
+    }
+
+    /// Get every guild a user has activated a license in
+    pub async fn get_user_guilds(&self, user_id: UserId) -> Result<Vec<GuildId>> {
+        self.timed(
+            "get_user_guilds",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "SELECT DISTINCT guild_id FROM license_activation WHERE user_id = :user",
+                )?; // uses `user_lookup` index
+                let result =
+                    statement.query_map(named_params! {":user": user_id.get()}, |row| {
+                        let guild_id: u64 = row.get(0)?;
+                        Ok(GuildId::new(guild_id))
+                    })?;
+                let mut vec = Vec::with_capacity(result.size_hint().0);
+                for row in result {
+                    vec.push(row?);
+                }
+                Ok(vec)
+            }),
+        )
+        .await
+    }
+
+    /// Locally get all licences a users has been recorded to activate. This may be out of sync with Jinxxy!
+    pub async fn get_user_licenses(&self, guild: GuildId, user_id: u64) -> Result<Vec<String>> {
+        self.timed(
+            "get_user_licenses",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached("SELECT license_id FROM license_activation WHERE guild_id = :guild AND user_id = :user")?; // uses `user_license_lookup` index
+                let result = statement.query_map(
+                    named_params! {":guild": guild.get(), ":user": user_id},
+                    |row| {
+                        let license_id: String = row.get(0)?;
+                        Ok(license_id)
+                    },
+                )?;
+                let mut vec = Vec::with_capacity(result.size_hint().0);
+                for row in result {
+                    vec.push(row?);
+                }
+                Ok(vec)
+            }),
+        )
+        .await
+    }
+
+    /// Locally get all licences a user has been recorded to activate, along with whether each one is locked and when the
+    /// user first activated it, if known. This may be out of sync with Jinxxy!
+    pub async fn get_user_license_states(
+        &self,
+        guild: GuildId,
+        user_id: u64,
+    ) -> Result<Vec<(String, bool, Option<u64>)>> {
+        self.timed("get_user_license_states", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT activation.license_id, max(lock.license_id IS NOT NULL), min(activation.created_unix_ms) FROM license_activation AS activation \
+                LEFT JOIN license_activation AS lock ON lock.guild_id = activation.guild_id AND lock.license_id = activation.license_id AND lock.user_id = :locking_user \
+                WHERE activation.guild_id = :guild AND activation.user_id = :user GROUP BY activation.license_id ORDER BY activation.license_id")?; // uses `user_license_lookup` index, then the primary key for the lock
+            let result = statement.query_map(
+                named_params! {":guild": guild.get(), ":user": user_id, ":locking_user": LOCKING_USER_ID},
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )?;
+            let mut vec = Vec::with_capacity(result.size_hint().0);
+            for row in result {
+                vec.push(row?);
+            }
+            Ok(vec)
+        })).await
+    }
+
+    /// Locally get up to `limit` users who have activated a license, in user ID order starting after `after_user`. Lock
+    /// entries use a user ID of 0, so they come before every real user and are never included.
+    pub async fn get_activated_users(
+        &self,
+        guild: GuildId,
+        after_user: u64,
+        limit: u64,
+    ) -> Result<Vec<UserId>> {
+        self.timed(
+            "get_activated_users",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached("SELECT DISTINCT user_id FROM license_activation WHERE guild_id = :guild AND user_id > :after ORDER BY user_id LIMIT :limit")?; // uses `user_license_lookup` index
+                let result = statement.query_map(
+                    named_params! {":guild": guild.get(), ":after": after_user, ":limit": limit},
+                    |row| {
+                        let user_id: u64 = row.get(0)?;
+                        Ok(UserId::new(user_id))
+                    },
+                )?;
+                let mut vec = Vec::with_capacity(result.size_hint().0);
+                for row in result {
+                    vec.push(row?);
+                }
+                Ok(vec)
+            }),
+        )
+        .await
+    }
+
+    /// Locally get all activations for a user and license has been recorded to activate. This may be out of sync with Jinxxy!
+    pub async fn get_user_license_activations(
+        &self,
+        guild: GuildId,
+        user_id: u64,
+        license_id: String,
+    ) -> Result<Vec<String>> {
+        self.timed(
+            "get_user_license_activations",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached("SELECT license_activation_id FROM license_activation WHERE guild_id = :guild AND user_id = :user AND license_id = :license")?; // uses primary key index
+                let result = statement.query_map(
+                    named_params! {":guild": guild.get(), ":user": user_id, ":license": license_id},
+                    |row| {
+                        let activation_id: String = row.get(0)?;
+                        Ok(activation_id)
+                    },
+                )?;
+                let mut vec = Vec::with_capacity(result.size_hint().0);
+                for row in result {
+                    vec.push(row?);
+                }
+                Ok(vec)
+            }),
+        )
+        .await
+    }
+
+    /// Locally get all users that have activated the given license, along with when each first activated it, if known.
+    /// This may be out of sync with Jinxxy!
+    pub async fn get_license_users(
+        &self,
+        guild: GuildId,
+        license_id: String,
+    ) -> Result<Vec<(u64, Option<u64>)>> {
+        self.timed(
+            "get_license_users",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached("SELECT user_id, created_unix_ms FROM license_activation WHERE guild_id = :guild AND license_id = :license")?; // uses primary key index
+                let result = statement.query_map(
+                    named_params! {":guild": guild.get(), ":license": license_id},
+                    |row| {
+                        let user_id: u64 = row.get(0)?;
+                        let created_unix_ms: Option<u64> = row.get(1)?;
+                        Ok((user_id, created_unix_ms))
+                    },
+                )?;
+                // a user may have several activations of one license, in which case the earliest one counts
+                let mut users: BTreeMap<u64, Option<u64>> = BTreeMap::new();
+                for row in result {
+                    let (user_id, created_unix_ms) = row?;
+                    let earliest = users.entry(user_id).or_insert(created_unix_ms);
+                    *earliest = match (*earliest, created_unix_ms) {
+                        (Some(a), Some(b)) => Some(a.min(b)),
+                        (a, b) => a.or(b),
+                    };
+                }
+                Ok(users.into_iter().collect())
+            }),
+        )
+        .await
+    }
+
+    /// Get DB size in bytes
+    pub async fn size(&self) -> Result<u64> {
+        self.timed("size", self.connection.call(move |connection| {
+            let result: u64 = connection.query_row("SELECT page_count * page_size as size FROM pragma_page_count(), pragma_page_size()", [], |row| row.get(0))?;
+            Ok(result)
+        })).await
+    }
+
+    /// Write a consistent copy of the whole DB to a new file at `path`. This is safe to do while the bot is running.
+    pub async fn backup(&self, path: PathBuf) -> Result<()> {
+        self.timed(
+            "backup",
+            self.connection.call(move |connection| {
+                let path = path.to_string_lossy().into_owned();
+                connection.execute("VACUUM INTO :path", named_params! {":path": path})?;
+                Ok(())
+            }),
+        )
+        .await
+    }
+
+    /// Get count of license activations. This is a materialized count that does not hit the DB.
+    pub fn license_activation_count(&self) -> u64 {
+        self.license_activation_count.load(Ordering::Relaxed)
+    }
+
+    /// Recompute the materialized counts from scratch. These are maintained incrementally, but some operations (such
+    /// as changing a guild's test or opt-out status) can cause drift, so this should be run periodically.
+    pub async fn reconcile_counters(&self) -> Result<()> {
+        let (license_activation_count, product_role_count, distinct_user_count) = self.timed("reconcile_counters", self.connection.call(move |connection| {
+            let license_activation_count: u64 = connection.query_row("SELECT count(*) FROM license_activation LEFT JOIN guild USING (guild_id) WHERE guild.test = 0 AND guild.stats_opt_out = 0", [], |row| row.get(0))?;
+            let product_role_count: u64 = connection.query_row("SELECT count(*) FROM product_role LEFT JOIN guild USING (guild_id) WHERE guild.test = 0 AND guild.stats_opt_out = 0", [], |row| row.get(0))?;
+            let mut statement = connection.prepare_cached("SELECT count(DISTINCT license_activation.user_id) FROM license_activation LEFT JOIN guild USING (guild_id) WHERE guild.test = 0 AND guild.stats_opt_out = 0 AND license_activation.user_id != :locking_user")?;
+            let distinct_user_count: u64 = statement.query_row(named_params! {":locking_user": LOCKING_USER_ID}, |row| row.get(0))?;
+            Ok((license_activation_count, product_role_count, distinct_user_count))
+        })).await?;
+        let old_license_activation_count = self
+            .license_activation_count
+            .swap(license_activation_count, Ordering::Relaxed);
+        let old_product_role_count = self
+            .product_role_count
+            .swap(product_role_count, Ordering::Relaxed);
+        let old_distinct_user_count = self
+            .distinct_user_count
+            .swap(distinct_user_count, Ordering::Relaxed);
+        if old_distinct_user_count != distinct_user_count {
+            self.distinct_user_count_changed.notify_waiters();
+        }
+        if old_license_activation_count != license_activation_count
+            || old_product_role_count != product_role_count
+            || old_distinct_user_count != distinct_user_count
+        {
+            debug!(
+                "reconciled counters: license activations {} -> {}, product→role links {} -> {}, distinct users {} -> {}",
+                old_license_activation_count,
+                license_activation_count,
+                old_product_role_count,
+                product_role_count,
+                old_distinct_user_count,
+                distinct_user_count
+            );
+        }
+        Ok(())
+    }
+
+    /// Get count of configured guilds
+    pub async fn guild_count(&self) -> Result<u64> {
+        self.timed(
+            "guild_count",
+            self.connection.call(move |connection| {
+                let result: u64 = connection.query_row(GUILD_COUNT_QUERY, [], |row| row.get(0))?;
+                Ok(result)
+            }),
+        )
+        .await
+    }
+
+    /// Get count of distinct bot log channels
+    pub async fn log_channel_count(&self) -> Result<u64> {
+        self.timed(
+            "log_channel_count",
+            self.connection.call(move |connection| {
+                let result: u64 = connection.query_row(
+                    "SELECT count(DISTINCT log_channel_id) FROM guild WHERE test = 0 AND stats_opt_out = 0",
+                    [],
+                    |row| row.get(0),
+                )?;
+                Ok(result)
+            }),
+        )
+        .await
+    }
+
+    /// Get count of distinct users with a license activation. This is a materialized count that does not hit the DB.
+    pub fn distinct_user_count(&self) -> u64 {
+        self.distinct_user_count.load(Ordering::Relaxed)
+    }
+
+    /// Wait until the distinct user count changes
+    pub async fn distinct_user_count_changed(&self) {
+        self.distinct_user_count_changed.notified().await
+    }
+
+    /// Get count of product->role mappings. This is a materialized count that does not hit the DB.
+    pub fn product_role_count(&self) -> u64 {
+        self.product_role_count.load(Ordering::Relaxed)
+    }
+
+    /// Get count of license activations in a guild
+    pub async fn guild_license_activation_count(&self, guild: GuildId) -> Result<u64> {
+        self.timed("guild_license_activation_count", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT count(*) FROM license_activation LEFT JOIN guild USING (guild_id) WHERE guild.guild_id = :guild")?;
+            let result: u64 = statement.query_row(named_params! {":guild": guild.get()}, |row| row.get(0))?;
+            Ok(result)
+        })).await
+    }
+
+    /// Get count of license activations in a guild made since the given time. License locks aren't counted, and
+    /// neither are activations from before activation times were recorded.
+    pub async fn guild_license_activation_count_since(
+        &self,
+        guild: GuildId,
+        since_unix_ms: u64,
+    ) -> Result<u64> {
+        self.timed("guild_license_activation_count_since", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT count(*) FROM license_activation WHERE guild_id = :guild AND created_unix_ms >= :since AND user_id != :locking_user")?;
+            let result: u64 = statement.query_row(named_params! {":guild": guild.get(), ":since": since_unix_ms, ":locking_user": LOCKING_USER_ID}, |row| row.get(0))?;
+            Ok(result)
+        })).await
+    }
+
+    /// Get count of product->role mappings in a guild
+    pub async fn guild_product_role_count(&self, guild: GuildId) -> Result<u64> {
+        self.timed("guild_product_role_count", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT count(*) FROM product_role LEFT JOIN guild USING (guild_id) WHERE guild.guild_id = :guild")?;
+            let result: u64 = statement.query_row(named_params! {":guild": guild.get()}, |row| row.get(0))?;
+            Ok(result)
+        })).await
+    }
+
+    /// Get bot log channel
+    pub async fn get_log_channel(&self, guild: GuildId) -> Result<Option<ChannelId>> {
+        let channel_id = self
+            .timed(
+                "get_log_channel",
+                self.connection.call(move |connection| {
+                    let mut statement = connection
+                        .prepare_cached("SELECT log_channel_id FROM guild WHERE guild_id = ?")?;
+                    let result: Option<Option<u64>> = statement
+                        .query_row([guild.get()], |row| row.get(0))
+                        .optional()?;
+                    // inner optional is for if the guild has no log channel set
+                    // outer optional is for if the guild does not exist in our DB
+                    Ok(result.flatten())
+                }),
+            )
+            .await?;
+        Ok(channel_id.map(ChannelId::new))
+    }
+
+    /// Schedule an announcement. Returns its ID.
+    pub async fn add_announcement(
+        &self,
+        title: Option<String>,
+        message: String,
+        audience: AnnouncementAudience,
+        created_by: UserId,
+        send_at_unix_ms: u64,
+    ) -> Result<u64> {
+        self.timed("add_announcement", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO announcement (title, message, audience, created_by, send_at_unix_ms) VALUES (:title, :message, :audience, :created_by, :send_at) RETURNING announcement_id")?;
+            let announcement_id = statement.query_row(named_params! {":title": title, ":message": message, ":audience": audience.as_db_str(), ":created_by": created_by.get(), ":send_at": send_at_unix_ms}, |row| row.get(0))?;
+            Ok(announcement_id)
+        })).await
+    }
+
+    /// Get the IDs of announcements that are due to be sent but haven't been yet, oldest first
+    pub async fn get_due_announcements(&self, now_unix_ms: u64) -> Result<Vec<u64>> {
+        self.timed("get_due_announcements", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT announcement_id FROM announcement WHERE sent_unix_ms IS NULL AND send_at_unix_ms <= :now ORDER BY send_at_unix_ms")?;
+            let rows = statement.query_map(named_params! {":now": now_unix_ms}, |row| row.get(0))?;
+            let mut vec = Vec::new();
+            for row in rows {
+                vec.push(row?);
+            }
+            Ok(vec)
+        })).await
+    }
+
+    /// Mark an announcement as sent so it's only delivered once. Returns its title, message, and audience, or `None` if
+    /// it doesn't exist or was already sent.
+    pub async fn claim_announcement(
+        &self,
+        announcement_id: u64,
+        now_unix_ms: u64,
+    ) -> Result<Option<(Option<String>, String, AnnouncementAudience)>> {
+        self.timed("claim_announcement", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("UPDATE announcement SET sent_unix_ms = :now WHERE announcement_id = :id AND sent_unix_ms IS NULL RETURNING title, message, audience")?;
+            let announcement = statement.query_row(named_params! {":id": announcement_id, ":now": now_unix_ms}, |row| {
+                let audience: String = row.get(2)?;
+                // same fallback as announcement_from_row
+                let audience = AnnouncementAudience::from_db_str(&audience).unwrap_or(AnnouncementAudience::Test);
+                Ok((row.get(0)?, row.get(1)?, audience))
+            }).optional()?;
+            Ok(announcement)
+        })).await
+    }
+
+    /// Get the bot log channels an announcement for this audience goes to. Guilds marked for deletion are skipped.
+    pub async fn get_announcement_targets(
+        &self,
+        audience: AnnouncementAudience,
+    ) -> Result<Vec<(GuildId, ChannelId)>> {
+        self.timed("get_announcement_targets", self.connection.call(move |connection| {
+            let mut statement = match audience {
+                AnnouncementAudience::All => connection.prepare_cached("SELECT guild_id, log_channel_id FROM guild WHERE log_channel_id IS NOT NULL AND deleted_unix_ms IS NULL"),
+                AnnouncementAudience::Production => connection.prepare_cached("SELECT guild_id, log_channel_id FROM guild WHERE log_channel_id IS NOT NULL AND deleted_unix_ms IS NULL AND test = 0"),
+                AnnouncementAudience::Test => connection.prepare_cached("SELECT guild_id, log_channel_id FROM guild WHERE log_channel_id IS NOT NULL AND deleted_unix_ms IS NULL AND test != 0"),
+            }?;
+            let rows = statement.query_map((), |row| Ok((GuildId::new(row.get(0)?), ChannelId::new(row.get(1)?))))?;
+            let mut vec = Vec::new();
+            for row in rows {
+                vec.push(row?);
+            }
+            Ok(vec)
+        })).await
+    }
+
+    /// Record the result of delivering an announcement to a guild. `error` is `None` if it was delivered.
+    pub async fn record_announcement_delivery(
+        &self,
+        announcement_id: u64,
+        guild: GuildId,
+        channel: ChannelId,
+        error: Option<String>,
+    ) -> Result<()> {
+        self.timed("record_announcement_delivery", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT OR REPLACE INTO announcement_delivery (announcement_id, guild_id, channel_id, error) VALUES (:id, :guild, :channel, :error)")?;
+            statement.execute(named_params! {":id": announcement_id, ":guild": guild.get(), ":channel": channel.get(), ":error": error})?;
+            Ok(())
+        })).await
+    }
+
+    /// Get an announcement
+    pub async fn get_announcement(&self, announcement_id: u64) -> Result<Option<Announcement>> {
+        self.timed(
+            "get_announcement",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    format!(
+                        "{ANNOUNCEMENT_QUERY} WHERE announcement_id = :id GROUP BY announcement_id"
+                    )
+                    .as_str(),
+                )?;
+                let announcement = statement
+                    .query_and_then(
+                        named_params! {":id": announcement_id},
+                        announcement_from_row,
+                    )?
+                    .next()
+                    .transpose()?;
+                Ok(announcement)
+            }),
+        )
+        .await
+    }
+
+    /// Get the most recent announcements, newest first
+    pub async fn get_announcements(&self, limit: u64) -> Result<Vec<Announcement>> {
+        self.timed("get_announcements", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached(format!("{ANNOUNCEMENT_QUERY} GROUP BY announcement_id ORDER BY announcement_id DESC LIMIT :limit").as_str())?;
+            let rows = statement.query_and_then(named_params! {":limit": limit}, announcement_from_row)?;
+            let mut vec = Vec::new();
+            for row in rows {
+                vec.push(row?);
+            }
+            Ok(vec)
+        })).await
+    }
+
+    /// Get the guilds an announcement failed to be delivered to, along with the error
+    pub async fn get_announcement_failures(
+        &self,
+        announcement_id: u64,
+    ) -> Result<Vec<(GuildId, ChannelId, String)>> {
+        self.timed("get_announcement_failures", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT guild_id, channel_id, error FROM announcement_delivery WHERE announcement_id = :id AND error IS NOT NULL ORDER BY guild_id")?;
+            let rows = statement.query_map(named_params! {":id": announcement_id}, |row| Ok((GuildId::new(row.get(0)?), ChannelId::new(row.get(1)?), row.get(2)?)))?;
+            let mut vec = Vec::new();
+            for row in rows {
+                vec.push(row?);
+            }
+            Ok(vec)
+        })).await
+    }
+
+    /// Get all guilds that have a Jinxxy API key set, skipping guilds marked for deletion
+    pub async fn get_guilds_with_api_key(&self) -> Result<Vec<GuildId>> {
+        self.timed(
+            "get_guilds_with_api_key",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "SELECT guild_id FROM guild WHERE jinxxy_api_key IS NOT NULL AND deleted_unix_ms IS NULL",
+                )?;
+                let result = statement.query_and_then((), |row| row.get(0).map(GuildId::new))?;
+                let mut vec = Vec::with_capacity(result.size_hint().0);
+                for row in result {
+                    vec.push(row?);
+                }
+                Ok(vec)
+            }),
+        )
+        .await
+    }
+
+    /// Mark a guild's data for deletion after the bot is removed from the guild. Nothing is deleted until
+    /// [`Self::purge_deleted_guilds`] runs, so the data comes back if the bot is re-added before then. A guild that was
+    /// already marked keeps its original deletion time.
+    pub async fn soft_delete_guild(&self, guild: GuildId) -> Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        self.timed("soft_delete_guild", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO guild (guild_id, deleted_unix_ms) VALUES (:guild, :timestamp) ON CONFLICT (guild_id) DO UPDATE SET deleted_unix_ms = coalesce(deleted_unix_ms, excluded.deleted_unix_ms)")?;
+            statement.execute(named_params! {":guild": guild.get(), ":timestamp": timestamp})?;
+            Ok(())
+        })).await
+    }
+
+    /// Undo [`Self::soft_delete_guild`]. Returns `true` if the guild was marked for deletion.
+    pub async fn restore_guild(&self, guild: GuildId) -> Result<bool> {
+        self.timed(
+            "restore_guild",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "UPDATE guild SET deleted_unix_ms = NULL WHERE guild_id = :guild AND deleted_unix_ms IS NOT NULL",
+                )?;
+                let updated = statement.execute(named_params! {":guild": guild.get()})?;
+                Ok(updated != 0)
+            }),
+        )
+        .await
+    }
+
+    /// Permanently delete all data for guilds that were marked for deletion before the given time. Returns the purged
+    /// guilds. This leaves the materialized counters stale, so follow it with [`Self::reconcile_counters`].
+    pub async fn purge_deleted_guilds(&self, before_unix_ms: u64) -> Result<Vec<GuildId>> {
+        self.timed(
+            "purge_deleted_guilds",
+            self.connection.call(move |connection| {
+                let transaction = connection.transaction()?;
+                let mut guilds = Vec::new();
+                {
+                    let mut statement = transaction.prepare_cached(
+                        "SELECT guild_id FROM guild WHERE deleted_unix_ms < :before",
+                    )?;
+                    let rows = statement
+                        .query_map(named_params! {":before": before_unix_ms}, |row| {
+                            row.get(0).map(GuildId::new)
+                        })?;
+                    for row in rows {
+                        guilds.push(row?);
+                    }
+                    for table in GUILD_TABLES {
+                        let mut statement = transaction.prepare_cached(
+                            format!("DELETE FROM \"{table}\" WHERE guild_id = :guild").as_str(),
+                        )?;
+                        for guild in &guilds {
+                            statement.execute(named_params! {":guild": guild.get()})?;
+                        }
+                    }
+                }
+                transaction.commit()?;
+                Ok(guilds)
+            }),
+        )
+        .await
+    }
+
+    /// Replace the persisted product list for a guild. This backs autocomplete before the in-memory cache is loaded.
+    /// Returns how the list changed.
+    pub async fn replace_products(
+        &self,
+        guild: GuildId,
+        products: Vec<PartialProduct>,
+    ) -> Result<ProductDiff> {
+        self.timed("replace_products", self.connection.call(move |connection| {
+            let transaction = connection.transaction()?;
+            let mut diff = ProductDiff::default();
+            {
+                let mut statement = transaction.prepare_cached("SELECT product_id, product_name FROM product WHERE guild_id = :guild")?; // uses primary key index
+                let rows = statement.query_map(named_params! {":guild": guild.get()}, |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+                let mut old_products = HashMap::new();
+                for row in rows {
+                    let (product_id, product_name) = row?;
+                    old_products.insert(product_id, product_name);
+                }
+                diff.had_products = !old_products.is_empty();
+                let mut statement = transaction.prepare_cached("DELETE FROM product WHERE guild_id = :guild")?;
+                statement.execute(named_params! {":guild": guild.get()})?;
+                let mut statement = transaction.prepare_cached("INSERT INTO product (guild_id, product_id, product_name, thumbnail_url, price) VALUES (:guild, :product, :name, :thumbnail_url, :price)")?;
+                for PartialProduct { id: product_id, name: product_name, details } in products {
+                    statement.execute(named_params! {":guild": guild.get(), ":product": &product_id, ":name": &product_name, ":thumbnail_url": details.thumbnail_url, ":price": details.price})?;
+                    match old_products.remove(&product_id) {
+                        None => diff.added.push((product_id, product_name)),
+                        Some(old_name) if old_name != product_name => diff.renamed.push((product_id, old_name, product_name)),
+                        Some(_) => {}
+                    }
+                }
+                // anything left over is no longer in the store
+                diff.removed = old_products.into_iter().collect();
+                diff.removed.sort_unstable_by(|(_, a), (_, b)| a.cmp(b));
+            }
+            transaction.commit()?;
+            Ok(diff)
+        })).await
+    }
+
+    /// Record that a product cache refresh was requested for these guilds, so it can be replayed if we restart before it
+    /// finishes
+    pub async fn add_pending_cache_refreshes(&self, guilds: Vec<GuildId>) -> Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        self.timed("add_pending_cache_refreshes", self.connection.call(move |connection| {
+            let transaction = connection.transaction()?;
+            {
+                // a guild that's already pending keeps its original request time
+                let mut statement = transaction.prepare_cached("INSERT OR IGNORE INTO cache_refresh_pending (guild_id, requested_unix_ms) VALUES (:guild, :timestamp)")?;
+                for guild in guilds {
+                    statement.execute(named_params! {":guild": guild.get(), ":timestamp": timestamp})?;
+                }
+            }
+            transaction.commit()?;
+            Ok(())
+        })).await
+    }
+
+    /// Clear pending product cache refreshes once they're done
+    pub async fn remove_pending_cache_refreshes(&self, guilds: Vec<GuildId>) -> Result<()> {
+        self.timed(
+            "remove_pending_cache_refreshes",
+            self.connection.call(move |connection| {
+                let transaction = connection.transaction()?;
+                {
+                    let mut statement = transaction.prepare_cached(
+                        "DELETE FROM cache_refresh_pending WHERE guild_id = :guild",
+                    )?;
+                    for guild in guilds {
+                        statement.execute(named_params! {":guild": guild.get()})?;
+                    }
+                }
+                transaction.commit()?;
+                Ok(())
+            }),
+        )
+        .await
+    }
+
+    /// Get guilds with a pending product cache refresh, oldest request first. Guilds marked for deletion are skipped.
+    pub async fn get_pending_cache_refreshes(&self) -> Result<Vec<GuildId>> {
+        self.timed("get_pending_cache_refreshes", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT guild_id FROM cache_refresh_pending LEFT JOIN guild USING (guild_id) WHERE deleted_unix_ms IS NULL ORDER BY requested_unix_ms")?;
+            let rows = statement.query_map((), |row| row.get(0).map(GuildId::new))?;
+            let mut vec = Vec::new();
+            for row in rows {
+                vec.push(row?);
+            }
+            Ok(vec)
+        })).await
+    }
 
-impl JinxDb {
-    /// Open a new database
-    pub async fn open() -> Result<Self> {
-        Self::open_path("jinx.sqlite").await
+    /// Record that this guild's slash commands failed to register, so the background sync retries them
+    pub async fn add_pending_command_sync(&self, guild: GuildId) -> Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        self.timed("add_pending_command_sync", self.connection.call(move |connection| {
+            // a guild that's already pending keeps its original request time
+            let mut statement = connection.prepare_cached("INSERT OR IGNORE INTO command_sync_pending (guild_id, requested_unix_ms) VALUES (:guild, :timestamp)")?;
+            statement.execute(named_params! {":guild": guild.get(), ":timestamp": timestamp})?;
+            Ok(())
+        })).await
     }
 
-    /// Open a new database
-    async fn open_path<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let connection = Connection::open(path).await?;
-        JinxDb::init(&connection).await?;
-        let db = JinxDb {
-            connection,
-            api_key_cache: Default::default(),
-        };
-        Ok(db)
+    /// Clear a pending slash command sync once the guild's commands are registered
+    pub async fn remove_pending_command_sync(&self, guild: GuildId) -> Result<()> {
+        self.timed(
+            "remove_pending_command_sync",
+            self.connection.call(move |connection| {
+                let mut statement = connection
+                    .prepare_cached("DELETE FROM command_sync_pending WHERE guild_id = :guild")?;
+                statement.execute(named_params! {":guild": guild.get()})?;
+                Ok(())
+            }),
+        )
+        .await
     }
 
-    /// Set up the database
-    async fn init(connection: &Connection) -> Result<()> {
-        let start = Instant::now();
-        connection
-            .call(|connection| {
-                // all applications are encouraged to switch this setting off on every database connection as soon as that connection is opened
-                connection.execute("PRAGMA trusted_schema = OFF;", ())?;
+    /// Get guilds with a pending slash command sync, oldest first. Guilds marked for deletion are skipped.
+    pub async fn get_pending_command_syncs(&self) -> Result<Vec<GuildId>> {
+        self.timed("get_pending_command_syncs", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT guild_id FROM command_sync_pending LEFT JOIN guild USING (guild_id) WHERE deleted_unix_ms IS NULL ORDER BY requested_unix_ms")?;
+            let rows = statement.query_map((), |row| row.get(0).map(GuildId::new))?;
+            let mut vec = Vec::new();
+            for row in rows {
+                vec.push(row?);
+            }
+            Ok(vec)
+        })).await
+    }
 
-                connection.execute(
-                    "CREATE TABLE IF NOT EXISTS \"settings\" ( \
-                key                    TEXT PRIMARY KEY, \
-                value                  ANY \
-            ) STRICT",
-                    (),
+    /// Search persisted products for names containing `query`, returning up to `limit` (product ID, product name)
+    /// pairs. Matching is ASCII case-insensitive, and names starting with `query` are returned first.
+    pub async fn search_products(
+        &self,
+        guild: GuildId,
+        query: String,
+        limit: u64,
+    ) -> Result<Vec<(String, String)>> {
+        self.timed(
+            "search_products",
+            self.connection.call(move |connection| {
+                let escaped_query = escape_like(&query);
+                let pattern = format!("%{escaped_query}%");
+                let prefix_pattern = format!("{escaped_query}%");
+                let mut statement = connection.prepare_cached("SELECT product_id, product_name FROM product WHERE guild_id = :guild AND product_name LIKE :pattern ESCAPE '\\' \
+                    ORDER BY product_name LIKE :prefix_pattern ESCAPE '\\' DESC, product_name LIMIT :limit")?; // uses primary key index
+                let result = statement.query_map(
+                    named_params! {":guild": guild.get(), ":pattern": pattern, ":prefix_pattern": prefix_pattern, ":limit": limit},
+                    |row| Ok((row.get(0)?, row.get(1)?)),
                 )?;
+                let mut vec = Vec::with_capacity(result.size_hint().0);
+                for row in result {
+                    vec.push(row?);
+                }
+                Ok(vec)
+            }),
+        )
+        .await
+    }
 
-                connection.execute(
-                    "CREATE TABLE IF NOT EXISTS guild ( \
-                guild_id               INTEGER PRIMARY KEY, \
-                jinxxy_api_key         TEXT, \
-                log_channel_id         INTEGER, \
-                test                   INTEGER NOT NULL DEFAULT 0, \
-                owner                  INTEGER NOT NULL DEFAULT 0 \
-            ) STRICT",
-                    (),
-                )?;
+    /// Get all persisted (product ID, product name) pairs for a guild, ordered by name
+    pub async fn get_products(&self, guild: GuildId) -> Result<Vec<(String, String)>> {
+        self.timed(
+            "get_products",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "SELECT product_id, product_name FROM product WHERE guild_id = :guild ORDER BY product_name",
+                )?; // uses primary key index
+                let result = statement.query_map(named_params! {":guild": guild.get()}, |row| {
+                    Ok((row.get(0)?, row.get(1)?))
+                })?;
+                let mut vec = Vec::with_capacity(result.size_hint().0);
+                for row in result {
+                    vec.push(row?);
+                }
+                Ok(vec)
+            }),
+        )
+        .await
+    }
 
-                connection.execute(
-                    "CREATE TABLE IF NOT EXISTS product_role ( \
-                guild_id               INTEGER NOT NULL, \
-                product_id             TEXT NOT NULL, \
-                role_id                INTEGER NOT NULL, \
-                PRIMARY KEY            (guild_id, product_id, role_id) \
-            ) STRICT",
-                    (),
-                )?;
+    /// Get the persisted display details of a product. Unknown products have no details.
+    pub async fn get_product_details(
+        &self,
+        guild: GuildId,
+        product_id: String,
+    ) -> Result<ProductDetails> {
+        self.timed(
+            "get_product_details",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached("SELECT thumbnail_url, price FROM product WHERE guild_id = :guild AND product_id = :product")?; // uses primary key index
+                let details = statement
+                    .query_row(
+                        named_params! {":guild": guild.get(), ":product": product_id},
+                        |row| {
+                            Ok(ProductDetails {
+                                thumbnail_url: row.get(0)?,
+                                price: row.get(1)?,
+                            })
+                        },
+                    )
+                    .optional()?;
+                Ok(details.unwrap_or_default())
+            }),
+        )
+        .await
+    }
 
-                connection.execute(
-                    "CREATE INDEX IF NOT EXISTS role_lookup ON product_role (guild_id, product_id)",
-                    (),
-                )?;
+    /// Get the persisted display details of every product in a guild, by product ID
+    pub async fn get_all_product_details(
+        &self,
+        guild: GuildId,
+    ) -> Result<HashMap<String, ProductDetails>> {
+        self.timed(
+            "get_all_product_details",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "SELECT product_id, thumbnail_url, price FROM product WHERE guild_id = :guild",
+                )?; // uses primary key index
+                let rows = statement.query_map(named_params! {":guild": guild.get()}, |row| {
+                    let details = ProductDetails {
+                        thumbnail_url: row.get(1)?,
+                        price: row.get(2)?,
+                    };
+                    Ok((row.get(0)?, details))
+                })?;
+                let mut map = HashMap::new();
+                for row in rows {
+                    let (product_id, details) = row?;
+                    map.insert(product_id, details);
+                }
+                Ok(map)
+            }),
+        )
+        .await
+    }
 
-                connection.execute(
-                    "CREATE TABLE IF NOT EXISTS license_activation ( \
-                guild_id               INTEGER NOT NULL, \
-                license_id             TEXT NOT NULL, \
-                license_activation_id  TEXT NOT NULL, \
-                user_id                INTEGER NOT NULL, \
-                PRIMARY KEY            (guild_id, license_id, license_activation_id, user_id) \
-            ) STRICT",
-                    (),
-                )?;
+    /// Get the cached name of a product, and of one of its versions if a version is given and it's cached too
+    pub async fn get_product_version_name(
+        &self,
+        guild: GuildId,
+        product_id: String,
+        product_version_id: Option<String>,
+    ) -> Result<Option<(String, Option<String>)>> {
+        self.timed("get_product_version_name", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT product_name, product_version_name FROM product \
+                LEFT JOIN product_version ON product_version.guild_id = product.guild_id AND product_version.product_id = product.product_id AND product_version.product_version_id = :version \
+                WHERE product.guild_id = :guild AND product.product_id = :product")?; // uses primary key indexes
+            let names = statement
+                .query_row(
+                    named_params! {":guild": guild.get(), ":product": product_id, ":version": product_version_id},
+                    |row| Ok((row.get(0)?, row.get(1)?)),
+                )
+                .optional()?;
+            Ok(names)
+        })).await
+    }
 
-                connection.execute(
-                    "CREATE TABLE IF NOT EXISTS \"owner\" ( \
-                owner_id               INTEGER PRIMARY KEY \
-            ) STRICT",
-                    (),
+    /// Get the cached (version ID, version name) pairs for a product
+    pub async fn get_product_versions(
+        &self,
+        guild: GuildId,
+        product_id: String,
+    ) -> Result<Vec<(String, String)>> {
+        self.timed(
+            "get_product_versions",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached("SELECT product_version_id, product_version_name FROM product_version WHERE guild_id = :guild AND product_id = :product")?; // uses primary key index
+                let result = statement.query_map(
+                    named_params! {":guild": guild.get(), ":product": product_id},
+                    |row| Ok((row.get(0)?, row.get(1)?)),
                 )?;
-
-                let mut settings_read =
-                    connection.prepare("SELECT value FROM settings where key = :key")?;
-                let schema_version: i32 = settings_read
-                    .query_row(named_params! {":key": SCHEMA_VERSION_KEY}, |a| a.get(0))
-                    .optional()?
-                    .unwrap_or(SCHEMA_VERSION_VALUE);
-
-                // handle schema v1 -> v2 migration
-                if schema_version < 2 {
-                    // "log_channel_id" column needs to be added to "guild"
-                    connection
-                        .execute("ALTER TABLE guild ADD COLUMN log_channel_id INTEGER", ())?;
-                    // "test" column needs to be added to "guild"
-                    connection.execute(
-                        "ALTER TABLE guild ADD COLUMN test INTEGER NOT NULL DEFAULT 0",
-                        (),
-                    )?;
+                let mut vec = Vec::with_capacity(result.size_hint().0);
+                for row in result {
+                    vec.push(row?);
                 }
+                Ok(vec)
+            }),
+        )
+        .await
+    }
 
-                // handle schema v2 -> v3 migration
-                if schema_version < 3 {
-                    // "owner" column needs to be added to "guild"
-                    connection.execute(
-                        "ALTER TABLE guild ADD COLUMN owner INTEGER NOT NULL DEFAULT 0",
-                        (),
-                    )?;
+    /// Replace the cached (version ID, version name) pairs for a product
+    pub async fn replace_product_versions(
+        &self,
+        guild: GuildId,
+        product_id: String,
+        versions: Vec<(String, String)>,
+    ) -> Result<()> {
+        self.timed("replace_product_versions", self.connection.call(move |connection| {
+            let transaction = connection.transaction()?;
+            {
+                let mut statement = transaction.prepare_cached("DELETE FROM product_version WHERE guild_id = :guild AND product_id = :product")?;
+                statement.execute(named_params! {":guild": guild.get(), ":product": product_id})?;
+                let mut statement = transaction.prepare_cached("INSERT INTO product_version (guild_id, product_id, product_version_id, product_version_name) VALUES (:guild, :product, :version, :name)")?;
+                for (version_id, version_name) in versions {
+                    statement.execute(named_params! {":guild": guild.get(), ":product": product_id, ":version": version_id, ":name": version_name})?;
                 }
+            }
+            transaction.commit()?;
+            Ok(())
+        })).await
+    }
 
-                // handle schema v3 -> v4 migration
-                if schema_version < 4 {
-                    // "guild.id" column needs to be renamed to "guild_id"
-                    connection.execute("ALTER TABLE guild RENAME COLUMN id TO guild_id", ())?;
-                }
+    /// Get the cron-like schedule for pre-warming the API cache, if one is set
+    pub async fn get_cache_warm_schedule(&self) -> Result<Option<String>> {
+        self.get_setting(CACHE_WARM_SCHEDULE_KEY).await
+    }
 
-                // Applications that use long-lived database connections should run "PRAGMA optimize=0x10002;" when the connection is first opened.
-                // All applications should run "PRAGMA optimize;" after a schema change.
-                connection.execute("PRAGMA optimize = 0x10002", ())?;
+    /// Set or unset the cron-like schedule for pre-warming the API cache
+    pub async fn set_cache_warm_schedule(&self, schedule: Option<String>) -> Result<()> {
+        self.set_setting(CACHE_WARM_SCHEDULE_KEY, schedule).await
+    }
 
-                // update the schema version value persisted to the DB
-                let mut settings_insert = connection.prepare(
-                    "INSERT OR REPLACE INTO settings (key, value) VALUES (:key, :value)",
-                )?;
-                settings_insert.execute(
-                    named_params! {":key": SCHEMA_VERSION_KEY, ":value": SCHEMA_VERSION_VALUE},
-                )?;
+    /// Get the notice shown instead of processing registrations, if registration is globally disabled for an incident.
+    /// An empty notice means the built-in one should be shown.
+    pub async fn get_registration_incident_notice(&self) -> Result<Option<String>> {
+        self.get_setting(REGISTRATION_INCIDENT_NOTICE_KEY).await
+    }
 
-                Ok(())
-            })
-            .await?;
+    /// Globally disable registration with the given notice, or re-enable it with `None`
+    pub async fn set_registration_incident_notice(&self, notice: Option<String>) -> Result<()> {
+        self.set_setting(REGISTRATION_INCIDENT_NOTICE_KEY, notice)
+            .await
+    }
 
-        let elapsed = start.elapsed();
-        debug!("initialized db in {}ms", elapsed.as_millis());
+    /// Get the latest release owners were notified about, if any
+    pub async fn get_notified_update_version(&self) -> Result<Option<String>> {
+        self.get_setting(NOTIFIED_UPDATE_VERSION_KEY).await
+    }
 
-        Ok(())
+    /// Set the latest release owners were notified about
+    pub async fn set_notified_update_version(&self, version: String) -> Result<()> {
+        self.set_setting(NOTIFIED_UPDATE_VERSION_KEY, Some(version))
+            .await
     }
 
-    /// Attempt to optimize the database.
-    ///
-    /// Applications that use long-lived database connections should run "PRAGMA optimize;" periodically, perhaps once per day or once per hour.
-    pub async fn optimize(&self) -> Result<()> {
-        self.connection
-            .call(move |connection| {
-                connection.execute("PRAGMA optimize", ())?;
-                Ok(())
-            })
-            .await?;
-        Ok(())
+    /// Get which releases update checks should consider
+    pub async fn get_update_preferences(&self) -> Result<UpdatePreferences> {
+        let channel: Option<String> = self.get_setting(RELEASE_CHANNEL_KEY).await?;
+        let ignored_versions: Option<String> =
+            self.get_setting(IGNORED_UPDATE_VERSIONS_KEY).await?;
+        let mut preferences = UpdatePreferences::default();
+        if let Some(channel) = channel.as_deref().and_then(ReleaseChannel::from_db_str) {
+            preferences.channel = channel;
+        }
+        if let Some(ignored_versions) = ignored_versions {
+            preferences.ignored_versions = ignored_versions
+                .lines()
+                .filter_map(|version| update_checker::parse_version(version).ok())
+                .collect();
+        }
+        Ok(preferences)
     }
 
-    pub async fn add_owner(&self, owner_id: u64) -> Result<()> {
-        self.connection
-            .call(move |connection| {
-                let mut statement = connection
-                    .prepare_cached("INSERT OR IGNORE INTO owner (owner_id) VALUES (:owner)")?;
-                statement.execute(named_params! {":owner": owner_id})?;
-                Ok(())
-            })
-            .await?;
+    /// Set which releases update checks should consider
+    pub async fn set_release_channel(&self, channel: ReleaseChannel) -> Result<()> {
+        self.set_setting(RELEASE_CHANNEL_KEY, Some(channel.as_db_str()))
+            .await
+    }
+
+    /// Set the releases update checks should never suggest upgrading to
+    pub async fn set_ignored_update_versions(&self, versions: Vec<Version>) -> Result<()> {
+        let versions = if versions.is_empty() {
+            None
+        } else {
+            Some(
+                versions
+                    .iter()
+                    .map(Version::to_string)
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )
+        };
+        self.set_setting(IGNORED_UPDATE_VERSIONS_KEY, versions)
+            .await
+    }
+
+    /// Get how a destructive owner command must be confirmed. Defaults to [`ConfirmationMode::Button`].
+    pub async fn get_confirmation_mode(
+        &self,
+        command: ConfirmableCommand,
+    ) -> Result<ConfirmationMode> {
+        let mode: Option<String> = self.get_setting(command.setting_key()).await?;
+        Ok(mode
+            .as_deref()
+            .and_then(ConfirmationMode::from_db_str)
+            .unwrap_or(ConfirmationMode::Button))
+    }
+
+    /// Set how a destructive owner command must be confirmed
+    pub async fn set_confirmation_mode(
+        &self,
+        command: ConfirmableCommand,
+        mode: ConfirmationMode,
+    ) -> Result<()> {
+        self.set_setting(command.setting_key(), Some(mode.as_db_str()))
+            .await
+    }
+
+    /// Get the owner-configured presence messages, if any
+    pub async fn get_presence_messages(&self) -> Result<Option<Vec<String>>> {
+        let messages: Option<String> = self.get_setting(PRESENCE_MESSAGES_KEY).await?;
+        Ok(messages.map(|messages| messages.lines().map(|line| line.to_string()).collect()))
+    }
+
+    /// Set or unset the presence messages. Messages may not contain newlines.
+    pub async fn set_presence_messages(&self, messages: Option<Vec<String>>) -> Result<()> {
+        self.set_setting(
+            PRESENCE_MESSAGES_KEY,
+            messages.map(|messages| messages.join("\n")),
+        )
+        .await?;
+        self.presence_config_changed.notify_waiters();
         Ok(())
     }
 
-    pub async fn delete_owner(&self, owner_id: u64) -> Result<()> {
-        self.connection
-            .call(move |connection| {
-                let mut statement =
-                    connection.prepare_cached("DELETE FROM owner WHERE owner_id = :owner")?;
-                statement.execute(named_params! {":owner": owner_id})?;
-                Ok(())
-            })
+    /// Get how long each presence message is shown before rotating to the next
+    pub async fn get_presence_rotation_interval(&self) -> Result<Duration> {
+        let seconds: Option<u64> = self.get_setting(PRESENCE_ROTATION_INTERVAL_KEY).await?;
+        Ok(Duration::from_secs(
+            seconds.unwrap_or(DEFAULT_PRESENCE_ROTATION_INTERVAL_S),
+        ))
+    }
+
+    /// Set how long each presence message is shown before rotating to the next
+    pub async fn set_presence_rotation_interval(&self, interval: Duration) -> Result<()> {
+        self.set_setting(PRESENCE_ROTATION_INTERVAL_KEY, Some(interval.as_secs()))
             .await?;
+        self.presence_config_changed.notify_waiters();
         Ok(())
     }
 
-    pub async fn set_discord_token(&self, discord_token: String) -> Result<()> {
-        self.connection
-            .call(move |connection| {
-                let mut statement = connection.prepare_cached(
-                    "INSERT OR REPLACE INTO settings (key, value) VALUES (:key, :value)",
-                )?;
-                statement
-                    .execute(named_params! {":key": DISCORD_TOKEN_KEY, ":value": discord_token})?;
-                Ok(())
-            })
-            .await?;
+    /// Wait until the presence messages or rotation interval change
+    pub async fn presence_config_changed(&self) {
+        self.presence_config_changed.notified().await
+    }
+
+    /// Set or unset bot log channel
+    pub async fn set_log_channel(&self, guild: GuildId, channel: Option<ChannelId>) -> Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        self.timed("set_log_channel", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO guild (guild_id, log_channel_id) VALUES (:guild, :channel) ON CONFLICT (guild_id) DO UPDATE SET log_channel_id = excluded.log_channel_id")?;
+            statement.execute(named_params! {":guild": guild.get(), ":channel": channel.map(ChannelId::get)})?;
+            if channel.is_some() {
+                connection.prepare_cached(COMPLETE_ONBOARDING_STEP_QUERY)?.execute(named_params! {":guild": guild.get(), ":step": OnboardingStep::LogChannelSet.as_db_str(), ":timestamp": timestamp})?;
+            }
+            Ok(())
+        })).await?;
         Ok(())
     }
 
-    pub async fn get_owners(&self) -> Result<Vec<u64>> {
-        self.connection
-            .call(move |connection| {
-                let mut statement = connection.prepare_cached("SELECT owner_id FROM owner")?;
-                let result = statement.query_map((), |row| {
-                    let owner_id: u64 = row.get(0)?;
-                    Ok(owner_id)
-                })?;
-                let mut vec = Vec::with_capacity(result.size_hint().0);
-                for row in result {
-                    vec.push(row?);
-                }
-                Ok(vec)
-            })
-            .await
+    /// Record that a guild completed an onboarding step. Most steps are recorded by the DB operations that complete them,
+    /// so this is only needed for steps that happen entirely outside the DB, like creating a registration post.
+    pub async fn complete_onboarding_step(
+        &self,
+        guild: GuildId,
+        step: OnboardingStep,
+    ) -> Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        self.timed("complete_onboarding_step", self.connection.call(move |connection| {
+            connection.prepare_cached(COMPLETE_ONBOARDING_STEP_QUERY)?.execute(named_params! {":guild": guild.get(), ":step": step.as_db_str(), ":timestamp": timestamp})?;
+            Ok(())
+        })).await
     }
 
-    pub async fn is_user_owner(&self, owner_id: u64) -> Result<bool> {
-        self.connection
-            .call(move |connection| {
-                let mut statement = connection
-                    .prepare_cached("SELECT EXISTS(SELECT * FROM owner WHERE owner_id = :owner)")?;
-                let owner_exists =
-                    statement.query_row(named_params! {":owner": owner_id}, |row| {
-                        let exists: bool = row.get(0)?;
-                        Ok(exists)
-                    })?;
-                Ok(owner_exists)
-            })
-            .await
+    /// Get the onboarding steps a guild has completed, along with when each was first completed
+    pub async fn get_onboarding_steps(&self, guild: GuildId) -> Result<Vec<(OnboardingStep, u64)>> {
+        self.timed(
+            "get_onboarding_steps",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "SELECT step, completed_unix_ms FROM onboarding_step WHERE guild_id = :guild",
+                )?; // uses primary key index
+                let result = statement.query_map(named_params! {":guild": guild.get()}, |row| {
+                    let step: String = row.get(0)?;
+                    let completed_unix_ms: u64 = row.get(1)?;
+                    Ok((step, completed_unix_ms))
+                })?;
+                let mut vec = Vec::with_capacity(result.size_hint().0);
+                for row in result {
+                    let (step, completed_unix_ms) = row?;
+                    // steps from a newer version of the bot are ignored
+                    if let Some(step) = OnboardingStep::from_db_str(&step) {
+                        vec.push((step, completed_unix_ms));
+                    }
+                }
+                Ok(vec)
+            }),
+        )
+        .await
     }
 
-    pub async fn get_discord_token(&self) -> Result<Option<String>> {
-        let discord_token = self
-            .connection
-            .call(move |connection| {
-                let result: Option<String> = connection
-                    .query_row(
-                        format!(r#"SELECT value FROM settings WHERE key = "{DISCORD_TOKEN_KEY}""#)
-                            .as_str(),
-                        [],
-                        |row| row.get(0),
-                    )
-                    .optional()?;
-                Ok(result)
-            })
-            .await?;
-        Ok(discord_token)
+    /// Get guilds an advisory applies to that haven't received it yet, along with their log channels. See
+    /// [`Advisory`] for what `settled_before_unix_ms` means.
+    pub async fn get_advisory_candidates(
+        &self,
+        advisory: Advisory,
+        settled_before_unix_ms: u64,
+    ) -> Result<Vec<(GuildId, Option<ChannelId>)>> {
+        self.timed("get_advisory_candidates", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached(advisory.candidate_query())?;
+            let result = statement.query_map(
+                named_params! {":advisory": advisory.as_db_str(), ":settled_before": settled_before_unix_ms},
+                |row| {
+                    let guild_id: u64 = row.get(0)?;
+                    let channel_id: Option<u64> = row.get(1)?;
+                    Ok((GuildId::new(guild_id), channel_id.map(ChannelId::new)))
+                },
+            )?;
+            let mut vec = Vec::with_capacity(result.size_hint().0);
+            for row in result {
+                vec.push(row?);
+            }
+            Ok(vec)
+        })).await
     }
 
-    /// Locally record that we've activated a license for a user
-    pub async fn activate_license(
+    /// Record that a guild received an advisory, so it's never sent again. Returns `false` if it was already recorded.
+    pub async fn record_advisory_delivery(
+        &self,
+        guild: GuildId,
+        advisory: Advisory,
+    ) -> Result<bool> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        self.timed("record_advisory_delivery", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT OR IGNORE INTO advisory_delivery (guild_id, advisory, delivered_unix_ms) VALUES (:guild, :advisory, :timestamp)")?;
+            let insert_count = statement.execute(named_params! {":guild": guild.get(), ":advisory": advisory.as_db_str(), ":timestamp": timestamp})?;
+            Ok(insert_count != 0)
+        })).await
+    }
+
+    /// Record that a user registered a license themselves, and which Jinxxy user bought it
+    pub async fn record_license_registration(
         &self,
         guild: GuildId,
         license_id: String,
-        license_activation_id: String,
         user_id: u64,
+        buyer_id: String,
     ) -> Result<()> {
-        self.connection.call(move |connection| {
-            let mut statement = connection.prepare_cached("INSERT OR IGNORE INTO license_activation (guild_id, license_id, license_activation_id, user_id) VALUES (:guild, :license, :activation, :user)")?;
-            statement.execute(named_params! {":guild": guild.get(), ":license": license_id, ":activation": license_activation_id, ":user": user_id})?;
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        self.timed("record_license_registration", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT OR REPLACE INTO license_registration (guild_id, license_id, user_id, buyer_id, registered_unix_ms) VALUES (:guild, :license, :user, :buyer, :timestamp)")?;
+            statement.execute(named_params! {":guild": guild.get(), ":license": license_id, ":user": user_id, ":buyer": buyer_id, ":timestamp": timestamp})?;
             Ok(())
-        }).await
+        })).await
     }
 
-    /// Locally record that we've deactivated a license for a user. Returns `true` if a row was found and deleted, or `false` if no row was found to delete.
-    pub async fn deactivate_license(
+    /// Find users that registered at least `limit` distinct licenses in a guild since the given time. Returns the guild,
+    /// the user, and how many licenses they registered.
+    pub async fn get_prolific_registrants(
         &self,
-        guild: GuildId,
-        license_id: String,
-        license_activation_id: String,
-        user_id: u64,
-    ) -> Result<bool> {
-        self.connection.call(move |connection| {
-            let mut statement = connection.prepare_cached("DELETE FROM license_activation WHERE guild_id = :guild AND license_id = :license AND license_activation_id = :activation AND user_id = :user")?;
-            let delete_count = statement.execute(named_params! {":guild": guild.get(), ":license": license_id, ":activation": license_activation_id, ":user": user_id})?;
-            Ok(delete_count != 0)
-        }).await
+        since_unix_ms: u64,
+        limit: u64,
+    ) -> Result<Vec<(GuildId, UserId, u64)>> {
+        self.timed("get_prolific_registrants", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT license_registration.guild_id, user_id, count(DISTINCT license_id) AS license_count FROM license_registration \
+                LEFT JOIN guild USING (guild_id) \
+                WHERE registered_unix_ms >= :since AND guild.deleted_unix_ms IS NULL \
+                GROUP BY license_registration.guild_id, user_id HAVING license_count >= :limit")?;
+            let result = statement.query_map(named_params! {":since": since_unix_ms, ":limit": limit}, |row| {
+                let guild_id: u64 = row.get(0)?;
+                let user_id: u64 = row.get(1)?;
+                Ok((GuildId::new(guild_id), UserId::new(user_id), row.get(2)?))
+            })?;
+            let mut vec = Vec::with_capacity(result.size_hint().0);
+            for row in result {
+                vec.push(row?);
+            }
+            Ok(vec)
+        })).await
     }
 
-    /// Locally check if a license is locked. This may be out of sync with Jinxxy!
-    pub async fn is_license_locked(&self, guild: GuildId, license_id: String) -> Result<bool> {
-        self.connection
-            .call(move |connection| {
-                let mut statement = connection.prepare_cached("SELECT EXISTS(SELECT * FROM license_activation WHERE guild_id = :guild AND license_id = :license AND user_id = 0)")?; //TODO: could use an index
-                let lock_exists = statement.query_row(
-                    named_params! {":guild": guild.get(), ":license": license_id},
-                    |row| {
-                        let exists: bool = row.get(0)?;
-                        Ok(exists)
-                    },
-                )?;
-                Ok(lock_exists)
-            })
-            .await
+    /// Find Jinxxy buyers whose licenses were registered by at least `limit` distinct users in a guild since the given
+    /// time. Returns the guild, the buyer, and the users.
+    pub async fn get_shared_buyers(
+        &self,
+        since_unix_ms: u64,
+        limit: u64,
+    ) -> Result<Vec<(GuildId, String, Vec<UserId>)>> {
+        self.timed("get_shared_buyers", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT license_registration.guild_id, buyer_id, group_concat(DISTINCT user_id) FROM license_registration \
+                LEFT JOIN guild USING (guild_id) \
+                WHERE registered_unix_ms >= :since AND guild.deleted_unix_ms IS NULL \
+                GROUP BY license_registration.guild_id, buyer_id HAVING count(DISTINCT user_id) >= :limit")?;
+            let result = statement.query_map(named_params! {":since": since_unix_ms, ":limit": limit}, |row| {
+                let guild_id: u64 = row.get(0)?;
+                let user_ids: String = row.get(2)?;
+                let user_ids = user_ids
+                    .split(',')
+                    .filter_map(|user_id| user_id.parse().ok())
+                    .map(UserId::new)
+                    .collect();
+                Ok((GuildId::new(guild_id), row.get(1)?, user_ids))
+            })?;
+            let mut vec = Vec::with_capacity(result.size_hint().0);
+            for row in result {
+                vec.push(row?);
+            }
+            Ok(vec)
+        })).await
+    }
+
+    /// Record that a guild was alerted about suspicious activity, unless it was already alerted about the same thing
+    /// within `cooldown_ms`. Returns `false` if the alert was suppressed, in which case it shouldn't be sent.
+    pub async fn record_suspicious_activity_alert(
+        &self,
+        guild: GuildId,
+        kind: &'static str,
+        subject: String,
+        cooldown_ms: u64,
+    ) -> Result<bool> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        self.timed("record_suspicious_activity_alert", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO suspicious_activity_alert (guild_id, kind, subject, alerted_unix_ms) VALUES (:guild, :kind, :subject, :timestamp) \
+                ON CONFLICT (guild_id, kind, subject) DO UPDATE SET alerted_unix_ms = excluded.alerted_unix_ms WHERE alerted_unix_ms <= :cutoff")?;
+            let change_count = statement.execute(named_params! {":guild": guild.get(), ":kind": kind, ":subject": subject, ":timestamp": timestamp, ":cutoff": timestamp.saturating_sub(cooldown_ms)})?;
+            Ok(change_count != 0)
+        })).await
     }
 
-    /// Set Jinxxy API key for this guild
-    pub async fn set_jinxxy_api_key(&self, guild: GuildId, api_key: String) -> Result<()> {
-        let api_key_clone = api_key.clone();
-        self.connection.call(move |connection| {
-            let mut statement = connection.prepare_cached("INSERT INTO guild (guild_id, jinxxy_api_key) VALUES (:guild, :api_key) ON CONFLICT (guild_id) DO UPDATE SET jinxxy_api_key = excluded.jinxxy_api_key")?;
-            statement.execute(named_params! {":guild": guild.get(), ":api_key": api_key_clone})?;
+    /// Set or unset this guild as a test guild
+    pub async fn set_test(&self, guild: GuildId, test: bool) -> Result<()> {
+        self.timed("set_test", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO guild (guild_id, test) VALUES (:guild, :test) ON CONFLICT (guild_id) DO UPDATE SET test = excluded.test")?;
+            statement.execute(named_params! {":guild": guild.get(), ":test": test})?;
             Ok(())
-        }).await?;
-        self.api_key_cache.insert(guild, Some(api_key));
+        })).await?;
+        // this moves the guild's rows in or out of the production counts
+        self.reconcile_counters().await?;
         Ok(())
     }
 
-    /// Get Jinxxy API key for this guild
-    pub async fn get_jinxxy_api_key(&self, guild: GuildId) -> Result<Option<String>> {
-        if let Some(api_key) = self.api_key_cache.get(&guild) {
-            // cached read
-            Ok(api_key.value().clone())
-        } else {
-            // cache miss
-            let api_key = self
-                .connection
-                .call(move |connection| {
-                    let mut statement = connection
-                        .prepare_cached("SELECT jinxxy_api_key FROM guild WHERE guild_id = ?")?;
-                    let result: Option<String> = statement
-                        .query_row([guild.get()], |row| row.get(0))
-                        .optional()?;
-                    Ok(result)
-                })
-                .await?;
-            self.api_key_cache.insert(guild, api_key.clone());
-            Ok(api_key)
-        }
+    /// Get the role granted for every product in this guild, if one is set
+    pub async fn get_blanket_role(&self, guild: GuildId) -> Result<Option<RoleId>> {
+        self.timed(
+            "get_blanket_role",
+            self.connection.call(move |connection| {
+                let mut statement = connection
+                    .prepare_cached("SELECT blanket_role_id FROM guild WHERE guild_id = :guild")?;
+                let role: Option<Option<u64>> = statement
+                    .query_row(named_params! {":guild": guild.get()}, |row| row.get(0))
+                    .optional()?;
+                Ok(role.flatten().map(RoleId::new))
+            }),
+        )
+        .await
     }
 
-    /// link a Jinxxy product and a role
-    pub async fn link_product(
-        &self,
-        guild: GuildId,
-        product_id: String,
-        role: RoleId,
-    ) -> Result<()> {
-        self.connection.call(move |connection| {
-            let mut statement = connection.prepare_cached("INSERT OR IGNORE INTO product_role (guild_id, product_id, role_id) VALUES (:guild, :product, :role)")?;
-            statement.execute(named_params! {":guild": guild.get(), ":product": product_id, ":role": role.get()})?;
+    /// Set or unset the role granted for every product in this guild
+    pub async fn set_blanket_role(&self, guild: GuildId, role: Option<RoleId>) -> Result<()> {
+        self.timed("set_blanket_role", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO guild (guild_id, blanket_role_id) VALUES (:guild, :role) ON CONFLICT (guild_id) DO UPDATE SET blanket_role_id = excluded.blanket_role_id")?;
+            statement.execute(named_params! {":guild": guild.get(), ":role": role.map(RoleId::get)})?;
             Ok(())
-        }).await
+        })).await
     }
 
-    /// unlink a Jinxxy product and a role. Returns `true` if a row was found and deleted, or `false` if no row was found to delete.
-    pub async fn unlink_product(
-        &self,
-        guild: GuildId,
-        product_id: String,
-        role: RoleId,
-    ) -> Result<bool> {
-        self.connection.call(move |connection| {
-            let mut statement = connection.prepare_cached("DELETE FROM product_role WHERE guild_id = :guild AND product_id = :product AND role_id = :role")?;
-            let delete_count = statement.execute(named_params! {":guild": guild.get(), ":product": product_id, ":role": role.get()})?;
-            Ok(delete_count != 0)
-        }).await
+    /// Set or unset this guild as opted out of global statistics
+    pub async fn set_stats_opt_out(&self, guild: GuildId, opt_out: bool) -> Result<()> {
+        self.timed("set_stats_opt_out", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO guild (guild_id, stats_opt_out) VALUES (:guild, :opt_out) ON CONFLICT (guild_id) DO UPDATE SET stats_opt_out = excluded.stats_opt_out")?;
+            statement.execute(named_params! {":guild": guild.get(), ":opt_out": opt_out})?;
+            Ok(())
+        })).await?;
+        // this moves the guild's rows in or out of the production counts
+        self.reconcile_counters().await?;
+        Ok(())
     }
 
-    /// Get roles for a product ID
-    pub async fn get_roles(&self, guild: GuildId, product_id: String) -> Result<Vec<RoleId>> {
-        self.connection
-            .call(move |connection| {
-                let mut statement = connection.prepare_cached("SELECT role_id FROM product_role WHERE guild_id = :guild AND product_id = :product")?; // uses `role_lookup` index
-                let result = statement.query_map(
-                    named_params! {":guild": guild.get(), ":product": product_id},
-                    |row| {
-                        let role_id: u64 = row.get(0)?;
-                        Ok(RoleId::new(role_id))
-                    },
-                )?;
-                let mut vec = Vec::with_capacity(result.size_hint().0);
-                for row in result {
-                    vec.push(row?);
-                }
-                Ok(vec)
-            })
-            .await
+    /// Set whether roles are restored when a user with recorded license activations rejoins this guild
+    pub async fn set_restore_roles(&self, guild: GuildId, restore_roles: bool) -> Result<()> {
+        self.timed("set_restore_roles", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO guild (guild_id, restore_roles) VALUES (:guild, :restore_roles) ON CONFLICT (guild_id) DO UPDATE SET restore_roles = excluded.restore_roles")?;
+            statement.execute(named_params! {":guild": guild.get(), ":restore_roles": restore_roles})?;
+            Ok(())
+        })).await
     }
 
-    /// get all links
-    pub async fn get_links(&self, guild: GuildId) -> Result<Vec<(String, RoleId)>> {
-        self.connection
-            .call(move |connection| {
-                let mut statement = connection.prepare_cached(
-                    "SELECT product_id, role_id FROM product_role WHERE guild_id = ?",
-                )?; //TODO: could use an index
-                let result = statement.query_map([guild.get()], |row| {
-                    let product_id: String = row.get(0)?;
-                    let role_id: u64 = row.get(1)?;
-                    Ok((product_id, RoleId::new(role_id)))
-                })?;
-                let mut vec = Vec::with_capacity(result.size_hint().0);
-                for row in result {
-                    vec.push(row?);
-                }
-                Ok(vec)
-            })
-            .await
+    /// Check if roles are restored when a user with recorded license activations rejoins this guild
+    pub async fn get_restore_roles(&self, guild: GuildId) -> Result<bool> {
+        self.timed(
+            "get_restore_roles",
+            self.connection.call(move |connection| {
+                let mut statement = connection
+                    .prepare_cached("SELECT restore_roles FROM guild WHERE guild_id = :guild")?;
+                let restore_roles = statement
+                    .query_row(named_params! {":guild": guild.get()}, |row| {
+                        let restore_roles: bool = row.get(0)?;
+                        Ok(restore_roles)
+                    })
+                    .optional()?;
+                Ok(restore_roles.unwrap_or(false))
+            }),
+        )
+        .await
     }
 
-    /// Locally get all licences a users has been recorded to activate. This may be out of sync with Jinxxy!
-    pub async fn get_user_licenses(&self, guild: GuildId, user_id: u64) -> Result<Vec<String>> {
-        self.connection
-            .call(move |connection| {
-                let mut statement = connection.prepare_cached("SELECT license_id FROM license_activation WHERE guild_id = :guild AND user_id = :user")?; //TODO: could use an index
-                let result = statement.query_map(
-                    named_params! {":guild": guild.get(), ":user": user_id},
-                    |row| {
-                        let license_id: String = row.get(0)?;
-                        Ok(license_id)
-                    },
+    /// Set whether license registration is paused in this guild
+    pub async fn set_registrations_paused(&self, guild: GuildId, paused: bool) -> Result<()> {
+        self.timed("set_registrations_paused", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO guild (guild_id, registrations_paused) VALUES (:guild, :paused) ON CONFLICT (guild_id) DO UPDATE SET registrations_paused = excluded.registrations_paused")?;
+            statement.execute(named_params! {":guild": guild.get(), ":paused": paused})?;
+            Ok(())
+        })).await
+    }
+
+    /// Check if license registration is paused in this guild
+    pub async fn get_registrations_paused(&self, guild: GuildId) -> Result<bool> {
+        self.timed(
+            "get_registrations_paused",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "SELECT registrations_paused FROM guild WHERE guild_id = :guild",
                 )?;
-                let mut vec = Vec::with_capacity(result.size_hint().0);
-                for row in result {
-                    vec.push(row?);
-                }
-                Ok(vec)
-            })
-            .await
+                let paused = statement
+                    .query_row(named_params! {":guild": guild.get()}, |row| {
+                        let paused: bool = row.get(0)?;
+                        Ok(paused)
+                    })
+                    .optional()?;
+                Ok(paused.unwrap_or(false))
+            }),
+        )
+        .await
     }
 
-    /// Locally get all activations for a user and license has been recorded to activate. This may be out of sync with Jinxxy!
-    pub async fn get_user_license_activations(
+    /// Set how counts are shown in this guild's public outputs
+    pub async fn set_public_count_redaction(
         &self,
         guild: GuildId,
-        user_id: u64,
-        license_id: String,
-    ) -> Result<Vec<String>> {
-        self.connection
-            .call(move |connection| {
-                let mut statement = connection.prepare_cached("SELECT license_activation_id FROM license_activation WHERE guild_id = :guild AND user_id = :user AND license_id = :license")?; //TODO: could use an index
-                let result = statement.query_map(
-                    named_params! {":guild": guild.get(), ":user": user_id, ":license": license_id},
-                    |row| {
-                        let activation_id: String = row.get(0)?;
-                        Ok(activation_id)
-                    },
-                )?;
-                let mut vec = Vec::with_capacity(result.size_hint().0);
-                for row in result {
-                    vec.push(row?);
-                }
-                Ok(vec)
-            })
-            .await
+        redaction: CountRedaction,
+    ) -> Result<()> {
+        self.timed("set_public_count_redaction", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO guild (guild_id, public_count_redaction) VALUES (:guild, :redaction) ON CONFLICT (guild_id) DO UPDATE SET public_count_redaction = excluded.public_count_redaction")?;
+            statement.execute(named_params! {":guild": guild.get(), ":redaction": redaction.as_db_str()})?;
+            Ok(())
+        })).await
     }
 
-    /// Locally get all users that have activated the given license. This may be out of sync with Jinxxy!
-    pub async fn get_license_users(&self, guild: GuildId, license_id: String) -> Result<Vec<u64>> {
-        self.connection
-            .call(move |connection| {
-                let mut statement = connection.prepare_cached("SELECT user_id FROM license_activation WHERE guild_id = :guild AND license_id = :license")?; //TODO: could use an index
-                let result = statement.query_map(
-                    named_params! {":guild": guild.get(), ":license": license_id},
-                    |row| {
-                        let user_id: u64 = row.get(0)?;
-                        Ok(user_id)
-                    },
+    /// Get how counts are shown in this guild's public outputs
+    pub async fn get_public_count_redaction(&self, guild: GuildId) -> Result<CountRedaction> {
+        self.timed(
+            "get_public_count_redaction",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "SELECT public_count_redaction FROM guild WHERE guild_id = :guild",
                 )?;
-                let mut vec = Vec::with_capacity(result.size_hint().0);
-                for row in result {
-                    vec.push(row?);
-                }
-                Ok(vec)
-            })
-            .await
+                let redaction: Option<Option<String>> = statement
+                    .query_row(named_params! {":guild": guild.get()}, |row| row.get(0))
+                    .optional()?;
+                Ok(redaction
+                    .flatten()
+                    .and_then(|redaction| CountRedaction::from_db_str(&redaction))
+                    .unwrap_or_default())
+            }),
+        )
+        .await
     }
 
-    /// Get DB size in bytes
-    pub async fn size(&self) -> Result<u64> {
-        self.connection.call(move |connection| {
-            let result: u64 = connection.query_row("SELECT page_count * page_size as size FROM pragma_page_count(), pragma_page_size()", [], |row| row.get(0))?;
-            Ok(result)
-        }).await
+    /// Set how this guild's bot log is told about license activations
+    pub async fn set_notification_digest(
+        &self,
+        guild: GuildId,
+        digest: NotificationDigest,
+    ) -> Result<()> {
+        self.timed("set_notification_digest", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO guild (guild_id, notification_digest) VALUES (:guild, :digest) ON CONFLICT (guild_id) DO UPDATE SET notification_digest = excluded.notification_digest")?;
+            statement.execute(named_params! {":guild": guild.get(), ":digest": digest.as_db_str()})?;
+            Ok(())
+        })).await
     }
 
-    /// Get count of license activations
-    pub async fn license_activation_count(&self) -> Result<u64> {
-        self.connection.call(move |connection| {
-            let result: u64 = connection.query_row("SELECT count(*) FROM license_activation LEFT JOIN guild USING (guild_id) WHERE guild.test = 0", [], |row| row.get(0))?;
-            Ok(result)
-        }).await
+    /// Get how this guild's bot log is told about license activations
+    pub async fn get_notification_digest(&self, guild: GuildId) -> Result<NotificationDigest> {
+        self.timed(
+            "get_notification_digest",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "SELECT notification_digest FROM guild WHERE guild_id = :guild",
+                )?;
+                let digest: Option<Option<String>> = statement
+                    .query_row(named_params! {":guild": guild.get()}, |row| row.get(0))
+                    .optional()?;
+                Ok(digest
+                    .flatten()
+                    .and_then(|digest| NotificationDigest::from_db_str(&digest))
+                    .unwrap_or_default())
+            }),
+        )
+        .await
     }
 
-    /// Get count of configured guilds
-    pub async fn guild_count(&self) -> Result<u64> {
-        self.connection
-            .call(move |connection| {
-                let result: u64 = connection.query_row(
-                    "SELECT count(*) FROM guild WHERE test = 0",
-                    [],
-                    |row| row.get(0),
-                )?;
-                Ok(result)
-            })
-            .await
+    /// Hold an activation for this guild's next notification digest
+    pub async fn add_digest_entry(&self, guild: GuildId, entry: DigestEntry) -> Result<()> {
+        // role IDs are stored space-separated, as they're only ever read back all together
+        let role_ids = entry
+            .roles
+            .iter()
+            .map(|role| role.get().to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.timed("add_digest_entry", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO notification_digest_entry (guild_id, created_unix_ms, user_id, product_name, role_ids) VALUES (:guild, :created, :user, :product_name, :roles)")?;
+            statement.execute(named_params! {
+                ":guild": guild.get(),
+                ":created": entry.created_unix_ms,
+                ":user": entry.user_id.get(),
+                ":product_name": entry.product_name,
+                ":roles": role_ids,
+            })?;
+            Ok(())
+        })).await
     }
 
-    /// Get count of distinct bot log channels
-    pub async fn log_channel_count(&self) -> Result<u64> {
-        self.connection
-            .call(move |connection| {
-                let result: u64 = connection.query_row(
-                    "SELECT count(DISTINCT log_channel_id) FROM guild WHERE test = 0",
-                    [],
-                    |row| row.get(0),
-                )?;
-                Ok(result)
-            })
-            .await
+    /// Get every guild with activations waiting for a digest, along with its digest setting and when its oldest waiting
+    /// activation happened
+    pub async fn get_pending_digests(&self) -> Result<Vec<(GuildId, NotificationDigest, u64)>> {
+        self.timed("get_pending_digests", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT guild_id, notification_digest, min(created_unix_ms) FROM notification_digest_entry LEFT JOIN guild USING (guild_id) GROUP BY guild_id")?;
+            let rows = statement.query_map((), |row| {
+                let digest: Option<String> = row.get(1)?;
+                let digest = digest
+                    .as_deref()
+                    .and_then(NotificationDigest::from_db_str)
+                    .unwrap_or_default();
+                Ok((GuildId::new(row.get(0)?), digest, row.get(2)?))
+            })?;
+            let mut vec = Vec::new();
+            for row in rows {
+                vec.push(row?);
+            }
+            Ok(vec)
+        })).await
     }
 
-    /// Get count of product->role mappings
-    pub async fn product_role_count(&self) -> Result<u64> {
-        self.connection.call(move |connection| {
-            let result: u64 = connection.query_row("SELECT count(*) FROM product_role LEFT JOIN guild USING (guild_id) WHERE guild.test = 0", [], |row| row.get(0))?;
-            Ok(result)
-        }).await
+    /// Remove and return every activation waiting for this guild's digest, oldest first
+    pub async fn take_digest_entries(&self, guild: GuildId) -> Result<Vec<DigestEntry>> {
+        self.timed(
+            "take_digest_entries",
+            self.connection.call(move |connection| {
+                let transaction = connection.transaction()?;
+                let entries = {
+                    let mut statement = transaction.prepare_cached("SELECT created_unix_ms, user_id, product_name, role_ids FROM notification_digest_entry WHERE guild_id = :guild ORDER BY created_unix_ms")?; // uses `notification_digest_guild_lookup` index
+                    let rows =
+                        statement.query_map(named_params! {":guild": guild.get()}, |row| {
+                            let role_ids: String = row.get(3)?;
+                            Ok(DigestEntry {
+                                created_unix_ms: row.get(0)?,
+                                user_id: UserId::new(row.get(1)?),
+                                product_name: row.get(2)?,
+                                roles: role_ids
+                                    .split_whitespace()
+                                    .filter_map(|role_id| role_id.parse::<u64>().ok())
+                                    .filter(|role_id| *role_id != 0)
+                                    .map(RoleId::new)
+                                    .collect(),
+                            })
+                        })?;
+                    let mut vec = Vec::new();
+                    for row in rows {
+                        vec.push(row?);
+                    }
+                    let mut statement = transaction.prepare_cached(
+                        "DELETE FROM notification_digest_entry WHERE guild_id = :guild",
+                    )?;
+                    statement.execute(named_params! {":guild": guild.get()})?;
+                    vec
+                };
+                transaction.commit()?;
+                Ok(entries)
+            }),
+        )
+        .await
     }
 
-    /// Get count of license activations in a guild
-    pub async fn guild_license_activation_count(&self, guild: GuildId) -> Result<u64> {
-        self.connection.call(move |connection| {
-            let mut statement = connection.prepare_cached("SELECT count(*) FROM license_activation LEFT JOIN guild USING (guild_id) WHERE guild.guild_id = :guild")?;
-            let result: u64 = statement.query_row(named_params! {":guild": guild.get()}, |row| row.get(0))?;
-            Ok(result)
-        }).await
+    /// Set (or unset) the language this guild's translated messages are shown in
+    pub async fn set_language(&self, guild: GuildId, language: Option<Language>) -> Result<()> {
+        self.timed("set_language", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO guild (guild_id, language) VALUES (:guild, :language) ON CONFLICT (guild_id) DO UPDATE SET language = excluded.language")?;
+            statement.execute(named_params! {":guild": guild.get(), ":language": language.map(Language::locale)})?;
+            Ok(())
+        })).await
     }
 
-    /// Get count of product->role mappings in a guild
-    pub async fn guild_product_role_count(&self, guild: GuildId) -> Result<u64> {
-        self.connection.call(move |connection| {
-            let mut statement = connection.prepare_cached("SELECT count(*) FROM product_role LEFT JOIN guild USING (guild_id) WHERE guild.guild_id = :guild")?;
-            let result: u64 = statement.query_row(named_params! {":guild": guild.get()}, |row| row.get(0))?;
-            Ok(result)
-        }).await
+    /// Get the language this guild's translated messages are shown in, if it has chosen one
+    pub async fn get_language(&self, guild: GuildId) -> Result<Option<Language>> {
+        self.timed(
+            "get_language",
+            self.connection.call(move |connection| {
+                let mut statement = connection
+                    .prepare_cached("SELECT language FROM guild WHERE guild_id = :guild")?;
+                let language: Option<Option<String>> = statement
+                    .query_row(named_params! {":guild": guild.get()}, |row| row.get(0))
+                    .optional()?;
+                Ok(language
+                    .flatten()
+                    .and_then(|language| Language::from_db_str(&language)))
+            }),
+        )
+        .await
     }
 
-    /// Get bot log channel
-    pub async fn get_log_channel(&self, guild: GuildId) -> Result<Option<ChannelId>> {
-        let channel_id = self
-            .connection
-            .call(move |connection| {
-                let mut statement = connection
-                    .prepare_cached("SELECT log_channel_id FROM guild WHERE guild_id = ?")?;
-                let result: Option<Option<u64>> = statement
-                    .query_row([guild.get()], |row| row.get(0))
+    /// Set (or unset) the channel activation milestones are announced in
+    pub async fn set_milestone_channel(
+        &self,
+        guild: GuildId,
+        channel: Option<ChannelId>,
+    ) -> Result<()> {
+        self.timed("set_milestone_channel", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO guild (guild_id, milestone_channel_id) VALUES (:guild, :channel) ON CONFLICT (guild_id) DO UPDATE SET milestone_channel_id = excluded.milestone_channel_id")?;
+            statement.execute(named_params! {":guild": guild.get(), ":channel": channel.map(ChannelId::get)})?;
+            Ok(())
+        })).await
+    }
+
+    /// Get the channel activation milestones are announced in, if any
+    pub async fn get_milestone_channel(&self, guild: GuildId) -> Result<Option<ChannelId>> {
+        self.timed(
+            "get_milestone_channel",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "SELECT milestone_channel_id FROM guild WHERE guild_id = :guild",
+                )?;
+                let channel_id: Option<Option<u64>> = statement
+                    .query_row(named_params! {":guild": guild.get()}, |row| row.get(0))
                     .optional()?;
-                // inner optional is for if the guild has no log channel set
-                // outer optional is for if the guild does not exist in our DB
-                Ok(result.flatten())
-            })
-            .await?;
-        Ok(channel_id.map(ChannelId::new))
+                Ok(channel_id.flatten().map(ChannelId::new))
+            }),
+        )
+        .await
     }
 
-    /// Get all bot log channels.
-    /// If `TEST_ONLY` is true, then only returns non-production servers. Otherwise, returns all servers.
-    pub async fn get_log_channels<const TEST_ONLY: bool>(&self) -> Result<Vec<ChannelId>> {
-        self.connection.call(move |connection| {
-            let mut statement = if TEST_ONLY {
-                // only non-production servers
-                connection.prepare_cached("SELECT DISTINCT log_channel_id FROM guild WHERE log_channel_id IS NOT NULL AND guild.test != 0")
-            } else {
-                // all servers, including production servers
-                connection.prepare_cached("SELECT DISTINCT log_channel_id FROM guild WHERE log_channel_id IS NOT NULL")
-            }?;
-            let result = statement.query_and_then((), |row| row.get(0).map(|id| ChannelId::new(id)))?;
-            let mut vec = Vec::with_capacity(result.size_hint().0);
-            for row in result {
+    /// Set (or unset) the channel new purchases are posted in. Orders seen by a previous feed are forgotten, so the next
+    /// poll starts fresh.
+    pub async fn set_sales_feed_channel(
+        &self,
+        guild: GuildId,
+        channel: Option<ChannelId>,
+    ) -> Result<()> {
+        self.timed("set_sales_feed_channel", self.connection.call(move |connection| {
+            let transaction = connection.transaction()?;
+            {
+                let mut statement = transaction.prepare_cached("INSERT INTO guild (guild_id, sales_feed_channel_id) VALUES (:guild, :channel) ON CONFLICT (guild_id) DO UPDATE SET sales_feed_channel_id = excluded.sales_feed_channel_id, sales_feed_polled_unix_ms = NULL")?;
+                statement.execute(named_params! {":guild": guild.get(), ":channel": channel.map(ChannelId::get)})?;
+                let mut statement = transaction.prepare_cached("DELETE FROM sales_feed_order WHERE guild_id = :guild")?;
+                statement.execute(named_params! {":guild": guild.get()})?;
+            }
+            transaction.commit()?;
+            Ok(())
+        })).await
+    }
+
+    /// Get every guild with a sales feed, along with its channel. Guilds without an API key or marked for deletion are
+    /// skipped.
+    pub async fn get_sales_feeds(&self) -> Result<Vec<(GuildId, ChannelId)>> {
+        self.timed("get_sales_feeds", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT guild_id, sales_feed_channel_id FROM guild WHERE sales_feed_channel_id IS NOT NULL AND jinxxy_api_key IS NOT NULL AND deleted_unix_ms IS NULL")?;
+            let rows = statement.query_map((), |row| Ok((GuildId::new(row.get(0)?), ChannelId::new(row.get(1)?))))?;
+            let mut vec = Vec::new();
+            for row in rows {
                 vec.push(row?);
             }
             Ok(vec)
-        }).await
+        })).await
     }
 
-    /// Set or unset bot log channel
-    pub async fn set_log_channel(&self, guild: GuildId, channel: Option<ChannelId>) -> Result<()> {
-        self.connection.call(move |connection| {
-            let mut statement = connection.prepare_cached("INSERT INTO guild (guild_id, log_channel_id) VALUES (:guild, :channel) ON CONFLICT (guild_id) DO UPDATE SET log_channel_id = excluded.log_channel_id")?;
-            statement.execute(named_params! {":guild": guild.get(), ":channel": channel.map(ChannelId::get)})?;
+    /// Record the orders from a sales feed poll, and get the ones that weren't in any earlier poll. The first poll after
+    /// the feed is set up returns nothing, so the store's order history isn't posted all at once.
+    pub async fn record_sales_feed_orders(
+        &self,
+        guild: GuildId,
+        order_ids: Vec<String>,
+    ) -> Result<Vec<String>> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        self.timed("record_sales_feed_orders", self.connection.call(move |connection| {
+            let transaction = connection.transaction()?;
+            let mut new_order_ids = Vec::new();
+            {
+                let mut statement = transaction.prepare_cached("SELECT sales_feed_polled_unix_ms IS NOT NULL FROM guild WHERE guild_id = :guild")?;
+                let primed: bool = statement.query_row(named_params! {":guild": guild.get()}, |row| row.get(0)).optional()?.unwrap_or(false);
+                let mut insert = transaction.prepare_cached("INSERT OR IGNORE INTO sales_feed_order (guild_id, order_id, seen_unix_ms) VALUES (:guild, :order, :timestamp)")?;
+                let mut update = transaction.prepare_cached("UPDATE sales_feed_order SET seen_unix_ms = :timestamp WHERE guild_id = :guild AND order_id = :order")?;
+                for order_id in order_ids {
+                    let inserted = insert.execute(named_params! {":guild": guild.get(), ":order": &order_id, ":timestamp": timestamp})? != 0;
+                    if inserted {
+                        if primed {
+                            new_order_ids.push(order_id);
+                        }
+                    } else {
+                        update.execute(named_params! {":guild": guild.get(), ":order": &order_id, ":timestamp": timestamp})?;
+                    }
+                }
+                // orders that fell out of the latest poll are never coming back, as the API lists the newest orders first
+                let mut statement = transaction.prepare_cached("DELETE FROM sales_feed_order WHERE guild_id = :guild AND seen_unix_ms < :timestamp")?;
+                statement.execute(named_params! {":guild": guild.get(), ":timestamp": timestamp})?;
+                let mut statement = transaction.prepare_cached("UPDATE guild SET sales_feed_polled_unix_ms = :timestamp WHERE guild_id = :guild")?;
+                statement.execute(named_params! {":guild": guild.get(), ":timestamp": timestamp})?;
+            }
+            transaction.commit()?;
+            Ok(new_order_ids)
+        })).await
+    }
+
+    /// Set whether users with recorded license activations leaving this guild is logged to the bot log channel
+    pub async fn set_log_member_leave(&self, guild: GuildId, log_member_leave: bool) -> Result<()> {
+        self.timed("set_log_member_leave", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO guild (guild_id, log_member_leave) VALUES (:guild, :log_member_leave) ON CONFLICT (guild_id) DO UPDATE SET log_member_leave = excluded.log_member_leave")?;
+            statement.execute(named_params! {":guild": guild.get(), ":log_member_leave": log_member_leave})?;
             Ok(())
-        }).await?;
-        Ok(())
+        })).await
     }
 
-    /// Set or unset this guild as a test guild
-    pub async fn set_test(&self, guild: GuildId, test: bool) -> Result<()> {
-        self.connection.call(move |connection| {
-            let mut statement = connection.prepare_cached("INSERT INTO guild (guild_id, test) VALUES (:guild, :test) ON CONFLICT (guild_id) DO UPDATE SET test = excluded.test")?;
-            statement.execute(named_params! {":guild": guild.get(), ":test": test})?;
+    /// Set whether changes to this guild's products are logged to the bot log channel
+    pub async fn set_log_product_changes(
+        &self,
+        guild: GuildId,
+        log_product_changes: bool,
+    ) -> Result<()> {
+        self.timed("set_log_product_changes", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("INSERT INTO guild (guild_id, log_product_changes) VALUES (:guild, :log_product_changes) ON CONFLICT (guild_id) DO UPDATE SET log_product_changes = excluded.log_product_changes")?;
+            statement.execute(named_params! {":guild": guild.get(), ":log_product_changes": log_product_changes})?;
             Ok(())
-        }).await?;
-        Ok(())
+        })).await
+    }
+
+    /// Check if changes to this guild's products are logged to the bot log channel
+    pub async fn get_log_product_changes(&self, guild: GuildId) -> Result<bool> {
+        self.timed(
+            "get_log_product_changes",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached(
+                    "SELECT log_product_changes FROM guild WHERE guild_id = :guild",
+                )?;
+                let log_product_changes = statement
+                    .query_row(named_params! {":guild": guild.get()}, |row| {
+                        let log_product_changes: bool = row.get(0)?;
+                        Ok(log_product_changes)
+                    })
+                    .optional()?;
+                Ok(log_product_changes.unwrap_or(false))
+            }),
+        )
+        .await
+    }
+
+    /// Get everything stored on a guild's row, or `None` if the guild has never been set up
+    pub async fn get_guild_config(&self, guild: GuildId) -> Result<Option<GuildConfig>> {
+        self.timed("get_guild_config", self.connection.call(move |connection| {
+            let mut statement = connection.prepare_cached("SELECT jinxxy_api_key IS NOT NULL, jinxxy_user_id, log_channel_id, blanket_role_id, milestone_channel_id, sales_feed_channel_id, language, public_count_redaction, test, owner, stats_opt_out, restore_roles, log_member_leave, log_product_changes, registrations_paused, deleted_unix_ms, notification_digest FROM guild WHERE guild_id = :guild")?;
+            let config = statement
+                .query_row(named_params! {":guild": guild.get()}, |row| {
+                    let language: Option<String> = row.get(6)?;
+                    let public_count_redaction: Option<String> = row.get(7)?;
+                    let notification_digest: Option<String> = row.get(16)?;
+                    Ok(GuildConfig {
+                        api_key_set: row.get(0)?,
+                        jinxxy_user_id: row.get(1)?,
+                        log_channel: row.get::<_, Option<u64>>(2)?.map(ChannelId::new),
+                        blanket_role: row.get::<_, Option<u64>>(3)?.map(RoleId::new),
+                        milestone_channel: row.get::<_, Option<u64>>(4)?.map(ChannelId::new),
+                        sales_feed_channel: row.get::<_, Option<u64>>(5)?.map(ChannelId::new),
+                        language: language.as_deref().and_then(Language::from_db_str),
+                        public_count_redaction: public_count_redaction
+                            .as_deref()
+                            .and_then(CountRedaction::from_db_str),
+                        notification_digest: notification_digest
+                            .as_deref()
+                            .and_then(NotificationDigest::from_db_str),
+                        test: row.get(8)?,
+                        owner: row.get(9)?,
+                        stats_opt_out: row.get(10)?,
+                        restore_roles: row.get(11)?,
+                        log_member_leave: row.get(12)?,
+                        log_product_changes: row.get(13)?,
+                        registrations_paused: row.get(14)?,
+                        deleted_unix_ms: row.get(15)?,
+                    })
+                })
+                .optional()?;
+            Ok(config)
+        })).await
+    }
+
+    /// Check if users with recorded license activations leaving this guild is logged to the bot log channel
+    pub async fn get_log_member_leave(&self, guild: GuildId) -> Result<bool> {
+        self.timed(
+            "get_log_member_leave",
+            self.connection.call(move |connection| {
+                let mut statement = connection
+                    .prepare_cached("SELECT log_member_leave FROM guild WHERE guild_id = :guild")?;
+                let log_member_leave = statement
+                    .query_row(named_params! {":guild": guild.get()}, |row| {
+                        let log_member_leave: bool = row.get(0)?;
+                        Ok(log_member_leave)
+                    })
+                    .optional()?;
+                Ok(log_member_leave.unwrap_or(false))
+            }),
+        )
+        .await
     }
 
     /// Check if a guild is a test guild
     pub async fn is_test_guild(&self, guild: GuildId) -> Result<bool> {
-        self.connection
-            .call(move |connection| {
+        self.timed(
+            "is_test_guild",
+            self.connection.call(move |connection| {
                 let mut statement =
                     connection.prepare_cached("SELECT test FROM guild WHERE guild_id = :guild")?;
                 let test = statement
@@ -618,24 +5688,47 @@ impl JinxDb {
                     })
                     .optional()?;
                 Ok(test.unwrap_or(false))
-            })
-            .await
+            }),
+        )
+        .await
     }
 
     /// Set or unset this guild as an owner guild (gets extra slash commands)
     pub async fn set_owner_guild(&self, guild: GuildId, owner: bool) -> Result<()> {
-        self.connection.call(move |connection| {
+        self.timed("set_owner_guild", self.connection.call(move |connection| {
             let mut statement = connection.prepare_cached("INSERT INTO guild (guild_id, owner) VALUES (:guild, :owner) ON CONFLICT (guild_id) DO UPDATE SET owner = excluded.owner")?;
             statement.execute(named_params! {":guild": guild.get(), ":owner": owner})?;
             Ok(())
-        }).await?;
+        })).await?;
         Ok(())
     }
 
+    /// Get the bot log channels of all owner guilds that have one
+    pub async fn get_owner_log_channels(&self) -> Result<Vec<(GuildId, ChannelId)>> {
+        self.timed(
+            "get_owner_log_channels",
+            self.connection.call(move |connection| {
+                let mut statement = connection.prepare_cached("SELECT guild_id, log_channel_id FROM guild WHERE owner != 0 AND log_channel_id IS NOT NULL AND deleted_unix_ms IS NULL")?;
+                let result = statement.query_map((), |row| {
+                    let guild_id: u64 = row.get(0)?;
+                    let channel_id: u64 = row.get(1)?;
+                    Ok((GuildId::new(guild_id), ChannelId::new(channel_id)))
+                })?;
+                let mut vec = Vec::with_capacity(result.size_hint().0);
+                for row in result {
+                    vec.push(row?);
+                }
+                Ok(vec)
+            }),
+        )
+        .await
+    }
+
     /// Check if a guild is an owner guild (gets extra slash commands)
     pub async fn is_owner_guild(&self, guild: GuildId) -> Result<bool> {
-        self.connection
-            .call(move |connection| {
+        self.timed(
+            "is_owner_guild",
+            self.connection.call(move |connection| {
                 let mut statement =
                     connection.prepare_cached("SELECT owner FROM guild WHERE guild_id = :guild")?;
                 let owner = statement
@@ -645,7 +5738,1304 @@ impl JinxDb {
                     })
                     .optional()?;
                 Ok(owner.unwrap_or(false))
+            }),
+        )
+        .await
+    }
+}
+
+/// Run an async test against a fresh in-memory DB
+#[cfg(test)]
+pub(crate) fn test_db<F, Fut>(test: F) -> Fut::Output
+where
+    F: FnOnce(JinxDb) -> Fut,
+    Fut: Future,
+{
+    test::block_on(async { test(JinxDb::open_path(":memory:").await.unwrap()).await })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Run a future to completion on a single-threaded runtime
+    pub(super) fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    /// Run `EXPLAIN QUERY PLAN` on a query against a fresh in-memory DB, returning the plan details
+    fn query_plan(query: &'static str) -> Vec<String> {
+        test_db(|db| async move {
+            db.connection
+                .call(move |connection| {
+                    let mut statement =
+                        connection.prepare(format!("EXPLAIN QUERY PLAN {query}").as_str())?;
+                    let mut rows = statement.raw_query();
+                    let mut plan = Vec::new();
+                    while let Some(row) = rows.next()? {
+                        let detail: String = row.get(3)?;
+                        plan.push(detail);
+                    }
+                    Ok(plan)
+                })
+                .await
+                .unwrap()
+        })
+    }
+
+    /// Assert that a query is satisfied entirely by index lookups and never scans a table
+    fn assert_uses_index(query: &'static str, index: &str) {
+        let plan = query_plan(query);
+        assert!(
+            plan.iter()
+                .all(|detail| !detail.starts_with("SCAN") || detail == "SCAN CONSTANT ROW"),
+            "query plan contains a scan: {plan:?}"
+        );
+        assert!(
+            plan.iter().any(|detail| detail.contains(index)),
+            "query plan does not use {index}: {plan:?}"
+        );
+    }
+
+    #[test]
+    fn test_is_license_locked_uses_index() {
+        assert_uses_index(
+            "SELECT EXISTS(SELECT * FROM license_activation WHERE guild_id = :guild AND license_id = :license AND user_id = 0)",
+            "user_license_lookup",
+        );
+    }
+
+    #[test]
+    fn test_get_user_guilds_uses_index() {
+        assert_uses_index(
+            "SELECT DISTINCT guild_id FROM license_activation WHERE user_id = :user",
+            "user_lookup",
+        );
+    }
+
+    #[test]
+    fn test_get_due_scheduled_expiries_uses_index() {
+        assert_uses_index(
+            "SELECT guild_id, user_id, license_id, product_id, product_version_id, expires_unix_ms FROM scheduled_expiry LEFT JOIN guild USING (guild_id) \
+                WHERE NOT revoked AND expires_unix_ms < :before AND deleted_unix_ms IS NULL ORDER BY expires_unix_ms",
+            "scheduled_expiry_lookup",
+        );
+    }
+
+    #[test]
+    fn test_get_expiring_licenses_uses_index() {
+        assert_uses_index(
+            "SELECT guild_id, license_id, product_id, product_version_id, expires_unix_ms FROM license_expiry LEFT JOIN guild USING (guild_id) \
+                WHERE expires_unix_ms < :before AND deleted_unix_ms IS NULL ORDER BY expires_unix_ms",
+            "license_expiry_lookup",
+        );
+    }
+
+    #[test]
+    fn test_get_activated_users_uses_index() {
+        assert_uses_index(
+            "SELECT DISTINCT user_id FROM license_activation WHERE guild_id = :guild AND user_id > :after ORDER BY user_id LIMIT :limit",
+            "user_license_lookup",
+        );
+    }
+
+    #[test]
+    fn test_get_user_licenses_uses_index() {
+        assert_uses_index(
+            "SELECT license_id FROM license_activation WHERE guild_id = :guild AND user_id = :user",
+            "user_license_lookup",
+        );
+    }
+
+    #[test]
+    fn test_get_license_users_uses_index() {
+        assert_uses_index(
+            "SELECT user_id, created_unix_ms FROM license_activation WHERE guild_id = :guild AND license_id = :license",
+            "sqlite_autoindex_license_activation_1",
+        );
+    }
+
+    #[test]
+    fn test_get_guild_activation_events_uses_index() {
+        assert_uses_index(
+            "SELECT sequence, created_unix_ms, guild_id, kind, user_id, license_id, product_id, role_id FROM event \
+                WHERE guild_id = :guild AND sequence > :since AND kind IN (:activation, :deactivation) AND user_id != :locking_user ORDER BY sequence LIMIT :limit",
+            "event_guild_lookup",
+        );
+    }
+
+    #[test]
+    fn test_get_roles_uses_index() {
+        assert_uses_index(
+            "SELECT role_id FROM product_role WHERE guild_id = :guild AND product_id = :product",
+            "product_role",
+        );
+    }
+
+    #[test]
+    fn test_get_links_uses_index() {
+        assert_uses_index(
+            "SELECT product_id, role_id FROM product_role WHERE guild_id = ?",
+            "product_role",
+        );
+    }
+
+    #[test]
+    fn test_get_api_health_uses_index() {
+        assert_uses_index(
+            "SELECT endpoint, latency_ms, success FROM api_request_log WHERE timestamp_unix_ms >= :since ORDER BY endpoint, latency_ms",
+            "api_request_log_time",
+        );
+    }
+
+    #[test]
+    fn test_get_top_products_uses_index() {
+        assert_uses_index(
+            "SELECT product_id, COUNT(*) FROM product_activation_log WHERE guild_id = :guild AND timestamp_unix_ms >= :since GROUP BY product_id",
+            "product_activation_log_lookup",
+        );
+    }
+
+    #[test]
+    fn test_percentile() {
+        let latencies: Vec<u64> = (1..=100).collect();
+        assert_eq!(percentile(&latencies, 50), Duration::from_millis(50));
+        assert_eq!(percentile(&latencies, 95), Duration::from_millis(95));
+        assert_eq!(percentile(&latencies, 99), Duration::from_millis(99));
+        assert_eq!(percentile(&[7], 99), Duration::from_millis(7));
+        assert_eq!(percentile(&[], 50), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_search_products_uses_index() {
+        assert_uses_index(
+            "SELECT product_id, product_name FROM product WHERE guild_id = :guild AND product_name LIKE :pattern ESCAPE '\\' ORDER BY product_name LIKE :prefix_pattern ESCAPE '\\' DESC, product_name LIMIT :limit",
+            "sqlite_autoindex_product_1",
+        );
+    }
+
+    #[test]
+    fn test_get_daily_activations() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            db.increment_product_activation_count(guild, "a".to_string())
+                .await
+                .unwrap();
+            db.increment_product_activation_count(guild, "b".to_string())
+                .await
+                .unwrap();
+            db.increment_product_activation_count(GuildId::new(2), "a".to_string())
+                .await
+                .unwrap();
+
+            let days = db.get_daily_activations(guild, 1).await.unwrap();
+            assert!((28..=32).contains(&days.len()), "{} days", days.len());
+            assert!(days[..days.len() - 1].iter().all(|(_, count)| *count == 0));
+            assert_eq!(days.last().unwrap().1, 2);
+        });
+    }
+
+    #[test]
+    fn test_activation_timestamps() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            db.activate_license(guild, "license".to_string(), "a".to_string(), 5)
+                .await
+                .unwrap();
+            db.activate_license(guild, "license".to_string(), "b".to_string(), 5)
+                .await
+                .unwrap();
+            db.activate_license(
+                guild,
+                "license".to_string(),
+                "lock".to_string(),
+                LOCKING_USER_ID,
+            )
+            .await
+            .unwrap();
+
+            // both activations collapse into one entry, which is locked
+            let states = db.get_user_license_states(guild, 5).await.unwrap();
+            assert_eq!(states.len(), 1);
+            let (license_id, locked, created_unix_ms) = &states[0];
+            assert_eq!(license_id, "license");
+            assert!(locked);
+            assert!(created_unix_ms.is_some());
+
+            let users = db
+                .get_license_users(guild, "license".to_string())
+                .await
+                .unwrap();
+            assert_eq!(users.len(), 2);
+            assert_eq!(users[0].0, LOCKING_USER_ID);
+            assert_eq!(users[1].0, 5);
+            assert!(users
+                .iter()
+                .all(|(_, created_unix_ms)| created_unix_ms.is_some()));
+        });
+    }
+
+    #[test]
+    fn test_search_products() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            let products = ["Big Hat", "Hat", "100% Hat", "Shoes", "Hat_Pack"]
+                .iter()
+                .enumerate()
+                .map(|(index, name)| PartialProduct {
+                    id: index.to_string(),
+                    name: name.to_string(),
+                    ..Default::default()
+                })
+                .collect();
+            db.replace_products(guild, products).await.unwrap();
+
+            let names = |results: Vec<(String, String)>| -> Vec<String> {
+                results.into_iter().map(|(_, name)| name).collect()
+            };
+            assert_eq!(
+                names(
+                    db.search_products(guild, "hat".to_string(), 10)
+                        .await
+                        .unwrap()
+                ),
+                ["Hat", "Hat_Pack", "100% Hat", "Big Hat"]
+            );
+            assert_eq!(
+                names(
+                    db.search_products(guild, "0%".to_string(), 10)
+                        .await
+                        .unwrap()
+                ),
+                ["100% Hat"]
+            );
+            assert_eq!(
+                names(
+                    db.search_products(guild, "t_p".to_string(), 10)
+                        .await
+                        .unwrap()
+                ),
+                ["Hat_Pack"]
+            );
+            assert_eq!(
+                db.search_products(guild, "hat".to_string(), 2)
+                    .await
+                    .unwrap()
+                    .len(),
+                2
+            );
+        });
+    }
+
+    #[test]
+    fn test_registration_blocks() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            for _ in 1..USER_REGISTRATION_FAILURE_LIMIT {
+                assert_eq!(
+                    db.record_registration_failure(guild, 2).await.unwrap(),
+                    None
+                );
+            }
+            assert_eq!(db.get_registration_block(guild, 2).await.unwrap(), None);
+            let block = db
+                .record_registration_failure(guild, 2)
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(!block.guild_wide);
+            assert_eq!(
+                db.get_registration_block(guild, 2).await.unwrap(),
+                Some(block)
+            );
+            // the block is only started once, and only affects that user in that guild
+            assert_eq!(
+                db.record_registration_failure(guild, 2).await.unwrap(),
+                None
+            );
+            assert_eq!(db.get_registration_block(guild, 3).await.unwrap(), None);
+            assert_eq!(
+                db.get_registration_block(GuildId::new(4), 2).await.unwrap(),
+                None
+            );
+
+            // enough failures from different users blocks everyone
+            let mut guild_block = None;
+            for user_id in 0..GUILD_REGISTRATION_FAILURE_LIMIT {
+                guild_block = guild_block.or(db
+                    .record_registration_failure(guild, 100 + user_id)
+                    .await
+                    .unwrap());
+            }
+            let guild_block = guild_block.unwrap();
+            assert!(guild_block.guild_wide);
+            assert_eq!(
+                db.get_registration_block(guild, 3).await.unwrap(),
+                Some(guild_block)
+            );
+        });
+    }
+
+    #[test]
+    fn test_suspicious_activity() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            for (license, buyer) in [("a", "buyer"), ("b", "other"), ("c", "other")] {
+                db.record_license_registration(guild, license.to_string(), 2, buyer.to_string())
+                    .await
+                    .unwrap();
+            }
+            // the same buyer's licenses going to two different users
+            db.record_license_registration(guild, "d".to_string(), 3, "buyer".to_string())
+                .await
+                .unwrap();
+
+            assert_eq!(
+                db.get_prolific_registrants(0, 3).await.unwrap(),
+                [(guild, UserId::new(2), 3)]
+            );
+            assert!(db.get_prolific_registrants(0, 4).await.unwrap().is_empty());
+            let shared = db.get_shared_buyers(0, 2).await.unwrap();
+            assert_eq!(shared.len(), 1);
+            assert_eq!(shared[0].1, "buyer");
+            let mut user_ids = shared[0].2.clone();
+            user_ids.sort_unstable();
+            assert_eq!(user_ids, [UserId::new(2), UserId::new(3)]);
+
+            // alerts are suppressed during their cooldown
+            assert!(db
+                .record_suspicious_activity_alert(guild, "kind", "2".to_string(), 60_000)
+                .await
+                .unwrap());
+            assert!(!db
+                .record_suspicious_activity_alert(guild, "kind", "2".to_string(), 60_000)
+                .await
+                .unwrap());
+            assert!(db
+                .record_suspicious_activity_alert(guild, "kind", "2".to_string(), 0)
+                .await
+                .unwrap());
+        });
+    }
+
+    #[test]
+    fn test_events() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            let role = RoleId::new(2);
+            db.link_product(guild, "product".to_string(), role)
+                .await
+                .unwrap();
+            // relinking doesn't change anything, so it isn't an event
+            db.link_product(guild, "product".to_string(), role)
+                .await
+                .unwrap();
+            db.activate_license(guild, "license".to_string(), "activation".to_string(), 3)
+                .await
+                .unwrap();
+            db.deactivate_license(guild, "license".to_string(), "activation".to_string(), 3)
+                .await
+                .unwrap();
+            db.remove_role_references(guild, role).await.unwrap();
+
+            let events = db.get_events(0, 100).await.unwrap();
+            let kinds: Vec<EventKind> = events.iter().map(|event| event.kind).collect();
+            assert_eq!(
+                kinds,
+                [
+                    EventKind::Link,
+                    EventKind::Activation,
+                    EventKind::Deactivation,
+                    EventKind::Unlink
+                ]
+            );
+            assert_eq!(events[1].user_id, Some(3));
+            assert_eq!(events[3].role_id, Some(role));
+
+            // resuming from a sequence number only returns newer events
+            let events = db.get_events(events[1].sequence, 1).await.unwrap();
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].kind, EventKind::Deactivation);
+        });
+    }
+
+    #[test]
+    fn test_registration_posts() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            let channel = ChannelId::new(2);
+            db.add_registration_post(guild, channel, MessageId::new(4))
+                .await
+                .unwrap();
+            db.add_registration_post(guild, channel, MessageId::new(3))
+                .await
+                .unwrap();
+            // adding the same post again is harmless
+            db.add_registration_post(guild, channel, MessageId::new(3))
+                .await
+                .unwrap();
+            db.add_registration_post(GuildId::new(5), channel, MessageId::new(6))
+                .await
+                .unwrap();
+            assert_eq!(
+                db.get_registration_posts(guild).await.unwrap(),
+                vec![(channel, MessageId::new(3)), (channel, MessageId::new(4))]
+            );
+            db.delete_registration_post(guild, MessageId::new(3))
+                .await
+                .unwrap();
+            assert_eq!(
+                db.get_registration_posts(guild).await.unwrap(),
+                vec![(channel, MessageId::new(4))]
+            );
+        });
+    }
+
+    #[test]
+    fn test_notification_digest() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            assert_eq!(
+                db.get_notification_digest(guild).await.unwrap(),
+                NotificationDigest::Off
+            );
+            db.set_notification_digest(guild, NotificationDigest::Daily)
+                .await
+                .unwrap();
+            assert_eq!(
+                db.get_notification_digest(guild).await.unwrap(),
+                NotificationDigest::Daily
+            );
+
+            let entry = |created_unix_ms, roles: Vec<RoleId>| DigestEntry {
+                created_unix_ms,
+                user_id: UserId::new(2),
+                product_name: "Hat".to_string(),
+                roles,
+            };
+            db.add_digest_entry(guild, entry(20, vec![])).await.unwrap();
+            db.add_digest_entry(guild, entry(10, vec![RoleId::new(3), RoleId::new(4)]))
+                .await
+                .unwrap();
+            assert_eq!(
+                db.get_pending_digests().await.unwrap(),
+                vec![(guild, NotificationDigest::Daily, 10)]
+            );
+            assert_eq!(
+                db.take_digest_entries(guild).await.unwrap(),
+                vec![
+                    entry(10, vec![RoleId::new(3), RoleId::new(4)]),
+                    entry(20, vec![])
+                ]
+            );
+            assert!(db.get_pending_digests().await.unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_activation_hooks() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            let product = "product".to_string();
+            db.add_activation_hook(
+                guild,
+                product.clone(),
+                ActivationHookKind::Webhook,
+                "https://example.com/hook".to_string(),
+                Some(SecretString::new("secret".to_string())),
+            )
+            .await
+            .unwrap();
+            db.add_activation_hook(
+                guild,
+                product.clone(),
+                ActivationHookKind::AddToThread,
+                "2".to_string(),
+                None,
+            )
+            .await
+            .unwrap();
+            let hooks = db.get_activation_hooks(guild, product).await.unwrap();
+            assert_eq!(hooks.len(), 2);
+            assert_eq!(hooks[0].position, 1);
+            assert_eq!(
+                hooks[0]
+                    .signing_secret
+                    .as_ref()
+                    .map(|secret| secret.expose_secret().as_str()),
+                Some("secret")
+            );
+            assert_eq!(hooks[1].position, 2);
+            assert!(hooks[1].signing_secret.is_none());
+        });
+    }
+
+    #[test]
+    fn test_scheduled_expiry() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            let user = UserId::new(2);
+            let expiry = ScheduledExpiry {
+                guild_id: guild,
+                user_id: user,
+                license_id: "license".to_string(),
+                product_id: "product".to_string(),
+                product_version_id: None,
+                expires_unix_ms: 100,
+            };
+            db.schedule_expiry(expiry.clone()).await.unwrap();
+            // registering again doesn't push the expiry back
+            db.schedule_expiry(ScheduledExpiry {
+                expires_unix_ms: 500,
+                ..expiry.clone()
+            })
+            .await
+            .unwrap();
+            assert!(db.get_due_scheduled_expiries(100).await.unwrap().is_empty());
+            assert_eq!(
+                db.get_due_scheduled_expiries(101).await.unwrap(),
+                vec![expiry.clone()]
+            );
+            assert!(db
+                .get_expired_licenses(guild, user, 99)
+                .await
+                .unwrap()
+                .is_empty());
+            assert_eq!(
+                db.get_expired_licenses(guild, user, 100).await.unwrap(),
+                vec!["license".to_string()]
+            );
+
+            db.set_scheduled_expiry_revoked(guild, user, "license".to_string())
+                .await
+                .unwrap();
+            assert!(db.get_due_scheduled_expiries(101).await.unwrap().is_empty());
+            assert_eq!(
+                db.get_expired_licenses(guild, user, 100).await.unwrap(),
+                vec!["license".to_string()]
+            );
+        });
+    }
+
+    #[test]
+    fn test_event_webhooks() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            // only events after the webhook is set are delivered
+            db.activate_license(guild, "old".to_string(), "activation".to_string(), 3)
+                .await
+                .unwrap();
+            db.set_event_webhook(
+                guild,
+                "https://example.com".to_string(),
+                SecretString::new("secret".to_string()),
+            )
+            .await
+            .unwrap();
+            db.activate_license(guild, "new".to_string(), "activation".to_string(), 3)
+                .await
+                .unwrap();
+            db.activate_license(
+                guild,
+                "new".to_string(),
+                "lock".to_string(),
+                LOCKING_USER_ID,
+            )
+            .await
+            .unwrap();
+            db.activate_license(
+                GuildId::new(2),
+                "other".to_string(),
+                "activation".to_string(),
+                3,
+            )
+            .await
+            .unwrap();
+
+            let webhooks = db.get_due_event_webhooks().await.unwrap();
+            assert_eq!(webhooks.len(), 1);
+            assert_eq!(webhooks[0].signing_secret.expose_secret(), "secret");
+            let events = db
+                .get_guild_activation_events(guild, webhooks[0].cursor, 100)
+                .await
+                .unwrap();
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].license_id.as_deref(), Some("new"));
+
+            // a failure holds the webhook back until it's time to retry
+            db.record_event_webhook_failure(guild, Duration::from_secs(60))
+                .await
+                .unwrap();
+            assert!(db.get_due_event_webhooks().await.unwrap().is_empty());
+            db.advance_event_webhook(guild, events[0].sequence)
+                .await
+                .unwrap();
+            let webhooks = db.get_due_event_webhooks().await.unwrap();
+            assert_eq!(webhooks[0].cursor, events[0].sequence);
+            assert_eq!(webhooks[0].failure_count, 0);
+
+            assert!(db.delete_event_webhook(guild).await.unwrap());
+            assert!(!db.delete_event_webhook(guild).await.unwrap());
+        });
+    }
+
+    #[test]
+    fn test_message_variants() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            let key = MessageKey::RegistrationFailure;
+
+            // with no alternatives, the control is the only option
+            let control = db.get_message_variant(guild, key).await.unwrap();
+            assert!(control.text.is_none());
+
+            // assignments are sticky until the variant is retired
+            let variant_id = db
+                .add_message_variant(key, "try again".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                db.get_message_variant(guild, key).await.unwrap().variant_id,
+                control.variant_id
+            );
+            assert!(!db.retire_message_variant(control.variant_id).await.unwrap());
+            assert!(db.retire_message_variant(variant_id).await.unwrap());
+            assert!(!db.retire_message_variant(variant_id).await.unwrap());
+
+            // a follow-up is only counted once per showing
+            db.record_message_followup(guild, 2, key, true)
+                .await
+                .unwrap();
+            db.record_message_shown(guild, 2, key, control.variant_id)
+                .await
+                .unwrap();
+            db.record_message_followup(guild, 2, key, true)
+                .await
+                .unwrap();
+            db.record_message_followup(guild, 2, key, false)
+                .await
+                .unwrap();
+            let stats = db.get_message_variant_stats(key).await.unwrap();
+            assert_eq!(stats.len(), 2);
+            assert!(stats[0].text.is_none());
+            assert_eq!(stats[0].shown_count, 1);
+            assert_eq!(stats[0].followup_success_count, 1);
+            assert_eq!(stats[0].followup_failure_count, 0);
+            assert!(!stats[1].active);
+        });
+    }
+
+    #[test]
+    fn test_guild_tables_complete() {
+        test_db(|db| async move {
+            let mut tables: Vec<String> = db
+                .connection
+                .call(|connection| {
+                    let mut statement = connection.prepare("SELECT sqlite_schema.name FROM sqlite_schema JOIN pragma_table_info(sqlite_schema.name) AS column ON column.name = 'guild_id' WHERE sqlite_schema.type = 'table'")?;
+                    let rows = statement.query_map((), |row| row.get(0))?;
+                    let mut vec = Vec::new();
+                    for row in rows {
+                        vec.push(row?);
+                    }
+                    Ok(vec)
+                })
+                .await
+                .unwrap();
+            tables.sort();
+            let mut expected = GUILD_TABLES.map(str::to_string).to_vec();
+            expected.sort();
+            assert_eq!(tables, expected);
+        });
+    }
+
+    #[test]
+    fn test_soft_delete_guild() {
+        test_db(|db| async move {
+            let restored = GuildId::new(1);
+            let purged = GuildId::new(2);
+            for guild in [restored, purged] {
+                db.link_product(guild, "product".to_string(), RoleId::new(3))
+                    .await
+                    .unwrap();
+                db.soft_delete_guild(guild).await.unwrap();
+            }
+            assert!(db.restore_guild(restored).await.unwrap());
+            assert!(!db.restore_guild(restored).await.unwrap());
+
+            assert_eq!(
+                db.purge_deleted_guilds(0).await.unwrap(),
+                Vec::<GuildId>::new()
+            );
+            assert_eq!(
+                db.purge_deleted_guilds(i64::MAX as u64).await.unwrap(),
+                vec![purged]
+            );
+            assert_eq!(db.get_links(restored).await.unwrap().len(), 1);
+            assert!(db.get_links(purged).await.unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_announcements() {
+        test_db(|db| async move {
+            let production = GuildId::new(1);
+            let test = GuildId::new(2);
+            db.set_log_channel(production, Some(ChannelId::new(3)))
+                .await
+                .unwrap();
+            db.set_log_channel(test, Some(ChannelId::new(4)))
+                .await
+                .unwrap();
+            db.set_test(test, true).await.unwrap();
+            assert_eq!(
+                db.get_announcement_targets(AnnouncementAudience::All)
+                    .await
+                    .unwrap()
+                    .len(),
+                2
+            );
+            assert_eq!(
+                db.get_announcement_targets(AnnouncementAudience::Test)
+                    .await
+                    .unwrap(),
+                vec![(test, ChannelId::new(4))]
+            );
+
+            let announcement_id = db
+                .add_announcement(
+                    None,
+                    "message".to_string(),
+                    AnnouncementAudience::Production,
+                    UserId::new(5),
+                    1000,
+                )
+                .await
+                .unwrap();
+            assert!(db.get_due_announcements(999).await.unwrap().is_empty());
+            assert_eq!(
+                db.get_due_announcements(1000).await.unwrap(),
+                vec![announcement_id]
+            );
+            assert!(db
+                .claim_announcement(announcement_id, 1000)
+                .await
+                .unwrap()
+                .is_some());
+            assert!(db
+                .claim_announcement(announcement_id, 1001)
+                .await
+                .unwrap()
+                .is_none());
+            assert!(db.get_due_announcements(2000).await.unwrap().is_empty());
+
+            db.record_announcement_delivery(announcement_id, production, ChannelId::new(3), None)
+                .await
+                .unwrap();
+            db.record_announcement_delivery(
+                announcement_id,
+                test,
+                ChannelId::new(4),
+                Some("error".to_string()),
+            )
+            .await
+            .unwrap();
+            let announcement = db.get_announcement(announcement_id).await.unwrap().unwrap();
+            assert_eq!(announcement.audience, AnnouncementAudience::Production);
+            assert_eq!(announcement.sent_unix_ms, Some(1000));
+            assert_eq!(announcement.delivered_count, 1);
+            assert_eq!(announcement.failed_count, 1);
+            assert_eq!(
+                db.get_announcement_failures(announcement_id).await.unwrap(),
+                vec![(test, ChannelId::new(4), "error".to_string())]
+            );
+        });
+    }
+
+    #[test]
+    fn test_cache_invalidation() {
+        block_on(async {
+            let path = std::env::temp_dir().join(format!(
+                "jinx-test-cache-invalidation-{}.sqlite",
+                std::process::id()
+            ));
+            let first = JinxDb::open_path(&path).await.unwrap();
+            let second = JinxDb::open_path(&path).await.unwrap();
+            let guild = GuildId::new(1);
+            first
+                .set_jinxxy_api_key(guild, SecretString::new("key".to_string()), None)
+                .await
+                .unwrap();
+            // an instance ignores its own invalidations
+            assert!(first.poll_cache_invalidations().await.unwrap().is_empty());
+            assert_eq!(
+                second.poll_cache_invalidations().await.unwrap(),
+                vec![guild]
+            );
+            assert!(second.poll_cache_invalidations().await.unwrap().is_empty());
+            drop(first);
+            drop(second);
+            let _ = std::fs::remove_file(path);
+        });
+    }
+
+    #[test]
+    fn test_sales_feed_orders() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            db.set_sales_feed_channel(guild, Some(ChannelId::new(2)))
+                .await
+                .unwrap();
+            let orders = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect();
+            // the first poll only records existing orders
+            assert!(db
+                .record_sales_feed_orders(guild, orders(&["a", "b"]))
+                .await
+                .unwrap()
+                .is_empty());
+            assert_eq!(
+                db.record_sales_feed_orders(guild, orders(&["c", "a", "b"]))
+                    .await
+                    .unwrap(),
+                vec!["c"]
+            );
+            assert!(db
+                .record_sales_feed_orders(guild, orders(&["c", "a"]))
+                .await
+                .unwrap()
+                .is_empty());
+        });
+    }
+
+    #[test]
+    fn test_onboarding_steps() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            let steps = |steps: Vec<(OnboardingStep, u64)>| {
+                steps.into_iter().map(|(step, _)| step).collect::<Vec<_>>()
+            };
+            db.set_log_channel(guild, None).await.unwrap();
+            assert!(db.get_onboarding_steps(guild).await.unwrap().is_empty());
+            db.set_log_channel(guild, Some(ChannelId::new(2)))
+                .await
+                .unwrap();
+            // locking a license isn't an activation
+            db.activate_license(
+                guild,
+                "license".to_string(),
+                "lock".to_string(),
+                LOCKING_USER_ID,
+            )
+            .await
+            .unwrap();
+            assert_eq!(
+                steps(db.get_onboarding_steps(guild).await.unwrap()),
+                vec![OnboardingStep::LogChannelSet]
+            );
+            db.link_product(guild, "product".to_string(), RoleId::new(3))
+                .await
+                .unwrap();
+            db.activate_license(guild, "license".to_string(), "activation".to_string(), 4)
+                .await
+                .unwrap();
+            let mut completed = steps(db.get_onboarding_steps(guild).await.unwrap());
+            completed
+                .sort_by_key(|step| OnboardingStep::ALL.iter().position(|known| known == step));
+            assert_eq!(
+                completed,
+                vec![
+                    OnboardingStep::LogChannelSet,
+                    OnboardingStep::FirstLink,
+                    OnboardingStep::FirstActivation
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn test_advisories() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            let settled_before = i64::MAX as u64;
+            db.set_log_channel(guild, None).await.unwrap();
+            db.activate_license(guild, "license".to_string(), "activation".to_string(), 2)
+                .await
+                .unwrap();
+            assert!(db
+                .get_advisory_candidates(Advisory::ActivationsWithoutLogChannel, 0)
+                .await
+                .unwrap()
+                .is_empty());
+            assert_eq!(
+                db.get_advisory_candidates(Advisory::ActivationsWithoutLogChannel, settled_before)
+                    .await
+                    .unwrap(),
+                vec![(guild, None)]
+            );
+            // registrations mean the setup works, so setup advisories don't apply
+            assert!(db
+                .get_advisory_candidates(Advisory::StoreWithoutLinks, settled_before)
+                .await
+                .unwrap()
+                .is_empty());
+            assert!(db
+                .record_advisory_delivery(guild, Advisory::ActivationsWithoutLogChannel)
+                .await
+                .unwrap());
+            assert!(!db
+                .record_advisory_delivery(guild, Advisory::ActivationsWithoutLogChannel)
+                .await
+                .unwrap());
+            assert!(db
+                .get_advisory_candidates(Advisory::ActivationsWithoutLogChannel, settled_before)
+                .await
+                .unwrap()
+                .is_empty());
+        });
+    }
+
+    #[test]
+    fn test_remove_role_references() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            let dead_role = RoleId::new(2);
+            let live_role = RoleId::new(3);
+            db.link_product(guild, "a".to_string(), dead_role)
+                .await
+                .unwrap();
+            db.link_product(guild, "b".to_string(), live_role)
+                .await
+                .unwrap();
+            db.add_link_rule(guild, "*".to_string(), dead_role)
+                .await
+                .unwrap();
+            db.set_blanket_role(guild, Some(dead_role)).await.unwrap();
+            let mut references = db.get_role_references().await.unwrap();
+            references.sort();
+            assert_eq!(references, vec![(guild, dead_role), (guild, live_role)]);
+
+            assert_eq!(
+                db.remove_role_references(guild, dead_role).await.unwrap(),
+                (vec!["a".to_string()], true)
+            );
+            assert_eq!(
+                db.get_role_references().await.unwrap(),
+                vec![(guild, live_role)]
+            );
+            assert_eq!(db.get_blanket_role(guild).await.unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn test_daily_metrics() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            db.set_log_channel(guild, None).await.unwrap();
+            db.increment_product_activation_count(guild, "a".to_string())
+                .await
+                .unwrap();
+            let now_unix_ms = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64;
+            let sample = |latency_ms, success| ApiSample {
+                timestamp_unix_ms: now_unix_ms,
+                endpoint: "GET /me",
+                latency_ms,
+                success,
+            };
+            db.record_api_samples(vec![sample(100, true), sample(300, false)])
+                .await
+                .unwrap();
+
+            let today = now_unix_ms / MS_PER_DAY;
+            assert!(db.record_daily_metrics(today).await.unwrap());
+            // a day is only ever recorded once
+            assert!(!db.record_daily_metrics(today).await.unwrap());
+            let metrics = db.get_daily_metrics(1).await.unwrap();
+            assert_eq!(metrics.len(), 1);
+            assert_eq!(metrics[0].activation_count, 1);
+            assert_eq!(metrics[0].api_request_count, 2);
+            assert_eq!(metrics[0].api_error_count, 1);
+            assert_eq!(metrics[0].api_latency_p95, Duration::from_millis(300));
+            assert_eq!(metrics[0].guild_count, 1);
+        });
+    }
+
+    #[test]
+    fn test_replace_products_diff() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            let product = |id: &str, name: &str| (id.to_string(), name.to_string());
+            let partial_product = |id: &str, name: &str| PartialProduct {
+                id: id.to_string(),
+                name: name.to_string(),
+                ..Default::default()
+            };
+            let diff = db
+                .replace_products(
+                    guild,
+                    vec![partial_product("a", "A"), partial_product("b", "B")],
+                )
+                .await
+                .unwrap();
+            assert!(!diff.had_products);
+            assert_eq!(diff.added.len(), 2);
+
+            let diff = db
+                .replace_products(
+                    guild,
+                    vec![partial_product("a", "A2"), partial_product("c", "C")],
+                )
+                .await
+                .unwrap();
+            assert_eq!(
+                diff,
+                ProductDiff {
+                    had_products: true,
+                    added: vec![product("c", "C")],
+                    removed: vec![product("b", "B")],
+                    renamed: vec![("a".to_string(), "A".to_string(), "A2".to_string())],
+                }
+            );
+
+            let mut product_a = partial_product("a", "A2");
+            product_a.details.price = Some("5.00 USD".to_string());
+            db.replace_products(guild, vec![product_a]).await.unwrap();
+            assert_eq!(
+                db.get_product_details(guild, "a".to_string())
+                    .await
+                    .unwrap()
+                    .price
+                    .as_deref(),
+                Some("5.00 USD")
+            );
+            assert_eq!(
+                db.get_product_details(guild, "b".to_string())
+                    .await
+                    .unwrap(),
+                ProductDetails::default()
+            );
+        });
+    }
+
+    #[test]
+    fn test_rotate_jinxxy_api_key() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            let key = |key: &str| SecretString::new(key.to_string());
+            // nothing to rotate without an existing key
+            assert!(!db
+                .rotate_jinxxy_api_key(guild, key("a"), "user".to_string())
+                .await
+                .unwrap());
+
+            // keys from before user IDs were recorded can be claimed by any user
+            db.set_jinxxy_api_key(guild, key("a"), None).await.unwrap();
+            assert!(db
+                .rotate_jinxxy_api_key(guild, key("b"), "user".to_string())
+                .await
+                .unwrap());
+            assert_eq!(
+                db.get_jinxxy_user_id(guild).await.unwrap().as_deref(),
+                Some("user")
+            );
+
+            assert!(!db
+                .rotate_jinxxy_api_key(guild, key("c"), "other".to_string())
+                .await
+                .unwrap());
+            assert!(db
+                .rotate_jinxxy_api_key(guild, key("d"), "user".to_string())
+                .await
+                .unwrap());
+            assert_eq!(
+                db.get_jinxxy_api_key(guild)
+                    .await
+                    .unwrap()
+                    .unwrap()
+                    .expose_secret(),
+                "d"
+            );
+        });
+    }
+
+    #[test]
+    fn test_get_guild_config() {
+        test_db(|db| async move {
+            let guild = GuildId::new(1);
+            assert!(db.get_guild_config(guild).await.unwrap().is_none());
+
+            db.set_log_channel(guild, Some(ChannelId::new(2)))
+                .await
+                .unwrap();
+            db.set_language(guild, Some(Language::Spanish))
+                .await
+                .unwrap();
+            db.set_test(guild, true).await.unwrap();
+            let config = db.get_guild_config(guild).await.unwrap().unwrap();
+            assert!(!config.api_key_set);
+            assert_eq!(config.log_channel, Some(ChannelId::new(2)));
+            assert_eq!(config.blanket_role, None);
+            assert_eq!(config.language, Some(Language::Spanish));
+            assert!(config.test);
+            assert!(!config.registrations_paused);
+        });
+    }
+
+    #[test]
+    fn test_connection_tuning() {
+        test_db(|db| async move {
+            // too long for SQLite to take, so it must not be applied or saved
+            assert!(db
+                .set_connection_tuning(Some(Duration::from_millis(u64::MAX)), None)
+                .await
+                .is_err());
+            assert_eq!(db.get_setting::<u64>(BUSY_TIMEOUT_KEY).await.unwrap(), None);
+
+            db.set_connection_tuning(Some(Duration::from_millis(100)), Some(32))
+                .await
+                .unwrap();
+            let tuning = db.connection_tuning();
+            assert_eq!(tuning.busy_timeout, Duration::from_millis(100));
+            assert_eq!(tuning.statement_cache_capacity, 32);
+            assert_eq!(
+                db.get_setting::<u64>(BUSY_TIMEOUT_KEY).await.unwrap(),
+                Some(100)
+            );
+        });
+    }
+
+    /// Create a DB file with the schema v4 had, which is the oldest schema we migrate from
+    async fn create_v4_db(path: &Path, extra_sql: &'static str) {
+        let connection = Connection::open(path).await.unwrap();
+        connection
+            .call(move |connection| {
+                connection.execute_batch(
+                    "CREATE TABLE settings (key TEXT PRIMARY KEY, value ANY) STRICT; \
+                    CREATE TABLE guild (guild_id INTEGER PRIMARY KEY, jinxxy_api_key TEXT, log_channel_id INTEGER, \
+                        test INTEGER NOT NULL DEFAULT 0, owner INTEGER NOT NULL DEFAULT 0) STRICT; \
+                    CREATE TABLE product_role (guild_id INTEGER NOT NULL, product_id TEXT NOT NULL, role_id INTEGER NOT NULL, \
+                        PRIMARY KEY (guild_id, product_id, role_id)) STRICT; \
+                    CREATE INDEX role_lookup ON product_role (guild_id, product_id); \
+                    CREATE TABLE license_activation (guild_id INTEGER NOT NULL, license_id TEXT NOT NULL, \
+                        license_activation_id TEXT NOT NULL, user_id INTEGER NOT NULL, \
+                        PRIMARY KEY (guild_id, license_id, license_activation_id, user_id)) STRICT; \
+                    CREATE TABLE owner (owner_id INTEGER PRIMARY KEY) STRICT; \
+                    INSERT INTO settings (key, value) VALUES ('schema_version', 4); \
+                    INSERT INTO guild (guild_id, log_channel_id) VALUES (1, 2); \
+                    INSERT INTO license_activation (guild_id, license_id, license_activation_id, user_id) VALUES (1, 'license', 'a', 3);",
+                )?;
+                connection.execute_batch(extra_sql)?;
+                Ok(())
             })
             .await
+            .unwrap();
+    }
+
+    /// Path for a test's DB file, removing any left over from an earlier run
+    fn test_db_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("jinx-test-{name}-{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_migrate_from_v4() {
+        block_on(async {
+            let path = test_db_path("migrate-from-v4");
+            create_v4_db(&path, "").await;
+            let db = JinxDb::open_path(&path).await.unwrap();
+            assert_eq!(
+                db.get_setting::<i32>(SCHEMA_VERSION_KEY).await.unwrap(),
+                Some(SCHEMA_VERSION_VALUE)
+            );
+            let guild = GuildId::new(1);
+            assert_eq!(
+                db.get_log_channel(guild).await.unwrap(),
+                Some(ChannelId::new(2))
+            );
+            assert_eq!(
+                db.get_user_licenses(guild, 3).await.unwrap(),
+                vec!["license".to_string()]
+            );
+
+            // tables created since v4 have every column, including those later migrations add to older DBs
+            let details = ProductDetails {
+                thumbnail_url: None,
+                price: Some("5.00 USD".to_string()),
+            };
+            let product = PartialProduct {
+                id: "product".to_string(),
+                name: "Hat".to_string(),
+                details: details.clone(),
+            };
+            db.replace_products(guild, vec![product]).await.unwrap();
+            assert_eq!(
+                db.get_all_product_details(guild)
+                    .await
+                    .unwrap()
+                    .get("product"),
+                Some(&details)
+            );
+            db.add_activation_hook(
+                guild,
+                "product".to_string(),
+                ActivationHookKind::Webhook,
+                "https://example.com/hook".to_string(),
+                Some(SecretString::new("secret".to_string())),
+            )
+            .await
+            .unwrap();
+            let hooks = db
+                .get_activation_hooks(guild, "product".to_string())
+                .await
+                .unwrap();
+            assert_eq!(
+                hooks[0]
+                    .signing_secret
+                    .as_ref()
+                    .map(|secret| secret.expose_secret().as_str()),
+                Some("secret")
+            );
+            drop(db);
+            let _ = std::fs::remove_file(path);
+        });
+    }
+
+    #[test]
+    fn test_failed_migration_rolls_back() {
+        block_on(async {
+            let path = test_db_path("failed-migration");
+            // the v13 migration can't add a column that's already there
+            create_v4_db(&path, "ALTER TABLE guild ADD COLUMN stats_opt_out INTEGER").await;
+            assert!(JinxDb::open_path(&path).await.is_err());
+
+            let connection = Connection::open(&path).await.unwrap();
+            let (schema_version, product_table_count) = connection
+                .call(|connection| {
+                    let schema_version: i32 = connection.query_row(
+                        "SELECT value FROM settings WHERE key = 'schema_version'",
+                        (),
+                        |row| row.get(0),
+                    )?;
+                    let product_table_count: i32 = connection.query_row(
+                        "SELECT count(*) FROM sqlite_schema WHERE name = 'product'",
+                        (),
+                        |row| row.get(0),
+                    )?;
+                    Ok((schema_version, product_table_count))
+                })
+                .await
+                .unwrap();
+            assert_eq!(schema_version, 4);
+            assert_eq!(product_table_count, 0);
+            drop(connection);
+            let _ = std::fs::remove_file(path);
+        });
     }
 }
@@ -6,7 +6,8 @@
 use crate::http::jinxxy::{GetProfileImageUrl, GetUsername};
 use crate::license::LOCKING_USER_ID;
 use ahash::HashSet;
-use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
 use tracing::{debug, warn};
 
 const DISCORD_PREFIX: &str = "discord_";
@@ -30,6 +31,9 @@ pub struct License {
     user: LicenseUser,
     inventory_item: LicenseInventoryItem,
     activations: LicenseActivations,
+    /// When a subscription license expires. Other licenses don't have this.
+    #[serde(default, deserialize_with = "lenient")]
+    expires_at: Option<String>,
 }
 
 impl From<License> for super::LicenseInfo {
@@ -47,6 +51,9 @@ impl From<License> for super::LicenseInfo {
                 .version
                 .map(|version| version.id),
             activations: license.activations.total_count,
+            expires_at: license
+                .expires_at
+                .filter(|expires_at| !expires_at.is_empty()),
         }
     }
 }
@@ -81,6 +88,7 @@ impl From<LicenseInventoryItemItem> for PartialProduct {
         Self {
             id: item.id,
             name: item.name,
+            details: Default::default(),
         }
     }
 }
@@ -97,11 +105,13 @@ struct LicenseActivations {
 
 #[derive(Debug, Deserialize)]
 pub struct AuthUser {
+    /// Jinxxy user ID. Stays the same even if the account's name or username changes.
+    pub id: Option<String>,
     /// No sure what this is, but it can be null or empty. I think this is custom display name?
     name: Option<String>,
     /// Account's username; used in profile URL
     username: Option<String>,
-    profile_image: Option<ProfileImage>,
+    profile_image: Option<Image>,
     /// API scopes
     pub scopes: HashSet<String>,
 }
@@ -116,42 +126,50 @@ impl AuthUser {
 
     /// Check if this API key has all the required scopes
     pub fn has_required_scopes(&self) -> bool {
-        self.has_scope_licenses_read()
-            && self.has_scope_licenses_write()
-            && self.has_scope_products_read()
+        SCOPES
+            .iter()
+            .all(|scope| !scope.required || self.has_scope(scope.name))
     }
 
-    /// Check if this API key has the `products_read` scope
-    fn has_scope_products_read(&self) -> bool {
-        self.scopes.contains("products_read")
-    }
-
-    // /// Check if this API key has the `orders_read` scope
-    // fn has_scope_orders_read(&self) -> bool {
-    //     self.scopes.contains("orders_read")
-    // }
-
-    // /// Check if this API key has the `discount_codes_read` scope
-    // fn has_scope_discount_codes_read(&self) -> bool {
-    //     self.scopes.contains("discount_codes_read")
-    // }
-
-    // /// Check if this API key has the `customers_read` scope
-    // fn has_scope_customers_read(&self) -> bool {
-    //     self.scopes.contains("customers_read")
-    // }
-
-    /// Check if this API key has the `licenses_read` scope
-    fn has_scope_licenses_read(&self) -> bool {
-        self.scopes.contains("licenses_read")
-    }
-
-    /// Check if this API key has the `licenses_write` scope
-    fn has_scope_licenses_write(&self) -> bool {
-        self.scopes.contains("licenses_write")
+    /// Check if this API key has the given scope
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.contains(scope)
     }
 }
 
+/// A Jinxxy API scope that Jinx makes use of
+pub struct ScopeInfo {
+    pub name: &'static str,
+    /// If this is missing, core features such as license activation don't work
+    pub required: bool,
+    /// What Jinx needs this scope for
+    pub purpose: &'static str,
+}
+
+/// Every Jinxxy API scope Jinx makes use of, required ones first
+pub const SCOPES: [ScopeInfo; 4] = [
+    ScopeInfo {
+        name: "products_read",
+        required: true,
+        purpose: "list products for role links",
+    },
+    ScopeInfo {
+        name: "licenses_read",
+        required: true,
+        purpose: "verify license keys",
+    },
+    ScopeInfo {
+        name: "licenses_write",
+        required: true,
+        purpose: "link Discord users to license keys",
+    },
+    ScopeInfo {
+        name: "orders_read",
+        required: false,
+        purpose: "post new purchases with `/set_sales_feed`",
+    },
+];
+
 impl GetUsername for AuthUser {
     fn username(&self) -> Option<&str> {
         self.username.as_deref()
@@ -182,16 +200,27 @@ impl From<AuthUser> for super::DisplayUser {
 }
 
 #[derive(Debug, Deserialize)]
-struct ProfileImage {
+struct Image {
     url: String,
 }
 
 /// While part of the Jinxxy API this is also very useful as an external DTO
+#[derive(Debug, Default, Clone)]
 pub struct PartialProduct {
     /// Product ID
     pub id: String,
     /// Product Name
     pub name: String,
+    pub details: ProductDetails,
+}
+
+/// Product information only used for display. The API doesn't always provide these, so any of them may be missing.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ProductDetails {
+    /// URL of the product's cover image
+    pub thumbnail_url: Option<String>,
+    /// Price formatted for display, like `5.00 USD`
+    pub price: Option<String>,
 }
 
 impl PartialProduct {
@@ -225,6 +254,8 @@ pub struct FullProduct {
     /// Product name
     pub name: String,
     pub versions: Vec<ProductVersion>,
+    #[serde(flatten)]
+    details: ProductDetailFields,
 }
 
 impl From<FullProduct> for PartialProduct {
@@ -232,6 +263,7 @@ impl From<FullProduct> for PartialProduct {
         Self {
             id: product.id,
             name: product.name,
+            details: product.details.into(),
         }
     }
 }
@@ -266,6 +298,8 @@ struct ProductListResult {
     id: String,
     /// Product Name
     name: String,
+    #[serde(flatten)]
+    details: ProductDetailFields,
 }
 
 impl From<ProductListResult> for PartialProduct {
@@ -273,10 +307,102 @@ impl From<ProductListResult> for PartialProduct {
         Self {
             id: product.id,
             name: product.name,
+            details: product.details.into(),
         }
     }
 }
 
+/// Number of decimal places most currencies' minor unit has, e.g. cents
+const DEFAULT_CURRENCY_EXPONENT: u32 = 2;
+
+/// Number of decimal places in an ISO 4217 currency's minor unit. For example JPY has no minor unit, so its exponent
+/// is 0.
+fn currency_exponent(currency_code: &str) -> u32 {
+    match currency_code.to_ascii_uppercase().as_str() {
+        "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX"
+        | "UYI" | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        "CLF" | "UYW" => 4,
+        _ => DEFAULT_CURRENCY_EXPONENT,
+    }
+}
+
+/// Format an amount given in minor units, such as 1250 cents as "12.50"
+fn format_amount(minor_units: u64, exponent: u32) -> String {
+    if exponent == 0 {
+        return minor_units.to_string();
+    }
+    let scale = 10u64.pow(exponent);
+    format!(
+        "{}.{:0width$}",
+        minor_units / scale,
+        minor_units % scale,
+        width = exponent as usize
+    )
+}
+
+/// Display fields shared by the product list and product lookup responses
+#[derive(Debug, Default, Deserialize)]
+struct ProductDetailFields {
+    /// Price in the minor unit of its currency, e.g. cents. See [`currency_exponent`].
+    #[serde(default, deserialize_with = "lenient")]
+    price: Option<u64>,
+    #[serde(default, deserialize_with = "lenient")]
+    currency_code: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    cover_image: Option<Image>,
+}
+
+impl From<ProductDetailFields> for ProductDetails {
+    fn from(fields: ProductDetailFields) -> Self {
+        let price = fields.price.map(|price| match fields.currency_code {
+            Some(currency_code) if !currency_code.is_empty() => {
+                let amount = format_amount(price, currency_exponent(&currency_code));
+                format!("{} {}", amount, currency_code)
+            }
+            _ => format_amount(price, DEFAULT_CURRENCY_EXPONENT),
+        });
+        Self {
+            thumbnail_url: fields
+                .cover_image
+                .map(|image| image.url)
+                .filter(|url| !url.is_empty()),
+            price,
+        }
+    }
+}
+
+/// Deserialize a display-only field, treating a value of an unexpected type as missing instead of failing the whole
+/// response over it
+fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(serde_json::from_value(value).ok())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrderList {
+    pub results: Vec<Order>,
+}
+
+/// While part of the Jinxxy API this is also used as an external DTO
+#[derive(Debug, Deserialize)]
+pub struct Order {
+    /// ID of this order
+    pub id: String,
+    #[serde(default)]
+    pub order_items: Vec<OrderItem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OrderItem {
+    /// Name of the product that was bought
+    pub target_name: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct LicenseActivationList {
     pub results: Vec<LicenseActivation>,
@@ -361,3 +487,39 @@ impl JinxxyError {
             || (self.error == "Bad Request" && self.message == "Resource not found.")
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_product_details() {
+        let list: ProductList = serde_json::from_str(
+            r#"{"results": [
+                {"id": "1", "name": "Hat", "price": 1250, "currency_code": "USD", "cover_image": {"url": "https://example.com/hat.png"}},
+                {"id": "2", "name": "Shoes", "price": "cheap", "cover_image": null},
+                {"id": "3", "name": "Gloves"}
+            ]}"#,
+        )
+        .unwrap();
+        let products: Vec<PartialProduct> = list.into();
+        assert_eq!(
+            products[0].details,
+            ProductDetails {
+                thumbnail_url: Some("https://example.com/hat.png".to_string()),
+                price: Some("12.50 USD".to_string()),
+            }
+        );
+        assert_eq!(products[1].details, ProductDetails::default());
+        assert_eq!(products[2].details, ProductDetails::default());
+    }
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(format_amount(1250, currency_exponent("USD")), "12.50");
+        assert_eq!(format_amount(5, currency_exponent("EUR")), "0.05");
+        assert_eq!(format_amount(1250, currency_exponent("JPY")), "1250");
+        assert_eq!(format_amount(1250, currency_exponent("jpy")), "1250");
+        assert_eq!(format_amount(1250, currency_exponent("KWD")), "1.250");
+    }
+}
@@ -0,0 +1,71 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Jinxxy API latency and error tracking. Samples are buffered in memory and periodically flushed to the DB by a
+//! background task, so recording a sample never has to wait on the DB.
+
+use std::sync::{LazyLock, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+/// Maximum number of unflushed samples to hold. If the DB can't keep up we drop samples rather than grow forever.
+const MAX_PENDING_SAMPLES: usize = 10_000;
+
+static PENDING_SAMPLES: LazyLock<Mutex<Vec<ApiSample>>> = LazyLock::new(Default::default);
+
+/// A single Jinxxy API request
+pub struct ApiSample {
+    pub timestamp_unix_ms: u64,
+    /// Method and path template, such as `GET /licenses/<id>`
+    pub endpoint: &'static str,
+    pub latency_ms: u64,
+    /// `false` if the request failed outright or Jinxxy returned a server error or rate limit. Client errors are
+    /// excluded because they're mostly caused by users entering invalid licenses.
+    pub success: bool,
+}
+
+/// Record the outcome of a Jinxxy API request that began at `start_time`
+pub(super) fn record(
+    endpoint: &'static str,
+    start_time: Instant,
+    response: &reqwest::Result<reqwest::Response>,
+) {
+    let latency_ms = start_time.elapsed().as_millis() as u64;
+    debug!("{} took {}ms", endpoint, latency_ms);
+    let success = is_success(response);
+    let timestamp_unix_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0);
+    let sample = ApiSample {
+        timestamp_unix_ms,
+        endpoint,
+        latency_ms,
+        success,
+    };
+
+    let mut pending = PENDING_SAMPLES.lock().unwrap_or_else(|e| e.into_inner());
+    if pending.len() < MAX_PENDING_SAMPLES {
+        pending.push(sample);
+    } else {
+        warn!("dropping Jinxxy API health sample because the pending buffer is full");
+    }
+}
+
+/// Check if a request succeeded, in the sense used by [`ApiSample::success`]
+pub(super) fn is_success(response: &reqwest::Result<reqwest::Response>) -> bool {
+    match response {
+        Ok(response) => {
+            let status = response.status();
+            !status.is_server_error() && status != reqwest::StatusCode::TOO_MANY_REQUESTS
+        }
+        Err(_) => false,
+    }
+}
+
+/// Take all samples that have not yet been flushed
+pub fn drain_samples() -> Vec<ApiSample> {
+    let mut pending = PENDING_SAMPLES.lock().unwrap_or_else(|e| e.into_inner());
+    std::mem::take(&mut *pending)
+}
@@ -4,22 +4,50 @@
 //! Jinxxy API calls and response objects
 
 mod dto;
+mod health;
+mod queue;
+mod rate_limit;
 
 use super::HTTP1_CLIENT as HTTP_CLIENT;
 use crate::error::JinxError;
-pub use dto::{AuthUser, FullProduct, LicenseActivation, PartialProduct};
+use dashmap::DashMap;
+pub use dto::{
+    AuthUser, FullProduct, LicenseActivation, Order, PartialProduct, ProductDetails, SCOPES,
+};
+pub use health::{drain_samples as drain_health_samples, ApiSample};
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+pub use queue::queue_position;
 use reqwest::header;
-use tokio::time::Instant;
+use secrecy::{ExposeSecret as _, SecretString};
+use std::sync::{Arc, LazyLock};
+use tokio::sync::OnceCell;
 use tracing::debug;
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 
 const JINXXY_BASE_URL: &str = "https://api.creators.jinxxy.com/v1/";
 
+/// Result of a license check shared between all callers waiting on the same in-flight lookup
+type SharedLicenseResult = Result<Option<LicenseInfo>, String>;
+
+/// In-flight license checks keyed by (API key ID, license)
+type InFlightLicenseChecks =
+    DashMap<(u64, String), Arc<OnceCell<SharedLicenseResult>>, ahash::RandomState>;
+
+/// Used to deduplicate identical concurrent license lookups
+static IN_FLIGHT_LICENSE_CHECKS: LazyLock<InFlightLicenseChecks> = LazyLock::new(Default::default);
+
+static API_KEY_ID_HASHER: LazyLock<ahash::RandomState> = LazyLock::new(Default::default);
+
+/// Identify an API key without keeping a copy of it around. The hasher is randomly seeded per process, and a 64-bit
+/// collision between two guilds' keys is astronomically unlikely.
+fn api_key_id(api_key: &SecretString) -> u64 {
+    API_KEY_ID_HASHER.hash_one(api_key.expose_secret())
+}
+
 /// Get extra headers needed for Jinxxy API calls
-fn get_headers(api_key: &str) -> header::HeaderMap {
-    let mut api_key = header::HeaderValue::try_from(api_key).unwrap();
+fn get_headers(api_key: &SecretString) -> header::HeaderMap {
+    let mut api_key = header::HeaderValue::try_from(api_key.expose_secret()).unwrap();
     api_key.set_sensitive(true);
     let mut header_map = header::HeaderMap::new();
     header_map.insert("x-api-key", api_key);
@@ -27,14 +55,15 @@ fn get_headers(api_key: &str) -> header::HeaderMap {
 }
 
 /// Get the user the API key belongs to
-pub async fn get_own_user(api_key: &str) -> Result<AuthUser, Error> {
-    let start_time = Instant::now();
-    let response = HTTP_CLIENT
-        .get(format!("{}me", JINXXY_BASE_URL))
-        .headers(get_headers(api_key))
-        .send()
+pub async fn get_own_user(api_key: &SecretString) -> Result<AuthUser, Error> {
+    let permit = queue::acquire(api_key).await;
+    let response = permit
+        .send("GET /me", || {
+            HTTP_CLIENT
+                .get(format!("{}me", JINXXY_BASE_URL))
+                .headers(get_headers(api_key))
+        })
         .await?;
-    debug!("GET /me took {}ms", start_time.elapsed().as_millis());
     if !response.status().is_success() {
         JinxError::fail(format!(
             "/me returned status code {}",
@@ -53,12 +82,23 @@ pub enum LicenseKey<'a> {
     Long(&'a str),
 }
 
+impl LicenseKey<'_> {
+    /// Key used to detect identical in-flight requests
+    fn dedupe_key(&self) -> String {
+        match self {
+            LicenseKey::Id(license) => format!("id:{}", license),
+            LicenseKey::Short(license) => format!("short:{}", license),
+            LicenseKey::Long(license) => format!("long:{}", license),
+        }
+    }
+}
+
 /// Get the license id corresponding to a license key, or `None` if the license key is invalid.
 ///
 /// Note that this function does **not** verify if a provided license ID is valid: it only converts
 /// keys into IDs.
 pub async fn get_license_id(
-    api_key: &str,
+    api_key: &SecretString,
     license: LicenseKey<'_>,
 ) -> Result<Option<String>, Error> {
     match license {
@@ -73,14 +113,15 @@ pub async fn get_license_id(
             } else {
                 "key"
             };
-            let start_time = Instant::now();
-            let response = HTTP_CLIENT
-                .get(format!("{}licenses", JINXXY_BASE_URL))
-                .headers(get_headers(api_key))
-                .query(&[(search_key, license_key)])
-                .send()
+            let permit = queue::acquire(api_key).await;
+            let response = permit
+                .send("GET /licenses", || {
+                    HTTP_CLIENT
+                        .get(format!("{}licenses", JINXXY_BASE_URL))
+                        .headers(get_headers(api_key))
+                        .query(&[(search_key, license_key)])
+                })
                 .await?;
-            debug!("GET /licenses took {}ms", start_time.elapsed().as_millis());
             if !response.status().is_success() {
                 JinxError::fail(format!(
                     "/licenses returned status code {}",
@@ -103,7 +144,7 @@ pub async fn get_license_id(
 ///
 /// Note that this function **does** verify provided license ID.
 pub async fn check_license_id(
-    api_key: &str,
+    api_key: &SecretString,
     license_id: &str,
 ) -> Result<Option<LicenseInfo>, Error> {
     check_license(api_key, LicenseKey::Id(license_id)).await
@@ -112,23 +153,47 @@ pub async fn check_license_id(
 /// Get the license info corresponding to a license key, or `None` if the license key is invalid.
 ///
 /// Note that this function **does** verify all provided licenses, whether it's an ID or a short/long key.
+///
+/// If an identical check is already in flight, this waits for and shares its result instead of making a second request.
 pub async fn check_license(
-    api_key: &str,
+    api_key: &SecretString,
+    license: LicenseKey<'_>,
+) -> Result<Option<LicenseInfo>, Error> {
+    let dedupe_key = (api_key_id(api_key), license.dedupe_key());
+    // purposefully clone the Arc out so the dashmap lock is not held across an await
+    let cell = IN_FLIGHT_LICENSE_CHECKS
+        .entry(dedupe_key.clone())
+        .or_default()
+        .value()
+        .clone();
+    let result = cell
+        .get_or_init(|| async {
+            check_license_uncoalesced(api_key, license)
+                .await
+                .map_err(|e| format!("{:?}", e))
+        })
+        .await
+        .clone();
+    IN_FLIGHT_LICENSE_CHECKS.remove_if(&dedupe_key, |_, value| Arc::ptr_eq(value, &cell));
+    result.map_err(|e| JinxError::boxed(e).into())
+}
+
+/// Actual implementation of [`check_license`], without any deduplication.
+async fn check_license_uncoalesced(
+    api_key: &SecretString,
     license: LicenseKey<'_>,
 ) -> Result<Option<LicenseInfo>, Error> {
+    let permit = queue::acquire(api_key).await;
     match license {
         LicenseKey::Id(license_id) => {
             // look up license directly by ID
-            let start_time = Instant::now();
-            let response = HTTP_CLIENT
-                .get(format!("{}licenses/{}", JINXXY_BASE_URL, license_id))
-                .headers(get_headers(api_key))
-                .send()
+            let response = permit
+                .send("GET /licenses/<id>", || {
+                    HTTP_CLIENT
+                        .get(format!("{}licenses/{}", JINXXY_BASE_URL, license_id))
+                        .headers(get_headers(api_key))
+                })
                 .await?;
-            debug!(
-                "GET /licenses/<id> took {}ms",
-                start_time.elapsed().as_millis()
-            );
             if response.status().is_success() {
                 let response: dto::License = response.json().await?;
                 Ok(Some(response.into()))
@@ -154,14 +219,14 @@ pub async fn check_license(
             } else {
                 "key"
             };
-            let start_time = Instant::now();
-            let response = HTTP_CLIENT
-                .get(format!("{}licenses", JINXXY_BASE_URL))
-                .headers(get_headers(api_key))
-                .query(&[(search_key, license_key)])
-                .send()
+            let response = permit
+                .send("GET /licenses", || {
+                    HTTP_CLIENT
+                        .get(format!("{}licenses", JINXXY_BASE_URL))
+                        .headers(get_headers(api_key))
+                        .query(&[(search_key, license_key)])
+                })
                 .await?;
-            debug!("GET /licenses took {}ms", start_time.elapsed().as_millis());
             if !response.status().is_success() {
                 JinxError::fail(format!(
                     "/licenses returned status code {}",
@@ -172,16 +237,13 @@ pub async fn check_license(
             let response: dto::LicenseList = response.json().await?;
             if let Some(result) = response.results.first() {
                 // now look up the license directly by ID
-                let start_time = Instant::now();
-                let response = HTTP_CLIENT
-                    .get(format!("{}licenses/{}", JINXXY_BASE_URL, result.id))
-                    .headers(get_headers(api_key))
-                    .send()
+                let response = permit
+                    .send("GET /licenses/<id>", || {
+                        HTTP_CLIENT
+                            .get(format!("{}licenses/{}", JINXXY_BASE_URL, result.id))
+                            .headers(get_headers(api_key))
+                    })
                     .await?;
-                debug!(
-                    "GET /licenses/<id> took {}ms",
-                    start_time.elapsed().as_millis()
-                );
                 if !response.status().is_success() {
                     JinxError::fail(format!(
                         "/licenses/<id> returned status code {}",
@@ -201,26 +263,24 @@ pub async fn check_license(
 
 /// Get list of all license activations
 pub async fn get_license_activations(
-    api_key: &str,
+    api_key: &SecretString,
     license_id: &str,
 ) -> Result<Vec<LicenseActivation>, Error> {
     //TODO: build db cache into this using "Etag" header value into "If-None-Match" header value, and check for 304 Not Modified
     //TODO: ...actually... ugh this thing is a list. Is this thing cache-safe?
     //TODO: stop calling db from outside this function
     //TODO: `search_query` field "A search query to filter results"
-    let start_time = Instant::now();
-    let response = HTTP_CLIENT
-        .get(format!(
-            "{}licenses/{}/activations",
-            JINXXY_BASE_URL, license_id
-        ))
-        .headers(get_headers(api_key))
-        .send()
+    let permit = queue::acquire(api_key).await;
+    let response = permit
+        .send("GET /licenses/<id>/activations", || {
+            HTTP_CLIENT
+                .get(format!(
+                    "{}licenses/{}/activations",
+                    JINXXY_BASE_URL, license_id
+                ))
+                .headers(get_headers(api_key))
+        })
         .await?;
-    debug!(
-        "GET /licenses/<id>/activations took {}ms",
-        start_time.elapsed().as_millis()
-    );
     if !response.status().is_success() {
         JinxError::fail(format!(
             "/licenses/<id>/activations returned status code {}",
@@ -235,26 +295,24 @@ pub async fn get_license_activations(
 
 /// Create a new license activation
 pub async fn create_license_activation(
-    api_key: &str,
+    api_key: &SecretString,
     license_id: &str,
     user_id: u64,
 ) -> Result<String, Error> {
     let body = dto::CreateLicenseActivation::from_user_id(user_id);
-    let start_time = Instant::now();
-    let response = HTTP_CLIENT
-        .post(format!(
-            "{}licenses/{}/activations",
-            JINXXY_BASE_URL, license_id
-        ))
-        .headers(get_headers(api_key))
-        .header(header::CONTENT_TYPE, "application/json")
-        .json(&body)
-        .send()
+    let permit = queue::acquire(api_key).await;
+    let response = permit
+        .send("POST /licenses/<id>/activations", || {
+            HTTP_CLIENT
+                .post(format!(
+                    "{}licenses/{}/activations",
+                    JINXXY_BASE_URL, license_id
+                ))
+                .headers(get_headers(api_key))
+                .header(header::CONTENT_TYPE, "application/json")
+                .json(&body)
+        })
         .await?;
-    debug!(
-        "POST /licenses/<id>/activations took {}ms",
-        start_time.elapsed().as_millis()
-    );
     if !response.status().is_success() {
         JinxError::fail(format!(
             "POST /licenses/<id>/activations returned status code {}",
@@ -268,23 +326,21 @@ pub async fn create_license_activation(
 
 /// Delete a license activation. Returns `true` if the activation was deleted, or `false` if it was not found.
 pub async fn delete_license_activation(
-    api_key: &str,
+    api_key: &SecretString,
     license_id: &str,
     activation_id: &str,
 ) -> Result<bool, Error> {
-    let start_time = Instant::now();
-    let response = HTTP_CLIENT
-        .delete(format!(
-            "{}licenses/{}/activations/{}",
-            JINXXY_BASE_URL, license_id, activation_id
-        ))
-        .headers(get_headers(api_key))
-        .send()
+    let permit = queue::acquire(api_key).await;
+    let response = permit
+        .send("DELETE /licenses/<id>/activations", || {
+            HTTP_CLIENT
+                .delete(format!(
+                    "{}licenses/{}/activations/{}",
+                    JINXXY_BASE_URL, license_id, activation_id
+                ))
+                .headers(get_headers(api_key))
+        })
         .await?;
-    debug!(
-        "DELETE /licenses/<id>/activations took {}ms",
-        start_time.elapsed().as_millis()
-    );
     if response.status().is_success() {
         Ok(true)
     } else {
@@ -305,18 +361,16 @@ pub async fn delete_license_activation(
 }
 
 /// Look up a product
-pub async fn get_product(api_key: &str, product_id: &str) -> Result<FullProduct, Error> {
+pub async fn get_product(api_key: &SecretString, product_id: &str) -> Result<FullProduct, Error> {
     //TODO: add disk cache for this
-    let start_time = Instant::now();
-    let response = HTTP_CLIENT
-        .get(format!("{}products/{}", JINXXY_BASE_URL, product_id))
-        .headers(get_headers(api_key))
-        .send()
+    let permit = queue::acquire(api_key).await;
+    let response = permit
+        .send("GET /products/<id>", || {
+            HTTP_CLIENT
+                .get(format!("{}products/{}", JINXXY_BASE_URL, product_id))
+                .headers(get_headers(api_key))
+        })
         .await?;
-    debug!(
-        "GET /products/<id> took {}ms",
-        start_time.elapsed().as_millis()
-    );
     if !response.status().is_success() {
         JinxError::fail(format!(
             "/products/<id> returned status code {}",
@@ -330,15 +384,16 @@ pub async fn get_product(api_key: &str, product_id: &str) -> Result<FullProduct,
 }
 
 /// Get all products on this account
-pub async fn get_products(api_key: &str) -> Result<Vec<PartialProduct>, Error> {
+pub async fn get_products(api_key: &SecretString) -> Result<Vec<PartialProduct>, Error> {
     //TODO: add disk cache for this (see above issue with list caching)
-    let start_time = Instant::now();
-    let response = HTTP_CLIENT
-        .get(format!("{}products", JINXXY_BASE_URL))
-        .headers(get_headers(api_key))
-        .send()
+    let permit = queue::acquire(api_key).await;
+    let response = permit
+        .send("GET /products", || {
+            HTTP_CLIENT
+                .get(format!("{}products", JINXXY_BASE_URL))
+                .headers(get_headers(api_key))
+        })
         .await?;
-    debug!("GET /products took {}ms", start_time.elapsed().as_millis());
     if !response.status().is_success() {
         JinxError::fail(format!(
             "/products returned status code {}",
@@ -351,6 +406,28 @@ pub async fn get_products(api_key: &str) -> Result<Vec<PartialProduct>, Error> {
     Ok(response.into())
 }
 
+/// Get the store's most recent orders, newest first
+pub async fn get_recent_orders(api_key: &SecretString) -> Result<Vec<Order>, Error> {
+    let permit = queue::acquire(api_key).await;
+    let response = permit
+        .send("GET /orders", || {
+            HTTP_CLIENT
+                .get(format!("{}orders", JINXXY_BASE_URL))
+                .headers(get_headers(api_key))
+        })
+        .await?;
+    if !response.status().is_success() {
+        JinxError::fail(format!(
+            "/orders returned status code {}",
+            response.status().as_u16()
+        ))?;
+        unreachable!()
+    }
+
+    let response: dto::OrderList = response.json().await?;
+    Ok(response.results)
+}
+
 /// Not part of the Jinxxy API: this is an internal DTO that is only used for `/create_post`
 pub struct DisplayUser {
     /// Custom display name, or username if no display name is set.
@@ -358,17 +435,6 @@ pub struct DisplayUser {
     profile_image_url: Option<String>,
 }
 
-impl DisplayUser {
-    /// Get possessive form of this user's display name
-    pub fn name_possessive(&self) -> String {
-        if self.display_name.ends_with('s') {
-            format!("{}'", self.display_name)
-        } else {
-            format!("{}'s", self.display_name)
-        }
-    }
-}
-
 impl GetProfileImageUrl for DisplayUser {
     fn profile_image_url(&self) -> Option<&str> {
         self.profile_image_url.as_deref()
@@ -376,6 +442,7 @@ impl GetProfileImageUrl for DisplayUser {
 }
 
 /// Not part of the Jinxxy API: this is an internal DTO
+#[derive(Clone)]
 pub struct LicenseInfo {
     pub license_id: String,
     pub short_key: String,
@@ -386,6 +453,8 @@ pub struct LicenseInfo {
     pub product_name: String,
     pub product_version_id: Option<String>,
     pub activations: u32,
+    /// When a subscription license expires, as an RFC 3339 timestamp. Other licenses don't expire.
+    pub expires_at: Option<String>,
 }
 
 trait GetUsername {
@@ -0,0 +1,290 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Per-API-key request queue. When many users register at once the Jinxxy API gets hammered, so we cap how many
+//! requests can be in flight for a single API key and make the rest wait their turn.
+//!
+//! The cap adapts to how the API is coping with each key's requests. If recent requests are slow or failing, the key
+//! drops to one request at a time so we aren't piling more load onto a struggling API, and it climbs back up one
+//! request at a time as the API recovers.
+
+use super::{api_key_id, health, rate_limit};
+use dashmap::DashMap;
+use reqwest::{RequestBuilder, Response};
+use secrecy::SecretString;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Maximum number of concurrent Jinxxy API requests for a single API key, used while the API is healthy
+const MAX_CONCURRENT_REQUESTS_PER_API_KEY: usize = 4;
+
+/// How many of an API key's most recent requests are used to judge how the API is coping
+const HEALTH_WINDOW: usize = 20;
+
+/// Fewest recent requests needed before judging how the API is coping
+const MIN_HEALTH_SAMPLES: usize = 5;
+
+/// Drop to sequential requests if the 95th percentile latency of recent requests is above this
+const MAX_P95_LATENCY: Duration = Duration::from_secs(5);
+
+/// Drop to sequential requests if more than this fraction of recent requests failed
+const MAX_ERROR_RATE: f64 = 0.2;
+
+/// Queues keyed by API key ID. Queues nobody is using are evicted whenever a new one is added, so keys that stop being
+/// used (such as rotated ones) don't pile up.
+static QUEUES: LazyLock<DashMap<u64, Arc<ApiKeyQueue>, ahash::RandomState>> =
+    LazyLock::new(Default::default);
+
+struct ApiKeyQueue {
+    semaphore: Arc<Semaphore>,
+    /// number of requests currently waiting for a permit
+    waiting: AtomicUsize,
+    /// Permits held back from requests to lower concurrency below the maximum
+    withheld: Mutex<Vec<OwnedSemaphorePermit>>,
+    concurrency: Mutex<Concurrency>,
+}
+
+impl Default for ApiKeyQueue {
+    fn default() -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(MAX_CONCURRENT_REQUESTS_PER_API_KEY)),
+            waiting: AtomicUsize::new(0),
+            withheld: Default::default(),
+            concurrency: Default::default(),
+        }
+    }
+}
+
+/// Recent request outcomes for an API key, and the concurrency they call for
+struct Concurrency {
+    /// (latency, success) of recent requests, oldest first
+    recent: VecDeque<(Duration, bool)>,
+    limit: usize,
+}
+
+impl Default for Concurrency {
+    fn default() -> Self {
+        Self {
+            recent: VecDeque::with_capacity(HEALTH_WINDOW),
+            limit: MAX_CONCURRENT_REQUESTS_PER_API_KEY,
+        }
+    }
+}
+
+impl Concurrency {
+    /// Record a request's outcome, returning the new concurrency limit
+    fn record(&mut self, latency: Duration, success: bool) -> usize {
+        if self.recent.len() >= HEALTH_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back((latency, success));
+        if self.recent.len() < MIN_HEALTH_SAMPLES {
+            return self.limit;
+        }
+
+        let mut latencies: Vec<Duration> =
+            self.recent.iter().map(|(latency, _)| *latency).collect();
+        latencies.sort_unstable();
+        let p95_latency = latencies[(latencies.len() * 95).div_ceil(100) - 1];
+        let error_count = self.recent.iter().filter(|(_, success)| !success).count();
+        let error_rate = error_count as f64 / self.recent.len() as f64;
+        if p95_latency > MAX_P95_LATENCY || error_rate > MAX_ERROR_RATE {
+            self.limit = 1;
+        } else if self.limit < MAX_CONCURRENT_REQUESTS_PER_API_KEY {
+            self.limit += 1;
+        }
+        self.limit
+    }
+}
+
+/// Permission to make requests with an API key. Requests should be sent with [`Permit::send`], which feeds their
+/// outcomes back into the key's concurrency limit.
+pub(super) struct Permit {
+    permit: Option<OwnedSemaphorePermit>,
+    queue: Arc<ApiKeyQueue>,
+}
+
+impl Permit {
+    /// Send a request with [`rate_limit::send`], recording how it went
+    pub(super) async fn send(
+        &self,
+        endpoint: &'static str,
+        request: impl Fn() -> RequestBuilder,
+    ) -> reqwest::Result<Response> {
+        let start_time = Instant::now();
+        let response = rate_limit::send(endpoint, request).await;
+        let latency = start_time.elapsed();
+        let success = health::is_success(&response);
+        let mut concurrency = self
+            .queue
+            .concurrency
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let old_limit = concurrency.limit;
+        let new_limit = concurrency.record(latency, success);
+        if new_limit < old_limit {
+            warn!(
+                "Jinxxy API is struggling: dropping to {} concurrent request(s) for an API key",
+                new_limit
+            );
+        } else if new_limit > old_limit {
+            debug!(
+                "Jinxxy API is recovering: raising to {} concurrent requests for an API key",
+                new_limit
+            );
+        }
+        response
+    }
+}
+
+impl Drop for Permit {
+    /// Return the permit to the queue, unless it's needed to bring concurrency down to the current limit
+    fn drop(&mut self) {
+        let limit = self
+            .queue
+            .concurrency
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .limit;
+        let withheld_target = MAX_CONCURRENT_REQUESTS_PER_API_KEY.saturating_sub(limit);
+        let mut withheld = self
+            .queue
+            .withheld
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if let Some(permit) = self.permit.take() {
+            if withheld.len() < withheld_target {
+                withheld.push(permit);
+            }
+        }
+        withheld.truncate(withheld_target);
+    }
+}
+
+fn get_queue(api_key: &SecretString) -> Arc<ApiKeyQueue> {
+    let api_key_id = api_key_id(api_key);
+    // purposefully clone the Arc out so the dashmap lock is not held across an await
+    if let Some(queue) = QUEUES.get(&api_key_id) {
+        queue.value().clone()
+    } else {
+        // a queue only referenced by the map has no requests waiting or in flight. Clones are made under the map's
+        // lock, so one can't appear while this checks.
+        QUEUES.retain(|_, queue| Arc::strong_count(queue) > 1);
+        QUEUES.entry(api_key_id).or_default().value().clone()
+    }
+}
+
+/// Counts a request as waiting for as long as it exists, so a request that stops waiting for any reason (including its
+/// future being dropped) is no longer counted
+struct WaitingGuard<'a> {
+    queue: &'a ApiKeyQueue,
+}
+
+impl<'a> WaitingGuard<'a> {
+    fn new(queue: &'a ApiKeyQueue) -> Self {
+        queue.waiting.fetch_add(1, Ordering::AcqRel);
+        Self { queue }
+    }
+}
+
+impl Drop for WaitingGuard<'_> {
+    fn drop(&mut self) {
+        self.queue.waiting.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Wait for our turn to make a request using this API key. The returned permit must be held until the request is done.
+pub(super) async fn acquire(api_key: &SecretString) -> Permit {
+    let queue = get_queue(api_key);
+    let permit = {
+        let _waiting = WaitingGuard::new(&queue);
+        queue
+            .semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("API key queue semaphore should never be closed")
+    };
+    Permit {
+        permit: Some(permit),
+        queue,
+    }
+}
+
+/// Get the position a new request for this API key would have in the queue, or `None` if it would not have to wait.
+pub fn queue_position(api_key: &SecretString) -> Option<usize> {
+    QUEUES.get(&api_key_id(api_key)).and_then(|queue| {
+        if queue.semaphore.available_permits() == 0 {
+            Some(queue.waiting.load(Ordering::Acquire) + 1)
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cancelled_waiter() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let api_key = SecretString::new("test_cancelled_waiter".to_string());
+            let mut permits = Vec::new();
+            for _ in 0..MAX_CONCURRENT_REQUESTS_PER_API_KEY {
+                permits.push(acquire(&api_key).await);
+            }
+            assert_eq!(queue_position(&api_key), Some(1));
+
+            // give up on a request while it's waiting
+            let waiting = tokio::time::timeout(Duration::from_millis(10), acquire(&api_key)).await;
+            assert!(waiting.is_err());
+            assert_eq!(queue_position(&api_key), Some(1));
+
+            drop(permits);
+            assert_eq!(queue_position(&api_key), None);
+        });
+    }
+
+    #[test]
+    fn test_concurrency() {
+        let fast = Duration::from_millis(100);
+        let slow = MAX_P95_LATENCY * 2;
+        let mut concurrency = Concurrency::default();
+
+        // a few bad requests aren't enough to judge by
+        for _ in 0..MIN_HEALTH_SAMPLES - 1 {
+            assert_eq!(
+                concurrency.record(slow, false),
+                MAX_CONCURRENT_REQUESTS_PER_API_KEY
+            );
+        }
+        assert_eq!(concurrency.record(slow, false), 1);
+
+        // recovery waits until all but one of the bad requests have left the window, and then climbs one step at a time
+        for _ in 0..HEALTH_WINDOW - 2 {
+            assert_eq!(concurrency.record(fast, true), 1);
+        }
+        for limit in 2..=MAX_CONCURRENT_REQUESTS_PER_API_KEY {
+            assert_eq!(concurrency.record(fast, true), limit);
+        }
+        assert_eq!(
+            concurrency.record(fast, true),
+            MAX_CONCURRENT_REQUESTS_PER_API_KEY
+        );
+
+        // an occasional failure is tolerated
+        assert_eq!(
+            concurrency.record(fast, false),
+            MAX_CONCURRENT_REQUESTS_PER_API_KEY
+        );
+    }
+}
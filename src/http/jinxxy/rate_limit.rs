@@ -0,0 +1,125 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Rate limit handling for the Jinxxy API. All requests go through [`send`], which caps how many requests can be in
+//! flight across every API key and retries rate limited or transiently failed requests with exponential backoff.
+
+use super::health;
+use super::HTTP_CLIENT;
+use rand::Rng as _;
+use reqwest::{header, Method, RequestBuilder, Response, StatusCode};
+use tokio::sync::Semaphore;
+use tokio::time::{Duration, Instant};
+use tracing::warn;
+
+/// Maximum number of concurrent Jinxxy API requests across all API keys
+const MAX_CONCURRENT_REQUESTS: usize = 32;
+
+/// Maximum number of times to try a single request
+const MAX_ATTEMPTS: u32 = 4;
+
+/// Backoff before the first retry. Each subsequent retry doubles this.
+const BASE_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Upper bound on any single wait, including waits requested by a `Retry-After` header. Users are waiting on most of
+/// these requests, so it's better to fail than to hang.
+const MAX_BACKOFF: Duration = Duration::from_secs(10);
+
+static GLOBAL_PERMITS: Semaphore = Semaphore::const_new(MAX_CONCURRENT_REQUESTS);
+
+/// Send a request, retrying if it is rate limited or fails transiently. `endpoint` is a method and path template
+/// such as `GET /licenses/<id>`, used for logging and health tracking.
+///
+/// `request` may be called once per attempt. Non-idempotent (POST) requests are only retried when rate limited, as
+/// otherwise we can't know if the server acted on the failed attempt.
+pub(super) async fn send(
+    endpoint: &'static str,
+    request: impl Fn() -> RequestBuilder,
+) -> reqwest::Result<Response> {
+    let mut attempt: u32 = 1;
+    loop {
+        let request = request().build()?;
+        let idempotent = request.method() != Method::POST;
+        let response = {
+            let _permit = GLOBAL_PERMITS
+                .acquire()
+                .await
+                .expect("global Jinxxy API semaphore should never be closed");
+            let start_time = Instant::now();
+            let response = HTTP_CLIENT.execute(request).await;
+            health::record(endpoint, start_time, &response);
+            response
+        };
+
+        if attempt >= MAX_ATTEMPTS {
+            return response;
+        }
+        let delay = match &response {
+            Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                retry_after(response).unwrap_or_else(|| backoff(attempt))
+            }
+            Ok(response) if idempotent && response.status().is_server_error() => backoff(attempt),
+            Err(e) if idempotent && (e.is_timeout() || e.is_connect()) => backoff(attempt),
+            _ => return response,
+        };
+        let reason = match &response {
+            Ok(response) => format!("status code {}", response.status().as_u16()),
+            Err(e) => format!("{:?}", e),
+        };
+        warn!(
+            "{} attempt {} failed with {}; retrying in {}ms",
+            endpoint,
+            attempt,
+            reason,
+            delay.as_millis()
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Exponential backoff with jitter: a random duration between half and all of the nominal backoff for this attempt
+fn backoff(attempt: u32) -> Duration {
+    let nominal = BASE_BACKOFF
+        .saturating_mul(1 << (attempt - 1).min(16))
+        .min(MAX_BACKOFF);
+    let half = nominal / 2;
+    let jitter_ms = rand::thread_rng().gen_range(0..=half.as_millis() as u64);
+    half + Duration::from_millis(jitter_ms)
+}
+
+/// Read how long the server asked us to wait. Only the delay-seconds form is supported.
+fn retry_after(response: &Response) -> Option<Duration> {
+    response
+        .headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(|seconds| Duration::from_secs(seconds).min(MAX_BACKOFF))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_backoff_bounds() {
+        for attempt in 1..=MAX_ATTEMPTS + 10 {
+            let nominal = BASE_BACKOFF
+                .saturating_mul(1 << (attempt - 1).min(16))
+                .min(MAX_BACKOFF);
+            let delay = backoff(attempt);
+            assert!(
+                delay >= nominal / 2,
+                "attempt {attempt} delay {delay:?} too short"
+            );
+            assert!(
+                delay <= nominal,
+                "attempt {attempt} delay {delay:?} too long"
+            );
+        }
+    }
+}
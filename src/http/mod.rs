@@ -3,20 +3,20 @@
 
 //! HTTP API calls
 
-use crate::constants;
+use crate::{config, constants};
 use std::sync::LazyLock;
-use std::time::Duration;
 
 pub mod jinxxy;
 pub mod update_checker;
+pub mod webhook;
 
 static HTTP1_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
     reqwest::Client::builder()
         .user_agent(constants::USER_AGENT)
         .gzip(true)
         .https_only(true)
-        .connect_timeout(Duration::from_secs(10))
-        .timeout(Duration::from_secs(10))
+        .connect_timeout(config::get().http_connect_timeout)
+        .timeout(config::get().http_timeout)
         // .connection_verbose(true) // useful for debugging
         .build()
         .unwrap()
@@ -31,8 +31,8 @@ static HTTP2_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
         // .http2_keep_alive_timeout(Duration::from_secs(10)) // if the ping is not acknowledged within the timeout, the connection will be closed
         .gzip(true)
         .https_only(true)
-        .connect_timeout(Duration::from_secs(10))
-        .timeout(Duration::from_secs(10))
+        .connect_timeout(config::get().http_connect_timeout)
+        .timeout(config::get().http_timeout)
         // .connection_verbose(true) // useful for debugging
         .build()
         .unwrap()
@@ -4,6 +4,7 @@
 //! GitHub Releases-based update checking
 
 use super::HTTP2_CLIENT as HTTP_CLIENT;
+use crate::db::ReleaseChannel;
 use crate::error::JinxError;
 use reqwest::header;
 use semver::Version;
@@ -15,7 +16,9 @@ use tracing::{debug, warn};
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 
-const UPDATE_CHECK_URI: &str = "https://api.github.com/repos/zkxs/jinx/releases/latest";
+/// Recent releases, newest first. The `/releases/latest` endpoint never returns pre-releases, so we pick from this list
+/// instead.
+const UPDATE_CHECK_URI: &str = "https://api.github.com/repos/zkxs/jinx/releases?per_page=30";
 
 thread_local! {
     static LOCAL_VERSION: Result<Version, JinxError> = Version::parse(env!("CARGO_PKG_VERSION"))
@@ -26,47 +29,78 @@ thread_local! {
         });
 }
 
-/// Compare the local version to the latest GitHub release. If there's a newer version available, return its URL.
-pub async fn check_for_update() -> VersionCheck {
-    match get_latest_release().await {
-        Ok(response) => {
-            debug!("Update Url: {:?}", response.url);
-            debug!("Update Version: {:?}", response.version);
-            match Version::parse(&response.version) {
-                Ok(remote_version) => {
-                    LOCAL_VERSION.with(|local_version| {
-                        match local_version {
-                            Ok(local_version) => {
-                                match remote_version.cmp(local_version) {
-                                    Ordering::Greater => {
-                                        // we are behind
-                                        warn!("Local version is outdated.");
-                                        VersionCheck::Outdated(response)
-                                    }
-                                    Ordering::Less => {
-                                        // we are NEWER than remote
-                                        warn!("Local version is NEWER than remote version! If you're not beta testing a pre-release then something is wrong.");
-                                        VersionCheck::Future(response)
-                                    }
-                                    Ordering::Equal => {
-                                        // we are up-to-date
-                                        VersionCheck::Current // even though the response is in-scope we don't need it
-                                    }
+/// Which releases an update check should consider
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UpdatePreferences {
+    pub channel: ReleaseChannel,
+    /// Releases to never suggest upgrading to
+    pub ignored_versions: Vec<Version>,
+}
+
+impl Default for UpdatePreferences {
+    fn default() -> Self {
+        Self {
+            channel: ReleaseChannel::Stable,
+            ignored_versions: Vec::new(),
+        }
+    }
+}
+
+impl UpdatePreferences {
+    /// Check if this release may be suggested as an upgrade
+    fn accepts(&self, version: &Version, prerelease: bool) -> bool {
+        let on_channel = match self.channel {
+            ReleaseChannel::Stable => !prerelease && version.pre.is_empty(),
+            ReleaseChannel::PreRelease => true,
+        };
+        on_channel && !self.ignored_versions.contains(version)
+    }
+}
+
+/// Parse a release tag as a semver version. Tags may have a leading `v`.
+pub fn parse_version(tag: &str) -> Result<Version, semver::Error> {
+    Version::parse(tag.strip_prefix('v').unwrap_or(tag))
+}
+
+/// Compare the local version to the newest GitHub release the preferences allow. If there's a newer version available,
+/// return its URL.
+pub async fn check_for_update(preferences: &UpdatePreferences) -> VersionCheck {
+    match get_releases().await {
+        Ok(releases) => match select_release(releases, preferences) {
+            SelectedRelease::Release(remote_version, response) => {
+                debug!("Update Url: {:?}", response.url);
+                debug!("Update Version: {:?}", response.version);
+                LOCAL_VERSION.with(|local_version| {
+                    match local_version {
+                        Ok(local_version) => {
+                            match remote_version.cmp(local_version) {
+                                Ordering::Greater => {
+                                    // we are behind
+                                    warn!("Local version is outdated.");
+                                    VersionCheck::Outdated(response)
+                                }
+                                Ordering::Less => {
+                                    // we are NEWER than remote
+                                    warn!("Local version is NEWER than remote version! If you're not beta testing a pre-release then something is wrong.");
+                                    VersionCheck::Future(response)
+                                }
+                                Ordering::Equal => {
+                                    // we are up-to-date
+                                    VersionCheck::Current // even though the response is in-scope we don't need it
                                 }
-                            }
-                            Err(_) => {
-                                // could not get local version (this gets logged in the thread_local)
-                                VersionCheck::BadLocal(response)
                             }
                         }
-                    })
-                }
-                Err(e) => {
-                    warn!("Error parsing remote version: {e:?}");
-                    VersionCheck::BadRemote(response)
-                }
+                        Err(_) => {
+                            // could not get local version (this gets logged in the thread_local)
+                            VersionCheck::BadLocal(response)
+                        }
+                    }
+                })
             }
-        }
+            SelectedRelease::Unparsable(response) => VersionCheck::BadRemote(response),
+            // everything available is either ignored or on another channel, so there's nothing to upgrade to
+            SelectedRelease::None => VersionCheck::Current,
+        },
         Err(e) => {
             warn!("Failed to get latest version info: {e:?}");
             VersionCheck::UnknownRemote
@@ -74,8 +108,52 @@ pub async fn check_for_update() -> VersionCheck {
     }
 }
 
-/// Get latest release from GitHub
-async fn get_latest_release() -> Result<RemoteVersion, Error> {
+/// Result of picking a release to compare against
+enum SelectedRelease {
+    Release(Version, RemoteVersion),
+    /// No release was usable, and at least one was skipped because its tag isn't semver
+    Unparsable(RemoteVersion),
+    None,
+}
+
+/// Pick the newest release the preferences allow. Drafts are always skipped.
+fn select_release(
+    releases: Vec<RemoteVersion>,
+    preferences: &UpdatePreferences,
+) -> SelectedRelease {
+    let mut newest: Option<(Version, RemoteVersion)> = None;
+    let mut unparsable = None;
+    for release in releases {
+        if release.draft {
+            continue;
+        }
+        match parse_version(&release.version) {
+            Ok(version) => {
+                if preferences.accepts(&version, release.prerelease)
+                    && newest
+                        .as_ref()
+                        .map_or(true, |(newest_version, _)| version > *newest_version)
+                {
+                    newest = Some((version, release));
+                }
+            }
+            Err(e) => {
+                warn!("Error parsing remote version {:?}: {e:?}", release.version);
+                if unparsable.is_none() {
+                    unparsable = Some(release);
+                }
+            }
+        }
+    }
+    match (newest, unparsable) {
+        (Some((version, release)), _) => SelectedRelease::Release(version, release),
+        (None, Some(release)) => SelectedRelease::Unparsable(release),
+        (None, None) => SelectedRelease::None,
+    }
+}
+
+/// Get recent releases from GitHub
+async fn get_releases() -> Result<Vec<RemoteVersion>, Error> {
     let request = HTTP_CLIENT
         .get(UPDATE_CHECK_URI)
         .header(header::ACCEPT, "application/json")
@@ -92,9 +170,9 @@ async fn get_latest_release() -> Result<RemoteVersion, Error> {
     //TODO: implement etag-based caching
 
     let status = response.status();
-    let result = response.json::<RemoteVersion>().await.map_err(|e| {
+    let result = response.json::<Vec<RemoteVersion>>().await.map_err(|e| {
         JinxError::new(format!(
-            "error parsing github release {} response: {}",
+            "error parsing github releases {} response: {}",
             status.as_str(),
             e
         ))
@@ -152,6 +230,13 @@ pub struct RemoteVersion {
     pub url: String,
     #[serde(rename = "tag_name")]
     pub version: String,
+    /// Release notes, in markdown
+    #[serde(rename = "body", default)]
+    pub changelog: Option<String>,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    draft: bool,
 }
 
 impl Display for RemoteVersion {
@@ -159,3 +244,56 @@ impl Display for RemoteVersion {
         write!(f, "[{}](<{}>)", self.version, self.url)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn release(version: &str, prerelease: bool) -> RemoteVersion {
+        RemoteVersion {
+            url: String::new(),
+            version: version.to_string(),
+            changelog: None,
+            prerelease,
+            draft: false,
+        }
+    }
+
+    fn selected_version(releases: Vec<RemoteVersion>, preferences: &UpdatePreferences) -> String {
+        match select_release(releases, preferences) {
+            SelectedRelease::Release(version, _) => version.to_string(),
+            SelectedRelease::Unparsable(release) => format!("unparsable {}", release.version),
+            SelectedRelease::None => "none".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_select_release() {
+        let releases = || {
+            vec![
+                release("1.9.0", false),
+                release("v2.0.0-rc.1", true),
+                release("1.10.0", false),
+                release("nightly", true),
+            ]
+        };
+        let mut preferences = UpdatePreferences::default();
+        // semver order, not string order
+        assert_eq!(selected_version(releases(), &preferences), "1.10.0");
+
+        preferences.channel = ReleaseChannel::PreRelease;
+        assert_eq!(selected_version(releases(), &preferences), "2.0.0-rc.1");
+
+        preferences.ignored_versions = vec![
+            parse_version("2.0.0-rc.1").unwrap(),
+            parse_version("1.10.0").unwrap(),
+        ];
+        assert_eq!(selected_version(releases(), &preferences), "1.9.0");
+
+        assert_eq!(
+            selected_version(vec![release("nightly", true)], &preferences),
+            "unparsable nightly"
+        );
+        assert_eq!(selected_version(vec![], &preferences), "none");
+    }
+}
@@ -0,0 +1,275 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Outgoing webhooks to creator-configured URLs.
+//!
+//! Activation hooks post a payload after a single product is activated. Event webhooks cover every activation and
+//! deactivation in a guild. Both are signed so the receiver can check they came from us: the [`SIGNATURE_HEADER`] is
+//! `sha256=` followed by the hex HMAC-SHA256 of the [`TIMESTAMP_HEADER`] value, a `.`, and the body, keyed with the
+//! hook's or guild's signing secret.
+//!
+//! Webhook URLs are chosen by guild admins, so they're only allowed to reach public addresses. Otherwise the bot could
+//! be pointed at services only reachable from the machine it runs on. [`check_url`] enforces this when a URL is set, and
+//! every delivery checks again and connects only to the addresses it checked, so a host can't pass the check and then
+//! resolve somewhere else.
+
+use crate::error::JinxError;
+use crate::{config, constants};
+use reqwest::{header, redirect, Url};
+use ring::hmac;
+use ring::rand::{SecureRandom as _, SystemRandom};
+use secrecy::{ExposeSecret as _, SecretString};
+use serde::Serialize;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// Header holding the Unix time (in seconds) an event webhook was signed at
+pub const TIMESTAMP_HEADER: &str = "X-Jinx-Timestamp";
+
+/// Header holding an event webhook's signature. See [`sign`].
+pub const SIGNATURE_HEADER: &str = "X-Jinx-Signature";
+
+/// Prefix of generated signing secrets, so they're recognizable if they end up somewhere they shouldn't
+const SIGNING_SECRET_PREFIX: &str = "jinx_whsec_";
+
+/// Random bytes in a generated signing secret
+const SIGNING_SECRET_LENGTH: usize = 32;
+
+/// Body of an activation webhook. Field names are part of the public webhook format, so don't change them!
+#[derive(Debug, Serialize)]
+pub struct ActivationPayload {
+    pub event: &'static str,
+    pub guild_id: String,
+    pub user_id: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub product_version_id: Option<String>,
+    pub license_id: String,
+    /// `false` if the user had already activated this license and just registered it again
+    pub new_activation: bool,
+}
+
+/// Check if an address is reachable from the public internet, rather than being loopback, private, link-local,
+/// unspecified, or otherwise reserved
+fn is_public_address(address: IpAddr) -> bool {
+    match address {
+        IpAddr::V4(address) => {
+            let [a, b, ..] = address.octets();
+            !(address.is_loopback()
+                || address.is_private()
+                || address.is_link_local()
+                || address.is_unspecified()
+                || address.is_broadcast()
+                || address.is_multicast()
+                || address.is_documentation()
+                || a == 0 // "this network"
+                || (a == 100 && (64..128).contains(&b)) // shared address space (carrier-grade NAT)
+                || a >= 240) // reserved
+        }
+        IpAddr::V6(address) => {
+            if let Some(mapped) = address.to_ipv4_mapped() {
+                return is_public_address(IpAddr::V4(mapped));
+            }
+            let first_segment = address.segments()[0];
+            !(address.is_loopback()
+                || address.is_unspecified()
+                || address.is_multicast()
+                || (first_segment & 0xfe00) == 0xfc00 // unique local
+                || (first_segment & 0xffc0) == 0xfe80) // link-local
+        }
+    }
+}
+
+/// Check that a webhook URL is a valid https URL whose host only resolves to public addresses. Returns the parsed URL
+/// and the addresses to connect to, or an error message to show the admin who set the URL.
+pub async fn check_url(url: &str) -> Result<(Url, Vec<SocketAddr>), &'static str> {
+    let url = match Url::parse(url) {
+        Ok(url) if url.scheme() == "https" => url,
+        _ => return Err("Webhook URL must be a valid https URL."),
+    };
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return Err("Webhook URL must be a valid https URL.");
+    };
+    // IPv6 hosts are bracketed in URLs, but not when looked up
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let addresses: Vec<SocketAddr> = match tokio::net::lookup_host((host, port)).await {
+        Ok(addresses) => addresses.collect(),
+        Err(_) => return Err("Webhook URL's host could not be found."),
+    };
+    if addresses.is_empty() {
+        Err("Webhook URL's host could not be found.")
+    } else if addresses
+        .iter()
+        .all(|address| is_public_address(address.ip()))
+    {
+        Ok((url, addresses))
+    } else {
+        Err("Webhook URL must point to a public address.")
+    }
+}
+
+/// Check a webhook URL again just before delivering to it, and build a client that can only connect to the addresses
+/// that passed the check
+async fn checked_client(url: &str) -> Result<(Url, reqwest::Client), Error> {
+    let (url, addresses) = check_url(url).await.map_err(JinxError::new)?;
+    let mut builder = reqwest::Client::builder()
+        .user_agent(constants::USER_AGENT)
+        .https_only(true)
+        // a proxy would do its own lookup, and a redirect could go anywhere
+        .no_proxy()
+        .redirect(redirect::Policy::none())
+        .connect_timeout(config::get().http_connect_timeout)
+        .timeout(config::get().http_timeout);
+    if let Some(domain) = url.domain() {
+        builder = builder.resolve_to_addrs(domain, &addresses);
+    }
+    Ok((url, builder.build()?))
+}
+
+/// POST a signed activation event to an activation hook's URL
+pub async fn post_activation(
+    url: &str,
+    signing_secret: &SecretString,
+    payload: &ActivationPayload,
+) -> Result<(), Error> {
+    post_signed(url, signing_secret, serde_json::to_vec(payload)?).await
+}
+
+/// Body of an event webhook. Field names are part of the public webhook format, so don't change them!
+#[derive(Debug, Serialize)]
+pub struct EventPayload {
+    /// `license_activated` or `license_deactivated`
+    pub event: &'static str,
+    /// Position in the guild's event stream. Always increases, so receivers can use it to ignore duplicate deliveries.
+    pub sequence: u64,
+    pub created_unix_ms: u64,
+    pub guild_id: String,
+    pub user_id: Option<String>,
+    pub license_id: Option<String>,
+}
+
+/// Generate a new event webhook signing secret
+pub fn generate_signing_secret() -> Result<SecretString, Error> {
+    let mut bytes = [0u8; SIGNING_SECRET_LENGTH];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| JinxError::new("failed to generate webhook signing secret"))?;
+    let hex: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+    Ok(SecretString::new(format!("{SIGNING_SECRET_PREFIX}{hex}")))
+}
+
+/// Signature of a webhook body, as sent in the [`SIGNATURE_HEADER`]: the hex HMAC-SHA256 of `{timestamp}.{body}`
+fn sign(signing_secret: &SecretString, timestamp: u64, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, signing_secret.expose_secret().as_bytes());
+    let mut context = hmac::Context::with_key(&key);
+    context.update(format!("{timestamp}.").as_bytes());
+    context.update(body);
+    let tag = context.sign();
+    let hex: String = tag
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("sha256={hex}")
+}
+
+/// POST a signed event to a guild's event webhook URL
+pub async fn post_event(
+    url: &str,
+    signing_secret: &SecretString,
+    payload: &EventPayload,
+) -> Result<(), Error> {
+    post_signed(url, signing_secret, serde_json::to_vec(payload)?).await
+}
+
+/// POST a JSON body to a webhook URL, signed with the given secret
+async fn post_signed(url: &str, signing_secret: &SecretString, body: Vec<u8>) -> Result<(), Error> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let (url, client) = checked_client(url).await?;
+    let response = client
+        .post(url)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(TIMESTAMP_HEADER, timestamp)
+        .header(SIGNATURE_HEADER, sign(signing_secret, timestamp, &body))
+        .body(body)
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        JinxError::fail(format!(
+            "webhook returned status code {}",
+            response.status().as_u16()
+        ))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_public_address() {
+        let public = ["1.1.1.1", "100.128.0.1", "2606:4700:4700::1111"];
+        let internal = [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "100.64.0.1",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ];
+        for address in public {
+            assert!(is_public_address(address.parse().unwrap()), "{address}");
+        }
+        for address in internal {
+            assert!(!is_public_address(address.parse().unwrap()), "{address}");
+        }
+    }
+
+    #[test]
+    fn test_check_url() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            assert!(check_url("http://1.1.1.1/hook").await.is_err());
+            assert!(check_url("not a url").await.is_err());
+            assert!(check_url("https://127.0.0.1/hook").await.is_err());
+            assert!(check_url("https://[::1]:8443/hook").await.is_err());
+            assert!(check_url("https://169.254.169.254/latest").await.is_err());
+            assert!(check_url("https://localhost/hook").await.is_err());
+            let (url, addresses) = check_url("https://1.1.1.1/hook").await.unwrap();
+            assert_eq!(url.as_str(), "https://1.1.1.1/hook");
+            assert_eq!(addresses, vec!["1.1.1.1:443".parse().unwrap()]);
+        });
+    }
+
+    #[test]
+    fn test_sign() {
+        let signing_secret = SecretString::new("secret".to_string());
+        // matches `printf '1700000000.{}' | openssl dgst -sha256 -hmac secret`
+        assert_eq!(
+            sign(&signing_secret, 1700000000, b"{}"),
+            "sha256=b8569b78799ff9e3cbff0fc2d63a33a2b57f3282abd07c37ae5e8e7d79a5f163"
+        );
+        let generated = generate_signing_secret().unwrap();
+        assert!(generated.expose_secret().starts_with(SIGNING_SECRET_PREFIX));
+        assert_ne!(
+            generated.expose_secret(),
+            generate_signing_secret().unwrap().expose_secret()
+        );
+    }
+}
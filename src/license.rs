@@ -6,24 +6,58 @@
 use crate::http::jinxxy::{LicenseActivation, LicenseKey};
 use poise::serenity_prelude::UserId;
 use regex::RegexSet;
+use std::collections::HashSet;
 use std::fmt::{Display, Formatter};
 use std::sync::LazyLock;
 use tracing::debug;
 
-// these indices MUST match the array positions in the below RegexSet
-const JINXXY_SHORT_KEY_INDEX: usize = 0;
-const JINXXY_LONG_KEY_INDEX: usize = 1;
-const GUMROAD_KEY_INDEX: usize = 2;
-const NUMBER_KEY_INDEX: usize = 3;
-static GLOBAL_ANY_LICENSE_REGEX: LazyLock<RegexSet> = LazyLock::new(|| {
-    RegexSet::new([
-        r"^[A-Z]{4}-[a-f0-9]{12}$", // jinxxy short key `XXXX-cd071c534191`
-        r"^[a-f0-9]{8}-[a-f0-9]{4}-4[a-f0-9]{3}-[89ab][a-f0-9]{3}-[a-f0-9]{12}$", // jinxxy long key `3642d957-c5d8-4d18-a1ae-cd071c534191`. This is a version 4 DCE 1.1, ISO/IEC 11578:1996 UUID.
-        r"^[A-F0-9]{8}-[A-F0-9]{8}-[A-F0-9]{8}-[A-F0-9]{8}$", // gumroad key `ABCD1234-1234FEDC-0987A321-A2B3C5D6`
-        r"^[0-9]+$", // an integer number `3245554511053325533`
-    ])
-    .unwrap()
-}); // in case you are wondering the above are not real keys: they're only examples
+/// Every license format we can detect. To detect a new format, add its pattern here. A value matching more than one
+/// pattern is [`LicenseType::Ambiguous`], so patterns should not overlap.
+const LICENSE_FORMATS: [(LicenseType, &str); 9] = [
+    // jinxxy short key `XXXX-cd071c534191`
+    (LicenseType::JinxxyShort, r"^[A-Z]{4}-[a-f0-9]{12}$"),
+    // jinxxy long key `3642d957-c5d8-4d18-a1ae-cd071c534191`. This is a version 4 DCE 1.1, ISO/IEC 11578:1996 UUID.
+    (
+        LicenseType::JinxxyLong,
+        r"^[a-f0-9]{8}-[a-f0-9]{4}-4[a-f0-9]{3}-[89ab][a-f0-9]{3}-[a-f0-9]{12}$",
+    ),
+    // gumroad key `ABCD1234-1234FEDC-0987A321-A2B3C5D6`
+    (
+        LicenseType::Gumroad,
+        r"^[A-F0-9]{8}-[A-F0-9]{8}-[A-F0-9]{8}-[A-F0-9]{8}$",
+    ),
+    // an integer number `3245554511053325533`
+    (LicenseType::Integer, r"^[0-9]+$"),
+    // payhip key `ZKQTV-NLW4M-8Y2BR-XH9PE`
+    (
+        LicenseType::Payhip,
+        r"^[A-Z0-9]{5}-[A-Z0-9]{5}-[A-Z0-9]{5}-[A-Z0-9]{5}$",
+    ),
+    // itch.io download key, which is only ever shown as part of a URL
+    // `https://creator.itch.io/avatar/download/aB3dE5gH7jK9mN1pQ3sT5vX7zA9cE1gI3kM5oQ7s`
+    (
+        LicenseType::Itch,
+        r"^(https?://)?[a-z0-9-]+\.itch\.io/\S+/download/[A-Za-z0-9_-]+$",
+    ),
+    // booth has no license keys, but users paste order and item URLs `https://accounts.booth.pm/orders/12345678`
+    (
+        LicenseType::Booth,
+        r"^(https?://)?([a-z0-9-]+\.)?booth\.pm(/\S*)?$",
+    ),
+    // lemon squeezy key, which is an uppercase UUID `38B1460A-5104-4067-A91D-77B872934D51`
+    (
+        LicenseType::LemonSqueezy,
+        r"^[A-F0-9]{8}-[A-F0-9]{4}-4[A-F0-9]{3}-[89AB][A-F0-9]{3}-[A-F0-9]{12}$",
+    ),
+    // sellix order ID `6a3f9c-1b2d4e6f80-a1b2c3`
+    (
+        LicenseType::Sellix,
+        r"^[a-f0-9]{6}-[a-f0-9]{10}-[a-f0-9]{6}$",
+    ),
+]; // in case you are wondering the above are not real keys: they're only examples
+
+static GLOBAL_ANY_LICENSE_REGEX: LazyLock<RegexSet> =
+    LazyLock::new(|| RegexSet::new(LICENSE_FORMATS.map(|(_, pattern)| pattern)).unwrap());
 
 pub const LOCKING_USER_ID: u64 = 0;
 
@@ -39,6 +73,11 @@ pub enum LicenseType {
     JinxxyLong,
     Gumroad,
     Integer,
+    Payhip,
+    Itch,
+    Booth,
+    LemonSqueezy,
+    Sellix,
     Unknown,
     /// Not possible under current regex set, but we have the logic for it anyway
     Ambiguous,
@@ -55,6 +94,19 @@ impl LicenseType {
         !matches!(self, LicenseType::Unknown)
     }
 
+    /// If the license is from some marketplace other than Jinxxy
+    pub fn is_other_marketplace(&self) -> bool {
+        matches!(
+            self,
+            LicenseType::Gumroad
+                | LicenseType::Payhip
+                | LicenseType::Itch
+                | LicenseType::Booth
+                | LicenseType::LemonSqueezy
+                | LicenseType::Sellix
+        )
+    }
+
     /// If the license is an integer. This indicates it *may* be a license ID.
     pub fn is_integer(&self) -> bool {
         matches!(self, LicenseType::Integer)
@@ -69,7 +121,7 @@ impl LicenseType {
         match self {
             LicenseType::JinxxyLong => Some(LicenseKey::Long(license)),
             LicenseType::Integer => None,
-            LicenseType::Gumroad => None,
+            license_type if license_type.is_other_marketplace() => None,
             _ => Some(LicenseKey::Short(license)), // if we aren't certain what this is just try it as a short key
         }
     }
@@ -81,7 +133,7 @@ impl LicenseType {
         match self {
             LicenseType::JinxxyLong => Some(LicenseKey::Long(license)),
             LicenseType::Integer => Some(LicenseKey::Id(license)),
-            LicenseType::Gumroad => None,
+            license_type if license_type.is_other_marketplace() => None,
             _ => Some(LicenseKey::Short(license)), // if we aren't certain what this is just try it as a short key
         }
     }
@@ -94,6 +146,11 @@ impl Display for LicenseType {
             LicenseType::JinxxyLong => write!(f, "a Jinxxy long key"),
             LicenseType::Gumroad => write!(f, "a Gumroad key"),
             LicenseType::Integer => write!(f, "a number"),
+            LicenseType::Payhip => write!(f, "a Payhip key"),
+            LicenseType::Itch => write!(f, "an itch.io download link"),
+            LicenseType::Booth => write!(f, "a Booth link"),
+            LicenseType::LemonSqueezy => write!(f, "a Lemon Squeezy key"),
+            LicenseType::Sellix => write!(f, "a Sellix order ID"),
             LicenseType::Unknown => write!(f, "an unknown value"),
             LicenseType::Ambiguous => write!(f, "an ambiguous value"),
         }
@@ -106,11 +163,8 @@ pub fn identify_license(license: &str) -> LicenseType {
     let mut match_iter = matches.iter();
     // get license type for the first match
     let license_type = match match_iter.next() {
-        Some(JINXXY_SHORT_KEY_INDEX) => LicenseType::JinxxyShort,
-        Some(JINXXY_LONG_KEY_INDEX) => LicenseType::JinxxyLong,
-        Some(GUMROAD_KEY_INDEX) => LicenseType::Gumroad,
-        Some(NUMBER_KEY_INDEX) => LicenseType::Integer,
-        _ => LicenseType::Unknown,
+        Some(index) => LICENSE_FORMATS[index].0,
+        None => LicenseType::Unknown,
     };
 
     if match_iter.next().is_some() {
@@ -150,7 +204,7 @@ fn validate_license_activation(
 ) -> ActivationValidation {
     let mut own_user = false;
     let mut multiple = false;
-    let mut other_user = false;
+    let mut other_users: HashSet<u64, ahash::RandomState> = Default::default();
     let mut locked = false;
 
     for user_id in user_ids {
@@ -165,14 +219,14 @@ fn validate_license_activation(
             locked = true;
         } else {
             // unexpected user activated
-            other_user = true;
+            other_users.insert(user_id);
         }
     }
 
     ActivationValidation {
         own_user,
         multiple,
-        other_user,
+        other_user_count: other_users.len(),
         locked,
     }
 }
@@ -184,16 +238,28 @@ pub struct ActivationValidation {
     pub own_user: bool,
     /// If the expected user has activated the license more than once (this shouldn't be possible)
     pub multiple: bool,
-    /// If an unexpected user has activated the license
-    pub other_user: bool,
+    /// Number of distinct unexpected users that have activated the license
+    pub other_user_count: usize,
     /// If the license is locked (otherwise valid, but forbidden from being used to grant roles)
     pub locked: bool,
 }
 
 impl ActivationValidation {
-    /// Check if the license has multiple conflicting activations (this shouldn't be possible)
-    pub fn deadlocked(&self) -> bool {
-        self.own_user && self.other_user
+    /// Check if every seat on the license is taken by some other user, meaning the expected user can't have one.
+    /// Most licenses have a single seat.
+    pub fn seats_exhausted(&self, max_activations: u32) -> bool {
+        self.other_user_count >= max_activations as usize
+    }
+
+    /// Check if the license is blocked from granting roles to the expected user, either because it's locked or because
+    /// other users have taken all the seats.
+    pub fn blocked(&self, max_activations: u32) -> bool {
+        self.locked || self.seats_exhausted(max_activations)
+    }
+
+    /// Check if the license has more activations than it has seats (this shouldn't be possible)
+    pub fn deadlocked(&self, max_activations: u32) -> bool {
+        self.own_user && self.seats_exhausted(max_activations)
     }
 }
 
@@ -229,9 +295,119 @@ mod test {
         );
     }
 
+    #[test]
+    #[traced_test]
+    fn test_payhip_license() {
+        assert_eq!(
+            identify_license("ZKQTV-NLW4M-8Y2BR-XH9PE"),
+            LicenseType::Payhip
+        );
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_itch_license() {
+        assert_eq!(
+            identify_license(
+                "https://creator.itch.io/avatar/download/aB3dE5gH7jK9mN1pQ3sT5vX7zA9cE1gI3kM5oQ7s"
+            ),
+            LicenseType::Itch
+        );
+        assert_eq!(
+            identify_license("creator.itch.io/avatar/download/aB3dE5gH7jK9"),
+            LicenseType::Itch
+        );
+        assert_eq!(
+            identify_license("https://creator.itch.io/avatar"),
+            LicenseType::Unknown
+        );
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_booth_license() {
+        assert_eq!(
+            identify_license("https://accounts.booth.pm/orders/12345678"),
+            LicenseType::Booth
+        );
+        assert_eq!(
+            identify_license("creator.booth.pm/items/1234567"),
+            LicenseType::Booth
+        );
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_lemon_squeezy_license() {
+        assert_eq!(
+            identify_license("38B1460A-5104-4067-A91D-77B872934D51"),
+            LicenseType::LemonSqueezy
+        );
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_sellix_license() {
+        assert_eq!(
+            identify_license("6a3f9c-1b2d4e6f80-a1b2c3"),
+            LicenseType::Sellix
+        );
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_other_marketplaces_are_not_jinxxy() {
+        for (license_type, _) in LICENSE_FORMATS {
+            assert_ne!(
+                license_type.is_jinxxy_license(),
+                license_type.is_other_marketplace() || license_type.is_integer()
+            );
+            if license_type.is_other_marketplace() {
+                assert!(license_type.create_untrusted_jinxxy_license("x").is_none());
+            }
+        }
+    }
+
     #[test]
     #[traced_test]
     fn test_not_a_license() {
         assert_eq!(identify_license("bing bong"), LicenseType::Unknown);
     }
+
+    #[test]
+    #[traced_test]
+    fn test_single_seat() {
+        let validation = validate_license_activation(UserId::new(1), [2].into_iter());
+        assert!(validation.blocked(1));
+        assert!(!validation.deadlocked(1));
+
+        let validation = validate_license_activation(UserId::new(1), [1, 2].into_iter());
+        assert!(validation.deadlocked(1));
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_multiple_seats() {
+        // a second seat is still free
+        let validation = validate_license_activation(UserId::new(1), [2].into_iter());
+        assert!(!validation.blocked(2));
+
+        // both seats are taken, and duplicate activations from the same user only take one seat
+        let validation = validate_license_activation(UserId::new(1), [2, 3, 3].into_iter());
+        assert!(validation.blocked(2));
+        assert!(!validation.blocked(3));
+
+        // we got the last seat
+        let validation = validate_license_activation(UserId::new(1), [1, 2].into_iter());
+        assert!(!validation.deadlocked(2));
+        assert!(validation.deadlocked(1));
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_locked_ignores_seats() {
+        let validation = validate_license_activation(UserId::new(1), [LOCKING_USER_ID].into_iter());
+        assert!(validation.blocked(5));
+        assert_eq!(validation.other_user_count, 0);
+    }
 }
@@ -3,6 +3,7 @@
 
 use crate::cli_args::{JinxArgs, OwnerCommand};
 use clap::Parser;
+use secrecy::SecretString;
 use std::process::ExitCode;
 use std::sync::atomic;
 use std::sync::atomic::AtomicBool;
@@ -11,12 +12,15 @@ use tokio_graceful_shutdown::{SubsystemBuilder, SubsystemHandle, Toplevel};
 use tracing::info;
 use tracing_subscriber::EnvFilter;
 
+mod admin;
 mod bot;
 mod cli_args;
+mod config;
 mod db;
 mod error;
 mod http;
 mod license;
+mod secret;
 
 /// constants generated in build.rs
 pub mod constants {
@@ -25,9 +29,8 @@ pub mod constants {
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
 
-const DB_OPEN_ERROR_MESSAGE: &str = "Failed to open jinx.sqlite";
+const DB_OPEN_ERROR_MESSAGE: &str = "Failed to open database";
 const DB_READ_ERROR_MESSAGE: &str = "Failed to read from database";
-const DB_WRITE_ERROR_MESSAGE: &str = "Failed to write to database";
 const DISCORD_ID_PARSE_ERROR_MESSAGE: &str = "Failed to parse Discord ID";
 
 /// If we should restart the bot on shutdown
@@ -36,6 +39,10 @@ static SHOULD_RESTART: AtomicBool = AtomicBool::new(false);
 #[tokio::main(flavor = "multi_thread")]
 async fn main() -> ExitCode {
     let cli_args = JinxArgs::parse();
+    if let Err(e) = config::init(cli_args.config.as_deref(), cli_args.db_path) {
+        eprintln!("{e}");
+        return ExitCode::FAILURE;
+    }
     match cli_args.command {
         Some(cli_args::Command::Init { discord_token }) => {
             let discord_token = discord_token.or_else(|| std::env::var("DISCORD_TOKEN").ok());
@@ -43,7 +50,7 @@ async fn main() -> ExitCode {
                 let db = db::JinxDb::open()
                     .await
                     .unwrap_or_else(|e| panic!("{}: {:?}", DB_OPEN_ERROR_MESSAGE, e));
-                db.set_discord_token(discord_token)
+                db.set_discord_token(SecretString::new(discord_token))
                     .await
                     .expect("Failed to set discord token");
                 ExitCode::SUCCESS
@@ -52,47 +59,74 @@ async fn main() -> ExitCode {
                 ExitCode::FAILURE
             }
         }
-        Some(cli_args::Command::UpdateCheck) => {
-            println!("{}", http::update_checker::check_for_update().await);
-            ExitCode::SUCCESS
-        }
-        Some(cli_args::Command::Owner(cli_args::OwnerArgs { command })) => {
+        Some(cli_args::Command::EncryptSecrets) => {
             let db = db::JinxDb::open()
                 .await
                 .unwrap_or_else(|e| panic!("{}: {:?}", DB_OPEN_ERROR_MESSAGE, e));
-            match command {
-                OwnerCommand::Add { discord_id } => {
-                    let discord_id = discord_id
-                        .parse()
-                        .unwrap_or_else(|e| panic!("{}: {:?}", DISCORD_ID_PARSE_ERROR_MESSAGE, e));
-                    db.add_owner(discord_id)
-                        .await
-                        .unwrap_or_else(|e| panic!("{}: {:?}", DB_WRITE_ERROR_MESSAGE, e));
-                }
-                OwnerCommand::Rm { discord_id } => {
-                    let discord_id = discord_id
-                        .parse()
-                        .unwrap_or_else(|e| panic!("{}: {:?}", DISCORD_ID_PARSE_ERROR_MESSAGE, e));
-                    db.delete_owner(discord_id)
-                        .await
-                        .unwrap_or_else(|e| panic!("{}: {:?}", DB_WRITE_ERROR_MESSAGE, e));
+            match db.encrypt_secrets().await {
+                Ok(encrypted_count) => {
+                    println!("encrypted {encrypted_count} secrets");
+                    ExitCode::SUCCESS
                 }
-                OwnerCommand::Ls => {
-                    let owners = db
-                        .get_owners()
-                        .await
-                        .unwrap_or_else(|e| panic!("{}: {:?}", DB_READ_ERROR_MESSAGE, e));
-                    owners.into_iter().for_each(|id| println!("{}", id));
+                Err(e) => {
+                    eprintln!("Failed to encrypt secrets: {e}");
+                    ExitCode::FAILURE
                 }
             }
+        }
+        Some(cli_args::Command::UpdateCheck) => {
+            let db = db::JinxDb::open()
+                .await
+                .unwrap_or_else(|e| panic!("{}: {:?}", DB_OPEN_ERROR_MESSAGE, e));
+            let preferences = db
+                .get_update_preferences()
+                .await
+                .unwrap_or_else(|e| panic!("{}: {:?}", DB_READ_ERROR_MESSAGE, e));
+            println!(
+                "{}",
+                http::update_checker::check_for_update(&preferences).await
+            );
             ExitCode::SUCCESS
         }
+        Some(cli_args::Command::Owner(cli_args::OwnerArgs { command })) => {
+            let request = match command {
+                OwnerCommand::Add { discord_id } => admin::Request::AddOwner(
+                    discord_id
+                        .parse()
+                        .unwrap_or_else(|e| panic!("{}: {:?}", DISCORD_ID_PARSE_ERROR_MESSAGE, e)),
+                ),
+                OwnerCommand::Rm { discord_id } => admin::Request::RemoveOwner(
+                    discord_id
+                        .parse()
+                        .unwrap_or_else(|e| panic!("{}: {:?}", DISCORD_ID_PARSE_ERROR_MESSAGE, e)),
+                ),
+                OwnerCommand::Ls => admin::Request::ListOwners,
+            };
+            run_admin_request(request).await
+        }
+        Some(cli_args::Command::Backup { path }) => {
+            // the running bot may have a different working directory
+            let path = std::path::absolute(&path)
+                .unwrap_or_else(|e| panic!("Failed to resolve {}: {:?}", path.display(), e));
+            run_admin_request(admin::Request::Backup(path)).await
+        }
+        Some(cli_args::Command::Stats) => run_admin_request(admin::Request::Stats).await,
+        Some(cli_args::Command::IncidentMode { enabled }) => {
+            run_admin_request(admin::Request::SetIncidentMode(enabled)).await
+        }
+        Some(cli_args::Command::Events { since }) => {
+            run_admin_request(admin::Request::Events(since)).await
+        }
         None => {
             // Init logging
             tracing_subscriber::fmt()
-                .with_env_filter(
-                    EnvFilter::try_new("info,jinx=debug,serenity::gateway::shard=error").unwrap(),
-                )
+                .with_env_filter(match EnvFilter::try_new(&config::get().log_filter) {
+                    Ok(filter) => filter,
+                    Err(e) => {
+                        eprintln!("invalid log_filter in config: {e}");
+                        return ExitCode::FAILURE;
+                    }
+                })
                 .init();
 
             info!(
@@ -119,6 +153,37 @@ async fn main() -> ExitCode {
     }
 }
 
+/// Send an owner maintenance request to the running bot, or run it against the DB directly if no bot is listening
+async fn run_admin_request(request: admin::Request) -> ExitCode {
+    let response = match config::get().admin_socket.as_deref() {
+        Some(socket) => admin::call(socket, &request).await,
+        None => None,
+    };
+    let response = match response {
+        Some(response) => response,
+        None => {
+            let db = db::JinxDb::open()
+                .await
+                .unwrap_or_else(|e| panic!("{}: {:?}", DB_OPEN_ERROR_MESSAGE, e));
+            admin::execute(&db, &request)
+                .await
+                .map_err(|e| e.to_string())
+        }
+    };
+    match response {
+        Ok(message) => {
+            if !message.is_empty() {
+                println!("{message}");
+            }
+            ExitCode::SUCCESS
+        }
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
 async fn bot_subsystem(subsystem: SubsystemHandle) -> Result<(), Error> {
     tokio::select! {
         _ = subsystem.on_shutdown_requested() => {
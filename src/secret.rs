@@ -0,0 +1,181 @@
+// This file is part of jinx. Copyright © 2024 jinx contributors.
+// jinx is licensed under the GNU AGPL v3.0 or any later version. See LICENSE file for full text.
+
+//! Encryption of secrets (Jinxxy API keys and the Discord token) at rest in the DB.
+//!
+//! The key is read from the `JINX_SECRET_KEY` environment variable as 64 hex digits, which can be generated with
+//! `openssl rand -hex 32`. If it is unset, secrets are stored in plaintext. Plaintext values are always readable, so
+//! existing DBs keep working until they are migrated with the `encrypt-secrets` subcommand.
+
+use crate::error::JinxError;
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom as _, SystemRandom};
+use secrecy::zeroize::Zeroizing;
+use secrecy::SecretString;
+use std::sync::LazyLock;
+
+/// Environment variable the key is read from
+pub const SECRET_KEY_ENV_VAR: &str = "JINX_SECRET_KEY";
+
+/// Prefix marking a stored value as encrypted. The remainder is the hex-encoded nonce followed by the ciphertext.
+const ENCRYPTED_PREFIX: &str = "enc1:";
+
+static SECRET_KEY: LazyLock<Result<Option<LessSafeKey>, String>> =
+    LazyLock::new(
+        || match std::env::var(SECRET_KEY_ENV_VAR).map(Zeroizing::new) {
+            Ok(key) => parse_key(key.trim()).map(Some),
+            Err(std::env::VarError::NotPresent) => Ok(None),
+            Err(e) => Err(format!("{SECRET_KEY_ENV_VAR} could not be read: {e}")),
+        },
+    );
+
+fn parse_key(key: &str) -> Result<LessSafeKey, String> {
+    let key_bytes = hex_decode(key)
+        .map(Zeroizing::new)
+        .filter(|key_bytes| key_bytes.len() == AES_256_GCM.key_len())
+        .ok_or_else(|| format!("{SECRET_KEY_ENV_VAR} must be exactly 64 hex digits"))?;
+    let key = UnboundKey::new(&AES_256_GCM, &key_bytes)
+        .map_err(|_| format!("{SECRET_KEY_ENV_VAR} is not a valid key"))?;
+    Ok(LessSafeKey::new(key))
+}
+
+fn secret_key() -> Result<Option<&'static LessSafeKey>, JinxError> {
+    SECRET_KEY
+        .as_ref()
+        .map(Option::as_ref)
+        .map_err(|e| JinxError::new(e.clone()))
+}
+
+/// Check if a secret encryption key is configured
+pub fn is_enabled() -> Result<bool, JinxError> {
+    Ok(secret_key()?.is_some())
+}
+
+/// Check if a stored value is encrypted
+pub fn is_encrypted(stored: &str) -> bool {
+    stored.starts_with(ENCRYPTED_PREFIX)
+}
+
+/// Encrypt a secret for storage. `context` identifies where the secret is stored (for example, which guild it
+/// belongs to) and must be given again to decrypt it, so encrypted values can't be swapped between rows. If no key is
+/// configured the secret is returned as-is.
+pub fn encrypt(plaintext: &str, context: &str) -> Result<String, JinxError> {
+    match secret_key()? {
+        Some(key) => encrypt_with_key(key, plaintext, context),
+        None => Ok(plaintext.to_string()),
+    }
+}
+
+/// Decrypt a stored secret. Values that were stored in plaintext are returned as-is. The result is wrapped so it's
+/// redacted from debug output and zeroed on drop.
+pub fn decrypt(stored: &str, context: &str) -> Result<SecretString, JinxError> {
+    if is_encrypted(stored) {
+        let key = secret_key()?.ok_or_else(|| {
+            JinxError::new(format!(
+                "found an encrypted secret, but {SECRET_KEY_ENV_VAR} is not set"
+            ))
+        })?;
+        decrypt_with_key(key, stored, context)
+    } else {
+        Ok(SecretString::new(stored.to_string()))
+    }
+}
+
+fn encrypt_with_key(
+    key: &LessSafeKey,
+    plaintext: &str,
+    context: &str,
+) -> Result<String, JinxError> {
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| JinxError::new("failed to generate nonce"))?;
+    let mut in_out = Zeroizing::new(plaintext.as_bytes().to_vec());
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::from(context.as_bytes()),
+        &mut *in_out,
+    )
+    .map_err(|_| JinxError::new("failed to encrypt secret"))?;
+    Ok(format!(
+        "{ENCRYPTED_PREFIX}{}{}",
+        hex_encode(&nonce),
+        hex_encode(&in_out)
+    ))
+}
+
+fn decrypt_with_key(
+    key: &LessSafeKey,
+    stored: &str,
+    context: &str,
+) -> Result<SecretString, JinxError> {
+    let bytes = stored
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .and_then(hex_decode)
+        .filter(|bytes| bytes.len() >= NONCE_LEN)
+        .ok_or_else(|| JinxError::new("encrypted secret is malformed"))?;
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce)
+        .map_err(|_| JinxError::new("encrypted secret is malformed"))?;
+    let mut in_out = Zeroizing::new(ciphertext.to_vec());
+    let plaintext = key
+        .open_in_place(nonce, Aad::from(context.as_bytes()), &mut in_out)
+        .map_err(|_| {
+            JinxError::new(format!(
+                "failed to decrypt secret: is {SECRET_KEY_ENV_VAR} correct?"
+            ))
+        })?;
+    String::from_utf8(plaintext.to_vec())
+        .map(SecretString::new)
+        .map_err(|_| JinxError::new("decrypted secret is not valid UTF-8"))
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use secrecy::ExposeSecret as _;
+
+    const TEST_KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+    #[test]
+    fn test_round_trip() {
+        let key = parse_key(TEST_KEY).unwrap();
+        let encrypted = encrypt_with_key(&key, "sk_secret", "guild 1").unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.contains("sk_secret"));
+        assert_eq!(
+            decrypt_with_key(&key, &encrypted, "guild 1")
+                .unwrap()
+                .expose_secret(),
+            "sk_secret"
+        );
+    }
+
+    #[test]
+    fn test_wrong_context() {
+        let key = parse_key(TEST_KEY).unwrap();
+        let encrypted = encrypt_with_key(&key, "sk_secret", "guild 1").unwrap();
+        assert!(decrypt_with_key(&key, &encrypted, "guild 2").is_err());
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert!(parse_key(TEST_KEY).is_ok());
+        assert!(parse_key(&TEST_KEY[2..]).is_err());
+        assert!(parse_key("not hex").is_err());
+    }
+}